@@ -0,0 +1,68 @@
+// Games per second for the game loop with the common pairings, and the cost
+// of one uncached minimax search, so refactors of the board, the agents or
+// the loop can be checked for slowdowns. Each figure is the best of a few
+// rounds. Run with
+//
+//     cargo bench -p sutton-tic-tac-toe --bench games
+
+use std::hint::black_box;
+use std::time::Instant;
+
+use sutton_tic_tac_toe::{
+    play_game, Agent, Board, MinimaxAgent, PlayerMarker, RLAgent, RLConfig, RandomAgent,
+};
+
+const ROUNDS: u32 = 5;
+
+// The best rate over `ROUNDS` rounds of `count` calls to `run`, each round
+// starting from what `setup` makes.
+fn best_rate<S>(count: u32, setup: impl Fn(u64) -> S, run: impl Fn(&mut S)) -> f64 {
+    (0..ROUNDS)
+        .map(|round| {
+            let mut state = setup(round as u64);
+            let started = Instant::now();
+            for _ in 0..count {
+                run(&mut state);
+            }
+            count as f64 / started.elapsed().as_secs_f64()
+        })
+        .fold(0.0, f64::max)
+}
+
+// Games per second between the agents `agents` makes from a seed.
+fn games_per_second<X: Agent, O: Agent>(games: u32, agents: impl Fn(u64) -> (X, O)) -> f64 {
+    best_rate(games, agents, |(x, o)| {
+        black_box(play_game(x, o).expect("agents only make legal moves"));
+    })
+}
+
+fn main() {
+    let random = games_per_second(50_000, |seed| {
+        (
+            RandomAgent::new(Some(2 * seed)),
+            RandomAgent::new(Some(2 * seed + 1)),
+        )
+    });
+    println!("random vs random   {:>10.0} games/s", random);
+    let self_play = games_per_second(20_000, |seed| {
+        (
+            RLAgent::new(RLConfig::default(), Some(2 * seed)),
+            RLAgent::new(RLConfig::default(), Some(2 * seed + 1)),
+        )
+    });
+    println!("rl self-play       {:>10.0} games/s", self_play);
+    // The agent remembers its moves, so after the first few games this
+    // measures the loop and the lookups rather than the search.
+    let minimax = games_per_second(50_000, |seed| {
+        (MinimaxAgent::new(), RandomAgent::new(Some(seed)))
+    });
+    println!("minimax vs random  {:>10.0} games/s", minimax);
+    let search = best_rate(
+        20,
+        |_| (),
+        |_| {
+            black_box(MinimaxAgent::new().choose_move(&Board::new(), PlayerMarker::X));
+        },
+    );
+    println!("minimax opening    {:>10.1} searches/s", search);
+}
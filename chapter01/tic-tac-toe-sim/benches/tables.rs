@@ -0,0 +1,41 @@
+// Training throughput of `RLAgent` with its values in a `HashMap` and in a
+// `DenseTable`: two agents of each kind train against each other and the
+// games per second are printed, best of a few rounds. Run with
+//
+//     cargo bench -p sutton-tic-tac-toe --bench tables
+
+use std::collections::HashMap;
+use std::hint::black_box;
+use std::time::Instant;
+
+use sutton_tic_tac_toe::{play_game, Agent, DenseTable, PolicyTable, RLAgent, RLConfig};
+
+const GAMES: u32 = 20_000;
+const ROUNDS: u32 = 5;
+
+// The best games per second over `ROUNDS` rounds of `GAMES` games, each
+// round between fresh agents holding their values in `table()`.
+fn games_per_second<T: PolicyTable>(table: impl Fn() -> T) -> f64 {
+    (0..ROUNDS)
+        .map(|round| {
+            let seed = 2 * round as u64;
+            let mut x = RLAgent::with_table(table(), RLConfig::default(), Some(seed));
+            let mut o = RLAgent::with_table(table(), RLConfig::default(), Some(seed + 1));
+            let started = Instant::now();
+            for _ in 0..GAMES {
+                black_box(play_game(&mut x, &mut o).expect("agents only make legal moves"));
+            }
+            let seconds = started.elapsed().as_secs_f64();
+            black_box(x.value_summary());
+            GAMES as f64 / seconds
+        })
+        .fold(0.0, f64::max)
+}
+
+fn main() {
+    let hashed = games_per_second(HashMap::new);
+    let dense = games_per_second(DenseTable::new);
+    println!("backend   games/s");
+    println!("hash-map  {:>8.0}", hashed);
+    println!("dense     {:>8.0}  ({:.2}x)", dense, dense / hashed);
+}
@@ -0,0 +1,539 @@
+use std::collections::{BTreeMap, HashMap};
+
+use rand::rngs::SmallRng;
+use rand::{Rng, RngCore};
+use rl_core::seeded_rng;
+use serde::{Deserialize, Serialize};
+
+use crate::{Board, GameError, PlayerMarker};
+
+// Whether the game on `board` is finished, leaving no move to make.
+pub(crate) fn is_over(board: &Board) -> bool {
+    board.is_full() || board.check_winner(PlayerMarker::X) || board.check_winner(PlayerMarker::O)
+}
+
+// How a finished game went for the agent being told about it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AgentOutcome {
+    Win,
+    Loss,
+    Draw,
+}
+
+// Anything that can play a side of a game. `play_game` asks for a move on each
+// of the agent's turns and reports the outcome once the game is over.
+pub trait Agent: Send {
+    // Only called with a move left to make.
+    fn choose_move(&mut self, board: &Board, player: PlayerMarker) -> usize;
+
+    // `choose_move` for the game loops: an error instead of a panic when
+    // there is no move to make, and when the move chosen isn't an empty cell.
+    // Agents that can fail for other reasons override it.
+    fn try_choose_move(&mut self, board: &Board, player: PlayerMarker) -> Result<usize, GameError> {
+        if is_over(board) {
+            return Err(GameError::NoMoves);
+        }
+        let index = self.choose_move(board, player);
+        if index < 9 && board.available(index) {
+            Ok(index)
+        } else {
+            Err(GameError::IllegalMove { player, index })
+        }
+    }
+
+    fn observe_result(&mut self, outcome: AgentOutcome, board: &Board);
+
+    // Scans every stored Q-value; too slow for every update, so callers run it
+    // periodically in debug builds.
+    fn assert_finite_q_values(&self) {}
+
+    // Stops learning and exploring so the current greedy policy is played
+    // as-is. Agents that never learn ignore these.
+    fn freeze(&mut self) {}
+
+    fn unfreeze(&mut self) {}
+
+    fn is_learning(&self) -> bool {
+        false
+    }
+
+    // Restarts the agent's random number stream from `seed`. Agents without
+    // their own generator ignore it.
+    fn reseed(&mut self, _seed: u64) {}
+
+    // The number of entries in the agent's value table and their mean, for
+    // training metrics. `None` for agents without one.
+    fn value_summary(&self) -> Option<(usize, f32)> {
+        None
+    }
+
+    // A frozen copy of the agent's current policy, for `Trainer` to evaluate
+    // against later. `None` for agents that can't be copied.
+    fn snapshot(&self) -> Option<Box<dyn Agent>> {
+        None
+    }
+
+    // Everything the agent has learned, between games, for `Trainer`
+    // checkpoints. An agent built the same way, given these bytes by
+    // `restore_state` and the same seed by `reseed`, plays on exactly as this
+    // one would. `None` for agents that can't be checkpointed.
+    fn save_state(&self) -> Option<Vec<u8>> {
+        None
+    }
+
+    fn restore_state(&mut self, _state: &[u8]) -> Result<(), String> {
+        Err("this agent can't be restored from a checkpoint".to_string())
+    }
+
+    // The agent's estimate of every move `player` could make on `board`, for
+    // showing as hints. `None` for agents that don't value moves.
+    fn move_values(&self, _board: &Board, _player: PlayerMarker) -> Option<Vec<(usize, f32)>> {
+        None
+    }
+
+    // The probability of an exploratory move at this point in training, for
+    // progress reports. `None` for agents that don't explore by epsilon.
+    fn exploration_rate(&self) -> Option<f32> {
+        None
+    }
+}
+
+pub struct RandomAgent {
+    rng: SmallRng,
+}
+
+impl RandomAgent {
+    pub fn new(seed: Option<u64>) -> Self {
+        RandomAgent {
+            rng: seeded_rng(seed),
+        }
+    }
+}
+
+impl Agent for RandomAgent {
+    fn choose_move(&mut self, board: &Board, _player: PlayerMarker) -> usize {
+        let available: Vec<usize> = board.available_moves().collect();
+        let index = self.rng.next_u32() as usize % available.len();
+        *available.get(index).expect("Board is full")
+    }
+
+    fn observe_result(&mut self, _outcome: AgentOutcome, _board: &Board) {}
+
+    fn reseed(&mut self, seed: u64) {
+        self.rng = seeded_rng(Some(seed));
+    }
+}
+
+// A hand-written rule of thumb: win if possible, otherwise block the
+// opponent's win, otherwise take the center, then a corner, then an edge.
+// Ties within a rule are broken at random.
+pub struct HeuristicAgent {
+    rng: SmallRng,
+}
+
+impl HeuristicAgent {
+    pub fn new(seed: Option<u64>) -> Self {
+        HeuristicAgent {
+            rng: seeded_rng(seed),
+        }
+    }
+}
+
+impl Agent for HeuristicAgent {
+    fn choose_move(&mut self, board: &Board, player: PlayerMarker) -> usize {
+        let available: Vec<usize> = board.available_moves().collect();
+        let completes = |mover: PlayerMarker| -> Vec<usize> {
+            available
+                .iter()
+                .copied()
+                .filter(|&i| board.with_move(i, mover).check_winner(mover))
+                .collect()
+        };
+        let candidates = [
+            completes(player),
+            completes(player.opposite()),
+            available.iter().copied().filter(|&i| i == 4).collect(),
+            available
+                .iter()
+                .copied()
+                .filter(|i| [0, 2, 6, 8].contains(i))
+                .collect(),
+            available.clone(),
+        ];
+        let moves = candidates
+            .into_iter()
+            .find(|moves| !moves.is_empty())
+            .expect("No available moves");
+        moves[self.rng.random_range(0..moves.len())]
+    }
+
+    fn observe_result(&mut self, _outcome: AgentOutcome, _board: &Board) {}
+
+    fn reseed(&mut self, seed: u64) {
+        self.rng = seeded_rng(Some(seed));
+    }
+}
+
+// Perfect play via exhaustive alpha-beta search. The chosen move for each
+// position is remembered, so only the first game pays for the search.
+#[derive(Default)]
+pub struct MinimaxAgent {
+    moves: HashMap<Board, usize>,
+}
+
+impl MinimaxAgent {
+    pub fn new() -> MinimaxAgent {
+        MinimaxAgent::default()
+    }
+}
+
+impl Agent for MinimaxAgent {
+    fn choose_move(&mut self, board: &Board, player: PlayerMarker) -> usize {
+        *self
+            .moves
+            .entry(*board)
+            .or_insert_with(|| minimax(board, player, true).1)
+    }
+
+    fn observe_result(&mut self, _outcome: AgentOutcome, _board: &Board) {}
+}
+
+// Monte Carlo Tree Search that builds a fresh tree of `simulations` random
+// rollouts for every move, selecting children by UCB1 with exploration `c`.
+pub struct MctsAgent {
+    pub simulations: u32,
+    pub c: f32,
+    // Drives expansion order and rollouts.
+    rng: SmallRng,
+}
+
+impl MctsAgent {
+    pub fn new(simulations: u32, c: f32, seed: Option<u64>) -> Self {
+        MctsAgent {
+            simulations,
+            c,
+            rng: seeded_rng(seed),
+        }
+    }
+}
+
+impl Agent for MctsAgent {
+    fn choose_move(&mut self, board: &Board, player: PlayerMarker) -> usize {
+        mcts_move(board, player, self.simulations, self.c, &mut self.rng)
+    }
+
+    fn observe_result(&mut self, _outcome: AgentOutcome, _board: &Board) {}
+
+    fn reseed(&mut self, seed: u64) {
+        self.rng = seeded_rng(Some(seed));
+    }
+}
+
+// Returns the game-theoretic value of `board` with `player` to move, and the
+// move that achieves it. Values are from the point of view of the maximizing
+// side: a win scores 1 plus the number of empty cells left, so faster wins are
+// preferred, and a draw scores 0.
+fn minimax(board: &Board, player: PlayerMarker, maximizing: bool) -> (i32, usize) {
+    let mut table = HashMap::new();
+    alpha_beta(board, player, maximizing, i32::MIN, i32::MAX, &mut table)
+}
+
+// `table` only memoizes exact values: results that fell outside the
+// (alpha, beta) window are bounds and would be wrong in another subtree.
+fn alpha_beta(
+    board: &Board,
+    player: PlayerMarker,
+    maximizing: bool,
+    mut alpha: i32,
+    mut beta: i32,
+    table: &mut HashMap<Board, i32>,
+) -> (i32, usize) {
+    let (initial_alpha, initial_beta) = (alpha, beta);
+    let mut best_move = None;
+    let mut best_value = if maximizing { i32::MIN } else { i32::MAX };
+    for i in board.available_moves() {
+        let next = board.with_move(i, player);
+        let value = if next.check_winner(player) {
+            let score = 1 + next.available_moves().count() as i32;
+            if maximizing {
+                score
+            } else {
+                -score
+            }
+        } else if next.is_draw() {
+            0
+        } else if let Some(&value) = table.get(&next) {
+            value
+        } else {
+            alpha_beta(&next, player.opposite(), !maximizing, alpha, beta, table).0
+        };
+        if (maximizing && value > best_value) || (!maximizing && value < best_value) {
+            best_value = value;
+            best_move = Some(i);
+        }
+        if maximizing {
+            alpha = alpha.max(value);
+        } else {
+            beta = beta.min(value);
+        }
+        if alpha >= beta {
+            break;
+        }
+    }
+    if initial_alpha < best_value && best_value < initial_beta {
+        table.insert(*board, best_value);
+    }
+    (best_value, best_move.expect("No available moves"))
+}
+
+// Fixed replies for the first few plies, so a learner doesn't have to
+// rediscover the openings by exploration. Keyed on the exact board rather than
+// its canonical form, so the stored move needs no transforming.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct OpeningBook {
+    pub(crate) entries: HashMap<Board, usize>,
+}
+
+impl OpeningBook {
+    // Records the minimax move for every position reachable in fewer than
+    // `depth` plies: depth 1 covers the empty board, depth 2 adds all 9
+    // replies to X's opening. Among equally good moves the center is preferred,
+    // then the corners.
+    pub fn build_from_minimax(depth: usize) -> OpeningBook {
+        const PREFERENCE: [usize; 9] = [4, 0, 2, 6, 8, 1, 3, 5, 7];
+        let mut book = OpeningBook::default();
+        let mut frontier = vec![Board::new()];
+        for _ in 0..depth {
+            let mut next_frontier = Vec::new();
+            for board in frontier {
+                if board.check_winner(PlayerMarker::X) || board.check_winner(PlayerMarker::O) {
+                    continue;
+                }
+                let player = board.player_to_move();
+                let value = |i: usize| {
+                    let next = board.with_move(i, player);
+                    if next.check_winner(player) {
+                        i32::MAX
+                    } else if next.is_draw() {
+                        0
+                    } else {
+                        minimax(&next, player.opposite(), false).0
+                    }
+                };
+                let best = PREFERENCE
+                    .into_iter()
+                    .filter(|&i| board.available(i))
+                    .map(|i| (i, value(i)))
+                    .fold(None, |best: Option<(usize, i32)>, (i, v)| match best {
+                        Some((_, best_v)) if best_v >= v => best,
+                        _ => Some((i, v)),
+                    });
+                if let Some((action, _)) = best {
+                    book.insert(board, action);
+                }
+                for i in board.available_moves() {
+                    let next = board.with_move(i, player);
+                    next_frontier.push(next);
+                }
+            }
+            frontier = next_frontier;
+        }
+        book
+    }
+
+    pub fn lookup(&self, board: &Board) -> Option<usize> {
+        self.entries.get(board).copied()
+    }
+
+    pub fn insert(&mut self, board: Board, action: usize) {
+        self.entries.insert(board, action);
+    }
+}
+
+// A position in an MCTS search tree. `total_value` is scored for the player
+// who moved into it: 1 per win, 0.5 per draw.
+struct MctsNode {
+    board: Board,
+    to_move: PlayerMarker,
+    parent: Option<usize>,
+    // Ordered so ties between children break the same way on every run.
+    children: BTreeMap<usize, usize>,
+    untried: Vec<usize>,
+    visits: u32,
+    total_value: f32,
+}
+
+impl MctsNode {
+    pub fn new(board: Board, to_move: PlayerMarker, parent: Option<usize>) -> MctsNode {
+        let untried = if board.check_winner(to_move.opposite()) {
+            vec![]
+        } else {
+            board.available_moves().collect()
+        };
+        MctsNode {
+            board,
+            to_move,
+            parent,
+            children: BTreeMap::new(),
+            untried,
+            visits: 0,
+            total_value: 0.0,
+        }
+    }
+}
+
+// Runs `simulations` rounds of selection, expansion, rollout and
+// backpropagation from `board`, then plays the most visited move. Nodes live in
+// a flat arena and refer to each other by index.
+fn mcts_move(
+    board: &Board,
+    player: PlayerMarker,
+    simulations: u32,
+    c: f32,
+    rng: &mut impl Rng,
+) -> usize {
+    let mut nodes = vec![MctsNode::new(*board, player, None)];
+    for _ in 0..simulations {
+        let mut current = 0;
+        while nodes[current].untried.is_empty() && !nodes[current].children.is_empty() {
+            let parent_visits = nodes[current].visits as f32;
+            current = *nodes[current]
+                .children
+                .values()
+                .max_by(|&&a, &&b| {
+                    let ucb = |i: usize| {
+                        let node = &nodes[i];
+                        node.total_value / node.visits as f32
+                            + c * (parent_visits.ln() / node.visits as f32).sqrt()
+                    };
+                    ucb(a).total_cmp(&ucb(b))
+                })
+                .expect("Expanded node has children");
+        }
+
+        if !nodes[current].untried.is_empty() {
+            let pick = rng.random_range(0..nodes[current].untried.len());
+            let index = nodes[current].untried.swap_remove(pick);
+            let next = nodes[current]
+                .board
+                .with_move(index, nodes[current].to_move);
+            let child = nodes.len();
+            nodes.push(MctsNode::new(
+                next,
+                nodes[current].to_move.opposite(),
+                Some(current),
+            ));
+            nodes[current].children.insert(index, child);
+            current = child;
+        }
+
+        let winner = mcts_rollout(&nodes[current].board, nodes[current].to_move, rng);
+        let mut node = Some(current);
+        while let Some(i) = node {
+            nodes[i].visits += 1;
+            nodes[i].total_value += match winner {
+                Some(winner) if winner == nodes[i].to_move.opposite() => 1.0,
+                Some(_) => 0.0,
+                None => 0.5,
+            };
+            node = nodes[i].parent;
+        }
+    }
+
+    let (&index, _) = nodes[0]
+        .children
+        .iter()
+        .max_by_key(|(_, &child)| nodes[child].visits)
+        .expect("No available moves");
+    index
+}
+
+// Plays uniformly random moves from `board` with `to_move` next, returning the
+// winner, or `None` for a draw.
+fn mcts_rollout(
+    board: &Board,
+    mut to_move: PlayerMarker,
+    rng: &mut impl Rng,
+) -> Option<PlayerMarker> {
+    let mut board = *board;
+    loop {
+        if board.check_winner(to_move.opposite()) {
+            return Some(to_move.opposite());
+        }
+        let available: Vec<usize> = board.available_moves().collect();
+        if available.is_empty() {
+            return None;
+        }
+        board.set(available[rng.random_range(0..available.len())], to_move);
+        to_move = to_move.opposite();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{play_game, GameResult};
+
+    #[test]
+    fn heuristic_agent_wins_then_blocks_then_takes_center_and_corners() {
+        let mut agent = HeuristicAgent::new(Some(1));
+        // X can win at 2 or block O at 5; winning comes first.
+        let board: Board = "XX.OO....".parse().unwrap();
+        assert_eq!(agent.choose_move(&board, PlayerMarker::X), 2);
+        let board: Board = "X..OO...X".parse().unwrap();
+        assert_eq!(agent.choose_move(&board, PlayerMarker::X), 5);
+        assert_eq!(agent.choose_move(&Board::new(), PlayerMarker::X), 4);
+        let board: Board = "....X....".parse().unwrap();
+        for _ in 0..20 {
+            assert!([0, 2, 6, 8].contains(&agent.choose_move(&board, PlayerMarker::O)));
+        }
+        // It seldom loses to random play.
+        let mut random = RandomAgent::new(Some(2));
+        let losses = (0..200)
+            .filter(|_| play_game(&mut random, &mut agent).unwrap() == GameResult::XWin)
+            .count();
+        assert!(losses < 20, "{}", losses);
+    }
+
+    #[test]
+    fn minimax_draws_against_itself_and_never_loses_to_random_play() {
+        let (mut x, mut o) = (MinimaxAgent::new(), MinimaxAgent::new());
+        for _ in 0..3 {
+            assert_eq!(play_game(&mut x, &mut o).unwrap(), GameResult::Draw);
+        }
+        let mut random = RandomAgent::new(Some(1));
+        for _ in 0..100 {
+            assert_ne!(play_game(&mut x, &mut random).unwrap(), GameResult::OWin);
+            assert_ne!(play_game(&mut random, &mut o).unwrap(), GameResult::XWin);
+        }
+    }
+
+    #[test]
+    fn mcts_never_loses_to_random_play() {
+        let mut mcts = MctsAgent::new(1000, std::f32::consts::SQRT_2, Some(1));
+        let mut random = RandomAgent::new(Some(2));
+        for _ in 0..30 {
+            assert_ne!(play_game(&mut mcts, &mut random).unwrap(), GameResult::OWin);
+            assert_ne!(play_game(&mut random, &mut mcts).unwrap(), GameResult::XWin);
+        }
+    }
+
+    #[test]
+    fn the_minimax_opening_book_takes_the_centre_and_answers_every_opening() {
+        let book = OpeningBook::build_from_minimax(2);
+        assert_eq!(book.lookup(&Board::new()), Some(4));
+        assert_eq!(book.entries.len(), 10);
+        for opening in 0..9 {
+            let board = Board::new().with_move(opening, PlayerMarker::X);
+            let reply = book.lookup(&board).unwrap();
+            assert!(board.available(reply));
+            // Every reply holds the draw, and a corner opening is met in
+            // the centre.
+            let after = board.with_move(reply, PlayerMarker::O);
+            assert!(minimax(&after, PlayerMarker::X, true).0 <= 0, "{}", opening);
+            if [0, 2, 6, 8].contains(&opening) {
+                assert_eq!(reply, 4);
+            }
+        }
+    }
+}
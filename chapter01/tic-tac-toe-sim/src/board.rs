@@ -0,0 +1,719 @@
+use std::collections::HashSet;
+use std::fmt;
+use std::io::{self, Read, Write};
+use std::str::FromStr;
+
+use rl_core::Packed;
+use serde::{Deserialize, Serialize};
+
+pub(crate) const WINNING_COMBINATIONS: [[usize; 3]; 8] = [
+    [0, 1, 2],
+    [3, 4, 5],
+    [6, 7, 8], // Rows
+    [0, 3, 6],
+    [1, 4, 7],
+    [2, 5, 8], // Columns
+    [0, 4, 8],
+    [2, 4, 6], // Diagonals
+];
+
+// Two bits per cell, cell `i` at bits `2i..2i+2`: 0b11 for X, 0b10 for O and 0
+// for empty. Serializes as the bare `u32` so saved Q-tables keep their format,
+// and deserializes through `TryFrom<u32>` so a corrupt file is rejected rather
+// than read as boards with cells that are neither X, O nor empty.
+// The encoding stays private to this module: callers read cells through
+// `cell`/`iter_cells`, build afterstates with `with_move`, and use `to_key`
+// where they need the compact form.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(try_from = "u32", into = "u32")]
+pub struct Board {
+    spaces: u32,
+}
+
+impl Board {
+    pub fn new() -> Self {
+        Board {
+            spaces: 0b000000000000000000,
+        }
+    }
+
+    pub fn at(&self, index: u32) -> char {
+        let x_mask = PlayerMarker::player_mask(PlayerMarker::X) << (index * 2);
+        let o_mask = PlayerMarker::player_mask(PlayerMarker::O) << (index * 2);
+        if self.spaces & x_mask == x_mask {
+            'X'
+        } else if self.spaces & o_mask == o_mask {
+            'O'
+        } else {
+            ' '
+        }
+    }
+
+    // The mark in cell `index`, if any.
+    pub fn cell(&self, index: usize) -> Option<PlayerMarker> {
+        match (self.spaces >> (index * 2)) & 0b11 {
+            0b11 => Some(PlayerMarker::X),
+            0b10 => Some(PlayerMarker::O),
+            _ => None,
+        }
+    }
+
+    // Every cell's mark in row-major order.
+    pub fn iter_cells(&self) -> impl Iterator<Item = Option<PlayerMarker>> + '_ {
+        (0..9).map(|i| self.cell(i))
+    }
+
+    // The nine cells in row-major order as X, O or . for empty, the form
+    // `from_str` reads.
+    pub fn cells(&self) -> String {
+        self.iter_cells()
+            .map(|cell| cell.map_or('.', PlayerMarker::player_char))
+            .collect()
+    }
+
+    // The 18-bit encoding, for compact keys and debug output.
+    pub fn to_key(&self) -> u32 {
+        self.spaces
+    }
+
+    // The board read as a base-3 number, cell `i` the `3^i` digit: 0 for empty,
+    // 1 for X and 2 for O. Every board gets its own index below 3^9.
+    pub fn ternary_index(&self) -> usize {
+        // Each cell's two bits are 0b00, 0b10 or 0b11; this maps them to digits.
+        const DIGITS: [usize; 4] = [0, 0, 2, 1];
+        (0..9).rev().fold(0, |index, i| {
+            3 * index + DIGITS[((self.spaces >> (2 * i)) & 0b11) as usize]
+        })
+    }
+
+    // The board whose `ternary_index` is `index`.
+    pub fn from_ternary_index(mut index: usize) -> Board {
+        let mut board = Board::new();
+        for i in 0..9 {
+            match index % 3 {
+                1 => board.set(i, PlayerMarker::X),
+                2 => board.set(i, PlayerMarker::O),
+                _ => {}
+            }
+            index /= 3;
+        }
+        board
+    }
+
+    #[cfg(feature = "cli")]
+    pub fn print(&self) {
+        println!("{}", self);
+    }
+
+    pub fn check_winner(&self, player: PlayerMarker) -> bool {
+        self.check_winner_combo(player).is_some()
+    }
+
+    // The first line `player` has completed, if any.
+    pub fn check_winner_combo(&self, player: PlayerMarker) -> Option<[usize; 3]> {
+        let player_char = PlayerMarker::player_char(player);
+        WINNING_COMBINATIONS
+            .into_iter()
+            .find(|combo| combo.iter().all(|&i| self.at(i as u32) == player_char))
+    }
+
+    // The grid with the cells in `highlight` wrapped in brackets, e.g. to mark
+    // a winning line.
+    pub fn highlighted(&self, highlight: &[usize]) -> String {
+        let cell = |i: usize| {
+            if highlight.contains(&i) {
+                format!("[{}]", self.at(i as u32))
+            } else {
+                format!(" {} ", self.at(i as u32))
+            }
+        };
+        let rows: Vec<String> = (0..3)
+            .map(|row| {
+                format!(
+                    "{}|{}|{}",
+                    cell(row * 3),
+                    cell(row * 3 + 1),
+                    cell(row * 3 + 2)
+                )
+            })
+            .collect();
+        rows.join("\n-----------\n")
+    }
+
+    // The number of empty cells that would complete a line for `player`, i.e.
+    // the third cell of a winning combination where `player` holds the other two.
+    pub fn count_threats(&self, player: PlayerMarker) -> u32 {
+        let player_char = PlayerMarker::player_char(player);
+        let mut threats = HashSet::new();
+        for combo in WINNING_COMBINATIONS {
+            let owned = combo
+                .iter()
+                .filter(|&&i| self.at(i as u32) == player_char)
+                .count();
+            if let Some(&empty) = combo.iter().find(|&&i| self.available(i)) {
+                if owned == 2 {
+                    threats.insert(empty);
+                }
+            }
+        }
+        threats.len() as u32
+    }
+
+    pub fn is_draw(&self) -> bool {
+        self.is_full() && !self.check_winner(PlayerMarker::X) && !self.check_winner(PlayerMarker::O)
+    }
+
+    // Every occupied cell has its high bit set, for X (0b11) and O (0b10) alike.
+    pub fn is_full(&self) -> bool {
+        self.spaces & 0b101010101010101010 == 0b101010101010101010
+    }
+
+    // X moves first, so it is X's turn whenever both sides have the same number
+    // of marks.
+    pub fn player_to_move(&self) -> PlayerMarker {
+        let marks = |player| {
+            (0..9)
+                .filter(|&i| self.at(i) == PlayerMarker::player_char(player))
+                .count()
+        };
+        if marks(PlayerMarker::X) == marks(PlayerMarker::O) {
+            PlayerMarker::X
+        } else {
+            PlayerMarker::O
+        }
+    }
+
+    pub fn available_moves(&self) -> impl Iterator<Item = usize> + '_ {
+        (0..9).filter(|&i| self.available(i))
+    }
+
+    pub fn available(&self, index: usize) -> bool {
+        let mask = 0b11 << (index * 2);
+        (self.spaces & mask) == 0b0
+    }
+
+    pub fn occupied(&self, index: usize) -> bool {
+        !self.available(index)
+    }
+
+    // The board after `player` marks the empty cell `index`.
+    pub fn with_move(&self, index: usize, player: PlayerMarker) -> Board {
+        let mut next = *self;
+        next.set(index, player);
+        next
+    }
+
+    // Marks an empty cell. OR-ing a second mark into an occupied cell would
+    // silently turn an O into an X, so debug builds reject it.
+    pub fn set(&mut self, index: usize, value: PlayerMarker) {
+        debug_assert!(self.available(index), "cell {} is already taken", index);
+        let player_char = PlayerMarker::player_mask(value);
+        self.spaces |= player_char << (index * 2);
+    }
+
+    pub fn apply_symmetry(&self, op: SymmetryOp) -> Board {
+        let mut spaces = 0;
+        for (i, &from) in op.permutation().iter().enumerate() {
+            spaces |= ((self.spaces >> (from * 2)) & 0b11) << (i * 2);
+        }
+        Board { spaces }
+    }
+
+    // Mirrors the board left to right.
+    pub fn reflect_horizontal(&self) -> Board {
+        self.apply_symmetry(SymmetryOp::FlipH)
+    }
+
+    // Mirrors the board top to bottom.
+    pub fn reflect_vertical(&self) -> Board {
+        self.apply_symmetry(SymmetryOp::FlipV)
+    }
+
+    // Rotates the board a quarter turn clockwise.
+    pub fn rotate90(&self) -> Board {
+        self.apply_symmetry(SymmetryOp::Rotate90)
+    }
+
+    // The board under each of the 8 symmetries, in `SymmetryOp::all` order.
+    pub fn all_symmetries(&self) -> [Board; 8] {
+        SymmetryOp::all().map(|op| self.apply_symmetry(op))
+    }
+
+    // The smallest encoding among the 8 rotations and reflections of this board.
+    // Q-tables are keyed on this, unless `RLConfig::symmetry` is off, so that
+    // equivalent positions share a value.
+    pub fn canonical(&self) -> Board {
+        self.all_symmetries()
+            .into_iter()
+            .min_by_key(|board| board.spaces)
+            .expect("there are always 8 symmetries")
+    }
+}
+
+// The packed encoding of `board`'s canonical form, identical for all 8 boards
+// related by a rotation or reflection.
+pub fn canonicalize(board: &Board) -> u32 {
+    board.canonical().to_key()
+}
+
+impl fmt::Display for Board {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}|{}|{}\n-----\n{}|{}|{}\n-----\n{}|{}|{}",
+            self.at(0),
+            self.at(1),
+            self.at(2),
+            self.at(3),
+            self.at(4),
+            self.at(5),
+            self.at(6),
+            self.at(7),
+            self.at(8)
+        )
+    }
+}
+
+// Two bytes in a trajectory file: the `ternary_index`, which is below 2^16.
+impl Packed for Board {
+    fn pack(&self, out: &mut dyn Write) -> io::Result<()> {
+        out.write_all(&(self.ternary_index() as u16).to_le_bytes())
+    }
+
+    fn unpack(input: &mut dyn Read) -> io::Result<Self> {
+        let mut bytes = [0; 2];
+        input.read_exact(&mut bytes)?;
+        let index = u16::from_le_bytes(bytes) as usize;
+        if index >= 19_683 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "a board index runs past 3^9",
+            ));
+        }
+        Ok(Board::from_ternary_index(index))
+    }
+}
+
+// The board whose `to_key` is `key`. Fails for bits past the ninth cell and
+// for cells encoded as 0b01.
+impl TryFrom<u32> for Board {
+    type Error = String;
+
+    fn try_from(key: u32) -> Result<Board, String> {
+        if key >> 18 != 0 {
+            return Err(format!("{:#x} has bits set past the ninth cell", key));
+        }
+        if let Some(index) = (0..9).find(|i| (key >> (2 * i)) & 0b11 == 0b01) {
+            return Err(format!("{:#x} has no valid mark in cell {}", key, index));
+        }
+        Ok(Board { spaces: key })
+    }
+}
+
+impl From<Board> for u32 {
+    fn from(board: Board) -> u32 {
+        board.spaces
+    }
+}
+
+// Parses nine cells in row-major order, e.g. "XO.X.O...". `.`, ` ` and `_` are
+// empty cells.
+impl FromStr for Board {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Board, String> {
+        let cells: Vec<char> = s.chars().collect();
+        if cells.len() != 9 {
+            return Err(format!(
+                "Expected 9 cells but found {} in {:?}",
+                cells.len(),
+                s
+            ));
+        }
+        let mut board = Board::new();
+        for (index, &cell) in cells.iter().enumerate() {
+            match cell {
+                'X' => board.set(index, PlayerMarker::X),
+                'O' => board.set(index, PlayerMarker::O),
+                '.' | ' ' | '_' => {}
+                other => {
+                    return Err(format!(
+                        "Invalid cell {:?} at index {} in {:?}",
+                        other, index, s
+                    ))
+                }
+            }
+        }
+        Ok(board)
+    }
+}
+
+// The 8 elements of the dihedral group of the square.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum SymmetryOp {
+    Identity,
+    Rotate90,
+    Rotate180,
+    Rotate270,
+    FlipH,
+    FlipV,
+    FlipD1,
+    FlipD2,
+}
+
+impl SymmetryOp {
+    pub fn all() -> [SymmetryOp; 8] {
+        [
+            SymmetryOp::Identity,
+            SymmetryOp::Rotate90,
+            SymmetryOp::Rotate180,
+            SymmetryOp::Rotate270,
+            SymmetryOp::FlipH,
+            SymmetryOp::FlipV,
+            SymmetryOp::FlipD1,
+            SymmetryOp::FlipD2,
+        ]
+    }
+
+    // Cell `i` of the transformed board takes the contents of cell `permutation()[i]`.
+    pub fn permutation(self) -> [usize; 9] {
+        match self {
+            SymmetryOp::Identity => [0, 1, 2, 3, 4, 5, 6, 7, 8],
+            // Clockwise: the cell at (r, c) moves to (c, 2 - r).
+            SymmetryOp::Rotate90 => [6, 3, 0, 7, 4, 1, 8, 5, 2],
+            SymmetryOp::Rotate180 => [8, 7, 6, 5, 4, 3, 2, 1, 0],
+            SymmetryOp::Rotate270 => [2, 5, 8, 1, 4, 7, 0, 3, 6],
+            // Swaps the left and right columns.
+            SymmetryOp::FlipH => [2, 1, 0, 5, 4, 3, 8, 7, 6],
+            // Swaps the top and bottom rows.
+            SymmetryOp::FlipV => [6, 7, 8, 3, 4, 5, 0, 1, 2],
+            // Reflects across the main diagonal (0, 4, 8).
+            SymmetryOp::FlipD1 => [0, 3, 6, 1, 4, 7, 2, 5, 8],
+            // Reflects across the anti-diagonal (2, 4, 6).
+            SymmetryOp::FlipD2 => [8, 5, 2, 7, 4, 1, 6, 3, 0],
+        }
+    }
+
+    // Where the cell at `action` ends up after applying this symmetry.
+    pub fn transform_action(self, action: usize) -> usize {
+        self.permutation()
+            .iter()
+            .position(|&from| from == action)
+            .expect("permutation covers every cell")
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub enum PlayerMarker {
+    X,
+    O,
+}
+
+impl PlayerMarker {
+    pub fn opposite(self) -> PlayerMarker {
+        match self {
+            PlayerMarker::X => PlayerMarker::O,
+            PlayerMarker::O => PlayerMarker::X,
+        }
+    }
+
+    pub fn player_char(player: PlayerMarker) -> char {
+        match player {
+            PlayerMarker::X => 'X',
+            PlayerMarker::O => 'O',
+        }
+    }
+
+    fn player_mask(player: PlayerMarker) -> u32 {
+        match player {
+            PlayerMarker::X => 0b11,
+            PlayerMarker::O => 0b10,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn board(cells: &str) -> Board {
+        cells.parse().expect("valid board")
+    }
+
+    #[test]
+    fn new_board_is_empty() {
+        let board = Board::new();
+        assert_eq!(board.spaces, 0);
+        assert_eq!(
+            board.available_moves().collect::<Vec<_>>(),
+            (0..9).collect::<Vec<_>>()
+        );
+        assert!(!board.is_full());
+        assert!(!board.is_draw());
+        assert!(!board.check_winner(PlayerMarker::X));
+        assert!(!board.check_winner(PlayerMarker::O));
+    }
+
+    #[test]
+    fn set_encodes_two_bits_per_cell() {
+        for index in 0..9 {
+            let mut x = Board::new();
+            x.set(index, PlayerMarker::X);
+            assert_eq!(x.spaces, 0b11 << (index * 2));
+            assert_eq!(x.at(index as u32), 'X');
+
+            let mut o = Board::new();
+            o.set(index, PlayerMarker::O);
+            assert_eq!(o.spaces, 0b10 << (index * 2));
+            assert_eq!(o.at(index as u32), 'O');
+        }
+    }
+
+    #[test]
+    fn set_leaves_other_cells_alone() {
+        let mut board = Board::new();
+        board.set(4, PlayerMarker::X);
+        board.set(0, PlayerMarker::O);
+        for index in 0..9 {
+            let expected = match index {
+                4 => 'X',
+                0 => 'O',
+                _ => ' ',
+            };
+            assert_eq!(board.at(index), expected);
+        }
+        assert_eq!(
+            board.available_moves().collect::<Vec<_>>(),
+            vec![1, 2, 3, 5, 6, 7, 8]
+        );
+    }
+
+    #[test]
+    fn with_move_returns_a_new_board() {
+        let board = board("X........");
+        let next = board.with_move(4, PlayerMarker::O);
+        assert_eq!(board.cell(4), None);
+        assert_eq!(next.cell(4), Some(PlayerMarker::O));
+        assert_eq!(next.cell(0), Some(PlayerMarker::X));
+        assert_eq!(next.to_key(), board.to_key() | 0b10 << 8);
+    }
+
+    #[test]
+    fn iter_cells_reads_every_mark() {
+        let cells: Vec<_> = board("XO......X").iter_cells().collect();
+        assert_eq!(cells.len(), 9);
+        assert_eq!(cells[0], Some(PlayerMarker::X));
+        assert_eq!(cells[1], Some(PlayerMarker::O));
+        assert!(cells[2..8].iter().all(Option::is_none));
+        assert_eq!(cells[8], Some(PlayerMarker::X));
+        assert_eq!(board("XO......X").cells(), "XO......X");
+    }
+
+    #[test]
+    fn occupied_cells_are_unavailable() {
+        let board = board("XO.......");
+        assert!(!board.available(0));
+        assert!(!board.available(1));
+        assert!(board.occupied(0) && board.occupied(1));
+        assert!((2..9).all(|i| board.available(i) && !board.occupied(i)));
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "already taken")]
+    fn set_rejects_an_occupied_cell() {
+        let mut board = board("O........");
+        board.set(0, PlayerMarker::X);
+    }
+
+    #[test]
+    fn every_line_wins_for_either_player() {
+        for combo in WINNING_COMBINATIONS {
+            for player in [PlayerMarker::X, PlayerMarker::O] {
+                let mut board = Board::new();
+                for &i in &combo {
+                    board.set(i, player);
+                }
+                assert!(board.check_winner(player), "{:?} on {:?}", player, combo);
+                assert!(!board.check_winner(player.opposite()));
+                assert_eq!(board.check_winner_combo(player), Some(combo));
+            }
+        }
+    }
+
+    #[test]
+    fn two_in_a_line_is_not_a_win() {
+        for combo in WINNING_COMBINATIONS {
+            let mut board = Board::new();
+            board.set(combo[0], PlayerMarker::X);
+            board.set(combo[1], PlayerMarker::X);
+            board.set(combo[2], PlayerMarker::O);
+            assert!(!board.check_winner(PlayerMarker::X), "{:?}", combo);
+            assert!(!board.check_winner(PlayerMarker::O), "{:?}", combo);
+        }
+    }
+
+    #[test]
+    fn full_board_without_a_line_is_a_draw() {
+        let board = board("XOXXOOOXX");
+        assert!(board.is_full());
+        assert!(board.is_draw());
+        assert_eq!(board.available_moves().count(), 0);
+    }
+
+    #[test]
+    fn full_board_with_a_line_is_not_a_draw() {
+        let board = board("XXXOOXXOO");
+        assert!(board.is_full());
+        assert!(board.check_winner(PlayerMarker::X));
+        assert!(!board.is_draw());
+    }
+
+    #[test]
+    fn board_with_an_empty_cell_is_not_full() {
+        // Filling any eight cells must not satisfy the full-board bitmask.
+        let full = board("XOXXOOOXX");
+        for index in 0..9 {
+            let mut board = Board::new();
+            for i in (0..9).filter(|&i| i != index) {
+                board.set(
+                    i,
+                    if full.at(i as u32) == 'X' {
+                        PlayerMarker::X
+                    } else {
+                        PlayerMarker::O
+                    },
+                );
+            }
+            assert!(!board.is_full(), "only cell {} empty", index);
+            assert!(!board.is_draw());
+        }
+    }
+
+    #[test]
+    fn player_to_move_alternates_from_x() {
+        assert_eq!(board(".........").player_to_move(), PlayerMarker::X);
+        assert_eq!(board("X........").player_to_move(), PlayerMarker::O);
+        assert_eq!(board("X...O....").player_to_move(), PlayerMarker::X);
+    }
+
+    #[test]
+    fn parse_and_display_round_trip() {
+        let board = board("XO.X.O..X");
+        assert_eq!(board.to_string(), "X|O| \n-----\nX| |O\n-----\n | |X");
+        let cells: String = (0..9)
+            .map(|i| match board.at(i) {
+                ' ' => '.',
+                mark => mark,
+            })
+            .collect();
+        assert_eq!(cells.parse::<Board>(), Ok(board));
+    }
+
+    #[test]
+    fn parse_rejects_bad_input() {
+        assert!("XO".parse::<Board>().is_err());
+        assert!("XO.X.O..Z".parse::<Board>().is_err());
+    }
+
+    #[test]
+    fn serializes_as_the_bare_bit_encoding() {
+        let board = board("XO.......");
+        let json = serde_json::to_string(&board).unwrap();
+        assert_eq!(json, board.spaces.to_string());
+        assert_eq!(serde_json::from_str::<Board>(&json).unwrap(), board);
+    }
+
+    #[test]
+    fn symmetries_preserve_winners_and_share_a_canonical_form() {
+        let board = board("XX.O.O...");
+        for symmetric in board.all_symmetries() {
+            assert_eq!(symmetric.canonical(), board.canonical());
+            assert_eq!(symmetric.available_moves().count(), 5);
+        }
+        let won = self::board("XXXOO....");
+        for symmetric in won.all_symmetries() {
+            assert!(symmetric.check_winner(PlayerMarker::X));
+        }
+    }
+
+    #[test]
+    fn transform_action_follows_the_board() {
+        let mut board = Board::new();
+        board.set(1, PlayerMarker::X);
+        for op in SymmetryOp::all() {
+            let moved = op.transform_action(1);
+            assert_eq!(board.apply_symmetry(op).at(moved as u32), 'X', "{:?}", op);
+        }
+    }
+
+    #[test]
+    fn only_valid_encodings_deserialize() {
+        let board = self::board("X...O...X");
+        let json = serde_json::to_string(&board).unwrap();
+        assert_eq!(json, board.to_key().to_string());
+        assert_eq!(serde_json::from_str::<Board>(&json).unwrap(), board);
+        assert!(serde_json::from_str::<Board>("1").is_err());
+        assert!(serde_json::from_str::<Board>(&(1u32 << 18).to_string()).is_err());
+        // Saved Q-tables key on boards, so a bad key rejects the whole file.
+        let table: HashMap<Board, f32> = serde_json::from_str(r#"{"3": 0.5}"#).unwrap();
+        assert_eq!(table[&self::board("X........")], 0.5);
+        assert!(serde_json::from_str::<HashMap<Board, f32>>(r#"{"4": 0.5}"#).is_err());
+    }
+
+    #[test]
+    fn four_quarter_turns_and_two_reflections_are_the_identity() {
+        let board = self::board("XO..X.O.X");
+        let turned = board.rotate90();
+        assert_ne!(turned, board);
+        assert_eq!(turned.rotate90().rotate90().rotate90(), board);
+        assert_eq!(board.reflect_horizontal().reflect_horizontal(), board);
+        assert_eq!(board.reflect_vertical().reflect_vertical(), board);
+        assert_eq!(board.rotate90().cells(), "O.X.XOX..");
+        assert_eq!(board.reflect_horizontal().cells(), ".OX.X.X.O");
+        assert_eq!(board.reflect_vertical().cells(), "O.X.X.XO.");
+    }
+
+    #[test]
+    fn a_board_has_at_most_eight_distinct_symmetries() {
+        let distinct = |cells: &str| {
+            let board = self::board(cells);
+            SymmetryOp::all()
+                .iter()
+                .map(|&op| board.apply_symmetry(op))
+                .collect::<HashSet<_>>()
+                .len()
+        };
+        assert_eq!(distinct("XO......."), 8);
+        assert_eq!(distinct("X........"), 4);
+        assert_eq!(distinct("....X...."), 1);
+        assert_eq!(distinct("........."), 1);
+    }
+
+    #[test]
+    fn quarter_turns_and_a_reflection_generate_every_symmetry() {
+        let board = self::board("XO.......");
+        let mut turned = board;
+        let mut generated = HashSet::new();
+        for _ in 0..4 {
+            generated.insert(turned);
+            generated.insert(turned.reflect_horizontal());
+            turned = turned.rotate90();
+        }
+        assert_eq!(turned, board);
+        let all: HashSet<Board> = board.all_symmetries().into_iter().collect();
+        assert_eq!(generated, all);
+        assert!(all
+            .iter()
+            .all(|symmetric| symmetric.canonical() == board.canonical()));
+    }
+
+    #[test]
+    fn available_moves_lists_the_empty_cells_in_order() {
+        assert!(Board::new().available_moves().eq(0..9));
+        assert_eq!(self::board("XOXXOOOXX").available_moves().count(), 0);
+        let moves: Vec<usize> = self::board("X.O.X...O").available_moves().collect();
+        assert_eq!(moves, [1, 3, 5, 6, 7]);
+    }
+}
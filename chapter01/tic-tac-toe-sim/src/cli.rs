@@ -0,0 +1,1756 @@
+use std::ops::Range;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use rl_core::parse_values;
+use sutton_tic_tac_toe::{
+    Board, EvalOpponent, MnkGame, PlayerMarker, RLConfig, Roles, Rules, TrainingConfig,
+};
+
+pub const USAGE: &str = "Usage: sutton-tic-tac-toe <command> [options]
+       sutton-tic-tac-toe --eval-only <policy> [--side <x|o>] [--games <n>]
+                          [--seed <n>]
+
+Score the rl policy saved at <policy> with --eval-only: it plays --games
+games (default: 10000) as --side against each of random, heuristic and
+minimax play, greedily and without learning, and the exit status is 0 only
+if it wins more than 80% of those against random play, for scripts to gate
+on. It is 1 if the policy fails or can't be loaded, and 2 for a usage error.
+The policy file is only read.
+
+Commands:
+  train        Train RL agents by self-play and save their policies
+  play         Play games and print each one. When one side is human, type a
+               cell number, or hint, undo, restart or quit; when both are,
+               they take turns at the keyboard
+  eval         Play games silently and print a summary of the results
+  selfplay     Train two RL agents against each other, evaluating them at
+               checkpoints
+  tournament   Play every pair of --agents against each other and print a
+               cross-table marking significant wins, and Elo ratings
+  serve        Wait for agents to connect over TCP at --listen and play each
+               one in a tournament against --agents, printing the
+               cross-table when it ends. The server writes lines of JSON:
+               move requests with the board (X, O or . for each cell), the
+               player and the legal cells, numbered 0 to 8, each answered
+               with a line like {\"move\": 4}, and each game's result
+  show-values  Print the rl agent's value for every move in --position
+  policy stats Print the size, value histogram and coverage of the --side rl
+               agent's value table
+  policy prune Drop the entries of the --side rl agent's table visited fewer
+               than --min-visits times in training, and save the rest
+  policy export
+               Print the --side rl agent's greedy move and its value in every
+               position up to rotation and reflection where that side is to
+               move, grouped by ply, to read through or diff between runs
+  replay <log> Step through the games of a --log file, a move at a time when
+               reading from a terminal. With --show-values, show the rl
+               agents' value for every move at each turn and flag blunders.
+               With --what-if, show each game beside the one it would have
+               been, the rl agents playing on greedily
+  report visitation <trajectories>
+               Count the visits the games of a selfplay --trajectories file
+               make to each position up to rotation and reflection, and
+               print for each ply how many were seen, the --top most and
+               least visited, and those never seen
+  analyze      Write the game tree the --side rl agent's greedy play allows,
+               against every opponent move, with its values and how often
+               games against random play visit each position
+  experiments  Run the agent comparisons
+  sweep        Train an rl agent against random play for every combination
+               of --alphas, --epsilons, --shaping and --initial-values,
+               --seeds times each in parallel, and rank them by the share
+               of --eval-games they win after training on the --roles
+               sides, as CSV
+  exploration-learning
+               Train rl agents against random play at each of --epsilons,
+               --seeds times each, with and without backups after
+               exploratory moves (Exercise 1.3), and rank them by the share
+               of --eval-games their greedy policies win, as CSV
+  ab-test      Train rl agents against random play with the hyperparameters
+               given (A) and with those of --b-config (B), from each of
+               --seeds seeds, and compare the share of --eval-games their
+               greedy policies win with a paired t-test
+  mnk          Train RL agents by self-play on a larger m,n,k-game (--game)
+               and evaluate them against random play
+
+Options:
+  --games <n>      Number of games (train: 100000, play: 1, eval: 1000,
+                   selfplay: 50000, tournament, serve: 100 per pairing,
+                   experiments: 100000, mnk: 50000; analyze: 1000 games
+                   against random play for the visit counts; replay: the
+                   last <n> games, default all; sweep, exploration-learning:
+                   5000 training games per trial; ab-test: 10000 per seed)
+  --x <agent>      Agent playing X (play: human, eval: random)
+  --o <agent>      Agent playing O (play: rl, eval: rl)
+  --agents <list>  tournament: comma-separated entrants (default:
+                   random,heuristic,minimax,rl); rl:<path> is an rl agent
+                   loaded from <path>; serve: the remote agent's opponents
+                   (default: rl)
+  --listen <addr>  serve: the address to listen on (default: 127.0.0.1:7878)
+  --show-values    Print the RL agent's move values on every turn
+  --blunder <x>    replay: flag moves the rl agent values at least <x> below
+                   its greedy move (default: 0.25)
+  --what-if <move>:<cell>
+                   replay: play <cell> at move <move> instead, both counted
+                   from 1
+  --top <n>        report visitation: positions listed per ply as most and
+                   least visited, and as never seen (default: 5)
+  --log <path>     play: append each finished game to <path>, one a line:
+                   the time it ended, its moves (X5 O1 X9 ...) and the result
+  --position <cells>
+                   show-values: the board, nine cells in row-major order with
+                   . for empty, e.g. X.O......
+  --no-color       show-values, replay: don't shade the values
+  --game <m,n,k>   mnk: an m by n board won by k in a row (default: 4,4,3;
+                   3,3,3 is tic-tac-toe and 15,15,5 Gomoku)
+  --freeze         eval: stop RL agents learning and exploring, so the games
+                   measure their greedy policies
+  --progress       eval: show a progress bar on stderr
+  --alternate      selfplay: swap sides every game
+  --eval-interval <n>
+                   selfplay: games between checkpoints (default: 5000; 0
+                   disables them)
+  --eval-games <n> selfplay: games per agent at each checkpoint (default: 100);
+                   sweep, exploration-learning, ab-test: games that score
+                   each trial (default: 1000)
+  --eval-opponent <minimax|random|snapshot>
+                   selfplay: who the agents face at checkpoints; snapshot is
+                   the other agent as of the previous checkpoint
+                   (default: minimax)
+  --metrics-out <path>
+                   selfplay: write the first agent's learning curve against
+                   random play to <path> as CSV
+  --metrics-interval <n>
+                   selfplay: games between learning-curve points
+                   (default: 1000)
+  --ratings-out <path>
+                   selfplay: every --rating-interval games, freeze the agents
+                   as a snapshot that plays --eval-games games against each
+                   earlier snapshot and random, heuristic and minimax play,
+                   and write every snapshot's rating after each round to
+                   <path> as CSV; random play is held at 1000
+  --rating-interval <n>
+                   selfplay: games between snapshots (default: 5000)
+  --trajectories <path>
+                   selfplay: record every training game to <path>, its
+                   boards, moves and rewards from X's view, in rl-core's
+                   trajectory format
+  --plot <path>    selfplay: draw the learning curve and checkpoints as an
+                   SVG image at <path>
+  --jsonl <path>   selfplay: write every checkpoint and learning-curve point
+                   to <path> as lines of JSON with the settings and seed
+  --opponent-refresh <n>
+                   selfplay: train each agent against a frozen copy of the
+                   other, retaken every <n> games, instead of both learning
+                   from each other at once (default: 0, at once)
+  --save-state <dir>
+                   selfplay: save everything training has reached to <dir>
+                   every --save-interval games, replacing the last save, for
+                   --resume to carry on from
+  --save-interval <n>
+                   selfplay: games between saves (default: 5000)
+  --resume <dir>   selfplay: carry on from the training state saved in <dir>
+                   exactly as the run that saved it would have, and keep
+                   saving there; give the options and --seed that run had
+  --save-policy <path>
+                   train: save only the --side agent's policy, to <path>;
+                   policy prune: save the pruned policy to <path> instead of
+                   over the one loaded
+  --side <x|o>     Side whose policy --save-policy saves, or policy, analyze
+                   and replay examine, or --eval-only plays (default: o;
+                   replay: both sides, or o with --load-policy)
+  --load-policy <path>
+                   play, eval, show-values, policy, analyze, replay: load the
+                   rl agent's policy from <path> instead of x_policy.bin or
+                   o_policy.bin
+  --min-visits <n> policy prune: the fewest visits an entry keeps (default: 2)
+  --alphas <list>  sweep: step sizes, comma-separated or <start>:<end>:<step>
+                   ranges (default: 0.05,0.1,0.2,0.4)
+  --epsilons <list>
+                   sweep, exploration-learning: constant exploration rates,
+                   as for --alphas (default: 0.01,0.05,0.1,0.2;
+                   exploration-learning: 0.05,0.1,0.2,0.4)
+  --shaping <list> sweep: weights of a shaping potential counting the agent's
+                   open two-in-a-rows less its opponent's, 0 for none, as for
+                   --alphas (default: 0,0.1)
+  --initial-values <list>
+                   sweep: values of positions before their first backup, as
+                   for --alphas (default: 0)
+  --seeds <n>      sweep, exploration-learning: trials of each combination,
+                   with their own seeds (default: 5); ab-test: seeds each
+                   configuration trains from (default: 10); selfplay: a
+                   range of seeds such as 0..30, end excluded, to train with
+                   each in parallel and average the checkpoints and learning
+                   curve over, with standard errors
+  --roles <x|o|shared|separate>
+                   sweep, exploration-learning: the sides the agent trains
+                   on: x, o, or both in alternate games with one value table
+                   (shared) or a table for each side (separate); it is
+                   scored on those sides, averaging both (default: o)
+  --misere         sweep, exploration-learning: play misère tic-tac-toe,
+                   where whoever completes three in a row loses
+  --fault-rate <p> sweep, exploration-learning: land each move, by either
+                   side, on a random empty cell instead with probability <p>
+                   (default: 0)
+  --format <dot|json|text>
+                   analyze: GraphViz DOT or JSON (default: dot); policy
+                   export: text, the only format it writes
+  --out <path>     analyze: write the tree to <path> instead of stdout;
+                   policy export: write the moves to <path>;
+                   report visitation: also write every position's visits to
+                   <path> as CSV;
+                   sweep, exploration-learning: write the ranking to <path>
+                   and print the best
+  --threads <n>    Threads to train the rl agents on whenever they are trained,
+                   or to run sweep's trials on (default: one per core;
+                   selfplay: only with --seeds, to run the seeds on)
+  --quiet          Hide the progress bar shown on stderr whenever rl agents
+                   are trained, with games per second, time left and the
+                   exploration rate; it is never drawn when stderr is not a
+                   terminal
+  --dashboard      selfplay: show a live view on stderr in place of the
+                   progress bar, with the first agent's win, draw and loss
+                   rates over its latest 1000 games, its exploration rate and
+                   table size, and a sparkline of how often the agents avoided
+                   losing at each checkpoint (builds with the tui feature)
+
+Hyperparameters for every learning agent that is trained:
+  --alpha <x>        Step size (default: 0.1)
+  --epsilon <schedule>
+                     Exploration rate: a constant (default: 0.01), or
+                     linear:<start>:<end>:<steps>, exponential:<start>:<decay>,
+                     step:<start>:<factor>:<interval>, a cosine from <start>
+                     to <end>, cosine:<start>:<end>:<steps>, or the same
+                     restarting every <period>, restart:<start>:<end>:<period>
+  --exploration <strategy>
+                     How rl agents explore: epsilon-greedy (default), at the
+                     --epsilon rate; ucb:<c>, the move maximizing value +
+                     c * sqrt(ln N(s) / N(s')) over the visit counts of the
+                     position and each afterstate, untried moves first;
+                     softmax:<temperature>; or count:<beta>, value +
+                     beta / sqrt(N(s') + 1)
+  --win-reward <x>   Reward for a win (default: 1)
+  --draw-reward <x>  Reward for a draw (default: -0.5)
+  --loss-reward <x>  Reward for a loss (default: -1)
+  --step-reward <x>  rl agents: reward for each move they make, a small
+                     negative one to hurry games along (default: 0)
+  --win-speed-bonus <x>
+                     rl agents: scale the win reward by 1 + <x> times the
+                     cells left empty, favouring quick wins (default: 0)
+  --initial-value <x>
+                     Value of a position or move before its first backup:
+                     0.5 as in Section 1.5, or above the win reward for
+                     optimistic exploration (default: 0)
+  --no-symmetry      Learn every rotation and reflection of a position
+                     separately instead of sharing one value
+  --learn-from-exploration
+                     rl agents: back values up after exploratory moves too,
+                     toward the value of the move made, rather than only
+                     after greedy moves
+
+  --seed <n>       Seed every random number generator, so runs can be
+                   repeated exactly (default: seeded from the OS)
+  --b-config <path>
+                   ab-test: an experiment file of the hyperparameters of
+                   configuration B, from their defaults as A's are
+  --config <path>  Read options from an experiment file of `option = value`
+                   lines, a subset of TOML; options given here override it,
+                   and its `command` is used when none is given here
+
+  -h, --help       Show this message
+
+Agents: random, human, heuristic, minimax, mcts, rl, q-learning, sarsa,
+monte-carlo, double-q, reinforce, dqn";
+
+// Which agent to put on a side. `Rl` is the trained RLAgent, loaded from disk
+// or trained on first use; the other learners start from scratch. `QLearning`
+// and `Sarsa` learn (state, action) values rather than afterstate values.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AgentKind {
+    Random,
+    Human,
+    Heuristic,
+    Minimax,
+    Mcts,
+    Rl,
+    QLearning,
+    Sarsa,
+    MonteCarlo,
+    DoubleQ,
+    Reinforce,
+    Dqn,
+}
+
+impl FromStr for AgentKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "random" => Ok(AgentKind::Random),
+            "human" => Ok(AgentKind::Human),
+            "heuristic" => Ok(AgentKind::Heuristic),
+            "minimax" => Ok(AgentKind::Minimax),
+            "mcts" => Ok(AgentKind::Mcts),
+            "rl" => Ok(AgentKind::Rl),
+            "q-learning" => Ok(AgentKind::QLearning),
+            "sarsa" => Ok(AgentKind::Sarsa),
+            "monte-carlo" => Ok(AgentKind::MonteCarlo),
+            "double-q" => Ok(AgentKind::DoubleQ),
+            "reinforce" => Ok(AgentKind::Reinforce),
+            "dqn" => Ok(AgentKind::Dqn),
+            _ => Err(format!("unknown agent '{}'", s)),
+        }
+    }
+}
+
+// A tournament entrant: an agent kind, and for `rl`, the policy to load in
+// place of the default X and O policies. Written `kind` or `rl:<path>`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AgentSpec {
+    pub name: String,
+    pub kind: AgentKind,
+    pub policy: Option<PathBuf>,
+}
+
+impl FromStr for AgentSpec {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (kind, policy) = match s.split_once(':') {
+            Some(("rl", path)) if !path.is_empty() => (AgentKind::Rl, Some(PathBuf::from(path))),
+            Some(_) => return Err(format!("only rl takes a policy path, in '{}'", s)),
+            None => (s.parse()?, None),
+        };
+        if kind == AgentKind::Human {
+            return Err("human can't enter a tournament".to_string());
+        }
+        Ok(AgentSpec {
+            name: s.to_string(),
+            kind,
+            policy,
+        })
+    }
+}
+
+// What `policy` does with a value table.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PolicyAction {
+    Stats,
+    Prune { min_visits: u32 },
+    Export,
+}
+
+// How `analyze` writes its tree, or `policy export` its moves.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TreeFormat {
+    Dot,
+    Json,
+    Text,
+}
+
+// How `selfplay` shows its training as it runs on stderr.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TrainingDisplay {
+    Progress,
+    Dashboard,
+    Hidden,
+}
+
+// Where selfplay saves its training state every `interval` games, and
+// whether it first carries on from the state already there.
+#[derive(Debug)]
+pub struct SavedState {
+    pub dir: PathBuf,
+    pub interval: u32,
+    pub resume: bool,
+}
+
+#[derive(Debug)]
+pub enum Command {
+    Train {
+        games: u32,
+        // Where to save the `side` agent; both agents go to their default
+        // files when this is `None`.
+        save_policy: Option<(PlayerMarker, PathBuf)>,
+    },
+    Play {
+        x: AgentKind,
+        o: AgentKind,
+        games: u32,
+        load_policy: Option<PathBuf>,
+        log: Option<PathBuf>,
+    },
+    Eval {
+        x: AgentKind,
+        o: AgentKind,
+        games: u32,
+        load_policy: Option<PathBuf>,
+        freeze: bool,
+        progress: bool,
+    },
+    SelfPlay {
+        games: u32,
+        training: TrainingConfig,
+        metrics_out: Option<PathBuf>,
+        ratings_out: Option<PathBuf>,
+        trajectories: Option<PathBuf>,
+        jsonl: Option<PathBuf>,
+        plot: Option<PathBuf>,
+        display: TrainingDisplay,
+        saved_state: Option<SavedState>,
+        // Seeds to train once with each and average over, in place of one run.
+        seeds: Option<Range<u64>>,
+    },
+    Tournament {
+        agents: Vec<AgentSpec>,
+        games: u32,
+    },
+    Serve {
+        listen: String,
+        agents: Vec<AgentSpec>,
+        games: u32,
+    },
+    ShowValues {
+        position: Board,
+        load_policy: Option<PathBuf>,
+        color: bool,
+    },
+    Policy {
+        action: PolicyAction,
+        side: PlayerMarker,
+        load_policy: Option<PathBuf>,
+        save_policy: Option<PathBuf>,
+        out: Option<PathBuf>,
+    },
+    // Scores a saved policy against the standard opponents without training.
+    EvalOnly {
+        policy: PathBuf,
+        side: PlayerMarker,
+        games: u32,
+    },
+    Replay {
+        log: PathBuf,
+        // The last `games` games; all of them when `None`.
+        games: Option<usize>,
+        // With `show_values`, the sides whose moves are reviewed (both when
+        // `None`), the policy to review them against, and the shortfall
+        // that counts as a blunder.
+        side: Option<PlayerMarker>,
+        load_policy: Option<PathBuf>,
+        blunder: f32,
+        color: bool,
+        // The move and the cell, counted from 0, to play instead and show
+        // each game beside.
+        what_if: Option<(usize, usize)>,
+    },
+    ReportVisitation {
+        trajectories: PathBuf,
+        // Positions listed per ply as most and least visited, and as never
+        // seen.
+        top: usize,
+        out: Option<PathBuf>,
+    },
+    Analyze {
+        side: PlayerMarker,
+        load_policy: Option<PathBuf>,
+        format: TreeFormat,
+        out: Option<PathBuf>,
+        games: u32,
+    },
+    Experiments {
+        games: u32,
+    },
+    // `games` trains each trial and `eval_games` scores it.
+    Sweep {
+        alphas: Vec<f64>,
+        epsilons: Vec<f64>,
+        shaping: Vec<f64>,
+        initial_values: Vec<f64>,
+        seeds: usize,
+        roles: Roles,
+        rules: Rules,
+        games: u32,
+        eval_games: u32,
+        out: Option<PathBuf>,
+    },
+    // As `Sweep`, with and without backups after exploratory moves.
+    ExplorationLearning {
+        epsilons: Vec<f64>,
+        seeds: usize,
+        roles: Roles,
+        rules: Rules,
+        games: u32,
+        eval_games: u32,
+        out: Option<PathBuf>,
+    },
+    // Configuration A is `Cli::config`.
+    AbTest {
+        config_b: RLConfig,
+        seeds: u32,
+        games: u32,
+        eval_games: u32,
+    },
+    Mnk {
+        game: MnkGame,
+        games: u32,
+    },
+    Help,
+}
+
+#[derive(Debug)]
+pub struct Cli {
+    pub command: Command,
+    pub show_values: bool,
+    pub config: RLConfig,
+    pub seed: Option<u64>,
+    // `None` trains on one thread per core.
+    pub threads: Option<usize>,
+    // Hides the training progress bar.
+    pub quiet: bool,
+}
+
+fn parse_f32(flag: &str, value: &str) -> Result<f32, String> {
+    value
+        .parse()
+        .map_err(|_| format!("invalid value '{}' for {}", value, flag))
+}
+
+// --plot's path. Charts are only drawn as SVG, and only by builds with the
+// plot feature.
+fn parse_plot_path(value: &str) -> Result<PathBuf, String> {
+    if cfg!(not(feature = "plot")) {
+        return Err("--plot needs a build with the plot feature".to_string());
+    }
+    if !value.ends_with(".svg") {
+        return Err(format!("--plot draws SVG, so '{}' must end in .svg", value));
+    }
+    Ok(PathBuf::from(value))
+}
+
+fn parse_u32(flag: &str, value: &str) -> Result<u32, String> {
+    value
+        .parse()
+        .map_err(|_| format!("invalid value '{}' for {}", value, flag))
+}
+
+fn parse_u64(flag: &str, value: &str) -> Result<u64, String> {
+    value
+        .parse()
+        .map_err(|_| format!("invalid value '{}' for {}", value, flag))
+}
+
+// Parses a range of seeds written `<start>..<end>`, the end excluded.
+fn parse_seed_range(flag: &str, value: &str) -> Result<Range<u64>, String> {
+    let (start, end) = value
+        .split_once("..")
+        .ok_or_else(|| format!("invalid range '{}' for {}", value, flag))?;
+    let range = parse_u64(flag, start)?..parse_u64(flag, end)?;
+    if range.is_empty() {
+        return Err(format!("{}'s range {} has no seeds in it", flag, value));
+    }
+    Ok(range)
+}
+
+// Parses a --what-if move written `<move>:<cell>`, both from 1, into a move
+// and a cell counted from 0.
+fn parse_what_if(flag: &str, value: &str) -> Result<(usize, usize), String> {
+    let invalid = || format!("invalid value '{}' for {}", value, flag);
+    let (turn, cell) = value.split_once(':').ok_or_else(invalid)?;
+    match (turn.parse::<usize>(), cell.parse::<usize>()) {
+        (Ok(turn @ 1..), Ok(cell @ 1..=9)) => Ok((turn - 1, cell - 1)),
+        _ => Err(invalid()),
+    }
+}
+
+// Parses an m,n,k-game written `<rows>,<cols>,<k>`.
+fn parse_mnk(value: &str) -> Result<MnkGame, String> {
+    let sizes = value
+        .split(',')
+        .map(|size| size.trim().parse::<usize>())
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|_| format!("invalid game '{}'", value))?;
+    match sizes[..] {
+        [rows, cols, k] => MnkGame::new(rows, cols, k),
+        _ => Err(format!("a game is <rows>,<cols>,<k>, not '{}'", value)),
+    }
+}
+
+impl Cli {
+    // Parses the arguments after the program name. Errors are messages for the
+    // user, to be printed above `USAGE`.
+    pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Cli, String> {
+        let mut args = args.into_iter();
+        let name = match args.next() {
+            Some(name) if name == "-h" || name == "--help" => {
+                return Ok(Cli {
+                    command: Command::Help,
+                    show_values: false,
+                    config: RLConfig::default(),
+                    seed: None,
+                    threads: None,
+                    quiet: false,
+                })
+            }
+            Some(name) => name,
+            None => return Err("missing command".to_string()),
+        };
+        let action = match name.as_str() {
+            "policy" => Some(
+                args.next()
+                    .ok_or("policy needs an action, stats, prune or export")?,
+            ),
+            "replay" => Some(args.next().ok_or("replay needs a log file")?),
+            "--eval-only" => Some(args.next().ok_or("--eval-only needs a policy file")?),
+            "report" => Some(
+                args.next()
+                    .ok_or("report needs a report to make, visitation")?,
+            ),
+            _ => None,
+        };
+        let trajectory_file = match name.as_str() {
+            "report" => Some(
+                args.next()
+                    .ok_or("report visitation needs a trajectory file")?,
+            ),
+            _ => None,
+        };
+        let mut games = None;
+        let mut x = None;
+        let mut o = None;
+        let mut show_values = false;
+        let mut save_policy = None;
+        let mut load_policy = None;
+        let mut side = None;
+        let mut config = RLConfig::default();
+        let mut freeze = false;
+        let mut progress = false;
+        let mut training = TrainingConfig {
+            eval_interval: 5000,
+            ..TrainingConfig::default()
+        };
+        let mut training_flags = false;
+        let mut metrics_out = None;
+        let mut metrics_interval = None;
+        let mut ratings_out = None;
+        let mut rating_interval = None;
+        let mut trajectories = None;
+        let mut jsonl = None;
+        let mut plot = None;
+        let mut save_state = None;
+        let mut save_interval = None;
+        let mut resume = None;
+        let mut seed = None;
+        let mut threads = None;
+        let mut agents = None;
+        let mut position = None;
+        let mut color = true;
+        let mut game = None;
+        let mut format = None;
+        let mut out = None;
+        let mut min_visits = None;
+        let mut log = None;
+        let mut blunder = None;
+        let mut what_if = None;
+        let mut b_config = None;
+        let mut top = None;
+        let mut quiet = false;
+        let mut dashboard = false;
+        let mut eval_games = false;
+        let mut listen = None;
+        // --alpha or --epsilon, which sweep replaces with lists, and
+        // --epsilon alone, which exploration-learning does.
+        let mut single_values = false;
+        let mut single_epsilon = false;
+        let mut exploration = false;
+        let mut alphas = None;
+        let mut epsilons = None;
+        let mut shaping = None;
+        let mut initial_values = None;
+        let mut seeds = None;
+        let mut seed_range = None;
+        let mut roles = None;
+        let mut misere = false;
+        let mut fault_rate = None;
+        while let Some(flag) = args.next() {
+            let mut value = || args.next().ok_or(format!("{} needs a value", flag));
+            match flag.as_str() {
+                "--games" => {
+                    let value = value()?;
+                    games = Some(
+                        value
+                            .parse()
+                            .map_err(|_| format!("invalid game count '{}'", value))?,
+                    );
+                }
+                "--x" => x = Some(value()?.parse()?),
+                "--agents" => {
+                    agents = Some(
+                        value()?
+                            .split(',')
+                            .map(str::parse)
+                            .collect::<Result<Vec<AgentSpec>, String>>()?,
+                    )
+                }
+                "--o" => o = Some(value()?.parse()?),
+                "--listen" => listen = Some(value()?),
+                "--show-values" => show_values = true,
+                "--log" => log = Some(PathBuf::from(value()?)),
+                "--blunder" => blunder = Some(parse_f32(&flag, &value()?)?),
+                "--what-if" => what_if = Some(parse_what_if(&flag, &value()?)?),
+                "--b-config" => b_config = Some(value()?),
+                "--top" => top = Some(parse_u32(&flag, &value()?)? as usize),
+                "--position" => position = Some(value()?.parse::<Board>()?),
+                "--no-color" => color = false,
+                "--game" => game = Some(parse_mnk(&value()?)?),
+                "--format" => {
+                    format = Some(match value()?.as_str() {
+                        "dot" => TreeFormat::Dot,
+                        "json" => TreeFormat::Json,
+                        "text" => TreeFormat::Text,
+                        other => return Err(format!("unknown format '{}'", other)),
+                    })
+                }
+                "--out" => out = Some(PathBuf::from(value()?)),
+                "--min-visits" => min_visits = Some(parse_u32(&flag, &value()?)?),
+                "--save-policy" => save_policy = Some(PathBuf::from(value()?)),
+                "--load-policy" => load_policy = Some(PathBuf::from(value()?)),
+                "--side" => {
+                    side = Some(match value()?.as_str() {
+                        "x" => PlayerMarker::X,
+                        "o" => PlayerMarker::O,
+                        other => return Err(format!("invalid side '{}'", other)),
+                    })
+                }
+                "--alpha" => {
+                    config.learning_rate = parse_f32(&flag, &value()?)?;
+                    single_values = true;
+                }
+                "--epsilon" => {
+                    config.epsilon = value()?.parse()?;
+                    single_values = true;
+                    single_epsilon = true;
+                }
+                "--exploration" => {
+                    config.exploration = value()?.parse()?;
+                    exploration = true;
+                }
+                "--alphas" => alphas = Some(parse_values(&flag, &value()?)?),
+                "--epsilons" => epsilons = Some(parse_values(&flag, &value()?)?),
+                "--shaping" => shaping = Some(parse_values(&flag, &value()?)?),
+                "--initial-values" => initial_values = Some(parse_values(&flag, &value()?)?),
+                "--roles" => roles = Some(value()?.parse()?),
+                "--misere" => misere = true,
+                "--fault-rate" => fault_rate = Some(parse_f32(&flag, &value()?)?),
+                "--seeds" => match value()? {
+                    range if range.contains("..") => {
+                        seed_range = Some(parse_seed_range(&flag, &range)?)
+                    }
+                    count => match parse_u32(&flag, &count)? {
+                        0 => return Err("--seeds must be at least 1".to_string()),
+                        n => seeds = Some(n as usize),
+                    },
+                },
+                "--freeze" => freeze = true,
+                "--progress" => progress = true,
+                "--alternate" => {
+                    training.alternate_sides = true;
+                    training_flags = true;
+                }
+                "--eval-interval" => {
+                    training.eval_interval = parse_u32(&flag, &value()?)?;
+                    training_flags = true;
+                }
+                "--eval-games" => {
+                    training.eval_games = parse_u32(&flag, &value()?)?;
+                    eval_games = true;
+                }
+                "--metrics-out" => {
+                    metrics_out = Some(PathBuf::from(value()?));
+                    training_flags = true;
+                }
+                "--metrics-interval" => {
+                    metrics_interval = Some(parse_u32(&flag, &value()?)?);
+                    training_flags = true;
+                }
+                "--ratings-out" => {
+                    ratings_out = Some(PathBuf::from(value()?));
+                    training_flags = true;
+                }
+                "--rating-interval" => {
+                    rating_interval = Some(parse_u32(&flag, &value()?)?);
+                    training_flags = true;
+                }
+                "--trajectories" => {
+                    trajectories = Some(PathBuf::from(value()?));
+                    training_flags = true;
+                }
+                "--jsonl" => {
+                    jsonl = Some(PathBuf::from(value()?));
+                    training_flags = true;
+                }
+                "--plot" => {
+                    plot = Some(parse_plot_path(&value()?)?);
+                    training_flags = true;
+                }
+                "--save-state" => {
+                    save_state = Some(PathBuf::from(value()?));
+                    training_flags = true;
+                }
+                "--save-interval" => {
+                    save_interval = match parse_u32(&flag, &value()?)? {
+                        0 => return Err("--save-interval must be at least 1".to_string()),
+                        n => Some(n),
+                    };
+                    training_flags = true;
+                }
+                "--resume" => {
+                    resume = Some(PathBuf::from(value()?));
+                    training_flags = true;
+                }
+                "--opponent-refresh" => {
+                    training.opponent_refresh = parse_u32(&flag, &value()?)?;
+                    training_flags = true;
+                }
+                "--eval-opponent" => {
+                    training.eval_opponent = match value()?.as_str() {
+                        "minimax" => EvalOpponent::Minimax,
+                        "random" => EvalOpponent::Random,
+                        "snapshot" => EvalOpponent::Snapshot,
+                        other => return Err(format!("unknown evaluation opponent '{}'", other)),
+                    };
+                    training_flags = true;
+                }
+                "--win-reward" => config.win_reward = parse_f32(&flag, &value()?)?,
+                "--draw-reward" => config.draw_reward = parse_f32(&flag, &value()?)?,
+                "--loss-reward" => config.loss_reward = parse_f32(&flag, &value()?)?,
+                "--step-reward" => config.step_reward = parse_f32(&flag, &value()?)?,
+                "--win-speed-bonus" => match parse_f32(&flag, &value()?)? {
+                    bonus if bonus < 0.0 => {
+                        return Err("--win-speed-bonus must be at least 0".to_string())
+                    }
+                    bonus => config.win_speed_bonus = bonus,
+                },
+                "--initial-value" => {
+                    config.initial_value = parse_f32(&flag, &value()?)?;
+                    single_values = true;
+                }
+                "--no-symmetry" => config.symmetry = false,
+                "--learn-from-exploration" => config.learn_from_exploration = true,
+                "--seed" => seed = Some(parse_u64(&flag, &value()?)?),
+                "--quiet" => quiet = true,
+                "--dashboard" => {
+                    if cfg!(not(feature = "tui")) {
+                        return Err("--dashboard needs a build with the tui feature".to_string());
+                    }
+                    dashboard = true;
+                }
+                "--threads" => match parse_u32(&flag, &value()?)? {
+                    0 => return Err("--threads must be at least 1".to_string()),
+                    n => threads = Some(n as usize),
+                },
+                "-h" | "--help" => {
+                    return Ok(Cli {
+                        command: Command::Help,
+                        show_values,
+                        config,
+                        seed,
+                        threads,
+                        quiet,
+                    })
+                }
+                _ => return Err(format!("unknown option '{}'", flag)),
+            }
+        }
+        // Only selfplay's checkpoints and sweep's trials are scored by
+        // --eval-games.
+        let compares = name == "sweep" || name == "exploration-learning" || name == "ab-test";
+        let training_flags = training_flags || (eval_games && !compares);
+        let sweep_flags = alphas.is_some()
+            || epsilons.is_some()
+            || shaping.is_some()
+            || initial_values.is_some()
+            || seeds.is_some()
+            || roles.is_some()
+            || misere
+            || fault_rate.is_some();
+        let command = match name.as_str() {
+            _ if listen.is_some() && name != "serve" => {
+                return Err(format!("{} does not take --listen", name))
+            }
+            _ if dashboard && name != "selfplay" => {
+                return Err(format!("{} does not take --dashboard", name))
+            }
+            _ if dashboard && quiet => {
+                return Err("--dashboard and --quiet can't be used together".to_string())
+            }
+            _ if seed_range.is_some() && compares => {
+                return Err(format!("{} takes a number of --seeds, not a range", name))
+            }
+            _ if seed_range.is_some() && name != "selfplay" => {
+                return Err(format!("{} does not take --seeds", name))
+            }
+            "selfplay" if seeds.is_some() => {
+                return Err("selfplay takes --seeds as a range, such as 0..30".to_string())
+            }
+            _ if sweep_flags && !compares => {
+                return Err(format!(
+                    "{} does not take --alphas, --epsilons, --shaping, --initial-values, --seeds, --roles, --misere or --fault-rate",
+                    name
+                ))
+            }
+            "--eval-only"
+                if x.is_some()
+                    || o.is_some()
+                    || agents.is_some()
+                    || show_values
+                    || position.is_some()
+                    || !color
+                    || save_policy.is_some()
+                    || load_policy.is_some()
+                    || freeze
+                    || progress
+                    || training_flags
+                    || eval_games
+                    || threads.is_some()
+                    || format.is_some()
+                    || out.is_some()
+                    || min_visits.is_some()
+                    || log.is_some()
+                    || blunder.is_some()
+                    || what_if.is_some()
+                    || b_config.is_some()
+                    || top.is_some()
+                    || game.is_some()
+                    || quiet =>
+            {
+                return Err("--eval-only only takes --side, --games and --seed".to_string())
+            }
+            "--eval-only" => Command::EvalOnly {
+                policy: PathBuf::from(action.expect("read with the command name")),
+                side: side.unwrap_or(PlayerMarker::O),
+                games: games.unwrap_or(10000),
+            },
+            "mnk"
+                if x.is_some()
+                    || o.is_some()
+                    || agents.is_some()
+                    || show_values
+                    || position.is_some()
+                    || !color
+                    || save_policy.is_some()
+                    || load_policy.is_some()
+                    || side.is_some()
+                    || freeze
+                    || progress
+                    || training_flags
+                    || threads.is_some()
+                    || format.is_some()
+                    || out.is_some()
+                    || min_visits.is_some()
+                    || log.is_some()
+                    || quiet
+                    || !config.symmetry
+                    || exploration
+                    || config.step_reward != 0.0
+                    || config.win_speed_bonus != 0.0
+                    || config.learn_from_exploration
+                    || config.initial_value != 0.0 =>
+            {
+                return Err(
+                    "mnk only takes --game, --games, --seed and the hyperparameters other than --no-symmetry, --exploration, --step-reward, --win-speed-bonus, --learn-from-exploration and --initial-value"
+                        .to_string(),
+                )
+            }
+            "mnk" => Command::Mnk {
+                game: match game {
+                    Some(game) => game,
+                    None => MnkGame::new(4, 4, 3)?,
+                },
+                games: games.unwrap_or(50000),
+            },
+            _ if game.is_some() => return Err(format!("{} does not take --game", name)),
+            _ if log.is_some() && name != "play" => {
+                return Err(format!("{} does not take --log", name))
+            }
+            _ if blunder.is_some() && name != "replay" => {
+                return Err(format!("{} does not take --blunder", name))
+            }
+            _ if what_if.is_some() && name != "replay" => {
+                return Err(format!("{} does not take --what-if", name))
+            }
+            "replay"
+                if x.is_some()
+                    || o.is_some()
+                    || agents.is_some()
+                    || position.is_some()
+                    || save_policy.is_some()
+                    || freeze
+                    || progress
+                    || training_flags
+                    || format.is_some()
+                    || out.is_some()
+                    || min_visits.is_some() =>
+            {
+                return Err(
+                    "replay only takes --games, --show-values, --side, --load-policy, --blunder, --no-color, --what-if, --threads, --seed and the hyperparameters"
+                        .to_string(),
+                )
+            }
+            "replay" if show_values && what_if.is_some() => {
+                return Err("--show-values and --what-if can't be used together".to_string())
+            }
+            "replay" if what_if.is_some() && (blunder.is_some() || !color) => {
+                return Err("--blunder and --no-color need --show-values".to_string())
+            }
+            "replay"
+                if !show_values
+                    && what_if.is_none()
+                    && (side.is_some() || load_policy.is_some() || blunder.is_some() || !color) =>
+            {
+                return Err(
+                    "--side, --load-policy, --blunder and --no-color need --show-values or --what-if"
+                        .to_string(),
+                )
+            }
+            _ if top.is_some() && name != "report" => {
+                return Err(format!("{} does not take --top", name))
+            }
+            "report" if action.as_deref() != Some("visitation") => {
+                return Err(format!(
+                    "unknown report '{}' (expected visitation)",
+                    action.unwrap_or_default()
+                ))
+            }
+            "report"
+                if games.is_some()
+                    || x.is_some()
+                    || o.is_some()
+                    || agents.is_some()
+                    || show_values
+                    || position.is_some()
+                    || !color
+                    || save_policy.is_some()
+                    || load_policy.is_some()
+                    || side.is_some()
+                    || freeze
+                    || progress
+                    || training_flags
+                    || format.is_some()
+                    || min_visits.is_some() =>
+            {
+                return Err("report visitation only takes --top and --out".to_string())
+            }
+            "report" => Command::ReportVisitation {
+                trajectories: PathBuf::from(trajectory_file.expect("read with the command name")),
+                top: top.unwrap_or(5),
+                out,
+            },
+            "replay" => Command::Replay {
+                log: PathBuf::from(action.expect("read with the command name")),
+                games: games.map(|games: u32| games as usize),
+                side: side.or(load_policy.as_ref().map(|_| PlayerMarker::O)),
+                load_policy,
+                blunder: blunder.unwrap_or(0.25),
+                color,
+                what_if,
+            },
+            "analyze"
+                if x.is_some()
+                    || o.is_some()
+                    || agents.is_some()
+                    || show_values
+                    || position.is_some()
+                    || !color
+                    || save_policy.is_some()
+                    || min_visits.is_some()
+                    || freeze
+                    || progress
+                    || training_flags =>
+            {
+                return Err(
+                    "analyze only takes --side, --load-policy, --format, --out, --games, --threads, --seed and the hyperparameters"
+                        .to_string(),
+                )
+            }
+            "analyze" if format == Some(TreeFormat::Text) => {
+                return Err("analyze writes --format dot or json".to_string())
+            }
+            "analyze" => Command::Analyze {
+                side: side.unwrap_or(PlayerMarker::O),
+                load_policy,
+                format: format.unwrap_or(TreeFormat::Dot),
+                out,
+                games: games.unwrap_or(1000),
+            },
+            "serve"
+                if x.is_some()
+                    || o.is_some()
+                    || show_values
+                    || position.is_some()
+                    || !color
+                    || save_policy.is_some()
+                    || load_policy.is_some()
+                    || side.is_some()
+                    || min_visits.is_some()
+                    || format.is_some()
+                    || out.is_some()
+                    || freeze
+                    || progress
+                    || training_flags =>
+            {
+                return Err(
+                    "serve only takes --listen, --agents, --games, --threads, --quiet, --seed and the hyperparameters"
+                        .to_string(),
+                )
+            }
+            "serve" => Command::Serve {
+                listen: listen.unwrap_or_else(|| "127.0.0.1:7878".to_string()),
+                agents: match agents {
+                    Some(agents) => agents,
+                    None => vec!["rl".parse()?],
+                },
+                games: games.unwrap_or(100),
+            },
+            _ if b_config.is_some() && name != "ab-test" => {
+                return Err(format!("{} does not take --b-config", name))
+            }
+            "ab-test"
+                if x.is_some()
+                    || o.is_some()
+                    || agents.is_some()
+                    || show_values
+                    || position.is_some()
+                    || !color
+                    || save_policy.is_some()
+                    || load_policy.is_some()
+                    || side.is_some()
+                    || min_visits.is_some()
+                    || format.is_some()
+                    || out.is_some()
+                    || freeze
+                    || progress
+                    || training_flags
+                    || alphas.is_some()
+                    || epsilons.is_some()
+                    || shaping.is_some()
+                    || initial_values.is_some()
+                    || roles.is_some()
+                    || misere
+                    || fault_rate.is_some() =>
+            {
+                return Err(
+                    "ab-test only takes --b-config, --seeds, --games, --eval-games, --seed and the hyperparameters"
+                        .to_string(),
+                )
+            }
+            "ab-test" if eval_games && training.eval_games == 0 => {
+                return Err("ab-test needs at least one --eval-games game".to_string())
+            }
+            "ab-test" => {
+                let path = b_config.ok_or("ab-test needs --b-config")?;
+                let args = rl_core::expand_config(["train", "--config", &path].map(str::to_string))?;
+                let b = Cli::parse(args).map_err(|error| format!("{}: {}", path, error))?;
+                // Parsed as train's options, which are the hyperparameters
+                // and a few more that are left at their defaults.
+                if !matches!(
+                    b.command,
+                    Command::Train {
+                        games: 100000,
+                        save_policy: None
+                    }
+                ) || b.seed.is_some()
+                    || b.threads.is_some()
+                    || b.quiet
+                    || b.show_values
+                {
+                    return Err(format!("{}: --b-config only sets hyperparameters", path));
+                }
+                Command::AbTest {
+                    config_b: b.config,
+                    seeds: seeds.unwrap_or(10) as u32,
+                    games: games.unwrap_or(10000),
+                    eval_games: if eval_games { training.eval_games } else { 1000 },
+                }
+            }
+            "sweep" | "exploration-learning"
+                if x.is_some()
+                    || o.is_some()
+                    || agents.is_some()
+                    || show_values
+                    || position.is_some()
+                    || !color
+                    || save_policy.is_some()
+                    || load_policy.is_some()
+                    || side.is_some()
+                    || min_visits.is_some()
+                    || format.is_some()
+                    || freeze
+                    || progress
+                    || training_flags =>
+            {
+                return Err(format!(
+                    "{} only takes {}--epsilons, --seeds, --roles, --misere, --fault-rate, --games, --eval-games, --out, --threads, --quiet, --seed and the reward and symmetry hyperparameters",
+                    name,
+                    if name == "sweep" {
+                        "--alphas, --shaping, --initial-values, "
+                    } else {
+                        "--alpha, --initial-value, "
+                    }
+                ))
+            }
+            "sweep" if single_values => {
+                return Err("sweep takes --alphas, --epsilons and --initial-values instead of --alpha, --epsilon and --initial-value".to_string())
+            }
+            "exploration-learning"
+                if alphas.is_some() || shaping.is_some() || initial_values.is_some() =>
+            {
+                return Err(
+                    "exploration-learning does not take --alphas, --shaping or --initial-values"
+                        .to_string(),
+                )
+            }
+            "exploration-learning" if config.learn_from_exploration => {
+                return Err("exploration-learning compares learning with and without --learn-from-exploration; it does not take it".to_string())
+            }
+            "exploration-learning" if single_epsilon => {
+                return Err("exploration-learning takes --epsilons instead of --epsilon".to_string())
+            }
+            "sweep" | "exploration-learning" if exploration => {
+                return Err(format!(
+                    "{} explores epsilon-greedily; it does not take --exploration",
+                    name
+                ))
+            }
+            "sweep" | "exploration-learning" if eval_games && training.eval_games == 0 => {
+                return Err(format!("{} needs at least one --eval-games game", name))
+            }
+            "sweep"
+                if alphas
+                    .iter()
+                    .flatten()
+                    .any(|&alpha| alpha <= 0.0 || alpha > 1.0) =>
+            {
+                return Err("--alphas must be in (0, 1]".to_string())
+            }
+            "sweep" | "exploration-learning"
+                if epsilons
+                    .iter()
+                    .flatten()
+                    .any(|epsilon| !(0.0..=1.0).contains(epsilon)) =>
+            {
+                return Err("--epsilons must be in [0, 1]".to_string())
+            }
+            "sweep" | "exploration-learning"
+                if fault_rate.is_some_and(|rate| !(0.0..=1.0).contains(&rate)) =>
+            {
+                return Err("--fault-rate must be in [0, 1]".to_string())
+            }
+            "sweep" => Command::Sweep {
+                alphas: alphas.unwrap_or_else(|| vec![0.05, 0.1, 0.2, 0.4]),
+                epsilons: epsilons.unwrap_or_else(|| vec![0.01, 0.05, 0.1, 0.2]),
+                shaping: shaping.unwrap_or_else(|| vec![0.0, 0.1]),
+                initial_values: initial_values.unwrap_or_else(|| vec![0.0]),
+                seeds: seeds.unwrap_or(5),
+                roles: roles.unwrap_or(Roles::O),
+                rules: Rules {
+                    misere,
+                    fault_rate: fault_rate.unwrap_or(0.0),
+                },
+                games: games.unwrap_or(5000),
+                eval_games: if eval_games { training.eval_games } else { 1000 },
+                out,
+            },
+            "exploration-learning" => Command::ExplorationLearning {
+                epsilons: epsilons.unwrap_or_else(|| vec![0.05, 0.1, 0.2, 0.4]),
+                seeds: seeds.unwrap_or(5),
+                roles: roles.unwrap_or(Roles::O),
+                rules: Rules {
+                    misere,
+                    fault_rate: fault_rate.unwrap_or(0.0),
+                },
+                games: games.unwrap_or(5000),
+                eval_games: if eval_games { training.eval_games } else { 1000 },
+                out,
+            },
+            "policy"
+                if games.is_some()
+                    || x.is_some()
+                    || o.is_some()
+                    || agents.is_some()
+                    || show_values
+                    || position.is_some()
+                    || !color
+                    || freeze
+                    || progress
+                    || training_flags =>
+            {
+                return Err(
+                    "policy only takes --side, --load-policy, --save-policy, --min-visits, --format, --out, --threads, --seed and the hyperparameters"
+                        .to_string(),
+                )
+            }
+            "policy" => {
+                let action = match action.as_deref() {
+                    Some("stats" | "prune") if format.is_some() || out.is_some() => {
+                        return Err(
+                            "only policy export takes --format and --out".to_string(),
+                        )
+                    }
+                    Some("export") if min_visits.is_some() || save_policy.is_some() => {
+                        return Err(
+                            "policy export does not take --min-visits or --save-policy".to_string(),
+                        )
+                    }
+                    Some("export") if format.is_some_and(|format| format != TreeFormat::Text) => {
+                        return Err("policy export only writes --format text".to_string())
+                    }
+                    Some("export") => PolicyAction::Export,
+                    Some("stats") if min_visits.is_some() || save_policy.is_some() => {
+                        return Err(
+                            "policy stats does not take --min-visits or --save-policy".to_string(),
+                        )
+                    }
+                    Some("stats") => PolicyAction::Stats,
+                    Some("prune") => PolicyAction::Prune {
+                        min_visits: min_visits.unwrap_or(2),
+                    },
+                    Some(other) => {
+                        return Err(format!(
+                            "unknown policy action '{}' (expected stats, prune or export)",
+                            other
+                        ))
+                    }
+                    None => unreachable!("read with the command name"),
+                };
+                Command::Policy {
+                    action,
+                    side: side.unwrap_or(PlayerMarker::O),
+                    load_policy,
+                    save_policy,
+                    out,
+                }
+            }
+            _ if format.is_some() || out.is_some() => {
+                return Err(format!("{} does not take --format or --out", name))
+            }
+            _ if min_visits.is_some() => {
+                return Err(format!("{} does not take --min-visits", name))
+            }
+            "train" | "play" | "eval" | "selfplay" | "tournament" | "experiments"
+                if position.is_some() || !color =>
+            {
+                return Err(format!("{} does not take --position or --no-color", name))
+            }
+            "show-values"
+                if games.is_some()
+                    || x.is_some()
+                    || o.is_some()
+                    || agents.is_some()
+                    || save_policy.is_some()
+                    || side.is_some()
+                    || freeze
+                    || progress
+                    || training_flags =>
+            {
+                return Err(
+                    "show-values only takes --position, --load-policy, --no-color and the hyperparameters"
+                        .to_string(),
+                )
+            }
+            "train" | "play" | "eval" | "selfplay" | "experiments" if agents.is_some() => {
+                return Err(format!("{} does not take --agents", name))
+            }
+            "tournament" if agents.as_ref().is_some_and(|agents| agents.len() < 2) => {
+                return Err("a tournament needs at least two --agents".to_string())
+            }
+            "train" | "play" | "eval" | "tournament" | "experiments" if training_flags => {
+                return Err(format!(
+                    "{} does not take --alternate, --opponent-refresh, --jsonl, --plot, --save-state, --save-interval, --resume, --trajectories or the --eval-*, --metrics-* and --rating* options",
+                    name
+                ))
+            }
+            "selfplay"
+                if metrics_interval.is_some()
+                    && metrics_out.is_none()
+                    && jsonl.is_none()
+                    && plot.is_none() =>
+            {
+                return Err("--metrics-interval needs --metrics-out, --jsonl or --plot".to_string())
+            }
+            "selfplay" if rating_interval.is_some() && ratings_out.is_none() => {
+                return Err("--rating-interval needs --ratings-out".to_string())
+            }
+            "train" | "selfplay" | "tournament" | "experiments" if x.is_some() || o.is_some() => {
+                return Err(format!("{} does not take --x or --o", name))
+            }
+            "play" | "eval" | "selfplay" | "tournament" | "experiments"
+                if save_policy.is_some() || side.is_some() =>
+            {
+                return Err(format!("{} does not take --save-policy or --side", name))
+            }
+            "selfplay" if seed_range.is_some() && seed.is_some() => {
+                return Err("--seed and --seeds can't be used together".to_string())
+            }
+            "selfplay"
+                if seed_range.is_some()
+                    && (dashboard
+                        || plot.is_some()
+                        || save_state.is_some()
+                        || resume.is_some()
+                        || ratings_out.is_some()
+                        || trajectories.is_some()) =>
+            {
+                return Err(
+                    "--seeds can't be used with --dashboard, --plot, --save-state, --resume, --ratings-out or --trajectories"
+                        .to_string(),
+                )
+            }
+            "selfplay" if threads.is_some() && seed_range.is_none() => {
+                return Err("selfplay only takes --threads with --seeds".to_string())
+            }
+            "train" | "selfplay" | "tournament" | "experiments" if load_policy.is_some() => {
+                return Err(format!("{} does not take --load-policy", name))
+            }
+            "train" | "play" | "selfplay" | "tournament" | "experiments" if freeze || progress => {
+                return Err(format!("{} does not take --freeze or --progress", name))
+            }
+            "train" if side.is_some() && save_policy.is_none() => {
+                return Err("--side needs --save-policy".to_string())
+            }
+            "train" => Command::Train {
+                games: games.unwrap_or(100000),
+                save_policy: save_policy.map(|path| (side.unwrap_or(PlayerMarker::O), path)),
+            },
+            "selfplay" if save_state.is_some() && resume.is_some() => {
+                return Err(
+                    "--resume keeps saving to the directory it reads, so it doesn't take --save-state"
+                        .to_string(),
+                )
+            }
+            "selfplay" if save_interval.is_some() && save_state.is_none() && resume.is_none() => {
+                return Err("--save-interval needs --save-state or --resume".to_string())
+            }
+            "selfplay" => Command::SelfPlay {
+                games: games.unwrap_or(50000),
+                training: TrainingConfig {
+                    metrics_interval: if metrics_out.is_some() || jsonl.is_some() || plot.is_some() {
+                        metrics_interval.unwrap_or(1000)
+                    } else {
+                        0
+                    },
+                    rating_interval: if ratings_out.is_some() {
+                        rating_interval.unwrap_or(5000)
+                    } else {
+                        0
+                    },
+                    seed,
+                    ..training
+                },
+                metrics_out,
+                ratings_out,
+                trajectories,
+                jsonl,
+                plot,
+                display: if dashboard {
+                    TrainingDisplay::Dashboard
+                } else if quiet {
+                    TrainingDisplay::Hidden
+                } else {
+                    TrainingDisplay::Progress
+                },
+                saved_state: save_state
+                    .map(|dir| (dir, false))
+                    .or(resume.map(|dir| (dir, true)))
+                    .map(|(dir, resume)| SavedState {
+                        dir,
+                        interval: save_interval.unwrap_or(5000),
+                        resume,
+                    }),
+                seeds: seed_range,
+            },
+            "tournament" => Command::Tournament {
+                agents: match agents {
+                    Some(agents) => agents,
+                    None => ["random", "heuristic", "minimax", "rl"]
+                        .iter()
+                        .map(|name| name.parse())
+                        .collect::<Result<_, _>>()?,
+                },
+                games: games.unwrap_or(100),
+            },
+            "show-values" => {
+                let position = position.ok_or("show-values needs --position")?;
+                if position.check_winner(PlayerMarker::X)
+                    || position.check_winner(PlayerMarker::O)
+                    || position.is_full()
+                {
+                    return Err("--position is a finished game".to_string());
+                }
+                Command::ShowValues {
+                    position,
+                    load_policy,
+                    color,
+                }
+            }
+            "experiments" => Command::Experiments {
+                games: games.unwrap_or(100000),
+            },
+            "play" => Command::Play {
+                x: x.unwrap_or(AgentKind::Human),
+                o: o.unwrap_or(AgentKind::Rl),
+                games: games.unwrap_or(1),
+                load_policy,
+                log,
+            },
+            "eval" => Command::Eval {
+                x: x.unwrap_or(AgentKind::Random),
+                o: o.unwrap_or(AgentKind::Rl),
+                games: games.unwrap_or(1000),
+                load_policy,
+                freeze,
+                progress,
+            },
+            _ => return Err(format!("unknown command '{}'", name)),
+        };
+        Ok(Cli {
+            command,
+            show_values,
+            config,
+            seed,
+            threads,
+            quiet,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &str) -> Result<Cli, String> {
+        Cli::parse(args.split_whitespace().map(str::to_string))
+    }
+
+    #[test]
+    fn commands_fill_in_their_defaults() {
+        assert!(matches!(
+            parse("train").unwrap().command,
+            Command::Train {
+                games: 100000,
+                save_policy: None
+            }
+        ));
+        assert!(matches!(
+            parse("play").unwrap().command,
+            Command::Play {
+                x: AgentKind::Human,
+                o: AgentKind::Rl,
+                games: 1,
+                load_policy: None,
+                log: None
+            }
+        ));
+        assert!(matches!(
+            parse("eval").unwrap().command,
+            Command::Eval {
+                x: AgentKind::Random,
+                o: AgentKind::Rl,
+                games: 1000,
+                freeze: false,
+                progress: false,
+                ..
+            }
+        ));
+        let Command::SelfPlay {
+            games,
+            training,
+            display,
+            saved_state,
+            seeds,
+            ..
+        } = parse("selfplay").unwrap().command
+        else {
+            panic!("selfplay parses as SelfPlay");
+        };
+        assert_eq!(games, 50000);
+        assert_eq!(training.eval_interval, 5000);
+        assert_eq!(training.metrics_interval, 0);
+        assert_eq!(display, TrainingDisplay::Progress);
+        assert!(saved_state.is_none() && seeds.is_none());
+        let Command::Tournament { agents, games } = parse("tournament").unwrap().command else {
+            panic!("tournament parses as Tournament");
+        };
+        let names: Vec<&str> = agents.iter().map(|agent| agent.name.as_str()).collect();
+        assert_eq!(names, ["random", "heuristic", "minimax", "rl"]);
+        assert_eq!(games, 100);
+        let cli = parse("eval").unwrap();
+        assert_eq!(cli.seed, None);
+        assert!(!cli.show_values && !cli.quiet);
+        assert_eq!(cli.config.learning_rate, RLConfig::default().learning_rate);
+    }
+
+    #[test]
+    fn unknown_commands_flags_and_agents_are_rejected() {
+        assert_eq!(parse("").unwrap_err(), "missing command");
+        assert_eq!(parse("fly").unwrap_err(), "unknown command 'fly'");
+        assert_eq!(
+            parse("eval --bogus").unwrap_err(),
+            "unknown option '--bogus'"
+        );
+        assert_eq!(
+            parse("eval --x robot").unwrap_err(),
+            "unknown agent 'robot'"
+        );
+        assert_eq!(
+            parse("tournament --agents random,robot").unwrap_err(),
+            "unknown agent 'robot'"
+        );
+        assert_eq!(parse("eval --games").unwrap_err(), "--games needs a value");
+        assert_eq!(
+            parse("train --x random").unwrap_err(),
+            "train does not take --x or --o"
+        );
+    }
+
+    #[test]
+    fn rl_specs_carry_a_policy_path() {
+        let spec: AgentSpec = "rl:policies/x.bin".parse().unwrap();
+        assert_eq!(spec.kind, AgentKind::Rl);
+        assert_eq!(spec.policy, Some(PathBuf::from("policies/x.bin")));
+        assert_eq!(spec.name, "rl:policies/x.bin");
+        let spec: AgentSpec = "minimax".parse().unwrap();
+        assert_eq!((spec.kind, spec.policy), (AgentKind::Minimax, None));
+        assert!("rl:".parse::<AgentSpec>().is_err());
+        assert!("random:x.bin".parse::<AgentSpec>().is_err());
+        assert!("human".parse::<AgentSpec>().is_err());
+        let Command::Tournament { agents, .. } = parse("tournament --agents rl:a.bin,rl:b.json")
+            .unwrap()
+            .command
+        else {
+            panic!("tournament parses as Tournament");
+        };
+        assert_eq!(agents[1].policy, Some(PathBuf::from("b.json")));
+    }
+
+    #[test]
+    fn seed_ranges_must_hold_a_seed() {
+        let Command::SelfPlay { seeds, .. } = parse("selfplay --seeds 3..7").unwrap().command
+        else {
+            panic!("selfplay parses as SelfPlay");
+        };
+        assert_eq!(seeds, Some(3..7));
+        assert_eq!(
+            parse("selfplay --seeds 5..5").unwrap_err(),
+            "--seeds's range 5..5 has no seeds in it"
+        );
+        assert!(parse("selfplay --seeds 7..3").is_err());
+        assert!(parse("selfplay --seeds 3..x").is_err());
+    }
+
+    #[test]
+    fn command_line_options_override_a_config_file() {
+        let path = std::env::temp_dir().join("cli_config_override.toml");
+        std::fs::write(
+            &path,
+            "command = \"eval\"\nx = \"heuristic\"\ngames = 10\nalpha = 0.3\n",
+        )
+        .unwrap();
+        let args = ["--config", path.to_str().unwrap(), "--games", "20"].map(str::to_string);
+        let cli = rl_core::expand_config(args).and_then(Cli::parse);
+        std::fs::remove_file(&path).unwrap();
+        let cli = cli.unwrap();
+        assert!(matches!(
+            cli.command,
+            Command::Eval {
+                x: AgentKind::Heuristic,
+                games: 20,
+                ..
+            }
+        ));
+        assert_eq!(cli.config.learning_rate, 0.3);
+    }
+
+    #[test]
+    fn replay_what_if_takes_a_move_and_a_cell() {
+        let Command::Replay { what_if, side, .. } =
+            parse("replay games.log --what-if 3:5 --load-policy o.bin")
+                .unwrap()
+                .command
+        else {
+            panic!("replay parses as Replay");
+        };
+        assert_eq!(what_if, Some((2, 4)));
+        assert_eq!(side, Some(PlayerMarker::O));
+        for value in ["3", "0:5", "3:10", "a:b"] {
+            assert_eq!(
+                parse(&format!("replay games.log --what-if {}", value)).unwrap_err(),
+                format!("invalid value '{}' for --what-if", value)
+            );
+        }
+        assert!(parse("replay games.log --what-if 3:5 --show-values").is_err());
+        assert!(parse("eval --what-if 3:5").is_err());
+    }
+
+    #[test]
+    fn ab_test_reads_configuration_b_from_a_file() {
+        let path = std::env::temp_dir().join("ab_test_b.toml");
+        std::fs::write(&path, "alpha = 0.4\nepsilon = 0.2\n").unwrap();
+        let cli = parse(&format!(
+            "ab-test --alpha 0.2 --b-config {} --seeds 4",
+            path.display()
+        ))
+        .unwrap();
+        let Command::AbTest {
+            config_b,
+            seeds,
+            games,
+            eval_games,
+        } = cli.command
+        else {
+            panic!("ab-test parses as AbTest");
+        };
+        assert_eq!((seeds, games, eval_games), (4, 10000, 1000));
+        assert_eq!(cli.config.learning_rate, 0.2);
+        assert_eq!(config_b.learning_rate, 0.4);
+        assert_eq!(config_b.epsilon.value(0), 0.2);
+        std::fs::write(&path, "alpha = 0.4\nsave_policy = \"b.bin\"\n").unwrap();
+        assert!(parse(&format!("ab-test --b-config {}", path.display()))
+            .unwrap_err()
+            .ends_with("--b-config only sets hyperparameters"));
+        assert_eq!(parse("ab-test").unwrap_err(), "ab-test needs --b-config");
+        assert!(parse("eval --b-config b.toml").is_err());
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn eval_only_takes_a_policy_file_in_place_of_a_command() {
+        let Command::EvalOnly {
+            policy,
+            side,
+            games,
+        } = parse("--eval-only o_policy.bin --games 500")
+            .unwrap()
+            .command
+        else {
+            panic!("--eval-only parses as EvalOnly");
+        };
+        assert_eq!(policy, PathBuf::from("o_policy.bin"));
+        assert_eq!(side, PlayerMarker::O);
+        assert_eq!(games, 500);
+        let Command::EvalOnly { side, games, .. } =
+            parse("--eval-only x_policy.bin --side x").unwrap().command
+        else {
+            panic!("--eval-only parses as EvalOnly");
+        };
+        assert_eq!((side, games), (PlayerMarker::X, 10000));
+        assert_eq!(
+            parse("--eval-only").unwrap_err(),
+            "--eval-only needs a policy file"
+        );
+        assert_eq!(
+            parse("--eval-only o_policy.bin --save-policy other.bin").unwrap_err(),
+            "--eval-only only takes --side, --games and --seed"
+        );
+    }
+}
@@ -0,0 +1,407 @@
+use std::fmt;
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{AgentOutcome, Board};
+
+const DEFAULT_VALUE: f32 = 0.0;
+
+// Hyperparameters shared by the learning agents.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct RLConfig {
+    pub learning_rate: f32,
+    // Probability of playing a random move instead of the greedy one.
+    pub epsilon: DecaySchedule,
+    pub win_reward: f32,
+    pub draw_reward: f32,
+    pub loss_reward: f32,
+    // Key value tables on the canonical form of each board under the 8
+    // symmetries of the square (Exercise 1.2). Turning this off learns every
+    // orientation separately, for comparison.
+    #[serde(default = "default_symmetry")]
+    pub symmetry: bool,
+    // Discount applied to the value of the next state in every backup. 1 (the
+    // default) suits episodic games like tic-tac-toe.
+    #[serde(default = "default_gamma")]
+    pub gamma: f32,
+    // Trace decay for TD(lambda): 0 is one-step TD, 1 approaches Monte Carlo.
+    #[serde(default)]
+    pub lambda: f32,
+    // Number of moves RLAgent looks ahead before bootstrapping. Values above 1
+    // replace the one-step (and TD(lambda)) backup with an n-step return.
+    #[serde(default = "default_n_step")]
+    pub n_step: usize,
+    // How RLAgent picks between exploring and exploiting.
+    #[serde(default)]
+    pub exploration: ExplorationStrategy,
+    // Experience replay for RLAgent: how many recent transitions to keep, and
+    // how many of them to back up after every step in place of the online
+    // update. A capacity of 0 disables replay.
+    #[serde(default)]
+    pub replay_capacity: usize,
+    #[serde(default = "default_replay_batch_size")]
+    pub replay_batch_size: usize,
+    // Shaping toward short games for RLAgent: a reward for every move it
+    // makes, a small negative one penalizing long games, and a bonus scaling
+    // the reward for a win by 1 + win_speed_bonus * the cells left empty.
+    #[serde(default)]
+    pub step_reward: f32,
+    #[serde(default)]
+    pub win_speed_bonus: f32,
+    // Back RLAgent's values up after exploratory moves too (Exercise 1.3),
+    // rather than only after greedy ones as in Section 1.5.
+    #[serde(default)]
+    pub learn_from_exploration: bool,
+    // The value of a state or action before its first backup: 0 by default,
+    // 0.5 as in Section 1.5, or above every reward for optimistic
+    // exploration (Section 2.6).
+    #[serde(default = "default_initial_value")]
+    pub initial_value: f32,
+    // Games RLAgent plays purely at random before it backs anything up. With
+    // replay they fill the buffer, whose backups then reach positions the
+    // greedy policy would never visit; without it they only count visits.
+    // The epsilon schedule starts once they are over.
+    #[serde(default)]
+    pub warmup_episodes: u32,
+    // Clips every reward RLAgent backs up, shaping and all, to [-clip, clip],
+    // so one extreme reward can't throw its values far off. Values then stay
+    // within clip / (1 - gamma) of 0.
+    #[serde(default)]
+    pub reward_clip: Option<f32>,
+    // A schedule for RLAgent's step size over the games it learns from after
+    // its warm-up, such as `DecaySchedule::Cosine`. It replaces
+    // `learning_rate` at the start of every game; `None` keeps it fixed.
+    #[serde(default)]
+    pub alpha_schedule: Option<DecaySchedule>,
+    // Polyak averaging: after every backup RLAgent moves an average of its
+    // values this fraction of the way toward the live ones, and plays by the
+    // average whenever it isn't learning, as in evaluation and human play. 0
+    // keeps no average; 1 makes it the live values.
+    #[serde(default)]
+    pub polyak_beta: f32,
+}
+
+fn default_initial_value() -> f32 {
+    DEFAULT_VALUE
+}
+
+fn default_symmetry() -> bool {
+    true
+}
+
+fn default_gamma() -> f32 {
+    1.0
+}
+
+fn default_n_step() -> usize {
+    1
+}
+
+fn default_replay_batch_size() -> usize {
+    32
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub enum ExplorationStrategy {
+    // Random moves with the probability given by `RLConfig::epsilon`.
+    #[default]
+    EpsilonGreedy,
+    // Upper confidence bound with exploration constant `c`: maximizes
+    // Q(s,a) + c * sqrt(ln N(s) / N(s,a)), trying every untried move first.
+    Ucb1(f32),
+    // Boltzmann selection: P(a) is proportional to exp(Q(s,a) / temperature).
+    Softmax {
+        temperature: f32,
+    },
+    // Greedy on Q(s,a) + beta / sqrt(N(s') + 1), where N(s') counts how often
+    // the resulting afterstate has been played; the bonus fades as counts grow.
+    CountBased {
+        beta: f32,
+    },
+}
+
+// Parses `epsilon-greedy`, `ucb:c`, `softmax:temperature` or `count:beta`.
+impl FromStr for ExplorationStrategy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<ExplorationStrategy, String> {
+        let parameter = |part: &str| {
+            part.parse::<f32>()
+                .ok()
+                .filter(|&value| value.is_finite() && value >= 0.0)
+                .ok_or_else(|| format!("Invalid parameter {:?} in {:?}", part, s))
+        };
+        match s.split(':').collect::<Vec<_>>()[..] {
+            ["epsilon-greedy"] => Ok(ExplorationStrategy::EpsilonGreedy),
+            ["ucb", c] => Ok(ExplorationStrategy::Ucb1(parameter(c)?)),
+            ["softmax", temperature] => match parameter(temperature)? {
+                0.0 => Err(format!("Invalid temperature 0 in {:?}", s)),
+                temperature => Ok(ExplorationStrategy::Softmax { temperature }),
+            },
+            ["count", beta] => Ok(ExplorationStrategy::CountBased {
+                beta: parameter(beta)?,
+            }),
+            _ => Err(format!("Unknown exploration strategy {:?}", s)),
+        }
+    }
+}
+
+impl fmt::Display for ExplorationStrategy {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ExplorationStrategy::EpsilonGreedy => write!(f, "epsilon-greedy"),
+            ExplorationStrategy::Ucb1(c) => write!(f, "ucb:{}", c),
+            ExplorationStrategy::Softmax { temperature } => write!(f, "softmax:{}", temperature),
+            ExplorationStrategy::CountBased { beta } => write!(f, "count:{}", beta),
+        }
+    }
+}
+
+// A value such as epsilon that changes with the number of steps taken.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum DecaySchedule {
+    Constant(f32),
+    // Interpolates from `start` to `end` over `decay_steps`, then holds `end`.
+    Linear {
+        start: f32,
+        end: f32,
+        decay_steps: u32,
+    },
+    // Multiplies `start` by `decay` once per step.
+    Exponential {
+        start: f32,
+        decay: f32,
+    },
+    // Multiplies `start` by `factor` once every `interval` steps, taking an
+    // `interval` of 0 as 1.
+    Step {
+        start: f32,
+        factor: f32,
+        interval: u32,
+    },
+    // Falls from `start` to `end` along half a cosine over `steps`, then
+    // holds `end`: large early for fast learning, small late to settle.
+    Cosine {
+        start: f32,
+        end: f32,
+        steps: u32,
+    },
+    // `Cosine` over each `period` steps, back at `start` at the beginning of
+    // every one: SGDR's warm restarts. A `period` of 0 is taken as 1.
+    WarmRestart {
+        start: f32,
+        end: f32,
+        period: u32,
+    },
+}
+
+// The value `step` steps into a cosine annealing from `alpha_max` to
+// `alpha_min` over `max_steps`, and `alpha_min` after it.
+pub fn cosine_anneal(step: u32, max_steps: u32, alpha_max: f32, alpha_min: f32) -> f32 {
+    let progress = step.min(max_steps) as f32 / max_steps.max(1) as f32;
+    alpha_min + 0.5 * (alpha_max - alpha_min) * (1.0 + (std::f32::consts::PI * progress).cos())
+}
+
+impl DecaySchedule {
+    pub fn value(&self, step: u32) -> f32 {
+        match *self {
+            DecaySchedule::Constant(value) => value,
+            DecaySchedule::Linear {
+                start,
+                end,
+                decay_steps,
+            } => {
+                if step >= decay_steps {
+                    end
+                } else {
+                    start + (end - start) * step as f32 / decay_steps as f32
+                }
+            }
+            DecaySchedule::Exponential { start, decay } => start * decay.powf(step as f32),
+            DecaySchedule::Step {
+                start,
+                factor,
+                interval,
+            } => start * factor.powi((step / interval.max(1)) as i32),
+            DecaySchedule::Cosine { start, end, steps } => cosine_anneal(step, steps, start, end),
+            DecaySchedule::WarmRestart { start, end, period } => {
+                cosine_anneal(step % period.max(1), period, start, end)
+            }
+        }
+    }
+}
+
+// Parses a bare number as a constant, or `linear:start:end:decay_steps`,
+// `exponential:start:decay`, `step:start:factor:interval`,
+// `cosine:start:end:steps` or `restart:start:end:period`.
+impl FromStr for DecaySchedule {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<DecaySchedule, String> {
+        let parts: Vec<&str> = s.split(':').collect();
+        let float = |part: &str| {
+            part.parse::<f32>()
+                .map_err(|_| format!("Invalid number {:?} in {:?}", part, s))
+        };
+        let steps = |part: &str| {
+            part.parse::<u32>()
+                .ok()
+                .filter(|&steps| steps > 0)
+                .ok_or_else(|| format!("Invalid step count {:?} in {:?}", part, s))
+        };
+        match parts[..] {
+            [value] => Ok(DecaySchedule::Constant(float(value)?)),
+            ["linear", start, end, decay_steps] => Ok(DecaySchedule::Linear {
+                start: float(start)?,
+                end: float(end)?,
+                decay_steps: steps(decay_steps)?,
+            }),
+            ["exponential", start, decay] => Ok(DecaySchedule::Exponential {
+                start: float(start)?,
+                decay: float(decay)?,
+            }),
+            ["step", start, factor, interval] => Ok(DecaySchedule::Step {
+                start: float(start)?,
+                factor: float(factor)?,
+                interval: steps(interval)?,
+            }),
+            ["cosine", start, end, count] => Ok(DecaySchedule::Cosine {
+                start: float(start)?,
+                end: float(end)?,
+                steps: steps(count)?,
+            }),
+            ["restart", start, end, period] => Ok(DecaySchedule::WarmRestart {
+                start: float(start)?,
+                end: float(end)?,
+                period: steps(period)?,
+            }),
+            _ => Err(format!("Unknown decay schedule {:?}", s)),
+        }
+    }
+}
+
+impl Default for RLConfig {
+    fn default() -> Self {
+        RLConfig {
+            learning_rate: 0.1,
+            epsilon: DecaySchedule::Constant(0.01),
+            win_reward: 1.0,
+            draw_reward: -0.5,
+            loss_reward: -1.0,
+            symmetry: default_symmetry(),
+            gamma: default_gamma(),
+            lambda: 0.0,
+            n_step: default_n_step(),
+            replay_capacity: 0,
+            replay_batch_size: default_replay_batch_size(),
+            exploration: ExplorationStrategy::default(),
+            step_reward: 0.0,
+            win_speed_bonus: 0.0,
+            learn_from_exploration: false,
+            initial_value: default_initial_value(),
+            warmup_episodes: 0,
+            reward_clip: None,
+            alpha_schedule: None,
+            polyak_beta: 0.0,
+        }
+    }
+}
+
+impl RLConfig {
+    pub fn reward(&self, outcome: AgentOutcome) -> f32 {
+        match outcome {
+            AgentOutcome::Win => self.win_reward,
+            AgentOutcome::Loss => self.loss_reward,
+            AgentOutcome::Draw => self.draw_reward,
+        }
+    }
+
+    // `reward` for a game that ended on `board`, with the win speed bonus.
+    pub fn terminal_reward(&self, outcome: AgentOutcome, board: &Board) -> f32 {
+        match outcome {
+            AgentOutcome::Win => {
+                let empty = board.available_moves().count() as f32;
+                self.win_reward * (1.0 + self.win_speed_bonus * empty)
+            }
+            _ => self.reward(outcome),
+        }
+    }
+
+    // `reward` clipped to `reward_clip`, if there is one.
+    pub fn clip_reward(&self, reward: f32) -> f32 {
+        match self.reward_clip {
+            Some(clip) => reward.clamp(-clip, clip),
+            None => reward,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{play_game, RLAgent, RandomAgent};
+
+    #[test]
+    fn exploration_strategies_parse_and_print() {
+        for text in ["epsilon-greedy", "ucb:1.5", "softmax:0.2", "count:0.5"] {
+            let strategy: ExplorationStrategy = text.parse().unwrap();
+            assert_eq!(strategy.to_string(), text);
+        }
+        assert!(matches!("ucb:2".parse(), Ok(ExplorationStrategy::Ucb1(c)) if c == 2.0));
+        for text in ["ucb", "ucb:-1", "softmax:0", "greedy"] {
+            assert!(text.parse::<ExplorationStrategy>().is_err(), "{}", text);
+        }
+    }
+
+    #[test]
+    fn linear_epsilon_reaches_its_end_after_decay_steps_and_holds_there() {
+        let schedule: DecaySchedule = "linear:1.0:0.1:100".parse().unwrap();
+        assert_eq!(schedule.value(0), 1.0);
+        assert!((schedule.value(50) - 0.55).abs() < 1e-6);
+        assert!(schedule.value(99) > 0.1);
+        assert_eq!(schedule.value(100), 0.1);
+        assert_eq!(schedule.value(1_000_000), 0.1);
+        let steps: DecaySchedule = "step:0.8:0.5:10".parse().unwrap();
+        assert_eq!(steps.value(9), 0.8);
+        assert_eq!(steps.value(25), 0.2);
+        let exponential: DecaySchedule = "exponential:1.0:0.5".parse().unwrap();
+        assert_eq!(exponential.value(3), 0.125);
+        assert!("linear:1.0:0.1:0".parse::<DecaySchedule>().is_err());
+    }
+
+    #[test]
+    fn cosine_annealing_falls_from_its_start_and_warm_restarts_return_there() {
+        assert_eq!(cosine_anneal(0, 100, 0.5, 0.1), 0.5);
+        assert!((cosine_anneal(50, 100, 0.5, 0.1) - 0.3).abs() < 1e-6);
+        assert!((cosine_anneal(100, 100, 0.5, 0.1) - 0.1).abs() < 1e-6);
+        assert!((cosine_anneal(500, 100, 0.5, 0.1) - 0.1).abs() < 1e-6);
+        let restarts: DecaySchedule = "restart:0.5:0.1:100".parse().unwrap();
+        assert_eq!(restarts.value(200), 0.5);
+        assert!((restarts.value(150) - 0.3).abs() < 1e-6);
+        let cosine: DecaySchedule = "cosine:0.5:0.1:100".parse().unwrap();
+        assert_eq!(cosine.value(200), cosine_anneal(100, 100, 0.5, 0.1));
+        assert!("restart:0.5:0.1:0".parse::<DecaySchedule>().is_err());
+    }
+
+    #[test]
+    fn schedules_loaded_with_no_steps_play_rather_than_panic() {
+        // `FromStr` refuses these, but a policy file carries its config as
+        // it was written, edits and all.
+        let mut json = serde_json::to_value(RLConfig::default()).unwrap();
+        json["epsilon"] = serde_json::json!({
+            "WarmRestart": { "start": 0.5, "end": 0.1, "period": 0 }
+        });
+        json["alpha_schedule"] = serde_json::json!({
+            "Step": { "start": 0.5, "factor": 0.5, "interval": 0 }
+        });
+        let config: RLConfig = serde_json::from_value(json).unwrap();
+        assert_eq!(config.epsilon.value(7), 0.5);
+        assert_eq!(config.alpha_schedule.as_ref().unwrap().value(2), 0.125);
+        let mut agent = RLAgent::new(config, Some(1));
+        let mut random = RandomAgent::new(Some(2));
+        for _ in 0..20 {
+            play_game(&mut agent, &mut random).unwrap();
+        }
+    }
+}
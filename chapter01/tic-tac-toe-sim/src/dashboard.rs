@@ -0,0 +1,293 @@
+use std::collections::VecDeque;
+use std::io::{self, IsTerminal, Stderr, Write};
+use std::time::{Duration, Instant};
+
+use ratatui::backend::CrosstermBackend;
+use ratatui::layout::{Constraint, Layout};
+use ratatui::widgets::{Block, Borders, Gauge, Paragraph, Sparkline};
+use ratatui::{Frame, Terminal, TerminalOptions, Viewport};
+
+use crate::progress::format_duration;
+use crate::{Agent, Evaluation, PlayerMarker};
+
+// Rows the dashboard takes below the command line.
+const HEIGHT: u16 = 7;
+
+// Rates are over this many of the latest games.
+const WINDOW: usize = 1000;
+
+// The sparkline's scale: an evaluation where neither agent lost reaches it.
+const SPARK_MAX: u64 = 1000;
+
+// Redraws at most this often; drawing sums the value table, so it is kept
+// well below once a game.
+const REDRAW_INTERVAL: Duration = Duration::from_millis(200);
+
+// A ratatui view on stderr, redrawn in place while `Trainer` runs: a gauge of
+// games done with games per second and the time left; the first agent's win,
+// draw and loss rates over its latest games; its exploration rate and
+// value-table size; and a sparkline of how often the agents avoided losing at
+// each evaluation. It is drawn inline below the command rather than on the
+// alternate screen, so the last view stays on screen with training's output.
+// Like `Progress`, nothing is drawn when stderr isn't a terminal.
+pub struct Dashboard {
+    total: u64,
+    started: Instant,
+    drawn: Option<Instant>,
+    terminal: Option<Terminal<CrosstermBackend<Stderr>>>,
+    // Winners of the first agent's latest games, `None` for draws, as seen
+    // from its side.
+    recent: VecDeque<Option<PlayerMarker>>,
+}
+
+impl Dashboard {
+    pub fn new(total: u64) -> Self {
+        let terminal = io::stderr()
+            .is_terminal()
+            .then(|| {
+                Terminal::with_options(
+                    CrosstermBackend::new(io::stderr()),
+                    TerminalOptions {
+                        viewport: Viewport::Inline(HEIGHT),
+                    },
+                )
+                .ok()
+            })
+            .flatten();
+        let mut dashboard = Dashboard::undrawn(total);
+        dashboard.terminal = terminal;
+        dashboard
+    }
+
+    // A dashboard that keeps count but never draws.
+    fn undrawn(total: u64) -> Self {
+        Dashboard {
+            total,
+            started: Instant::now(),
+            drawn: None,
+            terminal: None,
+            recent: VecDeque::with_capacity(WINDOW),
+        }
+    }
+
+    // Records a finished game of the first agent: `winner` is `Some(X)` if
+    // the first agent won, `Some(O)` if it lost and `None` for a draw.
+    pub fn record(&mut self, winner: Option<PlayerMarker>) {
+        if self.recent.len() == WINDOW {
+            self.recent.pop_front();
+        }
+        self.recent.push_back(winner);
+    }
+
+    // Records that `done` games are finished, redrawing if it is due. The
+    // last update, with every game done, is always drawn and left on screen.
+    pub fn update(&mut self, done: u64, agent: &dyn Agent, evaluations: &[Evaluation]) {
+        let now = Instant::now();
+        let finished = done >= self.total;
+        if self.terminal.is_none()
+            || (!finished
+                && self
+                    .drawn
+                    .is_some_and(|drawn| now - drawn < REDRAW_INTERVAL))
+        {
+            return;
+        }
+        self.drawn = Some(now);
+        let elapsed = now - self.started;
+        let epsilon = agent.exploration_rate();
+        let table_size = agent.value_summary().map(|(size, _)| size);
+        let mut terminal = self.terminal.take().expect("checked above");
+        let drawn = terminal
+            .draw(|frame| self.render(frame, done, elapsed, epsilon, table_size, evaluations));
+        // A terminal that can't be drawn on is dropped, as `Progress` stops
+        // drawing when stderr isn't a terminal.
+        if drawn.is_ok() {
+            self.terminal = Some(terminal);
+        }
+    }
+
+    fn render(
+        &self,
+        frame: &mut Frame,
+        done: u64,
+        elapsed: Duration,
+        epsilon: Option<f32>,
+        table_size: Option<usize>,
+        evaluations: &[Evaluation],
+    ) {
+        let [progress, rates, agent, chart] = Layout::vertical([
+            Constraint::Length(1),
+            Constraint::Length(1),
+            Constraint::Length(1),
+            Constraint::Min(2),
+        ])
+        .areas(frame.area());
+        let rate = done as f64 / elapsed.as_secs_f64().max(1e-3);
+        let left = self.total.saturating_sub(done) as f64 / rate.max(1e-3);
+        frame.render_widget(
+            Gauge::default()
+                .ratio(done.min(self.total) as f64 / self.total.max(1) as f64)
+                .label(format!(
+                    "{}/{}  {:.0} games/s  ETA {}",
+                    done,
+                    self.total,
+                    rate,
+                    format_duration(Duration::from_secs_f64(left.min(1e7)))
+                )),
+            progress,
+        );
+        let games = self.recent.len().max(1) as f32;
+        let share = |winner: Option<PlayerMarker>| {
+            self.recent.iter().filter(|&&seen| seen == winner).count() as f32 / games
+        };
+        frame.render_widget(
+            Paragraph::new(format!(
+                "last {:<5} win {:.3}  draw {:.3}  loss {:.3}",
+                self.recent.len(),
+                share(Some(PlayerMarker::X)),
+                share(None),
+                share(Some(PlayerMarker::O))
+            )),
+            rates,
+        );
+        let mut details = Vec::new();
+        if let Some(epsilon) = epsilon {
+            details.push(format!("epsilon {:.3}", epsilon));
+        }
+        if let Some(size) = table_size {
+            details.push(format!("table {} positions", size));
+        }
+        frame.render_widget(Paragraph::new(details.join("  ")), agent);
+        let title = match evaluations.last() {
+            Some(last) => format!("not lost {:.3} after {}", not_lost(last), last.episode),
+            None => "not lost (no evaluations yet)".to_string(),
+        };
+        // The latest evaluations that fit, oldest on the left.
+        let shown = &evaluations[evaluations.len().saturating_sub(chart.width as usize)..];
+        frame.render_widget(
+            Sparkline::default()
+                .block(Block::default().borders(Borders::TOP).title(title))
+                .max(SPARK_MAX)
+                .data(shown.iter().map(|evaluation| {
+                    (not_lost(evaluation).clamp(0.0, 1.0) * SPARK_MAX as f32).round() as u64
+                })),
+            chart,
+        );
+    }
+}
+
+// Moves the cursor below the last view, so whatever is printed next doesn't
+// draw over it.
+impl Drop for Dashboard {
+    fn drop(&mut self) {
+        if let Some(terminal) = &mut self.terminal {
+            let bottom = terminal.get_frame().area().bottom();
+            let _ = terminal.set_cursor_position((0, bottom.saturating_sub(1)));
+            let _ = terminal.show_cursor();
+            let _ = writeln!(io::stderr());
+        }
+    }
+}
+
+// How often the two agents avoided losing at an evaluation, on average.
+fn not_lost(evaluation: &Evaluation) -> f32 {
+    1.0 - (evaluation.x_agent_loss_rate + evaluation.o_agent_loss_rate) / 2.0
+}
+
+#[cfg(test)]
+mod tests {
+    use ratatui::backend::TestBackend;
+
+    use super::*;
+
+    fn evaluation(episode: u32, loss_rate: f32) -> Evaluation {
+        Evaluation {
+            episode,
+            x_agent_win_rate: 0.0,
+            x_agent_loss_rate: loss_rate,
+            o_agent_win_rate: 0.0,
+            o_agent_loss_rate: loss_rate,
+        }
+    }
+
+    // The rows of `dashboard` drawn 60 cells wide with the given figures.
+    fn rows(
+        dashboard: &Dashboard,
+        done: u64,
+        elapsed: Duration,
+        epsilon: Option<f32>,
+        table_size: Option<usize>,
+        evaluations: &[Evaluation],
+    ) -> Vec<String> {
+        let mut terminal = Terminal::new(TestBackend::new(60, HEIGHT)).unwrap();
+        terminal
+            .draw(|frame| dashboard.render(frame, done, elapsed, epsilon, table_size, evaluations))
+            .unwrap();
+        let buffer = terminal.backend().buffer();
+        (0..HEIGHT)
+            .map(|y| {
+                (0..60)
+                    .map(|x| buffer[(x, y)].symbol())
+                    .collect::<String>()
+                    .trim_end()
+                    .to_string()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn the_dashboard_shows_rates_agent_and_evaluations() {
+        let mut dashboard = Dashboard::undrawn(1000);
+        for winner in [
+            Some(PlayerMarker::X),
+            Some(PlayerMarker::X),
+            None,
+            Some(PlayerMarker::O),
+        ] {
+            dashboard.record(winner);
+        }
+        let evaluations = [
+            evaluation(100, 1.0),
+            evaluation(200, 0.5),
+            evaluation(300, 0.0),
+        ];
+        let rows = rows(
+            &dashboard,
+            250,
+            Duration::from_secs(5),
+            Some(0.1),
+            Some(42),
+            &evaluations,
+        );
+        assert!(
+            rows[0].contains("250/1000  50 games/s  ETA 0:15"),
+            "{}",
+            rows[0]
+        );
+        assert_eq!(rows[1], "last 4     win 0.500  draw 0.250  loss 0.250");
+        assert_eq!(rows[2], "epsilon 0.100  table 42 positions");
+        assert!(rows[3].contains("not lost 1.000 after 300"), "{}", rows[3]);
+        // No bar for the evaluation where both lost, half height, full height.
+        assert_eq!(rows[4], "  █");
+        assert_eq!(rows[6], " ██");
+    }
+
+    #[test]
+    fn only_the_latest_games_are_counted() {
+        let mut dashboard = Dashboard::undrawn(5000);
+        for _ in 0..WINDOW {
+            dashboard.record(Some(PlayerMarker::O));
+        }
+        for _ in 0..WINDOW / 2 {
+            dashboard.record(Some(PlayerMarker::X));
+        }
+        let rows = rows(&dashboard, 1500, Duration::from_secs(1), None, None, &[]);
+        assert_eq!(rows[1], "last 1000  win 0.500  draw 0.000  loss 0.500");
+        assert_eq!(rows[2], "");
+        assert!(
+            rows[3].contains("not lost (no evaluations yet)"),
+            "{}",
+            rows[3]
+        );
+    }
+}
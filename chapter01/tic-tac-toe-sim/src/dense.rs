@@ -0,0 +1,149 @@
+use std::collections::HashMap;
+
+use crate::Board;
+
+// Every board's `ternary_index` is below this, 3^9.
+const BOARDS: usize = 19_683;
+
+// Storage for `RLAgent`'s afterstate values. A `HashMap` only holds the boards
+// that have been backed up; a `DenseTable` holds a slot for every board there
+// could be.
+pub trait PolicyTable: Clone + Default + Send + 'static {
+    // The value stored for `board`, if any.
+    fn value(&self, board: &Board) -> Option<f32>;
+
+    // The value stored for `board`, set to `default` first if it has none.
+    fn value_mut(&mut self, board: Board, default: f32) -> &mut f32;
+
+    fn len(&self) -> usize;
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    // Every stored board and its value, in no particular order.
+    fn entries(&self) -> impl Iterator<Item = (Board, f32)> + '_;
+}
+
+impl PolicyTable for HashMap<Board, f32> {
+    fn value(&self, board: &Board) -> Option<f32> {
+        self.get(board).copied()
+    }
+
+    fn value_mut(&mut self, board: Board, default: f32) -> &mut f32 {
+        self.entry(board).or_insert(default)
+    }
+
+    fn len(&self) -> usize {
+        HashMap::len(self)
+    }
+
+    fn entries(&self) -> impl Iterator<Item = (Board, f32)> + '_ {
+        self.iter().map(|(&board, &value)| (board, value))
+    }
+}
+
+// A value for each of the 3^9 boards in one `Vec`, indexed by the board's
+// base-3 encoding, so a lookup is an index rather than a hash. It takes 77 KB
+// however few boards have values; NaN marks a board without one, which
+// `RLAgent`'s finiteness checks keep from being stored.
+#[derive(Clone, Debug)]
+pub struct DenseTable {
+    values: Vec<f32>,
+    len: usize,
+}
+
+impl DenseTable {
+    pub fn new() -> Self {
+        DenseTable {
+            values: vec![f32::NAN; BOARDS],
+            len: 0,
+        }
+    }
+
+    // The table as a map, the form agents are saved in.
+    pub fn to_hash_map(&self) -> HashMap<Board, f32> {
+        self.entries().collect()
+    }
+}
+
+impl Default for DenseTable {
+    fn default() -> Self {
+        DenseTable::new()
+    }
+}
+
+impl From<&HashMap<Board, f32>> for DenseTable {
+    fn from(table: &HashMap<Board, f32>) -> Self {
+        let mut dense = DenseTable::new();
+        for (&board, &value) in table {
+            *dense.value_mut(board, value) = value;
+        }
+        dense
+    }
+}
+
+impl PolicyTable for DenseTable {
+    fn value(&self, board: &Board) -> Option<f32> {
+        let value = self.values[board.ternary_index()];
+        (!value.is_nan()).then_some(value)
+    }
+
+    fn value_mut(&mut self, board: Board, default: f32) -> &mut f32 {
+        let value = &mut self.values[board.ternary_index()];
+        if value.is_nan() {
+            *value = default;
+            self.len += 1;
+        }
+        value
+    }
+
+    fn len(&self) -> usize {
+        self.len
+    }
+
+    fn entries(&self) -> impl Iterator<Item = (Board, f32)> + '_ {
+        self.values
+            .iter()
+            .enumerate()
+            .filter(|(_, value)| !value.is_nan())
+            .map(|(index, &value)| (Board::from_ternary_index(index), value))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{play_game, DecaySchedule, RLAgent, RLConfig};
+
+    #[test]
+    fn every_board_has_its_own_index() {
+        let mut seen = vec![false; BOARDS];
+        for board in crate::legal_positions() {
+            let index = board.ternary_index();
+            assert!(!seen[index]);
+            seen[index] = true;
+            assert_eq!(Board::from_ternary_index(index), board);
+        }
+        let corners: Board = "X.......O".parse().unwrap();
+        assert_eq!(corners.ternary_index(), 1 + 2 * 6561);
+    }
+
+    #[test]
+    fn dense_and_hashed_tables_learn_the_same_values() {
+        let config = RLConfig {
+            epsilon: DecaySchedule::Constant(0.1),
+            ..RLConfig::default()
+        };
+        let mut hashed = RLAgent::new(config.clone(), Some(3));
+        let mut dense = RLAgent::with_table(DenseTable::new(), config, Some(3));
+        let mut hashed_opponent = RLAgent::new(RLConfig::default(), Some(4));
+        let mut dense_opponent = RLAgent::new(RLConfig::default(), Some(4));
+        for _ in 0..300 {
+            play_game(&mut hashed, &mut hashed_opponent).unwrap();
+            play_game(&mut dense, &mut dense_opponent).unwrap();
+        }
+        assert_eq!(dense.table().len(), hashed.q_table().len());
+        assert_eq!(&dense.table().to_hash_map(), hashed.q_table());
+    }
+}
@@ -0,0 +1,251 @@
+use rand::rngs::SmallRng;
+use rand::{Rng, RngCore};
+use rl_core::{seeded_rng, Afterstates, Environment, Policy, Step};
+
+use crate::{Agent, AgentOutcome, Board, PlayerMarker};
+
+// Variations on the rules of `TicTacToeEnv`, to see how well learning holds
+// up when the game isn't the one the agents were written for. The default is
+// the ordinary game.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Rules {
+    // Misère play: whoever completes three in a row loses.
+    pub misere: bool,
+    // The chance that a move, by either side, lands on a random empty cell
+    // instead of the one chosen.
+    pub fault_rate: f32,
+}
+
+// Tic-tac-toe as a single-agent environment: the learner plays `player`
+// against a fixed opponent, which moves inside `reset` and `step`, so every
+// state the learner sees is its own turn. Rewards are 1 for a win, -1 for a
+// loss and 0 otherwise. The opponent is told each game's outcome, as in
+// `play_game`.
+pub struct TicTacToeEnv {
+    opponent: Box<dyn Agent>,
+    player: PlayerMarker,
+    board: Board,
+    rules: Rules,
+    // Picks faulty moves' cells; only drawn from when `rules.fault_rate` is
+    // above 0, so the ordinary game is unchanged by it.
+    rng: SmallRng,
+}
+
+impl TicTacToeEnv {
+    pub fn new(opponent: Box<dyn Agent>, player: PlayerMarker) -> Self {
+        TicTacToeEnv {
+            opponent,
+            player,
+            board: Board::new(),
+            rules: Rules::default(),
+            rng: seeded_rng(Some(0)),
+        }
+    }
+
+    // The game played by `rules`, with faulty moves drawn from `seed`.
+    pub fn with_rules(mut self, rules: Rules, seed: Option<u64>) -> Self {
+        self.rules = rules;
+        self.rng = seeded_rng(seed);
+        self
+    }
+
+    pub fn opponent_mut(&mut self) -> &mut dyn Agent {
+        self.opponent.as_mut()
+    }
+
+    // Switches the learner to `player` from the next `reset`.
+    pub fn set_player(&mut self, player: PlayerMarker) {
+        self.player = player;
+    }
+
+    // Marks `index` for `player`, or a random empty cell at the rules' fault
+    // rate, and returns the learner's reward if that ends the game.
+    fn play(&mut self, index: usize, player: PlayerMarker) -> Option<f64> {
+        let index =
+            if self.rules.fault_rate > 0.0 && self.rng.random::<f32>() < self.rules.fault_rate {
+                let available: Vec<usize> = self.board.available_moves().collect();
+                available[self.rng.next_u32() as usize % available.len()]
+            } else {
+                index
+            };
+        self.board.set(index, player);
+        if self.board.check_winner(player) {
+            let learner_won = (player == self.player) != self.rules.misere;
+            Some(if learner_won { 1.0 } else { -1.0 })
+        } else if self.board.is_draw() {
+            Some(0.0)
+        } else {
+            None
+        }
+    }
+
+    fn finish(&mut self, reward: f64) -> Step<Board> {
+        let opponent_outcome = match reward {
+            r if r > 0.0 => AgentOutcome::Loss,
+            r if r < 0.0 => AgentOutcome::Win,
+            _ => AgentOutcome::Draw,
+        };
+        self.opponent.observe_result(opponent_outcome, &self.board);
+        Step {
+            state: self.board,
+            reward,
+            done: true,
+        }
+    }
+}
+
+impl Environment for TicTacToeEnv {
+    type State = Board;
+    type Action = usize;
+
+    fn reset(&mut self) -> Board {
+        self.board = Board::new();
+        if self.player == PlayerMarker::O {
+            let index = self.opponent.choose_move(&self.board, PlayerMarker::X);
+            self.play(index, PlayerMarker::X);
+        }
+        self.board
+    }
+
+    fn step(&mut self, action: &usize) -> Step<Board> {
+        if let Some(reward) = self.play(*action, self.player) {
+            return self.finish(reward);
+        }
+        let opponent = self.player.opposite();
+        let index = self.opponent.choose_move(&self.board, opponent);
+        if let Some(reward) = self.play(index, opponent) {
+            return self.finish(reward);
+        }
+        Step {
+            state: self.board,
+            reward: 0.0,
+            done: false,
+        }
+    }
+
+    fn actions(&self, state: &Board) -> Vec<usize> {
+        state.available_moves().collect()
+    }
+}
+
+// The board after the learner's mark and before the opponent's reply, as in
+// Section 1.5, so rl-core's `AfterstateValueAgent` can learn the game. With
+// faulty moves this is the board the learner aimed for, not always the one
+// it got.
+impl Afterstates for TicTacToeEnv {
+    fn afterstates(&self, state: &Board) -> Vec<(usize, Board)> {
+        state
+            .available_moves()
+            .map(|index| (index, state.with_move(index, self.player)))
+            .collect()
+    }
+}
+
+// Any `Agent` can act in the environment: it is asked for moves as the side
+// to move and told the outcome when the game ends.
+impl<'a> Policy<TicTacToeEnv> for dyn Agent + 'a {
+    fn select_action(&mut self, _env: &TicTacToeEnv, state: &Board) -> usize {
+        self.choose_move(state, state.player_to_move())
+    }
+
+    fn observe(&mut self, _state: &Board, _action: &usize, step: &Step<Board>) {
+        if step.done {
+            let outcome = match step.reward {
+                r if r > 0.0 => AgentOutcome::Win,
+                r if r < 0.0 => AgentOutcome::Loss,
+                _ => AgentOutcome::Draw,
+            };
+            self.observe_result(outcome, &step.state);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{MinimaxAgent, RandomAgent};
+    use rl_core::{run_episode, AfterstateValueAgent};
+
+    #[test]
+    fn opponent_moves_first_when_the_learner_plays_o() {
+        let mut env = TicTacToeEnv::new(Box::new(RandomAgent::new(Some(1))), PlayerMarker::O);
+        let start = env.reset();
+        assert_eq!(start.available_moves().count(), 8);
+        assert_eq!(start.player_to_move(), PlayerMarker::O);
+    }
+
+    #[test]
+    fn random_play_never_beats_minimax() {
+        let mut env = TicTacToeEnv::new(Box::new(MinimaxAgent::new()), PlayerMarker::X);
+        let learner: &mut dyn Agent = &mut RandomAgent::new(Some(2));
+        for _ in 0..50 {
+            let episode = run_episode(&mut env, learner, None);
+            assert!(episode.terminated);
+            assert!(episode.total_reward() <= 0.0);
+            assert_eq!(episode.states.len(), episode.len() + 1);
+        }
+    }
+
+    #[test]
+    fn afterstate_learner_beats_random_play() {
+        let mut env = TicTacToeEnv::new(Box::new(RandomAgent::new(Some(3))), PlayerMarker::X);
+        let mut learner = AfterstateValueAgent::new(0.1, 0.1, Some(4));
+        for _ in 0..5000 {
+            run_episode(&mut env, &mut learner, None);
+        }
+        learner.freeze();
+        let losses = (0..500)
+            .filter(|_| run_episode(&mut env, &mut learner, None).total_reward() < 0.0)
+            .count();
+        assert!(losses < 25, "lost {} of 500", losses);
+    }
+
+    #[test]
+    fn completing_a_row_loses_under_misere_rules() {
+        let rules = Rules {
+            misere: true,
+            fault_rate: 0.0,
+        };
+        let mut env = TicTacToeEnv::new(Box::new(RandomAgent::new(Some(5))), PlayerMarker::X)
+            .with_rules(rules, Some(6));
+        let learner: &mut dyn Agent = &mut RandomAgent::new(Some(7));
+        let (mut rows, mut lost) = (0, 0);
+        for _ in 0..200 {
+            let episode = run_episode(&mut env, learner, None);
+            let last = episode.states.last().unwrap();
+            if last.check_winner(PlayerMarker::X) {
+                rows += 1;
+                lost += (episode.total_reward() == -1.0) as u32;
+            } else if last.check_winner(PlayerMarker::O) {
+                assert_eq!(episode.total_reward(), 1.0);
+            }
+        }
+        assert!(rows > 0);
+        assert_eq!(lost, rows);
+    }
+
+    #[test]
+    fn faulty_moves_land_off_the_chosen_cell() {
+        let missed = |fault_rate| {
+            let rules = Rules {
+                misere: false,
+                fault_rate,
+            };
+            let mut env = TicTacToeEnv::new(Box::new(RandomAgent::new(Some(8))), PlayerMarker::X)
+                .with_rules(rules, Some(9));
+            let learner: &mut dyn Agent = &mut RandomAgent::new(Some(10));
+            (0..100)
+                .map(|_| {
+                    let episode = run_episode(&mut env, learner, None);
+                    (0..episode.len())
+                        .filter(|&t| {
+                            episode.states[t + 1].cell(episode.actions[t]) != Some(PlayerMarker::X)
+                        })
+                        .count()
+                })
+                .sum::<usize>()
+        };
+        assert_eq!(missed(0.0), 0);
+        assert!(missed(0.5) > 50);
+    }
+}
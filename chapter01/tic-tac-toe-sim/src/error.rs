@@ -0,0 +1,41 @@
+use std::error::Error;
+use std::fmt;
+use std::io;
+
+use crate::PlayerMarker;
+
+// Why a game couldn't be played to the end.
+#[derive(Debug)]
+pub enum GameError {
+    // An agent was asked to move on a full board or after the game was won.
+    NoMoves,
+    // `player` chose `index`, which is not an empty cell.
+    IllegalMove { player: PlayerMarker, index: usize },
+    // A human's or remote agent's move couldn't be read, or their input was
+    // closed.
+    Input(io::Error),
+}
+
+impl fmt::Display for GameError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            GameError::NoMoves => write!(f, "asked for a move with none left"),
+            GameError::IllegalMove { player, index } => write!(
+                f,
+                "{} chose cell {}, which is not empty",
+                PlayerMarker::player_char(*player),
+                index + 1
+            ),
+            GameError::Input(error) => write!(f, "could not read a move: {}", error),
+        }
+    }
+}
+
+impl Error for GameError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            GameError::Input(error) => Some(error),
+            _ => None,
+        }
+    }
+}
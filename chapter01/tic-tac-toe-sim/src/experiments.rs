@@ -0,0 +1,518 @@
+use std::collections::VecDeque;
+use std::fmt;
+use std::str::FromStr;
+
+use rl_core::{derive_seed, mean_and_std_error, paired_t_test, run_episode};
+
+use crate::game::counted;
+use crate::{
+    play_game, play_game_with_transcript, Agent, GameResult, HeuristicAgent, MatchSummary,
+    MinimaxAgent, PlayerMarker, RLAgent, RLConfig, RandomAgent, Rules, TicTacToeEnv,
+};
+
+// Trains `agent` as O against a random X for `training_games`, then returns the
+// fraction of the following `eval_games` that it wins. `seed` seeds the random
+// opponent.
+pub fn win_rate_vs_random(
+    agent: &mut dyn Agent,
+    training_games: u32,
+    eval_games: u32,
+    seed: Option<u64>,
+) -> f32 {
+    let mut env = TicTacToeEnv::new(Box::new(RandomAgent::new(seed)), PlayerMarker::O);
+    for _ in 0..training_games {
+        run_episode(&mut env, agent, None);
+    }
+    let wins = (0..eval_games)
+        .filter(|_| run_episode(&mut env, agent, None).total_reward() > 0.0)
+        .count();
+    wins as f32 / eval_games as f32
+}
+
+// How often and how quickly an agent wins as O against a random X.
+#[derive(Clone, Copy, Debug)]
+pub struct WinSpeed {
+    // 0 when there were no evaluation games.
+    pub win_rate: f32,
+    // Moves by both sides in the games won; `None` if none were.
+    pub moves_per_win: Option<f32>,
+}
+
+// `win_rate_vs_random`, but with learning off for the `eval_games`, scoring
+// the greedy policy, and counting how many moves the wins took.
+pub fn win_speed_vs_random(
+    agent: &mut dyn Agent,
+    training_games: u32,
+    eval_games: u32,
+    seed: Option<u64>,
+) -> WinSpeed {
+    let mut env = TicTacToeEnv::new(Box::new(RandomAgent::new(seed)), PlayerMarker::O);
+    for _ in 0..training_games {
+        run_episode(&mut env, agent, None);
+    }
+    agent.freeze();
+    let (mut wins, mut moves) = (0, 0);
+    for _ in 0..eval_games {
+        let episode = run_episode(&mut env, agent, None);
+        if episode.total_reward() > 0.0 {
+            let board = episode.states.last().expect("episodes start with a state");
+            wins += 1;
+            moves += 9 - board.available_moves().count();
+        }
+    }
+    agent.unfreeze();
+    WinSpeed {
+        win_rate: wins as f32 / eval_games.max(1) as f32,
+        moves_per_win: (wins > 0).then(|| moves as f32 / wins as f32),
+    }
+}
+
+// Two rl configurations compared by `ab_test`: each one's greedy win rate as
+// O against random play for every seed, the mean and standard deviation of
+// those, and the paired t-test's p-value for the means being the same.
+#[derive(Clone, Debug)]
+pub struct ABTestResult {
+    pub win_rates_a: Vec<f64>,
+    pub win_rates_b: Vec<f64>,
+    pub mean_a: f64,
+    pub mean_b: f64,
+    pub std_a: f64,
+    pub std_b: f64,
+    pub p_value: f64,
+}
+
+// Trains an rl agent with each of `config_a` and `config_b` from each of
+// `n_seeds` seeds derived from `seed`, as O against random play for
+// `train_episodes` games, and scores its greedy policy over `eval_episodes`.
+// Both configurations meet the same random opponent for each seed, so their
+// win rates pair up for the t-test and luck in the draw of a seed cancels.
+// Without a `seed` every run draws its own, and the pairs share nothing.
+pub fn ab_test(
+    config_a: &RLConfig,
+    config_b: &RLConfig,
+    n_seeds: u32,
+    train_episodes: u32,
+    eval_episodes: u32,
+    seed: Option<u64>,
+) -> ABTestResult {
+    let win_rates = |config: &RLConfig| -> Vec<f64> {
+        (0..n_seeds as u64)
+            .map(|i| {
+                let seed = derive_seed(seed, 2 * i);
+                let mut agent = RLAgent::new(config.clone(), seed);
+                let opponent = derive_seed(seed, 1);
+                let speed =
+                    win_speed_vs_random(&mut agent, train_episodes, eval_episodes, opponent);
+                speed.win_rate as f64
+            })
+            .collect()
+    };
+    let (win_rates_a, win_rates_b) = (win_rates(config_a), win_rates(config_b));
+    // The sample standard deviation, from the standard error of the mean.
+    let spread = |rates: &[f64]| {
+        let (mean, std_error) = mean_and_std_error(rates);
+        (mean, std_error * (rates.len() as f64).sqrt())
+    };
+    let ((mean_a, std_a), (mean_b, std_b)) = (spread(&win_rates_a), spread(&win_rates_b));
+    let (_, p_value) = paired_t_test(&win_rates_a, &win_rates_b);
+    ABTestResult {
+        win_rates_a,
+        win_rates_b,
+        mean_a,
+        mean_b,
+        std_a,
+        std_b,
+        p_value,
+    }
+}
+
+impl fmt::Display for ABTestResult {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(
+            f,
+            "Greedy win rate as O against random play over {} seeds:",
+            self.win_rates_a.len()
+        )?;
+        writeln!(
+            f,
+            "A: {:.3} (standard deviation {:.3})",
+            self.mean_a, self.std_a
+        )?;
+        writeln!(
+            f,
+            "B: {:.3} (standard deviation {:.3})",
+            self.mean_b, self.std_b
+        )?;
+        write!(f, "Paired t-test: p = {:.4}", self.p_value)
+    }
+}
+
+// A saved policy's share of wins that `--eval-only` needs against random play
+// to pass.
+pub const EVAL_PASS_RATE: f32 = 0.8;
+
+// How a frozen agent playing `side` fared against each of the standard
+// opponents, from `evaluate_policy`.
+#[derive(Clone, Debug)]
+pub struct PolicyEvaluation {
+    pub side: PlayerMarker,
+    // Random, heuristic and minimax play, by name, with the games against each.
+    pub opponents: Vec<(&'static str, MatchSummary)>,
+}
+
+impl PolicyEvaluation {
+    // The agent's (wins, draws, losses) in `summary` as shares of its games.
+    pub fn rates(&self, summary: &MatchSummary) -> (f32, f32, f32) {
+        let (wins, losses) = match self.side {
+            PlayerMarker::X => (summary.x_wins, summary.o_wins),
+            PlayerMarker::O => (summary.o_wins, summary.x_wins),
+        };
+        let games = summary.games().max(1) as f32;
+        (
+            wins as f32 / games,
+            summary.draws as f32 / games,
+            losses as f32 / games,
+        )
+    }
+
+    // The agent's share of wins against random play.
+    pub fn win_rate_vs_random(&self) -> f32 {
+        self.opponents
+            .iter()
+            .find(|(name, _)| *name == "random")
+            .map_or(0.0, |(_, summary)| self.rates(summary).0)
+    }
+
+    pub fn passed(&self) -> bool {
+        self.win_rate_vs_random() > EVAL_PASS_RATE
+    }
+}
+
+// Plays `games` games as `side` against each of random, heuristic and
+// minimax play with `agent` frozen, so it neither explores nor learns and
+// the games score its greedy policy as it was saved. The opponents are
+// seeded from `seed`. The agent is unfrozen again afterwards.
+pub fn evaluate_policy(
+    agent: &mut dyn Agent,
+    side: PlayerMarker,
+    games: u32,
+    seed: Option<u64>,
+) -> PolicyEvaluation {
+    agent.freeze();
+    let opponents: Vec<(&'static str, Box<dyn Agent>)> = vec![
+        ("random", Box::new(RandomAgent::new(derive_seed(seed, 1)))),
+        (
+            "heuristic",
+            Box::new(HeuristicAgent::new(derive_seed(seed, 2))),
+        ),
+        ("minimax", Box::new(MinimaxAgent::new())),
+    ];
+    let opponents = opponents
+        .into_iter()
+        .map(|(name, mut opponent)| {
+            let mut summary = MatchSummary::default();
+            for _ in 0..games {
+                let game = match side {
+                    PlayerMarker::X => play_game_with_transcript(agent, opponent.as_mut()),
+                    PlayerMarker::O => play_game_with_transcript(opponent.as_mut(), agent),
+                };
+                match game {
+                    Ok((_, transcript)) => summary.record(&transcript),
+                    Err(error) => eprintln!("Warning: abandoned a game: {}", error),
+                }
+            }
+            (name, summary)
+        })
+        .collect();
+    agent.unfreeze();
+    PolicyEvaluation { side, opponents }
+}
+
+impl fmt::Display for PolicyEvaluation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(
+            f,
+            "Greedy play as {:?}, without learning or exploring:",
+            self.side
+        )?;
+        writeln!(
+            f,
+            "{:<10} {:>6} {:>6} {:>6} {:>6}",
+            "opponent", "games", "win", "draw", "loss"
+        )?;
+        for (name, summary) in &self.opponents {
+            let (win, draw, loss) = self.rates(summary);
+            writeln!(
+                f,
+                "{:<10} {:>6} {:>6.3} {:>6.3} {:>6.3}",
+                name,
+                summary.games(),
+                win,
+                draw,
+                loss
+            )?;
+        }
+        write!(
+            f,
+            "{}: won {:.3} against random play, needing more than {:.3}",
+            if self.passed() { "Passed" } else { "Failed" },
+            self.win_rate_vs_random(),
+            EVAL_PASS_RATE
+        )
+    }
+}
+
+// The sides a learner trained against a fixed opponent plays. X's afterstates
+// never coincide with O's, so a shared table holds the same positions as two
+// separate ones; sharing keeps one agent, with one exploration schedule, and
+// one policy file for both sides.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Roles {
+    X,
+    O,
+    // X and O in alternate games, with one agent and value table.
+    Shared,
+    // X and O in alternate games, with an agent and table for each side.
+    Separate,
+}
+
+impl Roles {
+    pub fn all() -> [Roles; 4] {
+        [Roles::X, Roles::O, Roles::Shared, Roles::Separate]
+    }
+}
+
+impl fmt::Display for Roles {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Roles::X => "x",
+            Roles::O => "o",
+            Roles::Shared => "shared",
+            Roles::Separate => "separate",
+        })
+    }
+}
+
+impl FromStr for Roles {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        Roles::all()
+            .into_iter()
+            .find(|roles| roles.to_string() == s)
+            .ok_or_else(|| format!("unknown roles '{}' (expected x, o, shared or separate)", s))
+    }
+}
+
+// A learner's win rates against random play on each side.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RoleWinRates {
+    pub x: f32,
+    pub o: f32,
+}
+
+impl RoleWinRates {
+    // The win rate on the sides `roles` trains, averaged when it trains both.
+    pub fn trained(&self, roles: Roles) -> f32 {
+        match roles {
+            Roles::X => self.x,
+            Roles::O => self.o,
+            Roles::Shared | Roles::Separate => (self.x + self.o) / 2.0,
+        }
+    }
+}
+
+// Training against a random opponent for `training_games` games on the sides
+// `roles` gives, then `eval_games` games on each side with learning and
+// exploration off, all in a `TicTacToeEnv` played by `rules`.
+#[derive(Clone, Copy, Debug)]
+pub struct RoleTraining {
+    pub roles: Roles,
+    pub training_games: u32,
+    pub eval_games: u32,
+    pub rules: Rules,
+}
+
+impl RoleTraining {
+    // Trains learners made by `agent` from a seed, `seed` for the first and
+    // `seed + 2` for O's with separate roles, against a random opponent seeded
+    // with `seed + 1`, with any faulty moves drawn from `seed + 3`, and
+    // returns their win rates as X and as O. A learner trained on one side is
+    // still scored on the other.
+    pub fn run<A: Agent>(
+        &self,
+        agent: impl Fn(Option<u64>) -> A,
+        seed: Option<u64>,
+    ) -> RoleWinRates {
+        let mut learner = agent(seed);
+        let mut o_learner =
+            (self.roles == Roles::Separate).then(|| agent(seed.map(|seed| seed.wrapping_add(2))));
+        let random = RandomAgent::new(seed.map(|seed| seed.wrapping_add(1)));
+        let mut env = TicTacToeEnv::new(Box::new(random), PlayerMarker::X)
+            .with_rules(self.rules, seed.map(|seed| seed.wrapping_add(3)));
+        for game in 0..self.training_games {
+            let side = match self.roles {
+                Roles::X => PlayerMarker::X,
+                Roles::O => PlayerMarker::O,
+                Roles::Shared | Roles::Separate if game % 2 == 0 => PlayerMarker::X,
+                Roles::Shared | Roles::Separate => PlayerMarker::O,
+            };
+            let player: &mut dyn Agent = match (&mut o_learner, side) {
+                (Some(o_learner), PlayerMarker::O) => o_learner,
+                _ => &mut learner,
+            };
+            env.set_player(side);
+            run_episode(&mut env, player, None);
+        }
+        learner.freeze();
+        env.set_player(PlayerMarker::X);
+        let learner: &mut dyn Agent = &mut learner;
+        let x_wins = (0..self.eval_games)
+            .filter(|_| run_episode(&mut env, learner, None).total_reward() > 0.0)
+            .count();
+        let o_player: &mut dyn Agent = match &mut o_learner {
+            Some(o_learner) => {
+                o_learner.freeze();
+                o_learner
+            }
+            None => learner,
+        };
+        env.set_player(PlayerMarker::O);
+        let o_wins = (0..self.eval_games)
+            .filter(|_| run_episode(&mut env, o_player, None).total_reward() > 0.0)
+            .count();
+        let games = self.eval_games.max(1) as f32;
+        RoleWinRates {
+            x: x_wins as f32 / games,
+            o: o_wins as f32 / games,
+        }
+    }
+}
+
+// Trains `agent` as O against a random X and returns the variance of its win
+// rate over a sliding window of `window` games. `None` if `episodes` never
+// fills a window.
+pub fn rolling_win_rate_variance(
+    agent: &mut dyn Agent,
+    episodes: u32,
+    window: usize,
+    seed: Option<u64>,
+) -> Option<f32> {
+    let mut opponent = RandomAgent::new(seed);
+    let mut recent = VecDeque::with_capacity(window);
+    let mut recent_wins = 0;
+    let mut rates = Vec::new();
+    for episode in 0..episodes {
+        let won = matches!(
+            counted(play_game(&mut opponent, agent)),
+            Some(GameResult::OWin)
+        );
+        if cfg!(debug_assertions) && episode % 1000 == 0 {
+            agent.assert_finite_q_values();
+        }
+        recent.push_back(won);
+        recent_wins += won as usize;
+        if recent.len() > window && recent.pop_front() == Some(true) {
+            recent_wins -= 1;
+        }
+        if window > 0 && recent.len() == window {
+            rates.push(recent_wins as f32 / window as f32);
+        }
+    }
+    if rates.is_empty() {
+        return None;
+    }
+    let mean = rates.iter().sum::<f32>() / rates.len() as f32;
+    Some(rates.iter().map(|rate| (rate - mean).powi(2)).sum::<f32>() / rates.len() as f32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::DecaySchedule;
+
+    #[test]
+    fn alternating_roles_learn_both_sides() {
+        let training = |roles| RoleTraining {
+            roles,
+            training_games: 4000,
+            eval_games: 500,
+            rules: Rules::default(),
+        };
+        let agent = |seed| RLAgent::new(RLConfig::default(), seed);
+        let o_only = training(Roles::O).run(agent, Some(1));
+        let shared = training(Roles::Shared).run(agent, Some(1));
+        let separate = training(Roles::Separate).run(agent, Some(1));
+        // Trained only as O, the agent plays X from an empty table.
+        assert!(shared.x > o_only.x + 0.05, "{:?} vs {:?}", shared, o_only);
+        assert!(
+            separate.x > o_only.x + 0.05,
+            "{:?} vs {:?}",
+            separate,
+            o_only
+        );
+        assert!(
+            shared.o > 0.5 && separate.o > 0.5,
+            "{:?} {:?}",
+            shared,
+            separate
+        );
+        assert_eq!(shared.trained(Roles::Shared), (shared.x + shared.o) / 2.0);
+        assert_eq!("separate".parse(), Ok(Roles::Separate));
+    }
+
+    #[test]
+    fn win_statistics_without_enough_games_are_missing_rather_than_nan() {
+        let mut agent = RLAgent::new(RLConfig::default(), Some(1));
+        assert_eq!(rolling_win_rate_variance(&mut agent, 5, 10, Some(2)), None);
+        assert_eq!(rolling_win_rate_variance(&mut agent, 5, 0, Some(2)), None);
+        let variance = rolling_win_rate_variance(&mut agent, 20, 10, Some(2)).unwrap();
+        assert!(variance.is_finite());
+        let speed = win_speed_vs_random(&mut agent, 0, 0, Some(3));
+        assert_eq!(speed.win_rate, 0.0);
+        assert_eq!(speed.moves_per_win, None);
+        let speed = win_speed_vs_random(&mut agent, 200, 50, Some(3));
+        assert!(speed.moves_per_win.unwrap() >= 5.0);
+    }
+
+    #[test]
+    fn ab_tests_tell_a_real_difference_from_none() {
+        let config = RLConfig {
+            epsilon: DecaySchedule::Constant(0.1),
+            ..RLConfig::default()
+        };
+        let same = ab_test(&config, &config, 5, 300, 200, Some(1));
+        assert_eq!(same.win_rates_a, same.win_rates_b);
+        assert_eq!(same.p_value, 1.0);
+        // Rewarded for losing, an agent learns to lose.
+        let losing = RLConfig {
+            win_reward: -1.0,
+            loss_reward: 1.0,
+            ..config.clone()
+        };
+        let result = ab_test(&config, &losing, 5, 300, 200, Some(1));
+        assert_eq!(result.win_rates_a, same.win_rates_a);
+        assert!(result.mean_a > result.mean_b, "{}", result);
+        assert!(result.p_value < 0.01, "{}", result);
+        assert!(result.to_string().contains("over 5 seeds"));
+    }
+
+    #[test]
+    fn evaluating_a_policy_leaves_it_as_it_was() {
+        let mut trained = RLAgent::new(RLConfig::default(), Some(5));
+        win_speed_vs_random(&mut trained, 5000, 0, Some(6));
+        let evaluation = evaluate_policy(&mut trained, PlayerMarker::O, 300, Some(7));
+        let names: Vec<&str> = evaluation.opponents.iter().map(|(name, _)| *name).collect();
+        assert_eq!(names, ["random", "heuristic", "minimax"]);
+        assert!(evaluation.passed(), "{}", evaluation);
+        let (_, minimax) = &evaluation.opponents[2];
+        assert_eq!(evaluation.rates(minimax).0, 0.0);
+        // Nothing was learnt from the games, so they replay exactly.
+        assert!(trained.is_learning());
+        let again = evaluate_policy(&mut trained, PlayerMarker::O, 300, Some(7));
+        assert_eq!(again.to_string(), evaluation.to_string());
+        let mut untrained = RLAgent::new(RLConfig::default(), Some(5));
+        let evaluation = evaluate_policy(&mut untrained, PlayerMarker::O, 300, Some(7));
+        assert!(!evaluation.passed(), "{}", evaluation);
+    }
+}
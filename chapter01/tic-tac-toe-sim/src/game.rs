@@ -0,0 +1,479 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+use rl_core::Episode;
+
+use crate::{Agent, AgentOutcome, Board, GameError, PlayerMarker};
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum GameResult {
+    XWin,
+    OWin,
+    Draw,
+}
+
+impl fmt::Display for GameResult {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            GameResult::XWin => write!(f, "X wins"),
+            GameResult::OWin => write!(f, "O wins"),
+            GameResult::Draw => write!(f, "Draw"),
+        }
+    }
+}
+
+pub struct EpisodeRecord {
+    episode: u32,
+    result: GameResult,
+    x_is_rl: bool,
+}
+
+// Collects game outcomes so a learning curve can be written out afterwards.
+pub struct Statistics {
+    window_size: usize,
+    records: Vec<EpisodeRecord>,
+}
+
+impl Statistics {
+    pub fn new(window_size: usize) -> Self {
+        Statistics {
+            window_size,
+            records: Vec::new(),
+        }
+    }
+
+    pub fn record(&mut self, result: GameResult, x_is_rl: bool) {
+        self.records.push(EpisodeRecord {
+            episode: self.records.len() as u32 + 1,
+            result,
+            x_is_rl,
+        });
+    }
+
+    // Writes one row per episode with outcome counts over the last
+    // `window_size` episodes (fewer at the start), X's win rate over them, and
+    // whether the learning agent played X (1) or O (0) in that episode.
+    pub fn write_csv(&self, path: &Path) -> io::Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        writeln!(writer, "episode,x_wins,o_wins,draws,x_win_rate,x_is_rl")?;
+        let mut counts = [0u32; 3];
+        for (i, record) in self.records.iter().enumerate() {
+            counts[record.result as usize] += 1;
+            if i >= self.window_size {
+                counts[self.records[i - self.window_size].result as usize] -= 1;
+            }
+            let window = (i + 1).min(self.window_size) as f32;
+            writeln!(
+                writer,
+                "{},{},{},{},{},{}",
+                record.episode,
+                counts[GameResult::XWin as usize],
+                counts[GameResult::OWin as usize],
+                counts[GameResult::Draw as usize],
+                counts[GameResult::XWin as usize] as f32 / window,
+                record.x_is_rl as u8
+            )?;
+        }
+        writer.flush()
+    }
+}
+
+// Writes the mean Q-value of the afterstates `player` produced, grouped by each
+// cell `player` holds in them, as `cell_index,mean_value,visit_count` rows.
+// With canonical keys, symmetric cells share their values.
+pub fn export_value_heatmap(
+    q_table: &HashMap<Board, f32>,
+    player: PlayerMarker,
+    path: &Path,
+) -> io::Result<()> {
+    let mut totals = [(0.0f32, 0u32); 9];
+    for (&board, &value) in q_table {
+        let cells: Vec<Option<PlayerMarker>> = board.iter_cells().collect();
+        let x_count = cells
+            .iter()
+            .filter(|&&cell| cell == Some(PlayerMarker::X))
+            .count();
+        let o_count = cells
+            .iter()
+            .filter(|&&cell| cell == Some(PlayerMarker::O))
+            .count();
+        // X has just moved when it holds one more cell than O; O when they are level.
+        let moved_last = match player {
+            PlayerMarker::X => x_count == o_count + 1,
+            PlayerMarker::O => x_count == o_count,
+        };
+        if !moved_last {
+            continue;
+        }
+        for (index, &cell) in cells.iter().enumerate() {
+            if cell == Some(player) {
+                totals[index].0 += value;
+                totals[index].1 += 1;
+            }
+        }
+    }
+
+    let mut writer = BufWriter::new(File::create(path)?);
+    writeln!(writer, "cell_index,mean_value,visit_count")?;
+    for (index, (sum, count)) in totals.iter().enumerate() {
+        let mean = if *count == 0 {
+            0.0
+        } else {
+            sum / *count as f32
+        };
+        writeln!(writer, "{},{},{}", index, mean, count)?;
+    }
+    writer.flush()
+}
+
+// Every move of a finished game, with the board packed after each one.
+pub struct GameTranscript {
+    pub moves: Vec<(PlayerMarker, usize)>,
+    pub outcome: GameResult,
+    pub board_states: Vec<Board>,
+    pub winning_combo: Option<[usize; 3]>,
+    // The illegal move that forfeited the game, if one did. It is not among
+    // `moves` and left the board as it was.
+    pub forfeit: Option<(PlayerMarker, usize)>,
+}
+
+// The result of a game `player` won.
+fn win_for(player: PlayerMarker) -> GameResult {
+    match player {
+        PlayerMarker::X => GameResult::XWin,
+        PlayerMarker::O => GameResult::OWin,
+    }
+}
+
+impl GameTranscript {
+    // The transcript of a game played as `moves`, which must take turns from
+    // X, mark only empty cells and finish the game with the last move, or
+    // forfeit it with a last move to a taken cell.
+    pub fn from_moves(mut moves: Vec<(PlayerMarker, usize)>) -> Result<GameTranscript, String> {
+        let mut board = Board::new();
+        let mut board_states = Vec::new();
+        let mut forfeit = None;
+        for (turn, &(player, index)) in moves.iter().enumerate() {
+            if board.check_winner(PlayerMarker::X) || board.check_winner(PlayerMarker::O) {
+                return Err(format!("move {} comes after the game is won", turn + 1));
+            }
+            if player != board.player_to_move() {
+                return Err(format!(
+                    "move {} is {}'s, but it is {}'s turn",
+                    turn + 1,
+                    PlayerMarker::player_char(player),
+                    PlayerMarker::player_char(board.player_to_move())
+                ));
+            }
+            if index >= 9 || !board.available(index) {
+                if turn + 1 == moves.len() {
+                    forfeit = Some((player, index));
+                    break;
+                }
+                return Err(format!(
+                    "move {} is to a taken cell, {}",
+                    turn + 1,
+                    index + 1
+                ));
+            }
+            board.set(index, player);
+            board_states.push(board);
+        }
+        let winning_combo = board
+            .check_winner_combo(PlayerMarker::X)
+            .or_else(|| board.check_winner_combo(PlayerMarker::O));
+        let outcome = if let Some((player, _)) = forfeit {
+            moves.pop();
+            win_for(player.opposite())
+        } else if board.check_winner(PlayerMarker::X) {
+            GameResult::XWin
+        } else if board.check_winner(PlayerMarker::O) {
+            GameResult::OWin
+        } else if board.is_draw() {
+            GameResult::Draw
+        } else {
+            return Err("the game is unfinished".to_string());
+        };
+        Ok(GameTranscript {
+            moves,
+            outcome,
+            board_states,
+            winning_combo,
+            forfeit,
+        })
+    }
+
+    pub fn replay(&self) -> impl Iterator<Item = Board> + '_ {
+        self.board_states.iter().copied()
+    }
+
+    // The game as an rl-core episode for a `TrajectoryRecorder`: the boards
+    // from the empty one on, the cells played, and rewards from X's view, 0
+    // until the last move and then 1 for an X win, -1 for an O win and 0 for
+    // a draw. A forfeiting move is the last action, leaving the board as it
+    // was.
+    pub fn episode(&self) -> Episode<Board, usize> {
+        let mut states = vec![Board::new()];
+        states.extend(self.replay());
+        let mut actions: Vec<usize> = self.moves.iter().map(|&(_, index)| index).collect();
+        if let Some((_, index)) = self.forfeit {
+            states.push(*states.last().expect("starts with the empty board"));
+            actions.push(index);
+        }
+        let mut rewards = vec![0.0; actions.len()];
+        if let Some(last) = rewards.last_mut() {
+            *last = match self.outcome {
+                GameResult::XWin => 1.0,
+                GameResult::OWin => -1.0,
+                GameResult::Draw => 0.0,
+            };
+        }
+        Episode {
+            states,
+            actions,
+            rewards,
+            terminated: true,
+        }
+    }
+}
+
+impl fmt::Display for GameTranscript {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (turn, (&(player, index), board)) in self.moves.iter().zip(self.replay()).enumerate() {
+            writeln!(
+                f,
+                "Move {}: {} plays {}\n{}\n",
+                turn + 1,
+                PlayerMarker::player_char(player),
+                index + 1,
+                board
+            )?;
+        }
+        write!(f, "Result: {}", self.outcome)?;
+        if let Some([a, b, c]) = self.winning_combo {
+            write!(f, " on {}-{}-{}", a + 1, b + 1, c + 1)?;
+        }
+        if let Some((player, index)) = self.forfeit {
+            write!(
+                f,
+                " by forfeit: {} played {}, which is taken",
+                PlayerMarker::player_char(player),
+                index + 1
+            )?;
+        }
+        Ok(())
+    }
+}
+
+// Outcome counts and total moves over a batch of games.
+#[derive(Clone, Debug, Default)]
+pub struct MatchSummary {
+    pub x_wins: u32,
+    pub o_wins: u32,
+    pub draws: u32,
+    pub total_moves: u64,
+}
+
+impl MatchSummary {
+    pub fn record(&mut self, transcript: &GameTranscript) {
+        self.record_result(transcript.outcome, transcript.moves.len());
+    }
+
+    pub fn record_result(&mut self, result: GameResult, moves: usize) {
+        match result {
+            GameResult::XWin => self.x_wins += 1,
+            GameResult::OWin => self.o_wins += 1,
+            GameResult::Draw => self.draws += 1,
+        }
+        self.total_moves += moves as u64;
+    }
+
+    pub fn games(&self) -> u32 {
+        self.x_wins + self.o_wins + self.draws
+    }
+
+    pub fn average_length(&self) -> f32 {
+        self.total_moves as f32 / self.games().max(1) as f32
+    }
+
+    // The fraction of games that `count` represents, with its 95% Wilson score
+    // interval, which stays inside [0, 1] even for rates near 0 or 1.
+    pub fn rate_with_interval(&self, count: u32) -> (f32, f32, f32) {
+        let n = self.games().max(1) as f32;
+        let p = count as f32 / n;
+        let z = 1.96f32;
+        let denominator = 1.0 + z * z / n;
+        let center = (p + z * z / (2.0 * n)) / denominator;
+        let margin = z * (p * (1.0 - p) / n + z * z / (4.0 * n * n)).sqrt() / denominator;
+        (p, (center - margin).max(0.0), (center + margin).min(1.0))
+    }
+}
+
+impl fmt::Display for MatchSummary {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "Games: {}", self.games())?;
+        for (label, count) in [
+            ("X wins", self.x_wins),
+            ("O wins", self.o_wins),
+            ("Draws", self.draws),
+        ] {
+            let (rate, low, high) = self.rate_with_interval(count);
+            writeln!(
+                f,
+                "{}: {} ({:.3}, 95% CI {:.3}-{:.3})",
+                label, count, rate, low, high
+            )?;
+        }
+        write!(f, "Average game length: {:.2} moves", self.average_length())
+    }
+}
+
+// Plays a game between `x_agent` and `o_agent`. An agent that picks a cell
+// that isn't empty forfeits: it is told it lost and the other that it won,
+// and the board is left untouched, since marking a taken cell would corrupt
+// it. If an agent fails to move at all, the game is abandoned with the error
+// and neither agent is told a result.
+pub fn play_game(
+    x_agent: &mut dyn Agent,
+    o_agent: &mut dyn Agent,
+) -> Result<GameResult, GameError> {
+    Ok(play_game_with_transcript(x_agent, o_agent)?.0)
+}
+
+pub fn play_game_with_transcript<'a>(
+    x_agent: &'a mut dyn Agent,
+    o_agent: &'a mut dyn Agent,
+) -> Result<(GameResult, GameTranscript), GameError> {
+    let mut board = Board::new();
+    let mut current_player = PlayerMarker::X;
+    let mut current_agent = x_agent;
+    let mut other_agent = o_agent;
+    let mut moves = Vec::new();
+    let mut board_states = Vec::new();
+
+    loop {
+        let move_index = match current_agent.try_choose_move(&board, current_player) {
+            Ok(index) => index,
+            Err(GameError::IllegalMove { player, index }) => {
+                current_agent.observe_result(AgentOutcome::Loss, &board);
+                other_agent.observe_result(AgentOutcome::Win, &board);
+                let outcome = win_for(player.opposite());
+                let transcript = GameTranscript {
+                    moves,
+                    outcome,
+                    board_states,
+                    winning_combo: None,
+                    forfeit: Some((player, index)),
+                };
+                return Ok((outcome, transcript));
+            }
+            Err(error) => return Err(error),
+        };
+        board.set(move_index, current_player);
+        moves.push((current_player, move_index));
+        board_states.push(board);
+
+        let winning_combo = board.check_winner_combo(current_player);
+        let outcome = if winning_combo.is_some() {
+            current_agent.observe_result(AgentOutcome::Win, &board);
+            other_agent.observe_result(AgentOutcome::Loss, &board);
+            win_for(current_player)
+        } else if board.is_draw() {
+            current_agent.observe_result(AgentOutcome::Draw, &board);
+            other_agent.observe_result(AgentOutcome::Draw, &board);
+            GameResult::Draw
+        } else {
+            current_player = current_player.opposite();
+            std::mem::swap(&mut current_agent, &mut other_agent);
+            continue;
+        };
+        let transcript = GameTranscript {
+            moves,
+            outcome,
+            board_states,
+            winning_combo,
+            forfeit: None,
+        };
+        return Ok((outcome, transcript));
+    }
+}
+
+// The result of a game played for statistics, or `None` with a warning if it
+// had to be abandoned, so one bad game doesn't end a long run.
+pub(crate) fn counted(result: Result<GameResult, GameError>) -> Option<GameResult> {
+    result
+        .inspect_err(|error| eprintln!("Warning: abandoned a game: {}", error))
+        .ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Trainer;
+
+    // Plays `moves` in turn, then the last of them forever, taken or not,
+    // and remembers how its last game went.
+    struct ScriptedAgent {
+        moves: Vec<usize>,
+        outcome: Option<AgentOutcome>,
+    }
+
+    impl ScriptedAgent {
+        fn new(moves: &[usize]) -> Self {
+            ScriptedAgent {
+                moves: moves.iter().rev().copied().collect(),
+                outcome: None,
+            }
+        }
+    }
+
+    impl Agent for ScriptedAgent {
+        fn choose_move(&mut self, _board: &Board, _player: PlayerMarker) -> usize {
+            if self.moves.len() > 1 {
+                self.moves.pop().unwrap()
+            } else {
+                self.moves[0]
+            }
+        }
+
+        fn observe_result(&mut self, outcome: AgentOutcome, _board: &Board) {
+            self.outcome = Some(outcome);
+        }
+    }
+
+    #[test]
+    fn marking_a_taken_cell_forfeits_without_corrupting_the_board() {
+        // X takes the center, O the corner, and X then marks O's corner. ORing
+        // X's bits over O's would turn the O into an X.
+        let mut x = ScriptedAgent::new(&[4, 0]);
+        let mut o = ScriptedAgent::new(&[0]);
+        let (result, transcript) = play_game_with_transcript(&mut x, &mut o).unwrap();
+        assert_eq!(result, GameResult::OWin);
+        assert_eq!(transcript.forfeit, Some((PlayerMarker::X, 0)));
+        assert_eq!(transcript.moves.len(), 2);
+        let last = transcript.replay().last().unwrap();
+        assert_eq!(last.cell(0), Some(PlayerMarker::O));
+        assert_eq!(x.outcome, Some(AgentOutcome::Loss));
+        assert_eq!(o.outcome, Some(AgentOutcome::Win));
+        assert!(transcript
+            .to_string()
+            .ends_with("by forfeit: X played 1, which is taken"));
+
+        let full: Board = "XOXXOOOXX".parse().unwrap();
+        assert!(matches!(
+            x.try_choose_move(&full, PlayerMarker::X),
+            Err(GameError::NoMoves)
+        ));
+        // Forfeits are ordinary results to a trainer: O, also playing the
+        // center, loses every game and nothing is abandoned.
+        let mut trainer = Trainer::new(
+            Box::new(ScriptedAgent::new(&[4])),
+            Box::new(ScriptedAgent::new(&[4])),
+        );
+        let stats = trainer.run(10);
+        assert_eq!((stats.x_agent_wins, stats.abandoned), (10, 0));
+    }
+}
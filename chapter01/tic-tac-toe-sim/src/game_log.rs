@@ -0,0 +1,379 @@
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::{Agent, Board, GameResult, GameTranscript, PlayerMarker};
+
+// Game logs hold one finished game a line: when it ended, in UTC, then each
+// move as the mark and the cell from 1 to 9, then the result as PGN writes it:
+//
+//     2026-10-14T09:30:00Z X5 O1 X9 O3 X2 O8 X7 O6 X4 1/2-1/2
+//
+// A game forfeited by a move to a taken cell ends with that move. Blank lines
+// and lines starting with `#` are skipped, so logs can be annotated by hand.
+
+// A game read back from a log.
+pub struct LoggedGame {
+    pub played: String,
+    pub transcript: GameTranscript,
+}
+
+fn result_token(result: GameResult) -> &'static str {
+    match result {
+        GameResult::XWin => "1-0",
+        GameResult::OWin => "0-1",
+        GameResult::Draw => "1/2-1/2",
+    }
+}
+
+// `time` as YYYY-MM-DDTHH:MM:SSZ, converting days to a date with the
+// proleptic Gregorian calendar.
+fn utc_timestamp(time: SystemTime) -> String {
+    let seconds = time
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs());
+    let days = (seconds / 86400) as i64 + 719468;
+    let era = days.div_euclid(146097);
+    let day_of_era = days - era * 146097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    // Months counted from March, so the leap day falls at the end.
+    let march_month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * march_month + 2) / 5 + 1;
+    let month = if march_month < 10 {
+        march_month + 3
+    } else {
+        march_month - 9
+    };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        seconds % 86400 / 3600,
+        seconds % 3600 / 60,
+        seconds % 60
+    )
+}
+
+// The log line for `transcript`, a game that ended at `played`.
+pub fn format_game(transcript: &GameTranscript, played: SystemTime) -> String {
+    let mut line = utc_timestamp(played);
+    for &(player, index) in transcript.moves.iter().chain(&transcript.forfeit) {
+        line += &format!(" {}{}", PlayerMarker::player_char(player), index + 1);
+    }
+    line + " " + result_token(transcript.outcome)
+}
+
+// Appends `transcript` to the log at `path`, creating it if need be.
+pub fn append_game(path: &Path, transcript: &GameTranscript) -> io::Result<()> {
+    let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+    writeln!(file, "{}", format_game(transcript, SystemTime::now()))
+}
+
+// Reads one log line back, checking that its moves are a legal game with the
+// result it records.
+pub fn parse_game(line: &str) -> Result<LoggedGame, String> {
+    let tokens: Vec<&str> = line.split_whitespace().collect();
+    let [played, moves @ .., result] = &tokens[..] else {
+        return Err("expected a time, moves and a result".to_string());
+    };
+    if !played.ends_with('Z') {
+        return Err(format!("'{}' is not a UTC time", played));
+    }
+    let moves = moves
+        .iter()
+        .map(|token| {
+            let mut chars = token.chars();
+            let player = match chars.next() {
+                Some('X') => PlayerMarker::X,
+                Some('O') => PlayerMarker::O,
+                _ => return Err(format!("invalid move '{}'", token)),
+            };
+            match chars.as_str().parse::<usize>() {
+                Ok(cell) if (1..=9).contains(&cell) => Ok((player, cell - 1)),
+                _ => Err(format!("invalid move '{}'", token)),
+            }
+        })
+        .collect::<Result<Vec<_>, String>>()?;
+    let transcript = GameTranscript::from_moves(moves)?;
+    if *result != result_token(transcript.outcome) {
+        return Err(format!(
+            "the result is {} but the moves give {}",
+            result,
+            result_token(transcript.outcome)
+        ));
+    }
+    Ok(LoggedGame {
+        played: played.to_string(),
+        transcript,
+    })
+}
+
+// Every game in the log at `path`, in the order played.
+pub fn read_game_log(path: &Path) -> Result<Vec<LoggedGame>, String> {
+    let file = File::open(path)
+        .map_err(|error| format!("could not open {}: {}", path.display(), error))?;
+    let mut games = Vec::new();
+    for (number, line) in BufReader::new(file).lines().enumerate() {
+        let line = line.map_err(|error| format!("could not read {}: {}", path.display(), error))?;
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let game = parse_game(line)
+            .map_err(|error| format!("{}:{}: {}", path.display(), number + 1, error))?;
+        games.push(game);
+    }
+    Ok(games)
+}
+
+// A move held up against an agent's values for every move in its position.
+pub struct MoveReview {
+    pub values: Vec<(usize, f32)>,
+    // (cell, value) of the move played and of the agent's greedy move, the
+    // first of the best.
+    pub played: (usize, f32),
+    pub best: (usize, f32),
+}
+
+impl MoveReview {
+    // How far below the greedy move the agent values the move played; 0 when
+    // they are the same move.
+    pub fn shortfall(&self) -> f32 {
+        self.best.1 - self.played.1
+    }
+
+    // Whether the move played gave up at least `margin` of value, a blunder
+    // by the agent's lights.
+    pub fn is_blunder(&self, margin: f32) -> bool {
+        self.shortfall() >= margin
+    }
+}
+
+// Reviews `player` marking `index` on `board` against `agent`'s values, or
+// `None` if the agent doesn't value moves.
+pub fn review_move(
+    agent: &dyn Agent,
+    board: &Board,
+    player: PlayerMarker,
+    index: usize,
+) -> Option<MoveReview> {
+    let values = agent.move_values(board, player)?;
+    let played = *values.iter().find(|&&(i, _)| i == index)?;
+    let best = greedy(&values)?;
+    Some(MoveReview {
+        values,
+        played,
+        best,
+    })
+}
+
+// The (cell, value) of the best of `values`, the first of them on a tie.
+fn greedy(values: &[(usize, f32)]) -> Option<(usize, f32)> {
+    values.iter().copied().reduce(|best, (i, value)| {
+        if value > best.1 || (value == best.1 && i < best.0) {
+            (i, value)
+        } else {
+            best
+        }
+    })
+}
+
+// The game `transcript` would have been had `alternative` been played at
+// move `at_move`, counted from 0, with `x_agent`'s and `o_agent`'s greedy
+// moves from then on.
+pub fn counterfactual(
+    transcript: &GameTranscript,
+    at_move: usize,
+    alternative: usize,
+    x_agent: &dyn Agent,
+    o_agent: &dyn Agent,
+) -> Result<GameTranscript, String> {
+    if at_move >= transcript.moves.len() {
+        return Err(format!(
+            "the game has only {} moves",
+            transcript.moves.len()
+        ));
+    }
+    let mut moves = transcript.moves[..at_move].to_vec();
+    let mut board = match at_move {
+        0 => Board::new(),
+        _ => transcript.board_states[at_move - 1],
+    };
+    let player = board.player_to_move();
+    if alternative >= 9 || !board.available(alternative) {
+        return Err(format!(
+            "cell {} is taken at move {}",
+            alternative + 1,
+            at_move + 1
+        ));
+    }
+    moves.push((player, alternative));
+    board.set(alternative, player);
+    while !(board.check_winner(PlayerMarker::X)
+        || board.check_winner(PlayerMarker::O)
+        || board.is_full())
+    {
+        let player = board.player_to_move();
+        let agent = match player {
+            PlayerMarker::X => x_agent,
+            PlayerMarker::O => o_agent,
+        };
+        let (index, _) = agent
+            .move_values(&board, player)
+            .as_deref()
+            .and_then(greedy)
+            .ok_or("the agent doesn't value moves")?;
+        moves.push((player, index));
+        board.set(index, player);
+    }
+    GameTranscript::from_moves(moves)
+}
+
+// `original` and `other` move by move, side by side, each board under the
+// move that made it, and then their results.
+pub fn side_by_side(original: &GameTranscript, other: &GameTranscript) -> String {
+    const WIDTH: usize = 20;
+    let column = |transcript: &GameTranscript, turn: usize| {
+        let Some(&(player, index)) = transcript.moves.get(turn) else {
+            return vec![String::new(); 6];
+        };
+        let board = transcript.board_states[turn].to_string();
+        let mut lines = vec![format!(
+            "{} plays {}",
+            PlayerMarker::player_char(player),
+            index + 1
+        )];
+        lines.extend(board.lines().map(str::to_string));
+        lines
+    };
+    let mut text = format!("{:WIDTH$}{}\n", "Played", "What if");
+    for turn in 0..original.moves.len().max(other.moves.len()) {
+        text += &format!("Move {}\n", turn + 1);
+        for (left, right) in column(original, turn).iter().zip(column(other, turn)) {
+            text += format!("{:WIDTH$}{}", left, right).trim_end();
+            text += "\n";
+        }
+    }
+    text + &format!("{:WIDTH$}{}", original.outcome.to_string(), other.outcome)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::time::Duration;
+
+    use super::*;
+    use crate::{RLAgent, RLConfig};
+
+    #[test]
+    fn games_round_trip_through_the_log_format() {
+        let transcript = GameTranscript::from_moves(vec![
+            (PlayerMarker::X, 4),
+            (PlayerMarker::O, 0),
+            (PlayerMarker::X, 8),
+            (PlayerMarker::O, 2),
+            (PlayerMarker::X, 1),
+            (PlayerMarker::O, 7),
+            (PlayerMarker::X, 6),
+            (PlayerMarker::O, 5),
+            (PlayerMarker::X, 3),
+        ])
+        .unwrap();
+        let played = UNIX_EPOCH + Duration::from_secs(1791970200);
+        let line = format_game(&transcript, played);
+        assert_eq!(
+            line,
+            "2026-10-14T09:30:00Z X5 O1 X9 O3 X2 O8 X7 O6 X4 1/2-1/2"
+        );
+        let game = parse_game(&line).unwrap();
+        assert_eq!(game.played, "2026-10-14T09:30:00Z");
+        assert_eq!(game.transcript.moves, transcript.moves);
+        assert_eq!(game.transcript.outcome, GameResult::Draw);
+        assert_eq!(utc_timestamp(UNIX_EPOCH), "1970-01-01T00:00:00Z");
+        // A leap day.
+        assert_eq!(
+            utc_timestamp(UNIX_EPOCH + Duration::from_secs(951782400)),
+            "2000-02-29T00:00:00Z"
+        );
+    }
+
+    #[test]
+    fn illegal_or_mislabelled_games_are_rejected() {
+        let time = "2026-10-14T09:30:00Z";
+        for line in [
+            "X5 O1 X9 1-0",
+            "2026-10-14T09:30:00Z X5 X1 1-0",
+            "2026-10-14T09:30:00Z X5 O5 0-1",
+            "2026-10-14T09:30:00Z X5 O5 X1 1-0",
+            "2026-10-14T09:30:00Z X5 O1 X0 1-0",
+        ] {
+            assert!(parse_game(line).is_err(), "{}", line);
+        }
+        // X completes the top row but the line claims O won.
+        assert!(parse_game(&format!("{} X1 O4 X2 O5 X3 0-1", time)).is_err());
+        assert!(parse_game(&format!("{} X1 O4 X2 O5 X3 1-0", time)).is_ok());
+        // Moves after the game is won.
+        assert!(parse_game(&format!("{} X1 O4 X2 O5 X3 O6 1-0", time)).is_err());
+        // Unfinished.
+        assert!(parse_game(&format!("{} X1 O4 1-0", time)).is_err());
+        // O forfeits by marking X's cell.
+        let game = parse_game(&format!("{} X5 O5 1-0", time)).unwrap();
+        assert_eq!(game.transcript.forfeit, Some((PlayerMarker::O, 4)));
+        let line = format_game(&game.transcript, UNIX_EPOCH);
+        assert_eq!(line, "1970-01-01T00:00:00Z X5 O5 1-0");
+    }
+
+    #[test]
+    fn reviews_flag_moves_well_below_the_greedy_one() {
+        let board: Board = "XX.OO....".parse().unwrap();
+        let config = RLConfig {
+            symmetry: false,
+            ..RLConfig::default()
+        };
+        let q_table = [(2, 1.0), (5, -0.1)]
+            .into_iter()
+            .map(|(i, value)| (board.with_move(i, PlayerMarker::X), value))
+            .collect();
+        let agent = RLAgent::with_q_table(q_table, config);
+        let review = review_move(&agent, &board, PlayerMarker::X, 5).unwrap();
+        assert_eq!(review.best, (2, 1.0));
+        assert_eq!(review.played, (5, -0.1));
+        assert!(review.is_blunder(0.5));
+        let review = review_move(&agent, &board, PlayerMarker::X, 2).unwrap();
+        assert_eq!(review.shortfall(), 0.0);
+        // Unvalued moves sit at 0, ahead of 5 but well behind 2.
+        let review = review_move(&agent, &board, PlayerMarker::X, 6).unwrap();
+        assert!(review.is_blunder(0.5) && !review.is_blunder(1.5));
+    }
+
+    #[test]
+    fn a_what_if_game_plays_on_greedily_from_the_changed_move() {
+        let game = parse_game("2026-10-14T09:30:00Z X5 O1 X9 O3 X2 O8 X7 O6 X4 1/2-1/2").unwrap();
+        let config = RLConfig {
+            symmetry: false,
+            ..RLConfig::default()
+        };
+        // With every move valued the same, each side takes the first empty
+        // cell.
+        let agent = RLAgent::with_q_table(HashMap::new(), config);
+        let other = counterfactual(&game.transcript, 1, 1, &agent, &agent).unwrap();
+        let cells: Vec<usize> = other.moves.iter().map(|&(_, index)| index).collect();
+        assert_eq!(cells, [4, 1, 0, 2, 3, 5, 6]);
+        assert_eq!(other.outcome, GameResult::XWin);
+        assert!(counterfactual(&game.transcript, 9, 0, &agent, &agent).is_err());
+        assert!(counterfactual(&game.transcript, 1, 4, &agent, &agent).is_err());
+        let text = side_by_side(&game.transcript, &other);
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(lines[0], "Played              What if");
+        assert_eq!(lines[8], "Move 2");
+        assert_eq!(lines[9], "O plays 1           O plays 2");
+        // The what-if game is over by the last move.
+        assert_eq!(lines[lines.len() - 2], "X|O|X");
+        assert_eq!(lines[lines.len() - 1], "Draw                X wins");
+    }
+}
@@ -0,0 +1,226 @@
+use std::io::{self, BufRead, IsTerminal};
+
+use crate::agents::is_over;
+use crate::{format_values, Agent, AgentOutcome, Board, GameError, GameResult, PlayerMarker};
+
+// Play at the keyboard: the human player and the interactive game loop,
+// reading from stdin and printing to stdout. Only built with the cli feature,
+// so builds without a terminal, such as for the web, leave them out.
+
+pub struct HumanAgent;
+
+impl HumanAgent {
+    fn read_move(board: &Board, player: PlayerMarker) -> Result<usize, GameError> {
+        loop {
+            board.print();
+            println!("{} to move!", PlayerMarker::player_char(player));
+            println!("Enter a number between 1 and 9:");
+            let mut input = String::new();
+            let read = std::io::stdin()
+                .read_line(&mut input)
+                .map_err(GameError::Input)?;
+            if read == 0 {
+                return Err(GameError::Input(io::ErrorKind::UnexpectedEof.into()));
+            }
+            let move_index: usize = match input.trim().parse::<usize>() {
+                Ok(num) if (1..=9).contains(&num) => num - 1,
+                _ => {
+                    println!("Invalid input. Please enter a number between 1 and 9.");
+                    continue;
+                }
+            };
+            if !board.available(move_index) {
+                println!("That space is taken. Try again.");
+                continue;
+            }
+            return Ok(move_index);
+        }
+    }
+}
+
+impl Agent for HumanAgent {
+    // Quits the program when the input fails; `try_choose_move` reports it.
+    fn choose_move(&mut self, board: &Board, player: PlayerMarker) -> usize {
+        HumanAgent::read_move(board, player).unwrap_or_else(|error| {
+            println!("{}; quitting.", error);
+            std::process::exit(0);
+        })
+    }
+
+    fn try_choose_move(&mut self, board: &Board, player: PlayerMarker) -> Result<usize, GameError> {
+        if is_over(board) {
+            return Err(GameError::NoMoves);
+        }
+        HumanAgent::read_move(board, player)
+    }
+
+    fn observe_result(&mut self, outcome: AgentOutcome, board: &Board) {
+        print_final_board(
+            board,
+            match outcome {
+                AgentOutcome::Win => "You win!",
+                AgentOutcome::Loss => "You lose!",
+                AgentOutcome::Draw => "It's a draw!",
+            },
+        );
+    }
+}
+
+// How an interactive game ended.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum InteractiveOutcome {
+    Finished(GameResult),
+    Quit,
+}
+
+// What the human typed at the interactive prompt.
+enum HumanCommand {
+    Move(usize),
+    Hint,
+    Undo,
+    Restart,
+    Quit,
+}
+
+fn parse_human_command(input: &str) -> Option<HumanCommand> {
+    match input {
+        "hint" | "h" => Some(HumanCommand::Hint),
+        "undo" | "u" => Some(HumanCommand::Undo),
+        "restart" | "r" => Some(HumanCommand::Restart),
+        "quit" | "q" => Some(HumanCommand::Quit),
+        _ => match input.parse::<usize>() {
+            Ok(num) if (1..=9).contains(&num) => Some(HumanCommand::Move(num - 1)),
+            _ => None,
+        },
+    }
+}
+
+// Plays one game between a human reading commands from `input` as `human` and
+// `opponent` on the other side or, with no opponent, between two humans taking
+// turns at the same keyboard. Besides a cell number the human can type `hint`
+// for `advisor`'s value of each of their moves, `undo` to take back their last
+// move (and the opponent's reply to it), `restart` to start the game over, or
+// `quit`; closing the input also quits. In hot-seat games `advisor` only
+// advises `human`. `opponent` is told the result only when the game is
+// finished. Returns how the game ended and the moves that stood.
+pub fn play_interactive(
+    mut opponent: Option<&mut dyn Agent>,
+    human: PlayerMarker,
+    advisor: Option<&dyn Agent>,
+    input: &mut dyn BufRead,
+) -> io::Result<(InteractiveOutcome, Vec<(PlayerMarker, usize)>)> {
+    let hot_seat = opponent.is_none();
+    let result_for = |player: PlayerMarker| match player {
+        PlayerMarker::X => GameResult::XWin,
+        PlayerMarker::O => GameResult::OWin,
+    };
+    let win_message = |player: PlayerMarker| match (hot_seat, player == human) {
+        (true, _) => format!("{} wins!", PlayerMarker::player_char(player)),
+        (false, true) => "You win!".to_string(),
+        (false, false) => "You lose!".to_string(),
+    };
+    let mut board = Board::new();
+    let mut moves = Vec::new();
+    // The board before each of the humans' moves so far, for `undo`.
+    let mut history: Vec<Board> = Vec::new();
+    loop {
+        let player = board.player_to_move();
+        if let Some(opponent) = opponent.as_deref_mut().filter(|_| player != human) {
+            let move_index = opponent
+                .try_choose_move(&board, player)
+                .map_err(io::Error::other)?;
+            board.set(move_index, player);
+            moves.push((player, move_index));
+            if board.check_winner(player) {
+                opponent.observe_result(AgentOutcome::Win, &board);
+                print_final_board(&board, &win_message(player));
+                return Ok((InteractiveOutcome::Finished(result_for(player)), moves));
+            }
+            if board.is_draw() {
+                opponent.observe_result(AgentOutcome::Draw, &board);
+                print_final_board(&board, "It's a draw!");
+                return Ok((InteractiveOutcome::Finished(GameResult::Draw), moves));
+            }
+            continue;
+        }
+
+        board.print();
+        if hot_seat {
+            println!(
+                "{} to move. Enter a cell from 1 to 9, or hint, undo, restart or quit:",
+                PlayerMarker::player_char(player)
+            );
+        } else {
+            println!(
+                "You are {}. Enter a cell from 1 to 9, or hint, undo, restart or quit:",
+                PlayerMarker::player_char(human)
+            );
+        }
+        let mut line = String::new();
+        if input.read_line(&mut line)? == 0 {
+            return Ok((InteractiveOutcome::Quit, moves));
+        }
+        match parse_human_command(line.trim()) {
+            Some(HumanCommand::Move(index)) if !board.available(index) => {
+                println!("That space is taken. Try again.");
+            }
+            Some(HumanCommand::Move(index)) => {
+                history.push(board);
+                board.set(index, player);
+                moves.push((player, index));
+                if board.check_winner(player) {
+                    if let Some(opponent) = opponent.as_deref_mut() {
+                        opponent.observe_result(AgentOutcome::Loss, &board);
+                    }
+                    print_final_board(&board, &win_message(player));
+                    return Ok((InteractiveOutcome::Finished(result_for(player)), moves));
+                }
+                if board.is_draw() {
+                    if let Some(opponent) = opponent.as_deref_mut() {
+                        opponent.observe_result(AgentOutcome::Draw, &board);
+                    }
+                    print_final_board(&board, "It's a draw!");
+                    return Ok((InteractiveOutcome::Finished(GameResult::Draw), moves));
+                }
+            }
+            Some(HumanCommand::Hint) => {
+                let values = advisor
+                    .filter(|_| player == human)
+                    .and_then(|advisor| advisor.move_values(&board, human));
+                match values {
+                    Some(move_values) => println!(
+                        "{}",
+                        format_values(&board, &move_values, io::stdout().is_terminal())
+                    ),
+                    None => println!("No hints are available."),
+                }
+            }
+            Some(HumanCommand::Undo) => match history.pop() {
+                Some(previous) => {
+                    board = previous;
+                    moves.truncate(9 - board.available_moves().count());
+                }
+                None => println!("Nothing to undo."),
+            },
+            Some(HumanCommand::Restart) => {
+                board = Board::new();
+                moves.clear();
+                history.clear();
+            }
+            Some(HumanCommand::Quit) => return Ok((InteractiveOutcome::Quit, moves)),
+            None => println!("Invalid input. Enter a number between 1 and 9, or a command."),
+        }
+    }
+}
+
+// Prints the finished game's board with any winning line highlighted, then
+// `message`.
+fn print_final_board(board: &Board, message: &str) {
+    let winning_line: Vec<usize> = board
+        .check_winner_combo(PlayerMarker::X)
+        .or_else(|| board.check_winner_combo(PlayerMarker::O))
+        .map(Vec::from)
+        .unwrap_or_default();
+    println!("{}", board.highlighted(&winning_line));
+    println!("{}", message);
+}
@@ -0,0 +1,85 @@
+// Ctrl-C as a request to stop rather than a kill. Once `catch_interrupts` is
+// called, a Ctrl-C while an `InterruptScope` is alive only sets a flag, which
+// the loop holding the scope checks between games so it can stop with what
+// it has, save it and report it. Anything waiting for a line from the
+// keyboard reads the end of the input instead, which ends interactive play
+// as closing the input does. A Ctrl-C outside every scope, or a second one,
+// ends the process at once as usual. Only Unix signals are caught; elsewhere
+// Ctrl-C still ends the process.
+
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+// How many `InterruptScope`s are alive.
+static SCOPES: AtomicUsize = AtomicUsize::new(0);
+
+// Whether Ctrl-C has asked the program to stop.
+pub fn interrupted() -> bool {
+    INTERRUPTED.load(Ordering::SeqCst)
+}
+
+// Held by a loop that checks `interrupted` and stops cleanly when it is set.
+pub struct InterruptScope(());
+
+impl InterruptScope {
+    pub fn enter() -> Self {
+        SCOPES.fetch_add(1, Ordering::SeqCst);
+        InterruptScope(())
+    }
+}
+
+impl Drop for InterruptScope {
+    fn drop(&mut self) {
+        SCOPES.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+#[cfg(unix)]
+mod unix {
+    use std::ffi::{c_char, c_int};
+    use std::sync::atomic::Ordering;
+
+    use super::{INTERRUPTED, SCOPES};
+
+    pub(super) const SIGINT: c_int = 2;
+    pub(super) const SIG_IGN: usize = 1;
+    const O_RDONLY: c_int = 0;
+    const STDIN: c_int = 0;
+    // The shell's status for a process ended by SIGINT.
+    const INTERRUPTED_STATUS: c_int = 130;
+
+    extern "C" {
+        pub(super) fn signal(signal: c_int, handler: usize) -> usize;
+        fn open(path: *const c_char, flags: c_int, ...) -> c_int;
+        fn dup2(from: c_int, to: c_int) -> c_int;
+        fn close(fd: c_int) -> c_int;
+        fn _exit(status: c_int) -> !;
+    }
+
+    // Only atomics and calls that are safe in a signal handler.
+    pub(super) extern "C" fn on_interrupt(_signal: c_int) {
+        if SCOPES.load(Ordering::SeqCst) == 0 || INTERRUPTED.swap(true, Ordering::SeqCst) {
+            unsafe { _exit(INTERRUPTED_STATUS) }
+        }
+        unsafe {
+            let null = open(c"/dev/null".as_ptr(), O_RDONLY);
+            if null >= 0 {
+                dup2(null, STDIN);
+                close(null);
+            }
+        }
+    }
+}
+
+// Installs the Ctrl-C handler for the rest of the process, unless SIGINT is
+// ignored, as it is for jobs a script starts in the background.
+pub fn catch_interrupts() {
+    #[cfg(unix)]
+    unsafe {
+        let handler = unix::on_interrupt as extern "C" fn(_) as usize;
+        if unix::signal(unix::SIGINT, handler) == unix::SIG_IGN {
+            unix::signal(unix::SIGINT, unix::SIG_IGN);
+        }
+    }
+}
@@ -0,0 +1,239 @@
+use serde::{Deserialize, Serialize};
+
+use crate::tournament::play_pairing;
+use crate::{
+    Agent, AgentOutcome, Board, HeuristicAgent, MinimaxAgent, PlayerMarker, RandomAgent,
+    TournamentResult,
+};
+
+// The fixed opponents every snapshot is rated against, ahead of the
+// snapshots in `RatingLeague::entrants`. Random play is listed first and
+// anchors the scale.
+const BASELINES: [&str; 3] = ["random", "heuristic", "minimax"];
+
+// Random play's rating in every round, so that ratings from different rounds
+// can be compared.
+const RANDOM_RATING: f64 = 1000.0;
+
+// One entrant's rating after one round of a `RatingLeague`.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Rating {
+    pub episode: u32,
+    pub entrant: String,
+    pub rating: f64,
+}
+
+// A frozen copy of each of a trainer's agents playing as one entrant: the X
+// agent's copy whenever it plays X, the O agent's whenever it plays O.
+pub(crate) struct SidedAgent {
+    x: Box<dyn Agent>,
+    o: Box<dyn Agent>,
+    // The side of the game in progress, for `observe_result`.
+    side: PlayerMarker,
+}
+
+impl SidedAgent {
+    pub(crate) fn new(x: Box<dyn Agent>, o: Box<dyn Agent>) -> Self {
+        SidedAgent {
+            x,
+            o,
+            side: PlayerMarker::X,
+        }
+    }
+
+    fn agent(&mut self, side: PlayerMarker) -> &mut dyn Agent {
+        match side {
+            PlayerMarker::X => self.x.as_mut(),
+            PlayerMarker::O => self.o.as_mut(),
+        }
+    }
+}
+
+impl Agent for SidedAgent {
+    fn choose_move(&mut self, board: &Board, player: PlayerMarker) -> usize {
+        self.side = player;
+        self.agent(player).choose_move(board, player)
+    }
+
+    fn observe_result(&mut self, outcome: AgentOutcome, board: &Board) {
+        self.agent(self.side).observe_result(outcome, board);
+    }
+
+    fn save_state(&self) -> Option<Vec<u8>> {
+        let states = (self.x.save_state()?, self.o.save_state()?);
+        Some(bincode::serialize(&states).expect("plain data always serializes"))
+    }
+
+    fn restore_state(&mut self, state: &[u8]) -> Result<(), String> {
+        let (x, o): (Vec<u8>, Vec<u8>) =
+            bincode::deserialize(state).map_err(|error| error.to_string())?;
+        self.x.restore_state(&x)?;
+        self.o.restore_state(&o)
+    }
+}
+
+// Frozen policies from along a training run, rated against each other and
+// against fixed baselines, to tell whether later policies are stronger or
+// only different. Each snapshot plays every entrant before it as it joins,
+// and the ratings are refitted to the whole cross-table after each.
+pub(crate) struct RatingLeague {
+    entrants: Vec<(String, Box<dyn Agent>)>,
+    // (wins, losses, draws) of each entrant against each other, as in
+    // `TournamentResult`.
+    records: Vec<Vec<(u32, u32, u32)>>,
+}
+
+// A `RatingLeague` as a `Trainer` checkpoint saves it: each snapshot's name
+// and `Agent::save_state`, and the cross-table.
+#[derive(Serialize, Deserialize)]
+pub(crate) struct SavedLeague {
+    snapshots: Vec<(String, Vec<u8>)>,
+    records: Vec<Vec<(u32, u32, u32)>>,
+}
+
+impl RatingLeague {
+    pub(crate) fn new() -> Self {
+        let entrants: Vec<(String, Box<dyn Agent>)> = vec![
+            (BASELINES[0].to_string(), Box::new(RandomAgent::new(None))),
+            (
+                BASELINES[1].to_string(),
+                Box::new(HeuristicAgent::new(None)),
+            ),
+            (BASELINES[2].to_string(), Box::new(MinimaxAgent::new())),
+        ];
+        RatingLeague {
+            entrants,
+            // The baselines' games against each other would never change, so
+            // they are left unplayed; only their games against snapshots count.
+            records: vec![vec![(0, 0, 0); BASELINES.len()]; BASELINES.len()],
+        }
+    }
+
+    // Plays the frozen `snapshot` `games` times against every entrant, adds
+    // it to the league as `name`, and returns every entrant's rating
+    // afterwards. With a `seed`, the baselines are reseeded from it first, so
+    // each round is repeatable on its own.
+    pub(crate) fn add(
+        &mut self,
+        name: String,
+        mut snapshot: Box<dyn Agent>,
+        games: usize,
+        seed: Option<u64>,
+        episode: u32,
+    ) -> Vec<Rating> {
+        if let Some(seed) = seed {
+            for (i, (_, baseline)) in self.entrants[..BASELINES.len()].iter_mut().enumerate() {
+                baseline.reseed(seed.wrapping_add(i as u64));
+            }
+        }
+        let mut row = Vec::with_capacity(self.entrants.len() + 1);
+        for (i, (_, entrant)) in self.entrants.iter_mut().enumerate() {
+            let (wins, losses, draws) = play_pairing(snapshot.as_mut(), entrant.as_mut(), games);
+            self.records[i].push((losses, wins, draws));
+            row.push((wins, losses, draws));
+        }
+        row.push((0, 0, 0));
+        self.records.push(row);
+        self.entrants.push((name, snapshot));
+        self.ratings(episode)
+    }
+
+    // Every entrant's rating, fitted as for a tournament and shifted so that
+    // random play is at `RANDOM_RATING`.
+    fn ratings(&self, episode: u32) -> Vec<Rating> {
+        let result = TournamentResult {
+            names: self.entrants.iter().map(|(name, _)| name.clone()).collect(),
+            records: self.records.clone(),
+        };
+        let ratings = result.ratings();
+        let shift = RANDOM_RATING - ratings[0];
+        result
+            .names
+            .into_iter()
+            .zip(ratings)
+            .map(|(entrant, rating)| Rating {
+                episode,
+                entrant,
+                rating: rating + shift,
+            })
+            .collect()
+    }
+
+    pub(crate) fn save(&self) -> Result<SavedLeague, String> {
+        let snapshots = self.entrants[BASELINES.len()..]
+            .iter()
+            .map(|(name, snapshot)| {
+                let state = snapshot
+                    .save_state()
+                    .ok_or_else(|| format!("the league's {} can't be checkpointed", name))?;
+                Ok((name.clone(), state))
+            })
+            .collect::<Result<_, String>>()?;
+        Ok(SavedLeague {
+            snapshots,
+            records: self.records.clone(),
+        })
+    }
+
+    // The league `saved` describes, with its snapshots restored into the
+    // agents `snapshots` makes, one for each.
+    pub(crate) fn restore(
+        saved: SavedLeague,
+        mut snapshot: impl FnMut() -> Result<Box<dyn Agent>, String>,
+    ) -> Result<Self, String> {
+        let mut league = RatingLeague::new();
+        for (name, state) in saved.snapshots {
+            let mut agent = snapshot()?;
+            agent.restore_state(&state)?;
+            league.entrants.push((name, agent));
+        }
+        if saved.records.len() != league.entrants.len() {
+            return Err("the checkpoint's league doesn't match its snapshots".to_string());
+        }
+        league.records = saved.records;
+        Ok(league)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{play_game, RLAgent, RLConfig};
+
+    #[test]
+    fn a_trained_snapshot_outrates_an_untrained_one() {
+        let mut league = RatingLeague::new();
+        let frozen = |agent: &RLAgent| agent.snapshot().unwrap();
+        let mut x = RLAgent::new(RLConfig::default(), Some(1));
+        let mut o = RLAgent::new(RLConfig::default(), Some(2));
+        league.add(
+            "untrained".to_string(),
+            Box::new(SidedAgent::new(frozen(&x), frozen(&o))),
+            100,
+            Some(3),
+            0,
+        );
+        for _ in 0..5000 {
+            play_game(&mut x, &mut o).unwrap();
+        }
+        let ratings = league.add(
+            "trained".to_string(),
+            Box::new(SidedAgent::new(frozen(&x), frozen(&o))),
+            100,
+            Some(3),
+            5000,
+        );
+        let names: Vec<&str> = ratings.iter().map(|r| r.entrant.as_str()).collect();
+        assert_eq!(
+            names,
+            ["random", "heuristic", "minimax", "untrained", "trained"]
+        );
+        assert_eq!(ratings[0].rating, RANDOM_RATING);
+        assert!(ratings.iter().all(|r| r.episode == 5000));
+        assert!(ratings[4].rating > ratings[3].rating, "{:?}", ratings);
+        // The trained snapshot played the untrained one as it joined.
+        let (wins, losses, draws) = league.records[4][3];
+        assert_eq!(wins + losses + draws, 100);
+        assert_eq!(league.records[3][4], (losses, wins, draws));
+    }
+}
@@ -0,0 +1,1047 @@
+use std::collections::HashMap;
+
+use rand::rngs::SmallRng;
+use rand::{Rng, RngCore};
+use rl_core::seeded_rng;
+
+use crate::updates::{
+    action_values, afterstate_key, assert_finite, assert_finite_action_values, assert_finite_table,
+    best_action_value, epsilon_greedy, epsilon_greedy_action, greedy_action, greedy_move,
+    monte_carlo_update, soft_update_target, state_action_key, update_action_value, update_q,
+};
+use crate::{Agent, AgentOutcome, Board, PlayerMarker, RLConfig};
+
+// Q-learning that bootstraps from a separate, slowly changing target table,
+// in the spirit of DQN's fixed target network. Every `update_freq` steps the
+// target becomes a hard copy of the behavior table. With `tau` above 0 it is
+// also blended a fraction `tau` of the way toward it at every step between;
+// `tau` of 0 leaves only the hard copies.
+pub struct DQNStyleAgent {
+    behavior: HashMap<Board, f32>,
+    target: HashMap<Board, f32>,
+    prev_board: Option<Board>,
+    config: RLConfig,
+    update_freq: u32,
+    step: u32,
+    tau: f32,
+    learning_enabled: bool,
+    rng: SmallRng,
+}
+
+impl DQNStyleAgent {
+    pub fn new(config: RLConfig, update_freq: u32, tau: f32, seed: Option<u64>) -> Self {
+        DQNStyleAgent {
+            behavior: HashMap::new(),
+            target: HashMap::new(),
+            prev_board: None,
+            config,
+            update_freq,
+            step: 0,
+            tau,
+            learning_enabled: true,
+            rng: seeded_rng(seed),
+        }
+    }
+}
+
+impl Agent for DQNStyleAgent {
+    fn choose_move(&mut self, board: &Board, player: PlayerMarker) -> usize {
+        let mut best_move = None;
+        let mut best_value = f32::MIN;
+        let mut target_value = f32::MIN;
+        for i in board.available_moves() {
+            let eval_board = afterstate_key(board, i, player, self.config.symmetry);
+            let value = *self
+                .behavior
+                .get(&eval_board)
+                .unwrap_or(&self.config.initial_value);
+            if value > best_value {
+                best_value = value;
+                best_move = Some(i);
+            }
+            target_value = target_value.max(
+                *self
+                    .target
+                    .get(&eval_board)
+                    .unwrap_or(&self.config.initial_value),
+            );
+        }
+        let best_move = best_move.expect("No available moves");
+        if !self.learning_enabled {
+            return best_move;
+        }
+        // Off-policy, like Q-learning: the backup bootstraps from the target's
+        // best move whether or not this move explores.
+        let chosen = if self.rng.random::<f32>() < self.config.epsilon.value(self.step) {
+            let available: Vec<usize> = board.available_moves().collect();
+            let index = self.rng.next_u32() as usize % available.len();
+            *available.get(index).expect("Board is full")
+        } else {
+            best_move
+        };
+        if let Some(prev_board) = self.prev_board {
+            update_q(
+                &mut self.behavior,
+                self.config.initial_value,
+                &prev_board,
+                self.config.gamma * target_value,
+                self.config.learning_rate,
+            );
+        }
+        self.step += 1;
+        if self.step.is_multiple_of(self.update_freq) {
+            self.target.clone_from(&self.behavior);
+        } else if self.tau > 0.0 {
+            soft_update_target(&self.behavior, &mut self.target, self.tau);
+        }
+        self.prev_board = Some(afterstate_key(board, chosen, player, self.config.symmetry));
+        chosen
+    }
+
+    fn observe_result(&mut self, outcome: AgentOutcome, _board: &Board) {
+        if let Some(prev_board) = self.prev_board.take() {
+            if !self.learning_enabled {
+                return;
+            }
+            update_q(
+                &mut self.behavior,
+                self.config.initial_value,
+                &prev_board,
+                self.config.reward(outcome),
+                self.config.learning_rate,
+            );
+        }
+    }
+
+    fn assert_finite_q_values(&self) {
+        assert_finite_table(&self.behavior);
+        assert_finite_table(&self.target);
+    }
+
+    fn freeze(&mut self) {
+        self.learning_enabled = false;
+    }
+
+    fn unfreeze(&mut self) {
+        self.learning_enabled = true;
+    }
+
+    fn is_learning(&self) -> bool {
+        self.learning_enabled
+    }
+
+    fn reseed(&mut self, seed: u64) {
+        self.rng = seeded_rng(Some(seed));
+    }
+}
+
+// Action values keyed on (state, action) rather than on afterstates, with
+// the state in canonical form when `RLConfig::symmetry` is on.
+pub type ActionValues = HashMap<(Board, usize), f32>;
+
+// Off-policy TD control over (state, action) pairs: Q(s, a) is backed up toward
+// the best action value of the agent's next state, whatever it plays there.
+pub struct QLearningAgent {
+    q_table: ActionValues,
+    // The state-action key awaiting its backup; `None` before the first move.
+    prev_key: Option<(Board, usize)>,
+    config: RLConfig,
+    step: u32,
+    learning_enabled: bool,
+    rng: SmallRng,
+}
+
+impl QLearningAgent {
+    pub fn new(config: RLConfig, seed: Option<u64>) -> Self {
+        QLearningAgent {
+            q_table: HashMap::new(),
+            prev_key: None,
+            config,
+            step: 0,
+            learning_enabled: true,
+            rng: seeded_rng(seed),
+        }
+    }
+
+    pub fn q_table(&self) -> &ActionValues {
+        &self.q_table
+    }
+}
+
+impl Agent for QLearningAgent {
+    fn choose_move(&mut self, board: &Board, _player: PlayerMarker) -> usize {
+        let symmetric = self.config.symmetry;
+        if !self.learning_enabled {
+            return greedy_action(&self.q_table, self.config.initial_value, board, symmetric);
+        }
+        let epsilon = self.config.epsilon.value(self.step);
+        self.step += 1;
+        if let Some(prev_key) = self.prev_key {
+            let best =
+                best_action_value(&self.q_table, self.config.initial_value, board, symmetric);
+            update_action_value(
+                &mut self.q_table,
+                self.config.initial_value,
+                prev_key,
+                self.config.gamma * best,
+                self.config.learning_rate,
+            );
+        }
+        let chosen = epsilon_greedy_action(
+            &self.q_table,
+            self.config.initial_value,
+            board,
+            epsilon,
+            symmetric,
+            &mut self.rng,
+        );
+        self.prev_key = Some(state_action_key(board, chosen, symmetric));
+        chosen
+    }
+
+    fn observe_result(&mut self, outcome: AgentOutcome, _board: &Board) {
+        if let Some(prev_key) = self.prev_key.take() {
+            if self.learning_enabled {
+                update_action_value(
+                    &mut self.q_table,
+                    self.config.initial_value,
+                    prev_key,
+                    self.config.reward(outcome),
+                    self.config.learning_rate,
+                );
+            }
+        }
+    }
+
+    fn assert_finite_q_values(&self) {
+        assert_finite_action_values(&self.q_table);
+    }
+
+    fn freeze(&mut self) {
+        self.learning_enabled = false;
+    }
+
+    fn unfreeze(&mut self) {
+        self.learning_enabled = true;
+    }
+
+    fn is_learning(&self) -> bool {
+        self.learning_enabled
+    }
+
+    fn reseed(&mut self, seed: u64) {
+        self.rng = seeded_rng(Some(seed));
+    }
+
+    fn move_values(&self, board: &Board, _player: PlayerMarker) -> Option<Vec<(usize, f32)>> {
+        Some(action_values(
+            &self.q_table,
+            self.config.initial_value,
+            board,
+            self.config.symmetry,
+        ))
+    }
+}
+
+// On-policy TD control over (state, action) pairs: Q(s, a) is backed up toward
+// the value of the action actually played next, exploratory or not.
+pub struct SarsaAgent {
+    q_table: ActionValues,
+    // The state-action key awaiting its backup; `None` before the first move.
+    prev_key: Option<(Board, usize)>,
+    config: RLConfig,
+    step: u32,
+    learning_enabled: bool,
+    rng: SmallRng,
+}
+
+impl SarsaAgent {
+    pub fn new(config: RLConfig, seed: Option<u64>) -> Self {
+        SarsaAgent {
+            q_table: HashMap::new(),
+            prev_key: None,
+            config,
+            step: 0,
+            learning_enabled: true,
+            rng: seeded_rng(seed),
+        }
+    }
+
+    pub fn q_table(&self) -> &ActionValues {
+        &self.q_table
+    }
+}
+
+impl Agent for SarsaAgent {
+    fn choose_move(&mut self, board: &Board, _player: PlayerMarker) -> usize {
+        let symmetric = self.config.symmetry;
+        if !self.learning_enabled {
+            return greedy_action(&self.q_table, self.config.initial_value, board, symmetric);
+        }
+        let epsilon = self.config.epsilon.value(self.step);
+        self.step += 1;
+        let chosen = epsilon_greedy_action(
+            &self.q_table,
+            self.config.initial_value,
+            board,
+            epsilon,
+            symmetric,
+            &mut self.rng,
+        );
+        let chosen_key = state_action_key(board, chosen, symmetric);
+        if let Some(prev_key) = self.prev_key {
+            let chosen_value = *self
+                .q_table
+                .get(&chosen_key)
+                .unwrap_or(&self.config.initial_value);
+            update_action_value(
+                &mut self.q_table,
+                self.config.initial_value,
+                prev_key,
+                self.config.gamma * chosen_value,
+                self.config.learning_rate,
+            );
+        }
+        self.prev_key = Some(chosen_key);
+        chosen
+    }
+
+    fn observe_result(&mut self, outcome: AgentOutcome, _board: &Board) {
+        if let Some(prev_key) = self.prev_key.take() {
+            if self.learning_enabled {
+                update_action_value(
+                    &mut self.q_table,
+                    self.config.initial_value,
+                    prev_key,
+                    self.config.reward(outcome),
+                    self.config.learning_rate,
+                );
+            }
+        }
+    }
+
+    fn assert_finite_q_values(&self) {
+        assert_finite_action_values(&self.q_table);
+    }
+
+    fn freeze(&mut self) {
+        self.learning_enabled = false;
+    }
+
+    fn unfreeze(&mut self) {
+        self.learning_enabled = true;
+    }
+
+    fn is_learning(&self) -> bool {
+        self.learning_enabled
+    }
+
+    fn reseed(&mut self, seed: u64) {
+        self.rng = seeded_rng(Some(seed));
+    }
+
+    fn move_values(&self, board: &Board, _player: PlayerMarker) -> Option<Vec<(usize, f32)>> {
+        Some(action_values(
+            &self.q_table,
+            self.config.initial_value,
+            board,
+            self.config.symmetry,
+        ))
+    }
+}
+
+// Plays out a whole game without learning, then moves every afterstate it
+// visited toward the final reward.
+pub struct MonteCarloAgent {
+    q_table: HashMap<Board, f32>,
+    episode_states: Vec<Board>,
+    config: RLConfig,
+    step: u32,
+    learning_enabled: bool,
+    rng: SmallRng,
+}
+
+impl MonteCarloAgent {
+    pub fn new(config: RLConfig, seed: Option<u64>) -> Self {
+        MonteCarloAgent {
+            q_table: HashMap::new(),
+            episode_states: Vec::new(),
+            config,
+            step: 0,
+            learning_enabled: true,
+            rng: seeded_rng(seed),
+        }
+    }
+}
+
+impl Agent for MonteCarloAgent {
+    fn choose_move(&mut self, board: &Board, player: PlayerMarker) -> usize {
+        if !self.learning_enabled {
+            return greedy_move(board, player, self.config.symmetry, |key| {
+                *self.q_table.get(&key).unwrap_or(&self.config.initial_value)
+            });
+        }
+        let epsilon = self.config.epsilon.value(self.step);
+        self.step += 1;
+        let chosen = epsilon_greedy(
+            &self.q_table,
+            self.config.initial_value,
+            board,
+            player,
+            epsilon,
+            self.config.symmetry,
+            &mut self.rng,
+        );
+        self.episode_states
+            .push(afterstate_key(board, chosen, player, self.config.symmetry));
+        chosen
+    }
+
+    fn observe_result(&mut self, outcome: AgentOutcome, _board: &Board) {
+        if self.learning_enabled {
+            monte_carlo_update(
+                &mut self.q_table,
+                &mut self.episode_states,
+                self.config.reward(outcome),
+                &self.config,
+            );
+        }
+    }
+
+    fn assert_finite_q_values(&self) {
+        assert_finite_table(&self.q_table);
+    }
+
+    fn freeze(&mut self) {
+        self.learning_enabled = false;
+    }
+
+    fn unfreeze(&mut self) {
+        self.learning_enabled = true;
+    }
+
+    fn is_learning(&self) -> bool {
+        self.learning_enabled
+    }
+
+    fn reseed(&mut self, seed: u64) {
+        self.rng = seeded_rng(Some(seed));
+    }
+}
+
+// Double Q-learning: one table picks the greedy next move and the other
+// values it, which removes the maximization bias of a single table. Moves
+// are chosen from the sum of both tables.
+pub struct DoubleQLAgent {
+    q_a: HashMap<Board, f32>,
+    q_b: HashMap<Board, f32>,
+    prev_board: Option<Board>,
+    // Which table the most recent update went to; redrawn on every update.
+    use_a: bool,
+    config: RLConfig,
+    step: u32,
+    learning_enabled: bool,
+    rng: SmallRng,
+}
+
+impl DoubleQLAgent {
+    pub fn new(config: RLConfig, seed: Option<u64>) -> Self {
+        DoubleQLAgent {
+            q_a: HashMap::new(),
+            q_b: HashMap::new(),
+            prev_board: None,
+            use_a: true,
+            config,
+            step: 0,
+            learning_enabled: true,
+            rng: seeded_rng(seed),
+        }
+    }
+}
+
+impl Agent for DoubleQLAgent {
+    fn choose_move(&mut self, board: &Board, player: PlayerMarker) -> usize {
+        let greedy = greedy_move(board, player, self.config.symmetry, |key| {
+            self.q_a.get(&key).unwrap_or(&self.config.initial_value)
+                + self.q_b.get(&key).unwrap_or(&self.config.initial_value)
+        });
+        if !self.learning_enabled {
+            return greedy;
+        }
+        let epsilon = self.config.epsilon.value(self.step);
+        self.step += 1;
+        let chosen = if self.rng.random::<f32>() < epsilon {
+            let available: Vec<usize> = board.available_moves().collect();
+            available[self.rng.next_u32() as usize % available.len()]
+        } else {
+            greedy
+        };
+        self.use_a = self.rng.random();
+        let (selector, evaluator) = if self.use_a {
+            (&mut self.q_a, &self.q_b)
+        } else {
+            (&mut self.q_b, &self.q_a)
+        };
+        let next_move = greedy_move(board, player, self.config.symmetry, |key| {
+            *selector.get(&key).unwrap_or(&self.config.initial_value)
+        });
+        let target = *evaluator
+            .get(&afterstate_key(
+                board,
+                next_move,
+                player,
+                self.config.symmetry,
+            ))
+            .unwrap_or(&self.config.initial_value);
+        if let Some(prev_board) = self.prev_board {
+            update_q(
+                selector,
+                self.config.initial_value,
+                &prev_board,
+                self.config.gamma * target,
+                self.config.learning_rate,
+            );
+        }
+        self.prev_board = Some(afterstate_key(board, chosen, player, self.config.symmetry));
+        chosen
+    }
+
+    fn observe_result(&mut self, outcome: AgentOutcome, _board: &Board) {
+        if !self.learning_enabled {
+            return;
+        }
+        self.use_a = self.rng.random();
+        let table = if self.use_a {
+            &mut self.q_a
+        } else {
+            &mut self.q_b
+        };
+        if let Some(prev_board) = self.prev_board.take() {
+            update_q(
+                table,
+                self.config.initial_value,
+                &prev_board,
+                self.config.reward(outcome),
+                self.config.learning_rate,
+            );
+        }
+    }
+
+    fn assert_finite_q_values(&self) {
+        assert_finite_table(&self.q_a);
+        assert_finite_table(&self.q_b);
+    }
+
+    fn freeze(&mut self) {
+        self.learning_enabled = false;
+    }
+
+    fn unfreeze(&mut self) {
+        self.learning_enabled = true;
+    }
+
+    fn is_learning(&self) -> bool {
+        self.learning_enabled
+    }
+
+    fn reseed(&mut self, seed: u64) {
+        self.rng = seeded_rng(Some(seed));
+    }
+}
+
+// REINFORCE with a baseline. The policy is a softmax over one logit per cell,
+// masked to the empty cells, so it learns which cells are worth taking rather
+// than a value for every position.
+pub struct PolicyGradientAgent {
+    logits: [f32; 9],
+    baseline: f32,
+    // The chosen cell and the whole of π at each of this episode's moves, so
+    // every move's gradient is taken under the policy that made it even after
+    // earlier moves' updates have changed the logits.
+    episode_policies: Vec<(usize, [f32; 9])>,
+    episode_rewards: Vec<f32>,
+    // The board each move was made on, to recover which cells were masked.
+    episode_boards: Vec<Board>,
+    config: RLConfig,
+    // Cleared by `freeze`, which also makes it play its most likely move.
+    learning_enabled: bool,
+    // Samples moves from the policy.
+    rng: SmallRng,
+}
+
+impl PolicyGradientAgent {
+    pub fn new(config: RLConfig, seed: Option<u64>) -> Self {
+        PolicyGradientAgent {
+            logits: [0.0; 9],
+            baseline: 0.0,
+            episode_policies: Vec::new(),
+            episode_rewards: Vec::new(),
+            episode_boards: Vec::new(),
+            config,
+            learning_enabled: true,
+            rng: seeded_rng(seed),
+        }
+    }
+
+    // π(·|board): zero for occupied cells.
+    pub fn policy(&self, board: &Board) -> [f32; 9] {
+        let max_logit = board
+            .available_moves()
+            .map(|i| self.logits[i])
+            .fold(f32::MIN, f32::max);
+        let mut probs = [0.0; 9];
+        for i in board.available_moves() {
+            probs[i] = (self.logits[i] - max_logit).exp();
+        }
+        let total: f32 = probs.iter().sum();
+        probs.iter_mut().for_each(|p| *p /= total);
+        probs
+    }
+}
+
+impl Agent for PolicyGradientAgent {
+    fn choose_move(&mut self, board: &Board, _player: PlayerMarker) -> usize {
+        let probs = self.policy(board);
+        if !self.learning_enabled {
+            return board
+                .available_moves()
+                .fold(None, |best: Option<usize>, i| match best {
+                    Some(b) if probs[b] >= probs[i] => Some(b),
+                    _ => Some(i),
+                })
+                .expect("No available moves");
+        }
+        let mut remaining = self.rng.random::<f32>();
+        let mut chosen = None;
+        for i in board.available_moves() {
+            chosen = Some(i);
+            if remaining < probs[i] {
+                break;
+            }
+            remaining -= probs[i];
+        }
+        let chosen = chosen.expect("No available moves");
+        self.episode_policies.push((chosen, probs));
+        self.episode_rewards.push(0.0);
+        self.episode_boards.push(*board);
+        chosen
+    }
+
+    fn observe_result(&mut self, outcome: AgentOutcome, _board: &Board) {
+        if !self.learning_enabled {
+            return;
+        }
+        if let Some(last) = self.episode_rewards.last_mut() {
+            *last = self.config.reward(outcome);
+        }
+        let mut returns = vec![0.0; self.episode_rewards.len()];
+        let mut g = 0.0;
+        for t in (0..returns.len()).rev() {
+            g = self.episode_rewards[t] + self.config.gamma * g;
+            returns[t] = g;
+        }
+        let alpha = self.config.learning_rate;
+        for ((&(chosen, probs), board), &g) in self
+            .episode_policies
+            .iter()
+            .zip(&self.episode_boards)
+            .zip(&returns)
+        {
+            let advantage = g - self.baseline;
+            for i in board.available_moves() {
+                self.logits[i] += if i == chosen {
+                    alpha * advantage * (1.0 - probs[i])
+                } else {
+                    -alpha * advantage * probs[i]
+                };
+            }
+            self.baseline += alpha * (g - self.baseline);
+        }
+        self.episode_policies.clear();
+        self.episode_rewards.clear();
+        self.episode_boards.clear();
+    }
+
+    fn assert_finite_q_values(&self) {
+        for (i, logit) in self.logits.iter().enumerate() {
+            assert_finite(*logit, &format!("logit for cell {}", i));
+        }
+        assert_finite(self.baseline, "baseline");
+    }
+
+    fn freeze(&mut self) {
+        self.learning_enabled = false;
+    }
+
+    fn unfreeze(&mut self) {
+        self.learning_enabled = true;
+    }
+
+    fn is_learning(&self) -> bool {
+        self.learning_enabled
+    }
+
+    fn reseed(&mut self, seed: u64) {
+        self.rng = seeded_rng(Some(seed));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::table_bytes;
+    use crate::{play_game, win_rate_vs_random, DecaySchedule, RLAgent, RandomAgent};
+
+    // Trains `agent` against random play, freezes it, and checks that `table`
+    // reads the same before and after a batch of evaluation games.
+    fn assert_frozen_table_unchanged<A: Agent>(mut agent: A, table: impl Fn(&A) -> Vec<u8>) {
+        let mut random = RandomAgent::new(Some(7));
+        for _ in 0..200 {
+            play_game(&mut agent, &mut random).unwrap();
+        }
+        agent.freeze();
+        assert!(!agent.is_learning());
+        let before = table(&agent);
+        assert!(!before.is_empty());
+        for _ in 0..100 {
+            play_game(&mut agent, &mut random).unwrap();
+            play_game(&mut random, &mut agent).unwrap();
+        }
+        assert!(table(&agent) == before);
+        agent.unfreeze();
+        assert!(agent.is_learning());
+    }
+
+    #[test]
+    fn frozen_agents_leave_their_tables_alone() {
+        let config = RLConfig::default();
+        let copied = |table: &HashMap<Board, f32>| table_bytes(table.iter().map(|(&k, &v)| (k, v)));
+        let copied_actions =
+            |table: &ActionValues| table_bytes(table.iter().map(|(&k, &v)| (k, v)));
+        assert_frozen_table_unchanged(RLAgent::new(config.clone(), Some(1)), |agent| {
+            let mut bytes = copied(&agent.q_table);
+            bytes.extend(table_bytes(
+                agent.visit_counts.iter().map(|(&k, &v)| (k, v as f32)),
+            ));
+            bytes
+        });
+        assert_frozen_table_unchanged(QLearningAgent::new(config.clone(), Some(1)), |agent| {
+            copied_actions(&agent.q_table)
+        });
+        assert_frozen_table_unchanged(SarsaAgent::new(config.clone(), Some(1)), |agent| {
+            copied_actions(&agent.q_table)
+        });
+        assert_frozen_table_unchanged(
+            DQNStyleAgent::new(config.clone(), 10, 0.0, Some(1)),
+            |agent| [copied(&agent.behavior), copied(&agent.target)].concat(),
+        );
+        assert_frozen_table_unchanged(MonteCarloAgent::new(config.clone(), Some(1)), |agent| {
+            copied(&agent.q_table)
+        });
+        assert_frozen_table_unchanged(DoubleQLAgent::new(config.clone(), Some(1)), |agent| {
+            [copied(&agent.q_a), copied(&agent.q_b)].concat()
+        });
+        assert_frozen_table_unchanged(PolicyGradientAgent::new(config, Some(1)), |agent| {
+            table_bytes(
+                agent
+                    .logits
+                    .iter()
+                    .enumerate()
+                    .map(|(i, &logit)| (i, logit))
+                    .chain([(9, agent.baseline)]),
+            )
+        });
+    }
+
+    #[test]
+    fn dqn_style_agent_backs_up_exploratory_moves_and_refreshes_its_target_on_schedule() {
+        let config = RLConfig {
+            epsilon: DecaySchedule::Constant(1.0),
+            ..RLConfig::default()
+        };
+        let mut agent = DQNStyleAgent::new(config, 4, 0.5, Some(1));
+        let board = Board::new();
+        // Every move explores, yet each afterstate but the last is backed up
+        // on the next move.
+        let first = agent.choose_move(&board, PlayerMarker::X);
+        assert_eq!(agent.step, 1);
+        assert!(agent.behavior.is_empty());
+        let board = board.with_move(first, PlayerMarker::X);
+        let reply = board.available_moves().next().unwrap();
+        let board = board.with_move(reply, PlayerMarker::O);
+        agent.choose_move(&board, PlayerMarker::X);
+        assert_eq!(agent.step, 2);
+        assert_eq!(agent.behavior.len(), 1);
+        // Between refreshes the target is blended halfway toward the behavior
+        // table at every step.
+        assert_eq!(agent.target.len(), 1);
+        for (state, value) in &agent.behavior {
+            assert_eq!(agent.target[state], 0.5 * value);
+        }
+        // On the fourth step it is a hard copy.
+        agent.step = 3;
+        agent.choose_move(&board, PlayerMarker::X);
+        assert!(agent.target == agent.behavior);
+    }
+
+    #[test]
+    fn without_tau_the_target_holds_between_hard_copies() {
+        let config = RLConfig {
+            epsilon: DecaySchedule::Constant(1.0),
+            ..RLConfig::default()
+        };
+        let mut agent = DQNStyleAgent::new(config, 3, 0.0, Some(1));
+        let mut random = RandomAgent::new(Some(2));
+        while agent.step % 3 != 2 || agent.behavior.is_empty() {
+            play_game(&mut agent, &mut random).unwrap();
+        }
+        let target = agent.target.clone();
+        let board: Board = "X.O......".parse().unwrap();
+        agent.choose_move(&board, PlayerMarker::X);
+        assert!(agent.target != target);
+        assert!(agent.target == agent.behavior);
+        let target = agent.target.clone();
+        let board: Board = "X.O.X.O..".parse().unwrap();
+        agent.choose_move(&board, PlayerMarker::X);
+        assert!(agent.target == target);
+    }
+
+    #[test]
+    fn policy_gradient_steps_use_the_policy_each_move_was_made_under() {
+        let config = RLConfig {
+            learning_rate: 0.5,
+            ..RLConfig::default()
+        };
+        let mut agent = PolicyGradientAgent::new(config.clone(), Some(1));
+        let first = Board::new();
+        let a = agent.choose_move(&first, PlayerMarker::X);
+        let second = first
+            .with_move(a, PlayerMarker::X)
+            .with_move((a + 1) % 9, PlayerMarker::O);
+        let b = agent.choose_move(&second, PlayerMarker::X);
+        agent.observe_result(AgentOutcome::Win, &second);
+
+        // Both moves were made under uniform policies, over 9 and 7 cells.
+        let reward = config.reward(AgentOutcome::Win);
+        let alpha = config.learning_rate;
+        let mut logits = [0.0f32; 9];
+        let mut baseline = 0.0f32;
+        for (board, chosen, g, p) in [
+            (first, a, config.gamma * reward, 1.0 / 9.0),
+            (second, b, reward, 1.0 / 7.0),
+        ] {
+            let advantage = g - baseline;
+            for i in board.available_moves() {
+                logits[i] += if i == chosen {
+                    alpha * advantage * (1.0 - p)
+                } else {
+                    -alpha * advantage * p
+                };
+            }
+            baseline += alpha * (g - baseline);
+        }
+        for (i, (&learned, &expected)) in agent.logits.iter().zip(&logits).enumerate() {
+            assert!((learned - expected).abs() < 1e-6, "cell {}", i);
+        }
+        assert!((agent.baseline - baseline).abs() < 1e-6);
+    }
+
+    #[test]
+    fn sarsa_backs_up_toward_the_move_it_plays_next() {
+        let config = RLConfig {
+            epsilon: DecaySchedule::Constant(1.0),
+            learning_rate: 1.0,
+            gamma: 1.0,
+            symmetry: false,
+            ..RLConfig::default()
+        };
+        let mut agent = SarsaAgent::new(config, Some(3));
+        let first = Board::new();
+        let opening = agent.choose_move(&first, PlayerMarker::X);
+        let second = first
+            .with_move(opening, PlayerMarker::X)
+            .with_move((opening + 1) % 9, PlayerMarker::O);
+        for i in second.available_moves() {
+            agent.q_table.insert((second, i), i as f32 / 10.0);
+        }
+        // Every move explores, and the backup follows it rather than the
+        // greedy move Q-learning would bootstrap from.
+        for _ in 0..5 {
+            agent.prev_key = Some((first, opening));
+            let next = agent.choose_move(&second, PlayerMarker::X);
+            assert!((agent.q_table[&(first, opening)] - next as f32 / 10.0).abs() < 1e-6);
+        }
+        agent.observe_result(AgentOutcome::Loss, &second);
+        assert_eq!(agent.prev_key, None);
+
+        let config = RLConfig {
+            epsilon: DecaySchedule::Constant(0.1),
+            ..RLConfig::default()
+        };
+        let rate = win_rate_vs_random(&mut SarsaAgent::new(config, Some(4)), 3000, 500, Some(5));
+        assert!(rate > 0.6, "{}", rate);
+    }
+
+    #[test]
+    fn monte_carlo_learns_only_at_the_end_of_the_game() {
+        let config = RLConfig {
+            epsilon: DecaySchedule::Constant(0.0),
+            learning_rate: 1.0,
+            gamma: 0.5,
+            symmetry: false,
+            ..RLConfig::default()
+        };
+        let mut agent = MonteCarloAgent::new(config.clone(), Some(1));
+        let mut board = Board::new();
+        for reply in [1, 5, 7] {
+            let cell = agent.choose_move(&board, PlayerMarker::X);
+            board = board.with_move(cell, PlayerMarker::X);
+            if board.available(reply) {
+                board = board.with_move(reply, PlayerMarker::O);
+            }
+            assert!(agent.q_table.is_empty());
+        }
+        let states = agent.episode_states.clone();
+        agent.observe_result(AgentOutcome::Win, &board);
+        assert!(agent.episode_states.is_empty());
+        // Each afterstate is worth the reward discounted once per later move.
+        let reward = config.reward(AgentOutcome::Win);
+        for (t, state) in states.iter().enumerate() {
+            let expected = reward * config.gamma.powi((states.len() - 1 - t) as i32);
+            assert!((agent.q_table[state] - expected).abs() < 1e-6);
+        }
+
+        let config = RLConfig {
+            epsilon: DecaySchedule::Constant(0.1),
+            ..RLConfig::default()
+        };
+        let rate = win_rate_vs_random(
+            &mut MonteCarloAgent::new(config, Some(2)),
+            3000,
+            500,
+            Some(3),
+        );
+        assert!(rate > 0.6, "{}", rate);
+    }
+
+    #[test]
+    fn double_q_learning_updates_one_table_at_a_time() {
+        let mut agent = DoubleQLAgent::new(RLConfig::default(), Some(1));
+        let mut random = RandomAgent::new(Some(2));
+        for _ in 0..50 {
+            play_game(&mut agent, &mut random).unwrap();
+        }
+        // A one-move game backs up only at the end, into the table drawn there.
+        let board = Board::new();
+        for _ in 0..20 {
+            let (a, b) = (agent.q_a.clone(), agent.q_b.clone());
+            agent.choose_move(&board, PlayerMarker::X);
+            agent.observe_result(AgentOutcome::Win, &board);
+            assert!((agent.q_a != a) != (agent.q_b != b));
+            assert_eq!(agent.q_a != a, agent.use_a);
+        }
+        assert!(!agent.q_a.is_empty() && !agent.q_b.is_empty());
+
+        let rate = win_rate_vs_random(&mut agent, 3000, 500, Some(3));
+        assert!(rate > 0.6, "{}", rate);
+    }
+
+    #[test]
+    fn sarsa_values_exploration_more_warily_than_q_learning() {
+        let config = RLConfig {
+            epsilon: DecaySchedule::Constant(0.3),
+            ..RLConfig::default()
+        };
+        let mean = |table: &ActionValues| table.values().sum::<f32>() / table.len() as f32;
+        let mut sarsa = SarsaAgent::new(config.clone(), Some(1));
+        let mut q_learning = QLearningAgent::new(config, Some(1));
+        let sarsa_rate = win_rate_vs_random(&mut sarsa, 5000, 500, Some(2));
+        let q_rate = win_rate_vs_random(&mut q_learning, 5000, 500, Some(2));
+        // SARSA's values include the cost of its own exploratory moves, which
+        // Q-learning's backups from the best move leave out.
+        assert!(
+            mean(&sarsa.q_table) < mean(&q_learning.q_table),
+            "{} {}",
+            mean(&sarsa.q_table),
+            mean(&q_learning.q_table)
+        );
+        assert!(
+            sarsa_rate > 0.5 && q_rate > 0.5,
+            "{} {}",
+            sarsa_rate,
+            q_rate
+        );
+    }
+
+    #[test]
+    fn monte_carlo_values_settle_sooner_near_the_end_of_the_game() {
+        // The value of `start`, an afterstate of X's, after 30 games played
+        // on from it at random by both sides.
+        let estimate = |start: Board, seed: u64| {
+            let config = RLConfig {
+                epsilon: DecaySchedule::Constant(1.0),
+                initial_value: 0.0,
+                gamma: 1.0,
+                symmetry: false,
+                ..RLConfig::default()
+            };
+            let mut agent = MonteCarloAgent::new(config, Some(seed));
+            let mut random = RandomAgent::new(Some(seed + 1000));
+            for _ in 0..30 {
+                agent.episode_states.push(start);
+                let mut board = start;
+                let outcome = loop {
+                    let player = board.player_to_move();
+                    let cell = if player == PlayerMarker::X {
+                        agent.choose_move(&board, player)
+                    } else {
+                        random.choose_move(&board, player)
+                    };
+                    board = board.with_move(cell, player);
+                    if board.check_winner(PlayerMarker::X) {
+                        break AgentOutcome::Win;
+                    } else if board.check_winner(PlayerMarker::O) {
+                        break AgentOutcome::Loss;
+                    } else if board.is_full() {
+                        break AgentOutcome::Draw;
+                    }
+                };
+                agent.observe_result(outcome, &board);
+            }
+            agent.q_table[&start]
+        };
+        // With the same number of updates, a position a few moves from the
+        // end has less varied returns than an opening, so its estimates
+        // agree more closely from run to run.
+        let spread = |start: &str| {
+            let estimates: Vec<f32> = (0..100)
+                .map(|seed| estimate(start.parse().unwrap(), seed))
+                .collect();
+            let mean = estimates.iter().sum::<f32>() / estimates.len() as f32;
+            (estimates.iter().map(|e| (e - mean).powi(2)).sum::<f32>() / estimates.len() as f32)
+                .sqrt()
+        };
+        let (opening, late) = (spread("....X...."), spread("...OX.XOX"));
+        assert!(late < opening / 1.25, "{} {}", late, opening);
+    }
+
+    #[test]
+    fn double_q_values_never_exceed_the_largest_reward() {
+        let config = RLConfig {
+            epsilon: DecaySchedule::Constant(0.1),
+            ..RLConfig::default()
+        };
+        let mut agent = DoubleQLAgent::new(config.clone(), Some(1));
+        let mut random = RandomAgent::new(Some(2));
+        for _ in 0..20000 {
+            play_game(&mut agent, &mut random).unwrap();
+        }
+        let max = agent
+            .q_a
+            .values()
+            .chain(agent.q_b.values())
+            .fold(f32::MIN, |max, &q| max.max(q));
+        assert!(max <= config.win_reward, "{}", max);
+        assert!(max > config.initial_value, "{}", max);
+    }
+}
@@ -0,0 +1,91 @@
+mod agents;
+mod board;
+mod config;
+#[cfg(feature = "tui")]
+mod dashboard;
+mod dense;
+mod env;
+mod error;
+mod experiments;
+mod game;
+mod game_log;
+#[cfg(feature = "cli")]
+mod interactive;
+mod interrupt;
+mod league;
+mod learners;
+mod mnk;
+mod progress;
+mod remote;
+mod rl_agent;
+mod session;
+mod table;
+mod tournament;
+mod trainer;
+mod tree;
+mod updates;
+mod visitation;
+
+pub use agents::{
+    Agent, AgentOutcome, HeuristicAgent, MctsAgent, MinimaxAgent, OpeningBook, RandomAgent,
+};
+pub use board::{canonicalize, Board, PlayerMarker, SymmetryOp};
+pub use config::{cosine_anneal, DecaySchedule, ExplorationStrategy, RLConfig};
+#[cfg(feature = "tui")]
+pub use dashboard::Dashboard;
+pub use dense::{DenseTable, PolicyTable};
+pub use env::{Rules, TicTacToeEnv};
+pub use error::GameError;
+pub use experiments::{
+    ab_test, evaluate_policy, rolling_win_rate_variance, win_rate_vs_random, win_speed_vs_random,
+    ABTestResult, PolicyEvaluation, RoleTraining, RoleWinRates, Roles, WinSpeed, EVAL_PASS_RATE,
+};
+pub use game::{
+    export_value_heatmap, play_game, play_game_with_transcript, EpisodeRecord, GameResult,
+    GameTranscript, MatchSummary, Statistics,
+};
+pub use game_log::{
+    append_game, counterfactual, format_game, parse_game, read_game_log, review_move, side_by_side,
+    LoggedGame, MoveReview,
+};
+#[cfg(feature = "cli")]
+pub use interactive::{play_interactive, HumanAgent, InteractiveOutcome};
+pub use interrupt::{catch_interrupts, interrupted, InterruptScope};
+pub use league::Rating;
+pub use learners::{
+    ActionValues, DQNStyleAgent, DoubleQLAgent, MonteCarloAgent, PolicyGradientAgent,
+    QLearningAgent, SarsaAgent,
+};
+pub use mnk::{
+    play_mnk_game, winning_lines, MnkAgent, MnkBoard, MnkGame, MnkRLAgent, MnkRandomAgent,
+};
+pub use progress::Progress;
+pub use remote::RemoteAgent;
+pub use rl_agent::{format_values, Potential, RLAgent};
+pub use session::TicTacToeSession;
+pub use table::{legal_positions, TableStats, LEGAL_POSITIONS};
+pub use tournament::{bonferroni_correct, run_tournament, TournamentResult};
+pub use trainer::{
+    average_evaluations, average_metrics, merge_q_tables, train_parallel, write_metrics_csv,
+    write_ratings_csv, write_seed_averages_csv, EpisodeCallback, EvalOpponent, Evaluation, Metrics,
+    SeedAverage, Trainer, TrainingConfig, TrainingStats,
+};
+pub use tree::{GameTree, TreeNode};
+pub use updates::polyak_update;
+pub use visitation::Visitation;
+
+// Helpers shared by the tests of several modules.
+#[cfg(test)]
+mod tests {
+    use serde::Serialize;
+
+    // Every entry of a table as bytes, in an order that doesn't depend on how
+    // the table iterates.
+    pub(crate) fn table_bytes<K: Serialize>(entries: impl Iterator<Item = (K, f32)>) -> Vec<u8> {
+        let mut entries: Vec<Vec<u8>> = entries
+            .map(|(key, value)| bincode::serialize(&(key, value.to_bits())).unwrap())
+            .collect();
+        entries.sort();
+        entries.concat()
+    }
+}
@@ -1,335 +1,1832 @@
-use rand;
-use std::collections::{hash_map::Entry, HashMap};
-
-use rand::RngCore;
-
-const WINNING_COMBINATIONS: [[usize; 3]; 8] = [
-    [0, 1, 2],
-    [3, 4, 5],
-    [6, 7, 8], // Rows
-    [0, 3, 6],
-    [1, 4, 7],
-    [2, 5, 8], // Columns
-    [0, 4, 8],
-    [2, 4, 6], // Diagonals
-];
-
-const DEFAULT_VALUE: f32 = 0.0;
-
-struct Board {
-    pub spaces: u32,
-}
-
-impl Board {
-    fn new() -> Self {
-        Board {
-            spaces: 0b000000000000000000,
-        }
-    }
-
-    fn at(&self, index: u32) -> char {
-        let x_mask = PlayerMarker::player_mask(&PlayerMarker::X) << (index * 2);
-        let o_mask = PlayerMarker::player_mask(&PlayerMarker::O) << (index * 2);
-        if self.spaces & x_mask == x_mask {
-            'X'
-        } else if self.spaces & o_mask == o_mask {
-            'O'
-        } else {
-            ' '
-        }
-    }
-
-    fn to_string(&self) -> String {
-        format!(
-            "{}|{}|{}\n-----\n{}|{}|{}\n-----\n{}|{}|{}",
-            self.at(0),
-            self.at(1),
-            self.at(2),
-            self.at(3),
-            self.at(4),
-            self.at(5),
-            self.at(6),
-            self.at(7),
-            self.at(8)
-        )
-    }
+mod cli;
+#[cfg(feature = "plot")]
+mod plot;
+
+use std::fs::File;
+use std::io::{self, BufWriter, IsTerminal, Write};
+use std::net::TcpListener;
+use std::ops::Range;
+use std::path::Path;
+use std::process;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+
+use cli::{
+    AgentKind, AgentSpec, Cli, Command, PolicyAction, SavedState, TrainingDisplay, TreeFormat,
+    USAGE,
+};
+use rayon::prelude::*;
+use rl_core::{
+    derive_seed, expand_config, json, mean_and_std_error, write_jsonl, write_sweep_table, Goal,
+    Ranked, Sweep, TrajectoryRecorder,
+};
+use sutton_tic_tac_toe::*;
 
-    fn print(&self) {
-        println!("{}", self.to_string());
+// Self-play games used to train the RL agent when no saved policy exists.
+const TRAINING_GAMES: u32 = 100000;
+
+// Games each training thread plays between merges of the value tables.
+const SYNC_INTERVAL: u32 = 1000;
+
+fn policy_path(player: PlayerMarker) -> &'static Path {
+    match player {
+        PlayerMarker::X => Path::new("x_policy.bin"),
+        PlayerMarker::O => Path::new("o_policy.bin"),
     }
+}
 
-    fn check_winner(&self, player: &PlayerMarker) -> bool {
-        let player_char = PlayerMarker::player_char(player);
-        WINNING_COMBINATIONS
-            .iter()
-            .any(|&combo| combo.iter().all(|&i| self.at(i as u32) == player_char))
+// How the rl agents are trained: on `threads` threads (one per core when
+// `None`), seeded from `seed`, with a progress bar unless `quiet`.
+#[derive(Clone, Copy)]
+struct TrainingRun {
+    threads: Option<usize>,
+    seed: Option<u64>,
+    quiet: bool,
+}
+
+fn train_both(games: u32, config: &RLConfig, run: TrainingRun) -> (RLAgent, RLAgent) {
+    let threads = run
+        .threads
+        .unwrap_or_else(|| std::thread::available_parallelism().map_or(1, |n| n.get()));
+    let mut progress = (!run.quiet).then(|| Progress::new(games as u64));
+    train_parallel(
+        config.clone(),
+        games,
+        threads,
+        SYNC_INTERVAL,
+        run.seed,
+        progress.as_mut(),
+    )
+}
+
+// Trains an X and an O agent by parallel self-play and saves both policies to
+// their default files.
+fn train(games: u32, config: &RLConfig, run: TrainingRun) -> (RLAgent, RLAgent) {
+    let (x_rl, o_rl) = train_both(games, config, run);
+    x_rl.save(policy_path(PlayerMarker::X))
+        .expect("Failed to save policy");
+    o_rl.save(policy_path(PlayerMarker::O))
+        .expect("Failed to save policy");
+    (x_rl, o_rl)
+}
+
+// How much `train` trained for, which is fewer than `games` if Ctrl-C
+// stopped it.
+fn trained_for(games: u32) -> String {
+    if interrupted() {
+        format!("Interrupted before all {} games were played", games)
+    } else {
+        format!("Trained for {} games", games)
     }
+}
 
-    fn is_draw(&self) -> bool {
-        self.spaces & 0b101010101010101010 == 0b101010101010101010
+// The trained RL agent for `player`. An explicit `load_policy` must exist;
+// otherwise the side's default file is used, and training runs first if there
+// is none yet.
+fn trained_rl(
+    player: PlayerMarker,
+    load_policy: Option<&Path>,
+    config: &RLConfig,
+    run: TrainingRun,
+) -> RLAgent {
+    if let Some(path) = load_policy {
+        return RLAgent::load(path).unwrap_or_else(|error| {
+            eprintln!("Error: could not load {}: {}", path.display(), error);
+            process::exit(1);
+        });
     }
+    let path = policy_path(player);
+    if path.exists() {
+        RLAgent::load(path).expect("Failed to load saved policy")
+    } else {
+        let (x_rl, o_rl) = train(TRAINING_GAMES, config, run);
+        match player {
+            PlayerMarker::X => x_rl,
+            PlayerMarker::O => o_rl,
+        }
+    }
+}
 
-    fn available(&self, index: usize) -> bool {
-        let mask = 0b11 << (index * 2);
-        (self.spaces & mask) == 0b0
+fn save_or_exit(agent: &RLAgent, path: &Path) {
+    if let Err(error) = agent.save(path) {
+        eprintln!("Error: could not save {}: {}", path.display(), error);
+        process::exit(1);
     }
+}
+
+// How `build_agent` sets up the agents it creates.
+struct AgentOptions<'a> {
+    show_values: bool,
+    load_policy: Option<&'a Path>,
+    config: &'a RLConfig,
+    // X's agent is seeded with `run.seed` and O's with `run.seed + 1`.
+    run: TrainingRun,
+}
 
-    fn set(&mut self, index: usize, value: &PlayerMarker) {
-        let player_char = PlayerMarker::player_mask(value);
-        self.spaces |= player_char << (index * 2);
+fn build_agent(kind: AgentKind, player: PlayerMarker, options: &AgentOptions) -> Box<dyn Agent> {
+    let config = options.config;
+    let seed = match player {
+        PlayerMarker::X => options.run.seed,
+        PlayerMarker::O => derive_seed(options.run.seed, 1),
+    };
+    match kind {
+        AgentKind::Random => Box::new(RandomAgent::new(seed)),
+        AgentKind::Human => Box::new(HumanAgent),
+        AgentKind::Heuristic => Box::new(HeuristicAgent::new(seed)),
+        AgentKind::Minimax => Box::new(MinimaxAgent::new()),
+        AgentKind::Mcts => Box::new(MctsAgent::new(1000, std::f32::consts::SQRT_2, seed)),
+        AgentKind::Rl => {
+            let mut agent = trained_rl(player, options.load_policy, config, options.run)
+                .with_value_display(options.show_values);
+            if let Some(seed) = seed {
+                agent.reseed(seed);
+            }
+            Box::new(agent)
+        }
+        AgentKind::QLearning => Box::new(QLearningAgent::new(config.clone(), seed)),
+        AgentKind::Sarsa => Box::new(SarsaAgent::new(config.clone(), seed)),
+        AgentKind::MonteCarlo => Box::new(MonteCarloAgent::new(config.clone(), seed)),
+        AgentKind::DoubleQ => Box::new(DoubleQLAgent::new(config.clone(), seed)),
+        AgentKind::Reinforce => Box::new(PolicyGradientAgent::new(config.clone(), seed)),
+        AgentKind::Dqn => Box::new(DQNStyleAgent::new(config.clone(), 1000, 0.0, seed)),
     }
 }
 
-enum PlayerMarker {
-    X,
-    O,
+// Unwraps the result of a game, or reports why it was abandoned and exits.
+fn or_exit<T>(result: Result<T, GameError>) -> T {
+    result.unwrap_or_else(|error| {
+        eprintln!("Error: {}", error);
+        process::exit(1);
+    })
 }
 
-impl PlayerMarker {
-    fn player_char(player: &PlayerMarker) -> char {
-        match player {
-            PlayerMarker::X => 'X',
-            PlayerMarker::O => 'O',
+// Appends `transcript` to the game log at `log`, if there is one.
+fn log_game(log: Option<&Path>, transcript: &GameTranscript) {
+    if let Some(path) = log {
+        if let Err(error) = append_game(path, transcript) {
+            eprintln!("Error: could not write {}: {}", path.display(), error);
+            process::exit(1);
         }
     }
+}
 
-    fn player_mask(player: &PlayerMarker) -> u32 {
-        match player {
-            PlayerMarker::X => 0b11,
-            PlayerMarker::O => 0b10,
+fn play(x: AgentKind, o: AgentKind, games: u32, options: &AgentOptions, log: Option<&Path>) {
+    match (x, o) {
+        (AgentKind::Human, AgentKind::Human) => {
+            return play_human(PlayerMarker::X, None, games, options, log)
         }
+        (AgentKind::Human, _) => return play_human(PlayerMarker::X, Some(o), games, options, log),
+        (_, AgentKind::Human) => return play_human(PlayerMarker::O, Some(x), games, options, log),
+        _ => {}
+    }
+    let mut x_agent = build_agent(x, PlayerMarker::X, options);
+    let mut o_agent = build_agent(o, PlayerMarker::O, options);
+    x_agent.freeze();
+    o_agent.freeze();
+    for _ in 0..games {
+        let (_, transcript) = or_exit(play_game_with_transcript(
+            x_agent.as_mut(),
+            o_agent.as_mut(),
+        ));
+        println!("{}", transcript);
+        log_game(log, &transcript);
     }
 }
 
-enum Agent {
-    Random,
-    Human,
-    RL(HashMap<u32, f32>, u32),
+// Plays up to `games` interactive games of a human as `human` against
+// `opponent`, with hints from the trained rl agent for the human's side, or
+// with no opponent, hot-seat games between two humans with hints for X.
+fn play_human(
+    human: PlayerMarker,
+    opponent: Option<AgentKind>,
+    games: u32,
+    options: &AgentOptions,
+    log: Option<&Path>,
+) {
+    let mut agent = opponent.map(|kind| {
+        let mut agent = build_agent(kind, human.opposite(), options);
+        agent.freeze();
+        agent
+    });
+    let advisor = trained_rl(human, None, options.config, options.run);
+    // Wins for X, wins for O and draws.
+    let mut summary = [0u32; 3];
+    // Ctrl-C ends the input, which quits the game in progress.
+    let _scope = InterruptScope::enter();
+    for _ in 0..games {
+        let (outcome, moves) = play_interactive(
+            agent.as_mut().map(|agent| agent.as_mut() as &mut dyn Agent),
+            human,
+            Some(&advisor),
+            &mut std::io::stdin().lock(),
+        )
+        .unwrap_or_else(|error| {
+            eprintln!("Error: could not read input: {}", error);
+            process::exit(1);
+        });
+        let InteractiveOutcome::Finished(result) = outcome else {
+            break;
+        };
+        match result {
+            GameResult::XWin => summary[0] += 1,
+            GameResult::OWin => summary[1] += 1,
+            GameResult::Draw => summary[2] += 1,
+        }
+        let transcript =
+            GameTranscript::from_moves(moves).expect("an interactive game is a legal game");
+        log_game(log, &transcript);
+    }
+    match (opponent, human) {
+        (None, _) => println!(
+            "X won {}, O won {} and {} were drawn",
+            summary[0], summary[1], summary[2]
+        ),
+        (Some(_), PlayerMarker::X) => println!(
+            "You won {}, lost {} and drew {}",
+            summary[0], summary[1], summary[2]
+        ),
+        (Some(_), PlayerMarker::O) => println!(
+            "You won {}, lost {} and drew {}",
+            summary[1], summary[0], summary[2]
+        ),
+    }
 }
 
-impl Agent {
-    fn get_move(&mut self, board: &Board, player: &PlayerMarker) -> usize {
-        match self {
-            Agent::Random => {
-                let available: Vec<usize> = (0..9).filter(|&i| board.available(i)).collect();
-                let index = rand::rng().next_u32() as usize % available.len();
-                *available.get(index).expect("Board is full")
-            }
-            Agent::Human => loop {
-                board.print();
-                println!("{} to move!", PlayerMarker::player_char(player));
-                println!("Enter a number between 1 and 9:");
-                let mut input = String::new();
-                std::io::stdin()
-                    .read_line(&mut input)
-                    .expect("Failed to read line");
-                let move_index: usize = match input.trim().parse::<usize>() {
-                    Ok(num) if num >= 1 && num <= 9 => num - 1,
-                    _ => {
-                        println!("Invalid input. Please enter a number between 1 and 9.");
-                        continue;
-                    }
-                };
-                if !board.available(move_index) {
-                    println!("That space is taken. Try again.");
-                    continue;
-                }
-                return move_index;
-            },
-            Agent::RL(q_table, prev_board) => {
-                let mut best_move = None;
-                let mut best_value = f32::MIN;
-                let mut values: Vec<Value> = Vec::new();
-                for i in 0..9 {
-                    if board.available(i) {
-                        let eval_board = board.spaces | (PlayerMarker::player_mask(player) << (i * 2));
-                        let value = q_table
-                            .get(&eval_board)
-                            .unwrap_or(&0.0);
-                        values.push(Value::Eval(*value));
-                        if *value > best_value {
-                            best_value = *value;
-                            best_move = Some(i);
-                        }
+// What `replay` holds each move up against: the rl agents for the sides
+// reviewed and the shortfall from their greedy move that counts as a blunder.
+struct Review {
+    agents: Vec<(PlayerMarker, RLAgent)>,
+    blunder: f32,
+    color: bool,
+}
+
+// Prints the last `games` games of the log at `path`, or all of them, a move
+// at a time. Reading from a terminal, it waits for Enter after each move. With
+// a `review`, each reviewed move shows the agent's values for the position it
+// was played in, and moves that fall `review.blunder` short of the greedy one
+// are flagged.
+fn replay(path: &Path, games: Option<usize>, review: Option<&Review>) {
+    let logged = read_game_log(path).unwrap_or_else(|error| {
+        eprintln!("Error: {}", error);
+        process::exit(1);
+    });
+    let step = io::stdin().is_terminal();
+    let skip = logged.len() - games.unwrap_or(logged.len()).min(logged.len());
+    for (number, game) in logged.iter().enumerate().skip(skip) {
+        println!("Game {}, ended {}", number + 1, game.played);
+        let transcript = &game.transcript;
+        let mut board = Board::new();
+        let mut blunders = Vec::new();
+        for (turn, &(player, index)) in transcript.moves.iter().enumerate() {
+            let reviewed = review.and_then(|review| {
+                let (_, agent) = review.agents.iter().find(|(side, _)| *side == player)?;
+                review_move(agent, &board, player, index)
+            });
+            match (review, reviewed) {
+                (Some(review), Some(reviewed)) => {
+                    println!(
+                        "Move {}: {} to move\n{}",
+                        turn + 1,
+                        PlayerMarker::player_char(player),
+                        format_values(&board, &reviewed.values, review.color)
+                    );
+                    let verdict = if reviewed.is_blunder(review.blunder) {
+                        blunders.push(turn + 1);
+                        " Blunder!"
                     } else {
-                        values.push(Value::PlayerMarker(board.at(i as u32)));
-                    }
+                        ""
+                    };
+                    println!(
+                        "{} plays {} ({:+.3}); greedy is {} ({:+.3}).{}",
+                        PlayerMarker::player_char(player),
+                        index + 1,
+                        reviewed.played.1,
+                        reviewed.best.0 + 1,
+                        reviewed.best.1,
+                        verdict
+                    );
                 }
-                visualize_values(values);
-                if rand::rng().next_u32() % 100 == 1 {
-                    let available: Vec<usize> = (0..9).filter(|&i| board.available(i)).collect();
-                    let index = rand::rng().next_u32() as usize % available.len();
-                    return *available.get(index).expect("Board is full");
+                _ => println!(
+                    "Move {}: {} plays {}\n{}",
+                    turn + 1,
+                    PlayerMarker::player_char(player),
+                    index + 1,
+                    board.with_move(index, player)
+                ),
+            }
+            board.set(index, player);
+            if step {
+                let mut line = String::new();
+                if io::stdin().read_line(&mut line).unwrap_or(0) == 0 {
+                    return;
                 }
-                update_q(q_table, prev_board, best_value);
-                let best_move: usize = best_move.expect("No available moves");
-                *prev_board = board.spaces | (PlayerMarker::player_mask(player) << (best_move * 2));
-                best_move
+            } else {
+                println!();
             }
         }
+        if review.is_some() {
+            println!("{}", board);
+            match &blunders[..] {
+                [] => println!("No blunders."),
+                moves => println!(
+                    "Blunders at move {}.",
+                    moves
+                        .iter()
+                        .map(|turn| turn.to_string())
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                ),
+            }
+        }
+        if let Some((player, index)) = transcript.forfeit {
+            println!(
+                "{} forfeits by playing {}, which is taken.",
+                PlayerMarker::player_char(player),
+                index + 1
+            );
+        }
+        println!("Result: {}\n", transcript.outcome);
     }
+}
 
-    fn report_win(&mut self, player: &PlayerMarker, board: &Board) {
-        match self {
-            Agent::Random => (),
-            Agent::Human => {
-                board.print();
-                println!("Player {} wins!", PlayerMarker::player_char(&player));
-            }
-            Agent::RL(q_table, prev_board) => {
-                let reward = 1.0;
-                update_q(q_table, prev_board, reward);
-                *prev_board = 0;
-            }
+// Prints the last `games` games of the log at `path`, or all of them, each
+// beside the game it would have been with `cell` played at move `turn`,
+// counting both from 0, and `agents`' greedy play after it.
+fn replay_what_if(
+    path: &Path,
+    games: Option<usize>,
+    (turn, cell): (usize, usize),
+    agents: [&RLAgent; 2],
+) {
+    let logged = read_game_log(path).unwrap_or_else(|error| {
+        eprintln!("Error: {}", error);
+        process::exit(1);
+    });
+    let skip = logged.len() - games.unwrap_or(logged.len()).min(logged.len());
+    for (number, game) in logged.iter().enumerate().skip(skip) {
+        println!("Game {}, ended {}", number + 1, game.played);
+        match counterfactual(&game.transcript, turn, cell, agents[0], agents[1]) {
+            Ok(other) => println!("{}\n", side_by_side(&game.transcript, &other)),
+            Err(error) => println!("No what-if: {}\n", error),
         }
     }
+}
 
-    fn report_draw(&mut self, board: &Board) {
-        match self {
-            Agent::Random => (),
-            Agent::Human => {
-                board.print();
-                println!("It's a draw!");
-            }
-            Agent::RL(q_table, prev_board) => {
-                let reward = -0.5;
-                update_q(q_table, prev_board, reward);
-                *prev_board = 0;
-                
-            }
+// Prints how often the games of the trajectory file at `path` visited each
+// position, ply by ply, and writes every position's visits to `out` as CSV.
+fn report_visitation(path: &Path, top: usize, out: Option<&Path>) {
+    let visitation = Visitation::read(path).unwrap_or_else(|error| {
+        eprintln!("Error: could not read {}: {}", path.display(), error);
+        process::exit(1);
+    });
+    if let Err(error) = visitation.write_report(top, &mut io::stdout().lock()) {
+        eprintln!("Error: could not write the report: {}", error);
+        process::exit(1);
+    }
+    if let Some(out) = out {
+        let written = File::create(out).and_then(|file| {
+            let mut writer = BufWriter::new(file);
+            visitation.write_csv(&mut writer)?;
+            writer.flush()
+        });
+        if let Err(error) = written {
+            eprintln!("Error: could not write {}: {}", out.display(), error);
+            process::exit(1);
         }
     }
+}
+
+// Plays `games` games without printing them and reports a `MatchSummary`.
+fn eval(
+    x: AgentKind,
+    o: AgentKind,
+    games: u32,
+    options: &AgentOptions,
+    freeze: bool,
+    progress: bool,
+) {
+    let mut x_agent = build_agent(x, PlayerMarker::X, options);
+    let mut o_agent = build_agent(o, PlayerMarker::O, options);
+    if freeze {
+        x_agent.freeze();
+        o_agent.freeze();
+    }
+    let mut bar = progress.then(|| Progress::new(games as u64));
+    let mut summary = MatchSummary::default();
+    for game in 1..=games {
+        let (_, transcript) = or_exit(play_game_with_transcript(
+            x_agent.as_mut(),
+            o_agent.as_mut(),
+        ));
+        summary.record(&transcript);
+        if let Some(bar) = &mut bar {
+            bar.update(game as u64, "");
+        }
+    }
+    println!("{}", summary);
+    // A draw is the best anyone can do against perfect play, so the draw rate
+    // is how close the other side's policy is to optimal.
+    let opponent = match (x, o) {
+        (AgentKind::Minimax, AgentKind::Minimax) => None,
+        (AgentKind::Minimax, _) => Some("O"),
+        (_, AgentKind::Minimax) => Some("X"),
+        _ => None,
+    };
+    if let Some(side) = opponent {
+        let (rate, low, high) = summary.rate_with_interval(summary.draws);
+        println!(
+            "{} drew {:.3} (95% CI {:.3}-{:.3}) of its games against perfect play",
+            side, rate, low, high
+        );
+    }
+}
 
-    fn report_loss(&mut self, player: &PlayerMarker, board: &Board) {
-        match self {
-            Agent::Random => (),
-            Agent::Human => {
-                board.print();
-                println!("Player {} loses!", PlayerMarker::player_char(&player));
+// Scores the policy saved at `path` against the standard opponents, reading
+// the file but never writing it, and exits with status 1 if it fails.
+fn eval_only(path: &Path, side: PlayerMarker, games: u32, seed: Option<u64>) {
+    let mut agent = RLAgent::load(path).unwrap_or_else(|error| {
+        eprintln!("Error: could not load {}: {}", path.display(), error);
+        process::exit(1);
+    });
+    let evaluation = evaluate_policy(&mut agent, side, games, seed);
+    println!("{}", evaluation);
+    if !evaluation.passed() {
+        process::exit(1);
+    }
+}
+
+// How `self_play` shows its training as it goes, where it saves it, where
+// it writes its snapshots' ratings and where it records its games.
+struct SelfPlayRun<'a> {
+    display: TrainingDisplay,
+    saved_state: Option<&'a SavedState>,
+    ratings_out: Option<&'a Path>,
+    trajectories: Option<&'a Path>,
+}
+
+// Trains two fresh RL agents against each other with a `Trainer`, printing
+// the overall results and every evaluation checkpoint.
+#[cfg_attr(not(feature = "plot"), allow(unused_variables))]
+fn self_play(
+    games: u32,
+    training: TrainingConfig,
+    config: &RLConfig,
+    metrics_out: Option<&Path>,
+    jsonl: Option<&Path>,
+    plot: Option<&Path>,
+    run: SelfPlayRun,
+) {
+    let opponent = training.eval_opponent;
+    let settings = training.clone();
+    let mode = self_play_mode(&training);
+    let mut trainer = Trainer::new(
+        Box::new(RLAgent::new(config.clone(), None)),
+        Box::new(RLAgent::new(config.clone(), None)),
+    )
+    .with_config(training);
+    if let Some(saved) = run.saved_state {
+        trainer = trainer.with_checkpoints(saved.dir.clone(), saved.interval);
+        if saved.resume {
+            match trainer.resume(&saved.dir) {
+                Ok(episode) => println!("Resuming after game {} of {}", episode, games),
+                Err(error) => {
+                    eprintln!("Error: could not resume: {}", error);
+                    process::exit(1);
+                }
             }
-            Agent::RL(q_table, prev_board) => {
-                let reward = -1.0;
-                update_q(q_table, prev_board, reward);
-                *prev_board = 0;
+        }
+    }
+    if let Some(path) = run.trajectories {
+        match TrajectoryRecorder::create(path) {
+            Ok(recorder) => trainer = trainer.with_trajectories(recorder),
+            Err(error) => {
+                eprintln!("Error: could not write {}: {}", path.display(), error);
+                process::exit(1);
             }
         }
     }
+    match run.display {
+        TrainingDisplay::Progress => trainer = trainer.with_progress(),
+        #[cfg(feature = "tui")]
+        TrainingDisplay::Dashboard => trainer = trainer.with_dashboard(),
+        _ => {}
+    }
+    let training_stats = trainer.run(games);
+    if let Some(episode) = trainer.interrupted_after() {
+        println!("Interrupted after game {} of {}", episode, games);
+    }
+    if let Some(path) = metrics_out {
+        if let Err(error) = write_metrics_csv(&training_stats.metrics, path) {
+            eprintln!("Error: could not write {}: {}", path.display(), error);
+            process::exit(1);
+        }
+    }
+    if let Some(path) = run.ratings_out {
+        if let Err(error) = write_ratings_csv(&training_stats.ratings, path) {
+            eprintln!("Error: could not write {}: {}", path.display(), error);
+            process::exit(1);
+        }
+    }
+    if let Some(path) = jsonl {
+        if let Err(error) = write_self_play_jsonl(path, games, &settings, config, &training_stats) {
+            eprintln!("Error: could not write {}: {}", path.display(), error);
+            process::exit(1);
+        }
+    }
+    #[cfg(feature = "plot")]
+    if let Some(path) = plot {
+        plot::self_play(path, &training_stats, opponent);
+    }
+    println!(
+        "{} - first agent wins: {}\t second agent wins: {}\t draws: {}",
+        mode, training_stats.x_agent_wins, training_stats.o_agent_wins, training_stats.draws
+    );
+    for evaluation in &training_stats.evaluations {
+        println!(
+            "After {} episodes vs {:?} - first agent as X won {:.3}, lost {:.3}\t second agent as O won {:.3}, lost {:.3}",
+            evaluation.episode,
+            opponent,
+            evaluation.x_agent_win_rate,
+            evaluation.x_agent_loss_rate,
+            evaluation.o_agent_win_rate,
+            evaluation.o_agent_loss_rate
+        );
+    }
+    if let Some(last) = training_stats.ratings.last() {
+        let mut ratings: Vec<_> = training_stats
+            .ratings
+            .iter()
+            .filter(|rating| rating.episode == last.episode)
+            .collect();
+        ratings.sort_by(|a, b| b.rating.total_cmp(&a.rating));
+        println!("Ratings after {} episodes:", last.episode);
+        for rating in ratings {
+            println!("  {:<16} {:>7.1}", rating.entrant, rating.rating);
+        }
+    }
+}
+
+// What the agents trained against, to head the results.
+fn self_play_mode(training: &TrainingConfig) -> String {
+    match training.opponent_refresh {
+        0 => "Self-play".to_string(),
+        refresh => format!("Against snapshots retaken every {} games", refresh),
+    }
 }
 
-fn update_q(q_table: &mut HashMap<u32, f32>, prev_board: &mut u32, reward: f32) {
-    let prev_value = q_table.entry(*prev_board);
-    match prev_value {
-        Entry::Occupied(mut entry) => {
-            let prev_reward = *entry.get();
-            entry.insert(prev_reward + 0.1 * (reward - prev_reward));
+// `self_play` once with each of `seeds`, the runs spread over `run.threads`
+// threads, printing the overall results and every checkpoint averaged over
+// the runs with their standard errors. `metrics_out` and `jsonl` get the
+// averaged learning curve and checkpoints.
+fn self_play_seeds(
+    games: u32,
+    training: TrainingConfig,
+    config: &RLConfig,
+    seeds: Range<u64>,
+    metrics_out: Option<&Path>,
+    jsonl: Option<&Path>,
+    run: TrainingRun,
+) {
+    let seeds: Vec<u64> = seeds.collect();
+    let total = seeds.len() as u64 * games as u64;
+    let progress = (!run.quiet).then(|| Mutex::new(Progress::new(total)));
+    let done = AtomicU64::new(0);
+    // `None` for a run Ctrl-C stopped before its first game.
+    let train = |&seed: &u64| {
+        let mut trainer = Trainer::new(
+            Box::new(RLAgent::new(config.clone(), None)),
+            Box::new(RLAgent::new(config.clone(), None)),
+        )
+        .with_config(TrainingConfig {
+            seed: Some(seed),
+            ..training.clone()
+        });
+        let stats = trainer.run(games);
+        let played = trainer.interrupted_after().unwrap_or(games);
+        let done = done.fetch_add(played as u64, Ordering::Relaxed) + played as u64;
+        if let Some(progress) = &progress {
+            progress.lock().unwrap().update(done, "");
+        }
+        (played > 0).then_some(stats)
+    };
+    let runs: Vec<TrainingStats> = match run.threads {
+        Some(threads) => rayon::ThreadPoolBuilder::new()
+            .num_threads(threads)
+            .build()
+            .expect("could not start the training threads")
+            .install(|| seeds.par_iter().filter_map(train).collect()),
+        None => seeds.par_iter().filter_map(train).collect(),
+    };
+    if interrupted() {
+        if let Some(progress) = &progress {
+            progress.lock().unwrap().stop();
+        }
+        println!(
+            "Interrupted: averaging the {} of {} runs that had started, up to the checkpoints all of them reached",
+            runs.len(),
+            seeds.len()
+        );
+    }
+    let metrics = average_metrics(&runs);
+    let evaluations = average_evaluations(&runs);
+    if let Some(path) = metrics_out {
+        if let Err(error) = write_seed_averages_csv(&metrics, path) {
+            eprintln!("Error: could not write {}: {}", path.display(), error);
+            process::exit(1);
         }
-        Entry::Vacant(entry) => {
-            entry.insert(DEFAULT_VALUE + 0.1 * (reward - DEFAULT_VALUE));
+    }
+    if let Some(path) = jsonl {
+        let averages = [("learning_curve", &metrics), ("evaluation", &evaluations)];
+        if let Err(error) =
+            write_seed_averages_jsonl(path, games, &training, config, &seeds, &averages)
+        {
+            eprintln!("Error: could not write {}: {}", path.display(), error);
+            process::exit(1);
         }
     }
-    // println!("{q_table:?}");
+    let count = |count: fn(&TrainingStats) -> u32| {
+        let counts: Vec<f64> = runs.iter().map(|stats| count(stats) as f64).collect();
+        let (mean, std_error) = mean_and_std_error(&counts);
+        format!("{:.1} (± {:.1})", mean, std_error)
+    };
+    println!(
+        "{}, averaged over {} seeds - first agent wins: {}\t second agent wins: {}\t draws: {}",
+        self_play_mode(&training),
+        runs.len(),
+        count(|stats| stats.x_agent_wins),
+        count(|stats| stats.o_agent_wins),
+        count(|stats| stats.draws)
+    );
+    for evaluation in &evaluations {
+        let rate = |i: usize| {
+            let (_, mean, std_error) = evaluation.measures[i];
+            format!("{:.3} (± {:.3})", mean, std_error)
+        };
+        println!(
+            "After {} episodes vs {:?} - first agent as X won {}, lost {}\t second agent as O won {}, lost {}",
+            evaluation.episode,
+            training.eval_opponent,
+            rate(0),
+            rate(1),
+            rate(2),
+            rate(3)
+        );
+    }
+}
+
+// Writes averaged learning-curve points and checkpoints to `path` as JSON
+// Lines, each `averages` entry as records of its name, with the run's
+// settings, hyperparameters and seeds. The lines' own seed is null, as the
+// runs had one each.
+fn write_seed_averages_jsonl(
+    path: &Path,
+    games: u32,
+    training: &TrainingConfig,
+    config: &RLConfig,
+    seeds: &[u64],
+    averages: &[(&str, &Vec<SeedAverage>)],
+) -> io::Result<()> {
+    let settings = json!({
+        "games": games,
+        "alternate_sides": training.alternate_sides,
+        "eval_interval": training.eval_interval,
+        "eval_games": training.eval_games,
+        "eval_opponent": format!("{:?}", training.eval_opponent).to_lowercase(),
+        "metrics_interval": training.metrics_interval,
+        "opponent_refresh": training.opponent_refresh,
+        "seeds": seeds,
+        "hyperparameters": config,
+    });
+    write_jsonl(path, "tic-tac-toe/selfplay", None, |lines| {
+        for &(record, points) in averages {
+            for point in points {
+                let mut metrics = json!({"episode": point.episode, "seeds": point.seeds});
+                for &(name, mean, std_error) in &point.measures {
+                    metrics[name] = json!(mean);
+                    metrics[format!("{}_std_error", name)] = json!(std_error);
+                }
+                lines.record(record, &settings, metrics)?;
+            }
+        }
+        Ok(())
+    })
+}
+
+// Writes a self-play run's evaluation checkpoints and learning-curve points
+// to `path` as JSON Lines, each with the run's settings and hyperparameters.
+fn write_self_play_jsonl(
+    path: &Path,
+    games: u32,
+    training: &TrainingConfig,
+    config: &RLConfig,
+    stats: &TrainingStats,
+) -> io::Result<()> {
+    let settings = json!({
+        "games": games,
+        "alternate_sides": training.alternate_sides,
+        "eval_interval": training.eval_interval,
+        "eval_games": training.eval_games,
+        "eval_opponent": format!("{:?}", training.eval_opponent).to_lowercase(),
+        "metrics_interval": training.metrics_interval,
+        "rating_interval": training.rating_interval,
+        "opponent_refresh": training.opponent_refresh,
+        "hyperparameters": config,
+    });
+    write_jsonl(path, "tic-tac-toe/selfplay", training.seed, |lines| {
+        for evaluation in &stats.evaluations {
+            lines.record(
+                "evaluation",
+                &settings,
+                json!({
+                    "episode": evaluation.episode,
+                    "x_agent_win_rate": evaluation.x_agent_win_rate,
+                    "x_agent_loss_rate": evaluation.x_agent_loss_rate,
+                    "o_agent_win_rate": evaluation.o_agent_win_rate,
+                    "o_agent_loss_rate": evaluation.o_agent_loss_rate,
+                }),
+            )?;
+        }
+        for point in &stats.metrics {
+            lines.record(
+                "learning_curve",
+                &settings,
+                json!({
+                    "episode": point.episode,
+                    "win_rate": point.win_rate,
+                    "draw_rate": point.draw_rate,
+                    "loss_rate": point.loss_rate,
+                    "table_size": point.table_size,
+                    "mean_value": point.mean_value,
+                }),
+            )?;
+        }
+        Ok(())
+    })
 }
 
-enum Value {
-    PlayerMarker(char),
-    Eval(f32),
+// Trains pairs of fresh RL agents for `games` episodes, once by simultaneous
+// self-play and then against frozen snapshots of each other retaken at a few
+// intervals, and prints how often each pair lost to minimax at every
+// checkpoint, so the modes' convergence can be compared.
+fn compare_opponent_refresh(games: u32, config: &RLConfig, seed: Option<u64>) {
+    let refreshes = [0, 500, 5000];
+    let runs: Vec<Vec<Evaluation>> = refreshes
+        .iter()
+        .map(|&opponent_refresh| {
+            Trainer::new(
+                Box::new(RLAgent::new(config.clone(), None)),
+                Box::new(RLAgent::new(config.clone(), None)),
+            )
+            .with_config(TrainingConfig {
+                alternate_sides: true,
+                eval_interval: games / 10,
+                eval_games: 100,
+                eval_opponent: EvalOpponent::Minimax,
+                metrics_interval: 0,
+                opponent_refresh,
+                rating_interval: 0,
+                seed,
+            })
+            .run(games)
+            .evaluations
+        })
+        .collect();
+    let names: Vec<String> = refreshes
+        .iter()
+        .map(|&refresh| match refresh {
+            0 => "simultaneous".to_string(),
+            refresh => format!("snapshots every {}", refresh),
+        })
+        .collect();
+    println!("Loss rate against minimax (first agent as X / second as O):");
+    println!("{:>9}  {}", "episode", names.join("  "));
+    for (i, evaluation) in runs[0].iter().enumerate() {
+        let cells: Vec<String> = runs
+            .iter()
+            .zip(&names)
+            .map(|(run, name)| {
+                let cell = format!(
+                    "{:.2} / {:.2}",
+                    run[i].x_agent_loss_rate, run[i].o_agent_loss_rate
+                );
+                format!("{:>width$}", cell, width = name.len())
+            })
+            .collect();
+        println!("{:>9}  {}", evaluation.episode, cells.join("  "));
+    }
+    for (run, name) in runs.iter().zip(&names) {
+        // The first checkpoint from which neither agent lost again.
+        let unbeaten = run
+            .iter()
+            .rposition(|e| e.x_agent_loss_rate > 0.0 || e.o_agent_loss_rate > 0.0)
+            .map_or(run.first().map(|e| e.episode), |last| {
+                run.get(last + 1).map(|e| e.episode)
+            });
+        match unbeaten {
+            Some(episode) => println!("  {}: unbeaten by minimax from episode {}", name, episode),
+            None => println!("  {}: still losing to minimax at the end", name),
+        }
+    }
 }
 
-impl Value {
-    fn to_string(&self) -> String {
-        match self {
-            Value::PlayerMarker(player) => {
-                return player.to_string();
+// Prints statistics about the `side` agent's value table, prunes it and
+// saves the result over the file it came from (or to `save_policy`), or
+// writes its greedy moves to stdout (or to `out`).
+fn policy(
+    action: PolicyAction,
+    side: PlayerMarker,
+    save_policy: Option<&Path>,
+    out: Option<&Path>,
+    options: &AgentOptions,
+) {
+    let mut agent = trained_rl(side, options.load_policy, options.config, options.run);
+    let source = options.load_policy.unwrap_or(policy_path(side));
+    match action {
+        PolicyAction::Stats => {
+            println!(
+                "{} policy from {}\n{}",
+                PlayerMarker::player_char(side),
+                source.display(),
+                agent.table_stats(10)
+            );
+        }
+        PolicyAction::Prune { min_visits } => {
+            if !agent.has_visit_counts() {
+                eprintln!(
+                    "Error: {} was saved without visit counts, so it can't be pruned",
+                    source.display()
+                );
+                process::exit(1);
             }
-            Value::Eval(value) => {
-                return value.to_string();
+            let before = agent.q_table().len();
+            let dropped = agent.prune(min_visits);
+            let path = save_policy.unwrap_or(source);
+            save_or_exit(&agent, path);
+            println!(
+                "Dropped {} of {} entries visited fewer than {} times; {} left, saved to {}",
+                dropped,
+                before,
+                min_visits,
+                before - dropped,
+                path.display()
+            );
+        }
+        PolicyAction::Export => {
+            let written = match out {
+                Some(path) => File::create(path).and_then(|file| {
+                    let mut writer = BufWriter::new(file);
+                    agent.write_policy_text(side, &mut writer)?;
+                    writer.flush()
+                }),
+                None => agent.write_policy_text(side, &mut io::stdout().lock()),
+            };
+            if let Err(error) = written {
+                eprintln!("Error: could not write the policy: {}", error);
+                process::exit(1);
             }
         }
     }
 }
 
-enum Result {
-    XWin,
-    OWin,
-    Draw,
+// Writes the tree of positions the `side` agent's greedy play allows, with
+// visit counts from `games` games against random play.
+fn analyze(
+    side: PlayerMarker,
+    format: TreeFormat,
+    out: Option<&Path>,
+    games: u32,
+    options: &AgentOptions,
+) {
+    let mut agent = trained_rl(side, options.load_policy, options.config, options.run);
+    agent.freeze();
+    let mut tree = GameTree::expand(&mut agent, side);
+    or_exit(tree.count_visits(&mut agent, games, derive_seed(options.run.seed, 2)));
+    let write = |writer: &mut dyn Write| match format {
+        TreeFormat::Dot => tree.write_dot(writer),
+        TreeFormat::Json => tree.write_json(writer),
+        TreeFormat::Text => unreachable!("analyze rejects --format text"),
+    };
+    let written = match out {
+        Some(path) => File::create(path).and_then(|file| {
+            let mut writer = BufWriter::new(file);
+            write(&mut writer)?;
+            writer.flush()
+        }),
+        None => write(&mut io::stdout().lock()),
+    };
+    if let Err(error) = written {
+        eprintln!("Error: could not write the tree: {}", error);
+        process::exit(1);
+    }
+    if let Some(path) = out {
+        let finished: Vec<&TreeNode> = tree
+            .nodes
+            .iter()
+            .filter(|node| node.result.is_some())
+            .collect();
+        let lost = finished
+            .iter()
+            .filter(|node| {
+                node.result
+                    == Some(match side {
+                        PlayerMarker::X => GameResult::OWin,
+                        PlayerMarker::O => GameResult::XWin,
+                    })
+            })
+            .count();
+        println!(
+            "{} positions reachable under {}'s greedy play, {} of them finished games ({} lost); written to {}",
+            tree.nodes.len(),
+            PlayerMarker::player_char(side),
+            finished.len(),
+            lost,
+            path.display()
+        );
+    }
 }
 
-fn main() {
-    let mut random_agent = Agent::Random;
-    let mut o_rl = Agent::RL(HashMap::new(), 0);
-    let mut x_rl= Agent::RL(HashMap::new(), 0);
-    // let mut o_agent = Agent::Random;
-    let mut x_wins = 0;
-    let mut o_wins = 0;
-    let mut draws = 0;
-    let games = 100000;
+// Plays a round robin between `entrants`, every agent frozen, `games` games
+// per pairing. A plain rl entrant joins the trained X and O policies so it can
+// play either side.
+// The agents for `entrants`, each with its name.
+fn tournament_agents<'a>(
+    entrants: &'a [AgentSpec],
+    options: &AgentOptions,
+) -> Vec<(&'a str, Box<dyn Agent>)> {
+    entrants
+        .iter()
+        .enumerate()
+        .map(|(i, entrant)| {
+            let agent: Box<dyn Agent> = match (entrant.kind, &entrant.policy) {
+                (AgentKind::Rl, Some(path)) => Box::new(trained_rl(
+                    PlayerMarker::O,
+                    Some(path),
+                    options.config,
+                    options.run,
+                )),
+                (AgentKind::Rl, None) => Box::new(RLAgent::combined(
+                    &trained_rl(PlayerMarker::X, None, options.config, options.run),
+                    &trained_rl(PlayerMarker::O, None, options.config, options.run),
+                )),
+                (kind, _) => build_agent(
+                    kind,
+                    PlayerMarker::X,
+                    &AgentOptions {
+                        run: TrainingRun {
+                            seed: derive_seed(options.run.seed, 2 * i as u64),
+                            ..options.run
+                        },
+                        ..*options
+                    },
+                ),
+            };
+            (entrant.name.as_str(), agent)
+        })
+        .collect()
+}
 
-    for _ in 0..games {
-        play_game(&mut x_rl, &mut o_rl);
+fn tournament(entrants: &[AgentSpec], games: u32, options: &AgentOptions) {
+    let mut agents = tournament_agents(entrants, options);
+    println!("{}", run_tournament(&mut agents, games as usize));
+}
+
+// Listens at `listen` for remote agents and plays each that connects, one at a
+// time, in a tournament of `games` a pairing against `entrants`, printing its
+// cross-table. Runs until stopped.
+fn serve(listen: &str, entrants: &[AgentSpec], games: u32, options: &AgentOptions) {
+    let listener = TcpListener::bind(listen).unwrap_or_else(|error| {
+        eprintln!("Error: couldn't listen on {}: {}", listen, error);
+        process::exit(1);
+    });
+    let mut agents = tournament_agents(entrants, options);
+    println!("Waiting for remote agents on {}", listen);
+    for stream in listener.incoming() {
+        let connected = stream.and_then(|stream| {
+            let peer = stream.peer_addr()?;
+            Ok((peer, RemoteAgent::new(stream)?))
+        });
+        let (peer, remote) = match connected {
+            Ok(connected) => connected,
+            Err(error) => {
+                eprintln!("Warning: couldn't accept a connection: {}", error);
+                continue;
+            }
+        };
+        println!("{} connected", peer);
+        agents.push(("remote", Box::new(remote)));
+        println!("{}", run_tournament(&mut agents, games as usize));
+        // Dropping the remote agent closes its connection.
+        agents.pop();
+    }
+}
+
+// Prints the value the rl agent for the side to move puts on every move in
+// `position`.
+fn show_values(position: &Board, color: bool, options: &AgentOptions) {
+    let player = position.player_to_move();
+    let agent = trained_rl(player, options.load_policy, options.config, options.run);
+    let values = agent
+        .move_values(position, player)
+        .expect("RLAgent values its moves");
+    println!(
+        "{} to move\n{}",
+        PlayerMarker::player_char(player),
+        format_values(position, &values, color)
+    );
+}
+
+// Compares the agents against each other and against random play, writing the
+// learning curve and value heatmap of the trained O agent along the way.
+fn experiments(games: u32, config: &RLConfig, run: TrainingRun) {
+    let seed = run.seed;
+    let mut random_agent = RandomAgent::new(seed);
+    let mut o_rl = trained_rl(PlayerMarker::O, None, config, run);
+    if let Some(seed) = derive_seed(seed, 1) {
+        o_rl.reseed(seed);
     }
 
+    let mut statistics = Statistics::new(1000);
+    let mut o_wins = 0;
     for _ in 0..games {
-        match play_game(&mut random_agent, &mut o_rl) {
-            Result::XWin => {
-                x_wins += 1;
-            }
-            Result::OWin => {
-                o_wins += 1;
-            }
-            Result::Draw => {
-                draws += 1;
-            }
+        let result = or_exit(play_game(&mut random_agent, &mut o_rl));
+        statistics.record(result, false);
+        if let GameResult::OWin = result {
+            o_wins += 1;
         }
-        println!("X wins: {}\t wins: {}\t Draws: {}", x_wins, o_wins, draws);
     }
+    println!("Trained O wins against random: {}/{}", o_wins, games);
 
-    for _ in 0..games {
-        play_game(&mut Agent::Human, &mut o_rl);
+    statistics
+        .write_csv(Path::new("learning_curve.csv"))
+        .expect("Failed to write learning curve");
+    export_value_heatmap(
+        o_rl.q_table(),
+        PlayerMarker::O,
+        Path::new("value_heatmap.csv"),
+    )
+    .expect("Failed to write value heatmap");
+
+    let minimax_games = 1000;
+    let mut minimax_draws = 0;
+    let mut minimax_agent = MinimaxAgent::new();
+    for _ in 0..minimax_games {
+        if let GameResult::Draw = or_exit(play_game(&mut minimax_agent, &mut o_rl)) {
+            minimax_draws += 1;
+        }
     }
-}
+    println!("Draws against minimax: {}/{}", minimax_draws, minimax_games);
+
+    let mut mcts = MctsAgent::new(1000, std::f32::consts::SQRT_2, derive_seed(seed, 2));
+    let mcts_games = 100;
+    let mcts_losses = (0..mcts_games)
+        .filter(|_| {
+            matches!(
+                or_exit(play_game(&mut random_agent, &mut mcts)),
+                GameResult::XWin
+            )
+        })
+        .count();
+    println!("MCTS losses against random: {}/{}", mcts_losses, mcts_games);
+
+    let mut afterstate = RLAgent::new(config.clone(), derive_seed(seed, 3));
+    let mut q_learning = QLearningAgent::new(config.clone(), derive_seed(seed, 18));
+    let mut sarsa = SarsaAgent::new(config.clone(), derive_seed(seed, 4));
+    let mut monte_carlo = MonteCarloAgent::new(config.clone(), derive_seed(seed, 5));
+    let mut double_q = DoubleQLAgent::new(config.clone(), derive_seed(seed, 6));
+    let mut reinforce = PolicyGradientAgent::new(config.clone(), derive_seed(seed, 7));
+    println!(
+        "Win rate vs random after training - afterstate TD: {}\t Q-learning: {}\t SARSA: {}\t Monte Carlo: {}\t Double Q-learning: {}\t REINFORCE: {}",
+        win_rate_vs_random(&mut afterstate, games, 10000, derive_seed(seed, 8)),
+        win_rate_vs_random(&mut q_learning, games, 10000, derive_seed(seed, 8)),
+        win_rate_vs_random(&mut sarsa, games, 10000, derive_seed(seed, 8)),
+        win_rate_vs_random(&mut monte_carlo, games, 10000, derive_seed(seed, 8)),
+        win_rate_vs_random(&mut double_q, games, 10000, derive_seed(seed, 8)),
+        win_rate_vs_random(&mut reinforce, games, 10000, derive_seed(seed, 8))
+    );
+
+    let shaping_games = 5000;
+    let mut shaped =
+        RLAgent::new(config.clone(), derive_seed(seed, 9)).with_shaping(threat_potential(0.1));
+    println!(
+        "Win rate vs random after {} games - unshaped: {}\t threat-shaped: {}",
+        shaping_games,
+        win_rate_vs_random(
+            &mut RLAgent::new(config.clone(), derive_seed(seed, 10)),
+            shaping_games,
+            10000,
+            derive_seed(seed, 8)
+        ),
+        win_rate_vs_random(&mut shaped, shaping_games, 10000, derive_seed(seed, 8))
+    );
 
-fn play_game(x_agent: &mut Agent, o_agent: &mut Agent) -> Result {
-    let mut board = Board::new();
-    let mut current_player = PlayerMarker::X;
-    let mut current_agent = x_agent;
-    let mut other_agent = o_agent;
+    // Shaping toward quick wins, scored greedily on how often the agent wins
+    // and how many moves its wins take.
+    let speed_games = 5000;
+    let speeds: Vec<String> = [
+        ("unshaped", 0.0, 0.0),
+        ("step penalty", -0.05, 0.0),
+        ("win speed bonus", 0.0, 0.1),
+        ("both", -0.05, 0.1),
+    ]
+    .into_iter()
+    .map(|(name, step_reward, win_speed_bonus)| {
+        let config = RLConfig {
+            step_reward,
+            win_speed_bonus,
+            ..config.clone()
+        };
+        let speed = win_speed_vs_random(
+            &mut RLAgent::new(config, derive_seed(seed, 22)),
+            speed_games,
+            10000,
+            derive_seed(seed, 8),
+        );
+        match speed.moves_per_win {
+            Some(moves) => format!("{}: {:.3} in {:.2} moves", name, speed.win_rate, moves),
+            None => format!("{}: {:.3}", name, speed.win_rate),
+        }
+    })
+    .collect();
+    println!(
+        "Win rate vs random and moves per win after {} games - {}",
+        speed_games,
+        speeds.join("\t ")
+    );
 
-    loop {
-        let move_index = current_agent.get_move(&board, &current_player);
-        board.set(move_index, &current_player);
+    // Sample efficiency: UCB1 on the visit counts against epsilon-greedy,
+    // each trained from scratch for a growing number of games. --exploration
+    // ucb:<c> sets the constant.
+    let ucb = match config.exploration {
+        ExplorationStrategy::Ucb1(_) => config.clone(),
+        _ => RLConfig {
+            exploration: ExplorationStrategy::Ucb1(0.5),
+            ..config.clone()
+        },
+    };
+    let epsilon_greedy = RLConfig {
+        exploration: ExplorationStrategy::EpsilonGreedy,
+        ..config.clone()
+    };
+    for budget in [500, 1000, 2000, 5000] {
+        println!(
+            "Win rate vs random after {} games - epsilon-greedy: {}\t {}: {}",
+            budget,
+            win_rate_vs_random(
+                &mut RLAgent::new(epsilon_greedy.clone(), derive_seed(seed, 19)),
+                budget,
+                10000,
+                derive_seed(seed, 8)
+            ),
+            ucb.exploration,
+            win_rate_vs_random(
+                &mut RLAgent::new(ucb.clone(), derive_seed(seed, 20)),
+                budget,
+                10000,
+                derive_seed(seed, 8)
+            )
+        );
+    }
 
-        if board.check_winner(&current_player) {
-            current_agent.report_win(&current_player, &board);
-            other_agent.report_loss(&current_player, &board);
-            return match current_player {
-                PlayerMarker::X => Result::XWin,
-                PlayerMarker::O => Result::OWin,
+    // Which sides the learner trains on, scored on both with learning off.
+    let role_games = 5000;
+    let rates: Vec<String> = Roles::all()
+        .into_iter()
+        .map(|roles| {
+            let training = RoleTraining {
+                roles,
+                training_games: role_games,
+                eval_games: 10000,
+                rules: Rules::default(),
             };
+            let rates = training.run(
+                |seed| RLAgent::new(config.clone(), seed),
+                derive_seed(seed, 21),
+            );
+            format!("{}: {:.3} / {:.3}", roles, rates.x, rates.o)
+        })
+        .collect();
+    println!(
+        "Win rate vs random as X / as O after {} games on the sides trained - {}",
+        role_games,
+        rates.join("\t ")
+    );
+
+    let symmetry_games = 5000;
+    let mut symmetric = RLAgent::new(config.clone(), derive_seed(seed, 11));
+    let mut asymmetric = RLAgent::new(
+        RLConfig {
+            symmetry: false,
+            ..config.clone()
+        },
+        derive_seed(seed, 12),
+    );
+    println!(
+        "Win rate vs random after {} games - symmetric keys: {} ({} states)\t raw keys: {} ({} states)",
+        symmetry_games,
+        win_rate_vs_random(&mut symmetric, symmetry_games, 10000, derive_seed(seed, 8)),
+        symmetric.q_table().len(),
+        win_rate_vs_random(&mut asymmetric, symmetry_games, 10000, derive_seed(seed, 8)),
+        asymmetric.q_table().len()
+    );
+
+    let book_games = 5000;
+    println!(
+        "Win rate vs random after {} games - no book: {}\t two-ply opening book: {}",
+        book_games,
+        win_rate_vs_random(
+            &mut RLAgent::new(config.clone(), derive_seed(seed, 13)),
+            book_games,
+            10000,
+            derive_seed(seed, 8)
+        ),
+        win_rate_vs_random(
+            &mut RLAgent::new(config.clone(), derive_seed(seed, 14))
+                .with_opening_book(OpeningBook::build_from_minimax(2)),
+            book_games,
+            10000,
+            derive_seed(seed, 8)
+        )
+    );
+
+    self_play(
+        50000,
+        TrainingConfig {
+            alternate_sides: true,
+            eval_interval: 10000,
+            eval_games: 100,
+            eval_opponent: EvalOpponent::Minimax,
+            metrics_interval: 0,
+            opponent_refresh: 0,
+            rating_interval: 0,
+            seed: seed.or(Some(1)),
+        },
+        config,
+        None,
+        None,
+        None,
+        SelfPlayRun {
+            display: if run.quiet {
+                TrainingDisplay::Hidden
+            } else {
+                TrainingDisplay::Progress
+            },
+            saved_state: None,
+            ratings_out: None,
+            trajectories: None,
+        },
+    );
+
+    compare_opponent_refresh(50000, config, seed.or(Some(1)));
+
+    let mut agents: [(&str, Box<dyn Agent>); 8] = [
+        ("Random", Box::new(RandomAgent::new(derive_seed(seed, 15)))),
+        ("Minimax", Box::new(MinimaxAgent::new())),
+        ("Afterstate TD", Box::new(afterstate)),
+        ("Q-learning", Box::new(q_learning)),
+        ("SARSA", Box::new(sarsa)),
+        ("Monte Carlo", Box::new(monte_carlo)),
+        ("Double Q", Box::new(double_q)),
+        ("REINFORCE", Box::new(reinforce)),
+    ];
+    println!("{}", run_tournament(&mut agents, 1000));
+
+    let mut vanilla = RLAgent::new(config.clone(), derive_seed(seed, 16));
+    let mut dqn_style = DQNStyleAgent::new(config.clone(), 1000, 0.0, derive_seed(seed, 17));
+    let vanilla_variance =
+        rolling_win_rate_variance(&mut vanilla, games, 1000, derive_seed(seed, 8));
+    let dqn_variance = rolling_win_rate_variance(&mut dqn_style, games, 1000, derive_seed(seed, 8));
+    let variance = |variance: Option<f32>| {
+        variance.map_or(format!("- (fewer than {} games)", 1000), |v| v.to_string())
+    };
+    println!(
+        "Rolling win rate variance vs random - Q-learning: {}\t DQN-style: {}",
+        variance(vanilla_variance),
+        variance(dqn_variance)
+    );
+}
+
+// Trains an X and an O agent against each other on `game`, then freezes
+// them and plays each against random play on its own side.
+// A shaping potential: `weight` times the player's open two-in-a-rows less
+// the opponent's.
+fn threat_potential(weight: f32) -> impl Fn(&Board, PlayerMarker) -> f32 + Send + 'static {
+    move |board, player| {
+        weight
+            * (board.count_threats(player) as f32 - board.count_threats(player.opposite()) as f32)
+    }
+}
+
+// Runs `training` with fresh rl agents for every combination in `grid` of
+// step size ("alpha"), constant exploration rate ("epsilon"),
+// threat_potential weight ("shaping") and initial value ("initial_value"),
+// `seeds` times each, and ranks the combinations by the agents' win rate on
+// the sides they trained.
+fn sweep(
+    grid: Sweep,
+    seeds: usize,
+    training: RoleTraining,
+    config: &RLConfig,
+    run: TrainingRun,
+    out: Option<&Path>,
+) {
+    let sweep = grid.with_seeds(seeds, run.seed).with_threads(run.threads);
+    let trial_games = (training.training_games + 2 * training.eval_games) as u64;
+    let total = sweep.configurations().len() as u64 * seeds as u64 * trial_games;
+    let progress = (!run.quiet).then(|| Mutex::new(Progress::new(total)));
+    let done = AtomicU64::new(0);
+    let ranked = sweep.run(|configuration, seed| {
+        let config = RLConfig {
+            learning_rate: configuration.get("alpha") as f32,
+            epsilon: DecaySchedule::Constant(configuration.get("epsilon") as f32),
+            initial_value: configuration.get("initial_value") as f32,
+            ..config.clone()
+        };
+        let weight = configuration.get("shaping") as f32;
+        let agent = |seed| {
+            let agent = RLAgent::new(config.clone(), seed);
+            if weight != 0.0 {
+                agent.with_shaping(threat_potential(weight))
+            } else {
+                agent
+            }
+        };
+        let win_rate = training.run(agent, seed).trained(training.roles);
+        let done = done.fetch_add(trial_games, Ordering::Relaxed) + trial_games;
+        if let Some(progress) = &progress {
+            progress.lock().unwrap().update(done, "");
         }
-        if board.is_draw() {
-            current_agent.report_draw(&board);
-            other_agent.report_draw(&board);
-            return Result::Draw;
+        win_rate as f64
+    });
+
+    let written = match out {
+        Some(path) => File::create(path)
+            .and_then(|file| write_sweep_table(&ranked, "win_rate", &mut BufWriter::new(file))),
+        None => write_sweep_table(&ranked, "win_rate", &mut io::stdout().lock()),
+    };
+    if let Err(error) = written {
+        eprintln!("Error: couldn't write the ranking: {}", error);
+        process::exit(1);
+    }
+    let Some(path) = out else { return };
+    let sides = match training.roles {
+        Roles::X => "as X",
+        Roles::O => "as O",
+        Roles::Shared => "as X and O in alternate games with one table",
+        Roles::Separate => "as X and O in alternate games with a table each",
+    };
+    println!(
+        "{} settings ranked by win rate against random play{} over {} games after {} of training {}, averaged over {} seeds; written to {}",
+        ranked.len(),
+        rules_note(&training.rules),
+        training.eval_games,
+        training.training_games,
+        sides,
+        seeds,
+        path.display()
+    );
+    for (rank, entry) in ranked.iter().take(5).enumerate() {
+        println!(
+            "  {}. alpha {}, epsilon {}, shaping {}, initial value {}: win rate {:.3} (± {:.3})",
+            rank + 1,
+            entry.configuration.get("alpha"),
+            entry.configuration.get("epsilon"),
+            entry.configuration.get("shaping"),
+            entry.configuration.get("initial_value"),
+            entry.mean,
+            entry.std_error
+        );
+    }
+}
+
+// How `rules` differ from the ordinary game, for the summaries of runs
+// played by them.
+fn rules_note(rules: &Rules) -> String {
+    let mut note = String::new();
+    if rules.misere {
+        note.push_str(" in misère play");
+    }
+    if rules.fault_rate > 0.0 {
+        note.push_str(&format!(
+            " with {}% of moves misplaced",
+            rules.fault_rate * 100.0
+        ));
+    }
+    note
+}
+
+// Exercise 1.3: agents trained at each of `epsilons` backing values up only
+// after greedy moves, and after every move, scored by the greedy policies
+// they end up with.
+fn exploration_learning(
+    epsilons: Vec<f64>,
+    seeds: usize,
+    training: RoleTraining,
+    config: &RLConfig,
+    run: TrainingRun,
+    out: Option<&Path>,
+) {
+    let sweep = Sweep::new(Goal::Maximize)
+        .with_parameter("epsilon", epsilons)
+        .with_parameter("learn_from_exploration", vec![0.0, 1.0])
+        .with_seeds(seeds, run.seed)
+        .with_threads(run.threads);
+    let trial_games = (training.training_games + 2 * training.eval_games) as u64;
+    let total = sweep.configurations().len() as u64 * seeds as u64 * trial_games;
+    let progress = (!run.quiet).then(|| Mutex::new(Progress::new(total)));
+    let done = AtomicU64::new(0);
+    let ranked = sweep.run(|configuration, seed| {
+        let config = RLConfig {
+            epsilon: DecaySchedule::Constant(configuration.get("epsilon") as f32),
+            learn_from_exploration: configuration.get("learn_from_exploration") == 1.0,
+            ..config.clone()
+        };
+        let win_rate = training
+            .run(|seed| RLAgent::new(config.clone(), seed), seed)
+            .trained(training.roles);
+        let done = done.fetch_add(trial_games, Ordering::Relaxed) + trial_games;
+        if let Some(progress) = &progress {
+            progress.lock().unwrap().update(done, "");
         }
-        current_player = match current_player {
-            PlayerMarker::X => PlayerMarker::O,
-            PlayerMarker::O => PlayerMarker::X,
+        win_rate as f64
+    });
+
+    let written = match out {
+        Some(path) => File::create(path)
+            .and_then(|file| write_sweep_table(&ranked, "win_rate", &mut BufWriter::new(file))),
+        None => write_sweep_table(&ranked, "win_rate", &mut io::stdout().lock()),
+    };
+    if let Err(error) = written {
+        eprintln!("Error: couldn't write the ranking: {}", error);
+        process::exit(1);
+    }
+    let Some(path) = out else { return };
+    println!(
+        "Greedy win rate against random play{} over {} games after {} of training, averaged over {} seeds; written to {}",
+        rules_note(&training.rules),
+        training.eval_games,
+        training.training_games,
+        seeds,
+        path.display()
+    );
+    let mut by_epsilon: Vec<&Ranked> = ranked.iter().collect();
+    by_epsilon.sort_by(|a, b| {
+        let key = |entry: &Ranked| {
+            (
+                entry.configuration.get("epsilon"),
+                entry.configuration.get("learn_from_exploration"),
+            )
         };
-        std::mem::swap(&mut current_agent, &mut other_agent);
+        key(a).partial_cmp(&key(b)).expect("parameters are finite")
+    });
+    for pair in by_epsilon.chunks(2) {
+        if let [greedy, every] = pair {
+            println!(
+                "  epsilon {}: after greedy moves {:.3} (± {:.3}), after every move {:.3} (± {:.3})",
+                greedy.configuration.get("epsilon"),
+                greedy.mean,
+                greedy.std_error,
+                every.mean,
+                every.std_error
+            );
+        }
     }
 }
 
-fn visualize_values(values: Vec<Value>) {
+fn mnk(game: &MnkGame, games: u32, config: &RLConfig, seed: Option<u64>) {
+    let mut x_agent = MnkRLAgent::new(config.clone(), derive_seed(seed, 0));
+    let mut o_agent = MnkRLAgent::new(config.clone(), derive_seed(seed, 1));
+    for _ in 0..games {
+        play_mnk_game(game, &mut x_agent, &mut o_agent);
+    }
     println!(
-        "{}|{}|{}\n-----\n{}|{}|{}\n-----\n{}|{}|{}\n",
-        values.get(0).expect("msg").to_string(),
-        values.get(1).expect("msg").to_string(),
-        values.get(2).expect("msg").to_string(),
-        values.get(3).expect("msg").to_string(),
-        values.get(4).expect("msg").to_string(),
-        values.get(5).expect("msg").to_string(),
-        values.get(6).expect("msg").to_string(),
-        values.get(7).expect("msg").to_string(),
-        values.get(8).expect("msg").to_string()
-    )
+        "Trained on the {},{},{}-game for {} games ({} winning lines); X's table holds {} positions, O's {}",
+        game.rows(),
+        game.cols(),
+        game.k(),
+        games,
+        game.lines().len(),
+        x_agent.table_size(),
+        o_agent.table_size()
+    );
+    x_agent.freeze();
+    o_agent.freeze();
+    let eval_games = 1000;
+    let mut random = MnkRandomAgent::new(derive_seed(seed, 2));
+    let mut as_x = MatchSummary::default();
+    let mut as_o = MatchSummary::default();
+    for _ in 0..eval_games {
+        let (result, moves) = play_mnk_game(game, &mut x_agent, &mut random);
+        as_x.record_result(result, moves);
+        let (result, moves) = play_mnk_game(game, &mut random, &mut o_agent);
+        as_o.record_result(result, moves);
+    }
+    println!("\nX against random play:\n{}", as_x);
+    println!("\nO against random play:\n{}", as_o);
+}
+
+fn main() {
+    let cli = expand_config(std::env::args().skip(1))
+        .and_then(Cli::parse)
+        .unwrap_or_else(|message| {
+            eprintln!("Error: {}\n\n{}", message, USAGE);
+            process::exit(2);
+        });
+    let run = TrainingRun {
+        threads: cli.threads,
+        seed: cli.seed,
+        quiet: cli.quiet,
+    };
+    catch_interrupts();
+    match cli.command {
+        Command::Train {
+            games,
+            save_policy: None,
+        } => {
+            train(games, &cli.config, run);
+            println!(
+                "{}; policies saved to {} and {}",
+                trained_for(games),
+                policy_path(PlayerMarker::X).display(),
+                policy_path(PlayerMarker::O).display()
+            );
+        }
+        Command::Train {
+            games,
+            save_policy: Some((side, path)),
+        } => {
+            let (x_rl, o_rl) = train_both(games, &cli.config, run);
+            let agent = match side {
+                PlayerMarker::X => x_rl,
+                PlayerMarker::O => o_rl,
+            };
+            save_or_exit(&agent, &path);
+            println!(
+                "{}; {} policy saved to {}",
+                trained_for(games),
+                PlayerMarker::player_char(side),
+                path.display()
+            );
+        }
+        Command::Play {
+            x,
+            o,
+            games,
+            load_policy,
+            log,
+        } => play(
+            x,
+            o,
+            games,
+            &AgentOptions {
+                show_values: cli.show_values,
+                load_policy: load_policy.as_deref(),
+                config: &cli.config,
+                run,
+            },
+            log.as_deref(),
+        ),
+        Command::Eval {
+            x,
+            o,
+            games,
+            load_policy,
+            freeze,
+            progress,
+        } => eval(
+            x,
+            o,
+            games,
+            &AgentOptions {
+                show_values: cli.show_values,
+                load_policy: load_policy.as_deref(),
+                config: &cli.config,
+                run,
+            },
+            freeze,
+            progress,
+        ),
+        Command::SelfPlay {
+            games,
+            training,
+            metrics_out,
+            jsonl,
+            seeds: Some(seeds),
+            ..
+        } => self_play_seeds(
+            games,
+            training,
+            &cli.config,
+            seeds,
+            metrics_out.as_deref(),
+            jsonl.as_deref(),
+            run,
+        ),
+        Command::SelfPlay {
+            games,
+            training,
+            metrics_out,
+            ratings_out,
+            trajectories,
+            jsonl,
+            plot,
+            display,
+            saved_state,
+            seeds: None,
+        } => self_play(
+            games,
+            training,
+            &cli.config,
+            metrics_out.as_deref(),
+            jsonl.as_deref(),
+            plot.as_deref(),
+            SelfPlayRun {
+                display,
+                saved_state: saved_state.as_ref(),
+                ratings_out: ratings_out.as_deref(),
+                trajectories: trajectories.as_deref(),
+            },
+        ),
+        Command::Serve {
+            listen,
+            agents,
+            games,
+        } => serve(
+            &listen,
+            &agents,
+            games,
+            &AgentOptions {
+                show_values: false,
+                load_policy: None,
+                config: &cli.config,
+                run,
+            },
+        ),
+        Command::Tournament { agents, games } => tournament(
+            &agents,
+            games,
+            &AgentOptions {
+                show_values: false,
+                load_policy: None,
+                config: &cli.config,
+                run,
+            },
+        ),
+        Command::ShowValues {
+            position,
+            load_policy,
+            color,
+        } => show_values(
+            &position,
+            color,
+            &AgentOptions {
+                show_values: false,
+                load_policy: load_policy.as_deref(),
+                config: &cli.config,
+                run,
+            },
+        ),
+        Command::Policy {
+            action,
+            side,
+            load_policy,
+            save_policy,
+            out,
+        } => policy(
+            action,
+            side,
+            save_policy.as_deref(),
+            out.as_deref(),
+            &AgentOptions {
+                show_values: false,
+                load_policy: load_policy.as_deref(),
+                config: &cli.config,
+                run,
+            },
+        ),
+        Command::Replay {
+            log,
+            games,
+            side,
+            load_policy,
+            blunder,
+            color,
+            what_if,
+        } => {
+            if let Some(what_if) = what_if {
+                // The loaded policy plays `side`, and the usual agent the other.
+                let [x, o] = [PlayerMarker::X, PlayerMarker::O].map(|player| {
+                    let load = load_policy.as_deref().filter(|_| side == Some(player));
+                    trained_rl(player, load, &cli.config, run)
+                });
+                replay_what_if(&log, games, what_if, [&x, &o]);
+            } else {
+                let review = cli.show_values.then(|| Review {
+                    agents: [PlayerMarker::X, PlayerMarker::O]
+                        .into_iter()
+                        .filter(|&player| side.is_none_or(|side| side == player))
+                        .map(|player| {
+                            let agent =
+                                trained_rl(player, load_policy.as_deref(), &cli.config, run);
+                            (player, agent)
+                        })
+                        .collect(),
+                    blunder,
+                    color,
+                });
+                replay(&log, games, review.as_ref())
+            }
+        }
+        Command::ReportVisitation {
+            trajectories,
+            top,
+            out,
+        } => report_visitation(&trajectories, top, out.as_deref()),
+        Command::Analyze {
+            side,
+            load_policy,
+            format,
+            out,
+            games,
+        } => analyze(
+            side,
+            format,
+            out.as_deref(),
+            games,
+            &AgentOptions {
+                show_values: false,
+                load_policy: load_policy.as_deref(),
+                config: &cli.config,
+                run,
+            },
+        ),
+        Command::Experiments { games } => experiments(games, &cli.config, run),
+        Command::Sweep {
+            alphas,
+            epsilons,
+            shaping,
+            initial_values,
+            seeds,
+            roles,
+            rules,
+            games,
+            eval_games,
+            out,
+        } => sweep(
+            Sweep::new(Goal::Maximize)
+                .with_parameter("alpha", alphas)
+                .with_parameter("epsilon", epsilons)
+                .with_parameter("shaping", shaping)
+                .with_parameter("initial_value", initial_values),
+            seeds,
+            RoleTraining {
+                roles,
+                training_games: games,
+                eval_games,
+                rules,
+            },
+            &cli.config,
+            run,
+            out.as_deref(),
+        ),
+        Command::ExplorationLearning {
+            epsilons,
+            seeds,
+            roles,
+            rules,
+            games,
+            eval_games,
+            out,
+        } => exploration_learning(
+            epsilons,
+            seeds,
+            RoleTraining {
+                roles,
+                training_games: games,
+                eval_games,
+                rules,
+            },
+            &cli.config,
+            run,
+            out.as_deref(),
+        ),
+        Command::AbTest {
+            config_b,
+            seeds,
+            games,
+            eval_games,
+        } => println!(
+            "{}",
+            ab_test(&cli.config, &config_b, seeds, games, eval_games, cli.seed)
+        ),
+        Command::EvalOnly {
+            policy,
+            side,
+            games,
+        } => eval_only(&policy, side, games, cli.seed),
+        Command::Mnk { game, games } => mnk(&game, games, &cli.config, cli.seed),
+        Command::Help => println!("{}", USAGE),
+    }
+    if interrupted() {
+        process::exit(130);
+    }
 }
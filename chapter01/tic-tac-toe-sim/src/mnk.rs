@@ -0,0 +1,353 @@
+use std::fmt;
+
+use rand::rngs::SmallRng;
+use rand::RngCore;
+use rl_core::{seeded_rng, AfterstateValueAgent};
+
+use crate::{AgentOutcome, GameResult, PlayerMarker, RLConfig};
+
+// The rules of an m,n,k-game: `rows` by `cols` cells, won by the first player
+// to get `k` marks in a row horizontally, vertically or diagonally.
+// Tic-tac-toe is the 3,3,3-game and Gomoku the 15,15,5-game.
+#[derive(Clone, Debug)]
+pub struct MnkGame {
+    rows: usize,
+    cols: usize,
+    k: usize,
+    lines: Vec<Vec<usize>>,
+}
+
+impl MnkGame {
+    pub fn new(rows: usize, cols: usize, k: usize) -> Result<MnkGame, String> {
+        if rows == 0 || cols == 0 {
+            return Err(format!("a {}x{} board has no cells", rows, cols));
+        }
+        if k == 0 || k > rows.max(cols) {
+            return Err(format!(
+                "{} in a row can't be made on a {}x{} board",
+                k, rows, cols
+            ));
+        }
+        Ok(MnkGame {
+            rows,
+            cols,
+            k,
+            lines: winning_lines(rows, cols, k),
+        })
+    }
+
+    pub fn rows(&self) -> usize {
+        self.rows
+    }
+
+    pub fn cols(&self) -> usize {
+        self.cols
+    }
+
+    pub fn k(&self) -> usize {
+        self.k
+    }
+
+    pub fn cells(&self) -> usize {
+        self.rows * self.cols
+    }
+
+    pub fn lines(&self) -> &[Vec<usize>] {
+        &self.lines
+    }
+
+    pub fn new_board(&self) -> MnkBoard {
+        MnkBoard {
+            cols: self.cols,
+            cells: vec![None; self.cells()],
+        }
+    }
+
+    pub fn check_winner(&self, board: &MnkBoard, player: PlayerMarker) -> bool {
+        self.lines
+            .iter()
+            .any(|line| line.iter().all(|&i| board.cells[i] == Some(player)))
+    }
+
+    pub fn is_draw(&self, board: &MnkBoard) -> bool {
+        board.is_full()
+            && !self.check_winner(board, PlayerMarker::X)
+            && !self.check_winner(board, PlayerMarker::O)
+    }
+}
+
+// Every run of `k` cells in a row on a `rows` by `cols` board, as row-major
+// cell indices: horizontal, then vertical, then both diagonals.
+pub fn winning_lines(rows: usize, cols: usize, k: usize) -> Vec<Vec<usize>> {
+    let directions: [(isize, isize); 4] = [(0, 1), (1, 0), (1, 1), (1, -1)];
+    let mut lines = Vec::new();
+    for (dr, dc) in directions {
+        for row in 0..rows as isize {
+            for col in 0..cols as isize {
+                let end_row = row + dr * (k as isize - 1);
+                let end_col = col + dc * (k as isize - 1);
+                if end_row < 0
+                    || end_row >= rows as isize
+                    || end_col < 0
+                    || end_col >= cols as isize
+                {
+                    continue;
+                }
+                lines.push(
+                    (0..k as isize)
+                        .map(|step| ((row + dr * step) * cols as isize + col + dc * step) as usize)
+                        .collect(),
+                );
+            }
+        }
+    }
+    lines
+}
+
+// A position in an m,n,k-game, one entry per cell in row-major order. Hashes
+// by its cells, so it can key a value table of any size.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct MnkBoard {
+    cols: usize,
+    cells: Vec<Option<PlayerMarker>>,
+}
+
+impl MnkBoard {
+    pub fn cell(&self, index: usize) -> Option<PlayerMarker> {
+        self.cells[index]
+    }
+
+    pub fn available(&self, index: usize) -> bool {
+        self.cells[index].is_none()
+    }
+
+    pub fn available_moves(&self) -> impl Iterator<Item = usize> + '_ {
+        (0..self.cells.len()).filter(|&i| self.available(i))
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.cells.iter().all(Option::is_some)
+    }
+
+    pub fn set(&mut self, index: usize, player: PlayerMarker) {
+        debug_assert!(self.available(index), "cell {} is already taken", index);
+        self.cells[index] = Some(player);
+    }
+
+    pub fn with_move(&self, index: usize, player: PlayerMarker) -> MnkBoard {
+        let mut next = self.clone();
+        next.set(index, player);
+        next
+    }
+}
+
+impl fmt::Display for MnkBoard {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let rows: Vec<String> = self
+            .cells
+            .chunks(self.cols)
+            .map(|row| {
+                row.iter()
+                    .map(|cell| cell.map_or('.', PlayerMarker::player_char))
+                    .collect()
+            })
+            .collect();
+        write!(f, "{}", rows.join("\n"))
+    }
+}
+
+// Anything that can play a side of an m,n,k-game; the counterpart of `Agent`
+// for boards other than 3x3.
+pub trait MnkAgent {
+    fn choose_move(&mut self, game: &MnkGame, board: &MnkBoard, player: PlayerMarker) -> usize;
+
+    fn observe_result(&mut self, outcome: AgentOutcome);
+
+    // Stops learning and exploring. Agents that never learn ignore it.
+    fn freeze(&mut self) {}
+}
+
+pub struct MnkRandomAgent {
+    rng: SmallRng,
+}
+
+impl MnkRandomAgent {
+    pub fn new(seed: Option<u64>) -> Self {
+        MnkRandomAgent {
+            rng: seeded_rng(seed),
+        }
+    }
+}
+
+impl MnkAgent for MnkRandomAgent {
+    fn choose_move(&mut self, _game: &MnkGame, board: &MnkBoard, _player: PlayerMarker) -> usize {
+        let available: Vec<usize> = board.available_moves().collect();
+        available[self.rng.next_u32() as usize % available.len()]
+    }
+
+    fn observe_result(&mut self, _outcome: AgentOutcome) {}
+}
+
+// The Section 1.5 afterstate learner on an m,n,k board: rl-core's
+// `AfterstateValueAgent` over the boards each move produces, following
+// `config`'s epsilon schedule while it learns.
+pub struct MnkRLAgent {
+    learner: AfterstateValueAgent<MnkBoard>,
+    config: RLConfig,
+    step: u32,
+    learning_enabled: bool,
+}
+
+impl MnkRLAgent {
+    pub fn new(config: RLConfig, seed: Option<u64>) -> Self {
+        MnkRLAgent {
+            learner: AfterstateValueAgent::new(
+                config.learning_rate as f64,
+                config.epsilon.value(0) as f64,
+                seed,
+            )
+            .with_gamma(config.gamma as f64),
+            config,
+            step: 0,
+            learning_enabled: true,
+        }
+    }
+
+    pub fn table_size(&self) -> usize {
+        self.learner.values().len()
+    }
+}
+
+impl MnkAgent for MnkRLAgent {
+    fn choose_move(&mut self, _game: &MnkGame, board: &MnkBoard, player: PlayerMarker) -> usize {
+        if self.learning_enabled {
+            self.learner
+                .set_epsilon(self.config.epsilon.value(self.step) as f64);
+            self.step += 1;
+        }
+        self.learner.choose(
+            board
+                .available_moves()
+                .map(|i| (i, board.with_move(i, player)))
+                .collect(),
+        )
+    }
+
+    fn observe_result(&mut self, outcome: AgentOutcome) {
+        self.learner.finish(self.config.reward(outcome) as f64);
+    }
+
+    fn freeze(&mut self) {
+        self.learning_enabled = false;
+        self.learner.freeze();
+    }
+}
+
+// Plays one m,n,k-game, X first, and returns the result and the number of
+// moves made.
+pub fn play_mnk_game(
+    game: &MnkGame,
+    x_agent: &mut dyn MnkAgent,
+    o_agent: &mut dyn MnkAgent,
+) -> (GameResult, usize) {
+    let mut board = game.new_board();
+    let mut player = PlayerMarker::X;
+    let mut moves = 0;
+    loop {
+        let index = match player {
+            PlayerMarker::X => x_agent.choose_move(game, &board, player),
+            PlayerMarker::O => o_agent.choose_move(game, &board, player),
+        };
+        board.set(index, player);
+        moves += 1;
+        if game.check_winner(&board, player) {
+            let (x_outcome, o_outcome, result) = match player {
+                PlayerMarker::X => (AgentOutcome::Win, AgentOutcome::Loss, GameResult::XWin),
+                PlayerMarker::O => (AgentOutcome::Loss, AgentOutcome::Win, GameResult::OWin),
+            };
+            x_agent.observe_result(x_outcome);
+            o_agent.observe_result(o_outcome);
+            return (result, moves);
+        }
+        if board.is_full() {
+            x_agent.observe_result(AgentOutcome::Draw);
+            o_agent.observe_result(AgentOutcome::Draw);
+            return (GameResult::Draw, moves);
+        }
+        player = player.opposite();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn three_by_three_lines_match_tic_tac_toe() {
+        let mut lines = winning_lines(3, 3, 3);
+        lines.sort();
+        let mut expected: Vec<Vec<usize>> = crate::board::WINNING_COMBINATIONS
+            .iter()
+            .map(|line| line.to_vec())
+            .collect();
+        expected.sort();
+        assert_eq!(lines, expected);
+    }
+
+    #[test]
+    fn line_counts_for_larger_boards() {
+        // 4x4 with 3 in a row: 8 horizontal, 8 vertical, 4 + 4 diagonal.
+        assert_eq!(winning_lines(4, 4, 3).len(), 24);
+        // Gomoku: 11 runs of 5 per row and column, 11 * 11 per diagonal direction.
+        assert_eq!(winning_lines(15, 15, 5).len(), 2 * 15 * 11 + 2 * 11 * 11);
+        // A 3x5 board only fits 5 in a row horizontally.
+        assert_eq!(
+            winning_lines(3, 5, 5),
+            vec![
+                vec![0, 1, 2, 3, 4],
+                vec![5, 6, 7, 8, 9],
+                vec![10, 11, 12, 13, 14]
+            ]
+        );
+    }
+
+    #[test]
+    fn detects_wins_and_draws() {
+        let game = MnkGame::new(4, 4, 3).unwrap();
+        let mut board = game.new_board();
+        for i in [5, 10, 15] {
+            board.set(i, PlayerMarker::O);
+        }
+        assert!(game.check_winner(&board, PlayerMarker::O));
+        assert!(!game.check_winner(&board, PlayerMarker::X));
+        assert!(!game.is_draw(&board));
+    }
+
+    #[test]
+    fn rejects_impossible_games() {
+        assert!(MnkGame::new(0, 3, 3).is_err());
+        assert!(MnkGame::new(3, 3, 4).is_err());
+        assert!(MnkGame::new(3, 3, 0).is_err());
+    }
+
+    #[test]
+    fn learns_to_beat_random_play_on_a_4x4_board() {
+        let game = MnkGame::new(4, 4, 3).unwrap();
+        let mut learner = MnkRLAgent::new(RLConfig::default(), Some(1));
+        let mut random = MnkRandomAgent::new(Some(2));
+        for _ in 0..3000 {
+            play_mnk_game(&game, &mut random, &mut learner);
+        }
+        learner.freeze();
+        let wins = (0..500)
+            .filter(|_| {
+                matches!(
+                    play_mnk_game(&game, &mut random, &mut learner).0,
+                    GameResult::OWin
+                )
+            })
+            .count();
+        assert!(learner.table_size() > 0);
+        assert!(wins > 250, "won {} of 500", wins);
+    }
+}
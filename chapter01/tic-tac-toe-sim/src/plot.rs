@@ -0,0 +1,61 @@
+use std::path::Path;
+use std::process;
+
+use rl_core::{write_svg, Chart};
+use sutton_tic_tac_toe::{EvalOpponent, TrainingStats};
+
+// Draws a self-play run to `path`: the first agent's learning curve against
+// random play and, if there were checkpoints, both agents' results at each,
+// exiting on errors.
+pub fn self_play(path: &Path, stats: &TrainingStats, opponent: EvalOpponent) {
+    let mut charts = Vec::new();
+    if !stats.metrics.is_empty() {
+        let rate = |rate: fn(&sutton_tic_tac_toe::Metrics) -> f32| {
+            stats
+                .metrics
+                .iter()
+                .map(move |point| (point.episode as f64, rate(point) as f64))
+        };
+        charts.push(
+            Chart::new(
+                "First agent as X against random play",
+                "Episodes",
+                "Fraction of games",
+            )
+            .with_y_range(0.0, 1.0)
+            .with_series("won", rate(|point| point.win_rate))
+            .with_series("drawn", rate(|point| point.draw_rate))
+            .with_series("lost", rate(|point| point.loss_rate)),
+        );
+    }
+    if !stats.evaluations.is_empty() {
+        let rate = |rate: fn(&sutton_tic_tac_toe::Evaluation) -> f32| {
+            stats
+                .evaluations
+                .iter()
+                .map(move |evaluation| (evaluation.episode as f64, rate(evaluation) as f64))
+        };
+        charts.push(
+            Chart::new(
+                &format!("Checkpoints against {:?}", opponent),
+                "Episodes",
+                "Fraction of games",
+            )
+            .with_y_range(0.0, 1.0)
+            .with_series("first agent won", rate(|e| e.x_agent_win_rate))
+            .with_series("first agent lost", rate(|e| e.x_agent_loss_rate))
+            .with_series("second agent won", rate(|e| e.o_agent_win_rate))
+            .with_series("second agent lost", rate(|e| e.o_agent_loss_rate)),
+        );
+    }
+    if charts.is_empty() {
+        eprintln!(
+            "Nothing to plot: the run ended before the first learning-curve point or checkpoint"
+        );
+        return;
+    }
+    if let Err(e) = write_svg(path, &charts) {
+        eprintln!("Error: could not write {}: {}", path.display(), e);
+        process::exit(1);
+    }
+}
@@ -0,0 +1,108 @@
+use std::io::{self, IsTerminal};
+use std::time::{Duration, Instant};
+
+const WIDTH: usize = 30;
+
+// Redraws at most this often, so updating after every game stays cheap.
+const REDRAW_INTERVAL: Duration = Duration::from_millis(100);
+
+// A one-line progress bar on stderr for long runs: games done out of `total`,
+// games per second, the time left at that rate and whatever detail the
+// caller adds, such as the exploration rate. Nothing is drawn when stderr
+// isn't a terminal, so logs of scripted runs stay clean.
+pub struct Progress {
+    total: u64,
+    started: Instant,
+    drawn: Option<Instant>,
+    visible: bool,
+}
+
+impl Progress {
+    pub fn new(total: u64) -> Self {
+        Progress {
+            total,
+            started: Instant::now(),
+            drawn: None,
+            visible: io::stderr().is_terminal(),
+        }
+    }
+
+    // Records that `done` games are finished, redrawing the bar if it is due.
+    // The last update, with every game done, is always drawn and ends the
+    // line.
+    pub fn update(&mut self, done: u64, detail: &str) {
+        let now = Instant::now();
+        let finished = done >= self.total;
+        if !self.visible
+            || (!finished
+                && self
+                    .drawn
+                    .is_some_and(|drawn| now - drawn < REDRAW_INTERVAL))
+        {
+            return;
+        }
+        self.drawn = Some(now);
+        eprint!("\r{}", self.line(done, now - self.started, detail));
+        if finished {
+            eprintln!();
+        }
+    }
+
+    // Ends the line of a bar stopped before every game was done, so that what
+    // is printed next starts on a line of its own.
+    pub fn stop(&mut self) {
+        if self.visible && self.drawn.is_some() {
+            eprintln!();
+        }
+    }
+
+    fn line(&self, done: u64, elapsed: Duration, detail: &str) -> String {
+        let filled = (done.min(self.total) as usize * WIDTH) / self.total.max(1) as usize;
+        let rate = done as f64 / elapsed.as_secs_f64().max(1e-3);
+        let left = self.total.saturating_sub(done) as f64 / rate.max(1e-3);
+        let mut line = format!(
+            "[{}{}] {}/{}  {:.0} games/s  ETA {}",
+            "#".repeat(filled),
+            " ".repeat(WIDTH - filled),
+            done,
+            self.total,
+            rate,
+            format_duration(Duration::from_secs_f64(left.min(1e7)))
+        );
+        if !detail.is_empty() {
+            line += "  ";
+            line += detail;
+        }
+        // Pads over anything left from a longer line before.
+        format!("{:<90}", line)
+    }
+}
+
+// `duration` as m:ss, or h:mm:ss from an hour up.
+pub(crate) fn format_duration(duration: Duration) -> String {
+    let seconds = duration.as_secs();
+    if seconds >= 3600 {
+        format!(
+            "{}:{:02}:{:02}",
+            seconds / 3600,
+            seconds % 3600 / 60,
+            seconds % 60
+        )
+    } else {
+        format!("{}:{:02}", seconds / 60, seconds % 60)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_line_shows_rate_time_left_and_detail() {
+        let progress = Progress::new(1000);
+        let line = progress.line(250, Duration::from_secs(5), "epsilon 0.100");
+        assert!(line.starts_with("[#######                       ] 250/1000"));
+        assert!(line.contains("  50 games/s  ETA 0:15  epsilon 0.100"));
+        assert_eq!(format_duration(Duration::from_secs(3725)), "1:02:05");
+    }
+}
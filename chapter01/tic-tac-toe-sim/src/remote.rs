@@ -0,0 +1,173 @@
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::TcpStream;
+use std::time::Duration;
+
+use serde_json::{json, Value};
+
+use crate::agents::is_over;
+use crate::{Agent, AgentOutcome, Board, GameError, PlayerMarker};
+
+// How long a remote agent has to answer a move request before its game is
+// abandoned.
+const MOVE_TIMEOUT: Duration = Duration::from_secs(60);
+
+// An agent playing from another program over a TCP connection, so agents
+// written in any language can play the ones here. The engine sends one JSON
+// object a line:
+//
+//     {"type":"move","board":"X...O....","player":"X","legal":[1,2,3,5,6,7,8]}
+//     {"type":"result","board":"XXXOO....","outcome":"win"}
+//
+// and the agent answers each move request with a line like {"move":2}.
+// Results need no answer. Cells are numbered 0 to 8 in row-major order, boards
+// are written X, O or . for each cell, and `outcome` is "win", "loss" or
+// "draw" for the remote agent.
+pub struct RemoteAgent {
+    reader: BufReader<TcpStream>,
+    writer: TcpStream,
+}
+
+impl RemoteAgent {
+    pub fn new(stream: TcpStream) -> io::Result<Self> {
+        stream.set_read_timeout(Some(MOVE_TIMEOUT))?;
+        Ok(RemoteAgent {
+            reader: BufReader::new(stream.try_clone()?),
+            writer: stream,
+        })
+    }
+
+    fn send(&mut self, message: Value) -> io::Result<()> {
+        self.writer.write_all(format!("{}\n", message).as_bytes())
+    }
+
+    fn read_move(&mut self) -> io::Result<usize> {
+        let mut line = String::new();
+        if self.reader.read_line(&mut line)? == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "the remote agent disconnected",
+            ));
+        }
+        serde_json::from_str::<Value>(&line)
+            .ok()
+            .and_then(|reply| reply.get("move")?.as_u64())
+            .map(|index| index as usize)
+            .ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("expected {{\"move\": <cell>}}, got {:?}", line.trim()),
+                )
+            })
+    }
+}
+
+impl Agent for RemoteAgent {
+    // Panics if the remote agent fails; the game loops call `try_choose_move`,
+    // which reports it.
+    fn choose_move(&mut self, board: &Board, player: PlayerMarker) -> usize {
+        self.try_choose_move(board, player)
+            .unwrap_or_else(|error| panic!("remote agent: {}", error))
+    }
+
+    fn try_choose_move(&mut self, board: &Board, player: PlayerMarker) -> Result<usize, GameError> {
+        if is_over(board) {
+            return Err(GameError::NoMoves);
+        }
+        let legal: Vec<usize> = board.available_moves().collect();
+        self.send(json!({
+            "type": "move",
+            "board": board.cells(),
+            "player": PlayerMarker::player_char(player).to_string(),
+            "legal": legal,
+        }))
+        .map_err(GameError::Input)?;
+        let index = self.read_move().map_err(GameError::Input)?;
+        if index < 9 && board.available(index) {
+            Ok(index)
+        } else {
+            Err(GameError::IllegalMove { player, index })
+        }
+    }
+
+    fn observe_result(&mut self, outcome: AgentOutcome, board: &Board) {
+        let outcome = match outcome {
+            AgentOutcome::Win => "win",
+            AgentOutcome::Loss => "loss",
+            AgentOutcome::Draw => "draw",
+        };
+        // A connection that has failed shows up at the next move request.
+        let _ = self.send(json!({
+            "type": "result",
+            "board": board.cells(),
+            "outcome": outcome,
+        }));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::TcpListener;
+    use std::thread;
+
+    use crate::{play_game, GameResult, MinimaxAgent};
+
+    #[test]
+    fn remote_agents_play_over_json_lines() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        // A client that plays the first legal cell and reports what it saw.
+        let client = thread::spawn(move || {
+            let stream = TcpStream::connect(address).unwrap();
+            let mut writer = stream.try_clone().unwrap();
+            let mut seen = Vec::new();
+            for line in BufReader::new(stream).lines() {
+                let message: Value = serde_json::from_str(&line.unwrap()).unwrap();
+                if message["type"] == "move" {
+                    let cell = message["legal"][0].as_u64().unwrap();
+                    writeln!(writer, "{}", json!({ "move": cell })).unwrap();
+                }
+                seen.push(message);
+            }
+            seen
+        });
+        let (stream, _) = listener.accept().unwrap();
+        let mut remote = RemoteAgent::new(stream).unwrap();
+        let result = play_game(&mut remote, &mut MinimaxAgent::new()).unwrap();
+        assert_eq!(result, GameResult::OWin);
+        drop(remote);
+
+        let seen = client.join().unwrap();
+        assert_eq!(seen[0]["board"], ".........");
+        assert_eq!(seen[0]["player"], "X");
+        let last = seen.last().unwrap();
+        assert_eq!(last["type"], "result");
+        assert_eq!(last["outcome"], "loss");
+    }
+
+    #[test]
+    fn bad_replies_are_errors() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let client = thread::spawn(move || {
+            let mut stream = TcpStream::connect(address).unwrap();
+            stream.write_all(b"{\"move\": 0}\nnot json\n").unwrap();
+        });
+        let (stream, _) = listener.accept().unwrap();
+        let mut remote = RemoteAgent::new(stream).unwrap();
+        let board: Board = "X........".parse().unwrap();
+        assert!(matches!(
+            remote.try_choose_move(&board, PlayerMarker::O),
+            Err(GameError::IllegalMove { index: 0, .. })
+        ));
+        assert!(matches!(
+            remote.try_choose_move(&board, PlayerMarker::O),
+            Err(GameError::Input(_))
+        ));
+        client.join().unwrap();
+        assert!(matches!(
+            remote.try_choose_move(&board, PlayerMarker::O),
+            Err(GameError::Input(_))
+        ));
+    }
+}
@@ -0,0 +1,1257 @@
+use std::collections::{HashMap, VecDeque};
+use std::fs::File;
+#[cfg(feature = "cli")]
+use std::io::IsTerminal;
+use std::io::{self, BufReader, BufWriter};
+use std::path::Path;
+
+use rand::rngs::SmallRng;
+use rand::{Rng, RngCore};
+use rl_core::seeded_rng;
+use serde::{Deserialize, Serialize};
+
+use crate::updates::{
+    afterstate_key, assert_finite_table, greedy_move, n_step_flush, n_step_update, replay_update,
+    softmax_move, sorted_entries, td_lambda_update, ucb1_move, EpisodeBuffer, Transition,
+};
+use crate::{
+    polyak_update, Agent, AgentOutcome, Board, ExplorationStrategy, OpeningBook, PlayerMarker,
+    PolicyTable, RLConfig,
+};
+
+// Bumped whenever the meaning of saved Q-values changes. Version 2 keys the
+// table on canonical board states.
+const SAVE_FORMAT_VERSION: u32 = 2;
+
+// A shaping potential Φ(board, player) for `RLAgent`.
+pub type Potential = Box<dyn Fn(&Board, PlayerMarker) -> f32 + Send>;
+
+// Keeps its values in a `HashMap` unless built with `with_table`; only that
+// form can be saved, combined or merged.
+pub struct RLAgent<T = HashMap<Board, f32>> {
+    pub(crate) q_table: T,
+    // Eligibility traces for TD(lambda); empty between episodes.
+    traces: HashMap<Board, f32>,
+    // How often each afterstate has been played, for UCB1 and count-based
+    // exploration.
+    pub(crate) visit_counts: HashMap<Board, u32>,
+    // The last afterstates played and the reward that followed each, awaiting
+    // their n-step backup; empty between episodes.
+    n_step_buffer: VecDeque<(Board, f32)>,
+    // The afterstates this agent produced in the current game, in order. The
+    // last one is backed up on the agent's next greedy move or at the end of
+    // the game.
+    trajectory: Vec<Board>,
+    pub(crate) config: RLConfig,
+    // Moves chosen so far, used to look up the current epsilon.
+    step: u32,
+    // Games finished while learning, to end the warm-up.
+    episodes: u32,
+    // Cleared by `freeze` so evaluation and human play leave the Q-table untouched.
+    learning_enabled: bool,
+    // Potential function for shaping: each backup adds Φ(s') - Φ(s) over the
+    // boards seen on consecutive turns, with Φ of a finished game taken as 0.
+    // Shaping of this form leaves the optimal policy unchanged.
+    shaping: Option<Potential>,
+    // Φ of the board at the last move that was backed up; 0 between episodes.
+    prev_potential: f32,
+    // Drives exploration, so a fixed seed reproduces the same Q-table.
+    rng: SmallRng,
+    // Present when `config.replay_capacity` is above 0.
+    replay: Option<EpisodeBuffer>,
+    // The board and move that produced the last afterstate in `trajectory`,
+    // for replay transitions.
+    prev_move: Option<(Board, usize)>,
+    // Moves played without consulting the Q-table in positions it covers.
+    opening_book: OpeningBook,
+    // The Polyak average of `q_table`, when `config.polyak_beta` is above 0.
+    polyak: Option<T>,
+    // Prints the value of every candidate move before choosing one.
+    #[cfg_attr(not(feature = "cli"), allow(dead_code))]
+    show_values: bool,
+}
+
+impl RLAgent {
+    pub fn new(config: RLConfig, seed: Option<u64>) -> Self {
+        RLAgent::with_table(HashMap::new(), config, seed)
+    }
+
+    pub fn with_q_table(q_table: HashMap<Board, f32>, config: RLConfig) -> Self {
+        RLAgent::with_table(q_table, config, None)
+    }
+
+    // Learned afterstate values, keyed on canonical boards when
+    // `config.symmetry` is on.
+    pub fn q_table(&self) -> &HashMap<Board, f32> {
+        &self.q_table
+    }
+
+    // One agent that plays both sides, from an agent trained as X and one
+    // trained as O. Their afterstates never collide, since X's hold one more
+    // X than O and O's hold equal numbers, so the tables and opening books can
+    // simply be joined. Uses `x`'s config.
+    pub fn combined(x: &RLAgent, o: &RLAgent) -> RLAgent {
+        let mut q_table = x.q_table.clone();
+        q_table.extend(o.q_table.iter().map(|(&state, &value)| (state, value)));
+        let mut opening_book = x.opening_book.clone();
+        opening_book.entries.extend(
+            o.opening_book
+                .entries
+                .iter()
+                .map(|(&board, &index)| (board, index)),
+        );
+        RLAgent::with_q_table(q_table, x.config.clone()).with_opening_book(opening_book)
+    }
+
+    // Writes the Q-table, visit counts, opening book and config as JSON if `path` ends in
+    // `.json`, or as bincode otherwise. With Polyak averaging the table is the
+    // average, which is what the policy plays by.
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let saved = SavedPolicy {
+            version: SAVE_FORMAT_VERSION,
+            config: self.config.clone(),
+            q_table: self.polyak.as_ref().unwrap_or(&self.q_table).clone(),
+            opening_book: self.opening_book.clone(),
+            visit_counts: self.visit_counts.clone(),
+        };
+        let writer = BufWriter::new(File::create(path)?);
+        if is_json(path) {
+            serde_json::to_writer(writer, &saved)?;
+        } else {
+            bincode::serialize_into(writer, &saved).map_err(io::Error::other)?;
+        }
+        Ok(())
+    }
+
+    // Reads an agent written by `save`, choosing the format the same way.
+    pub fn load(path: &Path) -> io::Result<RLAgent> {
+        let reader = BufReader::new(File::open(path)?);
+        let saved: SavedPolicy = if is_json(path) {
+            serde_json::from_reader(reader)?
+        } else {
+            bincode::deserialize_from(reader).map_err(io::Error::other)?
+        };
+        Ok(RLAgent::from_saved(saved, &path.display().to_string()))
+    }
+
+    // Reads an agent from the bytes of a bincode file written by `save`, for
+    // builds that get the policy some other way than from a file, such as a
+    // web page fetching it.
+    pub fn from_bytes(bytes: &[u8]) -> io::Result<RLAgent> {
+        let saved = bincode::deserialize(bytes).map_err(io::Error::other)?;
+        Ok(RLAgent::from_saved(saved, "the policy"))
+    }
+
+    // The agent in `saved`, warning about `name` if it was saved by another
+    // version or trained with other rewards.
+    fn from_saved(saved: SavedPolicy, name: &str) -> RLAgent {
+        if saved.version != SAVE_FORMAT_VERSION {
+            eprintln!(
+                "Warning: {} was saved in format version {} (current is {}); Q-values may be on a different scale.",
+                name,
+                saved.version,
+                SAVE_FORMAT_VERSION
+            );
+        }
+        let defaults = RLConfig::default();
+        if (
+            saved.config.win_reward,
+            saved.config.draw_reward,
+            saved.config.loss_reward,
+        ) != (
+            defaults.win_reward,
+            defaults.draw_reward,
+            defaults.loss_reward,
+        ) {
+            eprintln!(
+                "Warning: {} was trained with rewards {}/{}/{} (win/draw/loss); continuing with those.",
+                name,
+                saved.config.win_reward,
+                saved.config.draw_reward,
+                saved.config.loss_reward
+            );
+        }
+        RLAgent {
+            visit_counts: saved.visit_counts,
+            ..RLAgent::with_q_table(saved.q_table, saved.config)
+                .with_opening_book(saved.opening_book)
+        }
+    }
+}
+
+impl<T: PolicyTable> RLAgent<T> {
+    // An agent keeping its values in `table`, such as a `DenseTable`.
+    pub fn with_table(table: T, config: RLConfig, seed: Option<u64>) -> Self {
+        let replay =
+            (config.replay_capacity > 0).then(|| EpisodeBuffer::new(config.replay_capacity));
+        let polyak = (config.polyak_beta > 0.0).then(|| table.clone());
+        let mut agent = RLAgent {
+            q_table: table,
+            traces: HashMap::new(),
+            visit_counts: HashMap::new(),
+            n_step_buffer: VecDeque::new(),
+            trajectory: Vec::new(),
+            config,
+            step: 0,
+            episodes: 0,
+            learning_enabled: true,
+            shaping: None,
+            prev_potential: 0.0,
+            rng: seeded_rng(seed),
+            replay,
+            prev_move: None,
+            opening_book: OpeningBook::default(),
+            polyak,
+            show_values: false,
+        };
+        agent.schedule_alpha();
+        agent
+    }
+
+    // The afterstates played so far in the current game.
+    pub fn trajectory(&self) -> &[Board] {
+        &self.trajectory
+    }
+
+    // `q_table` for agents built with `with_table`.
+    pub fn table(&self) -> &T {
+        &self.q_table
+    }
+
+    pub fn with_shaping(
+        mut self,
+        potential: impl Fn(&Board, PlayerMarker) -> f32 + Send + 'static,
+    ) -> Self {
+        self.shaping = Some(Box::new(potential));
+        self
+    }
+
+    pub fn with_opening_book(mut self, opening_book: OpeningBook) -> Self {
+        self.opening_book = opening_book;
+        self
+    }
+
+    pub fn with_value_display(mut self, show_values: bool) -> Self {
+        self.show_values = show_values;
+        self
+    }
+
+    // Sets the step size for the next game from `config.alpha_schedule`.
+    fn schedule_alpha(&mut self) {
+        if let Some(schedule) = &self.config.alpha_schedule {
+            let games = self.episodes.saturating_sub(self.config.warmup_episodes);
+            self.config.learning_rate = schedule.value(games);
+        }
+    }
+
+    // The values the agent plays by: the Polyak average, if it keeps one and
+    // isn't learning, or else the live ones.
+    fn playing_table(&self) -> &T {
+        match &self.polyak {
+            Some(polyak) if !self.learning_enabled => polyak,
+            _ => &self.q_table,
+        }
+    }
+
+    // Moves the Polyak average, if there is one, toward the live values.
+    fn average_values(&mut self) {
+        if let Some(polyak) = &mut self.polyak {
+            polyak_update(&self.q_table, polyak, self.config.polyak_beta);
+        }
+    }
+
+    // Whether the agent is still playing its `config.warmup_episodes`.
+    fn warming_up(&self) -> bool {
+        self.learning_enabled && self.episodes < self.config.warmup_episodes
+    }
+}
+
+impl<T: PolicyTable> Agent for RLAgent<T> {
+    fn choose_move(&mut self, board: &Board, player: PlayerMarker) -> usize {
+        if let Some(book_move) = self.opening_book.lookup(board) {
+            self.trajectory.push(afterstate_key(
+                board,
+                book_move,
+                player,
+                self.config.symmetry,
+            ));
+            self.prev_move = Some((*board, book_move));
+            return book_move;
+        }
+        // Frozen agents play greedily and don't advance the epsilon schedule,
+        // and nor does the warm-up, which explores every move.
+        let warming_up = self.warming_up();
+        let (epsilon, exploration) = if warming_up {
+            (1.0, ExplorationStrategy::EpsilonGreedy)
+        } else if self.learning_enabled {
+            self.step += 1;
+            (
+                self.config.epsilon.value(self.step - 1),
+                self.config.exploration.clone(),
+            )
+        } else {
+            (0.0, ExplorationStrategy::EpsilonGreedy)
+        };
+        let mut best_move = None;
+        let mut best_value = f32::MIN;
+        let mut values = Vec::new();
+        for i in board.available_moves() {
+            let value = self
+                .playing_table()
+                .value(&afterstate_key(board, i, player, self.config.symmetry))
+                .unwrap_or(self.config.initial_value);
+            values.push((i, value));
+            if value > best_value {
+                best_value = value;
+                best_move = Some(i);
+            }
+        }
+        #[cfg(feature = "cli")]
+        if self.show_values {
+            println!(
+                "{}\n",
+                format_values(board, &values, io::stdout().is_terminal())
+            );
+        }
+        // As in Section 1.5, a random exploratory move backs nothing up unless
+        // `config.learn_from_exploration` is set: the afterstate before it is
+        // left alone and learning resumes from the afterstate it produced.
+        let mut explored = false;
+        let chosen = match exploration {
+            ExplorationStrategy::EpsilonGreedy => {
+                if self.rng.random::<f32>() < epsilon {
+                    let available: Vec<usize> = board.available_moves().collect();
+                    let index = self.rng.next_u32() as usize % available.len();
+                    explored = true;
+                    *available.get(index).expect("Board is full")
+                } else {
+                    best_move.expect("No available moves")
+                }
+            }
+            ExplorationStrategy::Ucb1(c) => ucb1_move(
+                &self.q_table,
+                self.config.initial_value,
+                &self.visit_counts,
+                board,
+                player,
+                c,
+                self.config.symmetry,
+            ),
+            ExplorationStrategy::Softmax { temperature } => softmax_move(
+                &self.q_table,
+                self.config.initial_value,
+                board,
+                player,
+                temperature,
+                self.config.symmetry,
+                &mut self.rng,
+            ),
+            ExplorationStrategy::CountBased { beta } => {
+                greedy_move(board, player, self.config.symmetry, |key| {
+                    let count = *self.visit_counts.get(&key).unwrap_or(&0);
+                    self.q_table
+                        .value(&key)
+                        .unwrap_or(self.config.initial_value)
+                        + beta / (count as f32 + 1.0).sqrt()
+                })
+            }
+        };
+        let afterstate = afterstate_key(board, chosen, player, self.config.symmetry);
+        if self.learning_enabled {
+            *self.visit_counts.entry(afterstate).or_insert(0) += 1;
+            let potential = self
+                .shaping
+                .as_ref()
+                .map_or(0.0, |potential| potential(board, player));
+            let shaping_reward = self.config.clip_reward(
+                self.config.step_reward + self.config.gamma * potential - self.prev_potential,
+            );
+            // Backups after an exploratory move bootstrap from the move made,
+            // learning the values of the exploring policy rather than the
+            // greedy one.
+            let bootstrap = if explored {
+                values
+                    .iter()
+                    .find(|&&(i, _)| i == chosen)
+                    .map_or(self.config.initial_value, |&(_, value)| value)
+            } else {
+                best_value
+            };
+            self.prev_potential = potential;
+            if let Some(replay) = &mut self.replay {
+                if let Some((state, action)) = self.prev_move {
+                    replay.push(Transition {
+                        state,
+                        action,
+                        reward: shaping_reward,
+                        next_state: *board,
+                        terminal: false,
+                    });
+                    if !warming_up {
+                        replay_update(&mut self.q_table, replay, &self.config, &mut self.rng);
+                    }
+                }
+            } else if warming_up {
+                // Nothing is backed up until the warm-up is over.
+            } else if explored && !self.config.learn_from_exploration {
+                // The returns of earlier afterstates no longer follow the greedy
+                // policy, so pending n-step backups and traces are dropped.
+                self.n_step_buffer.clear();
+                self.traces.clear();
+                if self.config.n_step > 1 {
+                    self.n_step_buffer.push_back((afterstate, 0.0));
+                }
+            } else if self.config.n_step > 1 {
+                if let Some((_, reward)) = self.n_step_buffer.back_mut() {
+                    *reward += shaping_reward;
+                }
+                n_step_update(
+                    &mut self.q_table,
+                    &mut self.n_step_buffer,
+                    bootstrap,
+                    &self.config,
+                );
+                self.n_step_buffer.push_back((afterstate, 0.0));
+            } else if let Some(&prev_board) = self.trajectory.last() {
+                td_lambda_update(
+                    &mut self.q_table,
+                    &mut self.traces,
+                    &prev_board,
+                    shaping_reward + self.config.gamma * bootstrap,
+                    &self.config,
+                );
+            }
+            if !warming_up {
+                self.average_values();
+            }
+        }
+        self.trajectory.push(afterstate);
+        self.prev_move = Some((*board, chosen));
+        chosen
+    }
+
+    fn observe_result(&mut self, outcome: AgentOutcome, board: &Board) {
+        let reward = self.config.clip_reward(
+            self.config.terminal_reward(outcome, board) + self.config.step_reward
+                - self.prev_potential,
+        );
+        let warming_up = self.warming_up();
+        if self.learning_enabled {
+            self.episodes += 1;
+            if let Some(replay) = &mut self.replay {
+                if let Some((state, action)) = self.prev_move {
+                    replay.push(Transition {
+                        state,
+                        action,
+                        reward,
+                        next_state: *board,
+                        terminal: true,
+                    });
+                    if !warming_up {
+                        replay_update(&mut self.q_table, replay, &self.config, &mut self.rng);
+                    }
+                }
+            } else if warming_up {
+            } else if self.config.n_step > 1 {
+                n_step_flush(
+                    &mut self.q_table,
+                    &mut self.n_step_buffer,
+                    reward,
+                    &self.config,
+                );
+            } else if let Some(&prev_board) = self.trajectory.last() {
+                td_lambda_update(
+                    &mut self.q_table,
+                    &mut self.traces,
+                    &prev_board,
+                    reward,
+                    &self.config,
+                );
+            }
+            if !warming_up {
+                self.average_values();
+            }
+        }
+        self.n_step_buffer.clear();
+        self.traces.clear();
+        self.trajectory.clear();
+        self.prev_move = None;
+        self.prev_potential = 0.0;
+        self.schedule_alpha();
+    }
+
+    fn assert_finite_q_values(&self) {
+        assert_finite_table(&self.q_table);
+    }
+
+    fn freeze(&mut self) {
+        self.learning_enabled = false;
+    }
+
+    fn unfreeze(&mut self) {
+        self.learning_enabled = true;
+    }
+
+    fn is_learning(&self) -> bool {
+        self.learning_enabled
+    }
+
+    fn reseed(&mut self, seed: u64) {
+        self.rng = seeded_rng(Some(seed));
+    }
+
+    fn value_summary(&self) -> Option<(usize, f32)> {
+        // Summed as f64, exactly for any table of plausible values, so the
+        // mean doesn't depend on the order the table iterates in.
+        let total: f64 = self.q_table.entries().map(|(_, value)| value as f64).sum();
+        Some((
+            self.q_table.len(),
+            (total / self.q_table.len().max(1) as f64) as f32,
+        ))
+    }
+
+    fn snapshot(&self) -> Option<Box<dyn Agent>> {
+        let table = self.polyak.as_ref().unwrap_or(&self.q_table);
+        let mut snapshot = RLAgent::with_table(table.clone(), self.config.clone(), None)
+            .with_opening_book(self.opening_book.clone());
+        snapshot.freeze();
+        Some(Box::new(snapshot))
+    }
+
+    fn save_state(&self) -> Option<Vec<u8>> {
+        let q_table = sorted_entries(&self.q_table);
+        let mut visit_counts: Vec<(Board, u32)> = self
+            .visit_counts
+            .iter()
+            .map(|(&board, &count)| (board, count))
+            .collect();
+        visit_counts.sort_by_key(|(board, _)| board.to_key());
+        let state = AgentState {
+            q_table,
+            visit_counts,
+            step: self.step,
+            episodes: self.episodes,
+            polyak: self
+                .polyak
+                .as_ref()
+                .map(|polyak| sorted_entries(polyak))
+                .unwrap_or_default(),
+            replay: self
+                .replay
+                .as_ref()
+                .map(|replay| replay.transitions.iter().cloned().collect())
+                .unwrap_or_default(),
+        };
+        Some(bincode::serialize(&state).expect("plain data always serializes"))
+    }
+
+    fn restore_state(&mut self, state: &[u8]) -> Result<(), String> {
+        let state: AgentState = bincode::deserialize(state).map_err(|error| error.to_string())?;
+        self.q_table = T::default();
+        for (board, value) in state.q_table {
+            *self.q_table.value_mut(board, value) = value;
+        }
+        self.visit_counts = state.visit_counts.into_iter().collect();
+        self.step = state.step;
+        self.episodes = state.episodes;
+        self.schedule_alpha();
+        if let Some(polyak) = &mut self.polyak {
+            *polyak = T::default();
+            for (board, value) in state.polyak {
+                *polyak.value_mut(board, value) = value;
+            }
+        }
+        if let Some(replay) = &mut self.replay {
+            replay.transitions = state.replay.into();
+        }
+        Ok(())
+    }
+
+    fn move_values(&self, board: &Board, player: PlayerMarker) -> Option<Vec<(usize, f32)>> {
+        Some(
+            board
+                .available_moves()
+                .map(|i| {
+                    let key = afterstate_key(board, i, player, self.config.symmetry);
+                    (
+                        i,
+                        self.playing_table()
+                            .value(&key)
+                            .unwrap_or(self.config.initial_value),
+                    )
+                })
+                .collect(),
+        )
+    }
+
+    fn exploration_rate(&self) -> Option<f32> {
+        match self.config.exploration {
+            ExplorationStrategy::EpsilonGreedy if self.learning_enabled => {
+                Some(self.config.epsilon.value(self.step))
+            }
+            ExplorationStrategy::EpsilonGreedy => Some(0.0),
+            _ => None,
+        }
+    }
+}
+
+// `RLAgent::save_state`'s form of an agent between games, when its traces,
+// trajectory and n-step buffer are empty. Entries are sorted by board so the
+// same agent always saves the same bytes.
+#[derive(Serialize, Deserialize)]
+struct AgentState {
+    q_table: Vec<(Board, f32)>,
+    visit_counts: Vec<(Board, u32)>,
+    step: u32,
+    episodes: u32,
+    polyak: Vec<(Board, f32)>,
+    replay: Vec<Transition>,
+}
+
+// On-disk form of a trained RL agent.
+#[derive(Serialize, Deserialize)]
+struct SavedPolicy {
+    version: u32,
+    config: RLConfig,
+    q_table: HashMap<Board, f32>,
+    #[serde(default)]
+    opening_book: OpeningBook,
+    // How often each entry was visited in training, for pruning.
+    #[serde(default)]
+    visit_counts: HashMap<Board, u32>,
+}
+
+fn is_json(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|extension| extension == "json")
+}
+
+// ANSI 256-colour codes from worst to best, for `format_values`.
+const VALUE_COLORS: [u8; 5] = [196, 208, 226, 154, 46];
+
+// The board as a grid with each move's value in its cell, every cell the same
+// width. The greedy move (the first best value) is bracketed. With `color`,
+// values are shaded red to green by where they fall between the lowest and the
+// highest on this board, and the greedy move is bold.
+pub fn format_values(board: &Board, values: &[(usize, f32)], color: bool) -> String {
+    let best = values
+        .iter()
+        .fold(None, |best: Option<(usize, f32)>, &(i, value)| match best {
+            Some((_, best_value)) if best_value >= value => best,
+            _ => Some((i, value)),
+        })
+        .map(|(i, _)| i);
+    let low = values.iter().map(|&(_, v)| v).fold(f32::INFINITY, f32::min);
+    let high = values
+        .iter()
+        .map(|&(_, v)| v)
+        .fold(f32::NEG_INFINITY, f32::max);
+    let cell = |i: usize| -> String {
+        let Some(&(_, value)) = values.iter().find(|&&(j, _)| j == i) else {
+            let mark = board.cell(i).map_or(' ', PlayerMarker::player_char);
+            return format!("{:^8}", mark);
+        };
+        let text = if best == Some(i) {
+            format!("[{:+.3}]", value)
+        } else {
+            format!(" {:+.3} ", value)
+        };
+        let text = format!("{:^8}", text);
+        if !color {
+            return text;
+        }
+        let position = if high > low {
+            (value - low) / (high - low)
+        } else {
+            1.0
+        };
+        let shade = VALUE_COLORS[((position * (VALUE_COLORS.len() - 1) as f32).round()) as usize];
+        let bold = if best == Some(i) { "\x1b[1m" } else { "" };
+        format!("{}\x1b[38;5;{}m{}\x1b[0m", bold, shade, text)
+    };
+    (0..3)
+        .map(|row| {
+            format!(
+                "{}|{}|{}",
+                cell(row * 3),
+                cell(row * 3 + 1),
+                cell(row * 3 + 2)
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n--------+--------+--------\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::table_bytes;
+    use crate::updates::{monte_carlo_update, update_q};
+    use crate::{
+        play_game, play_game_with_transcript, win_rate_vs_random, win_speed_vs_random,
+        DecaySchedule, QLearningAgent, RandomAgent, Trainer, TrainingConfig,
+    };
+    use rl_core::mean_and_std_error;
+    use std::collections::HashSet;
+    use std::fs;
+
+    #[test]
+    fn format_values_aligns_cells_and_brackets_the_greedy_move() {
+        let board: Board = "X.O......".parse().unwrap();
+        let values: Vec<(usize, f32)> = board
+            .available_moves()
+            .map(|i| (i, if i == 4 { 0.5 } else { -0.25 }))
+            .collect();
+        let grid = format_values(&board, &values, false);
+        let lines: Vec<&str> = grid.lines().collect();
+        assert_eq!(lines.len(), 5);
+        assert!(lines.iter().all(|line| line.len() == lines[0].len()));
+        assert_eq!(lines[0], "   X    | -0.250 |   O    ");
+        assert_eq!(lines[2], " -0.250 |[+0.500]| -0.250 ");
+    }
+
+    #[test]
+    fn ucb_exploration_tries_every_move_and_learns() {
+        let config = RLConfig {
+            exploration: ExplorationStrategy::Ucb1(0.5),
+            symmetry: false,
+            ..RLConfig::default()
+        };
+        let mut agent = RLAgent::new(config, Some(1));
+        // Without symmetry every opening is its own afterstate, and all nine
+        // are tried before any is repeated.
+        let openings: HashSet<usize> = (0..9)
+            .map(|_| {
+                let index = agent.choose_move(&Board::new(), PlayerMarker::X);
+                agent.observe_result(AgentOutcome::Draw, &Board::new());
+                index
+            })
+            .collect();
+        assert_eq!(openings.len(), 9);
+        let rate = win_rate_vs_random(&mut agent, 3000, 500, Some(2));
+        assert!(rate > 0.6, "{}", rate);
+    }
+
+    #[test]
+    fn speed_shaping_pays_more_for_quicker_wins() {
+        let config = RLConfig {
+            epsilon: DecaySchedule::Constant(0.0),
+            step_reward: -0.05,
+            win_speed_bonus: 0.1,
+            ..RLConfig::default()
+        };
+        let quick: Board = "XX.OO....".parse().unwrap();
+        let slow: Board = "XXOOOXX..".parse().unwrap();
+        assert_eq!(config.terminal_reward(AgentOutcome::Win, &quick), 1.5);
+        assert_eq!(config.terminal_reward(AgentOutcome::Win, &slow), 1.2);
+        assert_eq!(config.terminal_reward(AgentOutcome::Loss, &quick), -1.0);
+
+        // One move, straight to the end of the game: the afterstate moves a
+        // step of 0.1 toward the win reward with its bonus, less the step
+        // penalty.
+        let mut agent = RLAgent::new(config, Some(1));
+        let index = agent.choose_move(&quick, PlayerMarker::X);
+        let afterstate = agent.trajectory()[0];
+        let end = quick.with_move(index, PlayerMarker::X);
+        agent.observe_result(AgentOutcome::Win, &end);
+        let empty = end.available_moves().count() as f32;
+        let expected = 0.1 * ((1.0 + 0.1 * empty) - 0.05);
+        assert!((agent.q_table()[&afterstate] - expected).abs() < 1e-6);
+    }
+
+    #[test]
+    fn exploratory_moves_are_backed_up_only_when_asked() {
+        for learn_from_exploration in [false, true] {
+            let config = RLConfig {
+                epsilon: DecaySchedule::Constant(1.0),
+                learn_from_exploration,
+                ..RLConfig::default()
+            };
+            let mut agent = RLAgent::new(config, Some(1));
+            let mut random = RandomAgent::new(Some(2));
+            let (_, transcript) = play_game_with_transcript(&mut agent, &mut random).unwrap();
+            let moves = transcript.moves.len().div_ceil(2);
+            // Every move explores, so without learning from exploration only
+            // the last afterstate, backed up at the end, gets a value.
+            let expected = if learn_from_exploration { moves } else { 1 };
+            assert_eq!(agent.q_table().len(), expected);
+        }
+    }
+
+    #[test]
+    fn unseen_positions_start_at_the_initial_value() {
+        let config = RLConfig {
+            epsilon: DecaySchedule::Constant(0.0),
+            initial_value: 0.5,
+            ..RLConfig::default()
+        };
+        let mut agent = RLAgent::new(config.clone(), Some(1));
+        let board = Board::new();
+        let values = agent.move_values(&board, PlayerMarker::X).unwrap();
+        assert!(values.iter().all(|&(_, value)| value == 0.5));
+
+        // The first backup moves the value a step of 0.1 from 0.5 toward the
+        // loss reward of -1.
+        agent.choose_move(&board, PlayerMarker::X);
+        let afterstate = agent.trajectory()[0];
+        agent.observe_result(AgentOutcome::Loss, &board);
+        assert!((agent.q_table()[&afterstate] - 0.35).abs() < 1e-6);
+
+        let q_learning = QLearningAgent::new(config, Some(1));
+        let values = q_learning.move_values(&board, PlayerMarker::X).unwrap();
+        assert!(values.iter().all(|&(_, value)| value == 0.5));
+    }
+
+    #[test]
+    fn a_saved_agent_loads_back_with_the_same_tables() {
+        let mut agent = RLAgent::new(RLConfig::default(), Some(1))
+            .with_opening_book(OpeningBook::build_from_minimax(1));
+        let mut random = RandomAgent::new(Some(2));
+        for _ in 0..200 {
+            play_game(&mut agent, &mut random).unwrap();
+        }
+        assert!(!agent.visit_counts.is_empty());
+        for name in ["saved_agent.json", "saved_agent.bin"] {
+            let path = std::env::temp_dir().join(name);
+            agent.save(&path).unwrap();
+            let loaded = RLAgent::load(&path).unwrap();
+            fs::remove_file(&path).unwrap();
+            assert_eq!(loaded.q_table, agent.q_table, "{}", name);
+            assert_eq!(loaded.visit_counts, agent.visit_counts, "{}", name);
+            assert_eq!(loaded.opening_book.entries, agent.opening_book.entries);
+            assert_eq!(
+                loaded.config.epsilon.value(0),
+                agent.config.epsilon.value(0)
+            );
+        }
+    }
+
+    #[test]
+    fn td_lambda_with_lambda_zero_is_a_one_step_backup() {
+        let boards: Vec<Board> = [0, 4, 8]
+            .iter()
+            .scan(Board::new(), |board, &cell| {
+                *board = board.with_move(cell, board.player_to_move());
+                Some(*board)
+            })
+            .collect();
+        let targets = [0.5, -0.25, 1.0];
+        let mut expected: HashMap<Board, f32> = HashMap::new();
+        for (board, &target) in boards.iter().zip(&targets) {
+            update_q(&mut expected, 0.0, board, target, 0.1);
+        }
+        // Zero takes the one-step path; a lambda too small to leave a trace
+        // behind runs the trace update and must come to the same values.
+        for lambda in [0.0, 1e-9] {
+            let config = RLConfig {
+                lambda,
+                initial_value: 0.0,
+                learning_rate: 0.1,
+                ..RLConfig::default()
+            };
+            let mut table = HashMap::new();
+            let mut traces = HashMap::new();
+            for (board, &target) in boards.iter().zip(&targets) {
+                td_lambda_update(&mut table, &mut traces, board, target, &config);
+            }
+            assert_eq!(table.len(), expected.len());
+            for (board, value) in &expected {
+                assert!((table[board] - value).abs() < 1e-6, "lambda {}", lambda);
+            }
+        }
+    }
+
+    #[test]
+    fn n_step_backups_with_n_of_one_are_one_step_td() {
+        let config = RLConfig {
+            n_step: 1,
+            gamma: 0.9,
+            initial_value: 0.0,
+            ..RLConfig::default()
+        };
+        let boards = [
+            Board::new().with_move(4, PlayerMarker::X),
+            "O...X...X".parse().unwrap(),
+            "O.XOX...X".parse().unwrap(),
+        ];
+        let (rewards, bootstraps, final_reward) = ([0.0, -0.1], [0.3, 0.6], 1.0);
+        let mut n_step = HashMap::new();
+        let mut buffer = VecDeque::new();
+        let mut one_step = HashMap::new();
+        buffer.push_back((boards[0], 0.0));
+        for t in 0..2 {
+            buffer.back_mut().unwrap().1 += rewards[t];
+            n_step_update(&mut n_step, &mut buffer, bootstraps[t], &config);
+            buffer.push_back((boards[t + 1], 0.0));
+            update_q(
+                &mut one_step,
+                0.0,
+                &boards[t],
+                rewards[t] + config.gamma * bootstraps[t],
+                config.learning_rate,
+            );
+        }
+        n_step_flush(&mut n_step, &mut buffer, final_reward, &config);
+        update_q(
+            &mut one_step,
+            0.0,
+            &boards[2],
+            final_reward,
+            config.learning_rate,
+        );
+        assert!(buffer.is_empty());
+        assert_eq!(n_step, one_step);
+    }
+
+    #[test]
+    fn the_same_seeds_train_the_same_q_table() {
+        let train = |seed: u64| {
+            let config = RLConfig {
+                epsilon: DecaySchedule::Constant(0.2),
+                ..RLConfig::default()
+            };
+            let mut agent = RLAgent::new(config, Some(seed));
+            let mut random = RandomAgent::new(Some(seed + 1));
+            for _ in 0..300 {
+                play_game(&mut agent, &mut random).unwrap();
+            }
+            table_bytes(agent.q_table.iter().map(|(&k, &v)| (k, v)))
+        };
+        assert!(train(1) == train(1));
+        assert!(train(1) != train(2));
+    }
+
+    #[test]
+    fn count_based_exploration_visits_every_opening() {
+        let config = RLConfig {
+            exploration: ExplorationStrategy::CountBased { beta: 0.5 },
+            symmetry: false,
+            ..RLConfig::default()
+        };
+        let mut agent = RLAgent::new(config, Some(1));
+        // A draw lowers the value of the opening played as well as its bonus,
+        // so every opening comes round in turn.
+        let openings: HashSet<usize> = (0..9)
+            .map(|_| {
+                let index = agent.choose_move(&Board::new(), PlayerMarker::X);
+                agent.observe_result(AgentOutcome::Draw, &Board::new());
+                index
+            })
+            .collect();
+        assert_eq!(openings.len(), 9);
+        assert!(agent.visit_counts.values().all(|&count| count == 1));
+    }
+
+    #[test]
+    fn n_step_backups_longer_than_the_game_are_monte_carlo() {
+        // X moves at most five times, so with n of 9 nothing is bootstrapped
+        // and every afterstate is backed up toward the final reward.
+        let config = RLConfig {
+            n_step: 9,
+            gamma: 0.9,
+            initial_value: 0.0,
+            ..RLConfig::default()
+        };
+        let boards: [Board; 3] = [
+            "....X....".parse().unwrap(),
+            "O...X...X".parse().unwrap(),
+            "O.XOX...X".parse().unwrap(),
+        ];
+        let mut n_step = HashMap::new();
+        let mut buffer = VecDeque::new();
+        for board in boards {
+            buffer.push_back((board, 0.0));
+            n_step_update(&mut n_step, &mut buffer, 0.7, &config);
+        }
+        assert_eq!(buffer.len(), boards.len());
+        n_step_flush(&mut n_step, &mut buffer, 1.0, &config);
+        let mut monte_carlo = HashMap::new();
+        monte_carlo_update(&mut monte_carlo, &mut boards.to_vec(), 1.0, &config);
+        assert!(buffer.is_empty());
+        assert_eq!(n_step.len(), monte_carlo.len());
+        for (board, value) in &monte_carlo {
+            assert!((n_step[board] - value).abs() < 1e-6, "{:?}", board);
+        }
+    }
+
+    #[test]
+    fn shaping_reaches_a_high_win_rate_in_fewer_games() {
+        // Games of training until the greedy policy first wins 85% of its
+        // games as O against random play, at most 5000, summed over seeds.
+        let games_to_learn = |weight: f32| {
+            (0..8)
+                .map(|seed| {
+                    let config = RLConfig {
+                        epsilon: DecaySchedule::Constant(0.1),
+                        ..RLConfig::default()
+                    };
+                    let mut agent = RLAgent::new(config, Some(seed)).with_shaping(
+                        move |board: &Board, player: PlayerMarker| {
+                            weight
+                                * (board.count_threats(player) as f32
+                                    - board.count_threats(player.opposite()) as f32)
+                        },
+                    );
+                    let mut games = 0;
+                    while games < 5000 {
+                        games += 250;
+                        let eval_seed = Some(seed * 100 + games as u64);
+                        if win_speed_vs_random(&mut agent, 250, 300, eval_seed).win_rate >= 0.85 {
+                            break;
+                        }
+                    }
+                    games
+                })
+                .sum::<u32>()
+        };
+        let (unshaped, shaped) = (games_to_learn(0.0), games_to_learn(0.2));
+        assert!(shaped < unshaped, "{} {}", shaped, unshaped);
+    }
+
+    #[test]
+    fn replay_with_no_capacity_is_the_incremental_update() {
+        let train = |config: RLConfig| {
+            let mut agent = RLAgent::new(config, Some(1));
+            let mut random = RandomAgent::new(Some(2));
+            for _ in 0..300 {
+                play_game(&mut agent, &mut random).unwrap();
+            }
+            assert!(agent.replay.is_none());
+            table_bytes(agent.q_table.iter().map(|(&k, &v)| (k, v)))
+        };
+        let config = RLConfig {
+            epsilon: DecaySchedule::Constant(0.2),
+            ..RLConfig::default()
+        };
+        let disabled = RLConfig {
+            replay_capacity: 0,
+            replay_batch_size: 64,
+            ..config.clone()
+        };
+        assert!(train(disabled) == train(config));
+    }
+
+    #[test]
+    fn replay_learns_more_from_the_first_few_games() {
+        // The mean greedy win rate as O over 20 seeds after 25 games.
+        let win_rate = |replay_capacity: usize| {
+            (0..20)
+                .map(|seed| {
+                    let config = RLConfig {
+                        epsilon: DecaySchedule::Constant(0.1),
+                        replay_capacity,
+                        ..RLConfig::default()
+                    };
+                    let mut agent = RLAgent::new(config, Some(seed));
+                    win_speed_vs_random(&mut agent, 25, 500, Some(seed + 50)).win_rate
+                })
+                .sum::<f32>()
+                / 20.0
+        };
+        let (incremental, replayed) = (win_rate(0), win_rate(1000));
+        assert!(
+            replayed > incremental + 0.03,
+            "{} {}",
+            replayed,
+            incremental
+        );
+    }
+
+    #[test]
+    fn with_gamma_zero_only_the_final_reward_is_backed_up() {
+        let config = RLConfig {
+            epsilon: DecaySchedule::Constant(0.3),
+            gamma: 0.0,
+            initial_value: 0.0,
+            ..RLConfig::default()
+        };
+        let mut agent = RLAgent::new(config, Some(1));
+        let mut random = RandomAgent::new(Some(2));
+        for _ in 0..2000 {
+            play_game(&mut agent, &mut random).unwrap();
+        }
+        // Whether the game can end before the agent moves again.
+        let ends = |board: &Board| {
+            let over = |board: &Board| {
+                board.is_full()
+                    || board.check_winner(PlayerMarker::X)
+                    || board.check_winner(PlayerMarker::O)
+            };
+            over(board)
+                || board
+                    .available_moves()
+                    .any(|i| over(&board.with_move(i, board.player_to_move())))
+        };
+        let (mut final_moves, mut earlier) = (0, 0);
+        for (board, &value) in &agent.q_table {
+            if ends(board) {
+                final_moves += (value != 0.0) as u32;
+            } else {
+                assert_eq!(value, 0.0, "{}", board.cells());
+                earlier += 1;
+            }
+        }
+        assert!(
+            final_moves > 0 && earlier > 0,
+            "{} {}",
+            final_moves,
+            earlier
+        );
+    }
+
+    #[test]
+    fn a_warm_up_of_random_games_fills_replay_with_what_greed_misses() {
+        // Greedy after the warm-up, so without one the buffer only ever holds
+        // the games the agent's first values steer it into.
+        let win_rate = |warmup_episodes: u32, seed: u64| {
+            let config = RLConfig {
+                epsilon: DecaySchedule::Constant(0.0),
+                replay_capacity: 5000,
+                replay_batch_size: 8,
+                warmup_episodes,
+                ..RLConfig::default()
+            };
+            let episodes = warmup_episodes + 10000;
+            let mut trainer = Trainer::new(
+                Box::new(RLAgent::new(config, Some(seed))),
+                Box::new(RandomAgent::new(Some(seed + 100))),
+            )
+            .with_config(TrainingConfig {
+                eval_interval: 0,
+                metrics_interval: episodes,
+                eval_games: 2000,
+                seed: Some(seed),
+                ..TrainingConfig::default()
+            });
+            let stats = trainer.run(episodes);
+            assert_eq!(stats.metrics.len(), 1);
+            stats.metrics[0].win_rate
+        };
+        let (mut warm, mut cold) = (0.0, 0.0);
+        for seed in 0..4 {
+            warm += win_rate(1000, seed) / 4.0;
+            cold += win_rate(0, seed) / 4.0;
+        }
+        assert!(warm > cold + 0.005, "{} vs {}", warm, cold);
+    }
+
+    #[test]
+    fn the_warm_up_backs_nothing_up() {
+        let config = RLConfig {
+            warmup_episodes: 50,
+            ..RLConfig::default()
+        };
+        let mut agent = RLAgent::new(config, Some(1));
+        let mut random = RandomAgent::new(Some(2));
+        for _ in 0..50 {
+            play_game(&mut agent, &mut random).unwrap();
+        }
+        assert!(agent.q_table().is_empty());
+        assert_eq!(agent.step, 0);
+        assert!(agent.visit_counts.values().sum::<u32>() >= 150);
+        play_game(&mut agent, &mut random).unwrap();
+        assert!(!agent.q_table().is_empty());
+    }
+
+    #[test]
+    fn clipped_rewards_keep_values_within_their_discounted_sum() {
+        // Rewards of 5 for a win or loss and up to 5 of shaping, with a bound
+        // of 1 / (1 - 0.5) = 2 once they are clipped to 1.
+        let largest_value = |reward_clip: Option<f32>| {
+            let config = RLConfig {
+                gamma: 0.5,
+                win_reward: 5.0,
+                loss_reward: -5.0,
+                epsilon: DecaySchedule::Constant(0.2),
+                reward_clip,
+                ..RLConfig::default()
+            };
+            let mut agent = RLAgent::new(config, Some(1))
+                .with_shaping(|board, player| (2.5 * board.count_threats(player) as f32).min(5.0));
+            win_rate_vs_random(&mut agent, 2000, 0, Some(2));
+            agent
+                .q_table()
+                .values()
+                .fold(0.0f32, |max, v| max.max(v.abs()))
+        };
+        assert!(largest_value(None) > 2.0);
+        let clipped = largest_value(Some(1.0));
+        assert!(clipped > 0.5 && clipped <= 2.0, "{}", clipped);
+    }
+
+    #[test]
+    fn annealed_step_sizes_settle_the_values_and_play_better() {
+        // Each schedule's values' mean change over the last 1000 of 10000
+        // games against random play, and its greedy win rate after them,
+        // averaged over three seeds.
+        let train = |alpha_schedule: Option<DecaySchedule>| {
+            let (mut drift, mut win_rate) = (0.0, 0.0);
+            for seed in 0..3 {
+                let config = RLConfig {
+                    epsilon: DecaySchedule::Constant(0.1),
+                    alpha_schedule: alpha_schedule.clone(),
+                    ..RLConfig::default()
+                };
+                let mut agent = RLAgent::new(config, Some(seed));
+                win_rate_vs_random(&mut agent, 9000, 0, Some(seed + 50));
+                let before = agent.q_table().clone();
+                win_rate_vs_random(&mut agent, 1000, 0, Some(seed + 60));
+                let change: f32 = before
+                    .iter()
+                    .map(|(board, value)| (agent.q_table()[board] - value).abs())
+                    .sum();
+                drift += change / before.len() as f32 / 3.0;
+                win_rate +=
+                    win_speed_vs_random(&mut agent, 0, 2000, Some(seed + 70)).win_rate / 3.0;
+            }
+            (drift, win_rate)
+        };
+        let fixed = train(None);
+        let cosine = train(Some(DecaySchedule::Cosine {
+            start: 0.5,
+            end: 0.01,
+            steps: 10000,
+        }));
+        let restarts = train(Some(DecaySchedule::WarmRestart {
+            start: 0.5,
+            end: 0.01,
+            period: 2500,
+        }));
+        assert!(cosine.0 < fixed.0 / 4.0, "{:?} {:?}", cosine, fixed);
+        assert!(restarts.0 < fixed.0, "{:?} {:?}", restarts, fixed);
+        assert!(cosine.1 > fixed.1, "{:?} {:?}", cosine, fixed);
+        assert!(restarts.1 > fixed.1, "{:?} {:?}", restarts, fixed);
+    }
+
+    #[test]
+    fn polyak_averaged_play_is_steadier_between_evaluations() {
+        // A large step size keeps the live values, and so the greedy policy,
+        // moving after 5000 games; the average moves far less. Evaluations
+        // every 100 games meet the same random play, so only the policy
+        // changes between them.
+        let spread = |polyak_beta: f32, seed: u64| {
+            let config = RLConfig {
+                learning_rate: 0.5,
+                epsilon: DecaySchedule::Constant(0.1),
+                polyak_beta,
+                ..RLConfig::default()
+            };
+            let mut agent = RLAgent::new(config, Some(seed));
+            let mut random = RandomAgent::new(Some(seed + 10));
+            let mut rates = Vec::new();
+            for round in 0..80 {
+                for _ in 0..100 {
+                    play_game(&mut random, &mut agent).unwrap();
+                }
+                if round >= 50 {
+                    rates.push(win_speed_vs_random(&mut agent, 0, 500, Some(77)).win_rate as f64);
+                }
+            }
+            let (_, std_error) = mean_and_std_error(&rates);
+            std_error * (rates.len() as f64).sqrt()
+        };
+        let live = spread(0.0, 0) + spread(0.0, 1);
+        let averaged = spread(0.003, 0) + spread(0.003, 1);
+        assert!(averaged < 0.75 * live, "{} vs {}", averaged, live);
+    }
+}
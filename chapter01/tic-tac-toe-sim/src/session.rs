@@ -0,0 +1,185 @@
+use crate::agents::is_over;
+use crate::{Agent, AgentOutcome, Board, PlayerMarker, RLAgent};
+
+// A game against a trained rl agent driven from outside, as by a web page: the
+// caller makes the human's moves, asks for the agent's and can shade each open
+// cell by its value as a hint. Cells are numbered 0 to 8 in row-major order,
+// and everything passed in or out is a number, string or list of numbers, so
+// the methods are exported through wasm-bindgen as they are, by the
+// tic-tac-toe-wasm crate, which builds this one without default features.
+pub struct TicTacToeSession {
+    agent: RLAgent,
+    human: PlayerMarker,
+    board: Board,
+}
+
+impl TicTacToeSession {
+    // A session against `agent` with the human playing `human`. The agent is
+    // frozen, so it plays its greedy policy and learns nothing from the
+    // human's games.
+    pub fn new(mut agent: RLAgent, human: PlayerMarker) -> Self {
+        agent.freeze();
+        TicTacToeSession {
+            agent,
+            human,
+            board: Board::new(),
+        }
+    }
+
+    // A session against the X and O policies in `x_policy` and `o_policy`, the
+    // bytes of the x_policy.bin and o_policy.bin files training saves. Both
+    // sides are needed so the agent can play either and hint at the human's
+    // moves too.
+    pub fn from_policy_bytes(
+        x_policy: &[u8],
+        o_policy: &[u8],
+        human_plays_x: bool,
+    ) -> Result<Self, String> {
+        let load = |bytes| {
+            RLAgent::from_bytes(bytes).map_err(|error| format!("invalid policy: {}", error))
+        };
+        let agent = RLAgent::combined(&load(x_policy)?, &load(o_policy)?);
+        let human = if human_plays_x {
+            PlayerMarker::X
+        } else {
+            PlayerMarker::O
+        };
+        Ok(TicTacToeSession::new(agent, human))
+    }
+
+    // Clears the board for another game.
+    pub fn new_game(&mut self) {
+        // Ends the agent's record of the last game; frozen, it learns nothing
+        // from the outcome.
+        self.agent.observe_result(AgentOutcome::Draw, &self.board);
+        self.board = Board::new();
+    }
+
+    // Plays the human's move at `index`.
+    pub fn human_move(&mut self, index: usize) -> Result<(), String> {
+        self.check_turn(self.human)?;
+        if index >= 9 || !self.board.available(index) {
+            return Err(format!("cell {} is not free", index));
+        }
+        self.board.set(index, self.human);
+        Ok(())
+    }
+
+    // Plays the agent's move and returns its cell.
+    pub fn agent_move(&mut self) -> Result<usize, String> {
+        let player = self.human.opposite();
+        self.check_turn(player)?;
+        let index = self
+            .agent
+            .try_choose_move(&self.board, player)
+            .map_err(|error| error.to_string())?;
+        self.board.set(index, player);
+        Ok(index)
+    }
+
+    // The agent's value of moving to each cell for whoever is to move, NaN for
+    // taken cells and once the game is over.
+    pub fn cell_values(&self) -> Vec<f32> {
+        let mut values = vec![f32::NAN; 9];
+        if !is_over(&self.board) {
+            let player = self.board.player_to_move();
+            for (index, value) in self
+                .agent
+                .move_values(&self.board, player)
+                .unwrap_or_default()
+            {
+                values[index] = value;
+            }
+        }
+        values
+    }
+
+    // The board as nine characters in row-major order, X, O or . for empty.
+    pub fn cells(&self) -> String {
+        self.board.cells()
+    }
+
+    // Where the game stands: "human-to-move", "agent-to-move", "human-won",
+    // "agent-won" or "draw".
+    pub fn status(&self) -> String {
+        let agent = self.human.opposite();
+        let status = if self.board.check_winner(self.human) {
+            "human-won"
+        } else if self.board.check_winner(agent) {
+            "agent-won"
+        } else if self.board.is_full() {
+            "draw"
+        } else if self.board.player_to_move() == self.human {
+            "human-to-move"
+        } else {
+            "agent-to-move"
+        };
+        status.to_string()
+    }
+
+    fn check_turn(&self, player: PlayerMarker) -> Result<(), String> {
+        if is_over(&self.board) {
+            Err("the game is over".to_string())
+        } else if self.board.player_to_move() != player {
+            Err(format!(
+                "it is {}'s turn",
+                PlayerMarker::player_char(player.opposite())
+            ))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::RLConfig;
+
+    #[test]
+    fn the_human_and_agent_take_turns_until_the_game_ends() {
+        let agent = RLAgent::new(RLConfig::default(), Some(1));
+        let mut session = TicTacToeSession::new(agent, PlayerMarker::X);
+        assert_eq!(session.status(), "human-to-move");
+        assert!(session.agent_move().is_err());
+        session.human_move(4).unwrap();
+        assert_eq!(session.human_move(0), Err("it is O's turn".to_string()));
+
+        let reply = session.agent_move().unwrap();
+        assert_ne!(reply, 4);
+        assert_eq!(
+            session.human_move(reply),
+            Err(format!("cell {} is not free", reply))
+        );
+        let values = session.cell_values();
+        assert!(values[4].is_nan() && values[reply].is_nan());
+        assert_eq!(values.iter().filter(|value| !value.is_nan()).count(), 7);
+
+        while !session.status().contains("won") && session.status() != "draw" {
+            if session.status() == "human-to-move" {
+                let free = session.cells().find('.').unwrap();
+                session.human_move(free).unwrap();
+            } else {
+                session.agent_move().unwrap();
+            }
+        }
+        assert!(session.cell_values().iter().all(|value| value.is_nan()));
+        assert!(session.human_move(0).is_err());
+        session.new_game();
+        assert_eq!(session.cells(), ".........");
+    }
+
+    #[test]
+    fn sessions_load_the_saved_policy_files() {
+        let path = std::env::temp_dir().join("session_policy.bin");
+        RLAgent::new(RLConfig::default(), Some(1))
+            .save(&path)
+            .unwrap();
+        let bytes = std::fs::read(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        let mut session = TicTacToeSession::from_policy_bytes(&bytes, &bytes, false).unwrap();
+        assert_eq!(session.status(), "agent-to-move");
+        session.agent_move().unwrap();
+        assert!(TicTacToeSession::from_policy_bytes(b"not a policy", &bytes, true).is_err());
+    }
+}
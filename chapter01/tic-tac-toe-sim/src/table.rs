@@ -0,0 +1,309 @@
+use std::collections::{BTreeMap, HashSet};
+use std::fmt;
+use std::io::{self, Write};
+
+use crate::updates::{afterstate_key, greedy_move};
+use crate::{Board, PlayerMarker, RLAgent};
+
+// How many positions legal play can reach, counting the empty board and
+// every finished game but no position after a game is won.
+pub const LEGAL_POSITIONS: usize = 5478;
+
+// Every position legal play can reach from the empty board.
+pub fn legal_positions() -> HashSet<Board> {
+    let mut seen = HashSet::from([Board::new()]);
+    let mut frontier = vec![Board::new()];
+    while let Some(board) = frontier.pop() {
+        if board.check_winner(PlayerMarker::X) || board.check_winner(PlayerMarker::O) {
+            continue;
+        }
+        let player = board.player_to_move();
+        for i in board.available_moves() {
+            let next = board.with_move(i, player);
+            if seen.insert(next) {
+                frontier.push(next);
+            }
+        }
+    }
+    seen
+}
+
+// A summary of an `RLAgent`'s value table.
+#[derive(Clone, Debug)]
+pub struct TableStats {
+    pub size: usize,
+    // Entries with a visit count, which only agents trained in this process
+    // or saved with their counts have.
+    pub visited: usize,
+    // Entries that are no legal position, such as boards left over from a
+    // bug or hand-edited files; legal play never reaches them.
+    pub unreachable: usize,
+    pub min: f32,
+    pub mean: f32,
+    pub max: f32,
+    // Equal-width bins from `min` to `max`: (low, high, entries).
+    pub histogram: Vec<(f32, f32, usize)>,
+    // Legal positions with an entry, out of LEGAL_POSITIONS.
+    pub covered: usize,
+    // Legal positions X and O can move into that have an entry, and how many
+    // there are: (covered, total). The afterstates of each side are all a
+    // table for that side can hold.
+    pub x_afterstates: (usize, usize),
+    pub o_afterstates: (usize, usize),
+}
+
+impl fmt::Display for TableStats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let percent = |(covered, total): (usize, usize)| 100.0 * covered as f32 / total as f32;
+        writeln!(
+            f,
+            "Entries: {} ({} with visit counts, {} unreachable)",
+            self.size, self.visited, self.unreachable
+        )?;
+        writeln!(
+            f,
+            "Coverage: {} of the {} legal positions ({:.1}%); X afterstates {:.1}% of {}, O afterstates {:.1}% of {}",
+            self.covered,
+            LEGAL_POSITIONS,
+            percent((self.covered, LEGAL_POSITIONS)),
+            percent(self.x_afterstates),
+            self.x_afterstates.1,
+            percent(self.o_afterstates),
+            self.o_afterstates.1
+        )?;
+        write!(
+            f,
+            "Values: min {:+.3}, mean {:+.3}, max {:+.3}",
+            self.min, self.mean, self.max
+        )?;
+        let widest = self.histogram.iter().map(|&(_, _, n)| n).max().unwrap_or(0);
+        for &(low, high, count) in &self.histogram {
+            let bar = "#".repeat((40 * count).div_ceil(widest.max(1)));
+            write!(f, "\n  [{:+.3}, {:+.3}) {:6} {}", low, high, count, bar)?;
+        }
+        Ok(())
+    }
+}
+
+impl RLAgent {
+    // Size, values and coverage of the table, with the values in `bins`
+    // histogram bins.
+    pub fn table_stats(&self, bins: usize) -> TableStats {
+        let legal = legal_positions();
+        let key = |board: &Board| {
+            if self.config.symmetry {
+                board.canonical()
+            } else {
+                *board
+            }
+        };
+        let legal_keys: HashSet<Board> = legal.iter().map(key).collect();
+        let values: Vec<f32> = self.q_table.values().copied().collect();
+        let min = values.iter().copied().fold(f32::INFINITY, f32::min);
+        let max = values.iter().copied().fold(f32::NEG_INFINITY, f32::max);
+        let width = (max - min) / bins as f32;
+        let mut histogram: Vec<(f32, f32, usize)> = (0..bins)
+            .map(|bin| (min + bin as f32 * width, min + (bin + 1) as f32 * width, 0))
+            .collect();
+        for &value in &values {
+            let bin = if width > 0.0 {
+                (((value - min) / width) as usize).min(bins - 1)
+            } else {
+                0
+            };
+            if let Some((_, _, count)) = histogram.get_mut(bin) {
+                *count += 1;
+            }
+        }
+        let marks = |board: &Board, player: PlayerMarker| {
+            board
+                .iter_cells()
+                .filter(|&cell| cell == Some(player))
+                .count()
+        };
+        let coverage = |afterstate_of: PlayerMarker| {
+            let positions: Vec<&Board> = legal
+                .iter()
+                .filter(|board| {
+                    let (x, o) = (marks(board, PlayerMarker::X), marks(board, PlayerMarker::O));
+                    match afterstate_of {
+                        PlayerMarker::X => x == o + 1,
+                        PlayerMarker::O => x == o && x > 0,
+                    }
+                })
+                .collect();
+            let covered = positions
+                .iter()
+                .filter(|board| self.q_table.contains_key(&key(board)))
+                .count();
+            (covered, positions.len())
+        };
+        TableStats {
+            size: values.len(),
+            visited: self
+                .q_table
+                .keys()
+                .filter(|state| self.visit_counts.get(state).is_some_and(|&n| n > 0))
+                .count(),
+            unreachable: self
+                .q_table
+                .keys()
+                .filter(|state| !legal_keys.contains(state))
+                .count(),
+            min: if values.is_empty() { 0.0 } else { min },
+            mean: values.iter().sum::<f32>() / values.len().max(1) as f32,
+            max: if values.is_empty() { 0.0 } else { max },
+            histogram: if values.is_empty() {
+                Vec::new()
+            } else {
+                histogram
+            },
+            covered: legal
+                .iter()
+                .filter(|board| self.q_table.contains_key(&key(board)))
+                .count(),
+            x_afterstates: coverage(PlayerMarker::X),
+            o_afterstates: coverage(PlayerMarker::O),
+        }
+    }
+
+    // The greedy move in every canonical position legal play reaches with
+    // `side` to move and the game not over, as text to read through or diff
+    // between runs: a header for each ply, then a line for each position
+    // with its cells, the move (numbered from 1, as in play) and the value
+    // of the afterstate, `-` where the table has none. Positions are sorted
+    // within each ply, so the same table always writes the same text.
+    pub fn write_policy_text(&self, side: PlayerMarker, out: &mut dyn Write) -> io::Result<()> {
+        let finished = |board: &Board| {
+            board.check_winner(PlayerMarker::X)
+                || board.check_winner(PlayerMarker::O)
+                || board.is_full()
+        };
+        let mut plies: BTreeMap<usize, BTreeMap<String, Board>> = BTreeMap::new();
+        for board in legal_positions() {
+            if board.player_to_move() == side && !finished(&board) {
+                let canonical = board.canonical();
+                let ply = canonical.iter_cells().flatten().count();
+                plies
+                    .entry(ply)
+                    .or_default()
+                    .insert(canonical.cells(), canonical);
+            }
+        }
+        let symmetric = self.config.symmetry;
+        let initial = self.config.initial_value;
+        writeln!(
+            out,
+            "# {}'s greedy moves: cells, move, afterstate value",
+            PlayerMarker::player_char(side)
+        )?;
+        for (ply, positions) in &plies {
+            writeln!(out, "ply {}, positions: {}", ply, positions.len())?;
+            for (cells, board) in positions {
+                let cell = greedy_move(board, side, symmetric, |key| {
+                    self.q_table.get(&key).copied().unwrap_or(initial)
+                });
+                let value = self
+                    .q_table
+                    .get(&afterstate_key(board, cell, side, symmetric))
+                    .map_or("-".to_string(), |value| format!("{:+.3}", value));
+                writeln!(out, "  {} -> {}  {}", cells, cell + 1, value)?;
+            }
+        }
+        Ok(())
+    }
+
+    // Whether the agent knows how often it visited its table's entries:
+    // false for agents loaded from files saved without visit counts.
+    pub fn has_visit_counts(&self) -> bool {
+        !self.visit_counts.is_empty()
+    }
+
+    // Drops every entry visited fewer than `min_visits` times, with its visit
+    // count, and returns how many were dropped. The values of rarely visited
+    // afterstates rest on few backups and are often stale. Dropped entries
+    // read as the default value again.
+    pub fn prune(&mut self, min_visits: u32) -> usize {
+        let before = self.q_table.len();
+        let counts = &self.visit_counts;
+        self.q_table
+            .retain(|state, _| counts.get(state).is_some_and(|&n| n >= min_visits));
+        self.visit_counts.retain(|_, &mut n| n >= min_visits);
+        before - self.q_table.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{play_game, RLConfig, RandomAgent};
+
+    #[test]
+    fn there_are_5478_legal_positions() {
+        let legal = legal_positions();
+        assert_eq!(legal.len(), LEGAL_POSITIONS);
+        let canonical: HashSet<Board> = legal.iter().map(Board::canonical).collect();
+        // The well-known count up to rotation and reflection.
+        assert_eq!(canonical.len(), 765);
+    }
+
+    #[test]
+    fn stats_and_pruning_agree_with_training() {
+        let mut agent = RLAgent::new(RLConfig::default(), Some(1));
+        let mut random = RandomAgent::new(Some(2));
+        for _ in 0..500 {
+            play_game(&mut agent, &mut random).unwrap();
+        }
+        let stats = agent.table_stats(10);
+        assert_eq!(stats.size, agent.q_table().len());
+        assert_eq!(stats.unreachable, 0);
+        assert_eq!(stats.o_afterstates.0, 0);
+        assert!(stats.x_afterstates.0 > 0 && stats.x_afterstates.0 == stats.covered);
+        assert_eq!(
+            stats.histogram.iter().map(|&(_, _, n)| n).sum::<usize>(),
+            stats.size
+        );
+        let dropped = agent.prune(5);
+        assert!(dropped > 0);
+        assert_eq!(agent.table_stats(10).size, stats.size - dropped);
+        assert!(agent
+            .q_table()
+            .keys()
+            .all(|state| agent.visit_counts[state] >= 5));
+    }
+
+    #[test]
+    fn the_policy_text_lists_every_canonical_position_by_ply() {
+        let mut agent = RLAgent::new(RLConfig::default(), Some(1));
+        let mut random = RandomAgent::new(Some(2));
+        for _ in 0..500 {
+            play_game(&mut random, &mut agent).unwrap();
+        }
+        let mut text = Vec::new();
+        agent.write_policy_text(PlayerMarker::O, &mut text).unwrap();
+        let text = String::from_utf8(text).unwrap();
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(lines[1], "ply 1, positions: 3");
+        assert!(lines[2..5].iter().all(|line| line.starts_with("  ")));
+        let headers: Vec<&str> = lines
+            .iter()
+            .filter(|l| l.starts_with("ply"))
+            .copied()
+            .collect();
+        assert_eq!(headers.len(), 4);
+        let positions = lines.len() - 1 - headers.len();
+        let expected: HashSet<Board> = legal_positions()
+            .iter()
+            .filter(|board| {
+                board.player_to_move() == PlayerMarker::O
+                    && !board.check_winner(PlayerMarker::X)
+                    && !board.is_full()
+            })
+            .map(Board::canonical)
+            .collect();
+        assert_eq!(positions, expected.len());
+        // The greedy reply to a centre opening is one the agent has a value for.
+        let centre = lines.iter().find(|l| l.starts_with("  ....X....")).unwrap();
+        assert!(!centre.ends_with('-'));
+    }
+}
@@ -0,0 +1,319 @@
+use std::fmt;
+
+use crate::game::counted;
+use crate::{play_game, Agent, GameResult};
+
+// Outcomes for every ordered pair of tournament entrants: `records[i][j]` is
+// (wins, losses, draws) of entrant `i` against entrant `j`.
+pub struct TournamentResult {
+    pub names: Vec<String>,
+    pub records: Vec<Vec<(u32, u32, u32)>>,
+}
+
+impl TournamentResult {
+    // Elo-style ratings that fit every pairing's score at once, a draw counting
+    // as half a win, scaled like chess ratings and averaging 1500. Found by
+    // repeatedly nudging each rating by the gap between its actual and expected
+    // score, so the order games were played in doesn't matter. An entrant that
+    // won or lost every game has no finite fit and drifts to the edge of the
+    // iteration instead.
+    pub fn ratings(&self) -> Vec<f64> {
+        let n = self.names.len();
+        let mut ratings = vec![1500.0; n];
+        let expected = |a: f64, b: f64| 1.0 / (1.0 + 10f64.powf((b - a) / 400.0));
+        for _ in 0..1000 {
+            let mut next = ratings.clone();
+            for i in 0..n {
+                let (mut actual, mut predicted, mut games) = (0.0, 0.0, 0.0);
+                for j in (0..n).filter(|&j| j != i) {
+                    let (wins, losses, draws) = self.records[i][j];
+                    let played = (wins + losses + draws) as f64;
+                    actual += wins as f64 + 0.5 * draws as f64;
+                    predicted += played * expected(ratings[i], ratings[j]);
+                    games += played;
+                }
+                if games > 0.0 {
+                    next[i] += 400.0 * (actual - predicted) / games;
+                }
+            }
+            let mean = next.iter().sum::<f64>() / n as f64;
+            ratings = next.iter().map(|rating| rating - mean + 1500.0).collect();
+        }
+        ratings
+    }
+
+    // The two-sided sign test's p-value for entrants `i` and `j` being
+    // equally strong, from their decisive games; draws say nothing about
+    // which is stronger.
+    pub fn p_value(&self, i: usize, j: usize) -> f64 {
+        let (wins, losses, _) = self.records[i][j];
+        sign_test(wins, losses)
+    }
+
+    // For each ordered pair, whether the row entrant beat the column entrant
+    // significantly at level `alpha`, corrected over every pairing.
+    pub fn significant_wins(&self, alpha: f64) -> Vec<Vec<bool>> {
+        let n = self.names.len();
+        let pairs: Vec<(usize, usize)> = (0..n).flat_map(|j| (0..j).map(move |i| (i, j))).collect();
+        let p_values: Vec<f64> = pairs.iter().map(|&(i, j)| self.p_value(i, j)).collect();
+        let mut significant = vec![vec![false; n]; n];
+        for (&(i, j), passed) in pairs.iter().zip(bonferroni_correct(&p_values, alpha)) {
+            let (wins, losses, _) = self.records[i][j];
+            significant[i][j] = passed && wins > losses;
+            significant[j][i] = passed && losses > wins;
+        }
+        significant
+    }
+}
+
+// Which of `p_values` stay significant at level `alpha` once the level is
+// divided among all of them, so that running many comparisons doesn't turn
+// up chance differences.
+pub fn bonferroni_correct(p_values: &[f64], alpha: f64) -> Vec<bool> {
+    let level = alpha / p_values.len().max(1) as f64;
+    p_values.iter().map(|&p| p < level).collect()
+}
+
+// The exact two-sided sign test: the chance of a split of `wins` and `losses`
+// at least this uneven between two equally strong players.
+fn sign_test(wins: u32, losses: u32) -> f64 {
+    let n = wins + losses;
+    let fewer = wins.min(losses);
+    // ln of C(n, k) / 2^n, built up one k at a time.
+    let mut ln_probability = -(n as f64) * std::f64::consts::LN_2;
+    let mut tail = 0.0;
+    for k in 0..=fewer {
+        if k > 0 {
+            ln_probability += ((n - k + 1) as f64 / k as f64).ln();
+        }
+        tail += ln_probability.exp();
+    }
+    (2.0 * tail).min(1.0)
+}
+
+// The significance level of the marks in a tournament's table.
+const TOURNAMENT_ALPHA: f64 = 0.05;
+
+impl fmt::Display for TournamentResult {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let width = self.names.iter().map(|name| name.len()).max().unwrap_or(0);
+        let cell_width = width.max(17);
+        let significant = self.significant_wins(TOURNAMENT_ALPHA);
+        write!(f, "{:width$}", "", width = width)?;
+        for name in &self.names {
+            write!(f, " | {:>cell_width$}", name, cell_width = cell_width)?;
+        }
+        writeln!(f)?;
+        for (i, (name, row)) in self.names.iter().zip(&self.records).enumerate() {
+            write!(f, "{:width$}", name, width = width)?;
+            for (j, &(wins, losses, draws)) in row.iter().enumerate() {
+                let games = (wins + losses + draws).max(1) as f32;
+                let cell = if i == j {
+                    "-".to_string()
+                } else {
+                    let mark = if significant[i][j] {
+                        " *"
+                    } else if significant[j][i] {
+                        ""
+                    } else {
+                        " ns"
+                    };
+                    format!(
+                        "{:.2}/{:.2}/{:.2}{:>3}",
+                        wins as f32 / games,
+                        draws as f32 / games,
+                        losses as f32 / games,
+                        mark
+                    )
+                };
+                write!(f, " | {:>cell_width$}", cell, cell_width = cell_width)?;
+            }
+            writeln!(f)?;
+        }
+        writeln!(f, "(row entrant's win/draw/loss rates against each column)")?;
+        let pairings = self.names.len() * self.names.len().saturating_sub(1) / 2;
+        writeln!(
+            f,
+            "(* a significant win, ns no significant difference: sign test on decisive games, {}% level Bonferroni-corrected over {} pairings)",
+            TOURNAMENT_ALPHA * 100.0,
+            pairings
+        )?;
+        writeln!(f)?;
+        let ratings = self.ratings();
+        let mut order: Vec<usize> = (0..self.names.len()).collect();
+        order.sort_by(|&a, &b| ratings[b].total_cmp(&ratings[a]));
+        write!(f, "{:width$} | Elo", "", width = width)?;
+        for i in order {
+            write!(
+                f,
+                "\n{:width$} | {:.0}",
+                self.names[i],
+                ratings[i],
+                width = width
+            )?;
+        }
+        Ok(())
+    }
+}
+
+// Plays `games` between `first` and `second`, `first` taking X for the first
+// half, and returns `first`'s (wins, losses, draws).
+pub(crate) fn play_pairing(
+    first: &mut dyn Agent,
+    second: &mut dyn Agent,
+    games: usize,
+) -> (u32, u32, u32) {
+    let mut record = (0, 0, 0);
+    for game in 0..games {
+        let first_is_x = game < games / 2;
+        let result = if first_is_x {
+            play_game(first, second)
+        } else {
+            play_game(second, first)
+        };
+        let Some(result) = counted(result) else {
+            continue;
+        };
+        match (result, first_is_x) {
+            (GameResult::XWin, true) | (GameResult::OWin, false) => record.0 += 1,
+            (GameResult::XWin, false) | (GameResult::OWin, true) => record.1 += 1,
+            (GameResult::Draw, _) => record.2 += 1,
+        }
+    }
+    record
+}
+
+// Plays every pair of agents against each other `games_per_pair` times, with
+// each side taking X for half of the games. RL agents are frozen for the
+// duration so the results reflect their learned policies.
+pub fn run_tournament(
+    agents: &mut [(&str, Box<dyn Agent>)],
+    games_per_pair: usize,
+) -> TournamentResult {
+    let was_learning: Vec<bool> = agents
+        .iter()
+        .map(|(_, agent)| agent.is_learning())
+        .collect();
+    for (_, agent) in agents.iter_mut() {
+        agent.freeze();
+    }
+    let mut records = vec![vec![(0, 0, 0); agents.len()]; agents.len()];
+    let pairs: Vec<(usize, usize)> = (0..agents.len())
+        .flat_map(|j| (0..j).map(move |i| (i, j)))
+        .collect();
+    for (i, j) in pairs {
+        let (left, right) = agents.split_at_mut(j);
+        let (wins, losses, draws) =
+            play_pairing(left[i].1.as_mut(), right[0].1.as_mut(), games_per_pair);
+        records[i][j] = (wins, losses, draws);
+        records[j][i] = (losses, wins, draws);
+    }
+    for ((_, agent), was_learning) in agents.iter_mut().zip(was_learning) {
+        if was_learning {
+            agent.unfreeze();
+        }
+    }
+    TournamentResult {
+        names: agents.iter().map(|(name, _)| name.to_string()).collect(),
+        records,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::agents::is_over;
+    use crate::{
+        legal_positions, Board, HeuristicAgent, QLearningAgent, RLAgent, RLConfig, RandomAgent,
+        SarsaAgent,
+    };
+
+    // What `agent` has learned, or how it values every position a move can
+    // be made in for agents that can't be checkpointed.
+    fn learned(agent: &dyn Agent) -> Vec<u8> {
+        agent.save_state().unwrap_or_else(|| {
+            let mut positions: Vec<Board> = legal_positions()
+                .into_iter()
+                .filter(|board| !is_over(board))
+                .collect();
+            positions.sort_by_key(|board| board.to_key());
+            let values: Vec<Option<Vec<(usize, f32)>>> = positions
+                .iter()
+                .map(|board| agent.move_values(board, board.player_to_move()))
+                .collect();
+            bincode::serialize(&values).unwrap()
+        })
+    }
+
+    #[test]
+    fn a_tournament_leaves_every_entrant_as_it_found_it() {
+        let mut entrants: Vec<(&str, Box<dyn Agent>)> = vec![
+            ("rl", Box::new(RLAgent::new(RLConfig::default(), Some(1)))),
+            (
+                "q-learning",
+                Box::new(QLearningAgent::new(RLConfig::default(), Some(2))),
+            ),
+            (
+                "sarsa",
+                Box::new(SarsaAgent::new(RLConfig::default(), Some(3))),
+            ),
+            ("heuristic", Box::new(HeuristicAgent::new(Some(4)))),
+        ];
+        let mut random = RandomAgent::new(Some(5));
+        for (_, agent) in entrants.iter_mut().take(3) {
+            for _ in 0..300 {
+                play_game(agent.as_mut(), &mut random).unwrap();
+                play_game(&mut random, agent.as_mut()).unwrap();
+            }
+        }
+        let before: Vec<Vec<u8>> = entrants
+            .iter()
+            .map(|(_, agent)| learned(agent.as_ref()))
+            .collect();
+        let result = run_tournament(&mut entrants, 50);
+        assert_eq!(result.names.len(), 4);
+        for ((name, agent), before) in entrants.iter().zip(&before) {
+            assert!(learned(agent.as_ref()) == *before, "{} changed", name);
+        }
+        // Learners pick up learning again afterwards.
+        assert!(entrants
+            .iter()
+            .take(3)
+            .all(|(_, agent)| agent.is_learning()));
+    }
+
+    #[test]
+    fn tournament_wins_are_marked_significant_only_after_correction() {
+        assert_eq!(
+            bonferroni_correct(&[0.001, 0.02, 0.5], 0.05),
+            [true, false, false]
+        );
+        assert!((sign_test(10, 0) - 2.0 / 1024.0).abs() < 1e-12);
+        assert_eq!(sign_test(5, 5), 1.0);
+        assert_eq!(sign_test(0, 0), 1.0);
+        // 15 of 20 decisive games (p = 0.041) would pass alone, but not once
+        // the level is split over 3 pairings.
+        let result = TournamentResult {
+            names: vec!["a".to_string(), "b".to_string(), "c".to_string()],
+            records: vec![
+                vec![(0, 0, 0), (15, 5, 0), (40, 0, 10)],
+                vec![(5, 15, 0), (0, 0, 0), (10, 10, 5)],
+                vec![(0, 40, 10), (10, 10, 5), (0, 0, 0)],
+            ],
+        };
+        assert!(result.p_value(0, 1) < 0.05);
+        let significant = result.significant_wins(0.05);
+        assert_eq!(
+            significant,
+            [
+                [false, false, true],
+                [false, false, false],
+                [false, false, false]
+            ]
+        );
+        let table = result.to_string();
+        assert!(table.contains("0.75/0.00/0.25 ns"), "{}", table);
+        assert!(table.contains("0.80/0.20/0.00  *"), "{}", table);
+        assert!(table.contains("Bonferroni-corrected over 3 pairings"));
+    }
+}
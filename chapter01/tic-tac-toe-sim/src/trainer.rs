@@ -0,0 +1,1081 @@
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use rayon::prelude::*;
+use rl_core::{mean_and_std_error, TrajectoryRecorder};
+use serde::{Deserialize, Serialize};
+
+use crate::game::counted;
+use crate::league::{RatingLeague, SavedLeague, SidedAgent};
+#[cfg(feature = "tui")]
+use crate::Dashboard;
+use crate::{
+    interrupted, play_game, play_game_with_transcript, Agent, Board, GameError, GameResult,
+    InterruptScope, MinimaxAgent, PlayerMarker, Progress, RLAgent, RLConfig, RandomAgent, Rating,
+};
+
+// Who the agents face at a `Trainer` evaluation point.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum EvalOpponent {
+    // Perfect play, so losses measure how exploitable a policy is.
+    #[default]
+    Minimax,
+    Random,
+    // A frozen copy of the other agent as it was at the previous evaluation
+    // point (or the start of the run), so results track progress against its
+    // own past. Agents without `Agent::snapshot` are replaced by random play.
+    Snapshot,
+}
+
+// How a `Trainer` schedules its games.
+#[derive(Clone, Debug)]
+pub struct TrainingConfig {
+    // Swap sides every episode, so each agent plays X in half of the games.
+    pub alternate_sides: bool,
+    // Every `eval_interval` episodes both agents are frozen and each plays
+    // `eval_games` on its own side against `eval_opponent`. 0 disables
+    // evaluation.
+    pub eval_interval: u32,
+    pub eval_games: u32,
+    pub eval_opponent: EvalOpponent,
+    // Every `metrics_interval` episodes the X agent is frozen and plays
+    // `eval_games` as X against a random opponent for a `Metrics` row. 0
+    // disables metrics.
+    pub metrics_interval: u32,
+    // League-style training: when above 0, each agent trains against a frozen
+    // snapshot of the other, retaken every `opponent_refresh` episodes, rather
+    // than both chasing each other's moving policies. Every episode is then
+    // two games, one for each learner, so each still learns from one game an
+    // episode. Agents without `Agent::snapshot` are replaced by random play.
+    pub opponent_refresh: u32,
+    // Every `rating_interval` episodes frozen copies of both agents join a
+    // league as one entrant, play `eval_games` against every earlier entrant
+    // and the random, heuristic and minimax baselines, and every entrant's
+    // Elo rating is refitted into `TrainingStats::ratings`. 0 disables the
+    // league.
+    pub rating_interval: u32,
+    // Reseeds the X agent with `seed` and the O agent with `seed + 1` before
+    // the first episode, so the whole run can be repeated exactly.
+    pub seed: Option<u64>,
+}
+
+impl Default for TrainingConfig {
+    fn default() -> Self {
+        TrainingConfig {
+            alternate_sides: false,
+            eval_interval: 0,
+            eval_games: 100,
+            eval_opponent: EvalOpponent::default(),
+            metrics_interval: 0,
+            opponent_refresh: 0,
+            rating_interval: 0,
+            seed: None,
+        }
+    }
+}
+
+// How often each agent won and lost against the evaluation opponent at one
+// evaluation point.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Evaluation {
+    pub episode: u32,
+    pub x_agent_win_rate: f32,
+    pub x_agent_loss_rate: f32,
+    pub o_agent_win_rate: f32,
+    pub o_agent_loss_rate: f32,
+}
+
+// One point on the X agent's learning curve against random play.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Metrics {
+    pub episode: u32,
+    pub win_rate: f32,
+    pub draw_rate: f32,
+    pub loss_rate: f32,
+    pub table_size: usize,
+    pub mean_value: f32,
+}
+
+// Writes `metrics` as CSV with a header row, for plotting elsewhere.
+pub fn write_metrics_csv(metrics: &[Metrics], path: &Path) -> io::Result<()> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    writeln!(
+        writer,
+        "episode,win_rate,draw_rate,loss_rate,table_size,mean_value"
+    )?;
+    for row in metrics {
+        writeln!(
+            writer,
+            "{},{},{},{},{},{}",
+            row.episode, row.win_rate, row.draw_rate, row.loss_rate, row.table_size, row.mean_value
+        )?;
+    }
+    writer.flush()
+}
+
+// Writes `ratings` as CSV with a header row, a row for each entrant in each
+// round, for plotting each entrant's rating over the run.
+pub fn write_ratings_csv(ratings: &[Rating], path: &Path) -> io::Result<()> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    writeln!(writer, "episode,entrant,rating")?;
+    for row in ratings {
+        writeln!(writer, "{},{},{:.1}", row.episode, row.entrant, row.rating)?;
+    }
+    writer.flush()
+}
+
+// One learning-curve point or checkpoint averaged over `seeds` runs: each
+// measure's name, its mean over the runs and the standard error of the mean.
+#[derive(Clone, Debug)]
+pub struct SeedAverage {
+    pub episode: u32,
+    pub seeds: usize,
+    pub measures: Vec<(&'static str, f64, f64)>,
+}
+
+// A measure's name and how to read it off one point.
+type Measure<T> = (&'static str, fn(&T) -> f64);
+
+const METRIC_MEASURES: [Measure<Metrics>; 5] = [
+    ("win_rate", |point| point.win_rate as f64),
+    ("draw_rate", |point| point.draw_rate as f64),
+    ("loss_rate", |point| point.loss_rate as f64),
+    ("table_size", |point| point.table_size as f64),
+    ("mean_value", |point| point.mean_value as f64),
+];
+
+const EVALUATION_MEASURES: [Measure<Evaluation>; 4] = [
+    ("x_agent_win_rate", |e| e.x_agent_win_rate as f64),
+    ("x_agent_loss_rate", |e| e.x_agent_loss_rate as f64),
+    ("o_agent_win_rate", |e| e.o_agent_win_rate as f64),
+    ("o_agent_loss_rate", |e| e.o_agent_loss_rate as f64),
+];
+
+// The learning curves of `runs`, trained alike but for their seeds, averaged
+// point by point.
+pub fn average_metrics(runs: &[TrainingStats]) -> Vec<SeedAverage> {
+    average_points(
+        runs,
+        |stats| &stats.metrics,
+        |point| point.episode,
+        &METRIC_MEASURES,
+    )
+}
+
+// The checkpoints of `runs`, as for `average_metrics`.
+pub fn average_evaluations(runs: &[TrainingStats]) -> Vec<SeedAverage> {
+    average_points(
+        runs,
+        |stats| &stats.evaluations,
+        |evaluation| evaluation.episode,
+        &EVALUATION_MEASURES,
+    )
+}
+
+fn average_points<T>(
+    runs: &[TrainingStats],
+    points: impl Fn(&TrainingStats) -> &[T],
+    episode: fn(&T) -> u32,
+    measures: &[Measure<T>],
+) -> Vec<SeedAverage> {
+    let count = runs.iter().map(|run| points(run).len()).min().unwrap_or(0);
+    (0..count)
+        .map(|i| {
+            let at: Vec<&T> = runs.iter().map(|run| &points(run)[i]).collect();
+            assert!(
+                at.iter().all(|&point| episode(point) == episode(at[0])),
+                "the runs' points fall at different episodes"
+            );
+            SeedAverage {
+                episode: episode(at[0]),
+                seeds: runs.len(),
+                measures: measures
+                    .iter()
+                    .map(|&(name, measure)| {
+                        let values: Vec<f64> = at.iter().map(|&point| measure(point)).collect();
+                        let (mean, std_error) = mean_and_std_error(&values);
+                        (name, mean, std_error)
+                    })
+                    .collect(),
+            }
+        })
+        .collect()
+}
+
+// Writes `averages` as CSV with a header row: the episode, the number of
+// seeds, then each measure's mean and standard error.
+pub fn write_seed_averages_csv(averages: &[SeedAverage], path: &Path) -> io::Result<()> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    write!(writer, "episode,seeds")?;
+    for (name, _, _) in averages.first().map_or(&[][..], |first| &first.measures) {
+        write!(writer, ",{},{}_std_error", name, name)?;
+    }
+    writeln!(writer)?;
+    for row in averages {
+        write!(writer, "{},{}", row.episode, row.seeds)?;
+        for (_, mean, std_error) in &row.measures {
+            write!(writer, ",{},{}", mean, std_error)?;
+        }
+        writeln!(writer)?;
+    }
+    writer.flush()
+}
+
+// Wins are counted per agent, whichever side it played. With frozen
+// opponents only the learners' results count: each agent's wins are those of
+// its own games, and the draws those of both.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct TrainingStats {
+    pub x_agent_wins: u32,
+    pub o_agent_wins: u32,
+    pub draws: u32,
+    // Games abandoned because an agent failed to move; they count for
+    // neither agent.
+    pub abandoned: u32,
+    pub evaluations: Vec<Evaluation>,
+    pub metrics: Vec<Metrics>,
+    // Every entrant's rating after each round of the rating league, oldest
+    // round first.
+    pub ratings: Vec<Rating>,
+}
+
+pub type EpisodeCallback = Box<dyn Fn(u32, &GameResult)>;
+
+// Frozen copies of a `Trainer`'s X and O agents.
+type AgentPair = (Box<dyn Agent>, Box<dyn Agent>);
+
+// The file a `Trainer` keeps its latest checkpoint in, inside the directory
+// given to `with_checkpoints`.
+const CHECKPOINT_FILE: &str = "checkpoint.bin";
+
+// A `Trainer` after some episode: the agents' and frozen copies' states from
+// `Agent::save_state`, the seeds the agents were reseeded with as it was
+// taken, and the results so far.
+#[derive(Serialize, Deserialize)]
+struct TrainerCheckpoint {
+    episode: u32,
+    seeds: (u64, u64),
+    x_agent: Vec<u8>,
+    o_agent: Vec<u8>,
+    snapshots: Option<(Vec<u8>, Vec<u8>)>,
+    opponents: Option<(Vec<u8>, Vec<u8>)>,
+    league: Option<SavedLeague>,
+    stats: TrainingStats,
+}
+
+// Runs games between two agents, calling any `on_episode_end` hooks after each
+// one with the episode number and its result (the X agent's game, when
+// training against frozen opponents). A game an agent fails to move in is
+// abandoned with a warning, and training goes on; its hooks aren't called.
+pub struct Trainer {
+    x_agent: Box<dyn Agent>,
+    o_agent: Box<dyn Agent>,
+    config: TrainingConfig,
+    episode_callbacks: Vec<EpisodeCallback>,
+    // Frozen copies of the X and O agents for `EvalOpponent::Snapshot`.
+    snapshots: Option<(Box<dyn Agent>, Box<dyn Agent>)>,
+    // Frozen copies of the X and O agents to train against when
+    // `config.opponent_refresh` is above 0.
+    opponents: Option<(Box<dyn Agent>, Box<dyn Agent>)>,
+    progress: bool,
+    #[cfg(feature = "tui")]
+    dashboard: bool,
+    // The directory `run` writes a checkpoint to, and every how many
+    // episodes.
+    checkpoints: Option<(PathBuf, u32)>,
+    // The last episode and results of the checkpoint `resume` read, which
+    // `run` carries on from.
+    resumed: Option<(u32, TrainingStats)>,
+    // Present once `config.rating_interval` has first come round.
+    league: Option<RatingLeague>,
+    // Where `run` records every training game, until a write fails.
+    trajectories: Option<TrajectoryRecorder>,
+    // The last episode played when Ctrl-C stopped `run` early.
+    interrupted_after: Option<u32>,
+}
+
+impl Trainer {
+    pub fn new(x_agent: Box<dyn Agent>, o_agent: Box<dyn Agent>) -> Self {
+        Trainer {
+            x_agent,
+            o_agent,
+            config: TrainingConfig::default(),
+            episode_callbacks: Vec::new(),
+            snapshots: None,
+            opponents: None,
+            progress: false,
+            #[cfg(feature = "tui")]
+            dashboard: false,
+            checkpoints: None,
+            resumed: None,
+            league: None,
+            trajectories: None,
+            interrupted_after: None,
+        }
+    }
+
+    pub fn with_config(mut self, config: TrainingConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    // Shows a `Progress` bar during `run`, with the X agent's exploration
+    // rate.
+    pub fn with_progress(mut self) -> Self {
+        self.progress = true;
+        self
+    }
+
+    // Shows a `Dashboard` during `run` in place of the progress bar.
+    #[cfg(feature = "tui")]
+    pub fn with_dashboard(mut self) -> Self {
+        self.dashboard = true;
+        self
+    }
+
+    // Writes a checkpoint to `dir` every `interval` episodes, replacing the
+    // one before, for `resume` to carry on from if the run is stopped. Each
+    // checkpoint also reseeds the agents, with seeds derived from
+    // `config.seed` and the episode when there is one, so a run resumed from
+    // it plays on exactly as the run that wrote it did.
+    pub fn with_checkpoints(mut self, dir: impl Into<PathBuf>, interval: u32) -> Self {
+        self.checkpoints = Some((dir.into(), interval.max(1)));
+        self
+    }
+
+    // Records every training game `run` plays with `recorder`, as
+    // `GameTranscript::episode` gives it, for offline learning or analysis.
+    // Evaluation, metrics and rating games are not recorded.
+    pub fn with_trajectories(mut self, recorder: TrajectoryRecorder) -> Self {
+        self.trajectories = Some(recorder);
+        self
+    }
+
+    // Restores the agents, frozen copies and results from the checkpoint in
+    // `dir`, so that the next `run` carries on after the episode it was
+    // taken at, which is returned. The agents must be built as they were for
+    // the run that wrote it, and the run given the same config and episodes.
+    pub fn resume(&mut self, dir: &Path) -> Result<u32, String> {
+        let path = dir.join(CHECKPOINT_FILE);
+        let file = File::open(&path)
+            .map_err(|error| format!("couldn't read {}: {}", path.display(), error))?;
+        let checkpoint: TrainerCheckpoint = bincode::deserialize_from(BufReader::new(file))
+            .map_err(|error| format!("{} is not a checkpoint: {}", path.display(), error))?;
+        self.x_agent.restore_state(&checkpoint.x_agent)?;
+        self.o_agent.restore_state(&checkpoint.o_agent)?;
+        self.x_agent.reseed(checkpoint.seeds.0);
+        self.o_agent.reseed(checkpoint.seeds.1);
+        self.snapshots = self.restore_copies(checkpoint.snapshots)?;
+        self.opponents = self.restore_copies(checkpoint.opponents)?;
+        self.league = checkpoint
+            .league
+            .map(|saved| {
+                RatingLeague::restore(saved, || {
+                    let (x, o) = self.frozen_copies();
+                    Ok(Box::new(SidedAgent::new(x, o)))
+                })
+            })
+            .transpose()?;
+        self.resumed = Some((checkpoint.episode, checkpoint.stats));
+        Ok(checkpoint.episode)
+    }
+
+    // Frozen copies of the agents holding the `states` a checkpoint saved.
+    fn restore_copies(
+        &self,
+        states: Option<(Vec<u8>, Vec<u8>)>,
+    ) -> Result<Option<AgentPair>, String> {
+        let Some((x_state, o_state)) = states else {
+            return Ok(None);
+        };
+        let copy = |agent: &dyn Agent, state: &[u8]| {
+            let mut copy = agent
+                .snapshot()
+                .ok_or("the checkpoint has a frozen copy of an agent that can't be copied")?;
+            copy.restore_state(state)?;
+            Ok::<_, String>(copy)
+        };
+        Ok(Some((
+            copy(self.x_agent.as_ref(), &x_state)?,
+            copy(self.o_agent.as_ref(), &o_state)?,
+        )))
+    }
+
+    // Reseeds the agents and writes everything `resume` needs to carry on
+    // after `episode` to the checkpoint directory.
+    fn write_checkpoint(&mut self, episode: u32, stats: &TrainingStats) -> Result<(), String> {
+        let seeds = match self.config.seed {
+            Some(seed) => {
+                let seed = seed ^ (episode as u64).wrapping_mul(0x9E37_79B9_7F4A_7C15);
+                (seed, seed.wrapping_add(1))
+            }
+            None => (rand::random(), rand::random()),
+        };
+        self.x_agent.reseed(seeds.0);
+        self.o_agent.reseed(seeds.1);
+        let state = |agent: &dyn Agent| {
+            agent
+                .save_state()
+                .ok_or_else(|| "an agent can't be checkpointed".to_string())
+        };
+        let pair = |copies: &Option<AgentPair>| {
+            copies
+                .as_ref()
+                .map(|(x, o)| Ok::<_, String>((state(x.as_ref())?, state(o.as_ref())?)))
+                .transpose()
+        };
+        let checkpoint = TrainerCheckpoint {
+            episode,
+            seeds,
+            x_agent: state(self.x_agent.as_ref())?,
+            o_agent: state(self.o_agent.as_ref())?,
+            snapshots: pair(&self.snapshots)?,
+            opponents: pair(&self.opponents)?,
+            league: self.league.as_ref().map(RatingLeague::save).transpose()?,
+            stats: stats.clone(),
+        };
+        let (dir, _) = self
+            .checkpoints
+            .as_ref()
+            .expect("only called with checkpoints");
+        // Written beside the last checkpoint and renamed over it, so a run
+        // stopped while writing leaves the last one whole.
+        let path = dir.join(CHECKPOINT_FILE);
+        let partial = dir.join(format!("{}.partial", CHECKPOINT_FILE));
+        fs::create_dir_all(dir)
+            .and_then(|()| {
+                let mut writer = BufWriter::new(File::create(&partial)?);
+                bincode::serialize_into(&mut writer, &checkpoint).map_err(io::Error::other)?;
+                writer.flush()
+            })
+            .and_then(|()| fs::rename(&partial, &path))
+            .map_err(|error| format!("couldn't write {}: {}", path.display(), error))
+    }
+
+    pub fn on_episode_end(mut self, callback: impl Fn(u32, &GameResult) + 'static) -> Self {
+        self.episode_callbacks.push(Box::new(callback));
+        self
+    }
+
+    pub fn run(&mut self, episodes: u32) -> TrainingStats {
+        let (first, mut stats) = match self.resumed.take() {
+            Some((episode, stats)) => (episode + 1, stats),
+            None => {
+                if let Some(seed) = self.config.seed {
+                    self.x_agent.reseed(seed);
+                    self.o_agent.reseed(seed.wrapping_add(1));
+                }
+                if self.config.eval_opponent == EvalOpponent::Snapshot {
+                    self.take_snapshots();
+                }
+                let stats = TrainingStats {
+                    x_agent_wins: 0,
+                    o_agent_wins: 0,
+                    draws: 0,
+                    abandoned: 0,
+                    evaluations: Vec::new(),
+                    metrics: Vec::new(),
+                    ratings: Vec::new(),
+                };
+                (1, stats)
+            }
+        };
+        let refresh = self.config.opponent_refresh;
+        let mut progress = self.progress.then(|| Progress::new(episodes as u64));
+        #[cfg(feature = "tui")]
+        let mut dashboard = self.dashboard.then(|| Dashboard::new(episodes as u64));
+        #[cfg(feature = "tui")]
+        if dashboard.is_some() {
+            progress = None;
+        }
+        let _scope = InterruptScope::enter();
+        for episode in first..=episodes {
+            if interrupted() {
+                self.stop_early(episode - 1, &stats);
+                if let Some(progress) = &mut progress {
+                    progress.stop();
+                }
+                break;
+            }
+            let swapped = self.config.alternate_sides && episode % 2 == 0;
+            let result = if refresh > 0 {
+                if (episode - 1) % refresh == 0 {
+                    self.opponents = Some(self.frozen_copies());
+                }
+                let (x_opponent, o_opponent) = self.opponents.as_mut().expect("taken above");
+                let x_result = counted(play_sides(
+                    self.x_agent.as_mut(),
+                    o_opponent.as_mut(),
+                    swapped,
+                    &mut self.trajectories,
+                ));
+                let o_result = counted(play_sides(
+                    x_opponent.as_mut(),
+                    self.o_agent.as_mut(),
+                    swapped,
+                    &mut self.trajectories,
+                ));
+                match x_result.map(|result| winner(result, swapped)) {
+                    Some(Some(PlayerMarker::X)) => stats.x_agent_wins += 1,
+                    Some(Some(PlayerMarker::O)) => {}
+                    Some(None) => stats.draws += 1,
+                    None => stats.abandoned += 1,
+                }
+                match o_result.map(|result| winner(result, swapped)) {
+                    Some(Some(PlayerMarker::O)) => stats.o_agent_wins += 1,
+                    Some(Some(PlayerMarker::X)) => {}
+                    Some(None) => stats.draws += 1,
+                    None => stats.abandoned += 1,
+                }
+                x_result
+            } else {
+                let result = counted(play_sides(
+                    self.x_agent.as_mut(),
+                    self.o_agent.as_mut(),
+                    swapped,
+                    &mut self.trajectories,
+                ));
+                match result.map(|result| winner(result, swapped)) {
+                    Some(Some(PlayerMarker::X)) => stats.x_agent_wins += 1,
+                    Some(Some(PlayerMarker::O)) => stats.o_agent_wins += 1,
+                    Some(None) => stats.draws += 1,
+                    None => stats.abandoned += 1,
+                }
+                result
+            };
+            if cfg!(debug_assertions) && episode % 1000 == 0 {
+                self.x_agent.assert_finite_q_values();
+                self.o_agent.assert_finite_q_values();
+            }
+            #[cfg(feature = "tui")]
+            if let (Some(dashboard), Some(result)) = (&mut dashboard, result) {
+                dashboard.record(winner(result, swapped));
+            }
+            if let Some(result) = result {
+                for callback in &self.episode_callbacks {
+                    callback(episode, &result);
+                }
+            }
+            if self.config.eval_interval > 0 && episode % self.config.eval_interval == 0 {
+                stats.evaluations.push(self.evaluate(episode));
+            }
+            if self.config.metrics_interval > 0 && episode % self.config.metrics_interval == 0 {
+                stats.metrics.push(self.measure(episode));
+            }
+            if self.config.rating_interval > 0 && episode % self.config.rating_interval == 0 {
+                stats.ratings.extend(self.rate(episode));
+            }
+            if matches!(self.checkpoints, Some((_, interval)) if episode % interval == 0) {
+                if let Err(error) = self.write_checkpoint(episode, &stats) {
+                    eprintln!("Warning: {}", error);
+                }
+            }
+            if let Some(progress) = &mut progress {
+                progress.update(episode as u64, &exploration_detail(self.x_agent.as_ref()));
+            }
+            #[cfg(feature = "tui")]
+            if let Some(dashboard) = &mut dashboard {
+                dashboard.update(episode as u64, self.x_agent.as_ref(), &stats.evaluations);
+            }
+        }
+        if let Some(recorder) = &mut self.trajectories {
+            if let Err(error) = recorder.flush() {
+                eprintln!("Warning: couldn't write the trajectories: {}", error);
+            }
+        }
+        stats
+    }
+
+    // The last episode played if Ctrl-C stopped the last `run` early.
+    pub fn interrupted_after(&self) -> Option<u32> {
+        self.interrupted_after
+    }
+
+    // Ends a run stopped by Ctrl-C after `episode`, with a checkpoint there
+    // unless the one the interval called for was just written. A run resumed
+    // from it carries on from there, though not exactly as an uninterrupted
+    // run would, since the checkpoint reseeds the agents.
+    fn stop_early(&mut self, episode: u32, stats: &TrainingStats) {
+        self.interrupted_after = Some(episode);
+        if matches!(self.checkpoints, Some((_, interval)) if !episode.is_multiple_of(interval)) {
+            if let Err(error) = self.write_checkpoint(episode, stats) {
+                eprintln!("Warning: {}", error);
+            }
+        }
+    }
+
+    // Plays the frozen X agent against a random O for a learning-curve point.
+    pub fn measure(&mut self, episode: u32) -> Metrics {
+        let was_learning = self.x_agent.is_learning();
+        self.x_agent.freeze();
+        let mut random = RandomAgent::new(self.config.seed.map(|seed| seed.wrapping_add(3)));
+        let games = self.config.eval_games;
+        let mut results = [0u32; 3];
+        for _ in 0..games {
+            if let Some(result) = counted(play_game(self.x_agent.as_mut(), &mut random)) {
+                results[result as usize] += 1;
+            }
+        }
+        if was_learning {
+            self.x_agent.unfreeze();
+        }
+        let (table_size, mean_value) = self.x_agent.value_summary().unwrap_or((0, 0.0));
+        let rate = |result: GameResult| results[result as usize] as f32 / games as f32;
+        Metrics {
+            episode,
+            win_rate: rate(GameResult::XWin),
+            draw_rate: rate(GameResult::Draw),
+            loss_rate: rate(GameResult::OWin),
+            table_size,
+            mean_value,
+        }
+    }
+
+    // Adds frozen copies of the agents to the rating league as they are
+    // after `episode`, and returns every entrant's rating.
+    pub fn rate(&mut self, episode: u32) -> Vec<Rating> {
+        let (x, o) = self.frozen_copies();
+        let seed = self
+            .config
+            .seed
+            .map(|seed| seed ^ (episode as u64).wrapping_mul(0xBF58_476D_1CE4_E5B9));
+        self.league.get_or_insert_with(RatingLeague::new).add(
+            format!("snapshot-{}", episode),
+            Box::new(SidedAgent::new(x, o)),
+            self.config.eval_games as usize,
+            seed,
+            episode,
+        )
+    }
+
+    fn take_snapshots(&mut self) {
+        self.snapshots = Some(self.frozen_copies());
+    }
+
+    fn frozen_copies(&self) -> (Box<dyn Agent>, Box<dyn Agent>) {
+        let seed = self.config.seed.map(|seed| seed.wrapping_add(2));
+        let fallback = || -> Box<dyn Agent> { Box::new(RandomAgent::new(seed)) };
+        (
+            self.x_agent.snapshot().unwrap_or_else(fallback),
+            self.o_agent.snapshot().unwrap_or_else(fallback),
+        )
+    }
+
+    // The X agent plays X and the O agent plays O, `eval_games` each.
+    pub fn evaluate(&mut self, episode: u32) -> Evaluation {
+        let was_learning = (self.x_agent.is_learning(), self.o_agent.is_learning());
+        self.x_agent.freeze();
+        self.o_agent.freeze();
+        let seed = self.config.seed.map(|seed| seed.wrapping_add(2));
+        // Each agent faces an opponent on the other side.
+        let (mut x_opponent, mut o_opponent): (Box<dyn Agent>, Box<dyn Agent>) = match self
+            .config
+            .eval_opponent
+        {
+            EvalOpponent::Minimax => (Box::new(MinimaxAgent::new()), Box::new(MinimaxAgent::new())),
+            EvalOpponent::Random => (
+                Box::new(RandomAgent::new(seed)),
+                Box::new(RandomAgent::new(seed)),
+            ),
+            EvalOpponent::Snapshot => {
+                if self.snapshots.is_none() {
+                    self.take_snapshots();
+                }
+                let (x_snapshot, o_snapshot) = self.snapshots.take().expect("taken above");
+                (o_snapshot, x_snapshot)
+            }
+        };
+        let games = self.config.eval_games;
+        let mut x_results = [0u32; 3];
+        let mut o_results = [0u32; 3];
+        for _ in 0..games {
+            if let Some(result) = counted(play_game(self.x_agent.as_mut(), x_opponent.as_mut())) {
+                x_results[result as usize] += 1;
+            }
+            if let Some(result) = counted(play_game(o_opponent.as_mut(), self.o_agent.as_mut())) {
+                o_results[result as usize] += 1;
+            }
+        }
+        if was_learning.0 {
+            self.x_agent.unfreeze();
+        }
+        if was_learning.1 {
+            self.o_agent.unfreeze();
+        }
+        if self.config.eval_opponent == EvalOpponent::Snapshot {
+            self.take_snapshots();
+        }
+        let rate = |count: u32| count as f32 / games as f32;
+        Evaluation {
+            episode,
+            x_agent_win_rate: rate(x_results[GameResult::XWin as usize]),
+            x_agent_loss_rate: rate(x_results[GameResult::OWin as usize]),
+            o_agent_win_rate: rate(o_results[GameResult::OWin as usize]),
+            o_agent_loss_rate: rate(o_results[GameResult::XWin as usize]),
+        }
+    }
+}
+
+// The agent's exploration rate for a progress line, if it has one.
+fn exploration_detail(agent: &dyn Agent) -> String {
+    agent
+        .exploration_rate()
+        .map(|epsilon| format!("epsilon {:.3}", epsilon))
+        .unwrap_or_default()
+}
+
+// Plays the trainer's X agent (or its stand-in) against its O agent, on the
+// sides their names say unless `swapped`, and records the game with
+// `trajectories`. A recorder that fails to write is dropped with a warning,
+// leaving the run to go on unrecorded.
+fn play_sides(
+    x_agent: &mut dyn Agent,
+    o_agent: &mut dyn Agent,
+    swapped: bool,
+    trajectories: &mut Option<TrajectoryRecorder>,
+) -> Result<GameResult, GameError> {
+    let (result, transcript) = if swapped {
+        play_game_with_transcript(o_agent, x_agent)?
+    } else {
+        play_game_with_transcript(x_agent, o_agent)?
+    };
+    if let Some(recorder) = trajectories {
+        if let Err(error) = recorder.record(&transcript.episode()) {
+            eprintln!("Warning: stopped recording trajectories: {}", error);
+            *trajectories = None;
+        }
+    }
+    Ok(result)
+}
+
+// Which of a trainer's agents won a game from `play_sides`, named by the side
+// the agent plays when not swapped; `None` for a draw.
+fn winner(result: GameResult, swapped: bool) -> Option<PlayerMarker> {
+    let side = match result {
+        GameResult::XWin => PlayerMarker::X,
+        GameResult::OWin => PlayerMarker::O,
+        GameResult::Draw => return None,
+    };
+    Some(if swapped { side.opposite() } else { side })
+}
+
+// Splits `num_games` of self-play across `threads` sessions, each with its own
+// X and O agent, and returns an X and an O agent holding the merged tables.
+// Every `sync_interval` games per session the tables are merged and handed
+// back to every session, so they keep learning from each other's experience;
+// 0 merges only once, at the end. With a `seed`, each session's agents get
+// their own seed derived from it, so the merged tables are reproducible. Any
+// `progress` is updated at every merge. Ctrl-C stops every session after its
+// current game, and the tables are merged as they are then.
+pub fn train_parallel(
+    config: RLConfig,
+    num_games: u32,
+    threads: usize,
+    sync_interval: u32,
+    seed: Option<u64>,
+    mut progress: Option<&mut Progress>,
+) -> (RLAgent, RLAgent) {
+    let pool = rayon::ThreadPoolBuilder::new()
+        .num_threads(threads)
+        .build()
+        .expect("Failed to build thread pool");
+    let mut sessions: Vec<(RLAgent, RLAgent, u32)> = (0..threads)
+        .map(|thread| {
+            let share =
+                num_games / threads as u32 + ((thread as u32) < num_games % threads as u32) as u32;
+            let thread_seed = seed.map(|seed| seed.wrapping_add(2 * thread as u64));
+            (
+                RLAgent::new(config.clone(), thread_seed),
+                RLAgent::new(config.clone(), thread_seed.map(|seed| seed.wrapping_add(1))),
+                share,
+            )
+        })
+        .collect();
+    let round_games = if sync_interval == 0 {
+        u32::MAX
+    } else {
+        sync_interval
+    };
+
+    let _scope = InterruptScope::enter();
+    loop {
+        pool.install(|| {
+            sessions.par_iter_mut().for_each(|(x_rl, o_rl, remaining)| {
+                let games = (*remaining).min(round_games);
+                for episode in 0..games {
+                    if interrupted() {
+                        break;
+                    }
+                    counted(play_game(x_rl, o_rl));
+                    if cfg!(debug_assertions) && episode % 1000 == 0 {
+                        x_rl.assert_finite_q_values();
+                        o_rl.assert_finite_q_values();
+                    }
+                    *remaining -= 1;
+                }
+            })
+        });
+        if let Some(progress) = progress.as_deref_mut() {
+            let remaining: u32 = sessions.iter().map(|&(_, _, remaining)| remaining).sum();
+            progress.update(
+                (num_games - remaining) as u64,
+                &exploration_detail(&sessions[0].0),
+            );
+            if interrupted() {
+                progress.stop();
+            }
+        }
+        let x_merged = merge_by_visits(sessions.iter().map(|(x_rl, _, _)| x_rl));
+        let o_merged = merge_by_visits(sessions.iter().map(|(_, o_rl, _)| o_rl));
+        if interrupted() || sessions.iter().all(|&(_, _, remaining)| remaining == 0) {
+            let mut x_rl = RLAgent::with_q_table(x_merged, config.clone());
+            let mut o_rl = RLAgent::with_q_table(o_merged, config);
+            x_rl.visit_counts = total_visits(sessions.iter().map(|(x_rl, _, _)| x_rl));
+            o_rl.visit_counts = total_visits(sessions.iter().map(|(_, o_rl, _)| o_rl));
+            return (x_rl, o_rl);
+        }
+        for (x_rl, o_rl, _) in &mut sessions {
+            x_rl.q_table.clone_from(&x_merged);
+            o_rl.q_table.clone_from(&o_merged);
+        }
+    }
+}
+
+// Merges the agents' Q-tables, weighting each agent's value for a state by how
+// often that agent has played into it. A state no agent has played keeps the
+// plain average of the values it has.
+fn merge_by_visits<'a>(agents: impl Iterator<Item = &'a RLAgent>) -> HashMap<Board, f32> {
+    let mut totals: HashMap<Board, (f32, u32, f32, u32)> = HashMap::new();
+    for agent in agents {
+        for (state, &value) in &agent.q_table {
+            let visits = *agent.visit_counts.get(state).unwrap_or(&0);
+            let total = totals.entry(*state).or_insert((0.0, 0, 0.0, 0));
+            total.0 += value * visits as f32;
+            total.1 += visits;
+            total.2 += value;
+            total.3 += 1;
+        }
+    }
+    totals
+        .into_iter()
+        .map(|(state, (weighted, visits, sum, count))| {
+            let value = if visits > 0 {
+                weighted / visits as f32
+            } else {
+                sum / count as f32
+            };
+            (state, value)
+        })
+        .collect()
+}
+
+// Sums the agents' visit counts.
+fn total_visits<'a>(agents: impl Iterator<Item = &'a RLAgent>) -> HashMap<Board, u32> {
+    let mut totals = HashMap::new();
+    for agent in agents {
+        for (state, &visits) in &agent.visit_counts {
+            *totals.entry(*state).or_insert(0) += visits;
+        }
+    }
+    totals
+}
+
+// Averages each key's value over the tables that contain it.
+pub fn merge_q_tables(tables: Vec<HashMap<Board, f32>>) -> HashMap<Board, f32> {
+    let mut totals: HashMap<Board, (f32, u32)> = HashMap::new();
+    for (state, value) in tables.into_iter().flatten() {
+        let total = totals.entry(state).or_insert((0.0, 0));
+        total.0 += value;
+        total.1 += 1;
+    }
+    totals
+        .into_iter()
+        .map(|(state, (sum, count))| (state, sum / count as f32))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{AgentOutcome, DecaySchedule};
+    use rl_core::Episode;
+    use std::fs;
+
+    // Plays at random and fails every scan of its values, as an agent whose
+    // table had gone bad would.
+    struct CorruptAgent(RandomAgent);
+
+    impl Agent for CorruptAgent {
+        fn choose_move(&mut self, board: &Board, player: PlayerMarker) -> usize {
+            self.0.choose_move(board, player)
+        }
+
+        fn observe_result(&mut self, _outcome: AgentOutcome, _board: &Board) {}
+
+        fn assert_finite_q_values(&self) {
+            panic!("corrupt table");
+        }
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "corrupt table")]
+    fn self_play_scans_the_tables_in_debug_builds() {
+        let mut trainer = Trainer::new(
+            Box::new(RandomAgent::new(Some(1))),
+            Box::new(CorruptAgent(RandomAgent::new(Some(2)))),
+        );
+        trainer.run(1000);
+    }
+
+    #[test]
+    fn frozen_opponents_count_only_the_learners_games() {
+        let mut trainer = Trainer::new(
+            Box::new(RLAgent::new(RLConfig::default(), None)),
+            Box::new(RandomAgent::new(None)),
+        )
+        .with_config(TrainingConfig {
+            opponent_refresh: 50,
+            seed: Some(1),
+            ..TrainingConfig::default()
+        });
+        let stats = trainer.run(200);
+        // Two games an episode, each counting at most once.
+        let counted = stats.x_agent_wins + stats.o_agent_wins + stats.draws;
+        assert!(counted > 200 && counted <= 400, "{}", counted);
+        // The random O agent has no snapshot, so the RL agent trains against
+        // random play and wins far more of its games than it loses.
+        assert!(stats.x_agent_wins > 100, "{}", stats.x_agent_wins);
+        assert!(trainer.x_agent.value_summary().unwrap().0 > 0);
+    }
+
+    #[test]
+    fn a_resumed_run_carries_on_exactly_where_it_stopped() {
+        let dir = std::env::temp_dir().join("trainer_checkpoints");
+        for opponent_refresh in [0, 40] {
+            let trainer = |name: &str| {
+                let config = RLConfig {
+                    epsilon: DecaySchedule::Constant(0.2),
+                    replay_capacity: 50,
+                    ..RLConfig::default()
+                };
+                Trainer::new(
+                    Box::new(RLAgent::new(config.clone(), None)),
+                    Box::new(RLAgent::new(config, None)),
+                )
+                .with_config(TrainingConfig {
+                    alternate_sides: true,
+                    eval_interval: 100,
+                    eval_games: 20,
+                    eval_opponent: EvalOpponent::Snapshot,
+                    metrics_interval: 50,
+                    opponent_refresh,
+                    rating_interval: 100,
+                    seed: Some(7),
+                })
+                .with_checkpoints(dir.join(name), 100)
+            };
+            let straight = trainer("straight").run(300);
+            // Stopped partway between checkpoints, then resumed from the last.
+            trainer("stopped").run(170);
+            let mut resumed = trainer("stopped");
+            assert_eq!(resumed.resume(&dir.join("stopped")), Ok(100));
+            let resumed = resumed.run(300);
+            assert_eq!(
+                bincode::serialize(&resumed).unwrap(),
+                bincode::serialize(&straight).unwrap()
+            );
+            let checkpoint = |name: &str| fs::read(dir.join(name).join(CHECKPOINT_FILE)).unwrap();
+            assert_eq!(checkpoint("stopped"), checkpoint("straight"));
+        }
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn recorded_training_games_read_back_move_by_move() {
+        let path = std::env::temp_dir().join("trainer_trajectories.bin");
+        let mut trainer = Trainer::new(
+            Box::new(RLAgent::new(RLConfig::default(), None)),
+            Box::new(RandomAgent::new(None)),
+        )
+        .with_config(TrainingConfig {
+            seed: Some(4),
+            ..TrainingConfig::default()
+        })
+        .with_trajectories(TrajectoryRecorder::create(&path).unwrap());
+        let stats = trainer.run(100);
+        let episodes: Vec<Episode<Board, usize>> = rl_core::read_trajectories(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(episodes.len(), 100);
+        for episode in &episodes {
+            assert_eq!(episode.states[0], Board::new());
+            for (turn, &cell) in episode.actions.iter().enumerate() {
+                let player = episode.states[turn].player_to_move();
+                assert_eq!(
+                    episode.states[turn + 1],
+                    episode.states[turn].with_move(cell, player)
+                );
+            }
+        }
+        let final_rewards = |reward: f64| {
+            episodes
+                .iter()
+                .filter(|episode| episode.rewards.last() == Some(&reward))
+                .count() as u32
+        };
+        assert_eq!(final_rewards(1.0), stats.x_agent_wins);
+        assert_eq!(final_rewards(-1.0), stats.o_agent_wins);
+    }
+
+    #[test]
+    fn runs_are_averaged_point_by_point() {
+        let runs: Vec<TrainingStats> = [1, 2]
+            .into_iter()
+            .map(|seed| {
+                Trainer::new(
+                    Box::new(RLAgent::new(RLConfig::default(), None)),
+                    Box::new(RLAgent::new(RLConfig::default(), None)),
+                )
+                .with_config(TrainingConfig {
+                    eval_interval: 100,
+                    eval_games: 20,
+                    metrics_interval: 100,
+                    seed: Some(seed),
+                    ..TrainingConfig::default()
+                })
+                .run(200)
+            })
+            .collect();
+        let metrics = average_metrics(&runs);
+        let evaluations = average_evaluations(&runs);
+        assert_eq!(metrics.len(), 2);
+        assert_eq!(evaluations.len(), 2);
+        let (name, mean, std_error) = metrics[1].measures[0];
+        let (a, b) = (runs[0].metrics[1].win_rate, runs[1].metrics[1].win_rate);
+        assert_eq!(
+            (metrics[1].episode, metrics[1].seeds, name),
+            (200, 2, "win_rate")
+        );
+        assert!((mean - (a + b) as f64 / 2.0).abs() < 1e-6);
+        assert!((std_error - (a - b).abs() as f64 / 2.0).abs() < 1e-6);
+        assert_eq!(evaluations[0].measures[3].0, "o_agent_loss_rate");
+    }
+
+    #[test]
+    fn merging_q_tables_averages_over_the_tables_holding_each_key() {
+        let (a, b, c) = (
+            Board::new().with_move(0, PlayerMarker::X),
+            Board::new().with_move(4, PlayerMarker::X),
+            Board::new().with_move(8, PlayerMarker::X),
+        );
+        let merged = merge_q_tables(vec![
+            HashMap::from([(a, 1.0), (b, 0.5)]),
+            HashMap::from([(a, 0.0), (c, -1.0)]),
+            HashMap::from([(a, 0.5)]),
+        ]);
+        assert_eq!(merged, HashMap::from([(a, 0.5), (b, 0.5), (c, -1.0)]));
+        assert!(merge_q_tables(Vec::new()).is_empty());
+    }
+}
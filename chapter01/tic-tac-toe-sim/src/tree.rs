@@ -0,0 +1,290 @@
+use std::collections::HashMap;
+use std::io::{self, Write};
+
+use serde::Serialize;
+
+use crate::{
+    play_game_with_transcript, Agent, AgentOutcome, Board, GameError, GameResult, PlayerMarker,
+    RandomAgent,
+};
+
+// One position in a `GameTree`.
+pub struct TreeNode {
+    pub board: Board,
+    // The agent's estimate for this position: the value of its greedy move
+    // when it is to move, otherwise the value of the afterstate its last move
+    // produced. `None` for the empty board when the agent plays O, and for
+    // agents without values.
+    pub value: Option<f32>,
+    // How many of the games passed to `count_visits` reached this position.
+    pub visits: u32,
+    pub result: Option<GameResult>,
+    // (cell, node index) for each move considered here: every move on the
+    // opponent's turn, only the greedy one on the agent's.
+    pub children: Vec<(usize, usize)>,
+}
+
+// Every position reachable from the empty board when `side` plays its greedy
+// move and the opponent may play anything: what the agent would do against
+// any opening. Positions reached by different move orders share a node, so
+// this is really a directed acyclic graph. `nodes[0]` is the empty board.
+pub struct GameTree {
+    pub side: PlayerMarker,
+    pub nodes: Vec<TreeNode>,
+    index: HashMap<Board, usize>,
+}
+
+// The board's cells in row-major order with `.` for empty, as `--position`
+// takes them.
+fn cells(board: &Board) -> String {
+    board
+        .iter_cells()
+        .map(|cell| cell.map_or('.', PlayerMarker::player_char))
+        .collect()
+}
+
+fn result_of(board: &Board) -> Option<GameResult> {
+    if board.check_winner(PlayerMarker::X) {
+        Some(GameResult::XWin)
+    } else if board.check_winner(PlayerMarker::O) {
+        Some(GameResult::OWin)
+    } else if board.is_full() {
+        Some(GameResult::Draw)
+    } else {
+        None
+    }
+}
+
+impl GameTree {
+    // Expands the tree by asking `agent` for its move in every position where
+    // it is to move; it should be frozen, so that it moves greedily and
+    // learns nothing.
+    pub fn expand(agent: &mut dyn Agent, side: PlayerMarker) -> GameTree {
+        let mut tree = GameTree {
+            side,
+            nodes: Vec::new(),
+            index: HashMap::new(),
+        };
+        tree.add(Board::new(), None);
+        // Nodes are only ever appended, so a cursor walks them breadth first.
+        let mut next = 0;
+        while next < tree.nodes.len() {
+            let board = tree.nodes[next].board;
+            if tree.nodes[next].result.is_none() {
+                let player = board.player_to_move();
+                if player == side {
+                    let chosen = agent.choose_move(&board, player);
+                    let value = agent.move_values(&board, player).and_then(|values| {
+                        values
+                            .into_iter()
+                            .find(|&(i, _)| i == chosen)
+                            .map(|(_, value)| value)
+                    });
+                    tree.nodes[next].value = value;
+                    let child = tree.add(board.with_move(chosen, player), value);
+                    tree.nodes[next].children.push((chosen, child));
+                } else {
+                    for i in board.available_moves() {
+                        let child = tree.add(board.with_move(i, player), None);
+                        tree.nodes[next].children.push((i, child));
+                    }
+                }
+            }
+            next += 1;
+        }
+        // Lets the agent drop the moves it was asked for; frozen, it learns
+        // nothing from this.
+        agent.observe_result(AgentOutcome::Draw, &Board::new());
+        tree
+    }
+
+    // The node for `board`, added with `value` if it is new.
+    fn add(&mut self, board: Board, value: Option<f32>) -> usize {
+        if let Some(&node) = self.index.get(&board) {
+            return node;
+        }
+        self.nodes.push(TreeNode {
+            board,
+            value,
+            visits: 0,
+            result: result_of(&board),
+            children: Vec::new(),
+        });
+        self.index.insert(board, self.nodes.len() - 1);
+        self.nodes.len() - 1
+    }
+
+    // Plays `games` games of the frozen `agent` on its side against random
+    // play and counts how many reach each node, stopping at the first game the
+    // agent fails to move in.
+    pub fn count_visits(
+        &mut self,
+        agent: &mut dyn Agent,
+        games: u32,
+        seed: Option<u64>,
+    ) -> Result<(), GameError> {
+        let mut random = RandomAgent::new(seed);
+        for _ in 0..games {
+            let (_, transcript) = match self.side {
+                PlayerMarker::X => play_game_with_transcript(agent, &mut random)?,
+                PlayerMarker::O => play_game_with_transcript(&mut random, agent)?,
+            };
+            self.nodes[0].visits += 1;
+            for board in transcript.replay() {
+                if let Some(&node) = self.index.get(&board) {
+                    self.nodes[node].visits += 1;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // GraphViz DOT, one box per position showing the board, the agent's value
+    // and the visit count. The agent's moves are bold and finished games
+    // shaded: green for its wins, red for its losses and grey for draws.
+    pub fn write_dot(&self, out: &mut dyn Write) -> io::Result<()> {
+        writeln!(out, "digraph tree {{")?;
+        writeln!(out, "  node [shape=box, fontname=\"monospace\"];")?;
+        for (id, node) in self.nodes.iter().enumerate() {
+            let rows: Vec<String> = cells(&node.board)
+                .as_bytes()
+                .chunks(3)
+                .map(|row| String::from_utf8_lossy(row).into_owned())
+                .collect();
+            let value = node
+                .value
+                .map_or("-".to_string(), |value| format!("{:+.3}", value));
+            let fill = match node.result {
+                None => String::new(),
+                Some(GameResult::Draw) => ", style=filled, fillcolor=lightgrey".to_string(),
+                Some(result) => {
+                    let won = (result == GameResult::XWin) == (self.side == PlayerMarker::X);
+                    let color = if won { "palegreen" } else { "lightpink" };
+                    format!(", style=filled, fillcolor={}", color)
+                }
+            };
+            writeln!(
+                out,
+                "  n{} [label=\"{}\\nv={} n={}\"{}];",
+                id,
+                rows.join("\\n"),
+                value,
+                node.visits,
+                fill
+            )?;
+            let bold = node.board.player_to_move() == self.side;
+            for &(cell, child) in &node.children {
+                writeln!(
+                    out,
+                    "  n{} -> n{} [label=\"{}\"{}];",
+                    id,
+                    child,
+                    cell + 1,
+                    if bold { ", style=bold" } else { "" }
+                )?;
+            }
+        }
+        writeln!(out, "}}")
+    }
+
+    // JSON: the agent's side and a list of nodes, each with its id, cells,
+    // side to move, value, visits, result and children as {"cell", "node"}
+    // pairs. Cells are numbered from 1, as in play.
+    pub fn write_json(&self, out: &mut dyn Write) -> io::Result<()> {
+        #[derive(Serialize)]
+        struct Child {
+            cell: usize,
+            node: usize,
+        }
+        #[derive(Serialize)]
+        struct Node {
+            id: usize,
+            board: String,
+            to_move: Option<char>,
+            value: Option<f32>,
+            visits: u32,
+            result: Option<String>,
+            children: Vec<Child>,
+        }
+        #[derive(Serialize)]
+        struct Tree {
+            side: char,
+            nodes: Vec<Node>,
+        }
+        let tree = Tree {
+            side: PlayerMarker::player_char(self.side),
+            nodes: self
+                .nodes
+                .iter()
+                .enumerate()
+                .map(|(id, node)| Node {
+                    id,
+                    board: cells(&node.board),
+                    to_move: node
+                        .result
+                        .is_none()
+                        .then(|| PlayerMarker::player_char(node.board.player_to_move())),
+                    value: node.value,
+                    visits: node.visits,
+                    result: node.result.map(|result| result.to_string()),
+                    children: node
+                        .children
+                        .iter()
+                        .map(|&(cell, node)| Child {
+                            cell: cell + 1,
+                            node,
+                        })
+                        .collect(),
+                })
+                .collect(),
+        };
+        serde_json::to_writer_pretty(&mut *out, &tree)?;
+        writeln!(out)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::MinimaxAgent;
+
+    #[test]
+    fn the_agents_turns_have_one_move_and_the_opponents_every_move() {
+        let mut agent = MinimaxAgent::new();
+        let tree = GameTree::expand(&mut agent, PlayerMarker::O);
+        let root = &tree.nodes[0];
+        assert_eq!(root.children.len(), 9);
+        for &(_, child) in &root.children {
+            let node = &tree.nodes[child];
+            assert_eq!(node.children.len(), 1);
+            let (_, reply) = node.children[0];
+            assert_eq!(tree.nodes[reply].children.len(), 7);
+        }
+        // Minimax never loses, whatever X plays.
+        assert!(tree
+            .nodes
+            .iter()
+            .all(|node| node.result != Some(GameResult::XWin)));
+    }
+
+    #[test]
+    fn visits_follow_the_games_played() {
+        let mut agent = MinimaxAgent::new();
+        let mut tree = GameTree::expand(&mut agent, PlayerMarker::X);
+        tree.count_visits(&mut agent, 50, Some(1)).unwrap();
+        assert_eq!(tree.nodes[0].visits, 50);
+        let (_, opening) = tree.nodes[0].children[0];
+        assert_eq!(tree.nodes[opening].visits, 50);
+        let finished: u32 = tree
+            .nodes
+            .iter()
+            .filter(|node| node.result.is_some())
+            .map(|node| node.visits)
+            .sum();
+        assert_eq!(finished, 50);
+        let mut json = Vec::new();
+        tree.write_json(&mut json).unwrap();
+        let parsed: serde_json::Value = serde_json::from_slice(&json).unwrap();
+        assert_eq!(parsed["nodes"].as_array().unwrap().len(), tree.nodes.len());
+    }
+}
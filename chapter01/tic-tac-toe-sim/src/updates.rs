@@ -0,0 +1,619 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::{ActionValues, Board, PlayerMarker, PolicyTable, RLConfig, SymmetryOp};
+
+// The Q-table key of the board after `player` marks `index`: its canonical
+// form when `symmetric`, otherwise the board itself.
+pub(crate) fn afterstate_key(
+    board: &Board,
+    index: usize,
+    player: PlayerMarker,
+    symmetric: bool,
+) -> Board {
+    let afterstate = board.with_move(index, player);
+    if symmetric {
+        afterstate.canonical()
+    } else {
+        afterstate
+    }
+}
+
+// Plays a random move with probability `epsilon`, otherwise the move whose
+// afterstate has the highest value in `q_table`, `initial` for afterstates it
+// lacks.
+pub(crate) fn epsilon_greedy(
+    q_table: &impl PolicyTable,
+    initial: f32,
+    board: &Board,
+    player: PlayerMarker,
+    epsilon: f32,
+    symmetric: bool,
+    rng: &mut impl Rng,
+) -> usize {
+    if rng.random::<f32>() < epsilon {
+        let available: Vec<usize> = board.available_moves().collect();
+        return available[rng.next_u32() as usize % available.len()];
+    }
+    greedy_move(board, player, symmetric, |key| {
+        q_table.value(&key).unwrap_or(initial)
+    })
+}
+
+// The available move whose afterstate key scores highest.
+pub(crate) fn greedy_move(
+    board: &Board,
+    player: PlayerMarker,
+    symmetric: bool,
+    value: impl Fn(Board) -> f32,
+) -> usize {
+    board
+        .available_moves()
+        .map(|i| (i, value(afterstate_key(board, i, player, symmetric))))
+        .max_by(|a, b| a.1.total_cmp(&b.1))
+        .expect("No available moves")
+        .0
+}
+
+// UCB1 over the available moves, treating each afterstate's visit count as
+// N(s,a) and their sum as N(s). A move that has never been played is chosen
+// before any move is repeated.
+pub(crate) fn ucb1_move(
+    q_table: &impl PolicyTable,
+    initial: f32,
+    visit_counts: &HashMap<Board, u32>,
+    board: &Board,
+    player: PlayerMarker,
+    c: f32,
+    symmetric: bool,
+) -> usize {
+    let moves: Vec<(usize, Board, u32)> = board
+        .available_moves()
+        .map(|i| {
+            let key = afterstate_key(board, i, player, symmetric);
+            (i, key, *visit_counts.get(&key).unwrap_or(&0))
+        })
+        .collect();
+    if let Some(&(untried, _, _)) = moves.iter().find(|&&(_, _, count)| count == 0) {
+        return untried;
+    }
+    let parent_visits: u32 = moves.iter().map(|&(_, _, count)| count).sum();
+    moves
+        .iter()
+        .map(|&(i, key, count)| {
+            let value = q_table.value(&key).unwrap_or(initial);
+            let bonus = c * ((parent_visits as f32).ln() / count as f32).sqrt();
+            (i, value + bonus)
+        })
+        .max_by(|a, b| a.1.total_cmp(&b.1))
+        .expect("No available moves")
+        .0
+}
+
+// Samples a move with probability proportional to exp(Q / temperature). Values
+// are shifted by their maximum first so that a tiny temperature picks the
+// greedy move instead of overflowing.
+pub(crate) fn softmax_move(
+    q_table: &impl PolicyTable,
+    initial: f32,
+    board: &Board,
+    player: PlayerMarker,
+    temperature: f32,
+    symmetric: bool,
+    rng: &mut impl Rng,
+) -> usize {
+    let moves: Vec<(usize, f32)> = board
+        .available_moves()
+        .map(|i| {
+            let key = afterstate_key(board, i, player, symmetric);
+            (i, q_table.value(&key).unwrap_or(initial))
+        })
+        .collect();
+    let max_value = moves
+        .iter()
+        .map(|&(_, value)| value)
+        .fold(f32::MIN, f32::max);
+    let weights: Vec<f32> = moves
+        .iter()
+        .map(|&(_, value)| ((value - max_value) / temperature).exp())
+        .collect();
+    let mut remaining = rng.random::<f32>() * weights.iter().sum::<f32>();
+    for (&(i, _), weight) in moves.iter().zip(&weights) {
+        if remaining < *weight {
+            return i;
+        }
+        remaining -= weight;
+    }
+    moves.last().expect("No available moves").0
+}
+
+// One step of experience: `action` played on `state`, the reward that
+// followed, and the board at the agent's next turn, or the final board when
+// `terminal`.
+#[derive(Clone, Serialize, Deserialize)]
+pub(crate) struct Transition {
+    pub(crate) state: Board,
+    pub(crate) action: usize,
+    pub(crate) reward: f32,
+    pub(crate) next_state: Board,
+    pub(crate) terminal: bool,
+}
+
+// A ring buffer of the most recent `capacity` transitions for experience
+// replay.
+pub(crate) struct EpisodeBuffer {
+    capacity: usize,
+    pub(crate) transitions: VecDeque<Transition>,
+}
+
+impl EpisodeBuffer {
+    pub fn new(capacity: usize) -> Self {
+        EpisodeBuffer {
+            capacity,
+            transitions: VecDeque::with_capacity(capacity),
+        }
+    }
+
+    pub fn push(&mut self, transition: Transition) {
+        if self.transitions.len() == self.capacity {
+            self.transitions.pop_front();
+        }
+        self.transitions.push_back(transition);
+    }
+
+    // `batch_size` transitions drawn uniformly with replacement.
+    pub fn sample<'a>(&'a self, batch_size: usize, rng: &mut impl Rng) -> Vec<&'a Transition> {
+        if self.transitions.is_empty() {
+            return Vec::new();
+        }
+        (0..batch_size)
+            .map(|_| &self.transitions[rng.random_range(0..self.transitions.len())])
+            .collect()
+    }
+}
+
+// Backs up a sampled mini-batch with one-step Q-learning targets: the reward
+// plus, unless the game ended, the best afterstate value from `next_state`.
+pub(crate) fn replay_update(
+    q_table: &mut impl PolicyTable,
+    buffer: &EpisodeBuffer,
+    config: &RLConfig,
+    rng: &mut impl Rng,
+) {
+    for transition in buffer.sample(config.replay_batch_size, rng) {
+        let player = transition.state.player_to_move();
+        let bootstrap = if transition.terminal {
+            0.0
+        } else {
+            transition
+                .next_state
+                .available_moves()
+                .map(|i| {
+                    q_table
+                        .value(&afterstate_key(
+                            &transition.next_state,
+                            i,
+                            player,
+                            config.symmetry,
+                        ))
+                        .unwrap_or(config.initial_value)
+                })
+                .fold(f32::MIN, f32::max)
+        };
+        let afterstate = afterstate_key(
+            &transition.state,
+            transition.action,
+            player,
+            config.symmetry,
+        );
+        update_q(
+            q_table,
+            config.initial_value,
+            &afterstate,
+            transition.reward + config.gamma * bootstrap,
+            config.learning_rate,
+        );
+    }
+}
+
+// First-visit Monte Carlo backup: each distinct state in the episode moves
+// toward its return, which in tic-tac-toe is the terminal reward discounted
+// once per move that followed the state.
+pub(crate) fn monte_carlo_update(
+    q_table: &mut impl PolicyTable,
+    episode_states: &mut Vec<Board>,
+    reward: f32,
+    config: &RLConfig,
+) {
+    let last = episode_states.len().saturating_sub(1);
+    let mut visited = HashSet::new();
+    for (t, state) in episode_states.drain(..).enumerate() {
+        if visited.insert(state) {
+            let discount = config.gamma.powi((last - t) as i32);
+            update_q(
+                q_table,
+                config.initial_value,
+                &state,
+                discount * reward,
+                config.learning_rate,
+            );
+        }
+    }
+}
+
+// The (state, action) key for playing `action` on `board`. When `symmetric`,
+// the board is replaced by its canonical form and the action by the cell it
+// maps to under the same symmetry, so equivalent pairs share a value.
+pub(crate) fn state_action_key(board: &Board, action: usize, symmetric: bool) -> (Board, usize) {
+    if !symmetric {
+        return (*board, action);
+    }
+    let op = SymmetryOp::all()
+        .into_iter()
+        .min_by_key(|&op| board.apply_symmetry(op).to_key())
+        .expect("there are always 8 symmetries");
+    (board.apply_symmetry(op), op.transform_action(action))
+}
+
+// Q(board, a) for every available action a, in cell order, `initial` for
+// pairs `q_table` lacks.
+pub(crate) fn action_values(
+    q_table: &ActionValues,
+    initial: f32,
+    board: &Board,
+    symmetric: bool,
+) -> Vec<(usize, f32)> {
+    board
+        .available_moves()
+        .map(|i| {
+            let key = state_action_key(board, i, symmetric);
+            (i, *q_table.get(&key).unwrap_or(&initial))
+        })
+        .collect()
+}
+
+// The available action with the highest value, the lowest cell on ties.
+pub(crate) fn greedy_action(
+    q_table: &ActionValues,
+    initial: f32,
+    board: &Board,
+    symmetric: bool,
+) -> usize {
+    action_values(q_table, initial, board, symmetric)
+        .into_iter()
+        .fold(None, |best: Option<(usize, f32)>, (i, value)| match best {
+            Some((_, best_value)) if best_value >= value => best,
+            _ => Some((i, value)),
+        })
+        .expect("No available moves")
+        .0
+}
+
+pub(crate) fn best_action_value(
+    q_table: &ActionValues,
+    initial: f32,
+    board: &Board,
+    symmetric: bool,
+) -> f32 {
+    action_values(q_table, initial, board, symmetric)
+        .into_iter()
+        .map(|(_, value)| value)
+        .fold(f32::MIN, f32::max)
+}
+
+// Plays a random available action with probability `epsilon`, otherwise the
+// greedy one.
+pub(crate) fn epsilon_greedy_action(
+    q_table: &ActionValues,
+    initial: f32,
+    board: &Board,
+    epsilon: f32,
+    symmetric: bool,
+    rng: &mut impl Rng,
+) -> usize {
+    if rng.random::<f32>() < epsilon {
+        let available: Vec<usize> = board.available_moves().collect();
+        return available[rng.next_u32() as usize % available.len()];
+    }
+    greedy_action(q_table, initial, board, symmetric)
+}
+
+// Moves Q(key), `initial` if it has no value yet, a step of `learning_rate`
+// toward `target`.
+pub(crate) fn update_action_value(
+    q_table: &mut ActionValues,
+    initial: f32,
+    key: (Board, usize),
+    target: f32,
+    learning_rate: f32,
+) {
+    assert_finite(
+        target,
+        &format!("target for state {:#b}, action {}", key.0.to_key(), key.1),
+    );
+    let value = q_table.entry(key).or_insert(initial);
+    *value += learning_rate * (target - *value);
+}
+
+pub(crate) fn assert_finite_action_values(q_table: &ActionValues) {
+    for ((state, action), value) in q_table {
+        assert_finite(
+            *value,
+            &format!(
+                "table scan of state {:#b}, action {}",
+                state.to_key(),
+                action
+            ),
+        );
+    }
+}
+
+// Moves the value of `state`, `initial` if it has none yet, a step of
+// `learning_rate` toward `reward`.
+pub(crate) fn update_q(
+    q_table: &mut impl PolicyTable,
+    initial: f32,
+    state: &Board,
+    reward: f32,
+    learning_rate: f32,
+) {
+    assert_finite(reward, &format!("target for state {:#b}", state.to_key()));
+    let value = q_table.value_mut(*state, initial);
+    assert_finite(
+        *value,
+        &format!("stored value for state {:#b}", state.to_key()),
+    );
+    *value += learning_rate * (reward - *value);
+    assert_finite(*value, &format!("update of state {:#b}", state.to_key()));
+}
+
+// TD(lambda) with accumulating traces: every state's trace decays by
+// gamma * lambda,
+// the trace of `prev_board` is bumped by one, and the TD error toward `target`
+// is applied to each state in proportion to its trace. With a lambda of 0 this
+// is exactly `update_q`.
+pub(crate) fn td_lambda_update(
+    q_table: &mut impl PolicyTable,
+    traces: &mut HashMap<Board, f32>,
+    prev_board: &Board,
+    target: f32,
+    config: &RLConfig,
+) {
+    if config.lambda == 0.0 {
+        return update_q(
+            q_table,
+            config.initial_value,
+            prev_board,
+            target,
+            config.learning_rate,
+        );
+    }
+    assert_finite(
+        target,
+        &format!("target for state {:#b}", prev_board.to_key()),
+    );
+    let prev_board = *prev_board;
+    let td_error = target - q_table.value(&prev_board).unwrap_or(config.initial_value);
+    // Drop traces too small to matter so the map stays the size of an episode.
+    traces.retain(|_, trace| {
+        *trace *= config.gamma * config.lambda;
+        *trace >= 1e-4
+    });
+    *traces.entry(prev_board).or_insert(0.0) += 1.0;
+    for (state, trace) in traces.iter() {
+        let value = q_table.value_mut(*state, config.initial_value);
+        *value += config.learning_rate * td_error * trace;
+        assert_finite(*value, &format!("update of state {:#b}", state.to_key()));
+    }
+}
+
+// Once `buffer` holds `n_step` afterstates, backs up the oldest toward the
+// discounted rewards collected since it plus `bootstrap`, the value of the
+// current best move, discounted n times.
+pub(crate) fn n_step_update(
+    q_table: &mut impl PolicyTable,
+    buffer: &mut VecDeque<(Board, f32)>,
+    bootstrap: f32,
+    config: &RLConfig,
+) {
+    if buffer.len() < config.n_step {
+        return;
+    }
+    let target = buffer
+        .iter()
+        .rev()
+        .fold(bootstrap, |g, &(_, reward)| reward + config.gamma * g);
+    let (state, _) = buffer.pop_front().expect("buffer holds n_step entries");
+    update_q(
+        q_table,
+        config.initial_value,
+        &state,
+        target,
+        config.learning_rate,
+    );
+}
+
+// At the end of an episode there is nothing left to bootstrap from, so every
+// buffered afterstate is backed up toward the discounted rewards that followed
+// it, with the terminal `reward` arriving alongside the last of them.
+pub(crate) fn n_step_flush(
+    q_table: &mut impl PolicyTable,
+    buffer: &mut VecDeque<(Board, f32)>,
+    reward: f32,
+    config: &RLConfig,
+) {
+    if let Some((_, last)) = buffer.back_mut() {
+        *last += reward;
+    }
+    while !buffer.is_empty() {
+        let target = buffer
+            .iter()
+            .rev()
+            .fold(0.0, |g, &(_, reward)| reward + config.gamma * g);
+        let (state, _) = buffer.pop_front().expect("buffer is not empty");
+        update_q(
+            q_table,
+            config.initial_value,
+            &state,
+            target,
+            config.learning_rate,
+        );
+    }
+}
+
+// Stops training as soon as a NaN or infinity reaches a Q-table, rather than
+// letting it spread through every later bootstrap.
+pub(crate) fn assert_finite(value: f32, context: &str) {
+    if !value.is_finite() {
+        panic!("Q-value corruption: {} produced {}", context, value);
+    }
+}
+
+pub(crate) fn assert_finite_table(q_table: &impl PolicyTable) {
+    for (state, value) in q_table.entries() {
+        assert_finite(value, &format!("table scan of state {:#b}", state.to_key()));
+    }
+}
+
+// Moves `target` a fraction `tau` of the way toward `behavior`. States missing
+// from `behavior` count as zero, so they decay by `1 - tau`.
+pub(crate) fn soft_update_target(
+    behavior: &HashMap<Board, f32>,
+    target: &mut HashMap<Board, f32>,
+    tau: f32,
+) {
+    for (state, value) in target.iter_mut() {
+        if !behavior.contains_key(state) {
+            *value *= 1.0 - tau;
+        }
+    }
+    for (state, value) in behavior {
+        let target_value = target.entry(*state).or_insert(0.0);
+        *target_value = tau * value + (1.0 - tau) * *target_value;
+    }
+}
+
+// Moves every value in `polyak` a fraction `beta` of the way toward its value
+// in `live`. States new to `polyak` start from their live value.
+pub fn polyak_update<T: PolicyTable>(live: &T, polyak: &mut T, beta: f32) {
+    for (state, value) in live.entries() {
+        let average = polyak.value_mut(state, value);
+        *average = (1.0 - beta) * *average + beta * value;
+    }
+}
+
+// `table`'s entries in order of their boards, so equal tables save alike.
+pub(crate) fn sorted_entries(table: &impl PolicyTable) -> Vec<(Board, f32)> {
+    let mut entries: Vec<(Board, f32)> = table.entries().collect();
+    entries.sort_by_key(|(board, _)| board.to_key());
+    entries
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rl_core::seeded_rng;
+
+    #[test]
+    fn soft_target_updates_copy_at_tau_one_and_hold_at_tau_zero() {
+        let (a, b, c) = (
+            Board::new().with_move(0, PlayerMarker::X),
+            Board::new().with_move(4, PlayerMarker::X),
+            Board::new().with_move(8, PlayerMarker::X),
+        );
+        let behavior = HashMap::from([(a, 1.0), (b, -0.5)]);
+        let old_target = HashMap::from([(a, 0.25), (c, 0.75)]);
+        let mut target = old_target.clone();
+        soft_update_target(&behavior, &mut target, 0.0);
+        // At tau 0 nothing moves, though states only the behavior table holds
+        // are added at their old value of 0.
+        assert_eq!(target, HashMap::from([(a, 0.25), (b, 0.0), (c, 0.75)]));
+        let mut target = old_target.clone();
+        soft_update_target(&behavior, &mut target, 1.0);
+        assert_eq!(target, HashMap::from([(a, 1.0), (b, -0.5), (c, 0.0)]));
+        let mut target = old_target;
+        soft_update_target(&behavior, &mut target, 0.5);
+        assert_eq!(target, HashMap::from([(a, 0.625), (b, -0.25), (c, 0.375)]));
+    }
+
+    #[test]
+    fn ucb1_tries_every_move_before_weighing_values_against_visits() {
+        let board: Board = "XO.......".parse().unwrap();
+        let key = |i: usize| afterstate_key(&board, i, PlayerMarker::X, false);
+        let q_table: HashMap<Board, f32> = board.available_moves().map(|i| (key(i), 0.0)).collect();
+        let mut visits = HashMap::new();
+        for _ in 0..7 {
+            let chosen = ucb1_move(&q_table, 0.0, &visits, &board, PlayerMarker::X, 1.0, false);
+            assert!(!visits.contains_key(&key(chosen)));
+            visits.insert(key(chosen), 1);
+        }
+        // With equal values the least visited move wins, and a high enough
+        // value outweighs its visits.
+        for i in board.available_moves() {
+            visits.insert(key(i), 10);
+        }
+        visits.insert(key(5), 2);
+        assert_eq!(
+            ucb1_move(&q_table, 0.0, &visits, &board, PlayerMarker::X, 1.0, false),
+            5
+        );
+        let mut q_table = q_table;
+        q_table.insert(key(8), 2.0);
+        assert_eq!(
+            ucb1_move(&q_table, 0.0, &visits, &board, PlayerMarker::X, 1.0, false),
+            8
+        );
+    }
+
+    #[test]
+    fn softmax_is_greedy_when_cold_and_uniform_when_hot() {
+        let board = Board::new();
+        let q_table: HashMap<Board, f32> = board
+            .available_moves()
+            .map(|i| {
+                (
+                    afterstate_key(&board, i, PlayerMarker::X, false),
+                    i as f32 / 10.0,
+                )
+            })
+            .collect();
+        let mut rng = seeded_rng(Some(1));
+        let mut counts = [0; 9];
+        for _ in 0..1000 {
+            let cell = softmax_move(
+                &q_table,
+                0.0,
+                &board,
+                PlayerMarker::X,
+                1e-6,
+                false,
+                &mut rng,
+            );
+            counts[cell] += 1;
+        }
+        assert_eq!(counts[8], 1000);
+        let mut counts = [0; 9];
+        for _ in 0..9000 {
+            let cell = softmax_move(&q_table, 0.0, &board, PlayerMarker::X, 1e6, false, &mut rng);
+            counts[cell] += 1;
+        }
+        assert!(
+            counts.iter().all(|&count| (800..1200).contains(&count)),
+            "{:?}",
+            counts
+        );
+    }
+
+    #[test]
+    fn polyak_updates_move_the_average_a_fraction_of_the_way() {
+        let (a, b) = (Board::new(), Board::new().with_move(4, PlayerMarker::X));
+        let live = HashMap::from([(a, 1.0), (b, -1.0)]);
+        let mut polyak = HashMap::from([(a, 0.0)]);
+        polyak_update(&live, &mut polyak, 0.25);
+        assert_eq!(polyak, HashMap::from([(a, 0.25), (b, -1.0)]));
+        polyak_update(&live, &mut polyak, 1.0);
+        assert_eq!(polyak, live);
+    }
+}
@@ -0,0 +1,216 @@
+use std::collections::{BTreeMap, HashMap};
+use std::io::{self, Write};
+use std::path::Path;
+
+use rl_core::{Episode, TrajectoryReader};
+
+use crate::{legal_positions, Board};
+
+// How often training visited each legal position, up to rotation and
+// reflection, counted from the games `Trainer::with_trajectories` records.
+// Positions training never reached keep the values they started with, so
+// they are where an agent is most likely to blunder.
+pub struct Visitation {
+    // Every canonical legal position, with its visits.
+    visits: HashMap<Board, u64>,
+    pub games: u64,
+    // Boards in the games that legal play can't reach.
+    pub unreachable: u64,
+}
+
+impl Default for Visitation {
+    fn default() -> Self {
+        Visitation::new()
+    }
+}
+
+impl Visitation {
+    pub fn new() -> Self {
+        Visitation {
+            visits: legal_positions()
+                .iter()
+                .map(|board| (board.canonical(), 0))
+                .collect(),
+            games: 0,
+            unreachable: 0,
+        }
+    }
+
+    // The visits in every game of the trajectory file at `path`.
+    pub fn read(path: &Path) -> io::Result<Self> {
+        let mut reader = TrajectoryReader::open(path)?;
+        let mut visitation = Visitation::new();
+        while let Some(episode) = reader.next_episode()? {
+            visitation.record(&episode);
+        }
+        Ok(visitation)
+    }
+
+    // Counts a visit to each board of `episode`. A forfeiting move leaves the
+    // board as it was, which counts once.
+    pub fn record(&mut self, episode: &Episode<Board, usize>) {
+        let mut states = episode.states.clone();
+        states.dedup();
+        for state in states {
+            match self.visits.get_mut(&state.canonical()) {
+                Some(visits) => *visits += 1,
+                None => self.unreachable += 1,
+            }
+        }
+        self.games += 1;
+    }
+
+    pub fn visits(&self, board: &Board) -> u64 {
+        self.visits.get(&board.canonical()).copied().unwrap_or(0)
+    }
+
+    // The canonical positions of each ply with their visits, most visited
+    // first and ties in order of their cells, so the same games always give
+    // the same order.
+    pub fn plies(&self) -> BTreeMap<usize, Vec<(Board, u64)>> {
+        let mut plies: BTreeMap<usize, Vec<(Board, u64)>> = BTreeMap::new();
+        for (&board, &visits) in &self.visits {
+            let ply = board.iter_cells().flatten().count();
+            plies.entry(ply).or_default().push((board, visits));
+        }
+        for positions in plies.values_mut() {
+            positions.sort_by_key(|&(board, visits)| (std::cmp::Reverse(visits), board.cells()));
+        }
+        plies
+    }
+
+    // A summary for each ply: how many of its positions were seen, the `top`
+    // most and least visited, and up to `top` of those never seen.
+    pub fn write_report(&self, top: usize, out: &mut dyn Write) -> io::Result<()> {
+        let plies = self.plies();
+        let seen = |positions: &[(Board, u64)]| positions.iter().filter(|&&(_, n)| n > 0).count();
+        let total: usize = plies.values().map(Vec::len).sum();
+        writeln!(
+            out,
+            "Visits over {} games: {} of the {} positions up to rotation and reflection seen",
+            self.games,
+            plies
+                .values()
+                .map(|positions| seen(positions))
+                .sum::<usize>(),
+            total
+        )?;
+        if self.unreachable > 0 {
+            writeln!(
+                out,
+                "{} boards in the games are no legal position",
+                self.unreachable
+            )?;
+        }
+        for (ply, positions) in &plies {
+            let seen = seen(positions);
+            writeln!(
+                out,
+                "ply {}: {} of {} positions seen, {} visits",
+                ply,
+                seen,
+                positions.len(),
+                positions.iter().map(|&(_, n)| n).sum::<u64>()
+            )?;
+            let (visited, never) = positions.split_at(seen);
+            write_positions(out, "most visited", &visited[..top.min(seen)])?;
+            // Only those not already listed as most visited, least first.
+            let rest = &visited[top.min(seen)..];
+            let least: Vec<(Board, u64)> = rest.iter().rev().take(top).copied().collect();
+            write_positions(out, "least visited", &least)?;
+            if !never.is_empty() {
+                writeln!(out, "  never seen: {}", never.len())?;
+                for (board, _) in &never[..top.min(never.len())] {
+                    writeln!(out, "    {}", board.cells())?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // Every canonical position as CSV, by ply and then as `plies` orders
+    // them, to chart or search.
+    pub fn write_csv(&self, out: &mut dyn Write) -> io::Result<()> {
+        writeln!(out, "ply,position,visits")?;
+        for (ply, positions) in self.plies() {
+            for (board, visits) in positions {
+                writeln!(out, "{},{},{}", ply, board.cells(), visits)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+// A report's list of positions under `label`, if there are any.
+fn write_positions(out: &mut dyn Write, label: &str, positions: &[(Board, u64)]) -> io::Result<()> {
+    if positions.is_empty() {
+        return Ok(());
+    }
+    writeln!(out, "  {}:", label)?;
+    for (board, visits) in positions {
+        writeln!(out, "    {}  {}", board.cells(), visits)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::PlayerMarker;
+
+    fn game(cells: &[usize]) -> Episode<Board, usize> {
+        let mut states = vec![Board::new()];
+        for &cell in cells {
+            let board = *states.last().unwrap();
+            states.push(board.with_move(cell, board.player_to_move()));
+        }
+        Episode {
+            states,
+            rewards: vec![0.0; cells.len()],
+            actions: cells.to_vec(),
+            terminated: true,
+        }
+    }
+
+    #[test]
+    fn symmetric_positions_share_their_visits() {
+        let mut visitation = Visitation::new();
+        // Corner openings, all one position up to symmetry, then the centre.
+        visitation.record(&game(&[0, 4]));
+        visitation.record(&game(&[8, 4]));
+        visitation.record(&game(&[4]));
+        assert_eq!(visitation.games, 3);
+        assert_eq!(visitation.visits(&Board::new()), 3);
+        let corner = Board::new().with_move(2, PlayerMarker::X);
+        assert_eq!(visitation.visits(&corner), 2);
+        let plies = visitation.plies();
+        assert_eq!(plies[&1].len(), 3);
+        assert_eq!(plies[&1][0], (corner.canonical(), 2));
+        assert_eq!(plies[&1][2].1, 0);
+        let positions: usize = plies.values().map(Vec::len).sum();
+        assert_eq!(positions, 765);
+    }
+
+    #[test]
+    fn the_report_lists_unseen_positions_by_ply() {
+        let mut visitation = Visitation::new();
+        visitation.record(&game(&[4, 0]));
+        let mut text = Vec::new();
+        visitation.write_report(2, &mut text).unwrap();
+        let text = String::from_utf8(text).unwrap();
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(
+            lines[0],
+            "Visits over 1 games: 3 of the 765 positions up to rotation and reflection seen"
+        );
+        assert_eq!(lines[4], "ply 1: 1 of 3 positions seen, 1 visits");
+        assert_eq!(lines[6], "    ....X....  1");
+        assert_eq!(lines[7], "  never seen: 2");
+        assert!(text.contains("ply 3: 0 of 38 positions seen, 0 visits"));
+        let mut csv = Vec::new();
+        visitation.write_csv(&mut csv).unwrap();
+        let csv = String::from_utf8(csv).unwrap();
+        assert_eq!(csv.lines().count(), 766);
+        assert!(csv.contains("\n1,....X....,1\n"));
+    }
+}
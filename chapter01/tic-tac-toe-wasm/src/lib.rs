@@ -0,0 +1,86 @@
+use sutton_tic_tac_toe::TicTacToeSession;
+use wasm_bindgen::prelude::*;
+
+// A game against a trained agent for a web page: `TicTacToeSession` exported
+// through wasm-bindgen. From JavaScript,
+//
+//     const game = new Game(xPolicy, oPolicy, true);
+//     game.human_move(4);
+//     const reply = game.agent_move();
+//     const hints = game.cell_values();
+//
+// where the policies are the bytes of the x_policy.bin and o_policy.bin files
+// training saves, as Uint8Arrays. Errors are thrown as strings.
+#[wasm_bindgen]
+pub struct Game {
+    session: TicTacToeSession,
+}
+
+#[wasm_bindgen]
+impl Game {
+    // A game against the saved X and O policies, with the human playing X if
+    // `human_plays_x` and O otherwise.
+    #[wasm_bindgen(constructor)]
+    pub fn new(x_policy: &[u8], o_policy: &[u8], human_plays_x: bool) -> Result<Game, String> {
+        let session = TicTacToeSession::from_policy_bytes(x_policy, o_policy, human_plays_x)?;
+        Ok(Game { session })
+    }
+
+    // Clears the board for another game.
+    pub fn new_game(&mut self) {
+        self.session.new_game();
+    }
+
+    // Plays the human's move at `index`, 0 to 8 in row-major order.
+    pub fn human_move(&mut self, index: usize) -> Result<(), String> {
+        self.session.human_move(index)
+    }
+
+    // Plays the agent's move and returns its cell.
+    pub fn agent_move(&mut self) -> Result<usize, String> {
+        self.session.agent_move()
+    }
+
+    // The agent's value of moving to each cell for whoever is to move, NaN for
+    // taken cells and once the game is over.
+    pub fn cell_values(&self) -> Vec<f32> {
+        self.session.cell_values()
+    }
+
+    // The board as nine characters in row-major order, X, O or . for empty.
+    pub fn cells(&self) -> String {
+        self.session.cells()
+    }
+
+    // "human-to-move", "agent-to-move", "human-won", "agent-won" or "draw".
+    pub fn status(&self) -> String {
+        self.session.status()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sutton_tic_tac_toe::{RLAgent, RLConfig};
+
+    #[test]
+    fn games_play_from_the_saved_policies() {
+        let path = std::env::temp_dir().join("wasm_game_policy.bin");
+        RLAgent::new(RLConfig::default(), Some(1))
+            .save(&path)
+            .unwrap();
+        let bytes = std::fs::read(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let mut game = Game::new(&bytes, &bytes, true).unwrap();
+        assert_eq!(game.status(), "human-to-move");
+        game.human_move(4).unwrap();
+        let reply = game.agent_move().unwrap();
+        assert_ne!(reply, 4);
+        assert_eq!(game.cell_values().len(), 9);
+        assert_eq!(game.cells().matches('.').count(), 7);
+        game.new_game();
+        assert_eq!(game.cells(), ".........");
+        assert!(Game::new(b"not a policy", &bytes, true).is_err());
+    }
+}
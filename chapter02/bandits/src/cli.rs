@@ -0,0 +1,212 @@
+use std::path::PathBuf;
+
+use sutton_bandits::{AgentConfig, Family, TestbedConfig};
+
+pub const USAGE: &str = "Usage: sutton-bandits <command> [options]
+
+Commands:
+  testbed      Run agents on the 10-armed testbed and write each step's average
+               reward and fraction of optimal actions as CSV (Figures 2.2-2.5)
+  nonstationary
+               testbed on random-walking bandits, comparing sample-average and
+               constant step-size estimates (Exercise 2.5)
+  sweep        Run each algorithm across a power-of-two grid of its key
+               parameter and write the reward it averaged over all steps as
+               CSV (the parameter study of Figure 2.6)
+
+Options:
+  --agents <list>  Comma-separated agents (testbed: greedy,epsilon:0.1,
+                   epsilon:0.01; nonstationary: epsilon:0.1,epsilon:0.1:0.1)
+  --algorithms <list>
+                   sweep: comma-separated algorithms (default: all of
+                   epsilon-greedy, gradient, ucb and optimistic)
+  --runs <n>       Bandit problems each agent is run on (default: 2000)
+  --steps <n>      Steps per run (testbed: 1000, nonstationary: 10000)
+  --arms <n>       Arms per bandit (default: 10)
+  --true-mean <x>  Mean the arms' true values are drawn around (default: 0;
+                   Figure 2.5 uses 4)
+  --random-walk <std>
+                   Make the bandits nonstationary: true values start equal and
+                   take a random-walk step of this standard deviation after
+                   every pull (testbed: off, nonstationary: 0.01)
+  --out <path>     Write the CSV to <path> and print a summary instead of
+                   writing the CSV to stdout
+  --plot <path>    Also draw the curves (testbed) or the rewards across each
+                   grid (sweep) as an SVG image at <path>
+  --jsonl <path>   Also write each step (testbed) or grid point (sweep) to
+                   <path> as a line of JSON with its settings and seed
+  --seed <n>       Seed every random number generator, so runs can be
+                   repeated exactly (default: seeded from the OS)
+  --config <path>  Read options from an experiment file of `option = value`
+                   lines, a subset of TOML; options given here override it,
+                   and its `command` is used when none is given here
+
+  -h, --help       Show this message
+
+Agents: greedy, epsilon:<e> (sample averages), epsilon:<e>:<alpha> (constant
+step size alpha), optimistic:<q0> (greedy from estimates starting
+at q0, step size 0.1), ucb:<c> (upper confidence bound), gradient:<alpha>
+and gradient-no-baseline:<alpha> (gradient bandit with and without the
+average-reward baseline)";
+
+#[derive(Debug)]
+pub enum Command {
+    Testbed {
+        agents: Vec<AgentConfig>,
+        config: TestbedConfig,
+        out: Option<PathBuf>,
+    },
+    Sweep {
+        families: Vec<Family>,
+        config: TestbedConfig,
+        out: Option<PathBuf>,
+    },
+    Help,
+}
+
+#[derive(Debug)]
+pub struct Cli {
+    pub command: Command,
+    pub seed: Option<u64>,
+    pub jsonl: Option<PathBuf>,
+    pub plot: Option<PathBuf>,
+}
+
+fn parse_number<T: std::str::FromStr>(flag: &str, value: &str) -> Result<T, String> {
+    value
+        .parse()
+        .map_err(|_| format!("invalid value '{}' for {}", value, flag))
+}
+
+// --plot's path. Charts are only drawn as SVG, and only by builds with the
+// plot feature.
+fn parse_plot_path(value: &str) -> Result<PathBuf, String> {
+    if cfg!(not(feature = "plot")) {
+        return Err("--plot needs a build with the plot feature".to_string());
+    }
+    if !value.ends_with(".svg") {
+        return Err(format!("--plot draws SVG, so '{}' must end in .svg", value));
+    }
+    Ok(PathBuf::from(value))
+}
+
+impl Cli {
+    // Parses the arguments after the program name. Errors are messages for the
+    // user, to be printed above `USAGE`.
+    pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Cli, String> {
+        let mut args = args.into_iter();
+        let name = match args.next() {
+            Some(name) if name == "-h" || name == "--help" => {
+                return Ok(Cli {
+                    command: Command::Help,
+                    seed: None,
+                    jsonl: None,
+                    plot: None,
+                })
+            }
+            Some(name) => name,
+            None => return Err("missing command".to_string()),
+        };
+        let mut agents = None;
+        let mut families = None;
+        let mut config = TestbedConfig::default();
+        let mut steps = None;
+        let mut random_walk = None;
+        let mut out = None;
+        let mut seed = None;
+        let mut jsonl = None;
+        let mut plot = None;
+        while let Some(flag) = args.next() {
+            let mut value = || args.next().ok_or(format!("{} needs a value", flag));
+            match flag.as_str() {
+                "--agents" => {
+                    agents = Some(
+                        value()?
+                            .split(',')
+                            .map(str::parse)
+                            .collect::<Result<Vec<AgentConfig>, String>>()?,
+                    )
+                }
+                "--algorithms" => {
+                    families = Some(
+                        value()?
+                            .split(',')
+                            .map(str::parse)
+                            .collect::<Result<Vec<Family>, String>>()?,
+                    )
+                }
+                "--runs" => config.runs = parse_number(&flag, &value()?)?,
+                "--steps" => steps = Some(parse_number(&flag, &value()?)?),
+                "--arms" => match parse_number(&flag, &value()?)? {
+                    0 => return Err("--arms must be at least 1".to_string()),
+                    arms => config.arms = arms,
+                },
+                "--true-mean" => config.true_mean = parse_number(&flag, &value()?)?,
+                "--random-walk" => match parse_number(&flag, &value()?)? {
+                    std if std > 0.0 => random_walk = Some(std),
+                    _ => return Err("--random-walk must be positive".to_string()),
+                },
+                "--out" => out = Some(PathBuf::from(value()?)),
+                "--jsonl" => jsonl = Some(PathBuf::from(value()?)),
+                "--plot" => plot = Some(parse_plot_path(&value()?)?),
+                "--seed" => seed = Some(parse_number(&flag, &value()?)?),
+                "-h" | "--help" => {
+                    return Ok(Cli {
+                        command: Command::Help,
+                        seed,
+                        jsonl,
+                        plot,
+                    })
+                }
+                _ => return Err(format!("unknown option '{}'", flag)),
+            }
+        }
+        let command = match name.as_str() {
+            "testbed" | "nonstationary" if families.is_some() => {
+                return Err(format!("{} does not take --algorithms", name))
+            }
+            "sweep" if agents.is_some() => {
+                return Err("sweep does not take --agents; use --algorithms".to_string())
+            }
+            "sweep" => Command::Sweep {
+                families: families.unwrap_or_else(|| Family::all().to_vec()),
+                config: TestbedConfig {
+                    steps: steps.unwrap_or(1000),
+                    random_walk,
+                    ..config
+                },
+                out,
+            },
+            "testbed" | "nonstationary" => {
+                let nonstationary = name == "nonstationary";
+                let default_agents: &[&str] = if nonstationary {
+                    &["epsilon:0.1", "epsilon:0.1:0.1"]
+                } else {
+                    &["greedy", "epsilon:0.1", "epsilon:0.01"]
+                };
+                Command::Testbed {
+                    agents: match agents {
+                        Some(agents) => agents,
+                        None => default_agents
+                            .iter()
+                            .map(|name| name.parse())
+                            .collect::<Result<_, _>>()?,
+                    },
+                    config: TestbedConfig {
+                        steps: steps.unwrap_or(if nonstationary { 10000 } else { 1000 }),
+                        random_walk: random_walk.or(nonstationary.then_some(0.01)),
+                        ..config
+                    },
+                    out,
+                }
+            }
+            _ => return Err(format!("unknown command '{}'", name)),
+        };
+        Ok(Cli {
+            command,
+            seed,
+            jsonl,
+            plot,
+        })
+    }
+}
@@ -0,0 +1,738 @@
+use std::fmt;
+use std::io::{self, Write};
+use std::str::FromStr;
+
+use rand::rngs::SmallRng;
+use rand::{Rng, RngCore};
+use rl_core::{derive_seed, sample_normal, seeded_rng, Environment, Policy, Step};
+
+// A k-armed bandit from the Section 2.3 testbed: each arm's true value q*(a)
+// is drawn from N(0, 1) and each pull pays N(q*(a), 1). A nonstationary
+// bandit's true values instead start equal and take an independent
+// N(0, walk_std^2) random-walk step after every pull (Exercise 2.5).
+pub struct BanditEnv {
+    means: Vec<f64>,
+    walk_std: f64,
+    rng: SmallRng,
+}
+
+impl BanditEnv {
+    pub fn new(arms: usize, seed: Option<u64>) -> Self {
+        let mut rng = seeded_rng(seed);
+        let means = (0..arms)
+            .map(|_| sample_normal(&mut rng, 0.0, 1.0))
+            .collect();
+        BanditEnv {
+            means,
+            walk_std: 0.0,
+            rng,
+        }
+    }
+
+    pub fn nonstationary(arms: usize, walk_std: f64, seed: Option<u64>) -> Self {
+        BanditEnv {
+            means: vec![0.0; arms],
+            walk_std,
+            rng: seeded_rng(seed),
+        }
+    }
+
+    // Shifts every true value by `offset`, e.g. 4 for the N(4, 1) testbed of
+    // Figure 2.5, where a reward baseline matters.
+    pub fn with_mean_offset(mut self, offset: f64) -> Self {
+        for mean in &mut self.means {
+            *mean += offset;
+        }
+        self
+    }
+
+    pub fn arms(&self) -> usize {
+        self.means.len()
+    }
+
+    pub fn means(&self) -> &[f64] {
+        &self.means
+    }
+
+    pub fn pull(&mut self, arm: usize) -> f64 {
+        let reward = sample_normal(&mut self.rng, self.means[arm], 1.0);
+        if self.walk_std > 0.0 {
+            for mean in &mut self.means {
+                *mean += sample_normal(&mut self.rng, 0.0, self.walk_std);
+            }
+        }
+        reward
+    }
+
+    // The arm with the highest true value.
+    pub fn optimal_arm(&self) -> usize {
+        argmax(&self.means)
+    }
+}
+
+// A bandit is a continuing task with a single state: every step is a pull,
+// and no episode ever ends.
+impl Environment for BanditEnv {
+    type State = ();
+    type Action = usize;
+
+    fn reset(&mut self) {}
+
+    fn step(&mut self, arm: &usize) -> Step<()> {
+        Step {
+            state: (),
+            reward: self.pull(*arm),
+            done: false,
+        }
+    }
+
+    fn actions(&self, _state: &()) -> Vec<usize> {
+        (0..self.arms()).collect()
+    }
+}
+
+// The index of the largest value, the first on ties.
+pub fn argmax(values: &[f64]) -> usize {
+    values.iter().enumerate().fold(
+        0,
+        |best, (i, &value)| if value > values[best] { i } else { best },
+    )
+}
+
+// An action-selection method learning from the rewards of the arms it pulls.
+pub trait BanditAgent {
+    fn select(&mut self) -> usize;
+
+    fn update(&mut self, arm: usize, reward: f64);
+}
+
+impl<'a> Policy<BanditEnv> for dyn BanditAgent + 'a {
+    fn select_action(&mut self, _env: &BanditEnv, _state: &()) -> usize {
+        self.select()
+    }
+
+    fn observe(&mut self, _state: &(), arm: &usize, step: &Step<()>) {
+        self.update(*arm, step.reward);
+    }
+}
+
+// Epsilon-greedy action selection over action-value estimates, breaking ties
+// between equally valued arms at random. With epsilon 0 it is the greedy
+// method. Estimates are sample averages unless a constant step size is set.
+pub struct EpsilonGreedyAgent {
+    estimates: Vec<f64>,
+    counts: Vec<u32>,
+    epsilon: f64,
+    step_size: Option<f64>,
+    rng: SmallRng,
+}
+
+impl EpsilonGreedyAgent {
+    pub fn new(arms: usize, epsilon: f64, seed: Option<u64>) -> Self {
+        EpsilonGreedyAgent {
+            estimates: vec![0.0; arms],
+            counts: vec![0; arms],
+            epsilon,
+            step_size: None,
+            rng: seeded_rng(seed),
+        }
+    }
+
+    // Starts every estimate at `value`; an optimistic value drives early
+    // exploration even with epsilon 0 (Section 2.6).
+    pub fn with_initial_value(mut self, value: f64) -> Self {
+        self.estimates.fill(value);
+        self
+    }
+
+    pub fn with_step_size(mut self, alpha: f64) -> Self {
+        self.step_size = Some(alpha);
+        self
+    }
+
+    pub fn estimates(&self) -> &[f64] {
+        &self.estimates
+    }
+}
+
+impl BanditAgent for EpsilonGreedyAgent {
+    fn select(&mut self) -> usize {
+        let arms = self.estimates.len();
+        if self.rng.random::<f64>() < self.epsilon {
+            return self.rng.next_u32() as usize % arms;
+        }
+        let best = self.estimates[argmax(&self.estimates)];
+        let tied: Vec<usize> = (0..arms).filter(|&i| self.estimates[i] == best).collect();
+        tied[self.rng.next_u32() as usize % tied.len()]
+    }
+
+    fn update(&mut self, arm: usize, reward: f64) {
+        self.counts[arm] += 1;
+        let alpha = self.step_size.unwrap_or(1.0 / self.counts[arm] as f64);
+        self.estimates[arm] += alpha * (reward - self.estimates[arm]);
+    }
+}
+
+// Upper-confidence-bound action selection (Section 2.7): the arm maximizing
+// Q(a) + c * sqrt(ln t / N(a)), trying every unpulled arm first. Estimates
+// are sample averages.
+pub struct UcbAgent {
+    estimates: Vec<f64>,
+    counts: Vec<u32>,
+    c: f64,
+    step: u32,
+}
+
+impl UcbAgent {
+    pub fn new(arms: usize, c: f64) -> Self {
+        UcbAgent {
+            estimates: vec![0.0; arms],
+            counts: vec![0; arms],
+            c,
+            step: 0,
+        }
+    }
+}
+
+impl BanditAgent for UcbAgent {
+    fn select(&mut self) -> usize {
+        self.step += 1;
+        if let Some(unpulled) = self.counts.iter().position(|&count| count == 0) {
+            return unpulled;
+        }
+        let log_step = (self.step as f64).ln();
+        let bounds: Vec<f64> = self
+            .estimates
+            .iter()
+            .zip(&self.counts)
+            .map(|(estimate, &count)| estimate + self.c * (log_step / count as f64).sqrt())
+            .collect();
+        argmax(&bounds)
+    }
+
+    fn update(&mut self, arm: usize, reward: f64) {
+        self.counts[arm] += 1;
+        self.estimates[arm] += (reward - self.estimates[arm]) / self.counts[arm] as f64;
+    }
+}
+
+// The gradient bandit algorithm (Section 2.8): a softmax over action
+// preferences, each moved by alpha * (R - baseline) toward the pulled arm and
+// away from the others. The baseline is the average of all rewards so far,
+// or 0 without one.
+pub struct GradientAgent {
+    preferences: Vec<f64>,
+    alpha: f64,
+    baseline: bool,
+    average_reward: f64,
+    step: u32,
+    probabilities: Vec<f64>,
+    rng: SmallRng,
+}
+
+impl GradientAgent {
+    pub fn new(arms: usize, alpha: f64, baseline: bool, seed: Option<u64>) -> Self {
+        GradientAgent {
+            preferences: vec![0.0; arms],
+            alpha,
+            baseline,
+            average_reward: 0.0,
+            step: 0,
+            probabilities: vec![1.0 / arms as f64; arms],
+            rng: seeded_rng(seed),
+        }
+    }
+
+    pub fn probabilities(&self) -> &[f64] {
+        &self.probabilities
+    }
+
+    fn update_probabilities(&mut self) {
+        // Subtracting the largest preference keeps exp() from overflowing.
+        let max = self.preferences[argmax(&self.preferences)];
+        let exps: Vec<f64> = self.preferences.iter().map(|h| (h - max).exp()).collect();
+        let total: f64 = exps.iter().sum();
+        self.probabilities = exps.iter().map(|e| e / total).collect();
+    }
+}
+
+impl BanditAgent for GradientAgent {
+    fn select(&mut self) -> usize {
+        let mut remaining = self.rng.random::<f64>();
+        for (arm, probability) in self.probabilities.iter().enumerate() {
+            if remaining < *probability {
+                return arm;
+            }
+            remaining -= probability;
+        }
+        self.probabilities.len() - 1
+    }
+
+    fn update(&mut self, arm: usize, reward: f64) {
+        self.step += 1;
+        let baseline = if self.baseline {
+            self.average_reward += (reward - self.average_reward) / self.step as f64;
+            self.average_reward
+        } else {
+            0.0
+        };
+        for (i, preference) in self.preferences.iter_mut().enumerate() {
+            let indicator = if i == arm { 1.0 } else { 0.0 };
+            *preference += self.alpha * (reward - baseline) * (indicator - self.probabilities[i]);
+        }
+        self.update_probabilities();
+    }
+}
+
+// Which agent to run on the testbed. Written `greedy`, `epsilon:<e>` with
+// sample-average estimates, `epsilon:<e>:<alpha>` with constant step size
+// alpha, `optimistic:<q0>` for greedy selection from estimates starting at q0 with
+// step size 0.1 (Figure 2.3), `ucb:<c>` (Figure 2.4), or `gradient:<alpha>`
+// and `gradient-no-baseline:<alpha>` (Figure 2.5).
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AgentConfig {
+    EpsilonGreedy {
+        epsilon: f64,
+        step_size: Option<f64>,
+    },
+    Optimistic {
+        initial_value: f64,
+    },
+    Ucb {
+        c: f64,
+    },
+    Gradient {
+        alpha: f64,
+        baseline: bool,
+    },
+}
+
+impl AgentConfig {
+    pub fn build(&self, arms: usize, seed: Option<u64>) -> Box<dyn BanditAgent> {
+        match *self {
+            AgentConfig::EpsilonGreedy { epsilon, step_size } => {
+                let agent = EpsilonGreedyAgent::new(arms, epsilon, seed);
+                match step_size {
+                    Some(alpha) => Box::new(agent.with_step_size(alpha)),
+                    None => Box::new(agent),
+                }
+            }
+            AgentConfig::Optimistic { initial_value } => Box::new(
+                EpsilonGreedyAgent::new(arms, 0.0, seed)
+                    .with_initial_value(initial_value)
+                    .with_step_size(0.1),
+            ),
+            AgentConfig::Ucb { c } => Box::new(UcbAgent::new(arms, c)),
+            AgentConfig::Gradient { alpha, baseline } => {
+                Box::new(GradientAgent::new(arms, alpha, baseline, seed))
+            }
+        }
+    }
+}
+
+impl fmt::Display for AgentConfig {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AgentConfig::EpsilonGreedy {
+                epsilon,
+                step_size: None,
+            } if *epsilon == 0.0 => write!(f, "greedy"),
+            AgentConfig::EpsilonGreedy {
+                epsilon,
+                step_size: None,
+            } => write!(f, "epsilon:{}", epsilon),
+            AgentConfig::EpsilonGreedy {
+                epsilon,
+                step_size: Some(alpha),
+            } => write!(f, "epsilon:{}:{}", epsilon, alpha),
+            AgentConfig::Optimistic { initial_value } => write!(f, "optimistic:{}", initial_value),
+            AgentConfig::Ucb { c } => write!(f, "ucb:{}", c),
+            AgentConfig::Gradient {
+                alpha,
+                baseline: true,
+            } => write!(f, "gradient:{}", alpha),
+            AgentConfig::Gradient {
+                alpha,
+                baseline: false,
+            } => write!(f, "gradient-no-baseline:{}", alpha),
+        }
+    }
+}
+
+impl FromStr for AgentConfig {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parse = |value: &str| {
+            value
+                .parse::<f64>()
+                .map_err(|_| format!("invalid value '{}' in agent '{}'", value, s))
+        };
+        match s.split_once(':') {
+            None if s == "greedy" => Ok(AgentConfig::EpsilonGreedy {
+                epsilon: 0.0,
+                step_size: None,
+            }),
+            Some(("epsilon", value)) => {
+                let (epsilon, step_size) = match value.split_once(':') {
+                    Some((epsilon, alpha)) => (parse(epsilon)?, Some(parse(alpha)?)),
+                    None => (parse(value)?, None),
+                };
+                if !(0.0..=1.0).contains(&epsilon) {
+                    return Err(format!("epsilon must be between 0 and 1 in '{}'", s));
+                }
+                Ok(AgentConfig::EpsilonGreedy { epsilon, step_size })
+            }
+            Some(("optimistic", value)) => Ok(AgentConfig::Optimistic {
+                initial_value: parse(value)?,
+            }),
+            Some(("ucb", value)) => Ok(AgentConfig::Ucb { c: parse(value)? }),
+            Some(("gradient", value)) => Ok(AgentConfig::Gradient {
+                alpha: parse(value)?,
+                baseline: true,
+            }),
+            Some(("gradient-no-baseline", value)) => Ok(AgentConfig::Gradient {
+                alpha: parse(value)?,
+                baseline: false,
+            }),
+            _ => Err(format!("unknown agent '{}'", s)),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct TestbedConfig {
+    pub arms: usize,
+    pub runs: u32,
+    pub steps: usize,
+    // Mean of the distribution the arms' true values are drawn from.
+    pub true_mean: f64,
+    // When set, the bandits are nonstationary: their true values start equal
+    // and random-walk with this standard deviation.
+    pub random_walk: Option<f64>,
+}
+
+impl Default for TestbedConfig {
+    fn default() -> Self {
+        TestbedConfig {
+            arms: 10,
+            runs: 2000,
+            steps: 1000,
+            true_mean: 0.0,
+            random_walk: None,
+        }
+    }
+}
+
+// Per-step averages over every run of one agent: the reward received and the
+// fraction of runs that pulled the optimal arm.
+#[derive(Clone, Debug)]
+pub struct TestbedResult {
+    pub agent: AgentConfig,
+    pub average_reward: Vec<f64>,
+    pub optimal_action: Vec<f64>,
+}
+
+// Runs `agent` on `config.runs` freshly drawn bandit problems, each for
+// `config.steps` steps. Each run's bandit and agent are seeded from `seed`
+// and the run's index, so with a seed every agent faces the same problems.
+pub fn run_testbed(
+    config: &TestbedConfig,
+    agent: &AgentConfig,
+    seed: Option<u64>,
+) -> TestbedResult {
+    let mut reward_sums = vec![0.0; config.steps];
+    let mut optimal_counts = vec![0u32; config.steps];
+    for run in 0..config.runs as u64 {
+        let env_seed = derive_seed(seed, 2 * run);
+        let mut env = match config.random_walk {
+            Some(walk_std) => BanditEnv::nonstationary(config.arms, walk_std, env_seed),
+            None => BanditEnv::new(config.arms, env_seed),
+        }
+        .with_mean_offset(config.true_mean);
+        let mut learner = agent.build(config.arms, derive_seed(seed, 2 * run + 1));
+        for step in 0..config.steps {
+            // The optimal arm moves as a nonstationary bandit's values walk.
+            let optimal = env.optimal_arm();
+            let arm = learner.select_action(&env, &());
+            let transition = env.step(&arm);
+            learner.observe(&(), &arm, &transition);
+            reward_sums[step] += transition.reward;
+            if arm == optimal {
+                optimal_counts[step] += 1;
+            }
+        }
+    }
+    let runs = config.runs.max(1) as f64;
+    TestbedResult {
+        agent: *agent,
+        average_reward: reward_sums.iter().map(|sum| sum / runs).collect(),
+        optimal_action: optimal_counts
+            .iter()
+            .map(|&count| count as f64 / runs)
+            .collect(),
+    }
+}
+
+// Writes one row per step with each agent's average reward and fraction of
+// optimal actions, for plotting Figures 2.2 to 2.5.
+pub fn write_testbed_csv(results: &[TestbedResult], writer: &mut dyn Write) -> io::Result<()> {
+    let mut header = vec!["step".to_string()];
+    for result in results {
+        header.push(format!("{} reward", result.agent));
+        header.push(format!("{} optimal", result.agent));
+    }
+    writeln!(writer, "{}", header.join(","))?;
+    let steps = results
+        .iter()
+        .map(|r| r.average_reward.len())
+        .max()
+        .unwrap_or(0);
+    for step in 0..steps {
+        let mut row = vec![(step + 1).to_string()];
+        for result in results {
+            row.push(format!("{:.4}", result.average_reward[step]));
+            row.push(format!("{:.4}", result.optimal_action[step]));
+        }
+        writeln!(writer, "{}", row.join(","))?;
+    }
+    writer.flush()
+}
+
+// An algorithm whose key hyperparameter the parameter study (Figure 2.6)
+// varies: epsilon, the gradient bandit's alpha, UCB's c, or the optimistic
+// greedy method's initial value Q0.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Family {
+    EpsilonGreedy,
+    Gradient,
+    Ucb,
+    Optimistic,
+}
+
+impl Family {
+    pub fn all() -> [Family; 4] {
+        [
+            Family::EpsilonGreedy,
+            Family::Gradient,
+            Family::Ucb,
+            Family::Optimistic,
+        ]
+    }
+
+    pub fn parameter_name(self) -> &'static str {
+        match self {
+            Family::EpsilonGreedy => "epsilon",
+            Family::Gradient => "alpha",
+            Family::Ucb => "c",
+            Family::Optimistic => "Q0",
+        }
+    }
+
+    // The powers of two Figure 2.6 plots the family over, as exponents.
+    pub fn default_exponents(self) -> (i32, i32) {
+        match self {
+            Family::EpsilonGreedy => (-7, -2),
+            Family::Gradient => (-5, 2),
+            Family::Ucb => (-4, 2),
+            Family::Optimistic => (-2, 2),
+        }
+    }
+
+    pub fn agent(self, parameter: f64) -> AgentConfig {
+        match self {
+            Family::EpsilonGreedy => AgentConfig::EpsilonGreedy {
+                epsilon: parameter,
+                step_size: None,
+            },
+            Family::Gradient => AgentConfig::Gradient {
+                alpha: parameter,
+                baseline: true,
+            },
+            Family::Ucb => AgentConfig::Ucb { c: parameter },
+            Family::Optimistic => AgentConfig::Optimistic {
+                initial_value: parameter,
+            },
+        }
+    }
+}
+
+impl fmt::Display for Family {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Family::EpsilonGreedy => write!(f, "epsilon-greedy"),
+            Family::Gradient => write!(f, "gradient"),
+            Family::Ucb => write!(f, "ucb"),
+            Family::Optimistic => write!(f, "optimistic"),
+        }
+    }
+}
+
+impl FromStr for Family {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Family::all()
+            .into_iter()
+            .find(|family| family.to_string() == s)
+            .ok_or_else(|| format!("unknown algorithm '{}'", s))
+    }
+}
+
+// 2^low, 2^(low + 1), ..., 2^high: a log-spaced grid of parameter values.
+pub fn power_of_two_grid(low: i32, high: i32) -> Vec<f64> {
+    (low..=high).map(|exponent| 2f64.powi(exponent)).collect()
+}
+
+// One point of a parameter study: the reward an algorithm averaged over every
+// step of every run with its parameter set to `parameter`.
+#[derive(Clone, Copy, Debug)]
+pub struct SweepPoint {
+    pub family: Family,
+    pub parameter: f64,
+    pub average_reward: f64,
+}
+
+// Runs every family across its default grid on the testbed and averages each
+// setting's reward over all `config.steps` steps of all runs.
+pub fn run_sweep(
+    config: &TestbedConfig,
+    families: &[Family],
+    seed: Option<u64>,
+) -> Vec<SweepPoint> {
+    let mut points = Vec::new();
+    for &family in families {
+        let (low, high) = family.default_exponents();
+        for parameter in power_of_two_grid(low, high) {
+            let result = run_testbed(config, &family.agent(parameter), seed);
+            points.push(SweepPoint {
+                family,
+                parameter,
+                average_reward: result.average_reward.iter().sum::<f64>()
+                    / result.average_reward.len().max(1) as f64,
+            });
+        }
+    }
+    points
+}
+
+// Writes one row per sweep point, for plotting Figure 2.6.
+pub fn write_sweep_csv(points: &[SweepPoint], writer: &mut dyn Write) -> io::Result<()> {
+    writeln!(
+        writer,
+        "algorithm,parameter_name,parameter,log2_parameter,average_reward"
+    )?;
+    for point in points {
+        writeln!(
+            writer,
+            "{},{},{},{},{:.4}",
+            point.family,
+            point.family.parameter_name(),
+            point.parameter,
+            point.parameter.log2(),
+            point.average_reward
+        )?;
+    }
+    writer.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sample_averages_track_the_rewards() {
+        let mut agent = EpsilonGreedyAgent::new(3, 0.0, Some(1));
+        for reward in [1.0, 2.0, 6.0] {
+            agent.update(1, reward);
+        }
+        assert_eq!(agent.estimates(), &[0.0, 3.0, 0.0]);
+        assert_eq!(agent.select(), 1);
+    }
+
+    #[test]
+    fn agents_parse_and_print() {
+        for name in [
+            "greedy",
+            "epsilon:0.1",
+            "epsilon:0.1:0.1",
+            "optimistic:5",
+            "ucb:2",
+            "gradient:0.1",
+            "gradient-no-baseline:0.4",
+        ] {
+            assert_eq!(name.parse::<AgentConfig>().unwrap().to_string(), name);
+        }
+        assert!("epsilon:2".parse::<AgentConfig>().is_err());
+        assert!("softmax:2".parse::<AgentConfig>().is_err());
+    }
+
+    #[test]
+    fn ucb_tries_every_arm_before_using_the_bound() {
+        let mut agent = UcbAgent::new(3, 2.0);
+        for expected in 0..3 {
+            let arm = agent.select();
+            assert_eq!(arm, expected);
+            agent.update(arm, if arm == 2 { 1.0 } else { 0.0 });
+        }
+        assert_eq!(agent.select(), 2);
+    }
+
+    #[test]
+    fn gradient_preferences_follow_rewards_above_the_baseline() {
+        let mut agent = GradientAgent::new(2, 0.5, true, Some(1));
+        agent.update(0, 0.0);
+        for _ in 0..20 {
+            agent.update(1, 1.0);
+            agent.update(0, 0.0);
+        }
+        let probabilities = agent.probabilities();
+        assert!(probabilities[1] > 0.9, "{:?}", probabilities);
+        assert!((probabilities.iter().sum::<f64>() - 1.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn constant_step_sizes_track_a_random_walk_better_than_sample_averages() {
+        let config = TestbedConfig {
+            runs: 100,
+            steps: 5000,
+            random_walk: Some(0.01),
+            ..TestbedConfig::default()
+        };
+        let late = |result: &TestbedResult| result.average_reward[4000..].iter().sum::<f64>();
+        let averaging = run_testbed(&config, &"epsilon:0.1".parse().unwrap(), Some(1));
+        let constant = run_testbed(&config, &"epsilon:0.1:0.1".parse().unwrap(), Some(1));
+        assert!(late(&constant) > late(&averaging));
+    }
+
+    #[test]
+    fn sweeps_cover_each_family_grid() {
+        assert_eq!(power_of_two_grid(-2, 1), vec![0.25, 0.5, 1.0, 2.0]);
+        let config = TestbedConfig {
+            runs: 2,
+            steps: 10,
+            ..TestbedConfig::default()
+        };
+        let points = run_sweep(&config, &[Family::Ucb, Family::Optimistic], Some(1));
+        assert_eq!(points.len(), 7 + 5);
+        assert_eq!(points[0].parameter, 1.0 / 16.0);
+        assert_eq!(points[7].family, Family::Optimistic);
+        assert_eq!(
+            "epsilon-greedy".parse::<Family>(),
+            Ok(Family::EpsilonGreedy)
+        );
+    }
+
+    #[test]
+    fn exploring_finds_the_optimal_arm_more_often_than_greedy() {
+        let config = TestbedConfig {
+            runs: 200,
+            ..TestbedConfig::default()
+        };
+        let late =
+            |result: &TestbedResult| result.optimal_action[900..].iter().sum::<f64>() / 100.0;
+        let greedy = run_testbed(&config, &"greedy".parse().unwrap(), Some(1));
+        let epsilon = run_testbed(&config, &"epsilon:0.1".parse().unwrap(), Some(1));
+        assert!(late(&epsilon) > late(&greedy) + 0.2);
+    }
+}
@@ -0,0 +1,221 @@
+mod cli;
+#[cfg(feature = "plot")]
+mod plot;
+
+use std::fs::File;
+use std::io::{self, BufWriter};
+use std::path::Path;
+use std::process;
+
+use cli::{Cli, Command, USAGE};
+use rl_core::{expand_config, json, write_jsonl, JsonLines};
+use sutton_bandits::*;
+
+// The settings every record shares.
+fn config_json(config: &TestbedConfig) -> rl_core::Value {
+    json!({
+        "arms": config.arms,
+        "runs": config.runs,
+        "steps": config.steps,
+        "true_mean": config.true_mean,
+        "random_walk": config.random_walk,
+    })
+}
+
+// Runs every agent on the testbed and writes the learning curves to `out`,
+// or to stdout when it is `None`, each step to `jsonl` and the curves' chart
+// to `plot`.
+#[cfg_attr(not(feature = "plot"), allow(unused_variables))]
+fn testbed(
+    agents: &[AgentConfig],
+    config: &TestbedConfig,
+    out: Option<&Path>,
+    jsonl: Option<&Path>,
+    plot: Option<&Path>,
+    seed: Option<u64>,
+) {
+    let results: Vec<TestbedResult> = agents
+        .iter()
+        .map(|agent| run_testbed(config, agent, seed))
+        .collect();
+    write_output(out, |writer| write_testbed_csv(&results, writer));
+    write_records(jsonl, "bandits/testbed", seed, |lines| {
+        for result in &results {
+            let mut settings = config_json(config);
+            settings["agent"] = json!(result.agent.to_string());
+            for (step, (reward, optimal)) in result
+                .average_reward
+                .iter()
+                .zip(&result.optimal_action)
+                .enumerate()
+            {
+                lines.record(
+                    "step",
+                    &settings,
+                    json!({
+                        "step": step + 1,
+                        "average_reward": reward,
+                        "optimal_action": optimal,
+                    }),
+                )?;
+            }
+        }
+        Ok(())
+    });
+    #[cfg(feature = "plot")]
+    if let Some(path) = plot {
+        plot::testbed(path, &results);
+    }
+    let Some(path) = out else { return };
+    println!(
+        "{} runs of {} steps on {}{}-armed bandits; results written to {}",
+        config.runs,
+        config.steps,
+        if config.random_walk.is_some() {
+            "nonstationary "
+        } else {
+            ""
+        },
+        config.arms,
+        path.display()
+    );
+    let width = agents
+        .iter()
+        .map(|agent| agent.to_string().len())
+        .max()
+        .unwrap_or(0);
+    for result in &results {
+        let steps = result.average_reward.len().max(1) as f64;
+        println!(
+            "{:<width$}  average reward {:.3}, optimal action on the last step {:.1}%",
+            result.agent.to_string(),
+            result.average_reward.iter().sum::<f64>() / steps,
+            100.0 * result.optimal_action.last().copied().unwrap_or(0.0)
+        );
+    }
+}
+
+// Writes `write` to `out`, or to stdout when it is `None`, exiting on errors.
+fn write_output(out: Option<&Path>, write: impl FnOnce(&mut dyn io::Write) -> io::Result<()>) {
+    let written = match out {
+        Some(path) => File::create(path).and_then(|file| write(&mut BufWriter::new(file))),
+        None => write(&mut io::stdout().lock()),
+    };
+    if let Err(e) = written {
+        eprintln!("Error: couldn't write the results: {}", e);
+        process::exit(1);
+    }
+}
+
+// Writes JSON Lines records to `path` with `write`, if there is one, exiting
+// on errors.
+fn write_records(
+    path: Option<&Path>,
+    experiment: &str,
+    seed: Option<u64>,
+    write: impl FnOnce(&mut JsonLines) -> io::Result<()>,
+) {
+    let Some(path) = path else { return };
+    if let Err(e) = write_jsonl(path, experiment, seed, write) {
+        eprintln!("Error: couldn't write {}: {}", path.display(), e);
+        process::exit(1);
+    }
+}
+
+// Runs the parameter study and writes it to `out`, or to stdout when it is
+// `None`, each grid point to `jsonl` and its chart to `plot`. With `out`,
+// prints each algorithm's rewards across its grid and its best setting.
+#[cfg_attr(not(feature = "plot"), allow(unused_variables))]
+fn sweep(
+    families: &[Family],
+    config: &TestbedConfig,
+    out: Option<&Path>,
+    jsonl: Option<&Path>,
+    plot: Option<&Path>,
+    seed: Option<u64>,
+) {
+    let points = run_sweep(config, families, seed);
+    write_output(out, |writer| write_sweep_csv(&points, writer));
+    write_records(jsonl, "bandits/sweep", seed, |lines| {
+        for point in &points {
+            let mut settings = config_json(config);
+            settings["algorithm"] = json!(point.family.to_string());
+            settings[point.family.parameter_name()] = json!(point.parameter);
+            lines.record(
+                "evaluation",
+                &settings,
+                json!({ "average_reward": point.average_reward }),
+            )?;
+        }
+        Ok(())
+    });
+    #[cfg(feature = "plot")]
+    if let Some(path) = plot {
+        plot::sweep(path, families, &points);
+    }
+    let Some(path) = out else { return };
+    println!(
+        "Reward averaged over {} runs of {} steps; results written to {}",
+        config.runs,
+        config.steps,
+        path.display()
+    );
+    for &family in families {
+        let row: Vec<&SweepPoint> = points.iter().filter(|p| p.family == family).collect();
+        println!("\n{} ({}):", family, family.parameter_name());
+        for point in &row {
+            println!(
+                "  2^{:<3} {:.3}",
+                point.parameter.log2().round(),
+                point.average_reward
+            );
+        }
+        if let Some(best) = row
+            .iter()
+            .max_by(|a, b| a.average_reward.total_cmp(&b.average_reward))
+        {
+            println!(
+                "  best: {} = {} ({:.3})",
+                family.parameter_name(),
+                best.parameter,
+                best.average_reward
+            );
+        }
+    }
+}
+
+fn main() {
+    let cli = expand_config(std::env::args().skip(1))
+        .and_then(Cli::parse)
+        .unwrap_or_else(|message| {
+            eprintln!("Error: {}\n\n{}", message, USAGE);
+            process::exit(2);
+        });
+    match cli.command {
+        Command::Testbed {
+            agents,
+            config,
+            out,
+        } => testbed(
+            &agents,
+            &config,
+            out.as_deref(),
+            cli.jsonl.as_deref(),
+            cli.plot.as_deref(),
+            cli.seed,
+        ),
+        Command::Sweep {
+            families,
+            config,
+            out,
+        } => sweep(
+            &families,
+            &config,
+            out.as_deref(),
+            cli.jsonl.as_deref(),
+            cli.plot.as_deref(),
+            cli.seed,
+        ),
+        Command::Help => println!("{}", USAGE),
+    }
+}
@@ -0,0 +1,53 @@
+use std::path::Path;
+use std::process;
+
+use rl_core::{write_svg, Chart};
+use sutton_bandits::{Family, SweepPoint, TestbedResult};
+
+// Writes `charts` to `path`, exiting on errors.
+fn write(path: &Path, charts: &[Chart]) {
+    if let Err(e) = write_svg(path, charts) {
+        eprintln!("Error: couldn't write {}: {}", path.display(), e);
+        process::exit(1);
+    }
+}
+
+// Average reward and the percentage of optimal actions at each step, one line
+// per agent, as in Figure 2.2.
+pub fn testbed(path: &Path, results: &[TestbedResult]) {
+    let curve = |values: &[f64], scale: f64| {
+        values
+            .iter()
+            .enumerate()
+            .map(move |(step, value)| ((step + 1) as f64, value * scale))
+            .collect::<Vec<_>>()
+    };
+    let mut reward = Chart::new("Average reward", "Steps", "Average reward");
+    let mut optimal =
+        Chart::new("Optimal action", "Steps", "% Optimal action").with_y_range(0.0, 100.0);
+    for result in results {
+        let name = result.agent.to_string();
+        reward = reward.with_series(&name, curve(&result.average_reward, 1.0));
+        optimal = optimal.with_series(&name, curve(&result.optimal_action, 100.0));
+    }
+    write(path, &[reward, optimal]);
+}
+
+// Each algorithm's average reward across its parameter grid, as in Figure 2.6.
+pub fn sweep(path: &Path, families: &[Family], points: &[SweepPoint]) {
+    let mut chart = Chart::new(
+        "Parameter study",
+        "log2 of epsilon, alpha, c or Q0",
+        "Average reward over all steps",
+    );
+    for &family in families {
+        chart = chart.with_series(
+            &family.to_string(),
+            points
+                .iter()
+                .filter(|point| point.family == family)
+                .map(|point| (point.parameter.log2(), point.average_reward)),
+        );
+    }
+    write(path, &[chart]);
+}
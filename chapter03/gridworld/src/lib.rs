@@ -0,0 +1,218 @@
+use rl_core::{Environment, FiniteMdp, Step, Transition};
+
+// Up, down, left, right, as action indices 0 to 3.
+pub const ARROWS: [char; 4] = ['↑', '↓', '←', '→'];
+const MOVES: [(isize, isize); 4] = [(-1, 0), (1, 0), (0, -1), (0, 1)];
+
+// A jump out of a special cell: every action taken in `from` moves to `to`
+// and pays `reward`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Jump {
+    pub from: usize,
+    pub to: usize,
+    pub reward: f64,
+}
+
+// The gridworld of Example 3.5: moves are deterministic, bumping into the
+// edge leaves the agent in place for reward -1, the jump cells send the agent
+// elsewhere whatever it does, and every other move pays 0. Cells are numbered
+// in row-major order. It is a continuing task, so it never reports `done`.
+#[derive(Clone, Debug)]
+pub struct Gridworld {
+    rows: usize,
+    cols: usize,
+    jumps: Vec<Jump>,
+    start: usize,
+    position: usize,
+}
+
+impl Gridworld {
+    pub fn new(rows: usize, cols: usize, jumps: Vec<Jump>) -> Self {
+        Gridworld {
+            rows,
+            cols,
+            jumps,
+            start: 0,
+            position: 0,
+        }
+    }
+
+    // Figure 3.2's 5x5 grid: A at (0, 1) jumps to A' at (4, 1) for +10 and
+    // B at (0, 3) to B' at (2, 3) for +5.
+    pub fn figure_3_2() -> Self {
+        Gridworld::new(
+            5,
+            5,
+            vec![
+                Jump {
+                    from: 1,
+                    to: 21,
+                    reward: 10.0,
+                },
+                Jump {
+                    from: 3,
+                    to: 13,
+                    reward: 5.0,
+                },
+            ],
+        )
+    }
+
+    // The cell `reset` puts the agent in.
+    pub fn with_start(mut self, start: usize) -> Self {
+        self.start = start;
+        self
+    }
+
+    pub fn rows(&self) -> usize {
+        self.rows
+    }
+
+    pub fn cols(&self) -> usize {
+        self.cols
+    }
+
+    // Where `action` in `cell` leads, and its reward.
+    pub fn outcome(&self, cell: usize, action: usize) -> (usize, f64) {
+        if let Some(jump) = self.jumps.iter().find(|jump| jump.from == cell) {
+            return (jump.to, jump.reward);
+        }
+        let (dr, dc) = MOVES[action];
+        let row = (cell / self.cols) as isize + dr;
+        let col = (cell % self.cols) as isize + dc;
+        if row < 0 || row >= self.rows as isize || col < 0 || col >= self.cols as isize {
+            (cell, -1.0)
+        } else {
+            (row as usize * self.cols + col as usize, 0.0)
+        }
+    }
+}
+
+impl FiniteMdp for Gridworld {
+    fn num_states(&self) -> usize {
+        self.rows * self.cols
+    }
+
+    fn num_actions(&self, _state: usize) -> usize {
+        MOVES.len()
+    }
+
+    fn transitions(&self, state: usize, action: usize) -> Vec<Transition> {
+        let (next_state, reward) = self.outcome(state, action);
+        vec![Transition {
+            probability: 1.0,
+            next_state,
+            reward,
+        }]
+    }
+}
+
+impl Environment for Gridworld {
+    type State = usize;
+    type Action = usize;
+
+    fn reset(&mut self) -> usize {
+        self.position = self.start;
+        self.position
+    }
+
+    fn step(&mut self, action: &usize) -> Step<usize> {
+        let (next, reward) = self.outcome(self.position, *action);
+        self.position = next;
+        Step {
+            state: next,
+            reward,
+            done: false,
+        }
+    }
+
+    fn actions(&self, _state: &usize) -> Vec<usize> {
+        (0..MOVES.len()).collect()
+    }
+}
+
+// The equiprobable random policy: each of the four actions with probability
+// 1/4.
+pub fn equiprobable(_state: usize, _action: usize) -> f64 {
+    1.0 / MOVES.len() as f64
+}
+
+// `values` as a grid, one decimal place per cell, as in Figures 3.2 and 3.5.
+pub fn format_values(grid: &Gridworld, values: &[f64]) -> String {
+    values
+        .chunks(grid.cols)
+        .map(|row| {
+            row.iter()
+                .map(|value| format!("{:6.1}", value))
+                .collect::<String>()
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+// Every cell's greedy actions as arrows, ties side by side.
+pub fn format_policy(grid: &Gridworld, greedy: &[Vec<usize>]) -> String {
+    greedy
+        .chunks(grid.cols)
+        .map(|row| {
+            row.iter()
+                .map(|actions| {
+                    let arrows: String = actions.iter().map(|&a| ARROWS[a]).collect();
+                    format!("{:>6}", arrows)
+                })
+                .collect::<String>()
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rl_core::{evaluate_policy, greedy_actions, value_iteration};
+
+    fn assert_close(values: &[f64], cell: usize, expected: f64) {
+        assert!(
+            (values[cell] - expected).abs() < 0.05,
+            "cell {}: {} != {}",
+            cell,
+            values[cell],
+            expected
+        );
+    }
+
+    #[test]
+    fn edges_and_jumps() {
+        let grid = Gridworld::figure_3_2();
+        assert_eq!(grid.outcome(0, 0), (0, -1.0));
+        assert_eq!(grid.outcome(0, 3), (1, 0.0));
+        assert_eq!(grid.outcome(1, 0), (21, 10.0));
+        assert_eq!(grid.outcome(3, 2), (13, 5.0));
+        assert_eq!(grid.outcome(24, 1), (24, -1.0));
+    }
+
+    #[test]
+    fn equiprobable_values_match_figure_3_2() {
+        let grid = Gridworld::figure_3_2();
+        let mut values = vec![0.0; 25];
+        evaluate_policy(&grid, &equiprobable, 0.9, 1e-6, &mut values);
+        assert_close(&values, 0, 3.3);
+        assert_close(&values, 1, 8.8);
+        assert_close(&values, 3, 5.3);
+        assert_close(&values, 24, -2.0);
+    }
+
+    #[test]
+    fn optimal_values_and_policy_match_figure_3_5() {
+        let grid = Gridworld::figure_3_2();
+        let mut values = vec![0.0; 25];
+        value_iteration(&grid, 0.9, 1e-6, &mut values);
+        assert_close(&values, 0, 22.0);
+        assert_close(&values, 1, 24.4);
+        assert_close(&values, 24, 11.7);
+        let greedy = greedy_actions(&grid, &values, 0.9, 1e-4);
+        assert_eq!(greedy[0], vec![3]);
+        assert_eq!(greedy[20], vec![0, 3]);
+        assert_eq!(greedy[1].len(), 4);
+    }
+}
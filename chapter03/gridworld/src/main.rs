@@ -0,0 +1,128 @@
+use std::path::PathBuf;
+use std::process;
+
+use rl_core::{
+    evaluate_policy, expand_config, greedy_actions, json, value_iteration, write_jsonl, FiniteMdp,
+};
+use sutton_gridworld::*;
+
+const USAGE: &str = "Usage: sutton-gridworld [options]
+
+Evaluates the equiprobable random policy on the gridworld of Figure 3.2, then
+solves the Bellman optimality equations for v* and prints the optimal policy
+(Figure 3.5).
+
+Options:
+  --gamma <x>      Discount rate (default: 0.9)
+  --theta <x>      Stop sweeping once no value changes by more than this
+                   (default: 0.000001)
+  --jsonl <path>   Also write both value functions to <path> as lines of
+                   JSON with their settings and sweep counts; values are
+                   listed state by state, row by row
+  --config <path>  Read options from an experiment file of `option = value`
+                   lines, a subset of TOML; options given here override it
+
+  -h, --help       Show this message";
+
+struct Options {
+    gamma: f64,
+    theta: f64,
+    jsonl: Option<PathBuf>,
+}
+
+// Parses the arguments after the program name; `None` means --help.
+fn parse_args(args: impl IntoIterator<Item = String>) -> Result<Option<Options>, String> {
+    let mut options = Options {
+        gamma: 0.9,
+        theta: 1e-6,
+        jsonl: None,
+    };
+    let mut args = args.into_iter();
+    while let Some(flag) = args.next() {
+        if flag == "--jsonl" {
+            let path = args.next().ok_or(format!("{} needs a value", flag))?;
+            options.jsonl = Some(PathBuf::from(path));
+            continue;
+        }
+        let mut value = || -> Result<f64, String> {
+            let value = args.next().ok_or(format!("{} needs a value", flag))?;
+            value
+                .parse()
+                .map_err(|_| format!("invalid value '{}' for {}", value, flag))
+        };
+        match flag.as_str() {
+            "--gamma" => match value()? {
+                gamma if (0.0..1.0).contains(&gamma) => options.gamma = gamma,
+                _ => return Err("--gamma must be at least 0 and below 1".to_string()),
+            },
+            "--theta" => match value()? {
+                theta if theta > 0.0 => options.theta = theta,
+                _ => return Err("--theta must be positive".to_string()),
+            },
+            "-h" | "--help" => return Ok(None),
+            _ => return Err(format!("unknown option '{}'", flag)),
+        }
+    }
+    Ok(Some(options))
+}
+
+fn main() {
+    let options = match expand_config(std::env::args().skip(1)).and_then(parse_args) {
+        Ok(Some(options)) => options,
+        Ok(None) => {
+            println!("{}", USAGE);
+            return;
+        }
+        Err(message) => {
+            eprintln!("Error: {}\n\n{}", message, USAGE);
+            process::exit(2);
+        }
+    };
+    let grid = Gridworld::figure_3_2();
+    let mut values = vec![0.0; grid.num_states()];
+    let sweeps = evaluate_policy(
+        &grid,
+        &equiprobable,
+        options.gamma,
+        options.theta,
+        &mut values,
+    );
+    println!(
+        "State values of the equiprobable random policy ({} sweeps):\n{}",
+        sweeps,
+        format_values(&grid, &values)
+    );
+    let random = (sweeps, values.clone());
+
+    values.fill(0.0);
+    let sweeps = value_iteration(&grid, options.gamma, options.theta, &mut values);
+    println!(
+        "\nOptimal state values v* ({} sweeps of value iteration):\n{}",
+        sweeps,
+        format_values(&grid, &values)
+    );
+    let greedy = greedy_actions(&grid, &values, options.gamma, options.theta * 100.0);
+    println!("\nOptimal policy:\n{}", format_policy(&grid, &greedy));
+
+    let Some(path) = &options.jsonl else { return };
+    let solved = [("equiprobable", random), ("optimal", (sweeps, values))];
+    let written = write_jsonl(path, "gridworld", None, |lines| {
+        for (policy, (sweeps, values)) in &solved {
+            let config = json!({
+                "policy": policy,
+                "gamma": options.gamma,
+                "theta": options.theta,
+            });
+            lines.record(
+                "evaluation",
+                &config,
+                json!({ "sweeps": sweeps, "values": values }),
+            )?;
+        }
+        Ok(())
+    });
+    if let Err(e) = written {
+        eprintln!("Error: couldn't write {}: {}", path.display(), e);
+        process::exit(1);
+    }
+}
@@ -0,0 +1,274 @@
+use rl_core::{FiniteMdp, Transition};
+
+// Cars each location can hold; any more are returned to the company.
+pub const MAX_CARS: usize = 20;
+// Cars that can be moved overnight, in either direction.
+pub const MAX_MOVE: usize = 5;
+
+const COUNTS: usize = MAX_CARS + 1;
+
+// P(X = k) for X ~ Poisson(lambda).
+pub fn poisson(lambda: f64, k: usize) -> f64 {
+    (1..=k).fold((-lambda).exp(), |p, i| p * lambda / i as f64)
+}
+
+// The Poisson(lambda) distribution over 0..=cap, with everything above `cap`
+// folded into `cap`.
+fn capped_poisson(lambda: f64, cap: usize) -> Vec<f64> {
+    let mut probabilities: Vec<f64> = (0..cap).map(|k| poisson(lambda, k)).collect();
+    probabilities.push(1.0 - probabilities.iter().sum::<f64>());
+    probabilities
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct RentalConfig {
+    // Mean rental requests and returns per day at the first and second
+    // locations.
+    pub request_means: [f64; 2],
+    pub return_means: [f64; 2],
+    pub rental_reward: f64,
+    pub move_cost: f64,
+    // Exercise 4.7: an employee moves one car from the first location to the
+    // second for free each night.
+    pub free_shuttle: bool,
+    // Exercise 4.7: keeping more than `.0` cars overnight at a location, after
+    // moving, costs `.1`.
+    pub parking: Option<(usize, f64)>,
+}
+
+impl Default for RentalConfig {
+    fn default() -> Self {
+        RentalConfig {
+            request_means: [3.0, 4.0],
+            return_means: [3.0, 2.0],
+            rental_reward: 10.0,
+            move_cost: 2.0,
+            free_shuttle: false,
+            parking: None,
+        }
+    }
+}
+
+impl RentalConfig {
+    pub fn exercise_4_7() -> Self {
+        RentalConfig {
+            free_shuttle: true,
+            parking: Some((10, 4.0)),
+            ..RentalConfig::default()
+        }
+    }
+}
+
+// Jack's Car Rental (Example 4.2). A state is the number of cars at each
+// location at the end of a day, numbered `first * 21 + second`. Action `a`
+// moves `a - 5` cars from the first location to the second overnight
+// (negative moves go the other way), and only as many as the sending location
+// has.
+pub struct CarRental {
+    config: RentalConfig,
+    // For each location and morning count, the probability of each evening
+    // count and the rentals expected given that evening count.
+    days: [Vec<Vec<(f64, f64)>>; 2],
+}
+
+impl CarRental {
+    pub fn new(config: RentalConfig) -> Self {
+        let day = |location: usize| {
+            (0..COUNTS)
+                .map(|morning| {
+                    day_outcomes(
+                        morning,
+                        config.request_means[location],
+                        config.return_means[location],
+                    )
+                })
+                .collect()
+        };
+        CarRental {
+            config,
+            days: [day(0), day(1)],
+        }
+    }
+
+    pub fn config(&self) -> &RentalConfig {
+        &self.config
+    }
+
+    pub fn state(first: usize, second: usize) -> usize {
+        first * COUNTS + second
+    }
+
+    pub fn cars(state: usize) -> (usize, usize) {
+        (state / COUNTS, state % COUNTS)
+    }
+
+    pub fn action(moved: isize) -> usize {
+        (moved + MAX_MOVE as isize) as usize
+    }
+
+    pub fn moved(action: usize) -> isize {
+        action as isize - MAX_MOVE as isize
+    }
+
+    // What moving `moved` cars costs, including any parking charges for the
+    // counts it leaves at each location.
+    pub fn overnight_cost(&self, first: usize, second: usize, moved: isize) -> f64 {
+        let billed = if self.config.free_shuttle && moved > 0 {
+            moved - 1
+        } else {
+            moved.abs()
+        };
+        let mut cost = self.config.move_cost * billed as f64;
+        if let Some((limit, charge)) = self.config.parking {
+            let (first, second) = after_move(first, second, moved);
+            cost += charge * [first, second].iter().filter(|&&cars| cars > limit).count() as f64;
+        }
+        cost
+    }
+}
+
+// The counts after moving `moved` cars from the first location to the second;
+// cars that don't fit are lost.
+fn after_move(first: usize, second: usize, moved: isize) -> (usize, usize) {
+    (
+        (first as isize - moved).min(MAX_CARS as isize) as usize,
+        (second as isize + moved).min(MAX_CARS as isize) as usize,
+    )
+}
+
+// One location's day starting with `morning` cars: requests beyond the cars
+// available are turned away, and returns beyond MAX_CARS are lost. Returns,
+// for each evening count, its probability and the rentals expected given it.
+fn day_outcomes(morning: usize, request_mean: f64, return_mean: f64) -> Vec<(f64, f64)> {
+    let mut probability = vec![0.0; COUNTS];
+    let mut rentals = vec![0.0; COUNTS];
+    for (rented, p_request) in capped_poisson(request_mean, morning)
+        .into_iter()
+        .enumerate()
+    {
+        let left = morning - rented;
+        for (returned, p_return) in capped_poisson(return_mean, MAX_CARS - left)
+            .into_iter()
+            .enumerate()
+        {
+            let p = p_request * p_return;
+            probability[left + returned] += p;
+            rentals[left + returned] += p * rented as f64;
+        }
+    }
+    probability
+        .into_iter()
+        .zip(rentals)
+        .map(|(p, rented)| (p, if p > 0.0 { rented / p } else { 0.0 }))
+        .collect()
+}
+
+impl FiniteMdp for CarRental {
+    fn num_states(&self) -> usize {
+        COUNTS * COUNTS
+    }
+
+    fn num_actions(&self, _state: usize) -> usize {
+        2 * MAX_MOVE + 1
+    }
+
+    fn is_available(&self, state: usize, action: usize) -> bool {
+        let (first, second) = CarRental::cars(state);
+        let moved = CarRental::moved(action);
+        if moved >= 0 {
+            moved as usize <= first
+        } else {
+            moved.unsigned_abs() <= second
+        }
+    }
+
+    fn transitions(&self, state: usize, action: usize) -> Vec<Transition> {
+        let (first, second) = CarRental::cars(state);
+        let moved = CarRental::moved(action);
+        let cost = self.overnight_cost(first, second, moved);
+        let (first, second) = after_move(first, second, moved);
+        let mut transitions = Vec::with_capacity(COUNTS * COUNTS);
+        for (first_evening, &(p_first, rented_first)) in self.days[0][first].iter().enumerate() {
+            for (second_evening, &(p_second, rented_second)) in
+                self.days[1][second].iter().enumerate()
+            {
+                transitions.push(Transition {
+                    probability: p_first * p_second,
+                    next_state: CarRental::state(first_evening, second_evening),
+                    reward: self.config.rental_reward * (rented_first + rented_second) - cost,
+                });
+            }
+        }
+        transitions
+    }
+}
+
+// Moves the policy makes in each state, as a grid with the first location's
+// count falling from MAX_CARS down the rows and the second's rising from 0
+// across the columns, like the contour plots of Figure 4.2.
+pub fn format_policy(policy: &[usize]) -> String {
+    format_grid(|state| format!("{:3}", CarRental::moved(policy[state])))
+}
+
+pub fn format_values(values: &[f64]) -> String {
+    format_grid(|state| format!("{:5.0}", values[state]))
+}
+
+fn format_grid(cell: impl Fn(usize) -> String) -> String {
+    (0..COUNTS)
+        .rev()
+        .map(|first| {
+            let row: String = (0..COUNTS)
+                .map(|second| cell(CarRental::state(first, second)))
+                .collect();
+            format!("{:2} |{}", first, row)
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn day_outcomes_are_distributions() {
+        for morning in [0, 3, 20] {
+            let outcomes = day_outcomes(morning, 3.0, 2.0);
+            let total: f64 = outcomes.iter().map(|&(p, _)| p).sum();
+            assert!((total - 1.0).abs() < 1e-9, "{}", total);
+            assert!(outcomes.iter().all(|&(_, rented)| rented <= morning as f64));
+        }
+        // With no cars nothing is rented, whatever comes back.
+        assert!(day_outcomes(0, 3.0, 2.0)
+            .iter()
+            .all(|&(_, rented)| rented == 0.0));
+    }
+
+    #[test]
+    fn moves_are_limited_by_the_cars_available() {
+        let rental = CarRental::new(RentalConfig::default());
+        let state = CarRental::state(3, 1);
+        assert!(rental.is_available(state, CarRental::action(3)));
+        assert!(!rental.is_available(state, CarRental::action(4)));
+        assert!(rental.is_available(state, CarRental::action(-1)));
+        assert!(!rental.is_available(state, CarRental::action(-2)));
+        let total: f64 = rental
+            .transitions(state, CarRental::action(2))
+            .iter()
+            .map(|t| t.probability)
+            .sum();
+        assert!((total - 1.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn exercise_4_7_shuttles_one_car_free_and_charges_for_parking() {
+        let plain = CarRental::new(RentalConfig::default());
+        let modified = CarRental::new(RentalConfig::exercise_4_7());
+        assert_eq!(plain.overnight_cost(5, 5, 1), 2.0);
+        assert_eq!(modified.overnight_cost(5, 5, 1), 0.0);
+        assert_eq!(modified.overnight_cost(5, 5, -1), 2.0);
+        assert_eq!(modified.overnight_cost(15, 5, 3), 4.0 + 4.0);
+        assert_eq!(modified.overnight_cost(15, 12, 0), 8.0);
+    }
+}
@@ -0,0 +1,161 @@
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::process;
+
+use rl_core::{expand_config, json, policy_iteration, write_jsonl, FiniteMdp};
+use sutton_car_rental::*;
+
+const USAGE: &str = "Usage: sutton-car-rental [options]
+
+Solves Jack's Car Rental (Example 4.2) by policy iteration from the policy
+that never moves cars, printing every policy it evaluates and the final
+state values (Figure 4.2).
+
+Options:
+  --exercise-4-7   One car moved from the first location to the second each
+                   night is free, and keeping more than 10 cars overnight at a
+                   location costs $4 (Exercise 4.7)
+  --gamma <x>      Discount rate (default: 0.9)
+  --theta <x>      Stop evaluating a policy once no value changes by more than
+                   this (default: 0.01)
+  --out <path>     Also write the final policy and values to <path> as CSV
+  --jsonl <path>   Also write every policy evaluated, with its state values
+                   and the settings, to <path> as lines of JSON; states are
+                   listed as in the CSV
+  --config <path>  Read options from an experiment file of `option = value`
+                   lines, a subset of TOML; options given here override it
+
+  -h, --help       Show this message";
+
+struct Options {
+    config: RentalConfig,
+    gamma: f64,
+    theta: f64,
+    out: Option<PathBuf>,
+    jsonl: Option<PathBuf>,
+}
+
+// Parses the arguments after the program name; `None` means --help.
+fn parse_args(args: impl IntoIterator<Item = String>) -> Result<Option<Options>, String> {
+    let mut options = Options {
+        config: RentalConfig::default(),
+        gamma: 0.9,
+        theta: 0.01,
+        out: None,
+        jsonl: None,
+    };
+    let mut args = args.into_iter();
+    while let Some(flag) = args.next() {
+        let mut value = || args.next().ok_or(format!("{} needs a value", flag));
+        let parse = |value: String| -> Result<f64, String> {
+            value
+                .parse()
+                .map_err(|_| format!("invalid value '{}' for {}", value, flag))
+        };
+        match flag.as_str() {
+            "--exercise-4-7" => options.config = RentalConfig::exercise_4_7(),
+            "--gamma" => match parse(value()?)? {
+                gamma if (0.0..1.0).contains(&gamma) => options.gamma = gamma,
+                _ => return Err("--gamma must be at least 0 and below 1".to_string()),
+            },
+            "--theta" => match parse(value()?)? {
+                theta if theta > 0.0 => options.theta = theta,
+                _ => return Err("--theta must be positive".to_string()),
+            },
+            "--out" => options.out = Some(PathBuf::from(value()?)),
+            "--jsonl" => options.jsonl = Some(PathBuf::from(value()?)),
+            "-h" | "--help" => return Ok(None),
+            _ => return Err(format!("unknown option '{}'", flag)),
+        }
+    }
+    Ok(Some(options))
+}
+
+// One row per state: the car counts, the policy's move and the state's value.
+fn write_csv(path: &Path, policy: &[usize], values: &[f64]) -> io::Result<()> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    writeln!(writer, "first,second,moved,value")?;
+    for (state, (&action, value)) in policy.iter().zip(values).enumerate() {
+        let (first, second) = CarRental::cars(state);
+        writeln!(
+            writer,
+            "{},{},{},{:.3}",
+            first,
+            second,
+            CarRental::moved(action),
+            value
+        )?;
+    }
+    writer.flush()
+}
+
+fn main() {
+    let options = match expand_config(std::env::args().skip(1)).and_then(parse_args) {
+        Ok(Some(options)) => options,
+        Ok(None) => {
+            println!("{}", USAGE);
+            return;
+        }
+        Err(message) => {
+            eprintln!("Error: {}\n\n{}", message, USAGE);
+            process::exit(2);
+        }
+    };
+    let rental = CarRental::new(options.config);
+    let mut policy = vec![CarRental::action(0); rental.num_states()];
+    let mut values = vec![0.0; rental.num_states()];
+    // Each policy evaluated, as cars moved per state, with its values.
+    let mut evaluations = Vec::new();
+    let evaluated = policy_iteration(
+        &rental,
+        options.gamma,
+        options.theta,
+        &mut policy,
+        &mut values,
+        |iteration, policy, values| {
+            if options.jsonl.is_some() {
+                let moved: Vec<isize> = policy.iter().map(|&a| CarRental::moved(a)).collect();
+                evaluations.push((iteration, moved, values.to_vec()));
+            }
+            println!(
+                "Policy {} (cars moved from the first location to the second; rows are cars at the first, columns at the second):\n{}\n",
+                iteration,
+                format_policy(policy)
+            );
+        },
+    );
+    println!(
+        "Policy {} is stable. Its state values:\n{}",
+        evaluated - 1,
+        format_values(&values)
+    );
+    if let Some(path) = &options.out {
+        if let Err(e) = write_csv(path, &policy, &values) {
+            eprintln!("Error: couldn't write {}: {}", path.display(), e);
+            process::exit(1);
+        }
+    }
+    if let Some(path) = &options.jsonl {
+        let config = json!({
+            "free_shuttle": options.config.free_shuttle,
+            "parking": options.config.parking,
+            "gamma": options.gamma,
+            "theta": options.theta,
+        });
+        let written = write_jsonl(path, "car-rental", None, |lines| {
+            for (iteration, moved, values) in &evaluations {
+                lines.record(
+                    "evaluation",
+                    &config,
+                    json!({ "policy": iteration, "moved": moved, "values": values }),
+                )?;
+            }
+            Ok(())
+        });
+        if let Err(e) = written {
+            eprintln!("Error: couldn't write {}: {}", path.display(), e);
+            process::exit(1);
+        }
+    }
+}
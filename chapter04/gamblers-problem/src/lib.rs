@@ -0,0 +1,94 @@
+use rl_core::{FiniteMdp, Transition};
+
+// The gambler's problem (Example 4.3). A state is the gambler's capital, from
+// 0 to `goal`; both ends are terminal. Action `a` stakes a + 1, up to the
+// capital and no more than would reach the goal. The coin comes up heads,
+// winning the stake, with probability `heads`. Reaching the goal pays +1 and
+// every other transition 0, so with no discounting a state's value is the
+// probability of winning from it.
+pub struct Gambler {
+    goal: usize,
+    heads: f64,
+}
+
+impl Gambler {
+    pub fn new(goal: usize, heads: f64) -> Self {
+        Gambler { goal, heads }
+    }
+
+    pub fn goal(&self) -> usize {
+        self.goal
+    }
+
+    pub fn stake(action: usize) -> usize {
+        action + 1
+    }
+}
+
+impl FiniteMdp for Gambler {
+    fn num_states(&self) -> usize {
+        self.goal + 1
+    }
+
+    fn num_actions(&self, capital: usize) -> usize {
+        capital.min(self.goal - capital)
+    }
+
+    fn transitions(&self, capital: usize, action: usize) -> Vec<Transition> {
+        let stake = Gambler::stake(action);
+        let won = capital + stake;
+        vec![
+            Transition {
+                probability: self.heads,
+                next_state: won,
+                reward: if won == self.goal { 1.0 } else { 0.0 },
+            },
+            Transition {
+                probability: 1.0 - self.heads,
+                next_state: capital - stake,
+                reward: 0.0,
+            },
+        ]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rl_core::{greedy_actions, value_iteration};
+
+    fn solve(heads: f64) -> (Gambler, Vec<f64>) {
+        let gambler = Gambler::new(100, heads);
+        let mut values = vec![0.0; 101];
+        value_iteration(&gambler, 1.0, 1e-12, &mut values);
+        (gambler, values)
+    }
+
+    #[test]
+    fn stakes_stay_within_capital_and_goal() {
+        let gambler = Gambler::new(100, 0.4);
+        assert_eq!(gambler.num_actions(0), 0);
+        assert_eq!(gambler.num_actions(30), 30);
+        assert_eq!(gambler.num_actions(70), 30);
+        assert_eq!(gambler.num_actions(100), 0);
+    }
+
+    #[test]
+    fn bold_play_is_optimal_against_an_unfair_coin() {
+        let (gambler, values) = solve(0.25);
+        // Staking everything at 50 wins exactly when the coin comes up heads.
+        assert!((values[50] - 0.25).abs() < 1e-9);
+        assert!((values[75] - (0.25 + 0.75 * 0.25)).abs() < 1e-9);
+        assert!(values.windows(2).take(99).all(|pair| pair[0] <= pair[1]));
+        let greedy = greedy_actions(&gambler, &values, 1.0, 1e-9);
+        assert!(greedy[50].contains(&49));
+    }
+
+    #[test]
+    fn a_favourable_coin_makes_small_stakes_optimal() {
+        let (gambler, values) = solve(0.55);
+        let greedy = greedy_actions(&gambler, &values, 1.0, 1e-9);
+        assert_eq!(greedy[50], vec![0]);
+        assert!(values[1] > 0.0);
+    }
+}
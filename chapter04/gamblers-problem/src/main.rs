@@ -0,0 +1,188 @@
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::process;
+
+use rl_core::{expand_config, greedy_actions, json, value_iteration_sweep, write_jsonl, FiniteMdp};
+use sutton_gamblers_problem::*;
+
+const USAGE: &str = "Usage: sutton-gamblers-problem [options]
+
+Solves the gambler's problem (Example 4.3) by value iteration and writes the
+value function after every sweep and the final policy as CSV (Figure 4.3,
+Exercise 4.9).
+
+Options:
+  --heads <p>      Probability of the coin coming up heads (default: 0.4;
+                   Exercise 4.9 uses 0.25 and 0.55)
+  --goal <n>       Capital the gambler wins at (default: 100)
+  --theta <x>      Stop sweeping once no value changes by more than this
+                   (default: 1e-10)
+  --values-out <path>
+                   Where to write each sweep's values (default: values.csv)
+  --policy-out <path>
+                   Where to write the final policy (default: policy.csv)
+  --jsonl <path>   Also write each sweep's values, from capital 0 to the goal,
+                   to <path> as lines of JSON with the settings
+  --config <path>  Read options from an experiment file of `option = value`
+                   lines, a subset of TOML; options given here override it
+
+  -h, --help       Show this message";
+
+struct Options {
+    heads: f64,
+    goal: usize,
+    theta: f64,
+    values_out: PathBuf,
+    policy_out: PathBuf,
+    jsonl: Option<PathBuf>,
+}
+
+// Parses the arguments after the program name; `None` means --help.
+fn parse_args(args: impl IntoIterator<Item = String>) -> Result<Option<Options>, String> {
+    let mut options = Options {
+        heads: 0.4,
+        goal: 100,
+        theta: 1e-10,
+        values_out: PathBuf::from("values.csv"),
+        policy_out: PathBuf::from("policy.csv"),
+        jsonl: None,
+    };
+    let mut args = args.into_iter();
+    while let Some(flag) = args.next() {
+        let value = args.next();
+        let value = || value.clone().ok_or(format!("{} needs a value", flag));
+        let invalid = |value: &str| format!("invalid value '{}' for {}", value, flag);
+        match flag.as_str() {
+            "--heads" => {
+                let value = value()?;
+                match value.parse() {
+                    Ok(p) if (0.0..=1.0).contains(&p) => options.heads = p,
+                    _ => return Err(invalid(&value)),
+                }
+            }
+            "--goal" => {
+                let value = value()?;
+                match value.parse() {
+                    Ok(goal) if goal >= 2 => options.goal = goal,
+                    _ => return Err(invalid(&value)),
+                }
+            }
+            "--theta" => {
+                let value = value()?;
+                match value.parse() {
+                    Ok(theta) if theta > 0.0 => options.theta = theta,
+                    _ => return Err(invalid(&value)),
+                }
+            }
+            "--values-out" => options.values_out = PathBuf::from(value()?),
+            "--policy-out" => options.policy_out = PathBuf::from(value()?),
+            "--jsonl" => options.jsonl = Some(PathBuf::from(value()?)),
+            "-h" | "--help" => return Ok(None),
+            _ => return Err(format!("unknown option '{}'", flag)),
+        }
+    }
+    Ok(Some(options))
+}
+
+// One row per non-terminal capital, one column per sweep.
+fn write_values(path: &Path, sweeps: &[Vec<f64>]) -> io::Result<()> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    let header: Vec<String> = (1..=sweeps.len())
+        .map(|sweep| format!("sweep_{}", sweep))
+        .collect();
+    writeln!(writer, "capital,{}", header.join(","))?;
+    let goal = sweeps.first().map_or(0, |values| values.len() - 1);
+    for capital in 1..goal {
+        let row: Vec<String> = sweeps
+            .iter()
+            .map(|values| format!("{:.6}", values[capital]))
+            .collect();
+        writeln!(writer, "{},{}", capital, row.join(","))?;
+    }
+    writer.flush()
+}
+
+// One row per non-terminal capital: the smallest optimal stake, which is what
+// Figure 4.3 plots, and every stake within rounding of optimal.
+fn write_policy(path: &Path, greedy: &[Vec<usize>]) -> io::Result<()> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    writeln!(writer, "capital,stake,optimal_stakes")?;
+    for (capital, actions) in greedy.iter().enumerate().take(greedy.len() - 1).skip(1) {
+        let stakes: Vec<String> = actions
+            .iter()
+            .map(|&action| Gambler::stake(action).to_string())
+            .collect();
+        writeln!(writer, "{},{},{}", capital, stakes[0], stakes.join(" "))?;
+    }
+    writer.flush()
+}
+
+// One line per sweep, with the values after it.
+fn write_sweeps_jsonl(path: &Path, options: &Options, sweeps: &[Vec<f64>]) -> io::Result<()> {
+    let config = json!({
+        "heads": options.heads,
+        "goal": options.goal,
+        "theta": options.theta,
+    });
+    write_jsonl(path, "gamblers-problem", None, |lines| {
+        for (sweep, values) in sweeps.iter().enumerate() {
+            lines.record(
+                "sweep",
+                &config,
+                json!({ "sweep": sweep + 1, "values": values }),
+            )?;
+        }
+        Ok(())
+    })
+}
+
+fn main() {
+    let options = match expand_config(std::env::args().skip(1)).and_then(parse_args) {
+        Ok(Some(options)) => options,
+        Ok(None) => {
+            println!("{}", USAGE);
+            return;
+        }
+        Err(message) => {
+            eprintln!("Error: {}\n\n{}", message, USAGE);
+            process::exit(2);
+        }
+    };
+    let gambler = Gambler::new(options.goal, options.heads);
+    let mut values = vec![0.0; gambler.num_states()];
+    let mut sweeps = Vec::new();
+    loop {
+        let delta = value_iteration_sweep(&gambler, 1.0, &mut values);
+        sweeps.push(values.clone());
+        if delta <= options.theta {
+            break;
+        }
+    }
+    // Many stakes tie exactly in theory; allow for rounding when picking them.
+    let greedy = greedy_actions(&gambler, &values, 1.0, 1e-9);
+    let written = write_values(&options.values_out, &sweeps)
+        .and_then(|_| write_policy(&options.policy_out, &greedy))
+        .and_then(|_| match &options.jsonl {
+            Some(path) => write_sweeps_jsonl(path, &options, &sweeps),
+            None => Ok(()),
+        });
+    if let Err(e) = written {
+        eprintln!("Error: couldn't write the results: {}", e);
+        process::exit(1);
+    }
+    let half = options.goal / 2;
+    println!(
+        "p_h = {}: converged after {} sweeps; from {} the gambler wins with probability {:.4} by staking {}",
+        options.heads,
+        sweeps.len(),
+        half,
+        values[half],
+        Gambler::stake(greedy[half][0])
+    );
+    println!(
+        "Values written to {}, policy to {}",
+        options.values_out.display(),
+        options.policy_out.display()
+    );
+}
@@ -0,0 +1,132 @@
+use std::path::PathBuf;
+
+pub const USAGE: &str = "Usage: sutton-blackjack <command> [options]
+
+Commands:
+  predict      Estimate the value of the policy that sticks on 20 or 21 by
+               first-visit Monte Carlo prediction (Figure 5.1)
+  control      Find the optimal policy and its values by Monte Carlo with
+               exploring starts (Figure 5.2)
+  off-policy   Estimate one state's value under sticking on 20 from random
+               play by ordinary and weighted importance sampling, and write
+               each estimate's mean squared error after every episode
+               (Figure 5.3)
+  infinite-variance
+               Estimate the one-state MDP of Example 5.5 by ordinary
+               importance sampling and write each run's estimates at
+               log-spaced episode counts (Figure 5.4)
+
+predict and control write their grids as CSV: a row per usable-ace case and
+player sum, a column per dealer showing card.
+
+Options:
+  --episodes <n>   Episodes to learn from (predict and control: 500000,
+                   off-policy: 10000, infinite-variance: 100000)
+  --runs <n>       Independent runs (off-policy: 100, infinite-variance: 10)
+  --stick-at <n>   predict: the sum the evaluated policy sticks at (default: 20)
+  --out <path>     Write the CSV to <path> and print the grids instead of
+                   writing the CSV to stdout
+  --jsonl <path>   Also write the results to <path> as lines of JSON with
+                   the settings and seed: one per state (predict, control),
+                   per episode (off-policy) or per run and episode count
+                   (infinite-variance)
+  --seed <n>       Seed every random number generator, so runs can be
+                   repeated exactly (default: seeded from the OS)
+  --config <path>  Read options from an experiment file of `option = value`
+                   lines, a subset of TOML; options given here override it,
+                   and its `command` is used when none is given here
+
+  -h, --help       Show this message";
+
+#[derive(Debug, PartialEq)]
+pub enum Command {
+    Predict { stick_at: u8 },
+    Control,
+    OffPolicy { runs: usize },
+    InfiniteVariance { runs: usize },
+    Help,
+}
+
+#[derive(Debug)]
+pub struct Cli {
+    pub command: Command,
+    pub episodes: usize,
+    pub out: Option<PathBuf>,
+    pub jsonl: Option<PathBuf>,
+    pub seed: Option<u64>,
+}
+
+fn parse_number<T: std::str::FromStr>(flag: &str, value: &str) -> Result<T, String> {
+    value
+        .parse()
+        .map_err(|_| format!("invalid value '{}' for {}", value, flag))
+}
+
+impl Cli {
+    // Parses the arguments after the program name. Errors are messages for the
+    // user, to be printed above `USAGE`.
+    pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Cli, String> {
+        let mut args = args.into_iter();
+        let mut cli = Cli {
+            command: Command::Help,
+            episodes: 500_000,
+            out: None,
+            jsonl: None,
+            seed: None,
+        };
+        let name = match args.next() {
+            Some(name) if name == "-h" || name == "--help" => return Ok(cli),
+            Some(name) => name,
+            None => return Err("missing command".to_string()),
+        };
+        let mut episodes = None;
+        let mut runs = None;
+        let mut stick_at = None;
+        while let Some(flag) = args.next() {
+            let mut value = || args.next().ok_or(format!("{} needs a value", flag));
+            match flag.as_str() {
+                "--episodes" => episodes = Some(parse_number(&flag, &value()?)?),
+                "--runs" => match parse_number(&flag, &value()?)? {
+                    0 => return Err("--runs must be at least 1".to_string()),
+                    n => runs = Some(n),
+                },
+                "--stick-at" => match parse_number(&flag, &value()?)? {
+                    sum @ 12..=22 => stick_at = Some(sum),
+                    _ => return Err("--stick-at must be between 12 and 22".to_string()),
+                },
+                "--out" => cli.out = Some(PathBuf::from(value()?)),
+                "--jsonl" => cli.jsonl = Some(PathBuf::from(value()?)),
+                "--seed" => cli.seed = Some(parse_number(&flag, &value()?)?),
+                "-h" | "--help" => return Ok(cli),
+                _ => return Err(format!("unknown option '{}'", flag)),
+            }
+        }
+        cli.command = match name.as_str() {
+            "predict" | "control" if runs.is_some() => {
+                return Err(format!("{} does not take --runs", name))
+            }
+            "control" | "off-policy" | "infinite-variance" if stick_at.is_some() => {
+                return Err(format!("{} does not take --stick-at", name))
+            }
+            "predict" => Command::Predict {
+                stick_at: stick_at.unwrap_or(20),
+            },
+            "control" => Command::Control,
+            "off-policy" => {
+                cli.episodes = 10_000;
+                Command::OffPolicy {
+                    runs: runs.unwrap_or(100),
+                }
+            }
+            "infinite-variance" => {
+                cli.episodes = 100_000;
+                Command::InfiniteVariance {
+                    runs: runs.unwrap_or(10),
+                }
+            }
+            _ => return Err(format!("unknown command '{}'", name)),
+        };
+        cli.episodes = episodes.unwrap_or(cli.episodes);
+        Ok(cli)
+    }
+}
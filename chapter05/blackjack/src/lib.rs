@@ -0,0 +1,484 @@
+use std::fmt;
+use std::io::{self, Write};
+
+use rand::rngs::SmallRng;
+use rand::Rng;
+use rl_core::{run_episode, seeded_rng, Environment, Policy, Step};
+
+mod off_policy;
+
+pub use off_policy::{
+    blackjack_errors, log_checkpoints, one_state_estimates, ImportanceSampling, OneState, Side,
+    UniformRandom, FIGURE_5_3_STATE, FIGURE_5_3_VALUE,
+};
+
+// Player sums the agent decides at; below 12 it always hits, since no card can
+// bust it.
+pub const PLAYER_SUMS: std::ops::RangeInclusive<u8> = 12..=21;
+// 10 player sums x 10 dealer cards x usable ace or not.
+pub const STATES: usize = 200;
+
+// What the player sees (Example 5.1): its sum, the dealer's showing card (1 for
+// an ace) and whether it holds an ace counted as 11.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct State {
+    pub player_sum: u8,
+    pub dealer_showing: u8,
+    pub usable_ace: bool,
+}
+
+impl State {
+    // The state's index in 0..STATES; only meaningful for player sums 12 to
+    // 21.
+    pub fn index(&self) -> usize {
+        (self.player_sum as usize - 12) * 20
+            + (self.dealer_showing as usize - 1) * 2
+            + self.usable_ace as usize
+    }
+
+    pub fn from_index(index: usize) -> State {
+        State {
+            player_sum: (index / 20) as u8 + 12,
+            dealer_showing: (index % 20 / 2) as u8 + 1,
+            usable_ace: index % 2 == 1,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Action {
+    Stick,
+    Hit,
+}
+
+impl Action {
+    pub const ALL: [Action; 2] = [Action::Stick, Action::Hit];
+
+    pub fn index(self) -> usize {
+        self as usize
+    }
+}
+
+impl fmt::Display for Action {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Action::Stick => "S",
+            Action::Hit => "H",
+        })
+    }
+}
+
+// A card from an infinite deck: ace as 1, face cards as 10.
+fn draw(rng: &mut impl Rng) -> u8 {
+    rng.random_range(1..=13).min(10)
+}
+
+// A hand's cards, with aces counted as 1 in `total`.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+struct Hand {
+    total: u8,
+    has_ace: bool,
+    cards: u8,
+}
+
+impl Hand {
+    fn add(&mut self, card: u8) {
+        self.total += card;
+        self.has_ace |= card == 1;
+        self.cards += 1;
+    }
+
+    // An ace is usable when counting it as 11 doesn't bust the hand.
+    fn usable_ace(&self) -> bool {
+        self.has_ace && self.total + 10 <= 21
+    }
+
+    fn value(&self) -> u8 {
+        if self.usable_ace() {
+            self.total + 10
+        } else {
+            self.total
+        }
+    }
+
+    fn is_natural(&self) -> bool {
+        self.cards == 2 && self.value() == 21
+    }
+}
+
+// Blackjack against a dealer who hits below 17 and sticks otherwise, dealt
+// from an infinite deck. Winning pays +1, drawing 0 and losing -1, all at the
+// end of the episode; every other step pays 0.
+pub struct Blackjack {
+    rng: SmallRng,
+    start: Option<State>,
+    player: Hand,
+    dealer: Hand,
+    dealer_showing: u8,
+}
+
+impl Blackjack {
+    pub fn new(seed: Option<u64>) -> Self {
+        Blackjack {
+            rng: seeded_rng(seed),
+            start: None,
+            player: Hand::default(),
+            dealer: Hand::default(),
+            dealer_showing: 1,
+        }
+    }
+
+    // Makes the following episodes start in `start`, with the dealer's hidden
+    // card still dealt at random, instead of dealing the player's cards. For
+    // exploring starts.
+    pub fn start_from(&mut self, start: Option<State>) {
+        self.start = start;
+    }
+
+    fn state(&self) -> State {
+        State {
+            player_sum: self.player.value(),
+            dealer_showing: self.dealer_showing,
+            usable_ace: self.player.usable_ace(),
+        }
+    }
+
+    // Plays out the dealer's hand and settles the game.
+    fn settle(&mut self) -> Step<State> {
+        let player = self.player.value();
+        let reward = if self.player.is_natural() {
+            if self.dealer.is_natural() {
+                0.0
+            } else {
+                1.0
+            }
+        } else {
+            while self.dealer.value() < 17 {
+                self.dealer.add(draw(&mut self.rng));
+            }
+            let dealer = self.dealer.value();
+            if dealer > 21 || player > dealer {
+                1.0
+            } else if player == dealer {
+                0.0
+            } else {
+                -1.0
+            }
+        };
+        Step {
+            state: self.state(),
+            reward,
+            done: true,
+        }
+    }
+}
+
+impl Environment for Blackjack {
+    type State = State;
+    type Action = Action;
+
+    fn reset(&mut self) -> State {
+        self.player = Hand::default();
+        self.dealer = Hand::default();
+        match self.start {
+            Some(start) => {
+                // Cards worth the sum, with an ace among them if it's usable.
+                // There are taken to be more than two, so it's never a
+                // natural.
+                self.player = Hand {
+                    total: start.player_sum - if start.usable_ace { 10 } else { 0 },
+                    has_ace: start.usable_ace,
+                    cards: 3,
+                };
+                self.dealer_showing = start.dealer_showing;
+            }
+            None => {
+                while self.player.value() < 12 {
+                    self.player.add(draw(&mut self.rng));
+                }
+                self.dealer_showing = draw(&mut self.rng);
+            }
+        }
+        self.dealer.add(self.dealer_showing);
+        self.dealer.add(draw(&mut self.rng));
+        self.state()
+    }
+
+    fn step(&mut self, action: &Action) -> Step<State> {
+        // A natural settles at once, whatever the player does.
+        if *action == Action::Stick || self.player.is_natural() {
+            return self.settle();
+        }
+        self.player.add(draw(&mut self.rng));
+        let bust = self.player.value() > 21;
+        Step {
+            state: self.state(),
+            reward: if bust { -1.0 } else { 0.0 },
+            done: bust,
+        }
+    }
+
+    fn actions(&self, _state: &State) -> Vec<Action> {
+        Action::ALL.to_vec()
+    }
+}
+
+// Hits until the player's sum reaches `threshold`, then sticks. The policy
+// Example 5.1 evaluates sticks on 20.
+pub struct StickAt(pub u8);
+
+impl StickAt {
+    pub fn action(&self, state: State) -> Action {
+        if state.player_sum >= self.0 {
+            Action::Stick
+        } else {
+            Action::Hit
+        }
+    }
+}
+
+impl Policy<Blackjack> for StickAt {
+    fn select_action(&mut self, _env: &Blackjack, state: &State) -> Action {
+        self.action(*state)
+    }
+}
+
+// First-visit Monte Carlo prediction (Section 5.1) of `policy` over
+// `episodes` episodes: each state's value is the average of the returns that
+// followed its first visit in each episode. Returns are undiscounted, and
+// states never visited keep value 0.
+pub fn mc_prediction<P: Policy<Blackjack> + ?Sized>(
+    env: &mut Blackjack,
+    policy: &mut P,
+    episodes: usize,
+) -> Vec<f64> {
+    let mut values = vec![0.0; STATES];
+    let mut visits = vec![0u32; STATES];
+    for _ in 0..episodes {
+        let episode = run_episode(env, policy, None);
+        let mut g = 0.0;
+        for t in (0..episode.len()).rev() {
+            g += episode.rewards[t];
+            let state = episode.states[t];
+            if episode.states[..t].contains(&state) {
+                continue;
+            }
+            let index = state.index();
+            visits[index] += 1;
+            values[index] += (g - values[index]) / visits[index] as f64;
+        }
+    }
+    values
+}
+
+// Monte Carlo ES (Section 5.3): every episode starts from a state and action
+// chosen uniformly at random, then follows the greedy policy. Action values
+// average the returns that followed their first visits, and the policy starts
+// out sticking on 20 and 21.
+pub struct McEs {
+    rng: SmallRng,
+    q: Vec<[f64; 2]>,
+    visits: Vec<[u32; 2]>,
+    policy: Vec<Action>,
+    first: Option<Action>,
+}
+
+impl McEs {
+    pub fn new(seed: Option<u64>) -> Self {
+        McEs {
+            rng: seeded_rng(seed),
+            q: vec![[0.0; 2]; STATES],
+            visits: vec![[0; 2]; STATES],
+            policy: (0..STATES)
+                .map(|index| StickAt(20).action(State::from_index(index)))
+                .collect(),
+            first: None,
+        }
+    }
+
+    pub fn train(&mut self, env: &mut Blackjack, episodes: usize) {
+        for _ in 0..episodes {
+            let start = State::from_index(self.rng.random_range(0..STATES));
+            env.start_from(Some(start));
+            self.first = Some(Action::ALL[self.rng.random_range(0..2)]);
+            let episode = run_episode(env, self, None);
+            let mut g = 0.0;
+            for t in (0..episode.len()).rev() {
+                g += episode.rewards[t];
+                let (state, action) = (episode.states[t], episode.actions[t]);
+                let earlier = episode.states[..t]
+                    .iter()
+                    .zip(&episode.actions[..t])
+                    .any(|pair| pair == (&state, &action));
+                if earlier {
+                    continue;
+                }
+                let (s, a) = (state.index(), action.index());
+                self.visits[s][a] += 1;
+                self.q[s][a] += (g - self.q[s][a]) / self.visits[s][a] as f64;
+                self.policy[s] = if self.q[s][1] > self.q[s][0] {
+                    Action::Hit
+                } else {
+                    Action::Stick
+                };
+            }
+        }
+        env.start_from(None);
+    }
+
+    pub fn action_values(&self, state: State) -> [f64; 2] {
+        self.q[state.index()]
+    }
+
+    pub fn policy(&self, state: State) -> Action {
+        self.policy[state.index()]
+    }
+
+    // v*(s) = max over a of q*(s, a).
+    pub fn value(&self, state: State) -> f64 {
+        let [stick, hit] = self.action_values(state);
+        stick.max(hit)
+    }
+}
+
+impl Policy<Blackjack> for McEs {
+    fn select_action(&mut self, _env: &Blackjack, state: &State) -> Action {
+        self.first
+            .take()
+            .unwrap_or_else(|| self.policy[state.index()])
+    }
+}
+
+// Writes a grid per (name, cell) table and ace case, as in Figures 5.1 and
+// 5.2: a row per player sum from 21 down to 12 and a column per dealer
+// showing card from ace to 10.
+pub fn write_grids_csv(
+    tables: &[(&str, &dyn Fn(State) -> String)],
+    writer: &mut dyn Write,
+) -> io::Result<()> {
+    let dealer: Vec<String> = (2..=10).map(|card| card.to_string()).collect();
+    writeln!(writer, "table,usable_ace,player_sum,A,{}", dealer.join(","))?;
+    for &(name, cell) in tables {
+        for usable_ace in [true, false] {
+            for player_sum in PLAYER_SUMS.rev() {
+                let row: Vec<String> = (1..=10)
+                    .map(|dealer_showing| {
+                        cell(State {
+                            player_sum,
+                            dealer_showing,
+                            usable_ace,
+                        })
+                    })
+                    .collect();
+                writeln!(
+                    writer,
+                    "{},{},{},{}",
+                    name,
+                    usable_ace,
+                    player_sum,
+                    row.join(",")
+                )?;
+            }
+        }
+    }
+    Ok(())
+}
+
+// The same grid as text, for one ace case.
+pub fn format_grid(usable_ace: bool, cell: impl Fn(State) -> String) -> String {
+    let mut lines = vec![format!(
+        "    {}",
+        ["A", "2", "3", "4", "5", "6", "7", "8", "9", "10"]
+            .iter()
+            .map(|card| format!("{:>6}", card))
+            .collect::<String>()
+    )];
+    for player_sum in PLAYER_SUMS.rev() {
+        let row: String = (1..=10)
+            .map(|dealer_showing| {
+                let state = State {
+                    player_sum,
+                    dealer_showing,
+                    usable_ace,
+                };
+                format!("{:>6}", cell(state))
+            })
+            .collect();
+        lines.push(format!("{:2} |{}", player_sum, row));
+    }
+    lines.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn aces_count_as_eleven_until_they_would_bust() {
+        let mut hand = Hand::default();
+        hand.add(1);
+        hand.add(10);
+        assert!(hand.is_natural());
+        hand.add(5);
+        assert!(!hand.usable_ace());
+        assert_eq!(hand.value(), 16);
+        let mut soft = Hand::default();
+        soft.add(1);
+        soft.add(1);
+        assert_eq!((soft.value(), soft.usable_ace()), (12, true));
+    }
+
+    #[test]
+    fn state_indices_round_trip() {
+        for index in 0..STATES {
+            assert_eq!(State::from_index(index).index(), index);
+        }
+        let mut env = Blackjack::new(Some(3));
+        for _ in 0..100 {
+            let state = env.reset();
+            assert!(PLAYER_SUMS.contains(&state.player_sum));
+            assert!(state.index() < STATES);
+        }
+    }
+
+    #[test]
+    fn sticking_on_20_wins_on_20_and_loses_below() {
+        let mut env = Blackjack::new(Some(1));
+        let values = mc_prediction(&mut env, &mut StickAt(20), 50_000);
+        let value = |player_sum, usable_ace| {
+            values[State {
+                player_sum,
+                dealer_showing: 10,
+                usable_ace,
+            }
+            .index()]
+        };
+        assert!(value(20, false) > 0.3, "{}", value(20, false));
+        assert!(value(15, false) < -0.5, "{}", value(15, false));
+        assert!(value(21, true) > 0.8);
+    }
+
+    #[test]
+    fn exploring_starts_learn_to_stick_high_and_hit_soft_hands() {
+        let mut env = Blackjack::new(Some(2));
+        let mut es = McEs::new(Some(5));
+        es.train(&mut env, 200_000);
+        for dealer_showing in 1..=10 {
+            for usable_ace in [true, false] {
+                let state = |player_sum| State {
+                    player_sum,
+                    dealer_showing,
+                    usable_ace,
+                };
+                assert_eq!(es.policy(state(20)), Action::Stick);
+                assert_eq!(es.policy(state(21)), Action::Stick);
+            }
+        }
+        let soft_12 = State {
+            player_sum: 12,
+            dealer_showing: 10,
+            usable_ace: true,
+        };
+        assert_eq!(es.policy(soft_12), Action::Hit);
+    }
+}
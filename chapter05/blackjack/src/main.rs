@@ -0,0 +1,224 @@
+mod cli;
+
+use std::fs::File;
+use std::io::{self, BufWriter};
+use std::path::Path;
+use std::process;
+
+use cli::{Cli, Command, USAGE};
+use rl_core::{derive_seed, expand_config, json, write_jsonl, JsonLines};
+use sutton_blackjack::*;
+
+// Writes `write` to `out`, or to stdout when it is `None`, exiting on errors.
+fn write_output(out: Option<&Path>, write: impl FnOnce(&mut dyn io::Write) -> io::Result<()>) {
+    let written = match out {
+        Some(path) => File::create(path).and_then(|file| write(&mut BufWriter::new(file))),
+        None => write(&mut io::stdout().lock()),
+    };
+    if let Err(e) = written {
+        eprintln!("Error: couldn't write the results: {}", e);
+        process::exit(1);
+    }
+}
+
+// Writes JSON Lines records to `cli.jsonl` with `write`, if it is set,
+// exiting on errors.
+fn write_records(
+    cli: &Cli,
+    experiment: &str,
+    write: impl FnOnce(&mut JsonLines) -> io::Result<()>,
+) {
+    let Some(path) = &cli.jsonl else { return };
+    if let Err(e) = write_jsonl(path, experiment, cli.seed, write) {
+        eprintln!("Error: couldn't write {}: {}", path.display(), e);
+        process::exit(1);
+    }
+}
+
+// Every state the player decides in, as the grids lay them out.
+fn states() -> impl Iterator<Item = State> {
+    [true, false].into_iter().flat_map(|usable_ace| {
+        PLAYER_SUMS.rev().flat_map(move |player_sum| {
+            (1..=10).map(move |dealer_showing| State {
+                player_sum,
+                dealer_showing,
+                usable_ace,
+            })
+        })
+    })
+}
+
+fn state_json(state: State) -> rl_core::Value {
+    json!({
+        "usable_ace": state.usable_ace,
+        "player_sum": state.player_sum,
+        "dealer_showing": state.dealer_showing,
+    })
+}
+
+// Prints `cell` as a grid for each ace case.
+fn print_grids(title: &str, cell: impl Fn(State) -> String) {
+    for usable_ace in [true, false] {
+        println!(
+            "\n{}, {}:",
+            title,
+            if usable_ace {
+                "usable ace"
+            } else {
+                "no usable ace"
+            }
+        );
+        println!("{}", format_grid(usable_ace, &cell));
+    }
+}
+
+fn predict(cli: &Cli, stick_at: u8) {
+    let mut env = Blackjack::new(cli.seed);
+    let values = mc_prediction(&mut env, &mut StickAt(stick_at), cli.episodes);
+    let value = |state: State| format!("{:.3}", values[state.index()]);
+    write_output(cli.out.as_deref(), |writer| {
+        write_grids_csv(&[("value", &value)], writer)
+    });
+    write_records(cli, "blackjack/predict", |lines| {
+        let config = json!({ "stick_at": stick_at, "episodes": cli.episodes });
+        for state in states() {
+            let mut metrics = state_json(state);
+            metrics["value"] = json!(values[state.index()]);
+            lines.record("state", &config, metrics)?;
+        }
+        Ok(())
+    });
+    let Some(path) = &cli.out else { return };
+    println!(
+        "Values of sticking on {} after {} episodes; written to {}",
+        stick_at,
+        cli.episodes,
+        path.display()
+    );
+    print_grids("Values", |state| format!("{:.2}", values[state.index()]));
+}
+
+fn control(cli: &Cli) {
+    let mut env = Blackjack::new(cli.seed);
+    let mut es = McEs::new(derive_seed(cli.seed, 1));
+    es.train(&mut env, cli.episodes);
+    let policy = |state: State| es.policy(state).to_string();
+    let value = |state: State| format!("{:.3}", es.value(state));
+    write_output(cli.out.as_deref(), |writer| {
+        write_grids_csv(&[("policy", &policy), ("value", &value)], writer)
+    });
+    write_records(cli, "blackjack/control", |lines| {
+        let config = json!({ "episodes": cli.episodes });
+        for state in states() {
+            let mut metrics = state_json(state);
+            metrics["policy"] = json!(es.policy(state).to_string());
+            metrics["value"] = json!(es.value(state));
+            lines.record("state", &config, metrics)?;
+        }
+        Ok(())
+    });
+    let Some(path) = &cli.out else { return };
+    println!(
+        "Optimal policy (S = stick, H = hit) and values after {} episodes; written to {}",
+        cli.episodes,
+        path.display()
+    );
+    print_grids("Policy", policy);
+    print_grids("Values", |state| format!("{:.2}", es.value(state)));
+}
+
+fn off_policy(cli: &Cli, runs: usize) {
+    let errors = blackjack_errors(runs, cli.episodes, cli.seed);
+    write_output(cli.out.as_deref(), |writer| {
+        writeln!(writer, "episode,ordinary,weighted")?;
+        for (episode, (ordinary, weighted)) in errors.iter().enumerate() {
+            writeln!(writer, "{},{:.6},{:.6}", episode + 1, ordinary, weighted)?;
+        }
+        Ok(())
+    });
+    write_records(cli, "blackjack/off-policy", |lines| {
+        let config = json!({ "runs": runs, "episodes": cli.episodes });
+        for (episode, (ordinary, weighted)) in errors.iter().enumerate() {
+            lines.record(
+                "episode",
+                &config,
+                json!({ "episode": episode + 1, "ordinary": ordinary, "weighted": weighted }),
+            )?;
+        }
+        Ok(())
+    });
+    let Some(path) = &cli.out else { return };
+    println!(
+        "Mean squared error over {} runs estimating a state worth {}; written to {}",
+        runs,
+        FIGURE_5_3_VALUE,
+        path.display()
+    );
+    println!("{:>9}  {:>9}  {:>9}", "episodes", "ordinary", "weighted");
+    for checkpoint in log_checkpoints(cli.episodes)
+        .into_iter()
+        .filter(|n| n.to_string().starts_with('1'))
+    {
+        let (ordinary, weighted) = errors[checkpoint - 1];
+        println!("{:>9}  {:>9.4}  {:>9.4}", checkpoint, ordinary, weighted);
+    }
+}
+
+fn infinite_variance(cli: &Cli, runs: usize) {
+    let checkpoints = log_checkpoints(cli.episodes);
+    let estimates = one_state_estimates(runs, &checkpoints, cli.seed);
+    write_output(cli.out.as_deref(), |writer| {
+        let header: Vec<String> = (1..=runs).map(|run| format!("run_{}", run)).collect();
+        writeln!(writer, "episode,{}", header.join(","))?;
+        for (i, checkpoint) in checkpoints.iter().enumerate() {
+            let row: Vec<String> = estimates
+                .iter()
+                .map(|run| format!("{:.6}", run[i]))
+                .collect();
+            writeln!(writer, "{},{}", checkpoint, row.join(","))?;
+        }
+        Ok(())
+    });
+    write_records(cli, "blackjack/infinite-variance", |lines| {
+        let config = json!({ "runs": runs, "episodes": cli.episodes });
+        for (run, run_estimates) in estimates.iter().enumerate() {
+            for (checkpoint, estimate) in checkpoints.iter().zip(run_estimates) {
+                lines.record(
+                    "evaluation",
+                    &config,
+                    json!({ "run": run + 1, "episode": checkpoint, "estimate": estimate }),
+                )?;
+            }
+        }
+        Ok(())
+    });
+    let Some(path) = &cli.out else { return };
+    println!(
+        "Ordinary importance-sampling estimates of a state worth 1 after {} episodes; written to {}",
+        cli.episodes,
+        path.display()
+    );
+    for (run, run_estimates) in estimates.iter().enumerate() {
+        println!(
+            "  run {:2}: {:.4}",
+            run + 1,
+            run_estimates.last().copied().unwrap_or(0.0)
+        );
+    }
+}
+
+fn main() {
+    let cli = expand_config(std::env::args().skip(1))
+        .and_then(Cli::parse)
+        .unwrap_or_else(|message| {
+            eprintln!("Error: {}\n\n{}", message, USAGE);
+            process::exit(2);
+        });
+    match cli.command {
+        Command::Predict { stick_at } => predict(&cli, stick_at),
+        Command::Control => control(&cli),
+        Command::OffPolicy { runs } => off_policy(&cli, runs),
+        Command::InfiniteVariance { runs } => infinite_variance(&cli, runs),
+        Command::Help => println!("{}", USAGE),
+    }
+}
@@ -0,0 +1,223 @@
+// Off-policy Monte Carlo prediction by importance sampling (Section 5.5).
+
+use rand::rngs::SmallRng;
+use rand::Rng;
+use rl_core::{derive_seed, run_episode, seeded_rng, Environment, Policy, Step};
+
+use crate::{Blackjack, State, StickAt};
+
+// The state Figure 5.3 evaluates: the dealer showing a deuce and the player
+// holding 13 with a usable ace.
+pub const FIGURE_5_3_STATE: State = State {
+    player_sum: 13,
+    dealer_showing: 2,
+    usable_ace: true,
+};
+// Its value under the policy sticking on 20 or 21, from the book.
+pub const FIGURE_5_3_VALUE: f64 = -0.27726;
+
+// Running ordinary and weighted importance-sampling estimates (5.5 and 5.6)
+// of one state's value from returns weighted by their importance-sampling
+// ratios.
+#[derive(Clone, Debug, Default)]
+pub struct ImportanceSampling {
+    weighted_returns: f64,
+    ratios: f64,
+    episodes: usize,
+}
+
+impl ImportanceSampling {
+    pub fn add(&mut self, ratio: f64, g: f64) {
+        self.weighted_returns += ratio * g;
+        self.ratios += ratio;
+        self.episodes += 1;
+    }
+
+    pub fn ordinary(&self) -> f64 {
+        if self.episodes == 0 {
+            0.0
+        } else {
+            self.weighted_returns / self.episodes as f64
+        }
+    }
+
+    // 0 until some episode has a nonzero ratio.
+    pub fn weighted(&self) -> f64 {
+        if self.ratios == 0.0 {
+            0.0
+        } else {
+            self.weighted_returns / self.ratios
+        }
+    }
+}
+
+// Picks uniformly among the environment's actions: the behaviour policy of
+// both figures.
+pub struct UniformRandom(pub SmallRng);
+
+impl<E: Environment> Policy<E> for UniformRandom {
+    fn select_action(&mut self, env: &E, state: &E::State) -> E::Action {
+        let actions = env.actions(state);
+        actions[self.0.random_range(0..actions.len())].clone()
+    }
+}
+
+// The importance-sampling ratio of a deterministic target policy to the
+// uniform behaviour policy over two actions: 0 if the episode ever strayed
+// from the target, else 2^T.
+fn ratio<A: PartialEq>(actions: &[A], target: impl Fn(usize) -> A) -> f64 {
+    if actions
+        .iter()
+        .enumerate()
+        .all(|(t, action)| *action == target(t))
+    {
+        2f64.powi(actions.len() as i32)
+    } else {
+        0.0
+    }
+}
+
+// Figure 5.3: in each of `runs` runs, estimates FIGURE_5_3_STATE's value under
+// sticking on 20 from `episodes` episodes of uniformly random play. Returns
+// the squared error of the ordinary and weighted estimates after each episode,
+// averaged over the runs.
+pub fn blackjack_errors(runs: usize, episodes: usize, seed: Option<u64>) -> Vec<(f64, f64)> {
+    let mut errors = vec![(0.0, 0.0); episodes];
+    for run in 0..runs as u64 {
+        let mut env = Blackjack::new(derive_seed(seed, 2 * run));
+        env.start_from(Some(FIGURE_5_3_STATE));
+        let mut behaviour = UniformRandom(seeded_rng(derive_seed(seed, 2 * run + 1)));
+        let mut estimate = ImportanceSampling::default();
+        for error in errors.iter_mut() {
+            let episode = run_episode(&mut env, &mut behaviour, None);
+            let ratio = ratio(&episode.actions, |t| StickAt(20).action(episode.states[t]));
+            estimate.add(ratio, episode.total_reward());
+            error.0 += (estimate.ordinary() - FIGURE_5_3_VALUE).powi(2) / runs as f64;
+            error.1 += (estimate.weighted() - FIGURE_5_3_VALUE).powi(2) / runs as f64;
+        }
+    }
+    errors
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Side {
+    Left,
+    Right,
+}
+
+// The one-state MDP of Example 5.5: right ends the episode for reward 0, and
+// left returns to the state with probability 0.9 and otherwise ends it for
+// reward +1. Always going left is worth 1.
+pub struct OneState {
+    rng: SmallRng,
+}
+
+impl OneState {
+    pub fn new(seed: Option<u64>) -> Self {
+        OneState {
+            rng: seeded_rng(seed),
+        }
+    }
+}
+
+impl Environment for OneState {
+    type State = ();
+    type Action = Side;
+
+    fn reset(&mut self) {}
+
+    fn step(&mut self, action: &Side) -> Step<()> {
+        let ended = *action == Side::Right || self.rng.random::<f64>() < 0.1;
+        Step {
+            state: (),
+            reward: if ended && *action == Side::Left {
+                1.0
+            } else {
+                0.0
+            },
+            done: ended,
+        }
+    }
+
+    fn actions(&self, _state: &()) -> Vec<Side> {
+        vec![Side::Left, Side::Right]
+    }
+}
+
+// Figure 5.4: `runs` runs of ordinary importance sampling estimating the
+// one-state MDP's value under always going left from uniformly random play.
+// Returns each run's estimate after each of `checkpoints` episode counts,
+// which must be increasing.
+pub fn one_state_estimates(runs: usize, checkpoints: &[usize], seed: Option<u64>) -> Vec<Vec<f64>> {
+    (0..runs as u64)
+        .map(|run| {
+            let mut env = OneState::new(derive_seed(seed, 2 * run));
+            let mut behaviour = UniformRandom(seeded_rng(derive_seed(seed, 2 * run + 1)));
+            let mut estimate = ImportanceSampling::default();
+            let mut estimates = Vec::with_capacity(checkpoints.len());
+            for &checkpoint in checkpoints {
+                while estimate.episodes < checkpoint {
+                    let episode = run_episode(&mut env, &mut behaviour, None);
+                    estimate.add(
+                        ratio(&episode.actions, |_| Side::Left),
+                        episode.total_reward(),
+                    );
+                }
+                estimates.push(estimate.ordinary());
+            }
+            estimates
+        })
+        .collect()
+}
+
+// 1 to 9, 10 to 90 in tens, 100 to 900 in hundreds and so on up to `max`,
+// for plotting against episodes on a log scale.
+pub fn log_checkpoints(max: usize) -> Vec<usize> {
+    let mut checkpoints = Vec::new();
+    let mut step = 1;
+    while step <= max {
+        checkpoints.extend((1..10).map(|i| i * step).take_while(|&n| n <= max));
+        step *= 10;
+    }
+    if checkpoints.last() != Some(&max) && max > 0 {
+        checkpoints.push(max);
+    }
+    checkpoints
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn weighted_sampling_averages_the_matching_returns() {
+        let mut estimate = ImportanceSampling::default();
+        assert_eq!(estimate.weighted(), 0.0);
+        estimate.add(0.0, 1.0);
+        estimate.add(4.0, -1.0);
+        estimate.add(2.0, 1.0);
+        assert_eq!(estimate.weighted(), -2.0 / 6.0);
+        assert_eq!(estimate.ordinary(), -2.0 / 3.0);
+        assert_eq!(ratio(&[Side::Left, Side::Left], |_| Side::Left), 4.0);
+        assert_eq!(ratio(&[Side::Left, Side::Right], |_| Side::Left), 0.0);
+    }
+
+    #[test]
+    fn weighted_importance_sampling_has_lower_error_early_on() {
+        let errors = blackjack_errors(100, 100, Some(1));
+        let (ordinary, weighted) = errors[9];
+        assert!(weighted < ordinary, "{} vs {}", weighted, ordinary);
+        assert!(errors[99].1 < 0.2, "{:?}", errors[99]);
+    }
+
+    #[test]
+    fn checkpoints_are_log_spaced() {
+        assert_eq!(
+            log_checkpoints(250),
+            vec![1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 20, 30, 40, 50, 60, 70, 80, 90, 100, 200, 250]
+        );
+        let estimates = one_state_estimates(2, &log_checkpoints(100), Some(3));
+        assert_eq!(estimates.len(), 2);
+        assert!(estimates.iter().all(|run| run.len() == 19));
+    }
+}
@@ -0,0 +1,182 @@
+use rl_core::{derive_seed, run_episode, Environment, Step, TdAgent, TdControl};
+
+pub const ROWS: usize = 4;
+pub const COLS: usize = 12;
+// Up, down, left, right.
+pub const ARROWS: [char; 4] = ['↑', '↓', '←', '→'];
+const MOVES: [(isize, isize); 4] = [(-1, 0), (1, 0), (0, -1), (0, 1)];
+const START: usize = (ROWS - 1) * COLS;
+const GOAL: usize = ROWS * COLS - 1;
+
+// The cliff-walking gridworld of Example 6.6: the bottom row runs from the
+// start on the left to the goal on the right, with the cliff between them.
+// Every step costs -1, except stepping into the cliff, which costs -100 and
+// sends the agent back to the start. Moves off the grid leave the agent in
+// place. Cells are numbered in row-major order.
+pub struct CliffWalking {
+    position: usize,
+}
+
+pub fn is_cliff(cell: usize) -> bool {
+    cell > START && cell < GOAL
+}
+
+impl CliffWalking {
+    pub fn new() -> Self {
+        CliffWalking { position: START }
+    }
+}
+
+impl Default for CliffWalking {
+    fn default() -> Self {
+        CliffWalking::new()
+    }
+}
+
+impl Environment for CliffWalking {
+    type State = usize;
+    type Action = usize;
+
+    fn reset(&mut self) -> usize {
+        self.position = START;
+        START
+    }
+
+    fn step(&mut self, action: &usize) -> Step<usize> {
+        let (dr, dc) = MOVES[*action];
+        let row = ((self.position / COLS) as isize + dr).clamp(0, ROWS as isize - 1);
+        let col = ((self.position % COLS) as isize + dc).clamp(0, COLS as isize - 1);
+        let next = row as usize * COLS + col as usize;
+        let reward = if is_cliff(next) { -100.0 } else { -1.0 };
+        self.position = if is_cliff(next) { START } else { next };
+        Step {
+            state: self.position,
+            reward,
+            done: self.position == GOAL,
+        }
+    }
+
+    fn actions(&self, _state: &usize) -> Vec<usize> {
+        (0..MOVES.len()).collect()
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Experiment {
+    pub episodes: usize,
+    pub runs: usize,
+    pub alpha: f64,
+    pub epsilon: f64,
+}
+
+impl Default for Experiment {
+    // Example 6.6's settings.
+    fn default() -> Self {
+        Experiment {
+            episodes: 500,
+            runs: 100,
+            alpha: 0.5,
+            epsilon: 0.1,
+        }
+    }
+}
+
+// Trains `method` in `experiment.runs` independent runs. Returns the sum of
+// rewards in each episode averaged over the runs (Figure 6.4), and the agent
+// from the last run.
+pub fn run_experiment(
+    experiment: &Experiment,
+    method: TdControl,
+    seed: Option<u64>,
+) -> (Vec<f64>, Option<TdAgent>) {
+    let mut rewards = vec![0.0; experiment.episodes];
+    let mut last = None;
+    for run in 0..experiment.runs as u64 {
+        let mut env = CliffWalking::new();
+        let mut agent = TdAgent::new(
+            method,
+            ROWS * COLS,
+            MOVES.len(),
+            experiment.alpha,
+            experiment.epsilon,
+            derive_seed(seed, run),
+        );
+        for reward in rewards.iter_mut() {
+            let episode = run_episode(&mut env, &mut agent, None);
+            *reward += episode.total_reward() / experiment.runs as f64;
+        }
+        last = Some(agent);
+    }
+    (rewards, last)
+}
+
+// Follows `agent`'s greedy policy from the start. Returns the cells visited
+// and the actions taken there, stopping early if it hasn't reached the goal
+// in as many steps as there are cells.
+pub fn greedy_path(agent: &TdAgent) -> (Vec<usize>, Vec<usize>, bool) {
+    let mut env = CliffWalking::new();
+    let mut state = env.reset();
+    let (mut states, mut actions) = (Vec::new(), Vec::new());
+    while actions.len() < ROWS * COLS {
+        let action = agent.greedy_action(state);
+        states.push(state);
+        actions.push(action);
+        let step = env.step(&action);
+        if step.done {
+            return (states, actions, true);
+        }
+        state = step.state;
+    }
+    (states, actions, false)
+}
+
+// The grid with the path drawn as arrows for the action taken in each cell,
+// S and G marking the start and goal and C the cliff.
+pub fn format_path(states: &[usize], actions: &[usize]) -> String {
+    let mut grid: Vec<char> = (0..ROWS * COLS)
+        .map(|cell| if is_cliff(cell) { 'C' } else { '.' })
+        .collect();
+    for (&state, &action) in states.iter().zip(actions) {
+        grid[state] = ARROWS[action];
+    }
+    grid[START] = 'S';
+    grid[GOAL] = 'G';
+    grid.chunks(COLS)
+        .map(|row| row.iter().map(|c| format!(" {}", c)).collect::<String>())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_cliff_sends_the_agent_back_to_the_start() {
+        let mut env = CliffWalking::new();
+        env.reset();
+        let step = env.step(&3);
+        assert_eq!((step.state, step.reward, step.done), (START, -100.0, false));
+        env.step(&0);
+        let step = env.step(&1);
+        assert_eq!((step.state, step.reward), (START, -1.0));
+    }
+
+    #[test]
+    fn sarsa_earns_more_online_but_q_learning_takes_the_edge() {
+        let experiment = Experiment {
+            runs: 10,
+            ..Experiment::default()
+        };
+        let (sarsa, sarsa_agent) = run_experiment(&experiment, TdControl::Sarsa, Some(1));
+        let (q, q_agent) = run_experiment(&experiment, TdControl::QLearning, Some(1));
+        let late = |rewards: &[f64]| rewards[400..].iter().sum::<f64>() / 100.0;
+        assert!(late(&sarsa) > late(&q), "{} vs {}", late(&sarsa), late(&q));
+        // Q-learning's greedy path runs along the cliff edge: 13 steps.
+        let (_, actions, reached) = greedy_path(&q_agent.unwrap());
+        assert!(reached);
+        assert_eq!(actions.len(), 13);
+        let (_, actions, reached) = greedy_path(&sarsa_agent.unwrap());
+        assert!(reached && actions.len() > 13);
+    }
+}
@@ -0,0 +1,190 @@
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::PathBuf;
+use std::process;
+
+use rl_core::{derive_seed, expand_config, json, write_jsonl, TdControl};
+use sutton_cliff_walking::*;
+
+const USAGE: &str = "Usage: sutton-cliff-walking [options]
+
+Trains Sarsa, Q-learning and Expected Sarsa with the same epsilon-greedy
+behaviour on the cliff-walking task of Example 6.6 and writes each method's
+sum of rewards per episode, averaged over runs, as CSV (Figure 6.4). Then
+prints the greedy path each method learned.
+
+Options:
+  --methods <list> Comma-separated methods (default: sarsa,q-learning,
+                   expected-sarsa)
+  --episodes <n>   Episodes per run (default: 500)
+  --runs <n>       Independent runs to average (default: 100)
+  --alpha <x>      Step size (default: 0.5)
+  --epsilon <x>    Exploration rate (default: 0.1)
+  --out <path>     Write the CSV to <path> instead of stdout
+  --jsonl <path>   Also write each method's episodes to <path> as lines of
+                   JSON with the settings and seed
+  --seed <n>       Seed every random number generator, so runs can be
+                   repeated exactly (default: seeded from the OS)
+  --config <path>  Read options from an experiment file of `option = value`
+                   lines, a subset of TOML; options given here override it
+
+  -h, --help       Show this message";
+
+struct Options {
+    methods: Vec<TdControl>,
+    experiment: Experiment,
+    out: Option<PathBuf>,
+    jsonl: Option<PathBuf>,
+    seed: Option<u64>,
+}
+
+fn parse_number<T: std::str::FromStr>(flag: &str, value: &str) -> Result<T, String> {
+    value
+        .parse()
+        .map_err(|_| format!("invalid value '{}' for {}", value, flag))
+}
+
+// Parses the arguments after the program name; `None` means --help.
+fn parse_args(args: impl IntoIterator<Item = String>) -> Result<Option<Options>, String> {
+    let mut options = Options {
+        methods: TdControl::all().to_vec(),
+        experiment: Experiment::default(),
+        out: None,
+        jsonl: None,
+        seed: None,
+    };
+    let mut args = args.into_iter();
+    while let Some(flag) = args.next() {
+        let mut value = || args.next().ok_or(format!("{} needs a value", flag));
+        match flag.as_str() {
+            "--methods" => {
+                options.methods = value()?
+                    .split(',')
+                    .map(str::parse)
+                    .collect::<Result<_, String>>()?
+            }
+            "--episodes" => options.experiment.episodes = parse_number(&flag, &value()?)?,
+            "--runs" => match parse_number(&flag, &value()?)? {
+                0 => return Err("--runs must be at least 1".to_string()),
+                runs => options.experiment.runs = runs,
+            },
+            "--alpha" => match parse_number(&flag, &value()?)? {
+                alpha if alpha > 0.0 && alpha <= 1.0 => options.experiment.alpha = alpha,
+                _ => return Err("--alpha must be in (0, 1]".to_string()),
+            },
+            "--epsilon" => match parse_number(&flag, &value()?)? {
+                epsilon if (0.0..=1.0).contains(&epsilon) => options.experiment.epsilon = epsilon,
+                _ => return Err("--epsilon must be between 0 and 1".to_string()),
+            },
+            "--out" => options.out = Some(PathBuf::from(value()?)),
+            "--jsonl" => options.jsonl = Some(PathBuf::from(value()?)),
+            "--seed" => options.seed = Some(parse_number(&flag, &value()?)?),
+            "-h" | "--help" => return Ok(None),
+            _ => return Err(format!("unknown option '{}'", flag)),
+        }
+    }
+    Ok(Some(options))
+}
+
+fn write_curves(out: &mut dyn Write, methods: &[TdControl], curves: &[Vec<f64>]) -> io::Result<()> {
+    let names: Vec<String> = methods.iter().map(TdControl::to_string).collect();
+    writeln!(out, "episode,{}", names.join(","))?;
+    let episodes = curves.first().map_or(0, Vec::len);
+    for episode in 0..episodes {
+        let row: Vec<String> = curves
+            .iter()
+            .map(|curve| format!("{:.3}", curve[episode]))
+            .collect();
+        writeln!(out, "{},{}", episode + 1, row.join(","))?;
+    }
+    out.flush()
+}
+
+fn main() {
+    let options = match expand_config(std::env::args().skip(1)).and_then(parse_args) {
+        Ok(Some(options)) => options,
+        Ok(None) => {
+            println!("{}", USAGE);
+            return;
+        }
+        Err(message) => {
+            eprintln!("Error: {}\n\n{}", message, USAGE);
+            process::exit(2);
+        }
+    };
+    // Each method gets its own seeds, so adding or reordering methods doesn't
+    // change the others' results.
+    let (curves, agents): (Vec<_>, Vec<_>) = options
+        .methods
+        .iter()
+        .map(|&method| {
+            let offset = TdControl::all()
+                .iter()
+                .position(|&m| m == method)
+                .unwrap_or(0);
+            run_experiment(
+                &options.experiment,
+                method,
+                derive_seed(options.seed, (offset * options.experiment.runs) as u64),
+            )
+        })
+        .unzip();
+    let written = match &options.out {
+        Some(path) => File::create(path)
+            .and_then(|file| write_curves(&mut BufWriter::new(file), &options.methods, &curves)),
+        None => write_curves(&mut io::stdout().lock(), &options.methods, &curves),
+    };
+    if let Err(e) = written {
+        eprintln!("Error: couldn't write the results: {}", e);
+        process::exit(1);
+    }
+    if let Some(path) = &options.jsonl {
+        let experiment = &options.experiment;
+        let written = write_jsonl(path, "cliff-walking", options.seed, |lines| {
+            for (method, curve) in options.methods.iter().zip(&curves) {
+                let config = json!({
+                    "method": method.to_string(),
+                    "episodes": experiment.episodes,
+                    "runs": experiment.runs,
+                    "alpha": experiment.alpha,
+                    "epsilon": experiment.epsilon,
+                });
+                for (episode, sum) in curve.iter().enumerate() {
+                    lines.record(
+                        "episode",
+                        &config,
+                        json!({ "episode": episode + 1, "sum_of_rewards": sum }),
+                    )?;
+                }
+            }
+            Ok(())
+        });
+        if let Err(e) = written {
+            eprintln!("Error: couldn't write {}: {}", path.display(), e);
+            process::exit(1);
+        }
+    }
+    if let Some(path) = &options.out {
+        println!(
+            "Sum of rewards per episode over {} runs of {} episodes; written to {}",
+            options.experiment.runs,
+            options.experiment.episodes,
+            path.display()
+        );
+    }
+    let late = options.experiment.episodes.saturating_sub(100);
+    for ((method, curve), agent) in options.methods.iter().zip(&curves).zip(&agents) {
+        let Some(agent) = agent else { continue };
+        let (states, actions, reached) = greedy_path(agent);
+        let average = curve[late..].iter().sum::<f64>() / (curve.len() - late).max(1) as f64;
+        eprintln!(
+            "\n{}: average sum of rewards over the last {} episodes {:.1}; the greedy path {} {} steps:\n{}",
+            method,
+            curve.len() - late,
+            average,
+            if reached { "reaches the goal in" } else { "doesn't reach the goal in" },
+            actions.len(),
+            format_path(&states, &actions)
+        );
+    }
+}
@@ -0,0 +1,183 @@
+use rand::rngs::SmallRng;
+use rl_core::{
+    derive_seed, run_episode, sample_normal, seeded_rng, DoubleQLearningAgent, Environment, Policy,
+    Step, TdAgent, TdControl,
+};
+
+pub const A: usize = 0;
+pub const B: usize = 1;
+const TERMINAL: usize = 2;
+pub const LEFT: usize = 0;
+pub const RIGHT: usize = 1;
+
+// The two-state MDP of Example 6.7. Episodes start in A, where right ends
+// the episode with reward 0 and left moves to B, also for 0. Every one of
+// B's actions ends the episode with a reward drawn from N(-0.1, 1), so left
+// is worse on average, but the best of B's noisy estimates looks positive
+// and draws Q-learning left.
+pub struct MaximizationBias {
+    b_actions: usize,
+    state: usize,
+    rng: SmallRng,
+}
+
+impl MaximizationBias {
+    pub fn new(b_actions: usize, seed: Option<u64>) -> Self {
+        MaximizationBias {
+            b_actions,
+            state: A,
+            rng: seeded_rng(seed),
+        }
+    }
+}
+
+impl Environment for MaximizationBias {
+    type State = usize;
+    type Action = usize;
+
+    fn reset(&mut self) -> usize {
+        self.state = A;
+        A
+    }
+
+    fn step(&mut self, action: &usize) -> Step<usize> {
+        let (state, reward) = match (self.state, *action) {
+            (A, LEFT) => (B, 0.0),
+            (A, _) => (TERMINAL, 0.0),
+            _ => (TERMINAL, sample_normal(&mut self.rng, -0.1, 1.0)),
+        };
+        self.state = state;
+        Step {
+            state,
+            reward,
+            done: state == TERMINAL,
+        }
+    }
+
+    fn actions(&self, state: &usize) -> Vec<usize> {
+        match *state {
+            A => vec![LEFT, RIGHT],
+            _ => (0..self.b_actions).collect(),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Experiment {
+    pub episodes: usize,
+    pub runs: usize,
+    pub alpha: f64,
+    pub epsilon: f64,
+    pub b_actions: usize,
+}
+
+impl Default for Experiment {
+    // Figure 6.5's settings.
+    fn default() -> Self {
+        Experiment {
+            episodes: 300,
+            runs: 10_000,
+            alpha: 0.1,
+            epsilon: 0.1,
+            b_actions: 10,
+        }
+    }
+}
+
+// The share of left actions an epsilon-greedy agent takes from A when right is
+// greedy, the least any of them can take.
+pub fn optimal_left_share(experiment: &Experiment) -> f64 {
+    experiment.epsilon / 2.0
+}
+
+// The share of runs whose agent went left from A in each episode, for agents
+// made by `agent` from a seed. Each run's environment and agent get their own
+// seeds, the same for every kind of agent.
+fn left_share<P, F>(experiment: &Experiment, seed: Option<u64>, agent: F) -> Vec<f64>
+where
+    P: Policy<MaximizationBias>,
+    F: Fn(Option<u64>) -> P,
+{
+    let mut share = vec![0.0; experiment.episodes];
+    for run in 0..experiment.runs as u64 {
+        let mut env = MaximizationBias::new(experiment.b_actions, derive_seed(seed, 2 * run));
+        let mut agent = agent(derive_seed(seed, 2 * run + 1));
+        for left in share.iter_mut() {
+            let episode = run_episode(&mut env, &mut agent, None);
+            if episode.actions[0] == LEFT {
+                *left += 1.0 / experiment.runs as f64;
+            }
+        }
+    }
+    share
+}
+
+// Q-learning's share of left actions from A per episode (Figure 6.5).
+pub fn q_learning(experiment: &Experiment, seed: Option<u64>) -> Vec<f64> {
+    left_share(experiment, seed, |seed| {
+        TdAgent::new(
+            TdControl::QLearning,
+            TERMINAL,
+            experiment.b_actions,
+            experiment.alpha,
+            experiment.epsilon,
+            seed,
+        )
+        .with_actions(A, 2)
+    })
+}
+
+// Double Q-learning's, with the same settings and seeds.
+pub fn double_q_learning(experiment: &Experiment, seed: Option<u64>) -> Vec<f64> {
+    left_share(experiment, seed, |seed| {
+        DoubleQLearningAgent::new(
+            TERMINAL,
+            experiment.b_actions,
+            experiment.alpha,
+            experiment.epsilon,
+            seed,
+        )
+        .with_actions(A, 2)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn left_leads_to_b_and_a_noisy_loss() {
+        let mut env = MaximizationBias::new(10, Some(1));
+        assert_eq!(env.reset(), A);
+        assert_eq!(env.actions(&A), [LEFT, RIGHT]);
+        assert_eq!(env.actions(&B).len(), 10);
+        let step = env.step(&LEFT);
+        assert_eq!((step.state, step.reward, step.done), (B, 0.0, false));
+        assert!(env.step(&7).done);
+        env.reset();
+        let step = env.step(&RIGHT);
+        assert_eq!((step.reward, step.done), (0.0, true));
+    }
+
+    #[test]
+    fn q_learning_goes_left_more_than_double_q_learning() {
+        let experiment = Experiment {
+            episodes: 100,
+            runs: 1000,
+            ..Experiment::default()
+        };
+        let q = q_learning(&experiment, Some(1));
+        let double = double_q_learning(&experiment, Some(1));
+        let mean = |share: &[f64]| share.iter().sum::<f64>() / share.len() as f64;
+        assert!(
+            mean(&q) > 2.0 * mean(&double),
+            "{} vs {}",
+            mean(&q),
+            mean(&double)
+        );
+        // Early on Q-learning goes left in most runs, as in Figure 6.5.
+        let peak = q.iter().copied().fold(0.0, f64::max);
+        assert!(peak > 0.5, "{}", peak);
+        assert!(mean(&double[80..]) < 0.15, "{}", mean(&double[80..]));
+    }
+}
@@ -0,0 +1,178 @@
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::PathBuf;
+use std::process;
+
+use rl_core::{expand_config, json, write_jsonl};
+use sutton_maximization_bias::*;
+
+const USAGE: &str = "Usage: sutton-maximization-bias [options]
+
+Runs Q-learning and Double Q-learning on the two-state MDP of Example 6.7 and
+writes the percentage of runs that went left from A in each episode as CSV
+(Figure 6.5). Left leads to B, whose actions all pay N(-0.1, 1), so right is
+optimal; Q-learning's max over B's noisy estimates draws it left regardless.
+
+Options:
+  --episodes <n>   Episodes per run (default: 300)
+  --runs <n>       Independent runs to average (default: 10000)
+  --alpha <x>      Step size (default: 0.1)
+  --epsilon <x>    Exploration rate (default: 0.1)
+  --b-actions <n>  Actions in B (default: 10)
+  --out <path>     Write the CSV to <path> instead of stdout
+  --jsonl <path>   Also write each method's episodes to <path> as lines of
+                   JSON with the settings and seed
+  --seed <n>       Seed every random number generator, so runs can be
+                   repeated exactly (default: seeded from the OS)
+  --config <path>  Read options from an experiment file of `option = value`
+                   lines, a subset of TOML; options given here override it
+
+  -h, --help       Show this message";
+
+const METHODS: [&str; 2] = ["q-learning", "double-q-learning"];
+
+struct Options {
+    experiment: Experiment,
+    out: Option<PathBuf>,
+    jsonl: Option<PathBuf>,
+    seed: Option<u64>,
+}
+
+fn parse_number<T: std::str::FromStr>(flag: &str, value: &str) -> Result<T, String> {
+    value
+        .parse()
+        .map_err(|_| format!("invalid value '{}' for {}", value, flag))
+}
+
+// Parses the arguments after the program name; `None` means --help.
+fn parse_args(args: impl IntoIterator<Item = String>) -> Result<Option<Options>, String> {
+    let mut options = Options {
+        experiment: Experiment::default(),
+        out: None,
+        jsonl: None,
+        seed: None,
+    };
+    let mut args = args.into_iter();
+    while let Some(flag) = args.next() {
+        let mut value = || args.next().ok_or(format!("{} needs a value", flag));
+        match flag.as_str() {
+            "--episodes" => options.experiment.episodes = parse_number(&flag, &value()?)?,
+            "--runs" => match parse_number(&flag, &value()?)? {
+                0 => return Err("--runs must be at least 1".to_string()),
+                runs => options.experiment.runs = runs,
+            },
+            "--alpha" => match parse_number(&flag, &value()?)? {
+                alpha if alpha > 0.0 && alpha <= 1.0 => options.experiment.alpha = alpha,
+                _ => return Err("--alpha must be in (0, 1]".to_string()),
+            },
+            "--epsilon" => match parse_number(&flag, &value()?)? {
+                epsilon if (0.0..=1.0).contains(&epsilon) => options.experiment.epsilon = epsilon,
+                _ => return Err("--epsilon must be between 0 and 1".to_string()),
+            },
+            "--b-actions" => match parse_number(&flag, &value()?)? {
+                0 => return Err("--b-actions must be at least 1".to_string()),
+                actions => options.experiment.b_actions = actions,
+            },
+            "--out" => options.out = Some(PathBuf::from(value()?)),
+            "--jsonl" => options.jsonl = Some(PathBuf::from(value()?)),
+            "--seed" => options.seed = Some(parse_number(&flag, &value()?)?),
+            "-h" | "--help" => return Ok(None),
+            _ => return Err(format!("unknown option '{}'", flag)),
+        }
+    }
+    Ok(Some(options))
+}
+
+fn write_curves(out: &mut dyn Write, curves: &[Vec<f64>], optimal: f64) -> io::Result<()> {
+    writeln!(out, "episode,{},optimal", METHODS.join(","))?;
+    let episodes = curves.first().map_or(0, Vec::len);
+    for episode in 0..episodes {
+        let row: Vec<String> = curves
+            .iter()
+            .map(|curve| format!("{:.2}", 100.0 * curve[episode]))
+            .collect();
+        writeln!(
+            out,
+            "{},{},{:.2}",
+            episode + 1,
+            row.join(","),
+            100.0 * optimal
+        )?;
+    }
+    out.flush()
+}
+
+fn main() {
+    let options = match expand_config(std::env::args().skip(1)).and_then(parse_args) {
+        Ok(Some(options)) => options,
+        Ok(None) => {
+            println!("{}", USAGE);
+            return;
+        }
+        Err(message) => {
+            eprintln!("Error: {}\n\n{}", message, USAGE);
+            process::exit(2);
+        }
+    };
+    let experiment = &options.experiment;
+    // Both methods see the same environments and agent seeds.
+    let curves = [
+        q_learning(experiment, options.seed),
+        double_q_learning(experiment, options.seed),
+    ];
+    let optimal = optimal_left_share(experiment);
+    let written = match &options.out {
+        Some(path) => File::create(path)
+            .and_then(|file| write_curves(&mut BufWriter::new(file), &curves, optimal)),
+        None => write_curves(&mut io::stdout().lock(), &curves, optimal),
+    };
+    if let Err(e) = written {
+        eprintln!("Error: couldn't write the results: {}", e);
+        process::exit(1);
+    }
+    if let Some(path) = &options.jsonl {
+        let written = write_jsonl(path, "maximization-bias", options.seed, |lines| {
+            for (method, curve) in METHODS.iter().zip(&curves) {
+                let config = json!({
+                    "method": method,
+                    "episodes": experiment.episodes,
+                    "runs": experiment.runs,
+                    "alpha": experiment.alpha,
+                    "epsilon": experiment.epsilon,
+                    "b_actions": experiment.b_actions,
+                });
+                for (episode, left) in curve.iter().enumerate() {
+                    lines.record(
+                        "episode",
+                        &config,
+                        json!({ "episode": episode + 1, "left_share": left }),
+                    )?;
+                }
+            }
+            Ok(())
+        });
+        if let Err(e) = written {
+            eprintln!("Error: couldn't write {}: {}", path.display(), e);
+            process::exit(1);
+        }
+    }
+    if let Some(path) = &options.out {
+        println!(
+            "Percentage of left actions from A over {} runs of {} episodes; written to {}",
+            experiment.runs,
+            experiment.episodes,
+            path.display()
+        );
+    }
+    let late = experiment.episodes.saturating_sub(100);
+    for (method, curve) in METHODS.iter().zip(&curves) {
+        let average = curve[late..].iter().sum::<f64>() / (curve.len() - late).max(1) as f64;
+        eprintln!(
+            "{}: went left in {:.1}% of the last {} episodes (optimal {:.1}%)",
+            method,
+            100.0 * average,
+            curve.len() - late,
+            100.0 * optimal
+        );
+    }
+}
@@ -0,0 +1,276 @@
+use std::path::PathBuf;
+
+use rl_core::{parse_values, TraceKind};
+use sutton_random_walk::Method;
+
+pub const USAGE: &str = "Usage: sutton-random-walk <command> [options]
+
+Commands:
+  values       Write TD(0)'s estimates after 0, 1, 10 and 100 episodes
+               alongside the true values as CSV (Example 6.2, left)
+  rms          Write the RMS error after each episode of online TD(0) and
+               constant-alpha Monte Carlo for several step sizes, averaged
+               over runs, as CSV (Example 6.2, right)
+  batch        Write the RMS error after each episode of batch TD(0) and
+               batch Monte Carlo, averaged over runs, as CSV (Figure 6.2)
+  n-step       Write the RMS error of n-step TD over the first episodes,
+               averaged over runs, for every combination of n and step size
+               as CSV, on a walk paying -1 on the left (Figure 7.2)
+  sweep        Rank every combination of n and step size of n-step by its
+               RMS error, running the runs in parallel, and write the
+               ranking as CSV with each error's standard error
+  td-lambda    Write the RMS error of online TD(lambda) over the first
+               episodes, averaged over runs, for every combination of lambda
+               and step size as CSV, on the walk n-step uses (Figure 12.6)
+
+Options:
+  --states <n>     Non-terminal states in the walk (default: 5; n-step, sweep,
+                   td-lambda: 19)
+  --episodes <n>   Episodes per run (default: 100; n-step, sweep, td-lambda:
+                   10)
+  --runs <n>       All but values: independent runs to average (default: 100)
+  --td-alphas <list>
+                   rms: comma-separated TD step sizes (default: 0.15,0.1,0.05)
+  --mc-alphas <list>
+                   rms: comma-separated Monte Carlo step sizes (default:
+                   0.01,0.02,0.03,0.04)
+  --alpha <x>      values: TD step size (default: 0.1); batch: step size of
+                   both methods (default: 0.001)
+  --ns <list>      n-step, sweep: comma-separated step counts (default:
+                   1,2,4,...,512)
+  --alphas <list>  n-step, sweep, td-lambda: comma-separated step sizes or
+                   <start>:<end>:<step> ranges (default: 0.1:1:0.1)
+  --lambdas <list> td-lambda: comma-separated trace decay rates or ranges
+                   (default: 0,0.4,0.8,0.9,0.95,0.975,0.99,1)
+  --traces <kind>  td-lambda: accumulating or replacing (default:
+                   accumulating)
+  --out <path>     Write the CSV to <path> and print a summary instead of
+                   writing the CSV to stdout
+  --plot <path>    Also draw the results as an SVG image at <path> (not sweep)
+  --jsonl <path>   Also write the results to <path> as lines of JSON with
+                   the settings and seed: one per state and episode count
+                   (values), per curve and episode (rms, batch), per n and
+                   step size (n-step, sweep) or per lambda and step size
+                   (td-lambda)
+  --seed <n>       Seed every random number generator, so runs can be
+                   repeated exactly (default: seeded from the OS)
+  --config <path>  Read options from an experiment file of `option = value`
+                   lines, a subset of TOML; options given here override it,
+                   and its `command` is used when none is given here
+
+  -h, --help       Show this message";
+
+#[derive(Debug, PartialEq)]
+pub enum Command {
+    Values {
+        alpha: f64,
+    },
+    Rms {
+        alphas: Vec<(Method, f64)>,
+    },
+    Batch {
+        alpha: f64,
+    },
+    NStep {
+        ns: Vec<usize>,
+        alphas: Vec<f64>,
+    },
+    Sweep {
+        ns: Vec<usize>,
+        alphas: Vec<f64>,
+    },
+    TdLambda {
+        lambdas: Vec<f64>,
+        alphas: Vec<f64>,
+        traces: TraceKind,
+    },
+    Help,
+}
+
+#[derive(Debug)]
+pub struct Cli {
+    pub command: Command,
+    pub states: usize,
+    pub episodes: usize,
+    pub runs: usize,
+    pub out: Option<PathBuf>,
+    pub jsonl: Option<PathBuf>,
+    pub plot: Option<PathBuf>,
+    pub seed: Option<u64>,
+}
+
+fn parse_number<T: std::str::FromStr>(flag: &str, value: &str) -> Result<T, String> {
+    value
+        .parse()
+        .map_err(|_| format!("invalid value '{}' for {}", value, flag))
+}
+
+fn parse_alphas(flag: &str, value: &str) -> Result<Vec<f64>, String> {
+    parse_values(flag, value)?
+        .into_iter()
+        .map(|alpha| match alpha {
+            alpha if alpha > 0.0 && alpha <= 1.0 => Ok(alpha),
+            _ => Err(format!("{} step sizes must be in (0, 1]", flag)),
+        })
+        .collect()
+}
+
+// --plot's path. Charts are only drawn as SVG, and only by builds with the
+// plot feature.
+fn parse_plot_path(value: &str) -> Result<PathBuf, String> {
+    if cfg!(not(feature = "plot")) {
+        return Err("--plot needs a build with the plot feature".to_string());
+    }
+    if !value.ends_with(".svg") {
+        return Err(format!("--plot draws SVG, so '{}' must end in .svg", value));
+    }
+    Ok(PathBuf::from(value))
+}
+
+impl Cli {
+    // Parses the arguments after the program name. Errors are messages for the
+    // user, to be printed above `USAGE`.
+    pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Cli, String> {
+        let mut args = args.into_iter();
+        let mut cli = Cli {
+            command: Command::Help,
+            states: 5,
+            episodes: 100,
+            runs: 100,
+            out: None,
+            jsonl: None,
+            plot: None,
+            seed: None,
+        };
+        let name = match args.next() {
+            Some(name) if name == "-h" || name == "--help" => return Ok(cli),
+            Some(name) => name,
+            None => return Err("missing command".to_string()),
+        };
+        let mut td_alphas = None;
+        let mut mc_alphas = None;
+        let mut alpha = None;
+        let mut ns = None;
+        let mut alphas = None;
+        let mut lambdas = None;
+        let mut traces = None;
+        let mut states = None;
+        let mut episodes = None;
+        let mut runs = false;
+        while let Some(flag) = args.next() {
+            let mut value = || args.next().ok_or(format!("{} needs a value", flag));
+            match flag.as_str() {
+                "--states" => match parse_number(&flag, &value()?)? {
+                    0 => return Err("--states must be at least 1".to_string()),
+                    n => states = Some(n),
+                },
+                "--episodes" => episodes = Some(parse_number(&flag, &value()?)?),
+                "--runs" => match parse_number(&flag, &value()?)? {
+                    0 => return Err("--runs must be at least 1".to_string()),
+                    n => {
+                        cli.runs = n;
+                        runs = true;
+                    }
+                },
+                "--td-alphas" => td_alphas = Some(parse_alphas(&flag, &value()?)?),
+                "--mc-alphas" => mc_alphas = Some(parse_alphas(&flag, &value()?)?),
+                "--alpha" => alpha = Some(parse_alphas(&flag, &value()?)?[0]),
+                "--ns" => {
+                    ns = Some(
+                        value()?
+                            .split(',')
+                            .map(|n| match parse_number(&flag, n)? {
+                                0 => Err("--ns must all be at least 1".to_string()),
+                                n => Ok(n),
+                            })
+                            .collect::<Result<Vec<usize>, String>>()?,
+                    )
+                }
+                "--alphas" => alphas = Some(parse_alphas(&flag, &value()?)?),
+                "--lambdas" => {
+                    lambdas = Some(
+                        parse_values(&flag, &value()?)?
+                            .into_iter()
+                            .map(|lambda| match lambda {
+                                lambda if (0.0..=1.0).contains(&lambda) => Ok(lambda),
+                                _ => Err("--lambdas must all be between 0 and 1".to_string()),
+                            })
+                            .collect::<Result<Vec<f64>, String>>()?,
+                    )
+                }
+                "--traces" => traces = Some(value()?.parse()?),
+                "--out" => cli.out = Some(PathBuf::from(value()?)),
+                "--jsonl" => cli.jsonl = Some(PathBuf::from(value()?)),
+                "--plot" => cli.plot = Some(parse_plot_path(&value()?)?),
+                "--seed" => cli.seed = Some(parse_number(&flag, &value()?)?),
+                "-h" | "--help" => return Ok(cli),
+                _ => return Err(format!("unknown option '{}'", flag)),
+            }
+        }
+        let alpha_lists = td_alphas.is_some() || mc_alphas.is_some();
+        cli.command = match name.as_str() {
+            "values" | "batch" | "n-step" | "sweep" | "td-lambda" if alpha_lists => {
+                return Err(format!("{} does not take --td-alphas or --mc-alphas", name))
+            }
+            "values" | "rms" | "batch" if ns.is_some() || alphas.is_some() => {
+                return Err(format!("{} does not take --ns or --alphas", name))
+            }
+            "td-lambda" if ns.is_some() => return Err("td-lambda does not take --ns".to_string()),
+            "values" | "rms" | "batch" | "n-step" | "sweep"
+                if lambdas.is_some() || traces.is_some() =>
+            {
+                return Err(format!("{} does not take --lambdas or --traces", name))
+            }
+            "n-step" | "sweep" | "td-lambda" if alpha.is_some() => {
+                return Err(format!("{} does not take --alpha; use --alphas", name))
+            }
+            "sweep" if cli.plot.is_some() => return Err("sweep does not take --plot".to_string()),
+            "values" if runs => return Err("values does not take --runs".to_string()),
+            "rms" if alpha.is_some() => {
+                return Err("rms does not take --alpha; use --td-alphas and --mc-alphas".to_string())
+            }
+            "values" => Command::Values {
+                alpha: alpha.unwrap_or(0.1),
+            },
+            "batch" => Command::Batch {
+                alpha: alpha.unwrap_or(0.001),
+            },
+            "n-step" | "sweep" => {
+                cli.states = 19;
+                cli.episodes = 10;
+                let ns = ns.unwrap_or_else(|| (0..10).map(|i| 1 << i).collect());
+                let alphas = alphas.unwrap_or_else(|| (1..=10).map(|i| i as f64 / 10.0).collect());
+                if name == "sweep" {
+                    Command::Sweep { ns, alphas }
+                } else {
+                    Command::NStep { ns, alphas }
+                }
+            }
+            "td-lambda" => {
+                cli.states = 19;
+                cli.episodes = 10;
+                Command::TdLambda {
+                    lambdas: lambdas
+                        .unwrap_or_else(|| vec![0.0, 0.4, 0.8, 0.9, 0.95, 0.975, 0.99, 1.0]),
+                    alphas: alphas.unwrap_or_else(|| (1..=10).map(|i| i as f64 / 10.0).collect()),
+                    traces: traces.unwrap_or(TraceKind::Accumulating),
+                }
+            }
+            "rms" => {
+                let td = td_alphas.unwrap_or_else(|| vec![0.15, 0.1, 0.05]);
+                let mc = mc_alphas.unwrap_or_else(|| vec![0.01, 0.02, 0.03, 0.04]);
+                Command::Rms {
+                    alphas: td
+                        .into_iter()
+                        .map(|alpha| (Method::Td, alpha))
+                        .chain(mc.into_iter().map(|alpha| (Method::MonteCarlo, alpha)))
+                        .collect(),
+                }
+            }
+            _ => return Err(format!("unknown command '{}'", name)),
+        };
+        cli.states = states.unwrap_or(cli.states);
+        cli.episodes = episodes.unwrap_or(cli.episodes);
+        Ok(cli)
+    }
+}
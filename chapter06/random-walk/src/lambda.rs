@@ -0,0 +1,100 @@
+// TD(lambda) prediction (Section 12.2) on the random walk.
+
+use rl_core::{derive_seed, run_episode, EligibilityTraces, Policy, Step, TraceKind};
+
+use crate::RandomWalk;
+
+// Online tabular TD(lambda), undiscounted: every step's TD error
+// R + V(S') - V(S) moves each state's value by `alpha` times its trace.
+// Lambda 0 is TD(0); lambda 1 with accumulating traces approaches
+// every-visit Monte Carlo.
+pub struct TdLambda {
+    pub values: Vec<f64>,
+    pub alpha: f64,
+    lambda: f64,
+    traces: EligibilityTraces,
+}
+
+impl TdLambda {
+    pub fn new(values: Vec<f64>, lambda: f64, alpha: f64, kind: TraceKind) -> Self {
+        TdLambda {
+            traces: EligibilityTraces::new(kind, values.len()),
+            values,
+            alpha,
+            lambda,
+        }
+    }
+}
+
+impl Policy<RandomWalk> for TdLambda {
+    fn select_action(&mut self, _env: &RandomWalk, _state: &usize) {}
+
+    fn observe(&mut self, state: &usize, _action: &(), step: &Step<usize>) {
+        self.traces.visit(&[*state], self.lambda);
+        let error = step.reward + self.values[step.state] - self.values[*state];
+        self.traces.update(&mut self.values, self.alpha * error);
+        if step.done {
+            self.traces.clear();
+        }
+    }
+}
+
+// Figure 12.6's measure of TD(lambda) with step size `alpha`, set up as
+// `n_step_error`: the RMS error after each of the first `episodes` episodes
+// on a walk of `states` states paying -1 on the left, values starting at 0,
+// averaged over the episodes and `runs` independent runs.
+pub fn td_lambda_error(
+    states: usize,
+    lambda: f64,
+    alpha: f64,
+    kind: TraceKind,
+    episodes: usize,
+    runs: usize,
+    seed: Option<u64>,
+) -> f64 {
+    let mut total = 0.0;
+    for run in 0..runs as u64 {
+        let mut walk = RandomWalk::new(states, derive_seed(seed, run)).with_left_reward(-1.0);
+        let mut td = TdLambda::new(vec![0.0; states + 2], lambda, alpha, kind);
+        for _ in 0..episodes {
+            run_episode(&mut walk, &mut td, None);
+            total += walk.rms_error(&td.values);
+        }
+    }
+    total / (episodes * runs).max(1) as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{NStepTd, Observer};
+
+    #[test]
+    fn lambda_zero_matches_one_step_td() {
+        let mut walk = RandomWalk::new(19, Some(1)).with_left_reward(-1.0);
+        let mut n_step = NStepTd::new(vec![0.0; 21], 1, 0.3);
+        let mut lambda = TdLambda::new(vec![0.0; 21], 0.0, 0.3, TraceKind::Accumulating);
+        for _ in 0..5 {
+            let episode = run_episode(&mut walk, &mut Observer, None);
+            for t in 0..episode.len() {
+                let step = Step {
+                    state: episode.states[t + 1],
+                    reward: episode.rewards[t],
+                    done: t + 1 == episode.len(),
+                };
+                n_step.observe(&episode.states[t], &(), &step);
+                lambda.observe(&episode.states[t], &(), &step);
+            }
+        }
+        assert_eq!(n_step.values, lambda.values);
+    }
+
+    #[test]
+    fn intermediate_lambda_does_best_on_the_19_state_walk() {
+        let error =
+            |lambda| td_lambda_error(19, lambda, 0.2, TraceKind::Accumulating, 10, 50, Some(2));
+        assert!(error(0.8) < error(0.0), "{} vs {}", error(0.8), error(0.0));
+        // Alpha 0.2 is far too large for lambda 1, whose estimates diverge.
+        assert!(error(0.8) < error(1.0));
+    }
+}
@@ -0,0 +1,304 @@
+use std::fmt;
+use std::str::FromStr;
+
+use rand::rngs::SmallRng;
+use rand::Rng;
+use rl_core::{derive_seed, run_episode, seeded_rng, Environment, Episode, Policy, Step};
+
+mod lambda;
+mod n_step;
+
+pub use lambda::{td_lambda_error, TdLambda};
+pub use n_step::{n_step_error, NStepTd};
+
+// The random walk of Example 6.2: `states` non-terminal states numbered 1 to
+// `states` between terminals 0 and `states + 1`. Every episode starts in the
+// middle and steps left or right with equal probability; leaving on the right
+// pays +1, leaving on the left pays `left_reward` (0 unless set) and every
+// other step pays 0. There is nothing to choose, so the one action is `()`.
+pub struct RandomWalk {
+    states: usize,
+    left_reward: f64,
+    position: usize,
+    rng: SmallRng,
+}
+
+impl RandomWalk {
+    pub fn new(states: usize, seed: Option<u64>) -> Self {
+        RandomWalk {
+            states,
+            left_reward: 0.0,
+            position: 0,
+            rng: seeded_rng(seed),
+        }
+    }
+
+    // The reward for leaving on the left; Example 7.1's 19-state walk pays -1.
+    pub fn with_left_reward(mut self, left_reward: f64) -> Self {
+        self.left_reward = left_reward;
+        self
+    }
+
+    pub fn states(&self) -> usize {
+        self.states
+    }
+
+    // The value of each state, rising in a straight line from the left
+    // reward to the right one; 0 for the terminals.
+    pub fn true_values(&self) -> Vec<f64> {
+        let ends = (self.states + 1) as f64;
+        let mut values: Vec<f64> = (0..=self.states + 1)
+            .map(|s| self.left_reward + (1.0 - self.left_reward) * s as f64 / ends)
+            .collect();
+        values[0] = 0.0;
+        values[self.states + 1] = 0.0;
+        values
+    }
+
+    // Estimates every method starts from: 0.5 for the non-terminal states and
+    // 0 for the terminals.
+    pub fn initial_values(&self) -> Vec<f64> {
+        let mut values = vec![0.5; self.states + 2];
+        values[0] = 0.0;
+        values[self.states + 1] = 0.0;
+        values
+    }
+
+    // The root-mean-squared error of `values` over the non-terminal states.
+    pub fn rms_error(&self, values: &[f64]) -> f64 {
+        let truth = self.true_values();
+        let squares: f64 = (1..=self.states)
+            .map(|s| (values[s] - truth[s]).powi(2))
+            .sum();
+        (squares / self.states as f64).sqrt()
+    }
+}
+
+impl Environment for RandomWalk {
+    type State = usize;
+    type Action = ();
+
+    fn reset(&mut self) -> usize {
+        self.position = self.states.div_ceil(2);
+        self.position
+    }
+
+    fn step(&mut self, _action: &()) -> Step<usize> {
+        if self.rng.random::<bool>() {
+            self.position += 1;
+        } else {
+            self.position -= 1;
+        }
+        Step {
+            state: self.position,
+            reward: if self.position == self.states + 1 {
+                1.0
+            } else if self.position == 0 {
+                self.left_reward
+            } else {
+                0.0
+            },
+            done: self.position == 0 || self.position == self.states + 1,
+        }
+    }
+
+    fn actions(&self, _state: &usize) -> Vec<()> {
+        vec![()]
+    }
+}
+
+// Online TD(0) (Section 6.1), undiscounted: moves V(S) toward R + V(S') by
+// `alpha` after every step.
+pub struct TdZero {
+    pub values: Vec<f64>,
+    pub alpha: f64,
+}
+
+impl Policy<RandomWalk> for TdZero {
+    fn select_action(&mut self, _env: &RandomWalk, _state: &usize) {}
+
+    fn observe(&mut self, state: &usize, _action: &(), step: &Step<usize>) {
+        let target = step.reward + self.values[step.state];
+        self.values[*state] += self.alpha * (target - self.values[*state]);
+    }
+}
+
+// Follows the walk without learning, for methods that learn from whole
+// episodes.
+pub struct Observer;
+
+impl Policy<RandomWalk> for Observer {
+    fn select_action(&mut self, _env: &RandomWalk, _state: &usize) {}
+}
+
+// Every-visit constant-alpha Monte Carlo (6.1), undiscounted: moves each
+// visited state's value toward the return that followed it.
+pub fn constant_alpha_mc(values: &mut [f64], episode: &Episode<usize, ()>, alpha: f64) {
+    let mut g = 0.0;
+    for t in (0..episode.len()).rev() {
+        g += episode.rewards[t];
+        let state = episode.states[t];
+        values[state] += alpha * (g - values[state]);
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Method {
+    Td,
+    MonteCarlo,
+}
+
+impl Method {
+    pub fn all() -> [Method; 2] {
+        [Method::Td, Method::MonteCarlo]
+    }
+}
+
+impl fmt::Display for Method {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Method::Td => "td",
+            Method::MonteCarlo => "mc",
+        })
+    }
+}
+
+impl FromStr for Method {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        match s {
+            "td" => Ok(Method::Td),
+            "mc" => Ok(Method::MonteCarlo),
+            _ => Err(format!("unknown method '{}' (expected td or mc)", s)),
+        }
+    }
+}
+
+// Estimates after each of `episodes` episodes of online learning by `method`
+// with step size `alpha`, the initial estimates first.
+pub fn learn(walk: &mut RandomWalk, method: Method, alpha: f64, episodes: usize) -> Vec<Vec<f64>> {
+    let mut td = TdZero {
+        values: walk.initial_values(),
+        alpha,
+    };
+    let mut history = vec![td.values.clone()];
+    for _ in 0..episodes {
+        match method {
+            Method::Td => {
+                run_episode(walk, &mut td, None);
+            }
+            Method::MonteCarlo => {
+                let episode = run_episode(walk, &mut Observer, None);
+                constant_alpha_mc(&mut td.values, &episode, alpha);
+            }
+        }
+        history.push(td.values.clone());
+    }
+    history
+}
+
+// Batch updating (Section 6.3): after each episode, sweeps every episode seen
+// so far, summing the increments `method` would make with step size `alpha`,
+// and applies the sums until they total less than 1e-3. Returns the
+// estimates after each episode, the initial estimates first.
+pub fn learn_batch(
+    walk: &mut RandomWalk,
+    method: Method,
+    alpha: f64,
+    episodes: usize,
+) -> Vec<Vec<f64>> {
+    let mut values = walk.initial_values();
+    let mut history = vec![values.clone()];
+    let mut seen = Vec::with_capacity(episodes);
+    for _ in 0..episodes {
+        seen.push(run_episode(walk, &mut Observer, None));
+        loop {
+            let mut increments = vec![0.0; values.len()];
+            for episode in &seen {
+                let mut g = episode.total_reward();
+                for t in 0..episode.len() {
+                    let state = episode.states[t];
+                    let target = match method {
+                        Method::Td => episode.rewards[t] + values[episode.states[t + 1]],
+                        Method::MonteCarlo => g,
+                    };
+                    g -= episode.rewards[t];
+                    increments[state] += alpha * (target - values[state]);
+                }
+            }
+            for (value, increment) in values.iter_mut().zip(&increments) {
+                *value += increment;
+            }
+            if increments.iter().map(|i| i.abs()).sum::<f64>() < 1e-3 {
+                break;
+            }
+        }
+        history.push(values.clone());
+    }
+    history
+}
+
+// The RMS error after each episode, the initial estimates first, averaged
+// over `runs` independent runs of `learn` (or `learn_batch` when `batch`).
+pub fn average_rms_errors(
+    states: usize,
+    method: Method,
+    alpha: f64,
+    episodes: usize,
+    runs: usize,
+    batch: bool,
+    seed: Option<u64>,
+) -> Vec<f64> {
+    let mut errors = vec![0.0; episodes + 1];
+    for run in 0..runs as u64 {
+        let mut walk = RandomWalk::new(states, derive_seed(seed, run));
+        let history = if batch {
+            learn_batch(&mut walk, method, alpha, episodes)
+        } else {
+            learn(&mut walk, method, alpha, episodes)
+        };
+        for (error, values) in errors.iter_mut().zip(&history) {
+            *error += walk.rms_error(values) / runs as f64;
+        }
+    }
+    errors
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn walks_start_in_the_middle_and_pay_on_the_right() {
+        let mut walk = RandomWalk::new(5, Some(1));
+        for _ in 0..20 {
+            let episode = run_episode(&mut walk, &mut Observer, None);
+            assert_eq!(episode.states[0], 3);
+            let last = *episode.states.last().unwrap();
+            assert!(last == 0 || last == 6);
+            assert_eq!(episode.total_reward(), if last == 6 { 1.0 } else { 0.0 });
+        }
+        assert_eq!(walk.true_values()[1], 1.0 / 6.0);
+        assert_eq!(walk.rms_error(&walk.true_values()), 0.0);
+    }
+
+    #[test]
+    fn td_beats_monte_carlo_on_the_walk() {
+        let td = average_rms_errors(5, Method::Td, 0.05, 100, 50, false, Some(2));
+        let mc = average_rms_errors(5, Method::MonteCarlo, 0.02, 100, 50, false, Some(2));
+        assert!((td[0] - mc[0]).abs() < 1e-12);
+        assert!(td[100] < mc[100], "{} vs {}", td[100], mc[100]);
+        assert!(td[100] < 0.1);
+    }
+
+    #[test]
+    fn batch_td_converges_to_the_certainty_equivalence_estimate() {
+        let mut walk = RandomWalk::new(5, Some(4));
+        let history = learn_batch(&mut walk, Method::Td, 0.001, 30);
+        let mut walk = RandomWalk::new(5, Some(4));
+        let mc = learn_batch(&mut walk, Method::MonteCarlo, 0.001, 30);
+        // Both see the same episodes, but only TD exploits the Markov structure.
+        assert!(walk.rms_error(&history[30]) < walk.rms_error(&mc[30]));
+    }
+}
@@ -0,0 +1,435 @@
+mod cli;
+#[cfg(feature = "plot")]
+mod plot;
+
+use std::fs::File;
+use std::io::{self, BufWriter};
+use std::path::Path;
+use std::process;
+
+use cli::{Cli, Command, USAGE};
+use rl_core::{
+    expand_config, json, write_jsonl, write_sweep_table, Goal, JsonLines, Sweep, TraceKind,
+};
+use sutton_random_walk::*;
+
+// Writes `write` to `out`, or to stdout when it is `None`, exiting on errors.
+fn write_output(out: Option<&Path>, write: impl FnOnce(&mut dyn io::Write) -> io::Result<()>) {
+    let written = match out {
+        Some(path) => File::create(path).and_then(|file| write(&mut BufWriter::new(file))),
+        None => write(&mut io::stdout().lock()),
+    };
+    if let Err(e) = written {
+        eprintln!("Error: couldn't write the results: {}", e);
+        process::exit(1);
+    }
+}
+
+// Writes JSON Lines records to `cli.jsonl` with `write`, if it is set,
+// exiting on errors.
+fn write_records(
+    cli: &Cli,
+    experiment: &str,
+    write: impl FnOnce(&mut JsonLines) -> io::Result<()>,
+) {
+    let Some(path) = &cli.jsonl else { return };
+    if let Err(e) = write_jsonl(path, experiment, cli.seed, write) {
+        eprintln!("Error: couldn't write {}: {}", path.display(), e);
+        process::exit(1);
+    }
+}
+
+// Writes one row per episode count, from 0, with a column per curve.
+fn write_curves(
+    writer: &mut dyn io::Write,
+    names: &[String],
+    curves: &[Vec<f64>],
+) -> io::Result<()> {
+    writeln!(writer, "episode,{}", names.join(","))?;
+    let episodes = curves.first().map_or(0, Vec::len);
+    for episode in 0..episodes {
+        let row: Vec<String> = curves
+            .iter()
+            .map(|curve| format!("{:.6}", curve[episode]))
+            .collect();
+        writeln!(writer, "{},{}", episode, row.join(","))?;
+    }
+    Ok(())
+}
+
+// State names A, B, C, ... as in the book.
+fn state_name(state: usize) -> String {
+    match u8::try_from(state - 1) {
+        Ok(i) if i < 26 => char::from(b'A' + i).to_string(),
+        _ => state.to_string(),
+    }
+}
+
+fn values(cli: &Cli, alpha: f64) {
+    let mut walk = RandomWalk::new(cli.states, cli.seed);
+    let history = learn(&mut walk, Method::Td, alpha, cli.episodes);
+    let checkpoints: Vec<usize> = [0, 1, 10, 100]
+        .into_iter()
+        .filter(|&n| n < cli.episodes)
+        .chain([cli.episodes])
+        .collect();
+    let truth = walk.true_values();
+    write_output(cli.out.as_deref(), |writer| {
+        let names: Vec<String> = checkpoints
+            .iter()
+            .map(|n| format!("episodes_{}", n))
+            .collect();
+        writeln!(writer, "state,true,{}", names.join(","))?;
+        for state in 1..=cli.states {
+            let row: Vec<String> = checkpoints
+                .iter()
+                .map(|&n| format!("{:.6}", history[n][state]))
+                .collect();
+            writeln!(
+                writer,
+                "{},{:.6},{}",
+                state_name(state),
+                truth[state],
+                row.join(",")
+            )?;
+        }
+        Ok(())
+    });
+    write_records(cli, "random-walk/values", |lines| {
+        let config = json!({ "alpha": alpha, "states": cli.states, "episodes": cli.episodes });
+        for &n in &checkpoints {
+            for state in 1..=cli.states {
+                lines.record(
+                    "state",
+                    &config,
+                    json!({
+                        "episodes": n,
+                        "state": state_name(state),
+                        "true": truth[state],
+                        "estimate": history[n][state],
+                    }),
+                )?;
+            }
+        }
+        Ok(())
+    });
+    #[cfg(feature = "plot")]
+    if let Some(path) = &cli.plot {
+        plot::values(path, &checkpoints, &history, &truth);
+    }
+    let Some(path) = &cli.out else { return };
+    println!(
+        "TD(0) estimates with alpha = {}; written to {}",
+        alpha,
+        path.display()
+    );
+    for &n in &checkpoints {
+        println!(
+            "  after {:4} episodes: RMS error {:.4}",
+            n,
+            walk.rms_error(&history[n])
+        );
+    }
+}
+
+fn rms(cli: &Cli, alphas: &[(Method, f64)], batch: bool) {
+    let curves: Vec<Vec<f64>> = alphas
+        .iter()
+        .map(|&(method, alpha)| {
+            average_rms_errors(
+                cli.states,
+                method,
+                alpha,
+                cli.episodes,
+                cli.runs,
+                batch,
+                cli.seed,
+            )
+        })
+        .collect();
+    let names: Vec<String> = alphas
+        .iter()
+        .map(|(method, alpha)| {
+            if batch {
+                method.to_string()
+            } else {
+                format!("{}_{}", method, alpha)
+            }
+        })
+        .collect();
+    write_output(cli.out.as_deref(), |writer| {
+        write_curves(writer, &names, &curves)
+    });
+    let experiment = if batch {
+        "random-walk/batch"
+    } else {
+        "random-walk/rms"
+    };
+    write_records(cli, experiment, |lines| {
+        for (&(method, alpha), curve) in alphas.iter().zip(&curves) {
+            let config = json!({
+                "method": method.to_string(),
+                "alpha": alpha,
+                "states": cli.states,
+                "episodes": cli.episodes,
+                "runs": cli.runs,
+            });
+            for (episode, error) in curve.iter().enumerate() {
+                lines.record(
+                    "episode",
+                    &config,
+                    json!({ "episode": episode, "rms_error": error }),
+                )?;
+            }
+        }
+        Ok(())
+    });
+    #[cfg(feature = "plot")]
+    if let Some(path) = &cli.plot {
+        let title = if batch {
+            "Batch training"
+        } else {
+            "Online TD(0) and Monte Carlo"
+        };
+        plot::curves(path, title, &names, &curves);
+    }
+    let Some(path) = &cli.out else { return };
+    println!(
+        "{}RMS error averaged over {} runs of {} episodes; written to {}",
+        if batch { "Batch updating: " } else { "" },
+        cli.runs,
+        cli.episodes,
+        path.display()
+    );
+    for (name, curve) in names.iter().zip(&curves) {
+        println!(
+            "  {:<8} final RMS error {:.4}",
+            name,
+            curve.last().copied().unwrap_or(0.0)
+        );
+    }
+}
+
+fn n_step(cli: &Cli, ns: &[usize], alphas: &[f64]) {
+    let errors: Vec<Vec<f64>> = ns
+        .iter()
+        .map(|&n| {
+            alphas
+                .iter()
+                .map(|&alpha| n_step_error(cli.states, n, alpha, cli.episodes, cli.runs, cli.seed))
+                .collect()
+        })
+        .collect();
+    write_output(cli.out.as_deref(), |writer| {
+        writeln!(writer, "n,alpha,rms")?;
+        for (n, row) in ns.iter().zip(&errors) {
+            for (alpha, error) in alphas.iter().zip(row) {
+                writeln!(writer, "{},{},{:.6}", n, alpha, error)?;
+            }
+        }
+        Ok(())
+    });
+    write_records(cli, "random-walk/n-step", |lines| {
+        for (n, row) in ns.iter().zip(&errors) {
+            for (alpha, error) in alphas.iter().zip(row) {
+                let config = json!({
+                    "n": n,
+                    "alpha": alpha,
+                    "states": cli.states,
+                    "episodes": cli.episodes,
+                    "runs": cli.runs,
+                });
+                lines.record("evaluation", &config, json!({ "rms_error": error }))?;
+            }
+        }
+        Ok(())
+    });
+    #[cfg(feature = "plot")]
+    if let Some(path) = &cli.plot {
+        let names: Vec<String> = ns.iter().map(|n| format!("n = {}", n)).collect();
+        plot::by_step_size(path, "n-step TD", &names, alphas, &errors);
+    }
+    let Some(path) = &cli.out else { return };
+    println!(
+        "n-step TD on a {}-state walk: RMS error over the first {} episodes, averaged over {} runs; written to {}",
+        cli.states,
+        cli.episodes,
+        cli.runs,
+        path.display()
+    );
+    for (n, row) in ns.iter().zip(&errors) {
+        let best = (0..row.len()).min_by(|&a, &b| row[a].total_cmp(&row[b]));
+        if let Some(best) = best {
+            println!(
+                "  n = {:3}: best alpha {} (RMS error {:.4})",
+                n, alphas[best], row[best]
+            );
+        }
+    }
+}
+
+// n-step's grid as a ranked sweep: each run is a trial with its own seed, the
+// same seeds n-step uses, so the errors match its table.
+fn sweep(cli: &Cli, ns: &[usize], alphas: &[f64]) {
+    let ranked = Sweep::new(Goal::Minimize)
+        .with_parameter("n", ns.iter().map(|&n| n as f64).collect())
+        .with_parameter("alpha", alphas.to_vec())
+        .with_seeds(cli.runs, cli.seed)
+        .run(|configuration, seed| {
+            let n = configuration.get("n") as usize;
+            n_step_error(
+                cli.states,
+                n,
+                configuration.get("alpha"),
+                cli.episodes,
+                1,
+                seed,
+            )
+        });
+    write_output(cli.out.as_deref(), |writer| {
+        write_sweep_table(&ranked, "rms_error", writer)
+    });
+    write_records(cli, "random-walk/sweep", |lines| {
+        for (rank, entry) in ranked.iter().enumerate() {
+            let config = json!({
+                "n": entry.configuration.get("n"),
+                "alpha": entry.configuration.get("alpha"),
+                "states": cli.states,
+                "episodes": cli.episodes,
+                "runs": cli.runs,
+            });
+            let metrics = json!({
+                "rank": rank + 1,
+                "rms_error": entry.mean,
+                "std_error": entry.std_error,
+            });
+            lines.record("configuration", &config, metrics)?;
+        }
+        Ok(())
+    });
+    let Some(path) = &cli.out else { return };
+    println!(
+        "n-step TD on a {}-state walk: {} settings ranked by RMS error over the first {} episodes, averaged over {} runs; written to {}",
+        cli.states,
+        ranked.len(),
+        cli.episodes,
+        cli.runs,
+        path.display()
+    );
+    for (rank, entry) in ranked.iter().take(5).enumerate() {
+        println!(
+            "  {}. n = {:3}, alpha {}: RMS error {:.4} (± {:.4})",
+            rank + 1,
+            entry.configuration.get("n"),
+            entry.configuration.get("alpha"),
+            entry.mean,
+            entry.std_error
+        );
+    }
+}
+
+fn td_lambda(cli: &Cli, lambdas: &[f64], alphas: &[f64], traces: TraceKind) {
+    let errors: Vec<Vec<f64>> = lambdas
+        .iter()
+        .map(|&lambda| {
+            alphas
+                .iter()
+                .map(|&alpha| {
+                    td_lambda_error(
+                        cli.states,
+                        lambda,
+                        alpha,
+                        traces,
+                        cli.episodes,
+                        cli.runs,
+                        cli.seed,
+                    )
+                })
+                .collect()
+        })
+        .collect();
+    write_output(cli.out.as_deref(), |writer| {
+        writeln!(writer, "lambda,alpha,rms")?;
+        for (lambda, row) in lambdas.iter().zip(&errors) {
+            for (alpha, error) in alphas.iter().zip(row) {
+                writeln!(writer, "{},{},{:.6}", lambda, alpha, error)?;
+            }
+        }
+        Ok(())
+    });
+    write_records(cli, "random-walk/td-lambda", |lines| {
+        for (lambda, row) in lambdas.iter().zip(&errors) {
+            for (alpha, error) in alphas.iter().zip(row) {
+                let config = json!({
+                    "lambda": lambda,
+                    "alpha": alpha,
+                    "traces": traces.to_string(),
+                    "states": cli.states,
+                    "episodes": cli.episodes,
+                    "runs": cli.runs,
+                });
+                lines.record("evaluation", &config, json!({ "rms_error": error }))?;
+            }
+        }
+        Ok(())
+    });
+    #[cfg(feature = "plot")]
+    if let Some(path) = &cli.plot {
+        let names: Vec<String> = lambdas.iter().map(|l| format!("lambda = {}", l)).collect();
+        // Estimates start at 0 and the true values lie in [-1, 1], so errors
+        // above 1 mean the estimates diverged; they're left off the chart
+        // rather than stretching its scale.
+        let clipped: Vec<Vec<f64>> = errors
+            .iter()
+            .map(|row| {
+                row.iter()
+                    .map(|&error| if error <= 1.0 { error } else { f64::NAN })
+                    .collect()
+            })
+            .collect();
+        plot::by_step_size(path, "Online TD(lambda)", &names, alphas, &clipped);
+    }
+    let Some(path) = &cli.out else { return };
+    println!(
+        "TD(lambda) with {} traces on a {}-state walk: RMS error over the first {} episodes, averaged over {} runs; written to {}",
+        traces,
+        cli.states,
+        cli.episodes,
+        cli.runs,
+        path.display()
+    );
+    for (lambda, row) in lambdas.iter().zip(&errors) {
+        let best = (0..row.len())
+            .filter(|&a| row[a].is_finite())
+            .min_by(|&a, &b| row[a].total_cmp(&row[b]));
+        match best {
+            Some(best) => println!(
+                "  lambda = {:5}: best alpha {} (RMS error {:.4})",
+                lambda, alphas[best], row[best]
+            ),
+            None => println!("  lambda = {:5}: diverged at every step size", lambda),
+        }
+    }
+}
+
+fn main() {
+    let cli = expand_config(std::env::args().skip(1))
+        .and_then(Cli::parse)
+        .unwrap_or_else(|message| {
+            eprintln!("Error: {}\n\n{}", message, USAGE);
+            process::exit(2);
+        });
+    match &cli.command {
+        Command::Values { alpha } => values(&cli, *alpha),
+        Command::Rms { alphas } => rms(&cli, alphas, false),
+        Command::Batch { alpha } => rms(&cli, &Method::all().map(|method| (method, *alpha)), true),
+        Command::NStep { ns, alphas } => n_step(&cli, ns, alphas),
+        Command::Sweep { ns, alphas } => sweep(&cli, ns, alphas),
+        Command::TdLambda {
+            lambdas,
+            alphas,
+            traces,
+        } => td_lambda(&cli, lambdas, alphas, *traces),
+        Command::Help => println!("{}", USAGE),
+    }
+}
@@ -0,0 +1,129 @@
+// n-step TD prediction (Section 7.1) on the random walk.
+
+use std::collections::VecDeque;
+
+use rl_core::{derive_seed, run_episode, Policy, Step};
+
+use crate::RandomWalk;
+
+// Online n-step TD, undiscounted: once n rewards have followed a state, moves
+// its value toward their sum plus the value of the state reached, and at the
+// end of an episode moves every state still waiting toward the rewards that
+// followed it.
+pub struct NStepTd {
+    pub values: Vec<f64>,
+    pub alpha: f64,
+    n: usize,
+    // S(tau) and R(tau+1) for every state not yet updated, oldest first.
+    pending: VecDeque<(usize, f64)>,
+}
+
+impl NStepTd {
+    pub fn new(values: Vec<f64>, n: usize, alpha: f64) -> Self {
+        NStepTd {
+            values,
+            alpha,
+            n,
+            pending: VecDeque::with_capacity(n),
+        }
+    }
+
+    fn update_oldest(&mut self, bootstrap: f64) {
+        let g = self.pending.iter().map(|&(_, reward)| reward).sum::<f64>() + bootstrap;
+        if let Some((state, _)) = self.pending.pop_front() {
+            self.values[state] += self.alpha * (g - self.values[state]);
+        }
+    }
+}
+
+impl Policy<RandomWalk> for NStepTd {
+    fn select_action(&mut self, _env: &RandomWalk, _state: &usize) {}
+
+    fn observe(&mut self, state: &usize, _action: &(), step: &Step<usize>) {
+        self.pending.push_back((*state, step.reward));
+        if step.done {
+            while !self.pending.is_empty() {
+                self.update_oldest(0.0);
+            }
+        } else if self.pending.len() == self.n {
+            self.update_oldest(self.values[step.state]);
+        }
+    }
+}
+
+// Figure 7.2's measure of n-step TD with step size `alpha` on a walk of
+// `states` states that pays -1 on the left: the RMS error after each of the
+// first `episodes` episodes, with values starting at 0, averaged over the
+// episodes and `runs` independent runs.
+pub fn n_step_error(
+    states: usize,
+    n: usize,
+    alpha: f64,
+    episodes: usize,
+    runs: usize,
+    seed: Option<u64>,
+) -> f64 {
+    let mut total = 0.0;
+    for run in 0..runs as u64 {
+        let mut walk = RandomWalk::new(states, derive_seed(seed, run)).with_left_reward(-1.0);
+        let mut td = NStepTd::new(vec![0.0; states + 2], n, alpha);
+        for _ in 0..episodes {
+            run_episode(&mut walk, &mut td, None);
+            total += walk.rms_error(&td.values);
+        }
+    }
+    total / (episodes * runs).max(1) as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Observer, TdZero};
+
+    #[test]
+    fn one_step_matches_td_zero() {
+        let mut walk = RandomWalk::new(19, Some(1)).with_left_reward(-1.0);
+        let mut n_step = NStepTd::new(vec![0.0; 21], 1, 0.3);
+        let mut td = TdZero {
+            values: vec![0.0; 21],
+            alpha: 0.3,
+        };
+        for _ in 0..5 {
+            let episode = run_episode(&mut walk, &mut Observer, None);
+            for t in 0..episode.len() {
+                let step = Step {
+                    state: episode.states[t + 1],
+                    reward: episode.rewards[t],
+                    done: t + 1 == episode.len(),
+                };
+                n_step.observe(&episode.states[t], &(), &step);
+                td.observe(&episode.states[t], &(), &step);
+            }
+        }
+        assert_eq!(n_step.values, td.values);
+    }
+
+    #[test]
+    fn long_steps_update_toward_the_whole_return() {
+        // With n beyond the episode's length, each state moves toward the
+        // final reward, as in constant-alpha Monte Carlo.
+        let mut td = NStepTd::new(vec![0.0; 5], 10, 0.5);
+        let steps = [(2, 1, 0.0), (1, 2, 0.0), (2, 3, 0.0), (3, 4, 1.0)];
+        for (t, &(state, next, reward)) in steps.iter().enumerate() {
+            let step = Step {
+                state: next,
+                reward,
+                done: t == steps.len() - 1,
+            };
+            td.observe(&state, &(), &step);
+        }
+        assert_eq!(td.values, vec![0.0, 0.5, 0.75, 0.5, 0.0]);
+    }
+
+    #[test]
+    fn intermediate_n_does_best_on_the_19_state_walk() {
+        let error = |n| n_step_error(19, n, 0.4, 10, 50, Some(2));
+        assert!(error(4) < error(1), "{} vs {}", error(4), error(1));
+        assert!(error(4) < error(64));
+    }
+}
@@ -0,0 +1,65 @@
+use std::path::Path;
+use std::process;
+
+use rl_core::{write_svg, Chart};
+
+// Writes `chart` to `path`, exiting on errors.
+fn write(path: &Path, chart: Chart) {
+    if let Err(e) = write_svg(path, &[chart]) {
+        eprintln!("Error: couldn't write {}: {}", path.display(), e);
+        process::exit(1);
+    }
+}
+
+// The estimates after each of `checkpoints` episodes and the true values,
+// across the states, as in the left of Example 6.2. `history` and `truth`
+// are indexed by state, terminal states included.
+pub fn values(path: &Path, checkpoints: &[usize], history: &[Vec<f64>], truth: &[f64]) {
+    let states = 1..truth.len() - 1;
+    let mut chart = Chart::new("TD(0) estimates", "State", "Estimated value");
+    for &n in checkpoints {
+        chart = chart.with_series(
+            &format!("after {} episodes", n),
+            states
+                .clone()
+                .map(|state| (state as f64, history[n][state])),
+        );
+    }
+    chart = chart.with_series(
+        "true values",
+        states.map(|state| (state as f64, truth[state])),
+    );
+    write(path, chart);
+}
+
+// RMS error after each episode, from 0, one line per curve, as in the right
+// of Example 6.2 and Figure 6.2.
+pub fn curves(path: &Path, title: &str, names: &[String], curves: &[Vec<f64>]) {
+    let mut chart = Chart::new(title, "Episodes", "RMS error, averaged over states");
+    for (name, curve) in names.iter().zip(curves) {
+        chart = chart.with_series(
+            name,
+            curve
+                .iter()
+                .enumerate()
+                .map(|(episode, &error)| (episode as f64, error)),
+        );
+    }
+    write(path, chart);
+}
+
+// An RMS error against the step size for each of a parameter's values, one
+// line per value named `names`, as in Figures 7.2 and 12.6.
+pub fn by_step_size(
+    path: &Path,
+    title: &str,
+    names: &[String],
+    alphas: &[f64],
+    errors: &[Vec<f64>],
+) {
+    let mut chart = Chart::new(title, "alpha", "Average RMS error over the first episodes");
+    for (name, row) in names.iter().zip(errors) {
+        chart = chart.with_series(name, alphas.iter().copied().zip(row.iter().copied()));
+    }
+    write(path, chart);
+}
@@ -0,0 +1,199 @@
+use rand::rngs::SmallRng;
+use rand::Rng;
+use rl_core::{seeded_rng, Environment, Step};
+
+pub const ROWS: usize = 7;
+pub const COLS: usize = 10;
+// Cells the wind pushes the agent up in each column.
+pub const WIND: [usize; COLS] = [0, 0, 0, 1, 1, 1, 2, 2, 1, 0];
+const START: (usize, usize) = (3, 0);
+const GOAL: (usize, usize) = (3, 7);
+
+// Up, down, left, right, then the diagonals and staying put.
+const MOVES: [(isize, isize); 9] = [
+    (-1, 0),
+    (1, 0),
+    (0, -1),
+    (0, 1),
+    (-1, -1),
+    (-1, 1),
+    (1, -1),
+    (1, 1),
+    (0, 0),
+];
+pub const ARROWS: [char; 9] = ['↑', '↓', '←', '→', '↖', '↗', '↙', '↘', '·'];
+
+// Which moves the agent has.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Moves {
+    // The four of Example 6.5.
+    Standard,
+    // The eight king's moves of Exercise 6.9.
+    Kings,
+    // King's moves plus staying put, Exercise 6.9's ninth action.
+    KingsAndStay,
+}
+
+impl Moves {
+    pub fn count(self) -> usize {
+        match self {
+            Moves::Standard => 4,
+            Moves::Kings => 8,
+            Moves::KingsAndStay => 9,
+        }
+    }
+}
+
+// The windy gridworld of Example 6.5: every step costs -1 until the goal, and
+// the wind in the column the agent leaves pushes it up on top of its move.
+// Moves off the grid stop at the edge. With stochastic wind (Exercise 6.10),
+// a windy column's push is one more or one less than usual a third of the
+// time each. Cells are numbered in row-major order.
+pub struct WindyGridworld {
+    moves: Moves,
+    stochastic_wind: bool,
+    rng: SmallRng,
+    position: usize,
+}
+
+impl WindyGridworld {
+    pub fn new(moves: Moves, seed: Option<u64>) -> Self {
+        WindyGridworld {
+            moves,
+            stochastic_wind: false,
+            rng: seeded_rng(seed),
+            position: cell(START),
+        }
+    }
+
+    pub fn with_stochastic_wind(mut self, stochastic_wind: bool) -> Self {
+        self.stochastic_wind = stochastic_wind;
+        self
+    }
+
+    pub fn moves(&self) -> Moves {
+        self.moves
+    }
+
+    pub fn start(&self) -> usize {
+        cell(START)
+    }
+
+    pub fn goal(&self) -> usize {
+        cell(GOAL)
+    }
+
+    // Where `action` leads from `from` when the wind pushes `wind` cells up.
+    pub fn destination(from: usize, action: usize, wind: isize) -> usize {
+        let (dr, dc) = MOVES[action];
+        let row = (from / COLS) as isize + dr - wind;
+        let col = (from % COLS) as isize + dc;
+        cell((
+            row.clamp(0, ROWS as isize - 1) as usize,
+            col.clamp(0, COLS as isize - 1) as usize,
+        ))
+    }
+}
+
+fn cell((row, col): (usize, usize)) -> usize {
+    row * COLS + col
+}
+
+impl Environment for WindyGridworld {
+    type State = usize;
+    type Action = usize;
+
+    fn reset(&mut self) -> usize {
+        self.position = cell(START);
+        self.position
+    }
+
+    fn step(&mut self, action: &usize) -> Step<usize> {
+        let mut wind = WIND[self.position % COLS] as isize;
+        if self.stochastic_wind && wind > 0 {
+            wind += self.rng.random_range(-1..=1i32) as isize;
+        }
+        self.position = WindyGridworld::destination(self.position, *action, wind);
+        Step {
+            state: self.position,
+            reward: -1.0,
+            done: self.position == cell(GOAL),
+        }
+    }
+
+    fn actions(&self, _state: &usize) -> Vec<usize> {
+        (0..self.moves.count()).collect()
+    }
+}
+
+// The grid with the path drawn as arrows for the action taken in each cell,
+// S and G marking the start and goal.
+pub fn format_path(states: &[usize], actions: &[usize]) -> String {
+    let mut grid = vec!['.'; ROWS * COLS];
+    for (&state, &action) in states.iter().zip(actions) {
+        grid[state] = ARROWS[action];
+    }
+    grid[cell(START)] = 'S';
+    grid[cell(GOAL)] = 'G';
+    let mut lines: Vec<String> = grid
+        .chunks(COLS)
+        .map(|row| row.iter().map(|c| format!(" {}", c)).collect())
+        .collect();
+    lines.push(WIND.iter().map(|w| format!(" {}", w)).collect());
+    lines.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rl_core::{run_episode, TdAgent, TdControl};
+
+    #[test]
+    fn wind_pushes_up_and_the_edges_hold() {
+        // Right from (3, 6), in a column with wind 2, lands on (1, 7).
+        assert_eq!(
+            WindyGridworld::destination(cell((3, 6)), 3, 2),
+            cell((1, 7))
+        );
+        assert_eq!(
+            WindyGridworld::destination(cell((0, 6)), 3, 2),
+            cell((0, 7))
+        );
+        assert_eq!(
+            WindyGridworld::destination(cell((6, 0)), 7, 0),
+            cell((6, 1))
+        );
+        assert_eq!(
+            WindyGridworld::destination(cell((3, 0)), 2, 0),
+            cell((3, 0))
+        );
+    }
+
+    // Trains Sarsa as in Figure 6.3 and returns the average length of its last
+    // 50 episodes.
+    fn late_episode_length(moves: Moves) -> f64 {
+        let mut env = WindyGridworld::new(moves, Some(1));
+        let mut agent = TdAgent::new(
+            TdControl::Sarsa,
+            ROWS * COLS,
+            moves.count(),
+            0.5,
+            0.1,
+            Some(2),
+        );
+        let lengths: Vec<usize> = (0..500)
+            .map(|_| run_episode(&mut env, &mut agent, None).len())
+            .collect();
+        lengths[450..].iter().sum::<usize>() as f64 / 50.0
+    }
+
+    #[test]
+    fn sarsa_approaches_the_shortest_paths() {
+        // The shortest paths take 15 steps with four moves and 7 with king's
+        // moves; exploring adds a few.
+        let standard = late_episode_length(Moves::Standard);
+        assert!((15.0..20.0).contains(&standard), "{}", standard);
+        let kings = late_episode_length(Moves::Kings);
+        assert!((7.0..11.0).contains(&kings), "{}", kings);
+    }
+}
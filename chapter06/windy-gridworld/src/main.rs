@@ -0,0 +1,209 @@
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::PathBuf;
+use std::process;
+
+use rl_core::{
+    derive_seed, expand_config, json, run_episode, write_jsonl, Environment, TdAgent, TdControl,
+};
+use sutton_windy_gridworld::*;
+
+const USAGE: &str = "Usage: sutton-windy-gridworld [options]
+
+Trains epsilon-greedy Sarsa on the windy gridworld of Example 6.5 and writes,
+for each episode, the time steps taken by its end as CSV (Figure 6.3). Then
+prints the greedy path the agent learned.
+
+Options:
+  --king-moves     Allow diagonal moves too (Exercise 6.9)
+  --stay           With --king-moves, also allow staying put (Exercise 6.9)
+  --stochastic-wind
+                   Make the wind in windy columns one stronger or weaker a
+                   third of the time each (Exercise 6.10)
+  --episodes <n>   Episodes to train for (default: 170)
+  --alpha <x>      Step size (default: 0.5)
+  --epsilon <x>    Exploration rate (default: 0.1)
+  --out <path>     Write the CSV to <path> instead of stdout
+  --jsonl <path>   Also write each episode to <path> as a line of JSON with
+                   the settings and seed
+  --seed <n>       Seed every random number generator, so runs can be
+                   repeated exactly (default: seeded from the OS)
+  --config <path>  Read options from an experiment file of `option = value`
+                   lines, a subset of TOML; options given here override it
+
+  -h, --help       Show this message";
+
+struct Options {
+    moves: Moves,
+    stochastic_wind: bool,
+    episodes: usize,
+    alpha: f64,
+    epsilon: f64,
+    out: Option<PathBuf>,
+    jsonl: Option<PathBuf>,
+    seed: Option<u64>,
+}
+
+// Parses the arguments after the program name; `None` means --help.
+fn parse_args(args: impl IntoIterator<Item = String>) -> Result<Option<Options>, String> {
+    let mut options = Options {
+        moves: Moves::Standard,
+        stochastic_wind: false,
+        episodes: 170,
+        alpha: 0.5,
+        epsilon: 0.1,
+        out: None,
+        jsonl: None,
+        seed: None,
+    };
+    let mut kings = false;
+    let mut stay = false;
+    let mut args = args.into_iter();
+    while let Some(flag) = args.next() {
+        let mut value = || args.next().ok_or(format!("{} needs a value", flag));
+        let invalid = |value: &str| format!("invalid value '{}' for {}", value, flag);
+        match flag.as_str() {
+            "--king-moves" => kings = true,
+            "--stay" => stay = true,
+            "--stochastic-wind" => options.stochastic_wind = true,
+            "--episodes" => {
+                let value = value()?;
+                options.episodes = value.parse().map_err(|_| invalid(&value))?;
+            }
+            "--alpha" => {
+                let value = value()?;
+                match value.parse() {
+                    Ok(alpha) if alpha > 0.0 && alpha <= 1.0 => options.alpha = alpha,
+                    _ => return Err("--alpha must be in (0, 1]".to_string()),
+                }
+            }
+            "--epsilon" => {
+                let value = value()?;
+                match value.parse() {
+                    Ok(epsilon) if (0.0..=1.0).contains(&epsilon) => options.epsilon = epsilon,
+                    _ => return Err("--epsilon must be between 0 and 1".to_string()),
+                }
+            }
+            "--out" => options.out = Some(PathBuf::from(value()?)),
+            "--jsonl" => options.jsonl = Some(PathBuf::from(value()?)),
+            "--seed" => {
+                let value = value()?;
+                options.seed = Some(value.parse().map_err(|_| invalid(&value))?);
+            }
+            "-h" | "--help" => return Ok(None),
+            _ => return Err(format!("unknown option '{}'", flag)),
+        }
+    }
+    options.moves = match (kings, stay) {
+        (false, true) => return Err("--stay needs --king-moves".to_string()),
+        (false, false) => Moves::Standard,
+        (true, false) => Moves::Kings,
+        (true, true) => Moves::KingsAndStay,
+    };
+    Ok(Some(options))
+}
+
+fn write_curve(out: &mut dyn Write, lengths: &[usize]) -> io::Result<()> {
+    writeln!(out, "episode,time_steps,length")?;
+    let mut time_steps = 0;
+    for (episode, &length) in lengths.iter().enumerate() {
+        time_steps += length;
+        writeln!(out, "{},{},{}", episode + 1, time_steps, length)?;
+    }
+    out.flush()
+}
+
+fn main() {
+    let options = match expand_config(std::env::args().skip(1)).and_then(parse_args) {
+        Ok(Some(options)) => options,
+        Ok(None) => {
+            println!("{}", USAGE);
+            return;
+        }
+        Err(message) => {
+            eprintln!("Error: {}\n\n{}", message, USAGE);
+            process::exit(2);
+        }
+    };
+    let mut env = WindyGridworld::new(options.moves, options.seed)
+        .with_stochastic_wind(options.stochastic_wind);
+    let mut agent = TdAgent::new(
+        TdControl::Sarsa,
+        ROWS * COLS,
+        options.moves.count(),
+        options.alpha,
+        options.epsilon,
+        derive_seed(options.seed, 1),
+    );
+    let lengths: Vec<usize> = (0..options.episodes)
+        .map(|_| run_episode(&mut env, &mut agent, None).len())
+        .collect();
+    let written = match &options.out {
+        Some(path) => {
+            File::create(path).and_then(|file| write_curve(&mut BufWriter::new(file), &lengths))
+        }
+        None => write_curve(&mut io::stdout().lock(), &lengths),
+    };
+    if let Err(e) = written {
+        eprintln!("Error: couldn't write the results: {}", e);
+        process::exit(1);
+    }
+    if let Some(path) = &options.jsonl {
+        let config = json!({
+            "moves": options.moves.count(),
+            "stochastic_wind": options.stochastic_wind,
+            "episodes": options.episodes,
+            "alpha": options.alpha,
+            "epsilon": options.epsilon,
+        });
+        let written = write_jsonl(path, "windy-gridworld", options.seed, |lines| {
+            let mut time_steps = 0;
+            for (episode, &length) in lengths.iter().enumerate() {
+                time_steps += length;
+                lines.record(
+                    "episode",
+                    &config,
+                    json!({ "episode": episode + 1, "time_steps": time_steps, "length": length }),
+                )?;
+            }
+            Ok(())
+        });
+        if let Err(e) = written {
+            eprintln!("Error: couldn't write {}: {}", path.display(), e);
+            process::exit(1);
+        }
+    }
+    if let Some(path) = &options.out {
+        println!(
+            "{} episodes, {} time steps in all; written to {}",
+            options.episodes,
+            lengths.iter().sum::<usize>(),
+            path.display()
+        );
+    }
+
+    // Follows the greedy policy, giving up if it hasn't reached the goal in
+    // as many steps as there are cells, since it must be going round in
+    // circles.
+    let mut state = env.reset();
+    let (mut states, mut actions) = (Vec::new(), Vec::new());
+    let mut reached = false;
+    while !reached && actions.len() < ROWS * COLS {
+        let action = agent.greedy_action(state);
+        states.push(state);
+        actions.push(action);
+        let step = env.step(&action);
+        state = step.state;
+        reached = step.done;
+    }
+    let outcome = if reached {
+        format!("reaches the goal in {} steps", actions.len())
+    } else {
+        format!("doesn't reach the goal in {} steps", actions.len())
+    };
+    eprintln!(
+        "\nThe greedy policy {}:\n{}",
+        outcome,
+        format_path(&states, &actions)
+    );
+}
@@ -0,0 +1,181 @@
+use std::path::PathBuf;
+
+use sutton_dyna_maze::DynaConfig;
+
+pub const USAGE: &str = "Usage: sutton-dyna-maze <command> [options]
+
+Commands:
+  maze         Write the steps per episode Dyna-Q takes on the maze of Example
+               8.1 for each number of planning steps, averaged over runs, as
+               CSV (Figure 8.2)
+  blocking     Write the cumulative reward of Dyna-Q and Dyna-Q+ at each time
+               step on the blocking maze, whose gap moves after 1000 steps,
+               averaged over runs, as CSV (Figure 8.4)
+  shortcut     The same on the shortcut maze, where a shorter path opens
+               after 3000 steps (Figure 8.5)
+
+Options:
+  --planning-steps <list>
+                   maze: comma-separated planning steps (default: 0,5,50);
+                   blocking and shortcut: planning steps of both agents
+                   (default: 10 and 50)
+  --episodes <n>   maze: episodes per run (default: 50)
+  --steps <n>      blocking and shortcut: time steps per run (default: 3000
+                   and 6000)
+  --runs <n>       Independent runs to average (default: maze 30, blocking
+                   and shortcut 20)
+  --alpha <x>      Step size (default: maze 0.1, blocking and shortcut 1)
+  --epsilon <x>    Exploration rate (default: 0.1)
+  --kappa <x>      Dyna-Q+'s exploration bonus weight (default: 0.001)
+  --out <path>     Write the CSV to <path> and print a summary instead of
+                   writing the CSV to stdout
+  --jsonl <path>   Also write each curve's episodes (maze) or time steps
+                   (blocking, shortcut) to <path> as lines of JSON with the
+                   settings and seed
+  --seed <n>       Seed every random number generator, so runs can be
+                   repeated exactly (default: seeded from the OS)
+  --config <path>  Read options from an experiment file of `option = value`
+                   lines, a subset of TOML; options given here override it,
+                   and its `command` is used when none is given here
+
+  -h, --help       Show this message";
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Changing {
+    Blocking,
+    Shortcut,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Command {
+    Maze {
+        planning_steps: Vec<usize>,
+        episodes: usize,
+    },
+    // Dyna-Q+ uses `config` with `kappa`; Dyna-Q uses it without.
+    Changing {
+        maze: Changing,
+        steps: usize,
+        kappa: f64,
+    },
+    Help,
+}
+
+#[derive(Debug)]
+pub struct Cli {
+    pub command: Command,
+    pub config: DynaConfig,
+    pub runs: usize,
+    pub out: Option<PathBuf>,
+    pub jsonl: Option<PathBuf>,
+    pub seed: Option<u64>,
+}
+
+fn parse_number<T: std::str::FromStr>(flag: &str, value: &str) -> Result<T, String> {
+    value
+        .parse()
+        .map_err(|_| format!("invalid value '{}' for {}", value, flag))
+}
+
+impl Cli {
+    // Parses the arguments after the program name. Errors are messages for the
+    // user, to be printed above `USAGE`.
+    pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Cli, String> {
+        let mut args = args.into_iter();
+        let mut cli = Cli {
+            command: Command::Help,
+            config: DynaConfig::default(),
+            runs: 0,
+            out: None,
+            jsonl: None,
+            seed: None,
+        };
+        let name = match args.next() {
+            Some(name) if name == "-h" || name == "--help" => return Ok(cli),
+            Some(name) => name,
+            None => return Err("missing command".to_string()),
+        };
+        let mut planning_steps: Option<Vec<usize>> = None;
+        let mut episodes = None;
+        let mut steps = None;
+        let mut runs = None;
+        let mut alpha = None;
+        let mut kappa = 1e-3;
+        while let Some(flag) = args.next() {
+            let mut value = || args.next().ok_or(format!("{} needs a value", flag));
+            match flag.as_str() {
+                "--planning-steps" => {
+                    planning_steps = Some(
+                        value()?
+                            .split(',')
+                            .map(|n| parse_number(&flag, n))
+                            .collect::<Result<_, _>>()?,
+                    )
+                }
+                "--episodes" => episodes = Some(parse_number(&flag, &value()?)?),
+                "--steps" => steps = Some(parse_number(&flag, &value()?)?),
+                "--runs" => match parse_number(&flag, &value()?)? {
+                    0 => return Err("--runs must be at least 1".to_string()),
+                    n => runs = Some(n),
+                },
+                "--alpha" => match parse_number(&flag, &value()?)? {
+                    a if a > 0.0 && a <= 1.0 => alpha = Some(a),
+                    _ => return Err("--alpha must be in (0, 1]".to_string()),
+                },
+                "--epsilon" => match parse_number(&flag, &value()?)? {
+                    e if (0.0..=1.0).contains(&e) => cli.config.epsilon = e,
+                    _ => return Err("--epsilon must be between 0 and 1".to_string()),
+                },
+                "--kappa" => match parse_number(&flag, &value()?)? {
+                    k if k > 0.0 => kappa = k,
+                    _ => return Err("--kappa must be positive".to_string()),
+                },
+                "--out" => cli.out = Some(PathBuf::from(value()?)),
+                "--jsonl" => cli.jsonl = Some(PathBuf::from(value()?)),
+                "--seed" => cli.seed = Some(parse_number(&flag, &value()?)?),
+                "-h" | "--help" => return Ok(cli),
+                _ => return Err(format!("unknown option '{}'", flag)),
+            }
+        }
+        let maze = match name.as_str() {
+            "maze" => None,
+            "blocking" => Some(Changing::Blocking),
+            "shortcut" => Some(Changing::Shortcut),
+            _ => return Err(format!("unknown command '{}'", name)),
+        };
+        cli.command = match maze {
+            None if steps.is_some() => return Err("maze does not take --steps".to_string()),
+            None => {
+                cli.runs = runs.unwrap_or(30);
+                cli.config.alpha = alpha.unwrap_or(0.1);
+                Command::Maze {
+                    planning_steps: planning_steps.unwrap_or_else(|| vec![0, 5, 50]),
+                    episodes: episodes.unwrap_or(50),
+                }
+            }
+            Some(_) if episodes.is_some() => {
+                return Err(format!("{} does not take --episodes; use --steps", name))
+            }
+            Some(maze) => {
+                cli.runs = runs.unwrap_or(20);
+                cli.config.alpha = alpha.unwrap_or(1.0);
+                let default_steps = if maze == Changing::Blocking { 10 } else { 50 };
+                cli.config.planning_steps = match planning_steps.as_deref() {
+                    None => default_steps,
+                    Some(&[n]) => n,
+                    Some(_) => return Err(format!("{} takes a single --planning-steps", name)),
+                };
+                Command::Changing {
+                    maze,
+                    steps: steps.unwrap_or(if maze == Changing::Blocking {
+                        3000
+                    } else {
+                        6000
+                    }),
+                    kappa,
+                }
+            }
+        };
+        Ok(cli)
+    }
+}
@@ -0,0 +1,172 @@
+// Tabular Dyna-Q and Dyna-Q+ (Sections 8.2 and 8.3).
+
+use rand::rngs::SmallRng;
+use rand::Rng;
+use rl_core::{epsilon_greedy, seeded_rng, Environment, Policy, Step};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DynaConfig {
+    pub alpha: f64,
+    pub epsilon: f64,
+    pub gamma: f64,
+    // Simulated updates from the model after every real step; 0 is plain
+    // one-step Q-learning.
+    pub planning_steps: usize,
+    // Dyna-Q+'s exploration bonus: planning adds kappa sqrt(tau) to the reward
+    // of a transition last tried tau steps ago. 0 is plain Dyna-Q.
+    pub kappa: f64,
+}
+
+impl Default for DynaConfig {
+    // Example 8.1's settings.
+    fn default() -> Self {
+        DynaConfig {
+            alpha: 0.1,
+            epsilon: 0.1,
+            gamma: 0.95,
+            planning_steps: 0,
+            kappa: 0.0,
+        }
+    }
+}
+
+// What the model remembers of the last time an action was taken in a state.
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Outcome {
+    reward: f64,
+    next_state: usize,
+    done: bool,
+}
+
+// An epsilon-greedy agent that learns Q by one-step Q-learning from each real
+// step, records the step in a deterministic model, then makes
+// `planning_steps` more Q-learning updates from transitions the model picks
+// at random among those it has seen. It learns in `observe`, so `run_episode`
+// trains it.
+pub struct DynaQ {
+    config: DynaConfig,
+    q: Vec<Vec<f64>>,
+    model: Vec<Vec<Option<Outcome>>>,
+    // Every state-action pair in the model, in the order first seen.
+    known: Vec<(usize, usize)>,
+    // The time step each pair was last really taken.
+    last_tried: Vec<Vec<usize>>,
+    time: usize,
+    rng: SmallRng,
+}
+
+impl DynaQ {
+    pub fn new(config: DynaConfig, states: usize, actions: usize, seed: Option<u64>) -> Self {
+        DynaQ {
+            config,
+            q: vec![vec![0.0; actions]; states],
+            model: vec![vec![None; actions]; states],
+            known: Vec::new(),
+            last_tried: vec![vec![0; actions]; states],
+            time: 0,
+            rng: seeded_rng(seed),
+        }
+    }
+
+    pub fn q(&self) -> &[Vec<f64>] {
+        &self.q
+    }
+
+    fn learn(&mut self, state: usize, action: usize, outcome: Outcome) {
+        let next = if outcome.done {
+            0.0
+        } else {
+            self.q[outcome.next_state]
+                .iter()
+                .copied()
+                .fold(f64::NEG_INFINITY, f64::max)
+        };
+        let target = outcome.reward + self.config.gamma * next;
+        let value = &mut self.q[state][action];
+        *value += self.config.alpha * (target - *value);
+    }
+
+    fn remember(&mut self, state: usize, action: usize, outcome: Outcome) {
+        if self.model[state][action].is_none() {
+            self.known.push((state, action));
+        }
+        self.model[state][action] = Some(outcome);
+    }
+}
+
+impl<E: Environment<State = usize, Action = usize> + ?Sized> Policy<E> for DynaQ {
+    fn select_action(&mut self, _env: &E, state: &usize) -> usize {
+        epsilon_greedy(&self.q[*state], self.config.epsilon, &mut self.rng)
+    }
+
+    fn observe(&mut self, state: &usize, action: &usize, step: &Step<usize>) {
+        let (state, action) = (*state, *action);
+        self.time += 1;
+        let outcome = Outcome {
+            reward: step.reward,
+            next_state: step.state,
+            done: step.done,
+        };
+        self.learn(state, action, outcome);
+        // Dyna-Q+ lets planning try actions never taken in a state it has
+        // visited, modelling them as staying put for no reward.
+        if self.config.kappa > 0.0 && self.model[state].iter().all(Option::is_none) {
+            for untried in 0..self.model[state].len() {
+                let stay = Outcome {
+                    reward: 0.0,
+                    next_state: state,
+                    done: false,
+                };
+                self.remember(state, untried, stay);
+            }
+        }
+        self.remember(state, action, outcome);
+        self.last_tried[state][action] = self.time;
+        for _ in 0..self.config.planning_steps {
+            let (s, a) = self.known[self.rng.random_range(0..self.known.len())];
+            let Some(mut outcome) = self.model[s][a] else {
+                continue;
+            };
+            let tau = (self.time - self.last_tried[s][a]) as f64;
+            outcome.reward += self.config.kappa * tau.sqrt();
+            self.learn(s, a, outcome);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn planning_spreads_a_reward_backwards() {
+        let config = DynaConfig {
+            alpha: 1.0,
+            gamma: 0.5,
+            ..DynaConfig::default()
+        };
+        let step = |state, reward, done| Step {
+            state,
+            reward,
+            done,
+        };
+        let mut without = DynaQ::new(config, 3, 1, Some(1));
+        let mut with = DynaQ::new(
+            DynaConfig {
+                planning_steps: 50,
+                ..config
+            },
+            3,
+            1,
+            Some(1),
+        );
+        for agent in [&mut without, &mut with] {
+            // 0 -> 1 for nothing, then 1 -> 2 for +1, ending the episode.
+            Policy::<crate::Maze>::observe(agent, &0, &0, &step(1, 0.0, false));
+            Policy::<crate::Maze>::observe(agent, &1, &0, &step(2, 1.0, true));
+        }
+        assert_eq!(without.q()[0][0], 0.0);
+        assert_eq!(with.q()[0][0], 0.5);
+        assert_eq!(with.q()[1][0], 1.0);
+    }
+}
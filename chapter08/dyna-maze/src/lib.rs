@@ -0,0 +1,232 @@
+use rl_core::{derive_seed, run_episode, Environment, Step};
+
+mod dyna;
+
+pub use dyna::{DynaConfig, DynaQ};
+
+// Up, down, left, right.
+const MOVES: [(isize, isize); 4] = [(-1, 0), (1, 0), (0, -1), (0, 1)];
+pub const ACTIONS: usize = MOVES.len();
+
+// A gridworld maze: every move pays 0 except reaching the goal, which pays +1
+// and ends the episode. Moves into walls or off the grid leave the agent in
+// place. The walls can change once, after a given number of time steps
+// counted across episodes, as in the blocking and shortcut mazes. Cells are
+// numbered in row-major order.
+#[derive(Clone, Debug)]
+pub struct Maze {
+    rows: usize,
+    cols: usize,
+    start: usize,
+    goal: usize,
+    walls: Vec<bool>,
+    change: Option<(usize, Vec<bool>)>,
+    time: usize,
+    position: usize,
+}
+
+impl Maze {
+    pub fn new(
+        rows: usize,
+        cols: usize,
+        start: (usize, usize),
+        goal: (usize, usize),
+        walls: &[(usize, usize)],
+    ) -> Self {
+        Maze {
+            rows,
+            cols,
+            start: start.0 * cols + start.1,
+            goal: goal.0 * cols + goal.1,
+            walls: wall_cells(rows, cols, walls),
+            change: None,
+            time: 0,
+            position: start.0 * cols + start.1,
+        }
+    }
+
+    // Replaces the walls with `walls` once `time` steps have been taken.
+    pub fn with_change(mut self, time: usize, walls: &[(usize, usize)]) -> Self {
+        self.change = Some((time, wall_cells(self.rows, self.cols, walls)));
+        self
+    }
+
+    // Figure 8.2's 6x9 maze.
+    pub fn dyna_maze() -> Self {
+        Maze::new(
+            6,
+            9,
+            (2, 0),
+            (0, 8),
+            &[(1, 2), (2, 2), (3, 2), (4, 5), (0, 7), (1, 7), (2, 7)],
+        )
+    }
+
+    // Figure 8.4: the gap on the right of the wall closes after 1000 steps and
+    // one opens on the left.
+    pub fn blocking() -> Self {
+        let right_gap: Vec<(usize, usize)> = (0..8).map(|col| (3, col)).collect();
+        let left_gap: Vec<(usize, usize)> = (1..9).map(|col| (3, col)).collect();
+        Maze::new(6, 9, (5, 3), (0, 8), &right_gap).with_change(1000, &left_gap)
+    }
+
+    // Figure 8.5: a second gap, on the right, opens after 3000 steps.
+    pub fn shortcut() -> Self {
+        let left_gap: Vec<(usize, usize)> = (1..9).map(|col| (3, col)).collect();
+        let both_gaps: Vec<(usize, usize)> = (1..8).map(|col| (3, col)).collect();
+        Maze::new(6, 9, (5, 3), (0, 8), &left_gap).with_change(3000, &both_gaps)
+    }
+
+    pub fn cells(&self) -> usize {
+        self.rows * self.cols
+    }
+}
+
+fn wall_cells(rows: usize, cols: usize, walls: &[(usize, usize)]) -> Vec<bool> {
+    let mut cells = vec![false; rows * cols];
+    for &(row, col) in walls {
+        cells[row * cols + col] = true;
+    }
+    cells
+}
+
+impl Environment for Maze {
+    type State = usize;
+    type Action = usize;
+
+    fn reset(&mut self) -> usize {
+        self.position = self.start;
+        self.position
+    }
+
+    fn step(&mut self, action: &usize) -> Step<usize> {
+        let (dr, dc) = MOVES[*action];
+        let row = (self.position / self.cols) as isize + dr;
+        let col = (self.position % self.cols) as isize + dc;
+        if row >= 0 && row < self.rows as isize && col >= 0 && col < self.cols as isize {
+            let next = row as usize * self.cols + col as usize;
+            if !self.walls[next] {
+                self.position = next;
+            }
+        }
+        self.time += 1;
+        if let Some((_, walls)) = self.change.take_if(|(time, _)| *time == self.time) {
+            self.walls = walls;
+        }
+        let done = self.position == self.goal;
+        Step {
+            state: self.position,
+            reward: if done { 1.0 } else { 0.0 },
+            done,
+        }
+    }
+
+    fn actions(&self, _state: &usize) -> Vec<usize> {
+        (0..ACTIONS).collect()
+    }
+}
+
+// Figure 8.2: the steps Dyna-Q takes in each of `episodes` episodes on the
+// Dyna maze, averaged over `runs` independent runs.
+pub fn steps_per_episode(
+    config: DynaConfig,
+    episodes: usize,
+    runs: usize,
+    seed: Option<u64>,
+) -> Vec<f64> {
+    let mut steps = vec![0.0; episodes];
+    for run in 0..runs as u64 {
+        let mut maze = Maze::dyna_maze();
+        let mut agent = DynaQ::new(config, maze.cells(), ACTIONS, derive_seed(seed, run));
+        for total in steps.iter_mut() {
+            *total += run_episode(&mut maze, &mut agent, None).len() as f64 / runs as f64;
+        }
+    }
+    steps
+}
+
+// Figures 8.4 and 8.5: the reward Dyna-Q has collected by each of `steps`
+// time steps on mazes made by `maze`, averaged over `runs` independent runs.
+pub fn cumulative_reward(
+    maze: fn() -> Maze,
+    config: DynaConfig,
+    steps: usize,
+    runs: usize,
+    seed: Option<u64>,
+) -> Vec<f64> {
+    let mut cumulative = vec![0.0; steps];
+    for run in 0..runs as u64 {
+        let mut env = maze();
+        let mut agent = DynaQ::new(config, env.cells(), ACTIONS, derive_seed(seed, run));
+        let mut time = 0;
+        let mut total = 0.0;
+        while time < steps {
+            let episode = run_episode(&mut env, &mut agent, Some(steps - time));
+            for reward in &episode.rewards {
+                total += reward;
+                cumulative[time] += total / runs as f64;
+                time += 1;
+            }
+        }
+    }
+    cumulative
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn walls_block_and_change() {
+        let mut maze = Maze::blocking();
+        maze.reset();
+        // Start at (5, 3): up to (4, 3), then the wall at (3, 3) blocks.
+        assert_eq!(maze.step(&0).state, 4 * 9 + 3);
+        assert_eq!(maze.step(&0).state, 4 * 9 + 3);
+        assert!(!maze.walls[3 * 9 + 8]);
+        for _ in 2..1000 {
+            maze.step(&2);
+        }
+        assert!(maze.walls[3 * 9 + 8] && !maze.walls[3 * 9]);
+    }
+
+    #[test]
+    fn planning_learns_the_maze_faster() {
+        let config = |planning_steps| DynaConfig {
+            planning_steps,
+            ..DynaConfig::default()
+        };
+        let direct = steps_per_episode(config(0), 10, 10, Some(1));
+        let planned = steps_per_episode(config(50), 10, 10, Some(1));
+        assert!(planned[4] < direct[4], "{} vs {}", planned[4], direct[4]);
+        // The shortest path takes 14 steps; exploring adds a few.
+        assert!(planned[9] < 20.0, "{}", planned[9]);
+    }
+
+    #[test]
+    fn dyna_q_plus_finds_the_shortcut() {
+        let config = DynaConfig {
+            alpha: 1.0,
+            planning_steps: 50,
+            ..DynaConfig::default()
+        };
+        let plain = cumulative_reward(Maze::shortcut, config, 6000, 5, Some(2));
+        let plus = cumulative_reward(
+            Maze::shortcut,
+            DynaConfig {
+                kappa: 1e-3,
+                ..config
+            },
+            6000,
+            5,
+            Some(2),
+        );
+        let after = |rewards: &[f64]| rewards[5999] - rewards[2999];
+        assert!(
+            after(&plus) > after(&plain),
+            "{} vs {}",
+            after(&plus),
+            after(&plain)
+        );
+    }
+}
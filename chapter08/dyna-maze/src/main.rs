@@ -0,0 +1,179 @@
+mod cli;
+
+use std::fs::File;
+use std::io::{self, BufWriter};
+use std::path::Path;
+use std::process;
+
+use cli::{Changing, Cli, Command, USAGE};
+use rl_core::{expand_config, json, write_jsonl, JsonLines};
+use sutton_dyna_maze::*;
+
+// Writes `write` to `out`, or to stdout when it is `None`, exiting on errors.
+fn write_output(out: Option<&Path>, write: impl FnOnce(&mut dyn io::Write) -> io::Result<()>) {
+    let written = match out {
+        Some(path) => File::create(path).and_then(|file| write(&mut BufWriter::new(file))),
+        None => write(&mut io::stdout().lock()),
+    };
+    if let Err(e) = written {
+        eprintln!("Error: couldn't write the results: {}", e);
+        process::exit(1);
+    }
+}
+
+// Writes JSON Lines records to `cli.jsonl` with `write`, if it is set,
+// exiting on errors.
+fn write_records(
+    cli: &Cli,
+    experiment: &str,
+    write: impl FnOnce(&mut JsonLines) -> io::Result<()>,
+) {
+    let Some(path) = &cli.jsonl else { return };
+    if let Err(e) = write_jsonl(path, experiment, cli.seed, write) {
+        eprintln!("Error: couldn't write {}: {}", path.display(), e);
+        process::exit(1);
+    }
+}
+
+fn config_json(config: &DynaConfig, runs: usize) -> rl_core::Value {
+    json!({
+        "alpha": config.alpha,
+        "epsilon": config.epsilon,
+        "gamma": config.gamma,
+        "planning_steps": config.planning_steps,
+        "kappa": config.kappa,
+        "runs": runs,
+    })
+}
+
+// Writes a row per episode or time step, from 1, with a column per curve.
+fn write_curves(
+    writer: &mut dyn io::Write,
+    index: &str,
+    names: &[String],
+    curves: &[Vec<f64>],
+) -> io::Result<()> {
+    writeln!(writer, "{},{}", index, names.join(","))?;
+    for i in 0..curves.first().map_or(0, Vec::len) {
+        let row: Vec<String> = curves
+            .iter()
+            .map(|curve| format!("{:.4}", curve[i]))
+            .collect();
+        writeln!(writer, "{},{}", i + 1, row.join(","))?;
+    }
+    Ok(())
+}
+
+fn maze(cli: &Cli, planning_steps: &[usize], episodes: usize) {
+    let curves: Vec<Vec<f64>> = planning_steps
+        .iter()
+        .map(|&n| {
+            let config = DynaConfig {
+                planning_steps: n,
+                ..cli.config
+            };
+            steps_per_episode(config, episodes, cli.runs, cli.seed)
+        })
+        .collect();
+    let names: Vec<String> = planning_steps.iter().map(|n| format!("n_{}", n)).collect();
+    write_output(cli.out.as_deref(), |writer| {
+        write_curves(writer, "episode", &names, &curves)
+    });
+    write_records(cli, "dyna-maze/maze", |lines| {
+        for (&n, curve) in planning_steps.iter().zip(&curves) {
+            let config = config_json(
+                &DynaConfig {
+                    planning_steps: n,
+                    ..cli.config
+                },
+                cli.runs,
+            );
+            for (episode, steps) in curve.iter().enumerate() {
+                lines.record(
+                    "episode",
+                    &config,
+                    json!({ "episode": episode + 1, "steps": steps }),
+                )?;
+            }
+        }
+        Ok(())
+    });
+    let Some(path) = &cli.out else { return };
+    println!(
+        "Steps per episode over {} runs; written to {}",
+        cli.runs,
+        path.display()
+    );
+    for (n, curve) in planning_steps.iter().zip(&curves) {
+        let second = curve.get(1).copied().unwrap_or(0.0);
+        let last = curve.last().copied().unwrap_or(0.0);
+        println!(
+            "  {:3} planning steps: episode 2 took {:.1} steps, the last {:.1}",
+            n, second, last
+        );
+    }
+}
+
+fn changing(cli: &Cli, maze: Changing, steps: usize, kappa: f64) {
+    let make = match maze {
+        Changing::Blocking => Maze::blocking,
+        Changing::Shortcut => Maze::shortcut,
+    };
+    let plus = DynaConfig {
+        kappa,
+        ..cli.config
+    };
+    let curves: Vec<Vec<f64>> = [cli.config, plus]
+        .into_iter()
+        .map(|config| cumulative_reward(make, config, steps, cli.runs, cli.seed))
+        .collect();
+    let names = ["dyna_q".to_string(), "dyna_q_plus".to_string()];
+    write_output(cli.out.as_deref(), |writer| {
+        write_curves(writer, "time_step", &names, &curves)
+    });
+    let experiment = match maze {
+        Changing::Blocking => "dyna-maze/blocking",
+        Changing::Shortcut => "dyna-maze/shortcut",
+    };
+    write_records(cli, experiment, |lines| {
+        for (config, curve) in [cli.config, plus].iter().zip(&curves) {
+            let config = config_json(config, cli.runs);
+            for (step, reward) in curve.iter().enumerate() {
+                lines.record(
+                    "time_step",
+                    &config,
+                    json!({ "time_step": step + 1, "cumulative_reward": reward }),
+                )?;
+            }
+        }
+        Ok(())
+    });
+    let Some(path) = &cli.out else { return };
+    println!(
+        "Cumulative reward over {} runs of {} steps, {} planning steps; written to {}",
+        cli.runs,
+        steps,
+        cli.config.planning_steps,
+        path.display()
+    );
+    for (name, curve) in names.iter().zip(&curves) {
+        println!("  {:<12} {:.1}", name, curve.last().copied().unwrap_or(0.0));
+    }
+}
+
+fn main() {
+    let cli = expand_config(std::env::args().skip(1))
+        .and_then(Cli::parse)
+        .unwrap_or_else(|message| {
+            eprintln!("Error: {}\n\n{}", message, USAGE);
+            process::exit(2);
+        });
+    match &cli.command {
+        Command::Maze {
+            planning_steps,
+            episodes,
+        } => maze(&cli, planning_steps, *episodes),
+        Command::Changing { maze, steps, kappa } => changing(&cli, *maze, *steps, *kappa),
+        Command::Help => println!("{}", USAGE),
+    }
+}
@@ -0,0 +1,287 @@
+use rand::rngs::SmallRng;
+use rand::Rng;
+use rl_core::{
+    derive_seed, epsilon_greedy, run_episode, seeded_rng, EligibilityTraces, Environment, Policy,
+    Step, TileCoder, TraceKind,
+};
+
+pub const POSITION: (f64, f64) = (-1.2, 0.5);
+pub const VELOCITY: (f64, f64) = (-0.07, 0.07);
+// Full throttle reverse, zero throttle and full throttle forward.
+pub const ACTIONS: usize = 3;
+
+// The mountain car task of Example 10.1: an underpowered car in a valley must
+// rock back and forth to reach the goal at the top of the right hill. The
+// state is (position, velocity); every step costs -1 until the goal.
+// Hitting the left bound stops the car dead.
+pub struct MountainCar {
+    rng: SmallRng,
+    position: f64,
+    velocity: f64,
+}
+
+impl MountainCar {
+    pub fn new(seed: Option<u64>) -> Self {
+        MountainCar {
+            rng: seeded_rng(seed),
+            position: -0.5,
+            velocity: 0.0,
+        }
+    }
+
+    // The state `action` leads to from (`position`, `velocity`).
+    pub fn dynamics(position: f64, velocity: f64, action: usize) -> (f64, f64) {
+        let velocity = (velocity + 0.001 * (action as f64 - 1.0) - 0.0025 * (3.0 * position).cos())
+            .clamp(VELOCITY.0, VELOCITY.1);
+        let position = (position + velocity).clamp(POSITION.0, POSITION.1);
+        if position == POSITION.0 {
+            (position, 0.0)
+        } else {
+            (position, velocity)
+        }
+    }
+}
+
+impl Environment for MountainCar {
+    type State = (f64, f64);
+    type Action = usize;
+
+    // Starts at rest somewhere in [-0.6, -0.4).
+    fn reset(&mut self) -> (f64, f64) {
+        self.position = self.rng.random_range(-0.6..-0.4);
+        self.velocity = 0.0;
+        (self.position, self.velocity)
+    }
+
+    fn step(&mut self, action: &usize) -> Step<(f64, f64)> {
+        (self.position, self.velocity) =
+            MountainCar::dynamics(self.position, self.velocity, *action);
+        Step {
+            state: (self.position, self.velocity),
+            reward: -1.0,
+            done: self.position >= POSITION.1,
+        }
+    }
+
+    fn actions(&self, _state: &(f64, f64)) -> Vec<usize> {
+        (0..ACTIONS).collect()
+    }
+}
+
+// Episodic semi-gradient Sarsa (Section 10.1) with a linear action-value
+// function over tile-coded states: q(s, a) is the sum of the weights of the
+// tiles active for s and a, all starting at 0. The step size `alpha` is
+// shared between the tilings, so each weight moves by alpha / tilings.
+pub struct SemiGradientSarsa {
+    coder: TileCoder,
+    weights: Vec<f64>,
+    alpha: f64,
+    epsilon: f64,
+    rng: SmallRng,
+    next_action: Option<usize>,
+}
+
+impl SemiGradientSarsa {
+    // Example 10.1's coding: `tilings` tilings of 8x8 tiles over the state
+    // space.
+    pub fn new(tilings: usize, alpha: f64, epsilon: f64, seed: Option<u64>) -> Self {
+        let coder = TileCoder::new(tilings, 4096).with_resolution(&[POSITION, VELOCITY], 8);
+        SemiGradientSarsa {
+            weights: vec![0.0; coder.size()],
+            coder,
+            alpha,
+            epsilon,
+            rng: seeded_rng(seed),
+            next_action: None,
+        }
+    }
+
+    fn active(&mut self, (position, velocity): (f64, f64), action: usize) -> Vec<usize> {
+        self.coder.tiles(&[position, velocity], &[action as i64])
+    }
+
+    pub fn value(&mut self, state: (f64, f64), action: usize) -> f64 {
+        self.active(state, action)
+            .iter()
+            .map(|&t| self.weights[t])
+            .sum()
+    }
+
+    fn choose(&mut self, state: (f64, f64)) -> usize {
+        let values: Vec<f64> = (0..ACTIONS).map(|a| self.value(state, a)).collect();
+        epsilon_greedy(&values, self.epsilon, &mut self.rng)
+    }
+
+    // The cost-to-go of Figure 10.1: -max over a of q(s, a).
+    pub fn cost_to_go(&mut self, state: (f64, f64)) -> f64 {
+        -(0..ACTIONS)
+            .map(|a| self.value(state, a))
+            .fold(f64::NEG_INFINITY, f64::max)
+    }
+}
+
+impl Policy<MountainCar> for SemiGradientSarsa {
+    fn select_action(&mut self, _env: &MountainCar, state: &(f64, f64)) -> usize {
+        match self.next_action.take() {
+            Some(action) => action,
+            None => self.choose(*state),
+        }
+    }
+
+    fn observe(&mut self, state: &(f64, f64), action: &usize, step: &Step<(f64, f64)>) {
+        let mut target = step.reward;
+        if !step.done {
+            let next_action = self.choose(step.state);
+            self.next_action = Some(next_action);
+            target += self.value(step.state, next_action);
+        }
+        let active = self.active(*state, *action);
+        let error = target - active.iter().map(|&t| self.weights[t]).sum::<f64>();
+        let step_size = self.alpha / self.coder.tilings() as f64;
+        for tile in active {
+            self.weights[tile] += step_size * error;
+        }
+    }
+}
+
+// Sarsa(lambda) with binary features (Section 12.7): semi-gradient Sarsa's
+// tile coding and step size, with each step's TD error applied to every
+// recently active tile through eligibility traces decaying by `lambda`.
+// Lambda 0 is one-step semi-gradient Sarsa.
+pub struct SarsaLambda {
+    sarsa: SemiGradientSarsa,
+    lambda: f64,
+    traces: EligibilityTraces,
+}
+
+impl SarsaLambda {
+    pub fn new(
+        tilings: usize,
+        alpha: f64,
+        lambda: f64,
+        kind: TraceKind,
+        epsilon: f64,
+        seed: Option<u64>,
+    ) -> Self {
+        let sarsa = SemiGradientSarsa::new(tilings, alpha, epsilon, seed);
+        SarsaLambda {
+            traces: EligibilityTraces::new(kind, sarsa.weights.len()),
+            sarsa,
+            lambda,
+        }
+    }
+}
+
+impl Policy<MountainCar> for SarsaLambda {
+    fn select_action(&mut self, env: &MountainCar, state: &(f64, f64)) -> usize {
+        self.sarsa.select_action(env, state)
+    }
+
+    fn observe(&mut self, state: &(f64, f64), action: &usize, step: &Step<(f64, f64)>) {
+        let sarsa = &mut self.sarsa;
+        let mut error = step.reward - sarsa.value(*state, *action);
+        if !step.done {
+            let next_action = sarsa.choose(step.state);
+            sarsa.next_action = Some(next_action);
+            error += sarsa.value(step.state, next_action);
+        }
+        let active = sarsa.active(*state, *action);
+        self.traces.visit(&active, self.lambda);
+        let step_size = sarsa.alpha / sarsa.coder.tilings() as f64;
+        self.traces.update(&mut sarsa.weights, step_size * error);
+        if step.done {
+            self.traces.clear();
+        }
+    }
+}
+
+// The steps `agent` takes in each of `episodes` episodes, averaged over
+// `runs` independent runs, with agents made by `agent` from a seed.
+fn average_steps<P: Policy<MountainCar>>(
+    episodes: usize,
+    runs: usize,
+    seed: Option<u64>,
+    agent: impl Fn(Option<u64>) -> P,
+) -> Vec<f64> {
+    let mut steps = vec![0.0; episodes];
+    for run in 0..runs as u64 {
+        let mut env = MountainCar::new(derive_seed(seed, 2 * run));
+        let mut agent = agent(derive_seed(seed, 2 * run + 1));
+        for total in steps.iter_mut() {
+            *total += run_episode(&mut env, &mut agent, None).len() as f64 / runs as f64;
+        }
+    }
+    steps
+}
+
+// Figure 10.2: the steps semi-gradient Sarsa with step size `alpha` takes in
+// each of `episodes` episodes, averaged over `runs` independent runs. The
+// agent is greedy, relying on its optimistic initial values to explore.
+pub fn steps_per_episode(
+    tilings: usize,
+    alpha: f64,
+    episodes: usize,
+    runs: usize,
+    seed: Option<u64>,
+) -> Vec<f64> {
+    average_steps(episodes, runs, seed, |seed| {
+        SemiGradientSarsa::new(tilings, alpha, 0.0, seed)
+    })
+}
+
+// As `steps_per_episode` for Sarsa(lambda), as in Figure 12.10, which uses
+// replacing traces.
+pub fn sarsa_lambda_steps(
+    tilings: usize,
+    alpha: f64,
+    lambda: f64,
+    kind: TraceKind,
+    episodes: usize,
+    runs: usize,
+    seed: Option<u64>,
+) -> Vec<f64> {
+    average_steps(episodes, runs, seed, |seed| {
+        SarsaLambda::new(tilings, alpha, lambda, kind, 0.0, seed)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn gravity_beats_the_engine_and_the_left_wall_stops_the_car() {
+        // Full throttle from rest at the bottom can't climb straight out.
+        let (mut position, mut velocity) = (-0.5, 0.0);
+        for _ in 0..200 {
+            (position, velocity) = MountainCar::dynamics(position, velocity, 2);
+            assert!(position < 0.0);
+        }
+        let (position, velocity) = MountainCar::dynamics(-1.19, -0.05, 0);
+        assert_eq!((position, velocity), (-1.2, 0.0));
+    }
+
+    #[test]
+    fn sarsa_learns_to_rock_out_of_the_valley() {
+        let steps = steps_per_episode(8, 0.5, 50, 3, Some(1));
+        assert!(steps[0] > 300.0, "{}", steps[0]);
+        let late = steps[40..].iter().sum::<f64>() / 10.0;
+        assert!(late < 200.0, "{:?}", &steps[40..]);
+    }
+
+    #[test]
+    fn traces_speed_up_sarsa() {
+        let one_step = steps_per_episode(8, 0.5, 20, 5, Some(1));
+        let lambda = sarsa_lambda_steps(8, 0.5, 0.9, TraceKind::Replacing, 20, 5, Some(1));
+        let total = |steps: &[f64]| steps.iter().sum::<f64>();
+        assert!(
+            total(&lambda) < total(&one_step),
+            "{} vs {}",
+            total(&lambda),
+            total(&one_step)
+        );
+        // Without decay, Sarsa(0) takes the same steps as one-step Sarsa.
+        let zero = sarsa_lambda_steps(8, 0.5, 0.0, TraceKind::Accumulating, 5, 2, Some(1));
+        assert_eq!(zero, steps_per_episode(8, 0.5, 5, 2, Some(1)));
+    }
+}
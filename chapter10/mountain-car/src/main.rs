@@ -0,0 +1,222 @@
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::PathBuf;
+use std::process;
+
+use rl_core::{expand_config, json, write_jsonl, TraceKind};
+use sutton_mountain_car::*;
+
+const USAGE: &str = "Usage: sutton-mountain-car [options]
+
+Trains episodic semi-gradient Sarsa with tile coding on the mountain car task
+of Example 10.1 and writes the steps taken in each episode, averaged over runs,
+for each step size as CSV (Figure 10.2). With --lambda, trains Sarsa(lambda)
+with eligibility traces instead (Figure 12.10).
+
+Options:
+  --alphas <list>  Comma-separated step sizes, shared between the tilings
+                   (default: 0.1,0.2,0.5)
+  --tilings <n>    Tilings of 8x8 tiles (default: 8)
+  --lambda <x>     Train Sarsa(lambda) with this trace decay rate, in [0, 1]
+  --traces <kind>  With --lambda: accumulating or replacing traces (default:
+                   replacing)
+  --episodes <n>   Episodes per run (default: 500)
+  --runs <n>       Independent runs to average (default: 10; the book uses
+                   100)
+  --out <path>     Write the CSV to <path> and print a summary instead of
+                   writing the CSV to stdout
+  --jsonl <path>   Also write each step size's episodes to <path> as lines of JSON
+                   with the settings and seed
+  --seed <n>       Seed every random number generator, so runs can be
+                   repeated exactly (default: seeded from the OS)
+  --config <path>  Read options from an experiment file of `option = value`
+                   lines, a subset of TOML; options given here override it
+
+  -h, --help       Show this message";
+
+struct Options {
+    alphas: Vec<f64>,
+    tilings: usize,
+    lambda: Option<f64>,
+    traces: TraceKind,
+    episodes: usize,
+    runs: usize,
+    out: Option<PathBuf>,
+    jsonl: Option<PathBuf>,
+    seed: Option<u64>,
+}
+
+fn parse_number<T: std::str::FromStr>(flag: &str, value: &str) -> Result<T, String> {
+    value
+        .parse()
+        .map_err(|_| format!("invalid value '{}' for {}", value, flag))
+}
+
+// Parses the arguments after the program name; `None` means --help.
+fn parse_args(args: impl IntoIterator<Item = String>) -> Result<Option<Options>, String> {
+    let mut options = Options {
+        alphas: vec![0.1, 0.2, 0.5],
+        tilings: 8,
+        lambda: None,
+        traces: TraceKind::Replacing,
+        episodes: 500,
+        runs: 10,
+        out: None,
+        jsonl: None,
+        seed: None,
+    };
+    let mut traces = false;
+    let mut args = args.into_iter();
+    while let Some(flag) = args.next() {
+        let mut value = || args.next().ok_or(format!("{} needs a value", flag));
+        match flag.as_str() {
+            "--alphas" => {
+                options.alphas = value()?
+                    .split(',')
+                    .map(|alpha| match parse_number(&flag, alpha)? {
+                        alpha if alpha > 0.0 => Ok(alpha),
+                        _ => Err("--alphas must all be positive".to_string()),
+                    })
+                    .collect::<Result<_, String>>()?
+            }
+            "--tilings" => match parse_number(&flag, &value()?)? {
+                0 => return Err("--tilings must be at least 1".to_string()),
+                tilings => options.tilings = tilings,
+            },
+            "--lambda" => match parse_number(&flag, &value()?)? {
+                lambda if (0.0..=1.0).contains(&lambda) => options.lambda = Some(lambda),
+                _ => return Err("--lambda must be between 0 and 1".to_string()),
+            },
+            "--traces" => {
+                options.traces = value()?.parse()?;
+                traces = true;
+            }
+            "--episodes" => options.episodes = parse_number(&flag, &value()?)?,
+            "--runs" => match parse_number(&flag, &value()?)? {
+                0 => return Err("--runs must be at least 1".to_string()),
+                runs => options.runs = runs,
+            },
+            "--out" => options.out = Some(PathBuf::from(value()?)),
+            "--jsonl" => options.jsonl = Some(PathBuf::from(value()?)),
+            "--seed" => options.seed = Some(parse_number(&flag, &value()?)?),
+            "-h" | "--help" => return Ok(None),
+            _ => return Err(format!("unknown option '{}'", flag)),
+        }
+    }
+    if traces && options.lambda.is_none() {
+        return Err("--traces needs --lambda".to_string());
+    }
+    Ok(Some(options))
+}
+
+fn write_curves(out: &mut dyn Write, alphas: &[f64], curves: &[Vec<f64>]) -> io::Result<()> {
+    let names: Vec<String> = alphas
+        .iter()
+        .map(|alpha| format!("alpha_{}", alpha))
+        .collect();
+    writeln!(out, "episode,{}", names.join(","))?;
+    for episode in 0..curves.first().map_or(0, Vec::len) {
+        let row: Vec<String> = curves
+            .iter()
+            .map(|curve| format!("{:.2}", curve[episode]))
+            .collect();
+        writeln!(out, "{},{}", episode + 1, row.join(","))?;
+    }
+    out.flush()
+}
+
+fn main() {
+    let options = match expand_config(std::env::args().skip(1)).and_then(parse_args) {
+        Ok(Some(options)) => options,
+        Ok(None) => {
+            println!("{}", USAGE);
+            return;
+        }
+        Err(message) => {
+            eprintln!("Error: {}\n\n{}", message, USAGE);
+            process::exit(2);
+        }
+    };
+    let curves: Vec<Vec<f64>> = options
+        .alphas
+        .iter()
+        .map(|&alpha| match options.lambda {
+            Some(lambda) => sarsa_lambda_steps(
+                options.tilings,
+                alpha,
+                lambda,
+                options.traces,
+                options.episodes,
+                options.runs,
+                options.seed,
+            ),
+            None => steps_per_episode(
+                options.tilings,
+                alpha,
+                options.episodes,
+                options.runs,
+                options.seed,
+            ),
+        })
+        .collect();
+    let written = match &options.out {
+        Some(path) => File::create(path)
+            .and_then(|file| write_curves(&mut BufWriter::new(file), &options.alphas, &curves)),
+        None => write_curves(&mut io::stdout().lock(), &options.alphas, &curves),
+    };
+    if let Err(e) = written {
+        eprintln!("Error: couldn't write the results: {}", e);
+        process::exit(1);
+    }
+    if let Some(path) = &options.jsonl {
+        let written = write_jsonl(path, "mountain-car", options.seed, |lines| {
+            for (alpha, curve) in options.alphas.iter().zip(&curves) {
+                let mut config = json!({
+                    "alpha": alpha,
+                    "tilings": options.tilings,
+                    "episodes": options.episodes,
+                    "runs": options.runs,
+                });
+                if let Some(lambda) = options.lambda {
+                    config["lambda"] = json!(lambda);
+                    config["traces"] = json!(options.traces.to_string());
+                }
+                for (episode, steps) in curve.iter().enumerate() {
+                    lines.record(
+                        "episode",
+                        &config,
+                        json!({ "episode": episode + 1, "steps": steps }),
+                    )?;
+                }
+            }
+            Ok(())
+        });
+        if let Err(e) = written {
+            eprintln!("Error: couldn't write {}: {}", path.display(), e);
+            process::exit(1);
+        }
+    }
+    let Some(path) = &options.out else { return };
+    let method = match options.lambda {
+        Some(lambda) => format!("Sarsa({}) with {} traces", lambda, options.traces),
+        None => "Semi-gradient Sarsa".to_string(),
+    };
+    println!(
+        "{}: steps per episode over {} runs with {} tilings; written to {}",
+        method,
+        options.runs,
+        options.tilings,
+        path.display()
+    );
+    let late = options.episodes.saturating_sub(50);
+    for (alpha, curve) in options.alphas.iter().zip(&curves) {
+        println!(
+            "  alpha = {}/{}: first episode {:.0} steps, last {} episodes {:.1} on average",
+            alpha,
+            options.tilings,
+            curve.first().copied().unwrap_or(0.0),
+            curve.len() - late,
+            curve[late..].iter().sum::<f64>() / (curve.len() - late).max(1) as f64
+        );
+    }
+}
@@ -0,0 +1,315 @@
+use std::fmt;
+use std::str::FromStr;
+
+use rand::rngs::SmallRng;
+use rand::Rng;
+use rl_core::{derive_seed, run_episode, seeded_rng, Environment, Policy, Step};
+
+pub const LEFT: usize = 0;
+pub const RIGHT: usize = 1;
+const GOAL: usize = 3;
+// The least probability the agents give either action, and the probability of
+// right they start from.
+const MIN_PROBABILITY: f64 = 0.05;
+
+// The short corridor of Example 13.1: three states and a terminal on the
+// right, every step costing -1. Moving left in the first state leaves the
+// agent in place, and in the second state the actions are switched. The
+// states all look the same to the agent, so the best it can do is pick right
+// with some fixed probability.
+#[derive(Default)]
+pub struct ShortCorridor {
+    position: usize,
+}
+
+impl Environment for ShortCorridor {
+    type State = usize;
+    type Action = usize;
+
+    fn reset(&mut self) -> usize {
+        self.position = 0;
+        0
+    }
+
+    fn step(&mut self, action: &usize) -> Step<usize> {
+        let right = (*action == RIGHT) != (self.position == 1);
+        self.position = if right {
+            self.position + 1
+        } else {
+            self.position.saturating_sub(1)
+        };
+        Step {
+            state: self.position,
+            reward: -1.0,
+            done: self.position == GOAL,
+        }
+    }
+
+    fn actions(&self, _state: &usize) -> Vec<usize> {
+        vec![LEFT, RIGHT]
+    }
+}
+
+// The start state's value when choosing right with probability `p`, from
+// solving the Bellman equations: (2p - 4) / (p (1 - p)).
+pub fn start_value(p: f64) -> f64 {
+    (2.0 * p - 4.0) / (p * (1.0 - p))
+}
+
+// The probability of right that maximizes `start_value`, about 0.59.
+pub fn optimal_probability() -> f64 {
+    // Where the derivative of start_value vanishes: p^2 - 4p + 2 = 0.
+    2.0 - 2f64.sqrt()
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AgentConfig {
+    // REINFORCE (Section 13.3) with step size `alpha`.
+    Reinforce { alpha: f64 },
+    // REINFORCE with a learned state-value baseline (Section 13.4).
+    Baseline { alpha_theta: f64, alpha_w: f64 },
+    // One-step actor-critic (Section 13.5).
+    ActorCritic { alpha_theta: f64, alpha_w: f64 },
+}
+
+impl AgentConfig {
+    pub fn build(&self, seed: Option<u64>) -> PolicyGradientAgent {
+        PolicyGradientAgent {
+            config: *self,
+            // The poor policy the book's runs start from.
+            theta: [0.0, ((1.0 - MIN_PROBABILITY) / MIN_PROBABILITY).ln()],
+            w: [0.0; GOAL],
+            trajectory: Vec::new(),
+            rng: seeded_rng(seed),
+        }
+    }
+}
+
+// Step sizes print as powers of two where they are one, as the book gives
+// them.
+fn format_step_size(alpha: f64) -> String {
+    let exponent = alpha.log2();
+    if exponent == exponent.round() {
+        format!("2^{}", exponent)
+    } else {
+        alpha.to_string()
+    }
+}
+
+fn parse_step_size(s: &str) -> Result<f64, String> {
+    let alpha = match s.strip_prefix("2^") {
+        Some(exponent) => exponent.parse().ok().map(|e: i32| 2f64.powi(e)),
+        None => s.parse().ok(),
+    }
+    .ok_or(format!("invalid step size '{}'", s))?;
+    if alpha > 0.0 {
+        Ok(alpha)
+    } else {
+        Err(format!("step size '{}' must be positive", s))
+    }
+}
+
+impl fmt::Display for AgentConfig {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            AgentConfig::Reinforce { alpha } => write!(f, "reinforce:{}", format_step_size(alpha)),
+            AgentConfig::Baseline {
+                alpha_theta,
+                alpha_w,
+            } => write!(
+                f,
+                "baseline:{}:{}",
+                format_step_size(alpha_theta),
+                format_step_size(alpha_w)
+            ),
+            AgentConfig::ActorCritic {
+                alpha_theta,
+                alpha_w,
+            } => write!(
+                f,
+                "actor-critic:{}:{}",
+                format_step_size(alpha_theta),
+                format_step_size(alpha_w)
+            ),
+        }
+    }
+}
+
+impl FromStr for AgentConfig {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        let parts: Vec<&str> = s.split(':').collect();
+        match parts[..] {
+            ["reinforce", alpha] => Ok(AgentConfig::Reinforce {
+                alpha: parse_step_size(alpha)?,
+            }),
+            ["baseline", theta, w] => Ok(AgentConfig::Baseline {
+                alpha_theta: parse_step_size(theta)?,
+                alpha_w: parse_step_size(w)?,
+            }),
+            ["actor-critic", theta, w] => Ok(AgentConfig::ActorCritic {
+                alpha_theta: parse_step_size(theta)?,
+                alpha_w: parse_step_size(w)?,
+            }),
+            _ => Err(format!(
+                "unknown agent '{}' (expected reinforce:<alpha>, baseline:<alpha>:<alpha-w> \
+                 or actor-critic:<alpha>:<alpha-w>)",
+                s
+            )),
+        }
+    }
+}
+
+// A policy-gradient agent with softmax in action preferences (13.2) and the
+// corridor's features x(s, right) = (1, 0) and x(s, left) = (0, 1), so the
+// preference for each action is one component of theta. The baseline and
+// critic, unlike the policy, can tell the states apart: they keep one value
+// per state in `w`. A critic that couldn't would see every step's TD error as
+// -1 and only ever punish the action taken. Undiscounted, as in the book's
+// experiments. Each action keeps a probability of at least MIN_PROBABILITY:
+// a policy that all but always picked one action could take millions of steps
+// to finish an episode, and the return of such an episode would throw theta
+// further out still.
+pub struct PolicyGradientAgent {
+    config: AgentConfig,
+    // Preferences for right and left.
+    theta: [f64; 2],
+    w: [f64; GOAL],
+    // The states, actions and rewards of the episode so far, for the Monte
+    // Carlo methods.
+    trajectory: Vec<(usize, usize, f64)>,
+    rng: SmallRng,
+}
+
+impl PolicyGradientAgent {
+    pub fn probability_of_right(&self) -> f64 {
+        let softmax = 1.0 / (1.0 + (self.theta[1] - self.theta[0]).exp());
+        softmax.clamp(MIN_PROBABILITY, 1.0 - MIN_PROBABILITY)
+    }
+
+    // Moves theta by `step` times the gradient of ln pi(action).
+    fn ascend(&mut self, action: usize, step: f64) {
+        let right = self.probability_of_right();
+        let gradient = if action == RIGHT {
+            [1.0 - right, right - 1.0]
+        } else {
+            [-right, right]
+        };
+        self.theta[0] += step * gradient[0];
+        self.theta[1] += step * gradient[1];
+    }
+}
+
+impl Policy<ShortCorridor> for PolicyGradientAgent {
+    fn select_action(&mut self, _env: &ShortCorridor, _state: &usize) -> usize {
+        if self.rng.random::<f64>() < self.probability_of_right() {
+            RIGHT
+        } else {
+            LEFT
+        }
+    }
+
+    fn observe(&mut self, state: &usize, action: &usize, step: &Step<usize>) {
+        match self.config {
+            AgentConfig::ActorCritic {
+                alpha_theta,
+                alpha_w,
+            } => {
+                let next = if step.done { 0.0 } else { self.w[step.state] };
+                let delta = step.reward + next - self.w[*state];
+                self.w[*state] += alpha_w * delta;
+                self.ascend(*action, alpha_theta * delta);
+            }
+            AgentConfig::Reinforce { .. } | AgentConfig::Baseline { .. } => {
+                self.trajectory.push((*state, *action, step.reward));
+                if !step.done {
+                    return;
+                }
+                let mut g: f64 = self.trajectory.iter().map(|&(_, _, reward)| reward).sum();
+                for (state, action, reward) in std::mem::take(&mut self.trajectory) {
+                    match self.config {
+                        AgentConfig::Baseline {
+                            alpha_theta,
+                            alpha_w,
+                        } => {
+                            let delta = g - self.w[state];
+                            self.w[state] += alpha_w * delta;
+                            self.ascend(action, alpha_theta * delta);
+                        }
+                        AgentConfig::Reinforce { alpha } => self.ascend(action, alpha * g),
+                        AgentConfig::ActorCritic { .. } => unreachable!(),
+                    }
+                    g -= reward;
+                }
+            }
+        }
+    }
+}
+
+// Figures 13.1 and 13.2: the total reward of each of `episodes` episodes,
+// averaged over `runs` independent runs of `agent`.
+pub fn learning_curve(
+    agent: &AgentConfig,
+    episodes: usize,
+    runs: usize,
+    seed: Option<u64>,
+) -> Vec<f64> {
+    let mut rewards = vec![0.0; episodes];
+    for run in 0..runs as u64 {
+        let mut env = ShortCorridor::default();
+        let mut agent = agent.build(derive_seed(seed, run));
+        for total in rewards.iter_mut() {
+            *total += run_episode(&mut env, &mut agent, None).total_reward() / runs as f64;
+        }
+    }
+    rewards
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_middle_state_switches_the_actions() {
+        let mut env = ShortCorridor::default();
+        env.reset();
+        assert_eq!(env.step(&LEFT).state, 0);
+        assert_eq!(env.step(&RIGHT).state, 1);
+        assert_eq!(env.step(&RIGHT).state, 0);
+        env.step(&RIGHT);
+        assert_eq!(env.step(&LEFT).state, 2);
+        let step = env.step(&RIGHT);
+        assert!(step.done);
+        assert!((start_value(optimal_probability()) + 11.657).abs() < 1e-3);
+    }
+
+    #[test]
+    fn specs_round_trip() {
+        for spec in [
+            "reinforce:2^-13",
+            "baseline:2^-9:2^-6",
+            "actor-critic:0.01:2^-4",
+        ] {
+            let agent: AgentConfig = spec.parse().unwrap();
+            assert_eq!(agent.to_string(), spec);
+        }
+        assert!("reinforce:-1".parse::<AgentConfig>().is_err());
+        assert!("baseline:2^-9".parse::<AgentConfig>().is_err());
+    }
+
+    #[test]
+    fn every_agent_improves_on_the_initial_policy() {
+        let initial = start_value(0.05);
+        for spec in [
+            "reinforce:2^-13",
+            "baseline:2^-9:2^-6",
+            "actor-critic:2^-9:2^-6",
+        ] {
+            let agent: AgentConfig = spec.parse().unwrap();
+            let curve = learning_curve(&agent, 1000, 5, Some(1));
+            let late = curve[900..].iter().sum::<f64>() / 100.0;
+            assert!(late > initial / 2.0, "{}: {} vs {}", spec, late, initial);
+        }
+    }
+}
@@ -0,0 +1,181 @@
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::PathBuf;
+use std::process;
+
+use rl_core::{expand_config, json, write_jsonl};
+use sutton_short_corridor::*;
+
+const USAGE: &str = "Usage: sutton-short-corridor [options]
+
+Trains policy-gradient agents on the short corridor with switched actions of
+Example 13.1 and writes the total reward of each episode, averaged over runs,
+as CSV. The defaults compare the agents of Figure 13.2 with one-step
+actor-critic; for Figure 13.1 use
+--agents reinforce:2^-12,reinforce:2^-13,reinforce:2^-14.
+
+Options:
+  --agents <list>  Comma-separated agents (default: reinforce:2^-13,
+                   baseline:2^-9:2^-6,actor-critic:2^-9:2^-6)
+  --episodes <n>   Episodes per run (default: 1000)
+  --runs <n>       Independent runs to average (default: 100)
+  --out <path>     Write the CSV to <path> and print a summary instead of
+                   writing the CSV to stdout
+  --jsonl <path>   Also write each agent's episodes to <path> as lines of JSON
+                   with the settings and seed
+  --seed <n>       Seed every random number generator, so runs can be
+                   repeated exactly (default: seeded from the OS)
+  --config <path>  Read options from an experiment file of `option = value`
+                   lines, a subset of TOML; options given here override it
+
+  -h, --help       Show this message
+
+Agents: reinforce:<alpha>, baseline:<alpha>:<alpha-w> (REINFORCE with a
+state-value baseline learned with step size alpha-w) and
+actor-critic:<alpha>:<alpha-w> (one-step actor-critic). Step sizes can be
+written as powers of two, like 2^-13.";
+
+struct Options {
+    agents: Vec<AgentConfig>,
+    episodes: usize,
+    runs: usize,
+    out: Option<PathBuf>,
+    jsonl: Option<PathBuf>,
+    seed: Option<u64>,
+}
+
+fn parse_number<T: std::str::FromStr>(flag: &str, value: &str) -> Result<T, String> {
+    value
+        .parse()
+        .map_err(|_| format!("invalid value '{}' for {}", value, flag))
+}
+
+// Parses the arguments after the program name; `None` means --help.
+fn parse_args(args: impl IntoIterator<Item = String>) -> Result<Option<Options>, String> {
+    let mut options = Options {
+        agents: [
+            "reinforce:2^-13",
+            "baseline:2^-9:2^-6",
+            "actor-critic:2^-9:2^-6",
+        ]
+        .iter()
+        .map(|spec| spec.parse())
+        .collect::<Result<_, _>>()?,
+        episodes: 1000,
+        runs: 100,
+        out: None,
+        jsonl: None,
+        seed: None,
+    };
+    let mut args = args.into_iter();
+    while let Some(flag) = args.next() {
+        let mut value = || args.next().ok_or(format!("{} needs a value", flag));
+        match flag.as_str() {
+            "--agents" => {
+                options.agents = value()?
+                    .split(',')
+                    .map(str::parse)
+                    .collect::<Result<_, String>>()?
+            }
+            "--episodes" => options.episodes = parse_number(&flag, &value()?)?,
+            "--runs" => match parse_number(&flag, &value()?)? {
+                0 => return Err("--runs must be at least 1".to_string()),
+                runs => options.runs = runs,
+            },
+            "--out" => options.out = Some(PathBuf::from(value()?)),
+            "--jsonl" => options.jsonl = Some(PathBuf::from(value()?)),
+            "--seed" => options.seed = Some(parse_number(&flag, &value()?)?),
+            "-h" | "--help" => return Ok(None),
+            _ => return Err(format!("unknown option '{}'", flag)),
+        }
+    }
+    Ok(Some(options))
+}
+
+fn write_curves(
+    out: &mut dyn Write,
+    agents: &[AgentConfig],
+    curves: &[Vec<f64>],
+) -> io::Result<()> {
+    let names: Vec<String> = agents.iter().map(AgentConfig::to_string).collect();
+    writeln!(out, "episode,{}", names.join(","))?;
+    for episode in 0..curves.first().map_or(0, Vec::len) {
+        let row: Vec<String> = curves
+            .iter()
+            .map(|curve| format!("{:.3}", curve[episode]))
+            .collect();
+        writeln!(out, "{},{}", episode + 1, row.join(","))?;
+    }
+    out.flush()
+}
+
+fn main() {
+    let options = match expand_config(std::env::args().skip(1)).and_then(parse_args) {
+        Ok(Some(options)) => options,
+        Ok(None) => {
+            println!("{}", USAGE);
+            return;
+        }
+        Err(message) => {
+            eprintln!("Error: {}\n\n{}", message, USAGE);
+            process::exit(2);
+        }
+    };
+    let curves: Vec<Vec<f64>> = options
+        .agents
+        .iter()
+        .map(|agent| learning_curve(agent, options.episodes, options.runs, options.seed))
+        .collect();
+    let written = match &options.out {
+        Some(path) => File::create(path)
+            .and_then(|file| write_curves(&mut BufWriter::new(file), &options.agents, &curves)),
+        None => write_curves(&mut io::stdout().lock(), &options.agents, &curves),
+    };
+    if let Err(e) = written {
+        eprintln!("Error: couldn't write the results: {}", e);
+        process::exit(1);
+    }
+    if let Some(path) = &options.jsonl {
+        let written = write_jsonl(path, "short-corridor", options.seed, |lines| {
+            for (agent, curve) in options.agents.iter().zip(&curves) {
+                let config = json!({
+                    "agent": agent.to_string(),
+                    "episodes": options.episodes,
+                    "runs": options.runs,
+                });
+                for (episode, reward) in curve.iter().enumerate() {
+                    lines.record(
+                        "episode",
+                        &config,
+                        json!({ "episode": episode + 1, "total_reward": reward }),
+                    )?;
+                }
+            }
+            Ok(())
+        });
+        if let Err(e) = written {
+            eprintln!("Error: couldn't write {}: {}", path.display(), e);
+            process::exit(1);
+        }
+    }
+    let Some(path) = &options.out else { return };
+    println!(
+        "Total reward per episode over {} runs; written to {}",
+        options.runs,
+        path.display()
+    );
+    println!(
+        "The best policy picks right with probability {:.2} and earns {:.2}",
+        optimal_probability(),
+        start_value(optimal_probability())
+    );
+    let late = options.episodes.saturating_sub(100);
+    for (agent, curve) in options.agents.iter().zip(&curves) {
+        println!(
+            "  {:<24} last {} episodes {:.2} on average",
+            agent.to_string(),
+            curve.len() - late,
+            curve[late..].iter().sum::<f64>() / (curve.len() - late).max(1) as f64
+        );
+    }
+}
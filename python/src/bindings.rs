@@ -0,0 +1,191 @@
+// The `sutton_rl` Python module. Its classes wrap an `Env` each, and
+// `run_episode` runs rl-core's episode loop with a Python callable choosing
+// the actions. The `///` comments are the Python docstrings. The classes are
+// unsendable, used only from the thread that made them, as a tic-tac-toe
+// opponent can't be shared between threads.
+
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use rl_core::{Environment as _, Policy};
+
+use crate::Env;
+
+/// An environment simulated in Rust: reset, step and actions. States are
+/// integers: a tic-tac-toe board's base-3 index (the sum of each cell's 0
+/// for empty, 1 for X or 2 for O times 3 ** cell), a gridworld cell, and 0
+/// for a bandit's one state. Actions are cell or arm numbers.
+#[pyclass(subclass, unsendable, module = "sutton_rl")]
+pub struct Environment {
+    env: Env,
+    /// The step limit run_episode uses when none is given, None for
+    /// environments whose episodes never end.
+    #[pyo3(get)]
+    max_steps: Option<usize>,
+}
+
+#[pymethods]
+impl Environment {
+    /// Starts a new episode and returns its first state.
+    fn reset(&mut self) -> i64 {
+        self.env.reset()
+    }
+
+    /// Takes `action` and returns the next state, the reward and whether the
+    /// episode has ended.
+    fn step(&mut self, action: usize) -> PyResult<(i64, f64, bool)> {
+        let step = self.env.step(&action);
+        if self.env.take_illegal() {
+            return Err(PyValueError::new_err(format!(
+                "action {} isn't allowed here",
+                action
+            )));
+        }
+        Ok((step.state, step.reward, step.done))
+    }
+
+    /// The actions allowed in `state`.
+    fn actions(&self, state: i64) -> Vec<usize> {
+        self.env.actions(&state)
+    }
+}
+
+/// Tic-tac-toe against a fixed opponent ("random", "heuristic" or
+/// "minimax"), the learner playing `player` ("x" or "o"). Rewards are 1 for a
+/// win, -1 for a loss and 0 otherwise. With `misere`, three in a row loses;
+/// `fault_rate` is the chance a move lands on a random empty cell.
+#[pyclass(extends = Environment, unsendable, module = "sutton_rl")]
+pub struct TicTacToe;
+
+#[pymethods]
+impl TicTacToe {
+    #[new]
+    #[pyo3(signature = (opponent="random", player="x", misere=false, fault_rate=0.0, seed=None))]
+    fn new(
+        opponent: &str,
+        player: &str,
+        misere: bool,
+        fault_rate: f32,
+        seed: Option<u64>,
+    ) -> PyResult<(Self, Environment)> {
+        let env = Env::tic_tac_toe(opponent, player, misere, fault_rate, seed)
+            .map_err(PyValueError::new_err)?;
+        let max_steps = Some(9);
+        Ok((TicTacToe, Environment { env, max_steps }))
+    }
+}
+
+/// A k-armed bandit from the Section 2.3 testbed, or a nonstationary one
+/// whose values random-walk by `walk_std` a step. Episodes never end, so
+/// run_episode needs `max_steps`.
+#[pyclass(extends = Environment, unsendable, module = "sutton_rl")]
+pub struct Bandit;
+
+#[pymethods]
+impl Bandit {
+    #[new]
+    #[pyo3(signature = (arms=10, walk_std=0.0, seed=None))]
+    fn new(arms: usize, walk_std: f64, seed: Option<u64>) -> (Self, Environment) {
+        let env = Env::bandit(arms, walk_std, seed);
+        (
+            Bandit,
+            Environment {
+                env,
+                max_steps: None,
+            },
+        )
+    }
+}
+
+/// Figure 3.2's 5x5 gridworld, actions up, down, left and right as 0 to 3,
+/// started from cell `start`. Episodes never end, so run_episode needs
+/// `max_steps`.
+#[pyclass(extends = Environment, unsendable, module = "sutton_rl")]
+pub struct Gridworld;
+
+#[pymethods]
+impl Gridworld {
+    #[new]
+    #[pyo3(signature = (start=0))]
+    fn new(start: usize) -> (Self, Environment) {
+        let env = Env::gridworld(start);
+        (
+            Gridworld,
+            Environment {
+                env,
+                max_steps: None,
+            },
+        )
+    }
+}
+
+// A policy written in Python, called with the state and its allowed actions.
+// An error it raises, or a result that isn't an action, is kept for
+// `run_episode` to raise, and ends the episode by choosing an action no
+// environment allows.
+struct Callback<'py> {
+    policy: Bound<'py, PyAny>,
+    error: Option<PyErr>,
+}
+
+impl Policy<Env> for Callback<'_> {
+    fn select_action(&mut self, env: &Env, state: &i64) -> usize {
+        if self.error.is_some() {
+            return usize::MAX;
+        }
+        let chosen = self
+            .policy
+            .call1((*state, env.actions(state)))
+            .and_then(|action| action.extract::<usize>());
+        chosen.unwrap_or_else(|error| {
+            self.error = Some(error);
+            usize::MAX
+        })
+    }
+}
+
+/// Runs one episode in `env`, `policy(state, actions)` choosing each action,
+/// for at most `max_steps` steps. Returns its states, one more than its
+/// steps, its actions, its rewards, and whether it ended by itself rather
+/// than at the step limit.
+#[pyfunction]
+#[pyo3(signature = (env, policy, max_steps=None))]
+#[allow(clippy::type_complexity)]
+fn run_episode(
+    mut env: PyRefMut<'_, Environment>,
+    policy: Bound<'_, PyAny>,
+    max_steps: Option<usize>,
+) -> PyResult<(Vec<i64>, Vec<usize>, Vec<f64>, bool)> {
+    let max_steps = max_steps.or(env.max_steps).ok_or_else(|| {
+        PyValueError::new_err("this environment's episodes never end; give max_steps")
+    })?;
+    let mut callback = Callback {
+        policy,
+        error: None,
+    };
+    let episode = rl_core::run_episode(&mut env.env, &mut callback, Some(max_steps));
+    if let Some(error) = callback.error {
+        env.env.take_illegal();
+        return Err(error);
+    }
+    if env.env.take_illegal() {
+        return Err(PyValueError::new_err(
+            "the policy chose an action that isn't allowed",
+        ));
+    }
+    Ok((
+        episode.states,
+        episode.actions,
+        episode.rewards,
+        episode.terminated,
+    ))
+}
+
+#[pymodule]
+fn sutton_rl(module: &Bound<'_, PyModule>) -> PyResult<()> {
+    module.add_class::<Environment>()?;
+    module.add_class::<TicTacToe>()?;
+    module.add_class::<Bandit>()?;
+    module.add_class::<Gridworld>()?;
+    module.add_function(wrap_pyfunction!(run_episode, module)?)?;
+    Ok(())
+}
@@ -0,0 +1,224 @@
+// The book's environments behind one type with integer states, for Python to
+// drive. With the pyo3 feature the crate builds the `sutton_rl` extension
+// module (see bindings.rs); without it, it is a plain library, so the
+// workspace builds and tests without Python. States cross as integers: a
+// tic-tac-toe board's base-3 index (`Board::ternary_index`), a gridworld
+// cell, and 0 for a bandit's one state. Actions are cell or arm numbers.
+
+#[cfg(feature = "pyo3")]
+mod bindings;
+
+use rl_core::{Environment, Step};
+use sutton_bandits::BanditEnv;
+use sutton_gridworld::Gridworld;
+use sutton_tic_tac_toe::{
+    Agent, Board, HeuristicAgent, MinimaxAgent, PlayerMarker, RandomAgent, Rules, TicTacToeEnv,
+};
+
+// 3^9, one more than the largest board index.
+const BOARDS: i64 = 19_683;
+
+enum Game {
+    TicTacToe(TicTacToeEnv),
+    Bandit(BanditEnv),
+    Gridworld(Gridworld),
+}
+
+// One environment with its states as integers. An action not allowed in the
+// current state ends the episode, leaving the state as it was, and is
+// reported by `take_illegal` rather than played.
+pub struct Env {
+    game: Game,
+    state: i64,
+    illegal: bool,
+}
+
+impl Env {
+    fn from_game(game: Game) -> Self {
+        Env {
+            game,
+            state: 0,
+            illegal: false,
+        }
+    }
+
+    // Tic-tac-toe against a fixed opponent, "random", "heuristic" or
+    // "minimax", with the learner playing `player`, "x" or "o"; `misere` and
+    // `fault_rate` are as in `Rules`.
+    pub fn tic_tac_toe(
+        opponent: &str,
+        player: &str,
+        misere: bool,
+        fault_rate: f32,
+        seed: Option<u64>,
+    ) -> Result<Self, String> {
+        let opponent: Box<dyn Agent> = match opponent {
+            "random" => Box::new(RandomAgent::new(seed)),
+            "heuristic" => Box::new(HeuristicAgent::new(seed)),
+            "minimax" => Box::new(MinimaxAgent::new()),
+            _ => return Err(format!("unknown opponent {:?}", opponent)),
+        };
+        let player = match player {
+            "x" => PlayerMarker::X,
+            "o" => PlayerMarker::O,
+            _ => return Err(format!("unknown player {:?}", player)),
+        };
+        let rules = Rules { misere, fault_rate };
+        let env = TicTacToeEnv::new(opponent, player).with_rules(rules, seed.map(|seed| seed ^ 1));
+        Ok(Env::from_game(Game::TicTacToe(env)))
+    }
+
+    // A k-armed bandit from the Section 2.3 testbed, or a nonstationary one
+    // whose values random-walk with steps of standard deviation `walk_std`
+    // if that is above 0. It never ends an episode.
+    pub fn bandit(arms: usize, walk_std: f64, seed: Option<u64>) -> Self {
+        let env = if walk_std > 0.0 {
+            BanditEnv::nonstationary(arms, walk_std, seed)
+        } else {
+            BanditEnv::new(arms, seed)
+        };
+        Env::from_game(Game::Bandit(env))
+    }
+
+    // Figure 3.2's gridworld, started from cell `start`. It never ends an
+    // episode.
+    pub fn gridworld(start: usize) -> Self {
+        Env::from_game(Game::Gridworld(Gridworld::figure_3_2().with_start(start)))
+    }
+
+    // Whether the last step was an action that wasn't allowed, clearing it.
+    pub fn take_illegal(&mut self) -> bool {
+        std::mem::take(&mut self.illegal)
+    }
+}
+
+impl Environment for Env {
+    type State = i64;
+    type Action = usize;
+
+    fn reset(&mut self) -> i64 {
+        self.illegal = false;
+        self.state = match &mut self.game {
+            Game::TicTacToe(env) => env.reset().ternary_index() as i64,
+            Game::Bandit(env) => {
+                env.reset();
+                0
+            }
+            Game::Gridworld(env) => env.reset() as i64,
+        };
+        self.state
+    }
+
+    fn step(&mut self, action: &usize) -> Step<i64> {
+        if !self.actions(&self.state).contains(action) {
+            self.illegal = true;
+            return Step {
+                state: self.state,
+                reward: 0.0,
+                done: true,
+            };
+        }
+        let step = match &mut self.game {
+            Game::TicTacToe(env) => {
+                let step = env.step(action);
+                Step {
+                    state: step.state.ternary_index() as i64,
+                    reward: step.reward,
+                    done: step.done,
+                }
+            }
+            Game::Bandit(env) => env.step(action).map_state(|_| 0),
+            Game::Gridworld(env) => env.step(action).map_state(|cell| cell as i64),
+        };
+        self.state = step.state;
+        step
+    }
+
+    // No actions for states the environment can't be in.
+    fn actions(&self, &state: &i64) -> Vec<usize> {
+        match &self.game {
+            Game::TicTacToe(env) if (0..BOARDS).contains(&state) => {
+                env.actions(&Board::from_ternary_index(state as usize))
+            }
+            Game::Bandit(env) if state == 0 => env.actions(&()),
+            Game::Gridworld(env) if (0..(env.rows() * env.cols()) as i64).contains(&state) => {
+                env.actions(&(state as usize))
+            }
+            _ => Vec::new(),
+        }
+    }
+}
+
+trait MapState<S> {
+    fn map_state(self, map: impl Fn(S) -> i64) -> Step<i64>;
+}
+
+impl<S> MapState<S> for Step<S> {
+    fn map_state(self, map: impl Fn(S) -> i64) -> Step<i64> {
+        Step {
+            state: map(self.state),
+            reward: self.reward,
+            done: self.done,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rl_core::{run_episode, Policy};
+
+    struct Always(usize);
+
+    impl Policy<Env> for Always {
+        fn select_action(&mut self, env: &Env, state: &i64) -> usize {
+            let actions = env.actions(state);
+            if actions.contains(&self.0) {
+                self.0
+            } else {
+                actions[0]
+            }
+        }
+    }
+
+    #[test]
+    fn an_episode_runs_with_integer_states() {
+        let mut env = Env::tic_tac_toe("minimax", "x", false, 0.0, Some(1)).unwrap();
+        let episode = run_episode(&mut env, &mut Always(0), Some(9));
+        assert!(!env.take_illegal() && episode.terminated);
+        assert_eq!(episode.states[0], Board::new().ternary_index() as i64);
+        let last = Board::from_ternary_index(episode.states[episode.len()] as usize);
+        assert!(last.check_winner(PlayerMarker::O) || last.is_draw());
+        assert!(episode.rewards.iter().all(|&reward| reward <= 0.0));
+    }
+
+    #[test]
+    fn disallowed_actions_are_reported_not_played() {
+        let mut env = Env::tic_tac_toe("random", "x", false, 0.0, Some(2)).unwrap();
+        let start = env.reset();
+        let step = env.step(&0);
+        assert!(!env.take_illegal() && !step.done);
+        assert_ne!(step.state, start);
+        let again = env.step(&0);
+        assert!(env.take_illegal() && again.done);
+        assert_eq!(again.state, step.state);
+        assert!(!env.take_illegal());
+        let actions = env.actions(&step.state);
+        assert_eq!(actions.len(), 7);
+        assert!(!actions.contains(&0));
+        assert!(Env::tic_tac_toe("perfect", "x", false, 0.0, None).is_err());
+        assert!(Env::tic_tac_toe("random", "z", false, 0.0, None).is_err());
+    }
+
+    #[test]
+    fn bandits_and_gridworlds_run_to_the_step_limit() {
+        let mut bandit = Env::bandit(10, 0.0, Some(3));
+        let episode = run_episode(&mut bandit, &mut Always(2), Some(50));
+        assert!(!episode.terminated && episode.len() == 50);
+        assert!(episode.states.iter().all(|&state| state == 0));
+        let mut grid = Env::gridworld(1);
+        let episode = run_episode(&mut grid, &mut Always(1), Some(5));
+        assert_eq!(episode.states[0], 1);
+        assert_eq!(episode.len(), 5);
+    }
+}
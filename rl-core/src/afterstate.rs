@@ -0,0 +1,234 @@
+// Learning the values of afterstates (Section 6.8) rather than of states or
+// state-action pairs. In games like tic-tac-toe an action leads to a known
+// position before anything random happens, and many state-action pairs lead to
+// the same position, so valuing those positions learns faster.
+
+use std::collections::HashMap;
+use std::hash::Hash;
+
+use rand::rngs::SmallRng;
+use rand::Rng;
+
+use crate::{seeded_rng, Environment, Policy, Step};
+
+// An environment whose actions each lead deterministically to an afterstate,
+// before the environment's own response.
+pub trait Afterstates: Environment {
+    // Every action allowed in `state` with the afterstate it leads to.
+    fn afterstates(&self, state: &Self::State) -> Vec<(Self::Action, Self::State)>;
+}
+
+// The learner of Section 1.5: epsilon-greedy over afterstate values held in a
+// hash table, all starting at 0. Each greedy move backs the previous
+// afterstate up toward the rewards since it plus the discounted value of the
+// new one, and the end of an episode backs it up toward the final rewards.
+// Exploratory moves back nothing up, so the values learned are those of
+// greedy play. The table only holds afterstates actually backed up, so it
+// scales with what the agent visits rather than with the state space.
+pub struct AfterstateValueAgent<S> {
+    values: HashMap<S, f64>,
+    alpha: f64,
+    epsilon: f64,
+    gamma: f64,
+    learning: bool,
+    // The afterstate of the agent's last move and the rewards received since.
+    previous: Option<(S, f64)>,
+    rng: SmallRng,
+}
+
+impl<S: Clone + Eq + Hash> AfterstateValueAgent<S> {
+    pub fn new(alpha: f64, epsilon: f64, seed: Option<u64>) -> Self {
+        AfterstateValueAgent {
+            values: HashMap::new(),
+            alpha,
+            epsilon,
+            gamma: 1.0,
+            learning: true,
+            previous: None,
+            rng: seeded_rng(seed),
+        }
+    }
+
+    // Discount rate; undiscounted by default.
+    pub fn with_gamma(mut self, gamma: f64) -> Self {
+        self.gamma = gamma;
+        self
+    }
+
+    pub fn values(&self) -> &HashMap<S, f64> {
+        &self.values
+    }
+
+    pub fn value(&self, afterstate: &S) -> f64 {
+        self.values.get(afterstate).copied().unwrap_or(0.0)
+    }
+
+    // For decaying exploration between moves.
+    pub fn set_epsilon(&mut self, epsilon: f64) {
+        self.epsilon = epsilon;
+    }
+
+    // Stops learning and exploring, for evaluation.
+    pub fn freeze(&mut self) {
+        self.learning = false;
+    }
+
+    pub fn unfreeze(&mut self) {
+        self.learning = true;
+    }
+
+    // Picks one of `afterstates`, backing up the previous afterstate if the
+    // pick is greedy. Ties go to the first of the best afterstates, so play
+    // in positions the agent knows nothing about is consistent and a line
+    // that works is learned quickly.
+    pub fn choose<A>(&mut self, mut afterstates: Vec<(A, S)>) -> A {
+        assert!(!afterstates.is_empty(), "no actions to choose from");
+        let epsilon = if self.learning { self.epsilon } else { 0.0 };
+        let index = if self.rng.random::<f64>() < epsilon {
+            self.previous = None;
+            self.rng.random_range(0..afterstates.len())
+        } else {
+            let values: Vec<f64> = afterstates.iter().map(|(_, s)| self.value(s)).collect();
+            let best = (0..values.len()).fold(
+                0,
+                |best, i| {
+                    if values[i] > values[best] {
+                        i
+                    } else {
+                        best
+                    }
+                },
+            );
+            self.backup(self.gamma * values[best]);
+            best
+        };
+        let (action, afterstate) = afterstates.swap_remove(index);
+        self.previous = Some((afterstate, 0.0));
+        action
+    }
+
+    // Counts `reward` toward the backup of the last afterstate.
+    pub fn reward(&mut self, reward: f64) {
+        if let Some((_, rewards)) = &mut self.previous {
+            *rewards += reward;
+        }
+    }
+
+    // Ends the episode with a last `reward`.
+    pub fn finish(&mut self, reward: f64) {
+        self.reward(reward);
+        self.backup(0.0);
+    }
+
+    // Moves the previous afterstate toward the rewards since it plus `next`,
+    // and forgets it.
+    fn backup(&mut self, next: f64) {
+        let Some((afterstate, rewards)) = self.previous.take() else {
+            return;
+        };
+        if self.learning {
+            let value = self.values.entry(afterstate).or_insert(0.0);
+            *value += self.alpha * (rewards + next - *value);
+        }
+    }
+}
+
+impl<E> Policy<E> for AfterstateValueAgent<E::State>
+where
+    E: Afterstates + ?Sized,
+    E::State: Eq + Hash,
+{
+    fn select_action(&mut self, env: &E, state: &E::State) -> E::Action {
+        self.choose(env.afterstates(state))
+    }
+
+    fn observe(&mut self, _state: &E::State, _action: &E::Action, step: &Step<E::State>) {
+        if step.done {
+            self.finish(step.reward);
+        } else {
+            self.reward(step.reward);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::run_episode;
+
+    // Nim with one pile of `start` stones: the agent takes 1 or 2 and a
+    // fixed opponent then takes 1. Taking the last stone wins +1; leaving the
+    // opponent the last stone loses -1. The afterstate is the pile the
+    // opponent faces.
+    struct Nim {
+        start: u32,
+        pile: u32,
+    }
+
+    impl Environment for Nim {
+        type State = u32;
+        type Action = u32;
+
+        fn reset(&mut self) -> u32 {
+            self.pile = self.start;
+            self.pile
+        }
+
+        fn step(&mut self, action: &u32) -> Step<u32> {
+            self.pile -= action;
+            if self.pile == 0 {
+                return Step {
+                    state: 0,
+                    reward: 1.0,
+                    done: true,
+                };
+            }
+            self.pile -= 1;
+            Step {
+                state: self.pile,
+                reward: if self.pile == 0 { -1.0 } else { 0.0 },
+                done: self.pile == 0,
+            }
+        }
+
+        fn actions(&self, state: &u32) -> Vec<u32> {
+            (1..=2).filter(|&take| take <= *state).collect()
+        }
+    }
+
+    impl Afterstates for Nim {
+        fn afterstates(&self, state: &u32) -> Vec<(u32, u32)> {
+            self.actions(state)
+                .into_iter()
+                .map(|take| (take, state - take))
+                .collect()
+        }
+    }
+
+    #[test]
+    fn learns_never_to_leave_a_single_stone() {
+        let mut env = Nim { start: 7, pile: 0 };
+        let mut agent = AfterstateValueAgent::new(0.2, 0.1, Some(1));
+        for _ in 0..2000 {
+            run_episode(&mut env, &mut agent, None);
+        }
+        // Against an opponent who always takes one, only leaving one stone
+        // loses; the agent can always move to an even pile from any other.
+        assert!(agent.value(&1) < 0.0);
+        assert!(agent.value(&2) > 0.5 && agent.value(&6) > 0.5);
+        agent.freeze();
+        let episode = run_episode(&mut env, &mut agent, None);
+        assert_eq!(episode.total_reward(), 1.0);
+    }
+
+    #[test]
+    fn exploratory_moves_back_nothing_up() {
+        let mut agent = AfterstateValueAgent::new(0.5, 1.0, Some(2));
+        agent.choose(vec![((), 'a')]);
+        agent.choose(vec![((), 'b')]);
+        agent.finish(1.0);
+        // Only the last afterstate, backed up at the end, has a value.
+        assert_eq!(agent.values().len(), 1);
+        assert_eq!(agent.value(&'b'), 0.5);
+    }
+}
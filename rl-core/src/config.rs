@@ -0,0 +1,269 @@
+use std::fs;
+
+// Experiment files: the options of a long command line kept in a file read
+// with `--config <path>`. They are a subset of TOML, one `key = value` a
+// line:
+//
+//     # Figure 2.6 at a tenth of the runs.
+//     command = "sweep"
+//     algorithms = ["epsilon-greedy", "ucb"]
+//     runs = 200
+//     seed = 7
+//     out = "sweep.csv"
+//
+// Each key is the option without its dashes, with `_` or `-` between words,
+// and becomes that option: strings and numbers are its argument, arrays are
+// joined with commas as in lists like --agents, `true` gives a switch such as
+// --king-moves and `false` leaves it out. `command` names the binary's
+// command, used when the command line doesn't give one. Tables and values
+// spanning lines aren't supported.
+
+// A key's value, as it is passed on.
+#[derive(Debug, PartialEq)]
+enum Setting {
+    Switch(bool),
+    Argument(String),
+}
+
+// `line` without any comment, leaving `#`s inside strings be.
+fn strip_comment(line: &str) -> &str {
+    let mut quote = None;
+    let mut escaped = false;
+    for (i, c) in line.char_indices() {
+        match quote {
+            Some('"') if escaped => escaped = false,
+            Some('"') if c == '\\' => escaped = true,
+            Some(open) if c == open => quote = None,
+            Some(_) => {}
+            None if c == '"' || c == '\'' => quote = Some(c),
+            None if c == '#' => return &line[..i],
+            None => {}
+        }
+    }
+    line
+}
+
+// A string or number, as the option's argument.
+fn scalar(text: &str) -> Result<String, String> {
+    if let Some(rest) = text.strip_prefix('"') {
+        let mut value = String::new();
+        let mut chars = rest.chars();
+        while let Some(c) = chars.next() {
+            match c {
+                '"' if chars.as_str().trim().is_empty() => return Ok(value),
+                '"' => break,
+                '\\' => match chars.next() {
+                    Some('n') => value.push('\n'),
+                    Some('t') => value.push('\t'),
+                    Some(c @ ('"' | '\\')) => value.push(c),
+                    _ => return Err(format!("unsupported escape in {}", text)),
+                },
+                c => value.push(c),
+            }
+        }
+        return Err(format!("badly quoted string {}", text));
+    }
+    if let Some(rest) = text.strip_prefix('\'') {
+        return match rest.strip_suffix('\'') {
+            Some(value) if !value.contains('\'') => Ok(value.to_string()),
+            _ => Err(format!("badly quoted string {}", text)),
+        };
+    }
+    let number = text.replace('_', "");
+    if number.parse::<f64>().is_ok() {
+        Ok(number)
+    } else {
+        Err(format!(
+            "'{}' is not a string, number, boolean or array",
+            text
+        ))
+    }
+}
+
+// The items of a one-line array, between its brackets.
+fn array_items(inner: &str) -> Result<Vec<String>, String> {
+    let mut items = Vec::new();
+    let mut item = String::new();
+    let mut quote = None;
+    for c in inner.chars() {
+        match quote {
+            Some(open) if c == open => quote = None,
+            Some(_) => {}
+            None if c == '"' || c == '\'' => quote = Some(c),
+            None if c == ',' => {
+                items.push(scalar(item.trim())?);
+                item.clear();
+                continue;
+            }
+            None => {}
+        }
+        item.push(c);
+    }
+    // A trailing comma is allowed.
+    if !item.trim().is_empty() {
+        items.push(scalar(item.trim())?);
+    }
+    Ok(items)
+}
+
+fn parse_value(text: &str) -> Result<Setting, String> {
+    match text {
+        "true" => Ok(Setting::Switch(true)),
+        "false" => Ok(Setting::Switch(false)),
+        _ if text.starts_with('[') => {
+            match text.strip_prefix('[').and_then(|t| t.strip_suffix(']')) {
+                Some(inner) => Ok(Setting::Argument(array_items(inner)?.join(","))),
+                None => Err("arrays must open and close on one line".to_string()),
+            }
+        }
+        _ => scalar(text).map(Setting::Argument),
+    }
+}
+
+// The settings in an experiment file's `text`, in order, as (key, value)
+// with keys spelled as options, dashes between words.
+fn parse_file(text: &str) -> Result<Vec<(String, Setting)>, String> {
+    let mut settings: Vec<(String, Setting)> = Vec::new();
+    for (number, line) in text.lines().enumerate() {
+        let line = strip_comment(line).trim();
+        if line.is_empty() {
+            continue;
+        }
+        let at = |message: String| format!("line {}: {}", number + 1, message);
+        if line.starts_with('[') {
+            return Err(at("tables aren't supported".to_string()));
+        }
+        let Some((key, value)) = line.split_once('=') else {
+            return Err(at("expected key = value".to_string()));
+        };
+        let key = key.trim().trim_matches('"').replace('_', "-");
+        if key.is_empty() || !key.chars().all(|c| c.is_ascii_alphanumeric() || c == '-') {
+            return Err(at(format!("invalid key '{}'", key)));
+        }
+        if settings.iter().any(|(seen, _)| *seen == key) {
+            return Err(at(format!("{} is set twice", key)));
+        }
+        let value = parse_value(value.trim()).map_err(at)?;
+        settings.push((key, value));
+    }
+    Ok(settings)
+}
+
+// `args`, the arguments after the program name, with `--config <path>`
+// replaced by the options the file at `path` sets. Those go after the
+// command and any other leading words, ahead of every option given on the
+// command line, so options given there override the file's. Errors are
+// messages for the user.
+pub fn expand_config(args: impl IntoIterator<Item = String>) -> Result<Vec<String>, String> {
+    let mut args: Vec<String> = args.into_iter().collect();
+    let Some(at) = args.iter().position(|arg| arg == "--config") else {
+        return Ok(args);
+    };
+    let Some(path) = args.get(at + 1).cloned() else {
+        return Err("--config needs a value".to_string());
+    };
+    args.drain(at..at + 2);
+    if args.iter().any(|arg| arg == "--config") {
+        return Err("--config can only be given once".to_string());
+    }
+    let text =
+        fs::read_to_string(&path).map_err(|error| format!("could not read {}: {}", path, error))?;
+    let settings = parse_file(&text).map_err(|error| format!("{}: {}", path, error))?;
+
+    let leading = args.iter().take_while(|arg| !arg.starts_with('-')).count();
+    let mut expanded: Vec<String> = args[..leading].to_vec();
+    let mut options = Vec::new();
+    for (key, setting) in settings {
+        match (key.as_str(), setting) {
+            ("command", Setting::Argument(command)) => {
+                if leading == 0 {
+                    expanded.push(command);
+                }
+            }
+            ("command", Setting::Switch(_)) => {
+                return Err(format!("{}: command must be a string", path))
+            }
+            (_, Setting::Switch(true)) => options.push(format!("--{}", key)),
+            (_, Setting::Switch(false)) => {}
+            (_, Setting::Argument(value)) => {
+                options.push(format!("--{}", key));
+                options.push(value);
+            }
+        }
+    }
+    expanded.extend(options);
+    expanded.extend(args.drain(leading..));
+    Ok(expanded)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn values_become_option_arguments() {
+        let text = r#"
+            # A comment, and one after a value.
+            command = "sweep"   # the parameter study
+            algorithms = ["epsilon-greedy", 'ucb',]
+            runs = 2_000
+            true_mean = -1.5
+            out = "runs #1.csv"
+            king-moves = true
+            stay = false
+        "#;
+        let settings = parse_file(text).unwrap();
+        let argument = |value: &str| Setting::Argument(value.to_string());
+        assert_eq!(
+            settings,
+            vec![
+                ("command".to_string(), argument("sweep")),
+                ("algorithms".to_string(), argument("epsilon-greedy,ucb")),
+                ("runs".to_string(), argument("2000")),
+                ("true-mean".to_string(), argument("-1.5")),
+                ("out".to_string(), argument("runs #1.csv")),
+                ("king-moves".to_string(), Setting::Switch(true)),
+                ("stay".to_string(), Setting::Switch(false)),
+            ]
+        );
+    }
+
+    #[test]
+    fn malformed_files_are_rejected_with_their_line() {
+        for (text, error) in [
+            ("runs 100", "line 1: expected key = value"),
+            ("[testbed]", "line 1: tables aren't supported"),
+            ("agents = greedy", "line 1: 'greedy' is not"),
+            ("out = \"a.csv", "line 1: badly quoted string"),
+            ("runs = 1\nruns = 2", "line 2: runs is set twice"),
+            ("agents = [\n", "line 1: arrays must"),
+        ] {
+            let message = parse_file(text).unwrap_err();
+            assert!(message.starts_with(error), "{}: {}", text, message);
+        }
+    }
+
+    #[test]
+    fn command_line_options_come_after_the_files() {
+        let path = std::env::temp_dir().join("rl-core-expand-config.toml");
+        fs::write(&path, "command = \"sweep\"\nruns = 10\nseed = 3\n").unwrap();
+        let path = path.to_str().unwrap().to_string();
+        let args = |list: &[&str]| -> Vec<String> {
+            list.iter().map(|arg| arg.replace("PATH", &path)).collect()
+        };
+        assert_eq!(
+            expand_config(args(&["--config", "PATH", "--runs", "20"])).unwrap(),
+            args(&["sweep", "--runs", "10", "--seed", "3", "--runs", "20"])
+        );
+        // A command given on the command line wins over the file's.
+        assert_eq!(
+            expand_config(args(&["testbed", "--config", "PATH"])).unwrap(),
+            args(&["testbed", "--runs", "10", "--seed", "3"])
+        );
+        assert_eq!(
+            expand_config(args(&["testbed", "--runs", "5"])).unwrap(),
+            args(&["testbed", "--runs", "5"])
+        );
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,271 @@
+// Dynamic programming over a finite MDP whose dynamics p(s', r | s, a) are
+// known (Chapters 3 and 4). States and actions are indices; a state with no
+// actions is terminal and keeps value 0, and an action `is_available` rules
+// out is never chosen.
+
+// One outcome of taking an action: with `probability`, the environment moves
+// to `next_state` and pays `reward`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Transition {
+    pub probability: f64,
+    pub next_state: usize,
+    pub reward: f64,
+}
+
+pub trait FiniteMdp {
+    fn num_states(&self) -> usize;
+
+    // Actions in `state` are 0..num_actions(state).
+    fn num_actions(&self, state: usize) -> usize;
+
+    // Every outcome of `action` in `state`; the probabilities sum to 1.
+    fn transitions(&self, state: usize, action: usize) -> Vec<Transition>;
+
+    // Whether `action` can be taken in `state`, for MDPs whose action sets
+    // differ between states. Every action can by default.
+    fn is_available(&self, _state: usize, _action: usize) -> bool {
+        true
+    }
+}
+
+fn available_actions<M: FiniteMdp + ?Sized>(
+    mdp: &M,
+    state: usize,
+) -> impl Iterator<Item = usize> + '_ {
+    (0..mdp.num_actions(state)).filter(move |&action| mdp.is_available(state, action))
+}
+
+// q(s, a) = sum over s', r of p(s', r | s, a) [r + gamma v(s')].
+pub fn action_value<M: FiniteMdp + ?Sized>(
+    mdp: &M,
+    values: &[f64],
+    state: usize,
+    action: usize,
+    gamma: f64,
+) -> f64 {
+    mdp.transitions(state, action)
+        .iter()
+        .map(|t| t.probability * (t.reward + gamma * values[t.next_state]))
+        .sum()
+}
+
+// One in-place sweep of iterative policy evaluation (Section 4.1) for the
+// policy giving action `a` in state `s` probability `policy(s, a)`. Returns
+// the largest change to any value.
+pub fn policy_evaluation_sweep<M: FiniteMdp + ?Sized>(
+    mdp: &M,
+    policy: &impl Fn(usize, usize) -> f64,
+    gamma: f64,
+    values: &mut [f64],
+) -> f64 {
+    let mut delta: f64 = 0.0;
+    for state in 0..mdp.num_states() {
+        // Skipping actions the policy never takes saves evaluating them.
+        let value: f64 = available_actions(mdp, state)
+            .map(|action| (action, policy(state, action)))
+            .filter(|&(_, probability)| probability > 0.0)
+            .map(|(action, probability)| {
+                probability * action_value(mdp, values, state, action, gamma)
+            })
+            .sum();
+        delta = delta.max((value - values[state]).abs());
+        values[state] = value;
+    }
+    delta
+}
+
+// Sweeps policy evaluation from `values` until no value changes by more than
+// `theta`. Returns the number of sweeps.
+pub fn evaluate_policy<M: FiniteMdp + ?Sized>(
+    mdp: &M,
+    policy: &impl Fn(usize, usize) -> f64,
+    gamma: f64,
+    theta: f64,
+    values: &mut [f64],
+) -> usize {
+    let mut sweeps = 1;
+    while policy_evaluation_sweep(mdp, policy, gamma, values) > theta {
+        sweeps += 1;
+    }
+    sweeps
+}
+
+// One in-place sweep of value iteration (Section 4.4), backing every state up
+// with the Bellman optimality equation. Returns the largest change.
+pub fn value_iteration_sweep<M: FiniteMdp + ?Sized>(
+    mdp: &M,
+    gamma: f64,
+    values: &mut [f64],
+) -> f64 {
+    let mut delta: f64 = 0.0;
+    for state in 0..mdp.num_states() {
+        let Some(value) = available_actions(mdp, state)
+            .map(|action| action_value(mdp, values, state, action, gamma))
+            .reduce(f64::max)
+        else {
+            continue;
+        };
+        delta = delta.max((value - values[state]).abs());
+        values[state] = value;
+    }
+    delta
+}
+
+// Sweeps value iteration from `values` until no value changes by more than
+// `theta`, leaving v* in `values`. Returns the number of sweeps.
+pub fn value_iteration<M: FiniteMdp + ?Sized>(
+    mdp: &M,
+    gamma: f64,
+    theta: f64,
+    values: &mut [f64],
+) -> usize {
+    let mut sweeps = 1;
+    while value_iteration_sweep(mdp, gamma, values) > theta {
+        sweeps += 1;
+    }
+    sweeps
+}
+
+// The actions in each state whose value under `values` is within `tolerance`
+// of the best, in index order; empty for terminal states.
+pub fn greedy_actions<M: FiniteMdp + ?Sized>(
+    mdp: &M,
+    values: &[f64],
+    gamma: f64,
+    tolerance: f64,
+) -> Vec<Vec<usize>> {
+    (0..mdp.num_states())
+        .map(|state| {
+            let q: Vec<(usize, f64)> = available_actions(mdp, state)
+                .map(|action| (action, action_value(mdp, values, state, action, gamma)))
+                .collect();
+            let best = q
+                .iter()
+                .map(|&(_, value)| value)
+                .fold(f64::NEG_INFINITY, f64::max);
+            q.iter()
+                .filter(|&&(_, value)| value >= best - tolerance)
+                .map(|&(action, _)| action)
+                .collect()
+        })
+        .collect()
+}
+
+// Policy iteration (Section 4.3) from the deterministic `policy`, giving
+// `policy[s]` in state s: evaluates the policy into `values` to within
+// `theta`, then makes it greedy, until it stops changing. An action only
+// replaces the current one if it is better by more than `theta`, so ties
+// can't make the policy flip back and forth forever. `on_iteration` sees each
+// policy with its values, starting with the initial one. Returns the number
+// of policies evaluated.
+pub fn policy_iteration<M: FiniteMdp + ?Sized>(
+    mdp: &M,
+    gamma: f64,
+    theta: f64,
+    policy: &mut [usize],
+    values: &mut [f64],
+    mut on_iteration: impl FnMut(usize, &[usize], &[f64]),
+) -> usize {
+    let mut iteration = 0;
+    loop {
+        let current = &*policy;
+        evaluate_policy(
+            mdp,
+            &|state, action| if current[state] == action { 1.0 } else { 0.0 },
+            gamma,
+            theta,
+            values,
+        );
+        on_iteration(iteration, policy, values);
+        iteration += 1;
+        let mut stable = true;
+        for (state, chosen) in policy.iter_mut().enumerate() {
+            let current = action_value(mdp, values, state, *chosen, gamma);
+            let best = available_actions(mdp, state)
+                .map(|action| (action, action_value(mdp, values, state, action, gamma)))
+                .max_by(|a, b| a.1.total_cmp(&b.1));
+            if let Some((action, value)) = best {
+                if value > current + theta {
+                    *chosen = action;
+                    stable = false;
+                }
+            }
+        }
+        if stable {
+            return iteration;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Two states: from 0, action 0 stays for reward 1 and action 1 moves to
+    // the terminal state 1 for reward 5.
+    struct Choice;
+
+    impl FiniteMdp for Choice {
+        fn num_states(&self) -> usize {
+            2
+        }
+
+        fn num_actions(&self, state: usize) -> usize {
+            if state == 0 {
+                2
+            } else {
+                0
+            }
+        }
+
+        fn transitions(&self, _state: usize, action: usize) -> Vec<Transition> {
+            vec![Transition {
+                probability: 1.0,
+                next_state: action,
+                reward: if action == 0 { 1.0 } else { 5.0 },
+            }]
+        }
+    }
+
+    #[test]
+    fn staying_forever_is_worth_a_geometric_series() {
+        let mut values = vec![0.0; 2];
+        evaluate_policy(
+            &Choice,
+            &|_, a| if a == 0 { 1.0 } else { 0.0 },
+            0.9,
+            1e-10,
+            &mut values,
+        );
+        assert!((values[0] - 10.0).abs() < 1e-6, "{:?}", values);
+        assert_eq!(values[1], 0.0);
+    }
+
+    #[test]
+    fn value_iteration_picks_the_better_action() {
+        let mut values = vec![0.0; 2];
+        value_iteration(&Choice, 0.5, 1e-10, &mut values);
+        assert!((values[0] - 5.0).abs() < 1e-6);
+        assert_eq!(
+            greedy_actions(&Choice, &values, 0.5, 1e-9),
+            vec![vec![1], vec![]]
+        );
+        values.fill(0.0);
+        value_iteration(&Choice, 0.9, 1e-10, &mut values);
+        assert_eq!(greedy_actions(&Choice, &values, 0.9, 1e-9)[0], vec![0]);
+    }
+
+    #[test]
+    fn policy_iteration_switches_to_the_better_action() {
+        let mut policy = vec![1, 0];
+        let mut values = vec![0.0; 2];
+        let mut seen = Vec::new();
+        let iterations =
+            policy_iteration(&Choice, 0.9, 1e-9, &mut policy, &mut values, |i, p, _| {
+                seen.push((i, p[0]))
+            });
+        assert_eq!(iterations, 2);
+        assert_eq!(seen, vec![(0, 1), (1, 0)]);
+        assert!((values[0] - 10.0).abs() < 1e-6);
+    }
+}
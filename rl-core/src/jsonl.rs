@@ -0,0 +1,89 @@
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+use serde_json::{json, Value};
+
+// Results as JSON Lines, one object a line, so every chapter's output loads
+// the same way elsewhere (pandas' `read_json(path, lines=True)`, say):
+//
+//     {"config":{"alpha":0.5,"method":"sarsa",...},"experiment":"cliff-walking",
+//      "metrics":{"episode":1,...},"record":"episode","seed":7}
+//
+// `experiment` names the binary and command, `record` what a line measures,
+// such as an episode or an evaluation, `config` the settings it was measured
+// under and `metrics` the measurements. `seed` is null for runs seeded from
+// the OS.
+pub struct JsonLines<'a> {
+    writer: &'a mut dyn Write,
+    experiment: &'a str,
+    seed: Option<u64>,
+}
+
+impl JsonLines<'_> {
+    pub fn record(&mut self, record: &str, config: &Value, metrics: Value) -> io::Result<()> {
+        let line = json!({
+            "experiment": self.experiment,
+            "record": record,
+            "seed": self.seed,
+            "config": config,
+            "metrics": metrics,
+        });
+        writeln!(self.writer, "{}", line)
+    }
+}
+
+// Creates the JSON Lines file at `path` and writes it with `write`.
+pub fn write_jsonl(
+    path: &Path,
+    experiment: &str,
+    seed: Option<u64>,
+    write: impl FnOnce(&mut JsonLines) -> io::Result<()>,
+) -> io::Result<()> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    write(&mut JsonLines {
+        writer: &mut writer,
+        experiment,
+        seed,
+    })?;
+    writer.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn records_carry_the_experiment_seed_and_config() {
+        let mut buffer = Vec::new();
+        let mut lines = JsonLines {
+            writer: &mut buffer,
+            experiment: "maze",
+            seed: Some(3),
+        };
+        let config = json!({"alpha": 0.5});
+        lines
+            .record("episode", &config, json!({"episode": 1, "steps": 12.5}))
+            .unwrap();
+        lines
+            .record("episode", &config, json!({"episode": 2, "steps": f64::NAN}))
+            .unwrap();
+        let text = String::from_utf8(buffer).unwrap();
+        let records: Vec<Value> = text
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(
+            records[0],
+            json!({
+                "experiment": "maze",
+                "record": "episode",
+                "seed": 3,
+                "config": {"alpha": 0.5},
+                "metrics": {"episode": 1, "steps": 12.5},
+            })
+        );
+        // JSON has no NaN; it becomes null.
+        assert_eq!(records[1]["metrics"]["steps"], Value::Null);
+    }
+}
@@ -0,0 +1,260 @@
+use rand::rngs::SmallRng;
+use rand::{Rng, SeedableRng};
+
+mod afterstate;
+mod config;
+mod dp;
+mod jsonl;
+#[cfg(feature = "plot")]
+mod plot;
+mod sweep;
+mod td;
+mod tiles;
+mod traces;
+mod trajectory;
+
+pub use afterstate::{AfterstateValueAgent, Afterstates};
+pub use config::expand_config;
+pub use dp::{
+    action_value, evaluate_policy, greedy_actions, policy_evaluation_sweep, policy_iteration,
+    value_iteration, value_iteration_sweep, FiniteMdp, Transition,
+};
+pub use jsonl::{write_jsonl, JsonLines};
+#[cfg(feature = "plot")]
+pub use plot::{write_svg, Chart};
+// For building JSON Lines records without each binary depending on serde_json.
+pub use serde_json::{json, Value};
+pub use sweep::{
+    mean_and_std_error, paired_t_test, parse_values, write_sweep_table, Configuration, Goal,
+    Ranked, Sweep,
+};
+pub use td::{
+    epsilon_greedy, epsilon_greedy_expectation, DoubleQLearningAgent, TdAgent, TdControl,
+};
+pub use tiles::TileCoder;
+pub use traces::{EligibilityTraces, TraceKind};
+pub use trajectory::{read_trajectories, Packed, TrajectoryReader, TrajectoryRecorder};
+
+// A generator seeded from `seed`, or from OS entropy when it is `None`.
+pub fn seeded_rng(seed: Option<u64>) -> SmallRng {
+    match seed {
+        Some(seed) => SmallRng::seed_from_u64(seed),
+        None => SmallRng::from_os_rng(),
+    }
+}
+
+// A seed for the `offset`th generator of a run seeded with `seed`, so that
+// generators sharing one --seed still draw different numbers.
+pub fn derive_seed(seed: Option<u64>, offset: u64) -> Option<u64> {
+    seed.map(|seed| seed.wrapping_add(offset))
+}
+
+// A sample from the normal distribution with the given mean and standard
+// deviation, by the Box-Muller transform.
+pub fn sample_normal(rng: &mut impl Rng, mean: f64, std_dev: f64) -> f64 {
+    // 1 - u keeps the logarithm's argument in (0, 1].
+    let u: f64 = 1.0 - rng.random::<f64>();
+    let v: f64 = rng.random();
+    mean + std_dev * (-2.0 * u.ln()).sqrt() * (2.0 * std::f64::consts::PI * v).cos()
+}
+
+// What an environment returns for each action: the next state, the reward
+// for the transition and whether the episode has ended.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Step<S> {
+    pub state: S,
+    pub reward: f64,
+    pub done: bool,
+}
+
+// The world side of the agent-environment interface of Section 3.1. Continuing
+// tasks, like a bandit, never report `done`.
+pub trait Environment {
+    type State: Clone;
+    type Action: Clone;
+
+    // Starts a new episode and returns its first state.
+    fn reset(&mut self) -> Self::State;
+
+    fn step(&mut self, action: &Self::Action) -> Step<Self::State>;
+
+    // The actions allowed in `state`.
+    fn actions(&self, state: &Self::State) -> Vec<Self::Action>;
+}
+
+// The agent side: picks an action in each state, and learns from each
+// transition if it learns online. `observe` defaults to doing nothing.
+pub trait Policy<E: Environment + ?Sized> {
+    fn select_action(&mut self, env: &E, state: &E::State) -> E::Action;
+
+    fn observe(&mut self, _state: &E::State, _action: &E::Action, _step: &Step<E::State>) {}
+}
+
+// One episode as S0, A0, R1, S1, A1, R2, ...: `states` holds every state
+// visited including the last, so it is one longer than `actions` and
+// `rewards`. `rewards[t]` is R(t+1), the reward for taking `actions[t]`.
+#[derive(Clone, Debug)]
+pub struct Episode<S, A> {
+    pub states: Vec<S>,
+    pub actions: Vec<A>,
+    pub rewards: Vec<f64>,
+    // False when the episode was cut off by the step limit.
+    pub terminated: bool,
+}
+
+impl<S, A> Episode<S, A> {
+    pub fn len(&self) -> usize {
+        self.actions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.actions.is_empty()
+    }
+
+    pub fn total_reward(&self) -> f64 {
+        self.rewards.iter().sum()
+    }
+
+    // G0 = R1 + gamma R2 + gamma^2 R3 + ...
+    pub fn discounted_return(&self, gamma: f64) -> f64 {
+        self.rewards.iter().rev().fold(0.0, |g, r| r + gamma * g)
+    }
+}
+
+// Runs one episode of `policy` in `env`, calling `observe` after every step,
+// until the environment reports `done` or `max_steps` steps have been taken.
+// A continuing environment needs `max_steps`, or this never returns.
+pub fn run_episode<E, P>(
+    env: &mut E,
+    policy: &mut P,
+    max_steps: Option<usize>,
+) -> Episode<E::State, E::Action>
+where
+    E: Environment,
+    P: Policy<E> + ?Sized,
+{
+    let mut state = env.reset();
+    let mut episode = Episode {
+        states: vec![state.clone()],
+        actions: Vec::new(),
+        rewards: Vec::new(),
+        terminated: false,
+    };
+    while max_steps.is_none_or(|max| episode.len() < max) {
+        let action = policy.select_action(env, &state);
+        let step = env.step(&action);
+        policy.observe(&state, &action, &step);
+        episode.states.push(step.state.clone());
+        episode.actions.push(action);
+        episode.rewards.push(step.reward);
+        state = step.state;
+        if step.done {
+            episode.terminated = true;
+            break;
+        }
+    }
+    episode
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A corridor of `length` cells entered at 0; stepping right from the last
+    // cell ends the episode with reward 1, every other step costs 0.
+    struct Corridor {
+        length: i32,
+        position: i32,
+    }
+
+    impl Environment for Corridor {
+        type State = i32;
+        type Action = i32;
+
+        fn reset(&mut self) -> i32 {
+            self.position = 0;
+            0
+        }
+
+        fn step(&mut self, action: &i32) -> Step<i32> {
+            self.position = (self.position + action).max(0);
+            let done = self.position == self.length;
+            Step {
+                state: self.position,
+                reward: if done { 1.0 } else { 0.0 },
+                done,
+            }
+        }
+
+        fn actions(&self, _state: &i32) -> Vec<i32> {
+            vec![-1, 1]
+        }
+    }
+
+    struct AlwaysRight {
+        observed: usize,
+    }
+
+    impl Policy<Corridor> for AlwaysRight {
+        fn select_action(&mut self, env: &Corridor, state: &i32) -> i32 {
+            assert_eq!(*state, env.position);
+            1
+        }
+
+        fn observe(&mut self, _state: &i32, _action: &i32, _step: &Step<i32>) {
+            self.observed += 1;
+        }
+    }
+
+    #[test]
+    fn runs_an_episode_to_termination() {
+        let mut env = Corridor {
+            length: 3,
+            position: 0,
+        };
+        let mut policy = AlwaysRight { observed: 0 };
+        let episode = run_episode(&mut env, &mut policy, None);
+        assert_eq!(episode.states, vec![0, 1, 2, 3]);
+        assert_eq!(episode.actions, vec![1, 1, 1]);
+        assert_eq!(episode.rewards, vec![0.0, 0.0, 1.0]);
+        assert!(episode.terminated);
+        assert_eq!(policy.observed, 3);
+        assert_eq!(episode.discounted_return(0.5), 0.25);
+    }
+
+    #[test]
+    fn stops_at_the_step_limit() {
+        let mut env = Corridor {
+            length: 10,
+            position: 0,
+        };
+        let episode = run_episode(&mut env, &mut AlwaysRight { observed: 0 }, Some(4));
+        assert_eq!(episode.len(), 4);
+        assert_eq!(episode.states.len(), 5);
+        assert!(!episode.terminated);
+        assert_eq!(episode.total_reward(), 0.0);
+    }
+
+    #[test]
+    fn normal_samples_have_the_requested_moments() {
+        let mut rng = seeded_rng(Some(1));
+        let samples: Vec<f64> = (0..100_000)
+            .map(|_| sample_normal(&mut rng, 2.0, 3.0))
+            .collect();
+        let mean = samples.iter().sum::<f64>() / samples.len() as f64;
+        let variance =
+            samples.iter().map(|x| (x - mean).powi(2)).sum::<f64>() / samples.len() as f64;
+        assert!((mean - 2.0).abs() < 0.05, "mean {}", mean);
+        assert!(
+            (variance.sqrt() - 3.0).abs() < 0.05,
+            "std dev {}",
+            variance.sqrt()
+        );
+    }
+
+    #[test]
+    fn derived_seeds_differ_and_repeat() {
+        assert_eq!(derive_seed(Some(1), 2), Some(3));
+        assert_eq!(derive_seed(None, 2), None);
+    }
+}
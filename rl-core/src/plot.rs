@@ -0,0 +1,276 @@
+use std::fmt::Write as _;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+const WIDTH: f64 = 720.0;
+const HEIGHT: f64 = 420.0;
+// Room around the plot area for the title, tick labels and axis labels.
+const LEFT: f64 = 70.0;
+const RIGHT: f64 = 20.0;
+const TOP: f64 = 40.0;
+const BOTTOM: f64 = 55.0;
+
+// Line colours, cycled through by series.
+const COLORS: [&str; 8] = [
+    "#1f77b4", "#d62728", "#2ca02c", "#ff7f0e", "#9467bd", "#8c564b", "#e377c2", "#17becf",
+];
+
+// A line chart of one or more named series, drawn as SVG so the book's
+// figures can be reproduced without plotting elsewhere.
+pub struct Chart {
+    title: String,
+    x_label: String,
+    y_label: String,
+    series: Vec<(String, Vec<(f64, f64)>)>,
+    y_range: Option<(f64, f64)>,
+}
+
+impl Chart {
+    pub fn new(title: &str, x_label: &str, y_label: &str) -> Self {
+        Chart {
+            title: title.to_string(),
+            x_label: x_label.to_string(),
+            y_label: y_label.to_string(),
+            series: Vec::new(),
+            y_range: None,
+        }
+    }
+
+    // Fixes the y axis to run from `low` to `high`, as for rates, rather than
+    // fitting it to the data.
+    pub fn with_y_range(mut self, low: f64, high: f64) -> Self {
+        self.y_range = Some((low, high));
+        self
+    }
+
+    // Adds a line through `points`, in order. Points that aren't finite are
+    // left out.
+    pub fn with_series(mut self, name: &str, points: impl IntoIterator<Item = (f64, f64)>) -> Self {
+        let points = points
+            .into_iter()
+            .filter(|(x, y)| x.is_finite() && y.is_finite())
+            .collect();
+        self.series.push((name.to_string(), points));
+        self
+    }
+
+    // The smallest and largest x and y over every series, widened where they
+    // would otherwise be a single value.
+    fn bounds(&self) -> ((f64, f64), (f64, f64)) {
+        let points = self.series.iter().flat_map(|(_, points)| points);
+        let (mut x, mut y) = (
+            (f64::INFINITY, f64::NEG_INFINITY),
+            (f64::INFINITY, f64::NEG_INFINITY),
+        );
+        for &(px, py) in points {
+            x = (x.0.min(px), x.1.max(px));
+            y = (y.0.min(py), y.1.max(py));
+        }
+        let widen = |(low, high): (f64, f64)| {
+            if low > high {
+                (0.0, 1.0)
+            } else if low == high {
+                (low - 0.5, high + 0.5)
+            } else {
+                (low, high)
+            }
+        };
+        (widen(x), self.y_range.unwrap_or(widen(y)))
+    }
+
+    // Draws the chart with its top edge at `top`.
+    fn draw(&self, svg: &mut String, top: f64) {
+        let ((x_min, x_max), y_range) = self.bounds();
+        let y_ticks = ticks(y_range.0, y_range.1);
+        // The y axis spans whole ticks, so the curves don't touch the frame.
+        let (y_min, y_max) = (
+            y_ticks.first().copied().unwrap_or(y_range.0).min(y_range.0),
+            y_ticks.last().copied().unwrap_or(y_range.1).max(y_range.1),
+        );
+        let (plot_width, plot_height) = (WIDTH - LEFT - RIGHT, HEIGHT - TOP - BOTTOM);
+        let sx = |x: f64| LEFT + (x - x_min) / (x_max - x_min) * plot_width;
+        let sy = |y: f64| top + TOP + (y_max - y) / (y_max - y_min) * plot_height;
+
+        let _ = writeln!(
+            svg,
+            r#"<text x="{:.1}" y="{:.1}" text-anchor="middle" font-size="16">{}</text>"#,
+            WIDTH / 2.0,
+            top + 24.0,
+            escape(&self.title)
+        );
+        let _ = writeln!(
+            svg,
+            r##"<rect x="{:.1}" y="{:.1}" width="{:.1}" height="{:.1}" fill="none" stroke="#444"/>"##,
+            LEFT,
+            top + TOP,
+            plot_width,
+            plot_height
+        );
+        // The x axis spans the data exactly; ticks beyond it are dropped.
+        let x_ticks = ticks(x_min, x_max)
+            .into_iter()
+            .filter(|&tick| tick >= x_min - 1e-9 && tick <= x_max + 1e-9);
+        for tick in x_ticks {
+            let x = sx(tick);
+            let _ = writeln!(
+                svg,
+                r##"<line x1="{x:.1}" y1="{:.1}" x2="{x:.1}" y2="{:.1}" stroke="#444"/><text x="{x:.1}" y="{:.1}" text-anchor="middle">{}</text>"##,
+                top + HEIGHT - BOTTOM,
+                top + HEIGHT - BOTTOM + 5.0,
+                top + HEIGHT - BOTTOM + 19.0,
+                format_tick(tick)
+            );
+        }
+        for &tick in &y_ticks {
+            let y = sy(tick);
+            let _ = writeln!(
+                svg,
+                r##"<line x1="{:.1}" y1="{y:.1}" x2="{:.1}" y2="{y:.1}" stroke="#ddd"/><text x="{:.1}" y="{:.1}" text-anchor="end">{}</text>"##,
+                LEFT,
+                WIDTH - RIGHT,
+                LEFT - 6.0,
+                y + 4.0,
+                format_tick(tick)
+            );
+        }
+        let _ = writeln!(
+            svg,
+            r#"<text x="{:.1}" y="{:.1}" text-anchor="middle">{}</text>"#,
+            LEFT + plot_width / 2.0,
+            top + HEIGHT - 12.0,
+            escape(&self.x_label)
+        );
+        let (label_x, label_y) = (18.0, top + TOP + plot_height / 2.0);
+        let _ = writeln!(
+            svg,
+            r#"<text x="{label_x:.1}" y="{label_y:.1}" text-anchor="middle" transform="rotate(-90 {label_x:.1} {label_y:.1})">{}</text>"#,
+            escape(&self.y_label)
+        );
+
+        for (i, (_, points)) in self.series.iter().enumerate() {
+            let path: Vec<String> = points
+                .iter()
+                .map(|&(x, y)| format!("{:.1},{:.1}", sx(x), sy(y)))
+                .collect();
+            let _ = writeln!(
+                svg,
+                r#"<polyline points="{}" fill="none" stroke="{}" stroke-width="1.5"/>"#,
+                path.join(" "),
+                COLORS[i % COLORS.len()]
+            );
+        }
+
+        // The legend, in the top right corner of the plot area, on a pale
+        // backing so it stays legible over the lines.
+        let legend_x = WIDTH - RIGHT - 160.0;
+        let _ = writeln!(
+            svg,
+            r#"<rect x="{:.1}" y="{:.1}" width="152" height="{:.1}" fill="white" fill-opacity="0.85"/>"#,
+            legend_x - 6.0,
+            top + TOP + 4.0,
+            16.0 * self.series.len() as f64 + 4.0
+        );
+        for (i, (name, _)) in self.series.iter().enumerate() {
+            let legend_y = top + TOP + 18.0 + 16.0 * i as f64;
+            let _ = writeln!(
+                svg,
+                r#"<line x1="{:.1}" y1="{:.1}" x2="{:.1}" y2="{:.1}" stroke="{}" stroke-width="2"/><text x="{:.1}" y="{:.1}">{}</text>"#,
+                legend_x,
+                legend_y - 4.0,
+                legend_x + 20.0,
+                legend_y - 4.0,
+                COLORS[i % COLORS.len()],
+                legend_x + 26.0,
+                legend_y,
+                escape(name)
+            );
+        }
+    }
+}
+
+// Round tick values covering `low` to `high`: steps of 1, 2 or 5 times a
+// power of ten, about five of them.
+fn ticks(low: f64, high: f64) -> Vec<f64> {
+    let rough = (high - low) / 5.0;
+    let power = 10f64.powf(rough.log10().floor());
+    let step = [1.0, 2.0, 5.0, 10.0]
+        .into_iter()
+        .map(|m| m * power)
+        .find(|&step| step >= rough)
+        .unwrap_or(10.0 * power);
+    let first = (low / step).floor() as i64;
+    let last = (high / step).ceil() as i64;
+    (first..=last).map(|i| i as f64 * step).collect()
+}
+
+fn format_tick(tick: f64) -> String {
+    let text = format!("{:.4}", tick);
+    let text = text.trim_end_matches('0').trim_end_matches('.');
+    if text == "-0" {
+        "0".to_string()
+    } else {
+        text.to_string()
+    }
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+// `charts` as one SVG image, stacked top to bottom.
+fn render_svg(charts: &[Chart]) -> String {
+    let height = HEIGHT * charts.len() as f64;
+    let mut svg = format!(
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="{WIDTH}" height="{height}" viewBox="0 0 {WIDTH} {height}" font-family="sans-serif" font-size="12">"#
+    );
+    svg += "\n<rect width=\"100%\" height=\"100%\" fill=\"white\"/>\n";
+    for (i, chart) in charts.iter().enumerate() {
+        chart.draw(&mut svg, HEIGHT * i as f64);
+    }
+    svg + "</svg>\n"
+}
+
+// Writes `charts` to `path` as SVG, the only format drawn. SVG is text the
+// charts write directly; PNG would need a rasterizer and an encoder, such as
+// plotters' bitmap backend, which can't be fetched offline. A browser or
+// `rsvg-convert` turns the SVG into a PNG when one is needed.
+pub fn write_svg(path: &Path, charts: &[Chart]) -> io::Result<()> {
+    fs::write(path, render_svg(charts))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ticks_are_round_and_cover_the_range() {
+        let labels =
+            |low, high| -> Vec<String> { ticks(low, high).into_iter().map(format_tick).collect() };
+        assert_eq!(labels(0.0, 1.0), ["0", "0.2", "0.4", "0.6", "0.8", "1"]);
+        assert_eq!(
+            labels(3.0, 997.0),
+            ["0", "200", "400", "600", "800", "1000"]
+        );
+        assert_eq!(labels(-0.3, 0.05), ["-0.3", "-0.2", "-0.1", "0", "0.1"]);
+        assert_eq!(format_tick(0.30000000000000004), "0.3");
+        assert_eq!(format_tick(-0.0), "0");
+    }
+
+    #[test]
+    fn charts_draw_a_line_and_legend_entry_per_series() {
+        let chart = Chart::new("Rewards & <steps>", "step", "reward")
+            .with_series("greedy", [(1.0, 0.5), (2.0, f64::NAN), (3.0, 1.0)])
+            .with_series("epsilon:0.1", [(1.0, 0.2), (3.0, 1.4)]);
+        let svg = render_svg(&[chart]);
+        assert!(svg.starts_with("<svg") && svg.ends_with("</svg>\n"));
+        assert_eq!(svg.matches("<polyline").count(), 2);
+        assert!(svg.contains(">greedy</text>") && svg.contains(">epsilon:0.1</text>"));
+        assert!(svg.contains("Rewards &amp; &lt;steps&gt;"));
+        // The NaN point is dropped, leaving two.
+        let first = svg.split("points=\"").nth(1).unwrap();
+        assert_eq!(first.split('"').next().unwrap().split(' ').count(), 2);
+    }
+}
@@ -0,0 +1,377 @@
+use std::io::{self, Write};
+
+use rayon::prelude::*;
+
+use crate::derive_seed;
+
+// Grid search over hyperparameters: every combination of the parameters'
+// values is run once per seed, the trials spread over rayon's threads, and
+// the combinations ranked by their mean score.
+
+// Whether a higher score is better, as for a win rate, or a lower one, as for
+// an RMS error.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Goal {
+    Maximize,
+    Minimize,
+}
+
+// One value for each parameter of a sweep, in the order they were added.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Configuration {
+    pub values: Vec<(String, f64)>,
+}
+
+impl Configuration {
+    // The value of parameter `name`, which the sweep must have.
+    pub fn get(&self, name: &str) -> f64 {
+        self.values
+            .iter()
+            .find(|(parameter, _)| parameter == name)
+            .map(|&(_, value)| value)
+            .unwrap_or_else(|| panic!("the sweep has no parameter {}", name))
+    }
+}
+
+// A configuration's scores, one per seed, and their mean and standard error.
+#[derive(Clone, Debug)]
+pub struct Ranked {
+    pub configuration: Configuration,
+    pub scores: Vec<f64>,
+    pub mean: f64,
+    pub std_error: f64,
+}
+
+pub struct Sweep {
+    parameters: Vec<(String, Vec<f64>)>,
+    goal: Goal,
+    seeds: usize,
+    seed: Option<u64>,
+    threads: Option<usize>,
+}
+
+impl Sweep {
+    pub fn new(goal: Goal) -> Self {
+        Sweep {
+            parameters: Vec::new(),
+            goal,
+            seeds: 1,
+            seed: None,
+            threads: None,
+        }
+    }
+
+    pub fn with_parameter(mut self, name: &str, values: Vec<f64>) -> Self {
+        self.parameters.push((name.to_string(), values));
+        self
+    }
+
+    // Runs every configuration `seeds` times. The kth trial of each gets the
+    // same seed, derived from `seed`, so configurations are compared on the
+    // same random draws.
+    pub fn with_seeds(mut self, seeds: usize, seed: Option<u64>) -> Self {
+        self.seeds = seeds;
+        self.seed = seed;
+        self
+    }
+
+    // Runs the trials on `threads` threads rather than one per core.
+    pub fn with_threads(mut self, threads: Option<usize>) -> Self {
+        self.threads = threads;
+        self
+    }
+
+    // Every combination of the parameters' values, the last parameter
+    // varying fastest.
+    pub fn configurations(&self) -> Vec<Configuration> {
+        self.parameters
+            .iter()
+            .fold(vec![Vec::new()], |combinations, (name, values)| {
+                combinations
+                    .iter()
+                    .flat_map(|combination: &Vec<(String, f64)>| {
+                        values.iter().map(move |&value| {
+                            let mut combination = combination.clone();
+                            combination.push((name.clone(), value));
+                            combination
+                        })
+                    })
+                    .collect()
+            })
+            .into_iter()
+            .map(|values| Configuration { values })
+            .collect()
+    }
+
+    // Scores every configuration with `trial`, given the configuration and
+    // the trial's seed, and returns them best first. Scores that aren't
+    // numbers rank last.
+    pub fn run<F>(&self, trial: F) -> Vec<Ranked>
+    where
+        F: Fn(&Configuration, Option<u64>) -> f64 + Sync,
+    {
+        let configurations = self.configurations();
+        let trials: Vec<(usize, u64)> = (0..configurations.len())
+            .flat_map(|i| (0..self.seeds as u64).map(move |k| (i, k)))
+            .collect();
+        let score = |&(i, k): &(usize, u64)| trial(&configurations[i], derive_seed(self.seed, k));
+        let scores: Vec<f64> = match self.threads {
+            Some(threads) => rayon::ThreadPoolBuilder::new()
+                .num_threads(threads)
+                .build()
+                .expect("could not start the sweep's threads")
+                .install(|| trials.par_iter().map(score).collect()),
+            None => trials.par_iter().map(score).collect(),
+        };
+
+        let mut ranked: Vec<Ranked> = configurations
+            .into_iter()
+            .zip(scores.chunks(self.seeds.max(1)))
+            .map(|(configuration, scores)| {
+                let (mean, std_error) = mean_and_std_error(scores);
+                Ranked {
+                    configuration,
+                    scores: scores.to_vec(),
+                    mean,
+                    std_error,
+                }
+            })
+            .collect();
+        ranked.sort_by(|a, b| {
+            a.mean.is_nan().cmp(&b.mean.is_nan()).then(match self.goal {
+                Goal::Maximize => b.mean.total_cmp(&a.mean),
+                Goal::Minimize => a.mean.total_cmp(&b.mean),
+            })
+        });
+        ranked
+    }
+}
+
+// The mean of `scores` and its standard error, 0 for fewer than two scores.
+pub fn mean_and_std_error(scores: &[f64]) -> (f64, f64) {
+    let n = scores.len() as f64;
+    let mean = scores.iter().sum::<f64>() / n;
+    if scores.len() < 2 {
+        return (mean, 0.0);
+    }
+    let variance = scores.iter().map(|s| (s - mean).powi(2)).sum::<f64>() / (n - 1.0);
+    (mean, (variance / n).sqrt())
+}
+
+// The paired t-test of whether `a` and `b`, scores of two configurations
+// trained with the same seeds in the same order, have the same mean: the t
+// statistic of their differences and its two-sided p-value. Fewer than two
+// pairs give no evidence either way, a p-value of 1, and differences that
+// are all the same nonzero value give a p-value of 0.
+pub fn paired_t_test(a: &[f64], b: &[f64]) -> (f64, f64) {
+    assert_eq!(a.len(), b.len(), "paired scores come in pairs");
+    let differences: Vec<f64> = a.iter().zip(b).map(|(a, b)| a - b).collect();
+    let (mean, std_error) = mean_and_std_error(&differences);
+    if differences.len() < 2 || mean == 0.0 {
+        return (0.0, 1.0);
+    }
+    if std_error == 0.0 {
+        return (mean.signum() * f64::INFINITY, 0.0);
+    }
+    let t = mean / std_error;
+    let dof = (differences.len() - 1) as f64;
+    (t, incomplete_beta(dof / (dof + t * t), dof / 2.0, 0.5))
+}
+
+// ln Γ(x) for x > 0, by Lanczos' approximation.
+fn ln_gamma(x: f64) -> f64 {
+    const COEFFICIENTS: [f64; 6] = [
+        76.18009172947146,
+        -86.50532032941677,
+        24.01409824083091,
+        -1.231739572450155,
+        0.1208650973866179e-2,
+        -0.5395239384953e-5,
+    ];
+    let tmp = x + 5.5 - (x + 0.5) * (x + 5.5).ln();
+    let series = COEFFICIENTS
+        .iter()
+        .enumerate()
+        .fold(1.000000000190015, |sum, (i, c)| {
+            sum + c / (x + 1.0 + i as f64)
+        });
+    -tmp + (2.5066282746310005 * series / x).ln()
+}
+
+// The regularized incomplete beta function I_x(a, b), from its continued
+// fraction, which converges quickly on the side of x it is used on.
+fn incomplete_beta(x: f64, a: f64, b: f64) -> f64 {
+    if x <= 0.0 {
+        return 0.0;
+    }
+    if x >= 1.0 {
+        return 1.0;
+    }
+    if x > (a + 1.0) / (a + b + 2.0) {
+        return 1.0 - incomplete_beta(1.0 - x, b, a);
+    }
+    let front =
+        (ln_gamma(a + b) - ln_gamma(a) - ln_gamma(b) + a * x.ln() + b * (1.0 - x).ln()).exp() / a;
+    // Lentz's method.
+    let tiny = 1e-300;
+    let (mut c, mut d) = (1.0, 1.0 - (a + b) * x / (a + 1.0));
+    d = 1.0 / if d.abs() < tiny { tiny } else { d };
+    let mut fraction = d;
+    for m in 1..200 {
+        let m = m as f64;
+        for numerator in [
+            m * (b - m) * x / ((a + 2.0 * m - 1.0) * (a + 2.0 * m)),
+            -(a + m) * (a + b + m) * x / ((a + 2.0 * m) * (a + 2.0 * m + 1.0)),
+        ] {
+            d = 1.0 + numerator * d;
+            d = 1.0 / if d.abs() < tiny { tiny } else { d };
+            c = 1.0 + numerator / c;
+            if c.abs() < tiny {
+                c = tiny;
+            }
+            fraction *= c * d;
+        }
+        if (c * d - 1.0).abs() < 1e-12 {
+            break;
+        }
+    }
+    front * fraction
+}
+
+// Writes `ranked` as CSV, best first: the rank, each parameter's value, the
+// mean `score`, its standard error and the number of seeds.
+pub fn write_sweep_table(ranked: &[Ranked], score: &str, writer: &mut dyn Write) -> io::Result<()> {
+    let Some(first) = ranked.first() else {
+        return Ok(());
+    };
+    let names: Vec<&str> = first
+        .configuration
+        .values
+        .iter()
+        .map(|(name, _)| name.as_str())
+        .collect();
+    writeln!(writer, "rank,{},{},std_error,seeds", names.join(","), score)?;
+    for (rank, entry) in ranked.iter().enumerate() {
+        let values: Vec<String> = entry
+            .configuration
+            .values
+            .iter()
+            .map(|(_, value)| value.to_string())
+            .collect();
+        writeln!(
+            writer,
+            "{},{},{:.6},{:.6},{}",
+            rank + 1,
+            values.join(","),
+            entry.mean,
+            entry.std_error,
+            entry.scores.len()
+        )?;
+    }
+    Ok(())
+}
+
+// A parameter's values for --flag: comma-separated numbers or ranges, a range
+// being <start>:<end>:<step> with both ends included, as in 0.1:0.5:0.1.
+pub fn parse_values(flag: &str, text: &str) -> Result<Vec<f64>, String> {
+    let number = |item: &str| {
+        item.trim()
+            .parse::<f64>()
+            .ok()
+            .filter(|value| value.is_finite())
+            .ok_or(format!("invalid value '{}' for {}", item, flag))
+    };
+    let mut values = Vec::new();
+    for item in text.split(',') {
+        match item.split(':').collect::<Vec<_>>()[..] {
+            [value] => values.push(number(value)?),
+            [start, end, step] => {
+                let (start, end, step) = (number(start)?, number(end)?, number(step)?);
+                if step <= 0.0 || end < start {
+                    return Err(format!(
+                        "{}'s range {} needs a positive step and an end no smaller than its start",
+                        flag, item
+                    ));
+                }
+                let count = ((end - start) / step + 1e-9).floor() as usize;
+                // Rounded so that steps like 0.1 don't print as 0.30000000000000004.
+                values.extend((0..=count).map(|i| ((start + i as f64 * step) * 1e9).round() / 1e9));
+            }
+            _ => return Err(format!("invalid range '{}' for {}", item, flag)),
+        }
+    }
+    Ok(values)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn values_take_lists_and_ranges() {
+        assert_eq!(
+            parse_values("--alphas", "0.05,0.1:0.3:0.1").unwrap(),
+            vec![0.05, 0.1, 0.2, 0.3]
+        );
+        assert!(parse_values("--alphas", "0.3:0.1:0.1").is_err());
+        assert!(parse_values("--alphas", "0.1:0.2").is_err());
+        assert!(parse_values("--alphas", "fast").is_err());
+    }
+
+    #[test]
+    fn configurations_are_ranked_by_their_mean_score() {
+        let sweep = Sweep::new(Goal::Minimize)
+            .with_parameter("alpha", vec![0.1, 0.2, 0.3])
+            .with_parameter("n", vec![1.0, 2.0])
+            .with_seeds(4, Some(1))
+            .with_threads(Some(2));
+        assert_eq!(sweep.configurations().len(), 6);
+        // Closest to alpha 0.2, n 2 is best; the seed only adds a little.
+        let ranked = sweep.run(|configuration, seed| {
+            (configuration.get("alpha") - 0.2).abs()
+                + (configuration.get("n") - 2.0).abs()
+                + seed.unwrap() as f64 * 1e-3
+        });
+        let best = &ranked[0];
+        assert_eq!(
+            best.configuration.values,
+            [("alpha".to_string(), 0.2), ("n".to_string(), 2.0)]
+        );
+        assert_eq!(best.scores, [0.001, 0.002, 0.003, 0.004]);
+        assert!(ranked.windows(2).all(|pair| pair[0].mean <= pair[1].mean));
+
+        let mut table = Vec::new();
+        write_sweep_table(&ranked, "rms_error", &mut table).unwrap();
+        let table = String::from_utf8(table).unwrap();
+        let mut lines = table.lines();
+        assert_eq!(lines.next(), Some("rank,alpha,n,rms_error,std_error,seeds"));
+        assert_eq!(lines.next(), Some("1,0.2,2,0.002500,0.000645,4"));
+    }
+
+    #[test]
+    fn paired_t_tests_match_student_t_tables() {
+        // Differences of 1, 2, 3, 4 and 5: t = 3 / sqrt(0.5) on 4 degrees of
+        // freedom, p = 0.0132.
+        let a = [2.0, 4.0, 6.0, 8.0, 10.0];
+        let b = [1.0, 2.0, 3.0, 4.0, 5.0];
+        let (t, p) = paired_t_test(&a, &b);
+        assert!((t - 4.242641).abs() < 1e-6, "{}", t);
+        assert!((p - 0.013226).abs() < 1e-5, "{}", p);
+        let (t, p) = paired_t_test(&b, &a);
+        assert!((t + 4.242641).abs() < 1e-6 && (p - 0.013226).abs() < 1e-5);
+        // t = 2 on 1 degree of freedom.
+        let (t, p) = paired_t_test(&[1.0, 3.0], &[0.0, 0.0]);
+        assert!(
+            (t - 2.0).abs() < 1e-9 && (p - 0.295167).abs() < 1e-5,
+            "{} {}",
+            t,
+            p
+        );
+        assert_eq!(paired_t_test(&[1.0, 2.0], &[1.0, 2.0]), (0.0, 1.0));
+        assert_eq!(
+            paired_t_test(&[2.0, 3.0], &[1.0, 2.0]),
+            (f64::INFINITY, 0.0)
+        );
+        assert_eq!(paired_t_test(&[2.0], &[1.0]), (0.0, 1.0));
+    }
+}
@@ -0,0 +1,339 @@
+// Tabular one-step TD control (Chapter 6) for environments whose states and
+// actions are indices.
+
+use std::fmt;
+use std::str::FromStr;
+
+use rand::rngs::SmallRng;
+use rand::Rng;
+
+use crate::{seeded_rng, Environment, Policy, Step};
+
+// Which target `TdAgent` moves Q(S, A) toward.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TdControl {
+    // R + gamma Q(S', A'), for the A' the agent goes on to take (6.7).
+    Sarsa,
+    // R + gamma max over a of Q(S', a) (6.8).
+    QLearning,
+    // R + gamma times the expectation of Q(S', A') under the epsilon-greedy
+    // policy (6.9).
+    ExpectedSarsa,
+}
+
+impl TdControl {
+    pub fn all() -> [TdControl; 3] {
+        [
+            TdControl::Sarsa,
+            TdControl::QLearning,
+            TdControl::ExpectedSarsa,
+        ]
+    }
+}
+
+impl fmt::Display for TdControl {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            TdControl::Sarsa => "sarsa",
+            TdControl::QLearning => "q-learning",
+            TdControl::ExpectedSarsa => "expected-sarsa",
+        })
+    }
+}
+
+impl FromStr for TdControl {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        TdControl::all()
+            .into_iter()
+            .find(|method| method.to_string() == s)
+            .ok_or_else(|| {
+                format!(
+                    "unknown method '{}' (expected sarsa, q-learning or expected-sarsa)",
+                    s
+                )
+            })
+    }
+}
+
+// The greedy action for `values`, with ties broken at random, or with
+// probability `epsilon` any action uniformly.
+pub fn epsilon_greedy(values: &[f64], epsilon: f64, rng: &mut impl Rng) -> usize {
+    if rng.random::<f64>() < epsilon {
+        return rng.random_range(0..values.len());
+    }
+    let best = values.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+    let tied: Vec<usize> = (0..values.len()).filter(|&a| values[a] == best).collect();
+    tied[rng.random_range(0..tied.len())]
+}
+
+// The expected value of `values` when acting epsilon-greedily on them, with
+// the greedy share split evenly between tied actions.
+pub fn epsilon_greedy_expectation(values: &[f64], epsilon: f64) -> f64 {
+    let best = values.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+    let tied = values.iter().filter(|&&value| value == best).count();
+    let explore = epsilon / values.len() as f64;
+    values
+        .iter()
+        .map(|&value| {
+            let greedy = if value == best {
+                (1.0 - epsilon) / tied as f64
+            } else {
+                0.0
+            };
+            (explore + greedy) * value
+        })
+        .sum()
+}
+
+// An epsilon-greedy agent learning action values Q(s, a), all starting at 0,
+// by one-step TD control with step size `alpha`. It learns as it acts, in
+// `observe`, so `run_episode` trains it.
+pub struct TdAgent {
+    method: TdControl,
+    q: Vec<Vec<f64>>,
+    alpha: f64,
+    epsilon: f64,
+    gamma: f64,
+    rng: SmallRng,
+    // The action Sarsa already chose for the state it just reached.
+    next_action: Option<usize>,
+}
+
+impl TdAgent {
+    pub fn new(
+        method: TdControl,
+        states: usize,
+        actions: usize,
+        alpha: f64,
+        epsilon: f64,
+        seed: Option<u64>,
+    ) -> Self {
+        TdAgent {
+            method,
+            q: vec![vec![0.0; actions]; states],
+            alpha,
+            epsilon,
+            gamma: 1.0,
+            rng: seeded_rng(seed),
+            next_action: None,
+        }
+    }
+
+    // Discount rate; undiscounted by default.
+    pub fn with_gamma(mut self, gamma: f64) -> Self {
+        self.gamma = gamma;
+        self
+    }
+
+    // Gives `state` `actions` actions rather than the number passed to
+    // `new`, for environments whose states offer different numbers of them.
+    pub fn with_actions(mut self, state: usize, actions: usize) -> Self {
+        self.q[state] = vec![0.0; actions];
+        self
+    }
+
+    pub fn q(&self) -> &[Vec<f64>] {
+        &self.q
+    }
+
+    // The first action with the highest value in `state`.
+    pub fn greedy_action(&self, state: usize) -> usize {
+        first_best(&self.q[state])
+    }
+}
+
+fn first_best(values: &[f64]) -> usize {
+    (0..values.len()).fold(0, |best, a| if values[a] > values[best] { a } else { best })
+}
+
+impl<E: Environment<State = usize, Action = usize> + ?Sized> Policy<E> for TdAgent {
+    fn select_action(&mut self, _env: &E, state: &usize) -> usize {
+        self.next_action
+            .take()
+            .unwrap_or_else(|| epsilon_greedy(&self.q[*state], self.epsilon, &mut self.rng))
+    }
+
+    fn observe(&mut self, state: &usize, action: &usize, step: &Step<usize>) {
+        let next = if step.done {
+            0.0
+        } else {
+            match self.method {
+                TdControl::Sarsa => {
+                    let next_action =
+                        epsilon_greedy(&self.q[step.state], self.epsilon, &mut self.rng);
+                    self.next_action = Some(next_action);
+                    self.q[step.state][next_action]
+                }
+                TdControl::QLearning => self.q[step.state]
+                    .iter()
+                    .copied()
+                    .fold(f64::NEG_INFINITY, f64::max),
+                TdControl::ExpectedSarsa => {
+                    epsilon_greedy_expectation(&self.q[step.state], self.epsilon)
+                }
+            }
+        };
+        let target = step.reward + self.gamma * next;
+        let value = &mut self.q[*state][*action];
+        *value += self.alpha * (target - *value);
+    }
+}
+
+// Double Q-learning (Section 6.7): two independent estimates Q1 and Q2. Each
+// step a coin flip picks one to update, toward the other's value of its own
+// greedy action in S', so no estimate both chooses the maximizing action and
+// values it; Q-learning's max over noisy estimates is biased upward. The agent
+// acts epsilon-greedily on Q1 + Q2.
+pub struct DoubleQLearningAgent {
+    q1: Vec<Vec<f64>>,
+    q2: Vec<Vec<f64>>,
+    alpha: f64,
+    epsilon: f64,
+    gamma: f64,
+    rng: SmallRng,
+}
+
+impl DoubleQLearningAgent {
+    pub fn new(states: usize, actions: usize, alpha: f64, epsilon: f64, seed: Option<u64>) -> Self {
+        DoubleQLearningAgent {
+            q1: vec![vec![0.0; actions]; states],
+            q2: vec![vec![0.0; actions]; states],
+            alpha,
+            epsilon,
+            gamma: 1.0,
+            rng: seeded_rng(seed),
+        }
+    }
+
+    // Discount rate; undiscounted by default.
+    pub fn with_gamma(mut self, gamma: f64) -> Self {
+        self.gamma = gamma;
+        self
+    }
+
+    // As for `TdAgent::with_actions`.
+    pub fn with_actions(mut self, state: usize, actions: usize) -> Self {
+        self.q1[state] = vec![0.0; actions];
+        self.q2[state] = vec![0.0; actions];
+        self
+    }
+
+    // Q1(state, a) + Q2(state, a) for each action, the values it acts on.
+    pub fn values(&self, state: usize) -> Vec<f64> {
+        self.q1[state]
+            .iter()
+            .zip(&self.q2[state])
+            .map(|(a, b)| a + b)
+            .collect()
+    }
+
+    // The first action with the highest combined value in `state`.
+    pub fn greedy_action(&self, state: usize) -> usize {
+        first_best(&self.values(state))
+    }
+}
+
+impl<E: Environment<State = usize, Action = usize> + ?Sized> Policy<E> for DoubleQLearningAgent {
+    fn select_action(&mut self, _env: &E, state: &usize) -> usize {
+        let values = self.values(*state);
+        epsilon_greedy(&values, self.epsilon, &mut self.rng)
+    }
+
+    fn observe(&mut self, state: &usize, action: &usize, step: &Step<usize>) {
+        let (update, other) = if self.rng.random::<bool>() {
+            (&mut self.q1, &self.q2)
+        } else {
+            (&mut self.q2, &self.q1)
+        };
+        let next = if step.done {
+            0.0
+        } else {
+            other[step.state][first_best(&update[step.state])]
+        };
+        let target = step.reward + self.gamma * next;
+        let value = &mut update[*state][*action];
+        *value += self.alpha * (target - *value);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::run_episode;
+
+    // A corridor of five cells entered at 0: action 1 moves right and action 0
+    // stays put, each for reward -1, and reaching cell 4 ends the episode.
+    struct Corridor(usize);
+
+    impl Environment for Corridor {
+        type State = usize;
+        type Action = usize;
+
+        fn reset(&mut self) -> usize {
+            self.0 = 0;
+            0
+        }
+
+        fn step(&mut self, action: &usize) -> Step<usize> {
+            self.0 += action;
+            Step {
+                state: self.0,
+                reward: -1.0,
+                done: self.0 == 4,
+            }
+        }
+
+        fn actions(&self, _state: &usize) -> Vec<usize> {
+            vec![0, 1]
+        }
+    }
+
+    #[test]
+    fn epsilon_greedy_breaks_ties_and_explores() {
+        let mut rng = seeded_rng(Some(1));
+        let picks: Vec<usize> = (0..100)
+            .map(|_| epsilon_greedy(&[1.0, 3.0, 3.0], 0.0, &mut rng))
+            .collect();
+        assert!(picks.contains(&1) && picks.contains(&2) && !picks.contains(&0));
+        assert!((0..100).any(|_| epsilon_greedy(&[1.0, 3.0, 3.0], 1.0, &mut rng) == 0));
+    }
+
+    #[test]
+    fn every_method_learns_to_walk_the_corridor() {
+        for method in TdControl::all() {
+            let mut env = Corridor(0);
+            let mut agent = TdAgent::new(method, 5, 2, 0.5, 0.1, Some(2));
+            for _ in 0..200 {
+                run_episode(&mut env, &mut agent, Some(1000));
+            }
+            assert!(
+                (0..4).all(|state| agent.greedy_action(state) == 1),
+                "{}",
+                method
+            );
+            assert!((agent.q()[3][1] + 1.0).abs() < 1e-6, "{}", method);
+            assert_eq!(method.to_string().parse(), Ok(method));
+        }
+    }
+
+    #[test]
+    fn double_q_learning_walks_the_corridor() {
+        let mut env = Corridor(0);
+        let mut agent = DoubleQLearningAgent::new(5, 2, 0.5, 0.1, Some(2));
+        for _ in 0..400 {
+            run_episode(&mut env, &mut agent, Some(1000));
+        }
+        assert!((0..4).all(|state| agent.greedy_action(state) == 1));
+        // Each estimate of the last step's value converges on -1.
+        assert!((agent.values(3)[1] + 2.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn expectation_weights_the_greedy_actions() {
+        assert_eq!(epsilon_greedy_expectation(&[2.0, 0.0], 0.0), 2.0);
+        assert!((epsilon_greedy_expectation(&[2.0, 0.0], 0.2) - 1.8).abs() < 1e-12);
+        assert!((epsilon_greedy_expectation(&[2.0, 2.0, -4.0], 0.3) - 1.4).abs() < 1e-12);
+    }
+}
@@ -0,0 +1,132 @@
+// Tile coding (Section 9.5.4), after Sutton's tiles3: each of several
+// overlapping tilings, offset asymmetrically from one another, contributes one
+// active tile for a point, and tiles are numbered on first use up to a fixed
+// size, after which new ones are hashed into the existing numbers.
+
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+
+pub struct TileCoder {
+    tilings: usize,
+    size: usize,
+    // Tiles per unit along each dimension; inputs are used as given without.
+    scales: Vec<f64>,
+    indices: HashMap<Vec<i64>, usize>,
+    collisions: usize,
+}
+
+impl TileCoder {
+    // `tilings` tilings, each with tiles one unit wide, sharing `size` tile
+    // numbers. Sutton recommends a power of two for `tilings`, at least four
+    // times the number of dimensions.
+    pub fn new(tilings: usize, size: usize) -> Self {
+        TileCoder {
+            tilings,
+            size,
+            scales: Vec::new(),
+            indices: HashMap::new(),
+            collisions: 0,
+        }
+    }
+
+    // Scales each dimension so that its range, given as (low, high), spans
+    // `tiles` tiles in every tiling.
+    pub fn with_resolution(mut self, ranges: &[(f64, f64)], tiles: usize) -> Self {
+        self.scales = ranges
+            .iter()
+            .map(|(low, high)| tiles as f64 / (high - low))
+            .collect();
+        self
+    }
+
+    pub fn tilings(&self) -> usize {
+        self.tilings
+    }
+
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    // Tiles numbered after the table filled up, which may share a number
+    // with other tiles.
+    pub fn collisions(&self) -> usize {
+        self.collisions
+    }
+
+    // The active tile in each tiling for the point `floats`, with `ints`
+    // distinguishing otherwise identical points, such as different actions.
+    pub fn tiles(&mut self, floats: &[f64], ints: &[i64]) -> Vec<usize> {
+        let n = self.tilings as i64;
+        let quantized: Vec<i64> = floats
+            .iter()
+            .enumerate()
+            .map(|(i, &x)| {
+                (x * self.scales.get(i).copied().unwrap_or(1.0) * n as f64).floor() as i64
+            })
+            .collect();
+        (0..n)
+            .map(|tiling| {
+                let mut coords = Vec::with_capacity(1 + quantized.len() + ints.len());
+                coords.push(tiling);
+                // Offsetting dimension i by 2i + 1 tilings' worth keeps the
+                // tilings from lining up along the diagonals.
+                let mut offset = tiling;
+                for &q in &quantized {
+                    coords.push((q + offset).div_euclid(n));
+                    offset += tiling * 2;
+                }
+                coords.extend_from_slice(ints);
+                self.index(coords)
+            })
+            .collect()
+    }
+
+    fn index(&mut self, coords: Vec<i64>) -> usize {
+        if let Some(&index) = self.indices.get(&coords) {
+            return index;
+        }
+        if self.indices.len() >= self.size {
+            self.collisions += 1;
+            let mut hasher = DefaultHasher::new();
+            coords.hash(&mut hasher);
+            return hasher.finish() as usize % self.size;
+        }
+        let index = self.indices.len();
+        self.indices.insert(coords, index);
+        index
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn nearby_points_share_most_tiles() {
+        let mut coder = TileCoder::new(8, 4096).with_resolution(&[(0.0, 1.0), (0.0, 1.0)], 8);
+        let a = coder.tiles(&[0.50, 0.50], &[]);
+        let b = coder.tiles(&[0.53, 0.50], &[]);
+        let far = coder.tiles(&[0.9, 0.1], &[]);
+        assert_eq!(a.len(), 8);
+        assert_eq!(a, coder.tiles(&[0.50, 0.50], &[]));
+        let shared = a.iter().filter(|t| b.contains(t)).count();
+        assert!((4..8).contains(&shared), "{}", shared);
+        assert!(a.iter().all(|t| !far.contains(t)));
+        assert!(coder
+            .tiles(&[0.5, 0.5], &[1])
+            .iter()
+            .all(|t| !a.contains(t)));
+    }
+
+    #[test]
+    fn a_full_table_hashes_into_its_size() {
+        let mut coder = TileCoder::new(4, 16);
+        for x in 0..20 {
+            for tile in coder.tiles(&[x as f64], &[]) {
+                assert!(tile < 16);
+            }
+        }
+        assert!(coder.collisions() > 0);
+    }
+}
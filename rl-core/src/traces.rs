@@ -0,0 +1,153 @@
+// Eligibility traces (Chapter 12) for linear methods over binary features,
+// such as one-hot tabular states or tile coding: z holds a trace per weight,
+// decayed by gamma lambda each step, and the weights move by the TD error
+// times z, so one error updates every recently active feature.
+
+use std::fmt;
+use std::str::FromStr;
+
+// Traces smaller than this are dropped, so only the recently active features
+// are decayed and updated each step; their updates would be lost in rounding.
+const MIN_TRACE: f64 = 1e-12;
+
+// How a step's active features mark their traces.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TraceKind {
+    // Add 1, as the gradient of a linear value does (12.5).
+    Accumulating,
+    // Set to 1, so features revisited within an episode don't build up
+    // traces beyond 1 (12.12).
+    Replacing,
+}
+
+impl TraceKind {
+    pub fn all() -> [TraceKind; 2] {
+        [TraceKind::Accumulating, TraceKind::Replacing]
+    }
+}
+
+impl fmt::Display for TraceKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            TraceKind::Accumulating => "accumulating",
+            TraceKind::Replacing => "replacing",
+        })
+    }
+}
+
+impl FromStr for TraceKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        TraceKind::all()
+            .into_iter()
+            .find(|kind| kind.to_string() == s)
+            .ok_or_else(|| {
+                format!(
+                    "unknown traces '{}' (expected accumulating or replacing)",
+                    s
+                )
+            })
+    }
+}
+
+pub struct EligibilityTraces {
+    kind: TraceKind,
+    traces: Vec<f64>,
+    // The indices of the nonzero traces.
+    nonzero: Vec<usize>,
+}
+
+impl EligibilityTraces {
+    // All-zero traces for `size` weights.
+    pub fn new(kind: TraceKind, size: usize) -> Self {
+        EligibilityTraces {
+            kind,
+            traces: vec![0.0; size],
+            nonzero: Vec::new(),
+        }
+    }
+
+    pub fn kind(&self) -> TraceKind {
+        self.kind
+    }
+
+    pub fn get(&self, index: usize) -> f64 {
+        self.traces[index]
+    }
+
+    // Decays every trace by `decay`, gamma times lambda, then marks the
+    // `active` features.
+    pub fn visit(&mut self, active: &[usize], decay: f64) {
+        let traces = &mut self.traces;
+        self.nonzero.retain(|&i| {
+            traces[i] *= decay;
+            if traces[i].abs() < MIN_TRACE {
+                traces[i] = 0.0;
+            }
+            traces[i] != 0.0
+        });
+        for &i in active {
+            if traces[i] == 0.0 {
+                self.nonzero.push(i);
+            }
+            match self.kind {
+                TraceKind::Accumulating => traces[i] += 1.0,
+                TraceKind::Replacing => traces[i] = 1.0,
+            }
+        }
+    }
+
+    // Moves each weight by `step`, the step size times the TD error, times
+    // its trace.
+    pub fn update(&self, weights: &mut [f64], step: f64) {
+        for &i in &self.nonzero {
+            weights[i] += step * self.traces[i];
+        }
+    }
+
+    // Zeroes every trace, as at the start of an episode.
+    pub fn clear(&mut self) {
+        for &i in &self.nonzero {
+            self.traces[i] = 0.0;
+        }
+        self.nonzero.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn revisits_accumulate_or_replace() {
+        let mut accumulating = EligibilityTraces::new(TraceKind::Accumulating, 3);
+        let mut replacing = EligibilityTraces::new(TraceKind::Replacing, 3);
+        for traces in [&mut accumulating, &mut replacing] {
+            traces.visit(&[0], 0.5);
+            traces.visit(&[1], 0.5);
+            traces.visit(&[0], 0.5);
+        }
+        assert_eq!(accumulating.get(0), 1.25);
+        assert_eq!(replacing.get(0), 1.0);
+        assert_eq!((accumulating.get(1), replacing.get(1)), (0.5, 0.5));
+
+        let mut weights = vec![0.0; 3];
+        accumulating.update(&mut weights, 2.0);
+        assert_eq!(weights, [2.5, 1.0, 0.0]);
+        accumulating.clear();
+        accumulating.visit(&[2], 0.5);
+        assert_eq!((accumulating.get(0), accumulating.get(2)), (0.0, 1.0));
+        assert_eq!("replacing".parse(), Ok(TraceKind::Replacing));
+    }
+
+    #[test]
+    fn tiny_traces_are_dropped() {
+        let mut traces = EligibilityTraces::new(TraceKind::Accumulating, 2);
+        traces.visit(&[0], 0.0);
+        traces.visit(&[1], 1e-13);
+        let mut weights = vec![0.0; 2];
+        traces.update(&mut weights, 1.0);
+        assert_eq!(weights, [0.0, 1.0]);
+    }
+}
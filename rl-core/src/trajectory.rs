@@ -0,0 +1,272 @@
+// Episodes saved to disk as they are played, to learn from offline or to
+// analyse afterwards, such as counting how often each state was visited.
+// States and actions are written in their `Packed` form, a byte or two for
+// the small ones in the book, and rewards as 8-byte floats. After a header,
+// each episode is
+//
+//     steps (varint), terminated (1 byte), states[0..=steps],
+//     actions[0..steps], rewards[0..steps]
+
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::Path;
+
+use crate::Episode;
+
+const MAGIC: &[u8; 8] = b"RLTRAJ01";
+
+// A state or action in the compact form trajectory files hold.
+pub trait Packed: Sized {
+    fn pack(&self, out: &mut dyn Write) -> io::Result<()>;
+
+    fn unpack(input: &mut dyn Read) -> io::Result<Self>;
+}
+
+// Seven bits a byte, low bits first, the high bit set on all but the last.
+fn write_varint(out: &mut dyn Write, mut value: u64) -> io::Result<()> {
+    while value >= 0x80 {
+        out.write_all(&[value as u8 | 0x80])?;
+        value >>= 7;
+    }
+    out.write_all(&[value as u8])
+}
+
+fn read_varint(input: &mut dyn Read) -> io::Result<u64> {
+    let mut value = 0;
+    for shift in (0..64).step_by(7) {
+        let mut byte = [0];
+        input.read_exact(&mut byte)?;
+        value |= ((byte[0] & 0x7f) as u64) << shift;
+        if byte[0] < 0x80 {
+            return Ok(value);
+        }
+    }
+    Err(io::Error::new(
+        io::ErrorKind::InvalidData,
+        "a varint runs past 64 bits",
+    ))
+}
+
+impl Packed for usize {
+    fn pack(&self, out: &mut dyn Write) -> io::Result<()> {
+        write_varint(out, *self as u64)
+    }
+
+    fn unpack(input: &mut dyn Read) -> io::Result<Self> {
+        Ok(read_varint(input)? as usize)
+    }
+}
+
+// Zigzag-encoded, so small negative numbers stay small.
+impl Packed for i64 {
+    fn pack(&self, out: &mut dyn Write) -> io::Result<()> {
+        write_varint(out, ((self << 1) ^ (self >> 63)) as u64)
+    }
+
+    fn unpack(input: &mut dyn Read) -> io::Result<Self> {
+        let value = read_varint(input)?;
+        Ok((value >> 1) as i64 ^ -((value & 1) as i64))
+    }
+}
+
+impl Packed for bool {
+    fn pack(&self, out: &mut dyn Write) -> io::Result<()> {
+        out.write_all(&[*self as u8])
+    }
+
+    fn unpack(input: &mut dyn Read) -> io::Result<Self> {
+        let mut byte = [0];
+        input.read_exact(&mut byte)?;
+        Ok(byte[0] != 0)
+    }
+}
+
+// A bandit's single state takes no space at all.
+impl Packed for () {
+    fn pack(&self, _out: &mut dyn Write) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn unpack(_input: &mut dyn Read) -> io::Result<Self> {
+        Ok(())
+    }
+}
+
+impl<A: Packed, B: Packed> Packed for (A, B) {
+    fn pack(&self, out: &mut dyn Write) -> io::Result<()> {
+        self.0.pack(out)?;
+        self.1.pack(out)
+    }
+
+    fn unpack(input: &mut dyn Read) -> io::Result<Self> {
+        Ok((A::unpack(input)?, B::unpack(input)?))
+    }
+}
+
+// Appends episodes to a trajectory file.
+pub struct TrajectoryRecorder<W: Write = BufWriter<File>> {
+    writer: W,
+    episodes: u64,
+}
+
+impl TrajectoryRecorder {
+    pub fn create(path: &Path) -> io::Result<Self> {
+        TrajectoryRecorder::new(BufWriter::new(File::create(path)?))
+    }
+}
+
+impl<W: Write> TrajectoryRecorder<W> {
+    // A recorder writing to `writer`, starting with the header.
+    pub fn new(mut writer: W) -> io::Result<Self> {
+        writer.write_all(MAGIC)?;
+        Ok(TrajectoryRecorder {
+            writer,
+            episodes: 0,
+        })
+    }
+
+    pub fn record<S: Packed, A: Packed>(&mut self, episode: &Episode<S, A>) -> io::Result<()> {
+        let out: &mut dyn Write = &mut self.writer;
+        write_varint(out, episode.len() as u64)?;
+        episode.terminated.pack(out)?;
+        for state in &episode.states {
+            state.pack(out)?;
+        }
+        for action in &episode.actions {
+            action.pack(out)?;
+        }
+        for reward in &episode.rewards {
+            out.write_all(&reward.to_le_bytes())?;
+        }
+        self.episodes += 1;
+        Ok(())
+    }
+
+    // Episodes recorded so far.
+    pub fn episodes(&self) -> u64 {
+        self.episodes
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+// Reads back the episodes of a trajectory file, in the order recorded.
+pub struct TrajectoryReader<R: Read = BufReader<File>> {
+    reader: R,
+}
+
+impl TrajectoryReader {
+    pub fn open(path: &Path) -> io::Result<Self> {
+        TrajectoryReader::new(BufReader::new(File::open(path)?))
+    }
+}
+
+impl<R: Read> TrajectoryReader<R> {
+    // A reader of `reader`, which must start with a trajectory file's header.
+    pub fn new(mut reader: R) -> io::Result<Self> {
+        let mut magic = [0; 8];
+        reader.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "not a trajectory file",
+            ));
+        }
+        Ok(TrajectoryReader { reader })
+    }
+
+    // The next episode, or `None` at the end of the file. The states and
+    // actions must be read as the types they were recorded as.
+    pub fn next_episode<S: Packed, A: Packed>(&mut self) -> io::Result<Option<Episode<S, A>>> {
+        let input: &mut dyn Read = &mut self.reader;
+        let mut first = [0];
+        if input.read(&mut first)? == 0 {
+            return Ok(None);
+        }
+        let steps = if first[0] < 0x80 {
+            first[0] as usize
+        } else {
+            let rest = read_varint(input)?;
+            (first[0] & 0x7f) as usize | (rest as usize) << 7
+        };
+        let terminated = bool::unpack(input)?;
+        let states = (0..=steps)
+            .map(|_| S::unpack(input))
+            .collect::<io::Result<_>>()?;
+        let actions = (0..steps)
+            .map(|_| A::unpack(input))
+            .collect::<io::Result<_>>()?;
+        let rewards = (0..steps)
+            .map(|_| {
+                let mut bytes = [0; 8];
+                input.read_exact(&mut bytes)?;
+                Ok(f64::from_le_bytes(bytes))
+            })
+            .collect::<io::Result<_>>()?;
+        Ok(Some(Episode {
+            states,
+            actions,
+            rewards,
+            terminated,
+        }))
+    }
+}
+
+// Every episode in the trajectory file at `path`.
+pub fn read_trajectories<S: Packed, A: Packed>(path: &Path) -> io::Result<Vec<Episode<S, A>>> {
+    let mut reader = TrajectoryReader::open(path)?;
+    let mut episodes = Vec::new();
+    while let Some(episode) = reader.next_episode()? {
+        episodes.push(episode);
+    }
+    Ok(episodes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn episodes_read_back_as_recorded() {
+        let episodes = [
+            Episode {
+                states: vec![(0usize, -3i64), (200, 5), (1, i64::MIN)],
+                actions: vec![1usize, 300],
+                rewards: vec![-1.0, 0.25],
+                terminated: true,
+            },
+            Episode {
+                states: vec![(7, 0); 201],
+                actions: vec![0; 200],
+                rewards: vec![0.0; 200],
+                terminated: false,
+            },
+        ];
+        let mut recorder = TrajectoryRecorder::new(Vec::new()).unwrap();
+        for episode in &episodes {
+            recorder.record(episode).unwrap();
+        }
+        assert_eq!(recorder.episodes(), 2);
+        let bytes = recorder.writer;
+        // The long episode's states and actions are a byte each.
+        assert_eq!(
+            bytes.len(),
+            8 + (1 + 1 + 16 + 3 + 16) + (2 + 1 + 201 * 2 + 200 + 1600)
+        );
+        let mut reader = TrajectoryReader::new(&bytes[..]).unwrap();
+        for episode in &episodes {
+            let read: Episode<(usize, i64), usize> = reader.next_episode().unwrap().unwrap();
+            assert_eq!(read.states, episode.states);
+            assert_eq!(read.actions, episode.actions);
+            assert_eq!(read.rewards, episode.rewards);
+            assert_eq!(read.terminated, episode.terminated);
+        }
+        assert!(reader
+            .next_episode::<(usize, i64), usize>()
+            .unwrap()
+            .is_none());
+        assert!(TrajectoryReader::new(&b"RLTRAJ02"[..]).is_err());
+    }
+}